@@ -26,7 +26,7 @@ use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
 
 use crate::error::{Error, Result};
-use crate::infra::read_file;
+use crate::infra::{read_file, write_file_atomic};
 
 /// A parsed Git configuration.
 ///
@@ -449,6 +449,8 @@ impl ConfigLevel {
 /// 1. System configuration (`/etc/gitconfig`)
 /// 2. Global configuration (`~/.gitconfig`)
 /// 3. Local repository configuration (`.git/config`)
+/// 4. Per-worktree configuration (`.git/config.worktree`), if
+///    `extensions.worktreeConfig` is set
 ///
 /// # Arguments
 ///
@@ -487,9 +489,78 @@ pub fn load_config<P: AsRef<Path>>(git_dir: P) -> Result<Config> {
         config.merge(&local_config);
     }
 
+    // Per-worktree config, if enabled by the local config
+    if config
+        .get_bool("extensions", "worktreeConfig")
+        .unwrap_or(false)
+    {
+        let worktree_path = git_dir.as_ref().join("config.worktree");
+        if let Ok(worktree_config) = Config::from_file_with_includes(&worktree_path) {
+            config.merge(&worktree_config);
+        }
+    }
+
     Ok(config)
 }
 
+/// Sets `section.key` to `value` in a configuration file.
+///
+/// The file is edited in place, keeping everything else as it is: the
+/// last existing assignment of the key in a `[section]` is replaced, or the
+/// key is added to the first `[section]`, or a new section is appended.
+/// The file is created if it does not exist.
+///
+/// # Arguments
+///
+/// * `path` - The configuration file.
+/// * `section` - The section name (without subsection).
+/// * `key` - The key name.
+/// * `value` - The value to write.
+pub(crate) fn set_in_file(path: &Path, section: &str, key: &str, value: &str) -> Result<()> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let assignment = format!("\t{} = {}", key, value);
+
+    let mut in_section = false;
+    let mut first_header = None;
+    let mut last_key = None;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if let Some(header) = trimmed.strip_prefix('[') {
+            let name = header.split(']').next().unwrap_or("").trim();
+            in_section = name.eq_ignore_ascii_case(section);
+            if in_section && first_header.is_none() {
+                first_header = Some(i);
+            }
+        } else if in_section {
+            let name = trimmed
+                .split(|c: char| c == '=' || c.is_whitespace())
+                .next()
+                .unwrap_or("");
+            if name.eq_ignore_ascii_case(key) {
+                last_key = Some(i);
+            }
+        }
+    }
+
+    match (last_key, first_header) {
+        (Some(i), _) => lines[i] = assignment,
+        (None, Some(i)) => lines.insert(i + 1, assignment),
+        (None, None) => {
+            lines.push(format!("[{}]", section));
+            lines.push(assignment);
+        }
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    write_file_atomic(path, content.as_bytes())
+}
+
 /// Returns the XDG config path for Git (~/.config/git/config).
 fn xdg_config_path() -> Option<PathBuf> {
     // Check XDG_CONFIG_HOME first
@@ -688,4 +759,35 @@ mod tests {
         assert_eq!(config1.get("user", "email"), Some("jane@example.com"));
         assert_eq!(config1.get("core", "bare"), Some("false"));
     }
+
+    #[test]
+    fn test_set_in_file() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("config");
+        std::fs::write(
+            &path,
+            "[core]\n\tbare = false\n[remote \"core\"]\n\turl = x\n",
+        )
+        .unwrap();
+
+        set_in_file(&path, "core", "sparseCheckout", "true").unwrap();
+        set_in_file(&path, "core", "Bare", "true").unwrap();
+        set_in_file(&path, "user", "name", "John").unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content,
+            "[core]\n\tsparseCheckout = true\n\tBare = true\n[remote \"core\"]\n\turl = x\n[user]\n\tname = John\n"
+        );
+        let config = Config::from_file(&path).unwrap();
+        assert!(config.get_bool("core", "sparsecheckout").unwrap());
+        assert_eq!(config.get_subsection("remote", "core", "url"), Some("x"));
+
+        let new_path = temp.path().join("config.worktree");
+        set_in_file(&new_path, "core", "sparseCheckoutCone", "false").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&new_path).unwrap(),
+            "[core]\n\tsparseCheckoutCone = false\n"
+        );
+    }
 }
@@ -9,8 +9,10 @@ use std::path::{Path, PathBuf};
 
 use crate::error::Result;
 use crate::index::Index;
-use crate::infra::{hash_object, list_working_tree, read_file};
+use crate::infra::{hash_object_with, list_working_tree, path, read_file};
 use crate::objects::{Commit, FileMode, Oid, Tree};
+use crate::pathspec::Pathspec;
+use crate::status::entry_stat_clean;
 use crate::Repository;
 
 /// The status of a file in a diff.
@@ -246,6 +248,53 @@ impl<'a> IntoIterator for &'a TreeDiff {
     }
 }
 
+/// Options for computing diffs.
+///
+/// # Examples
+///
+/// ```
+/// use zerogit::{DiffOptions, Pathspec};
+///
+/// let options = DiffOptions::new().pathspec(Pathspec::new(["src/*.rs"]).unwrap());
+/// assert!(!options.get_pathspec().is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Limits the diff to paths matching this pathspec.
+    pathspec: Pathspec,
+}
+
+impl DiffOptions {
+    /// Creates a new DiffOptions with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the diff to paths matching the given pathspec.
+    ///
+    /// Calling this more than once adds to the existing pathspec.
+    ///
+    /// # Arguments
+    ///
+    /// * `pathspec` - The pathspec to match paths against.
+    pub fn pathspec(mut self, pathspec: Pathspec) -> Self {
+        self.pathspec.extend(pathspec);
+        self
+    }
+
+    /// Returns the pathspec used to limit the diff.
+    pub fn get_pathspec(&self) -> &Pathspec {
+        &self.pathspec
+    }
+
+    /// Removes entries that do not match the pathspec.
+    fn filter(&self, map: &mut HashMap<PathBuf, FlatEntry>) {
+        if !self.pathspec.is_empty() {
+            map.retain(|path, _| self.pathspec.matches(path));
+        }
+    }
+}
+
 /// Entry in a flattened tree.
 #[derive(Debug, Clone)]
 struct FlatEntry {
@@ -280,12 +329,35 @@ impl Repository {
     /// }
     /// ```
     pub fn diff_trees(&self, old_tree: Option<&Tree>, new_tree: &Tree) -> Result<TreeDiff> {
+        self.diff_trees_with_options(old_tree, new_tree, &DiffOptions::default())
+    }
+
+    /// Computes the diff between two trees with options.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_tree` - The old tree (None for initial commits).
+    /// * `new_tree` - The new tree.
+    /// * `options` - Options limiting the diff.
+    ///
+    /// # Returns
+    ///
+    /// A `TreeDiff` containing the changes between the two trees for
+    /// paths matching the options' pathspec.
+    pub fn diff_trees_with_options(
+        &self,
+        old_tree: Option<&Tree>,
+        new_tree: &Tree,
+        options: &DiffOptions,
+    ) -> Result<TreeDiff> {
         // Flatten both trees
-        let old_map = match old_tree {
+        let mut old_map = match old_tree {
             Some(tree) => self.flatten_tree(tree, PathBuf::new())?,
             None => HashMap::new(),
         };
-        let new_map = self.flatten_tree(new_tree, PathBuf::new())?;
+        let mut new_map = self.flatten_tree(new_tree, PathBuf::new())?;
+        options.filter(&mut old_map);
+        options.filter(&mut new_map);
 
         // Collect all paths
         let mut all_paths: BTreeSet<PathBuf> = BTreeSet::new();
@@ -351,6 +423,25 @@ impl Repository {
     /// }
     /// ```
     pub fn commit_diff(&self, commit: &Commit) -> Result<TreeDiff> {
+        self.commit_diff_with_options(commit, &DiffOptions::default())
+    }
+
+    /// Computes the diff for a commit against its first parent with options.
+    ///
+    /// # Arguments
+    ///
+    /// * `commit` - The commit to compute the diff for.
+    /// * `options` - Options limiting the diff.
+    ///
+    /// # Returns
+    ///
+    /// A `TreeDiff` containing the changes in this commit for paths
+    /// matching the options' pathspec.
+    pub fn commit_diff_with_options(
+        &self,
+        commit: &Commit,
+        options: &DiffOptions,
+    ) -> Result<TreeDiff> {
         // Get the tree of the current commit
         let new_tree = self.tree(&commit.tree().to_hex())?;
 
@@ -364,7 +455,7 @@ impl Repository {
         };
 
         // Compute the diff between the trees
-        self.diff_trees(old_tree.as_ref(), &new_tree)
+        self.diff_trees_with_options(old_tree.as_ref(), &new_tree, options)
     }
 
     /// Flattens a tree into a map of path -> (oid, mode).
@@ -377,9 +468,9 @@ impl Repository {
 
         for entry in tree.entries() {
             let path = if prefix.as_os_str().is_empty() {
-                PathBuf::from(entry.name())
+                entry.name_path()
             } else {
-                prefix.join(entry.name())
+                prefix.join(entry.name_path())
             };
 
             if entry.is_directory() {
@@ -423,9 +514,25 @@ impl Repository {
     /// }
     /// ```
     pub fn diff_index_to_workdir(&self) -> Result<TreeDiff> {
+        self.diff_index_to_workdir_with_options(&DiffOptions::default())
+    }
+
+    /// Computes the diff between the index and the working tree with options.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Options limiting the diff.
+    ///
+    /// # Returns
+    ///
+    /// A `TreeDiff` containing the unstaged changes for paths matching the
+    /// options' pathspec.
+    pub fn diff_index_to_workdir_with_options(&self, options: &DiffOptions) -> Result<TreeDiff> {
+        self.require_on_disk("diff against the working tree")?;
         let index = self.read_index_internal()?;
-        let index_map = index_to_flat_map(&index);
-        let workdir_map = self.workdir_to_flat_map(&index)?;
+        let mut index_map = index_to_flat_map(&index);
+        options.filter(&mut index_map);
+        let workdir_map = self.workdir_to_flat_map(&index, options)?;
 
         Ok(diff_flat_maps(&index_map, &workdir_map))
     }
@@ -453,9 +560,25 @@ impl Repository {
     /// }
     /// ```
     pub fn diff_head_to_index(&self) -> Result<TreeDiff> {
-        let head_map = self.get_head_flat_map()?;
+        self.diff_head_to_index_with_options(&DiffOptions::default())
+    }
+
+    /// Computes the diff between HEAD and the index with options.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Options limiting the diff.
+    ///
+    /// # Returns
+    ///
+    /// A `TreeDiff` containing the staged changes for paths matching the
+    /// options' pathspec.
+    pub fn diff_head_to_index_with_options(&self, options: &DiffOptions) -> Result<TreeDiff> {
+        let mut head_map = self.get_head_flat_map()?;
+        options.filter(&mut head_map);
         let index = self.read_index_internal()?;
-        let index_map = index_to_flat_map(&index);
+        let mut index_map = index_to_flat_map(&index);
+        options.filter(&mut index_map);
 
         Ok(diff_flat_maps(&head_map, &index_map))
     }
@@ -483,9 +606,25 @@ impl Repository {
     /// }
     /// ```
     pub fn diff_head_to_workdir(&self) -> Result<TreeDiff> {
-        let head_map = self.get_head_flat_map()?;
+        self.diff_head_to_workdir_with_options(&DiffOptions::default())
+    }
+
+    /// Computes the diff between HEAD and the working tree with options.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Options limiting the diff.
+    ///
+    /// # Returns
+    ///
+    /// A `TreeDiff` containing all changes since the last commit for paths
+    /// matching the options' pathspec.
+    pub fn diff_head_to_workdir_with_options(&self, options: &DiffOptions) -> Result<TreeDiff> {
+        self.require_on_disk("diff against the working tree")?;
+        let mut head_map = self.get_head_flat_map()?;
+        options.filter(&mut head_map);
         let index = self.read_index_internal()?;
-        let workdir_map = self.workdir_to_flat_map(&index)?;
+        let workdir_map = self.workdir_to_flat_map(&index, options)?;
 
         Ok(diff_flat_maps(&head_map, &workdir_map))
     }
@@ -500,21 +639,21 @@ impl Repository {
 
     /// Reads the index file.
     fn read_index_internal(&self) -> Result<Index> {
-        let index_path = self.git_dir().join("index");
-        if index_path.exists() {
-            let index_data = read_file(&index_path)?;
-            crate::index::parse(&index_data)
-        } else {
-            Ok(Index::empty(2))
-        }
+        self.read_index()
     }
 
     /// Builds a flat entry map from the working tree.
     ///
     /// This walks the working tree and computes hashes for all files.
     /// For performance, if a file exists in the index with matching
-    /// mtime and size, we skip re-hashing and use the index's OID.
-    fn workdir_to_flat_map(&self, index: &Index) -> Result<HashMap<PathBuf, FlatEntry>> {
+    /// stat data (and is not racily clean), we skip re-hashing and use
+    /// the index's OID. Files outside the options' pathspec are skipped
+    /// without being hashed. Skip-worktree entries keep their index OID.
+    fn workdir_to_flat_map(
+        &self,
+        index: &Index,
+        options: &DiffOptions,
+    ) -> Result<HashMap<PathBuf, FlatEntry>> {
         let mut map = HashMap::new();
         let work_dir = self.path();
 
@@ -522,23 +661,52 @@ impl Repository {
             let full_path = work_dir.join(&file_path);
             // Normalize path for cross-platform consistency
             let normalized_path = normalize_path(&file_path);
+            if !options.pathspec.matches(&normalized_path) {
+                continue;
+            }
 
-            // Read file content and compute hash
-            let content = read_file(&full_path)?;
-            let hash = hash_object("blob", &content);
-            let oid = Oid::from_bytes(hash);
-
-            // Get mode from index if available, otherwise detect
             // Try both normalized path and original path for index lookup
-            let mode = index
+            let index_entry = index
                 .get(&file_path)
-                .or_else(|| index.get(&normalized_path))
+                .or_else(|| index.get(&normalized_path));
+
+            // Use the index's OID if the stat cache says the file is unchanged,
+            // otherwise read file content and compute hash
+            let cached_oid = match index_entry {
+                Some(entry) if entry_stat_clean(work_dir, entry, index.timestamp()) => {
+                    Some(*entry.oid())
+                }
+                _ => None,
+            };
+            let oid = match cached_oid {
+                Some(oid) => oid,
+                None => {
+                    let content = read_file(&full_path)?;
+                    let hash = hash_object_with(self.object_format(), "blob", &content)?;
+                    Oid::from_slice(&hash)?
+                }
+            };
+
+            // Get mode from index if available, otherwise detect
+            let mode = index_entry
                 .map(|e| e.mode())
                 .unwrap_or_else(|| detect_file_mode(&full_path));
 
             map.insert(normalized_path, FlatEntry { oid, mode });
         }
 
+        // Entries outside a sparse checkout are taken as unmodified
+        for entry in index.iter().filter(|e| e.is_skip_worktree()) {
+            let path = normalize_path(entry.path());
+            if options.pathspec.matches(&path) {
+                let flat = FlatEntry {
+                    oid: *entry.oid(),
+                    mode: entry.mode(),
+                };
+                map.insert(path, flat);
+            }
+        }
+
         Ok(map)
     }
 }
@@ -565,9 +733,7 @@ fn index_to_flat_map(index: &Index) -> HashMap<PathBuf, FlatEntry> {
 /// This ensures paths from the index (which use `/`) match paths from
 /// the working tree (which may use `\` on Windows).
 fn normalize_path(path: &Path) -> PathBuf {
-    // Convert to string and replace backslashes with forward slashes
-    let path_str = path.to_string_lossy();
-    PathBuf::from(path_str.replace('\\', "/"))
+    path::from_bytes(&path::to_bytes(path))
 }
 
 /// Detects the file mode of a file in the working tree.
@@ -683,7 +849,9 @@ fn diff_flat_maps(
             (Some(old), Some(new)) => {
                 // Check if modified
                 if old.oid != new.oid || old.mode != new.mode {
-                    deltas.push(DiffDelta::modified(path, old.oid, new.oid, old.mode, new.mode));
+                    deltas.push(DiffDelta::modified(
+                        path, old.oid, new.oid, old.mode, new.mode,
+                    ));
                 }
                 // If OID and mode are the same, no change
             }
@@ -57,6 +57,10 @@ pub enum Error {
     /// Zlib decompression failed.
     DecompressionFailed,
 
+    /// The object hashes to the given SHA-1 (hex) through a known
+    /// collision attack.
+    Sha1Collision(String),
+
     // Phase 2: Write operations
     /// The reference already exists.
     RefAlreadyExists(String),
@@ -75,6 +79,57 @@ pub enum Error {
 
     /// A repository already exists at the specified path.
     AlreadyARepository(PathBuf),
+
+    /// The pathspec has unknown or conflicting magic.
+    InvalidPathspec(String),
+
+    /// The pathspec names a directory, but the operation is not recursive.
+    NotRecursive(String),
+
+    /// A file or directory cannot be renamed.
+    InvalidRename {
+        /// The source path.
+        from: PathBuf,
+        /// The destination path.
+        to: PathBuf,
+        /// The reason the rename was refused.
+        reason: &'static str,
+    },
+
+    /// A file cannot be removed without losing uncommitted changes.
+    UncommittedChanges {
+        /// The path of the file.
+        path: PathBuf,
+        /// The changes that would be lost: staged, local, or both.
+        reason: &'static str,
+    },
+
+    /// The path is outside the sparse checkout.
+    OutsideSparseCheckout(PathBuf),
+
+    /// The repository uses an object format (hash function) that is not
+    /// supported.
+    UnsupportedObjectFormat(String),
+
+    /// A packfile or pack index is invalid or corrupted.
+    InvalidPack {
+        /// The path of the pack or index file.
+        path: PathBuf,
+        /// The reason for invalidity.
+        reason: String,
+    },
+
+    /// A commit-graph file or chain is invalid or corrupted.
+    InvalidCommitGraph {
+        /// The path of the commit-graph file or chain.
+        path: PathBuf,
+        /// The reason for invalidity.
+        reason: String,
+    },
+
+    /// The operation needs files, such as a working tree, that an
+    /// in-memory repository does not have.
+    UnsupportedInMemory(&'static str),
 }
 
 impl fmt::Display for Error {
@@ -100,6 +155,9 @@ impl fmt::Display for Error {
             }
             Error::InvalidUtf8 => write!(f, "invalid UTF-8 sequence"),
             Error::DecompressionFailed => write!(f, "zlib decompression failed"),
+            Error::Sha1Collision(oid) => {
+                write!(f, "SHA-1 appears to be part of a collision attack: {}", oid)
+            }
             Error::RefAlreadyExists(name) => write!(f, "reference already exists: {}", name),
             Error::CannotDeleteCurrentBranch => write!(f, "cannot delete the current branch"),
             Error::EmptyCommit => write!(f, "nothing to commit"),
@@ -108,6 +166,39 @@ impl fmt::Display for Error {
             Error::AlreadyARepository(path) => {
                 write!(f, "repository already exists: {}", path.display())
             }
+            Error::InvalidPathspec(spec) => write!(f, "invalid pathspec: {}", spec),
+            Error::NotRecursive(spec) => {
+                write!(f, "not removing '{}' recursively without recursive", spec)
+            }
+            Error::InvalidRename { from, to, reason } => write!(
+                f,
+                "cannot rename {} to {}: {}",
+                from.display(),
+                to.display(),
+                reason
+            ),
+            Error::UncommittedChanges { path, reason } => {
+                write!(f, "cannot remove {}: {}", path.display(), reason)
+            }
+            Error::OutsideSparseCheckout(path) => {
+                write!(f, "path is outside the sparse checkout: {}", path.display())
+            }
+            Error::UnsupportedObjectFormat(name) => {
+                write!(f, "unsupported object format: {}", name)
+            }
+            Error::InvalidPack { path, reason } => {
+                write!(f, "invalid pack {}: {}", path.display(), reason)
+            }
+            Error::InvalidCommitGraph { path, reason } => {
+                write!(f, "invalid commit-graph {}: {}", path.display(), reason)
+            }
+            Error::UnsupportedInMemory(operation) => {
+                write!(
+                    f,
+                    "{} is not supported in an in-memory repository",
+                    operation
+                )
+            }
         }
     }
 }
@@ -197,12 +288,35 @@ mod tests {
             },
             Error::InvalidUtf8,
             Error::DecompressionFailed,
+            Error::Sha1Collision("38762cf7f55934b34d179ae6a4c80cadccbb7f0a".to_string()),
             Error::RefAlreadyExists("refs/heads/main".to_string()),
             Error::CannotDeleteCurrentBranch,
             Error::EmptyCommit,
             Error::DirtyWorkingTree,
             Error::ConfigNotFound("user.name".to_string()),
             Error::AlreadyARepository(PathBuf::from("/test/repo")),
+            Error::InvalidPathspec(":(bogus)x".to_string()),
+            Error::NotRecursive("src".to_string()),
+            Error::InvalidRename {
+                from: PathBuf::from("a"),
+                to: PathBuf::from("b"),
+                reason: "destination exists",
+            },
+            Error::UncommittedChanges {
+                path: PathBuf::from("a.txt"),
+                reason: "has local modifications",
+            },
+            Error::OutsideSparseCheckout(PathBuf::from("docs/guide.md")),
+            Error::UnsupportedObjectFormat("sha3".to_string()),
+            Error::InvalidPack {
+                path: PathBuf::from(".git/objects/pack/pack-1234.idx"),
+                reason: "bad signature".to_string(),
+            },
+            Error::InvalidCommitGraph {
+                path: PathBuf::from(".git/objects/info/commit-graph"),
+                reason: "bad signature".to_string(),
+            },
+            Error::UnsupportedInMemory("status"),
         ];
 
         // All variants should implement Display without panicking
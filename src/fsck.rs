@@ -0,0 +1,1158 @@
+//! Object database integrity checks (`git fsck`).
+//!
+//! Every loose object is read back, re-hashed and validated for its type:
+//! tree entries must be sorted and have valid modes and names, and commit
+//! and tag headers must follow Git's syntax, including the format of their
+//! signature lines. Unless connectivity checks are turned off, the object
+//! graph is then walked from HEAD, the refs, the reflogs and the index to
+//! find missing, dangling and unreachable objects.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::objects::{FileMode, LooseObjectStore, ObjectFormat, ObjectReader, ObjectType, Oid};
+use crate::refs::RefStore;
+use crate::Repository;
+
+/// Options for [`Repository::fsck`].
+///
+/// # Examples
+///
+/// ```
+/// use zerogit::FsckOptions;
+///
+/// let options = FsckOptions::new().strict(true).unreachable(true);
+/// assert!(options.get_connectivity());
+/// assert!(options.get_strict());
+/// assert!(options.get_unreachable());
+/// ```
+#[derive(Debug, Clone)]
+pub struct FsckOptions {
+    /// Walk the object graph to find missing and unreachable objects.
+    connectivity: bool,
+    /// Report unreachable objects that no other object refers to.
+    dangling: bool,
+    /// Report every unreachable object.
+    unreachable: bool,
+    /// Also reject group-writable file modes (`100664`) in trees.
+    strict: bool,
+}
+
+impl Default for FsckOptions {
+    fn default() -> Self {
+        Self {
+            connectivity: true,
+            dangling: true,
+            unreachable: false,
+            strict: false,
+        }
+    }
+}
+
+impl FsckOptions {
+    /// Creates a new FsckOptions with default settings: connectivity is
+    /// checked and dangling objects are reported, like `git fsck`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Walks the object graph from the refs, the reflogs and the index.
+    ///
+    /// When disabled, only the objects themselves are checked, and no
+    /// missing, dangling or unreachable objects are reported.
+    pub fn connectivity(mut self, connectivity: bool) -> Self {
+        self.connectivity = connectivity;
+        self
+    }
+
+    /// Reports unreachable objects that no other object refers to
+    /// (`git fsck --no-dangling` when false).
+    pub fn dangling(mut self, dangling: bool) -> Self {
+        self.dangling = dangling;
+        self
+    }
+
+    /// Reports every unreachable object instead of only the dangling ones
+    /// (`git fsck --unreachable`).
+    pub fn unreachable(mut self, unreachable: bool) -> Self {
+        self.unreachable = unreachable;
+        self
+    }
+
+    /// Enables stricter checks (`git fsck --strict`): tree entries with
+    /// the group-writable mode `100664` are reported.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Returns whether connectivity is checked.
+    pub fn get_connectivity(&self) -> bool {
+        self.connectivity
+    }
+
+    /// Returns whether dangling objects are reported.
+    pub fn get_dangling(&self) -> bool {
+        self.dangling
+    }
+
+    /// Returns whether every unreachable object is reported.
+    pub fn get_unreachable(&self) -> bool {
+        self.unreachable
+    }
+
+    /// Returns whether strict checks are enabled.
+    pub fn get_strict(&self) -> bool {
+        self.strict
+    }
+}
+
+/// The kind of problem found by [`Repository::fsck`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FsckFindingKind {
+    /// The object file cannot be read or inflated, or its header is invalid.
+    Corrupt,
+    /// The object content does not hash to the object's name.
+    HashMismatch,
+    /// The object content is not well-formed for its type.
+    Malformed,
+    /// An object referenced by a ref, the index or another object does
+    /// not exist.
+    Missing,
+    /// A ref or reflog cannot be read.
+    BadRef,
+    /// The object is unreachable and no other object refers to it.
+    Dangling,
+    /// The object is not reachable from any ref, reflog or the index.
+    Unreachable,
+}
+
+impl FsckFindingKind {
+    /// Returns true if this kind means the repository is damaged.
+    ///
+    /// Dangling and unreachable objects are normal leftovers of history
+    /// rewrites and are not errors.
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            FsckFindingKind::Dangling | FsckFindingKind::Unreachable
+        )
+    }
+}
+
+/// A problem found by [`Repository::fsck`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsckFinding {
+    /// What kind of problem this is.
+    kind: FsckFindingKind,
+    /// The object the problem is about, if any.
+    oid: Option<Oid>,
+    /// The type of the object, if known.
+    object_type: Option<ObjectType>,
+    /// A description of the problem.
+    message: String,
+}
+
+impl FsckFinding {
+    /// Returns the kind of problem.
+    pub fn kind(&self) -> FsckFindingKind {
+        self.kind
+    }
+
+    /// Returns the object the problem is about.
+    ///
+    /// This is `None` only for [`FsckFindingKind::BadRef`].
+    pub fn oid(&self) -> Option<&Oid> {
+        self.oid.as_ref()
+    }
+
+    /// Returns the type of the object, if it is known.
+    ///
+    /// The type of a corrupt object, or of a missing object referenced by
+    /// a ref, is not known.
+    pub fn object_type(&self) -> Option<ObjectType> {
+        self.object_type
+    }
+
+    /// Returns a description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// The result of [`Repository::fsck`].
+#[derive(Debug, Clone, Default)]
+pub struct FsckReport {
+    /// Every problem found, objects in Oid order.
+    findings: Vec<FsckFinding>,
+    /// The number of objects read.
+    objects_checked: usize,
+}
+
+impl FsckReport {
+    /// Returns every problem found.
+    pub fn findings(&self) -> &[FsckFinding] {
+        &self.findings
+    }
+
+    /// Returns the number of objects that were read and checked.
+    pub fn objects_checked(&self) -> usize {
+        self.objects_checked
+    }
+
+    /// Returns true if no errors were found.
+    ///
+    /// Dangling and unreachable objects do not count as errors.
+    pub fn is_ok(&self) -> bool {
+        !self.findings.iter().any(|finding| finding.kind.is_error())
+    }
+
+    /// Records a problem.
+    fn push(
+        &mut self,
+        kind: FsckFindingKind,
+        oid: Option<Oid>,
+        object_type: Option<ObjectType>,
+        message: String,
+    ) {
+        self.findings.push(FsckFinding {
+            kind,
+            oid,
+            object_type,
+            message,
+        });
+    }
+}
+
+/// A reference from an object (or a ref) to an object of a given type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Link {
+    pub(crate) oid: Oid,
+    pub(crate) object_type: ObjectType,
+}
+
+impl Repository {
+    /// Verifies the integrity of the object database (`git fsck`).
+    ///
+    /// Every object, loose or packed, is inflated and re-hashed (blobs as
+    /// a stream, without holding them in memory), and its content is
+    /// validated for its type: trees must be sorted without
+    /// duplicates and have valid modes and entry names, and commits and
+    /// tags must have well-formed headers and signature lines. With connectivity checks
+    /// (the default), objects are then walked from HEAD, all refs, the
+    /// reflogs and the index to find missing objects, and unreachable
+    /// objects are reported as dangling or unreachable. Objects borrowed
+    /// from alternate object directories are not checked, but links to them
+    /// are not missing either.
+    ///
+    /// Problems are reported as findings rather than errors, so one damaged
+    /// object does not hide the others.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Options controlling which checks are done.
+    ///
+    /// # Returns
+    ///
+    /// A report of every problem found.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::Io` if the object directory cannot be listed.
+    /// - `Error::InvalidIndex` if the index cannot be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::{FsckOptions, Repository};
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// let report = repo.fsck(FsckOptions::new()).unwrap();
+    /// for finding in report.findings() {
+    ///     println!("{:?} {}", finding.kind(), finding.message());
+    /// }
+    /// assert!(report.is_ok());
+    /// ```
+    pub fn fsck(&self, options: FsckOptions) -> Result<FsckReport> {
+        self.require_on_disk("fsck")?;
+        // Every object is read from disk, even ones read before, so that
+        // corruption since then is found
+        let format = self.object_format();
+        let store = LooseObjectStore::with_format(self.git_dir().join("objects"), format);
+        let mut report = FsckReport::default();
+
+        // Every object file, even corrupt ones, so that references to them
+        // are not reported as missing
+        let present: BTreeSet<Oid> = store.list_objects()?.into_iter().collect();
+        // The type and outgoing links of every well-formed object
+        let mut objects: BTreeMap<Oid, (ObjectType, Vec<Link>)> = BTreeMap::new();
+
+        for oid in &present {
+            report.objects_checked += 1;
+            let mut reader = match store.read_stream(oid) {
+                Ok(reader) => reader,
+                Err(e) => {
+                    report.push(FsckFindingKind::Corrupt, Some(*oid), None, e.to_string());
+                    continue;
+                }
+            };
+            let object_type = reader.object_type();
+
+            // The reader checks the hash once the content is read; blobs
+            // have nothing else to check, so they are never held in memory
+            let mut content = Vec::new();
+            let read = if object_type == ObjectType::Blob {
+                io::copy(&mut reader, &mut io::sink()).map(|_| ())
+            } else {
+                reader.read_to_end(&mut content).map(|_| ())
+            };
+            if let Err(e) = read {
+                let (kind, message) = match ObjectReader::into_error(e) {
+                    Error::InvalidObject { reason, .. } if reason.starts_with("hash mismatch") => {
+                        (FsckFindingKind::HashMismatch, reason)
+                    }
+                    e => (FsckFindingKind::Corrupt, e.to_string()),
+                };
+                report.push(kind, Some(*oid), Some(object_type), message);
+                continue;
+            }
+
+            let mut problems = Vec::new();
+            let links = check_object(object_type, &content, format, options.strict, &mut problems);
+            for problem in problems {
+                report.push(
+                    FsckFindingKind::Malformed,
+                    Some(*oid),
+                    Some(object_type),
+                    problem,
+                );
+            }
+            objects.insert(*oid, (object_type, links));
+        }
+
+        // Links must point to objects of the expected type
+        for (oid, (object_type, links)) in &objects {
+            for link in links {
+                if let Some((actual, _)) = objects.get(&link.oid) {
+                    if *actual != link.object_type {
+                        report.push(
+                            FsckFindingKind::Malformed,
+                            Some(*oid),
+                            Some(*object_type),
+                            format!(
+                                "object {} is a {}, not a {}",
+                                link.oid,
+                                actual.as_str(),
+                                link.object_type.as_str()
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+
+        if options.connectivity {
+            self.fsck_connectivity(&options, &store, &present, &objects, &mut report)?;
+        }
+
+        Ok(report)
+    }
+
+    /// Walks the object graph from the roots, reporting missing objects and
+    /// unreachable objects.
+    ///
+    /// Objects borrowed from alternate object directories count as present,
+    /// but are neither checked nor walked.
+    fn fsck_connectivity(
+        &self,
+        options: &FsckOptions,
+        store: &LooseObjectStore,
+        present: &BTreeSet<Oid>,
+        objects: &BTreeMap<Oid, (ObjectType, Vec<Link>)>,
+        report: &mut FsckReport,
+    ) -> Result<()> {
+        // The first referrer of each missing object, with the expected type
+        let mut missing: BTreeMap<Oid, (Option<ObjectType>, String)> = BTreeMap::new();
+        let mut reachable = HashSet::new();
+        let mut stack = Vec::new();
+
+        let roots =
+            self.object_roots(|message| report.push(FsckFindingKind::BadRef, None, None, message))?;
+        for (oid, object_type, source) in roots {
+            if !present.contains(&oid) {
+                if !store.exists(&oid) {
+                    missing.entry(oid).or_insert((object_type, source));
+                }
+            } else if reachable.insert(oid) {
+                stack.push(oid);
+            }
+        }
+
+        while let Some(oid) = stack.pop() {
+            // Corrupt objects have no known links
+            let Some((object_type, links)) = objects.get(&oid) else {
+                continue;
+            };
+            for link in links {
+                if !present.contains(&link.oid) {
+                    if store.exists(&link.oid) {
+                        continue;
+                    }
+                    missing.entry(link.oid).or_insert((
+                        Some(link.object_type),
+                        format!("referenced by {} {}", object_type.as_str(), oid),
+                    ));
+                } else if reachable.insert(link.oid) {
+                    stack.push(link.oid);
+                }
+            }
+        }
+
+        for (oid, (object_type, source)) in missing {
+            report.push(FsckFindingKind::Missing, Some(oid), object_type, source);
+        }
+
+        if options.unreachable || options.dangling {
+            let referenced: HashSet<&Oid> = objects
+                .values()
+                .flat_map(|(_, links)| links.iter().map(|link| &link.oid))
+                .collect();
+            for (oid, (object_type, _)) in objects {
+                if reachable.contains(oid) {
+                    continue;
+                }
+                if options.unreachable {
+                    report.push(
+                        FsckFindingKind::Unreachable,
+                        Some(*oid),
+                        Some(*object_type),
+                        format!("unreachable {} {}", object_type.as_str(), oid),
+                    );
+                } else if !referenced.contains(oid) {
+                    report.push(
+                        FsckFindingKind::Dangling,
+                        Some(*oid),
+                        Some(*object_type),
+                        format!("dangling {} {}", object_type.as_str(), oid),
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Collects the starting points of the connectivity walk: HEAD, every
+    /// ref, every reflog entry and the objects recorded in the index.
+    ///
+    /// Refs and reflogs that cannot be read are passed to `bad_ref` and
+    /// skipped.
+    ///
+    /// # Returns
+    ///
+    /// Each root with its expected type, if known, and where it is
+    /// referenced from.
+    pub(crate) fn object_roots(
+        &self,
+        mut bad_ref: impl FnMut(String),
+    ) -> Result<Vec<(Oid, Option<ObjectType>, String)>> {
+        let mut roots = Vec::new();
+
+        let refs = RefStore::new(self.git_dir());
+        for name in std::iter::once("HEAD".to_string()).chain(refs.references()?) {
+            match refs.resolve_recursive(&name) {
+                Ok(resolved) => roots.push((resolved.oid, None, format!("referenced by {}", name))),
+                // HEAD of a repository without commits points to an unborn branch
+                Err(Error::RefNotFound(_)) if name == "HEAD" => {}
+                Err(e) => bad_ref(format!("{}: {}", name, e)),
+            }
+        }
+
+        let mut logs = Vec::new();
+        collect_files(&self.git_dir().join("logs"), "logs", &mut logs)?;
+        logs.sort();
+        for log in logs {
+            let content = fs::read(self.git_dir().join(&log))?;
+            for (number, line) in content.split(|&b| b == b'\n').enumerate() {
+                if line.is_empty() {
+                    continue;
+                }
+                match parse_reflog_line(line, self.object_format()) {
+                    Some(oids) => roots.extend(
+                        oids.into_iter()
+                            .filter(|oid| !oid.is_zero())
+                            .map(|oid| (oid, None, format!("referenced by {}", log))),
+                    ),
+                    None => bad_ref(format!(
+                        "{}: invalid reflog entry on line {}",
+                        log,
+                        number + 1
+                    )),
+                }
+            }
+        }
+
+        let index = self.read_index()?;
+        for entry in index.entries() {
+            if entry.mode() != FileMode::Submodule {
+                roots.push((
+                    *entry.oid(),
+                    Some(ObjectType::Blob),
+                    format!("referenced by index entry {}", entry.path().display()),
+                ));
+            }
+        }
+        let mut cache_trees: Vec<_> = index.cache_tree().into_iter().collect();
+        while let Some(cache_tree) = cache_trees.pop() {
+            if let Some(oid) = cache_tree.oid() {
+                roots.push((
+                    *oid,
+                    Some(ObjectType::Tree),
+                    "referenced by the index cache tree".to_string(),
+                ));
+            }
+            cache_trees.extend(cache_tree.children());
+        }
+
+        Ok(roots)
+    }
+}
+
+/// Recursively collects the paths of the files below `dir`, relative to
+/// the `.git` directory.
+fn collect_files(dir: &Path, prefix: &str, files: &mut Vec<String>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry.path(), &name, files)?;
+        } else {
+            files.push(name);
+        }
+    }
+    Ok(())
+}
+
+/// Parses the old and new object IDs of a reflog line
+/// (`<old> <new> <identity> <timestamp> <tz>\t<message>`).
+fn parse_reflog_line(line: &[u8], format: ObjectFormat) -> Option<[Oid; 2]> {
+    let mut fields = line.splitn(3, |&b| b == b' ');
+    let old = parse_hex_oid(fields.next()?, format)?;
+    let new = parse_hex_oid(fields.next()?, format)?;
+    fields.next()?;
+    Some([old, new])
+}
+
+/// Parses a full hexadecimal object ID of the given format.
+fn parse_hex_oid(hex: &[u8], format: ObjectFormat) -> Option<Oid> {
+    if hex.len() != format.hex_len() {
+        return None;
+    }
+    Oid::from_hex(std::str::from_utf8(hex).ok()?).ok()
+}
+
+/// Returns the objects an object links to, ignoring any problems with it.
+pub(crate) fn object_links(
+    object_type: ObjectType,
+    content: &[u8],
+    format: ObjectFormat,
+) -> Vec<Link> {
+    check_object(object_type, content, format, false, &mut Vec::new())
+}
+
+/// Validates the content of an object and returns the objects it links to.
+///
+/// Problems are appended to `problems`, each at most once.
+fn check_object(
+    object_type: ObjectType,
+    content: &[u8],
+    format: ObjectFormat,
+    strict: bool,
+    problems: &mut Vec<String>,
+) -> Vec<Link> {
+    let mut report = |problem: &str| {
+        if !problems.iter().any(|p| p == problem) {
+            problems.push(problem.to_string());
+        }
+    };
+    match object_type {
+        ObjectType::Blob => Vec::new(),
+        ObjectType::Tree => check_tree(content, format, strict, &mut report),
+        ObjectType::Commit => check_commit(content, format, &mut report),
+        ObjectType::Tag => check_tag(content, format, &mut report),
+    }
+}
+
+/// Validates a tree: entry syntax, modes, names, and Git's sort order.
+fn check_tree(
+    content: &[u8],
+    format: ObjectFormat,
+    strict: bool,
+    report: &mut impl FnMut(&str),
+) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut previous: Option<(&[u8], bool)> = None;
+    let mut rest = content;
+
+    while !rest.is_empty() {
+        let space = rest.iter().position(|&b| b == b' ');
+        let nul = rest.iter().position(|&b| b == 0);
+        let (space, nul) = match (space, nul) {
+            (Some(space), Some(nul)) if space < nul => (space, nul),
+            _ => {
+                report("cannot be parsed as a tree: malformed entry");
+                break;
+            }
+        };
+        let oid_end = nul + 1 + format.size();
+        if rest.len() < oid_end {
+            report("cannot be parsed as a tree: truncated object ID");
+            break;
+        }
+        let mode = &rest[..space];
+        let name = &rest[space + 1..nul];
+        let oid = Oid::from_slice(&rest[nul + 1..oid_end]).expect("object ID size");
+        rest = &rest[oid_end..];
+
+        let mode_value = std::str::from_utf8(mode)
+            .ok()
+            .filter(|mode| !mode.is_empty() && mode.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|mode| u32::from_str_radix(mode, 8).ok());
+        let is_dir = mode_value == Some(0o40000);
+        match mode_value {
+            None => report("contains bad file modes"),
+            Some(_) if mode[0] == b'0' => report("contains zero-padded file modes"),
+            Some(0o100644 | 0o100755 | 0o120000 | 0o40000 | 0o160000) => {}
+            Some(0o100664) if !strict => {}
+            Some(_) => report("contains bad file modes"),
+        }
+        match mode_value {
+            Some(0o40000) => links.push(Link {
+                oid,
+                object_type: ObjectType::Tree,
+            }),
+            // Submodule commits live in another repository
+            Some(0o160000) => {}
+            _ => links.push(Link {
+                oid,
+                object_type: ObjectType::Blob,
+            }),
+        }
+
+        if name.is_empty() {
+            report("contains empty pathname");
+        } else if name.contains(&b'/') {
+            report("contains full pathnames");
+        } else if name == b"." {
+            report("contains '.'");
+        } else if name == b".." {
+            report("contains '..'");
+        } else if name.eq_ignore_ascii_case(b".git") {
+            report("contains '.git'");
+        }
+        if oid.is_zero() {
+            report("contains entries pointing to null sha1");
+        }
+
+        if let Some((previous_name, previous_is_dir)) = previous {
+            if previous_name == name {
+                report("contains duplicate file entries");
+            } else if tree_order(previous_name, previous_is_dir, name, is_dir) == Ordering::Greater
+            {
+                report("not properly sorted");
+            }
+        }
+        previous = Some((name, is_dir));
+    }
+
+    links
+}
+
+/// Compares two tree entry names in Git's tree order, where directory
+/// names sort as if they ended with `/`.
+fn tree_order(a: &[u8], a_is_dir: bool, b: &[u8], b_is_dir: bool) -> Ordering {
+    let a = a.iter().chain(a_is_dir.then_some(&b'/'));
+    let b = b.iter().chain(b_is_dir.then_some(&b'/'));
+    a.cmp(b)
+}
+
+/// Returns the header lines of a commit or tag, which must end with a
+/// blank line (or with the end of the object) and contain no NUL bytes.
+fn header_lines<'a>(content: &'a [u8], report: &mut impl FnMut(&str)) -> Option<Vec<&'a [u8]>> {
+    let end = match content.windows(2).position(|w| w == b"\n\n") {
+        Some(end) => end,
+        None if content.last() == Some(&b'\n') => content.len() - 1,
+        None => {
+            report("unterminated header");
+            return None;
+        }
+    };
+    let header = &content[..end];
+    if header.contains(&0) {
+        report("unterminated header: NUL in header");
+        return None;
+    }
+    Some(header.split(|&b| b == b'\n').collect())
+}
+
+/// Validates a commit: `tree`, `parent`, `author` and `committer` headers
+/// in that order, with valid object IDs and signatures.
+fn check_commit(content: &[u8], format: ObjectFormat, report: &mut impl FnMut(&str)) -> Vec<Link> {
+    let mut links = Vec::new();
+    let Some(lines) = header_lines(content, report) else {
+        return links;
+    };
+    let mut lines = lines.into_iter().peekable();
+
+    let Some(tree) = lines.next().and_then(|line| line.strip_prefix(b"tree ")) else {
+        report("invalid format - expected 'tree' line");
+        return links;
+    };
+    match parse_hex_oid(tree, format) {
+        Some(oid) => links.push(Link {
+            oid,
+            object_type: ObjectType::Tree,
+        }),
+        None => {
+            report("invalid 'tree' line format - bad sha1");
+            return links;
+        }
+    }
+
+    while let Some(parent) = lines.peek().and_then(|line| line.strip_prefix(b"parent ")) {
+        match parse_hex_oid(parent, format) {
+            Some(oid) => links.push(Link {
+                oid,
+                object_type: ObjectType::Commit,
+            }),
+            None => {
+                report("invalid 'parent' line format - bad sha1");
+                return links;
+            }
+        }
+        lines.next();
+    }
+
+    let Some(author) = lines.next().and_then(|line| line.strip_prefix(b"author ")) else {
+        report("invalid format - expected 'author' line");
+        return links;
+    };
+    if let Err(problem) = check_ident(author) {
+        report(problem);
+        return links;
+    }
+    if lines
+        .peek()
+        .is_some_and(|line| line.starts_with(b"author "))
+    {
+        report("invalid format - multiple 'author' lines");
+        return links;
+    }
+
+    let Some(committer) = lines
+        .next()
+        .and_then(|line| line.strip_prefix(b"committer "))
+    else {
+        report("invalid format - expected 'committer' line");
+        return links;
+    };
+    if let Err(problem) = check_ident(committer) {
+        report(problem);
+    }
+
+    links
+}
+
+/// Validates a tag: `object`, `type`, `tag` and optional `tagger`
+/// headers in that order.
+fn check_tag(content: &[u8], format: ObjectFormat, report: &mut impl FnMut(&str)) -> Vec<Link> {
+    let mut links = Vec::new();
+    let Some(lines) = header_lines(content, report) else {
+        return links;
+    };
+    let mut lines = lines.into_iter();
+
+    let Some(object) = lines.next().and_then(|line| line.strip_prefix(b"object ")) else {
+        report("invalid format - expected 'object' line");
+        return links;
+    };
+    let Some(object) = parse_hex_oid(object, format) else {
+        report("invalid 'object' line format - bad sha1");
+        return links;
+    };
+
+    let Some(object_type) = lines.next().and_then(|line| line.strip_prefix(b"type ")) else {
+        report("invalid format - expected 'type' line");
+        return links;
+    };
+    let Some(object_type) = std::str::from_utf8(object_type)
+        .ok()
+        .and_then(ObjectType::parse)
+    else {
+        report("invalid 'type' value");
+        return links;
+    };
+    links.push(Link {
+        oid: object,
+        object_type,
+    });
+
+    if !lines.next().is_some_and(|line| line.starts_with(b"tag ")) {
+        report("invalid format - expected 'tag' line");
+        return links;
+    }
+
+    if let Some(tagger) = lines.next().and_then(|line| line.strip_prefix(b"tagger ")) {
+        if let Err(problem) = check_ident(tagger) {
+            report(problem);
+        }
+    }
+
+    links
+}
+
+/// Validates a signature line: `Name <email> <timestamp> <+|-hhmm>`.
+fn check_ident(ident: &[u8]) -> std::result::Result<(), &'static str> {
+    if ident.first() == Some(&b'<') {
+        return Err("invalid author/committer line - missing space before email");
+    }
+    let lt = ident.iter().position(|&b| b == b'<' || b == b'>');
+    let lt = match lt {
+        Some(lt) if ident[lt] == b'<' => lt,
+        Some(_) => return Err("invalid author/committer line - bad name"),
+        None => return Err("invalid author/committer line - missing email"),
+    };
+    if ident[lt - 1] != b' ' {
+        return Err("invalid author/committer line - missing space before email");
+    }
+    let rest = &ident[lt + 1..];
+    let gt = match rest.iter().position(|&b| b == b'<' || b == b'>') {
+        Some(gt) if rest[gt] == b'>' => gt,
+        _ => return Err("invalid author/committer line - bad email"),
+    };
+
+    let Some(date) = rest[gt + 1..].strip_prefix(b" ") else {
+        return Err("invalid author/committer line - missing space before date");
+    };
+    let digits = date.iter().take_while(|b| b.is_ascii_digit()).count();
+    if date.first() == Some(&b'0') && digits > 1 {
+        return Err("invalid author/committer line - zero-padded date");
+    }
+    if digits == 0 {
+        return Err("invalid author/committer line - bad date");
+    }
+    if std::str::from_utf8(&date[..digits])
+        .ok()
+        .and_then(|digits| digits.parse::<u64>().ok())
+        .is_none()
+    {
+        return Err("invalid author/committer line - date causes integer overflow");
+    }
+
+    let Some(tz) = date[digits..].strip_prefix(b" ") else {
+        return Err("invalid author/committer line - bad date");
+    };
+    let valid_tz =
+        tz.len() == 5 && (tz[0] == b'+' || tz[0] == b'-') && tz[1..].iter().all(u8::is_ascii_digit);
+    if !valid_tz {
+        return Err("invalid author/committer line - bad time zone");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OID_A: &str = "da39a3ee5e6b4b0d3255bfef95601890afd80709";
+    const OID_B: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    fn problems_of(object_type: ObjectType, content: &[u8], strict: bool) -> Vec<String> {
+        let mut problems = Vec::new();
+        check_object(
+            object_type,
+            content,
+            ObjectFormat::Sha1,
+            strict,
+            &mut problems,
+        );
+        problems
+    }
+
+    fn tree_entry(mode: &str, name: &[u8], oid: &str) -> Vec<u8> {
+        let mut entry = format!("{} ", mode).into_bytes();
+        entry.extend_from_slice(name);
+        entry.push(0);
+        entry.extend_from_slice(Oid::from_hex(oid).unwrap().as_bytes());
+        entry
+    }
+
+    // F-001: Well-formed trees have no problems and link to their entries
+    #[test]
+    fn test_tree_valid() {
+        let content = [
+            tree_entry("100644", b"a.txt", OID_A),
+            tree_entry("40000", b"a", OID_B),
+            tree_entry("100755", b"a0", OID_A),
+            tree_entry("160000", b"sub", OID_B),
+        ]
+        .concat();
+        let mut problems = Vec::new();
+        let links = check_object(
+            ObjectType::Tree,
+            &content,
+            ObjectFormat::Sha1,
+            false,
+            &mut problems,
+        );
+
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(links.len(), 3, "gitlinks are not followed");
+        assert_eq!(links[1].object_type, ObjectType::Tree);
+        assert_eq!(links[2].object_type, ObjectType::Blob);
+    }
+
+    // F-002: Tree ordering, duplicates, modes and names are checked
+    #[test]
+    fn test_tree_problems() {
+        let unsorted = [
+            tree_entry("100644", b"b", OID_A),
+            tree_entry("100644", b"a", OID_A),
+        ]
+        .concat();
+        assert_eq!(
+            problems_of(ObjectType::Tree, &unsorted, false),
+            ["not properly sorted"]
+        );
+
+        // A directory "a" sorts as "a/", after "a.txt"
+        let dir_first = [
+            tree_entry("40000", b"a", OID_A),
+            tree_entry("100644", b"a.txt", OID_A),
+        ]
+        .concat();
+        assert_eq!(
+            problems_of(ObjectType::Tree, &dir_first, false),
+            ["not properly sorted"]
+        );
+
+        let duplicate = [
+            tree_entry("100644", b"a", OID_A),
+            tree_entry("40000", b"a", OID_A),
+        ]
+        .concat();
+        assert_eq!(
+            problems_of(ObjectType::Tree, &duplicate, false),
+            ["contains duplicate file entries"]
+        );
+
+        let modes = [
+            tree_entry("040000", b"a", OID_A),
+            tree_entry("100664", b"b", OID_A),
+            tree_entry("100600", b"c", OID_A),
+        ]
+        .concat();
+        assert_eq!(
+            problems_of(ObjectType::Tree, &modes, false),
+            ["contains zero-padded file modes", "contains bad file modes"]
+        );
+        assert_eq!(
+            problems_of(ObjectType::Tree, &tree_entry("100664", b"b", OID_A), true),
+            ["contains bad file modes"]
+        );
+
+        for (name, problem) in [
+            (&b""[..], "contains empty pathname"),
+            (b"a/b", "contains full pathnames"),
+            (b".", "contains '.'"),
+            (b"..", "contains '..'"),
+            (b".GIT", "contains '.git'"),
+        ] {
+            assert_eq!(
+                problems_of(ObjectType::Tree, &tree_entry("100644", name, OID_A), false),
+                [problem]
+            );
+        }
+
+        let zero = "0".repeat(40);
+        assert_eq!(
+            problems_of(ObjectType::Tree, &tree_entry("100644", b"a", &zero), false),
+            ["contains entries pointing to null sha1"]
+        );
+
+        let mut truncated = tree_entry("100644", b"a", OID_A);
+        truncated.truncate(truncated.len() - 1);
+        assert_eq!(
+            problems_of(ObjectType::Tree, &truncated, false),
+            ["cannot be parsed as a tree: truncated object ID"]
+        );
+    }
+
+    // F-003: Commit headers must come in order with valid values
+    #[test]
+    fn test_commit() {
+        let ident = "A U Thor <author@example.com> 1700000000 +0900";
+        let valid = format!(
+            "tree {}\nparent {}\nauthor {}\ncommitter {}\n\nmessage\n",
+            OID_A, OID_B, ident, ident
+        );
+        let mut problems = Vec::new();
+        let links = check_object(
+            ObjectType::Commit,
+            valid.as_bytes(),
+            ObjectFormat::Sha1,
+            false,
+            &mut problems,
+        );
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(
+            links,
+            [
+                Link {
+                    oid: Oid::from_hex(OID_A).unwrap(),
+                    object_type: ObjectType::Tree
+                },
+                Link {
+                    oid: Oid::from_hex(OID_B).unwrap(),
+                    object_type: ObjectType::Commit
+                },
+            ]
+        );
+
+        for (content, problem) in [
+            (
+                format!("parent {}\nauthor {}\n\n", OID_B, ident),
+                "invalid format - expected 'tree' line",
+            ),
+            (
+                format!(
+                    "tree {}\nauthor {}\ncommitter {}\n\n",
+                    &OID_A[..39],
+                    ident,
+                    ident
+                ),
+                "invalid 'tree' line format - bad sha1",
+            ),
+            (
+                format!("tree {}\ncommitter {}\n\n", OID_A, ident),
+                "invalid format - expected 'author' line",
+            ),
+            (
+                format!(
+                    "tree {}\nauthor {}\nauthor {}\ncommitter {}\n\n",
+                    OID_A, ident, ident, ident
+                ),
+                "invalid format - multiple 'author' lines",
+            ),
+            (
+                format!("tree {}\nauthor {}\n\n", OID_A, ident),
+                "invalid format - expected 'committer' line",
+            ),
+            (
+                format!("tree {}\nauthor {}\ncommitter {}", OID_A, ident, ident),
+                "unterminated header",
+            ),
+        ] {
+            assert_eq!(
+                problems_of(ObjectType::Commit, content.as_bytes(), false),
+                [problem],
+                "{}",
+                content
+            );
+        }
+    }
+
+    // F-004: Signature lines follow `Name <email> timestamp +hhmm`
+    #[test]
+    fn test_ident() {
+        assert_eq!(check_ident(b"A <a@b> 0 +0000"), Ok(()));
+        assert_eq!(check_ident(b" <a@b> 1 -0130"), Ok(()));
+        for (ident, problem) in [
+            (&b"<a@b> 1 +0000"[..], "missing space before email"),
+            (b"A a@b> 1 +0000", "bad name"),
+            (b"A", "missing email"),
+            (b"A<a@b> 1 +0000", "missing space before email"),
+            (b"A <a@b 1 +0000", "bad email"),
+            (b"A <a@b>1 +0000", "missing space before date"),
+            (b"A <a@b> 01 +0000", "zero-padded date"),
+            (b"A <a@b> x +0000", "bad date"),
+            (
+                b"A <a@b> 99999999999999999999 +0000",
+                "date causes integer overflow",
+            ),
+            (b"A <a@b> 1+0000", "bad date"),
+            (b"A <a@b> 1 0000", "bad time zone"),
+            (b"A <a@b> 1 +000", "bad time zone"),
+        ] {
+            let error = check_ident(ident).unwrap_err();
+            assert!(
+                error.ends_with(problem),
+                "{}: {}",
+                String::from_utf8_lossy(ident),
+                error
+            );
+        }
+    }
+
+    // F-005: Tag headers must come in order and name a known type
+    #[test]
+    fn test_tag() {
+        let valid = format!(
+            "object {}\ntype commit\ntag v1.0\ntagger T <t@t> 1 +0000\n\nmessage\n",
+            OID_A
+        );
+        let mut problems = Vec::new();
+        let links = check_object(
+            ObjectType::Tag,
+            valid.as_bytes(),
+            ObjectFormat::Sha1,
+            false,
+            &mut problems,
+        );
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(links[0].object_type, ObjectType::Commit);
+
+        for (content, problem) in [
+            (
+                "type commit\ntag v1.0\n\n".to_string(),
+                "invalid format - expected 'object' line",
+            ),
+            (
+                format!("object {}\ntype bogus\ntag v1.0\n\n", OID_A),
+                "invalid 'type' value",
+            ),
+            (
+                format!("object {}\ntype blob\n\n", OID_A),
+                "invalid format - expected 'tag' line",
+            ),
+            (
+                format!("object {}\ntype blob\ntag v\ntagger T 1 +0000\n\n", OID_A),
+                "invalid author/committer line - missing email",
+            ),
+        ] {
+            assert_eq!(
+                problems_of(ObjectType::Tag, content.as_bytes(), false),
+                [problem],
+                "{}",
+                content
+            );
+        }
+    }
+
+    // F-006: Reflog lines yield their old and new object IDs
+    #[test]
+    fn test_parse_reflog_line() {
+        let line = format!("{} {} T <t@t> 1 +0000\tcommit: x", OID_A, OID_B);
+        let [old, new] = parse_reflog_line(line.as_bytes(), ObjectFormat::Sha1).unwrap();
+        assert_eq!(old.to_hex(), OID_A);
+        assert_eq!(new.to_hex(), OID_B);
+        assert!(parse_reflog_line(b"garbage", ObjectFormat::Sha1).is_none());
+    }
+}
@@ -0,0 +1,531 @@
+//! Garbage collection (`git gc`).
+//!
+//! Collecting garbage packs every reachable object into a single packfile,
+//! deletes the loose objects and old packs it replaces, prunes unreachable
+//! objects once they are older than a grace period, and moves loose refs
+//! into `packed-refs`.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::error::{Error, Result};
+use crate::fsck::object_links;
+use crate::infra::remove_file;
+use crate::objects::{LooseObjectStore, ObjectType, Oid, Pack, PackWriter, TagObject};
+use crate::refs::{PackedRef, RefStore, RefValue};
+use crate::repository::big_file_threshold;
+use crate::Repository;
+
+/// Default grace period before unreachable objects are pruned (two weeks,
+/// as `gc.pruneExpire` defaults to in Git).
+const DEFAULT_PRUNE_EXPIRE: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+/// Options for [`Repository::gc`].
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use zerogit::GcOptions;
+///
+/// let options = GcOptions::new()
+///     .prune_expire(Duration::from_secs(3600))
+///     .window(20)
+///     .pack_refs(false);
+/// assert_eq!(options.get_prune_expire(), Duration::from_secs(3600));
+/// assert_eq!(options.get_window(), 20);
+/// assert_eq!(options.get_depth(), 50);
+/// assert!(!options.get_pack_refs());
+/// assert!(options.get_write_commit_graph());
+/// ```
+#[derive(Debug, Clone)]
+pub struct GcOptions {
+    /// Delete unreachable objects older than the grace period.
+    prune: bool,
+    /// How old an unreachable object must be before it is deleted.
+    prune_expire: Duration,
+    /// How many objects are tried as delta bases for each object.
+    window: usize,
+    /// The longest delta chain in the pack.
+    depth: usize,
+    /// Move loose refs into `packed-refs`.
+    pack_refs: bool,
+    /// Write a commit-graph of the reachable commits.
+    write_commit_graph: bool,
+}
+
+impl Default for GcOptions {
+    fn default() -> Self {
+        GcOptions {
+            prune: true,
+            prune_expire: DEFAULT_PRUNE_EXPIRE,
+            window: 10,
+            depth: 50,
+            pack_refs: true,
+            write_commit_graph: true,
+        }
+    }
+}
+
+impl GcOptions {
+    /// Creates a new GcOptions with default settings: prune unreachable
+    /// objects older than two weeks, a delta window of 10 and depth of 50,
+    /// pack refs and write a commit-graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deletes unreachable objects once they are older than the grace
+    /// period. When disabled, unreachable objects are kept as loose
+    /// objects (`git gc --no-prune`).
+    pub fn prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    /// Sets how long unreachable objects are kept, measured from their
+    /// modification time (`git gc --prune=<date>`). A zero duration prunes
+    /// every unreachable object.
+    pub fn prune_expire(mut self, prune_expire: Duration) -> Self {
+        self.prune_expire = prune_expire;
+        self
+    }
+
+    /// Sets how many preceding objects are tried as delta bases for each
+    /// object (`pack.window`). A window of 0 disables delta compression.
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the longest delta chain in the pack (`pack.depth`).
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Moves loose refs into `packed-refs` (`git pack-refs --all`).
+    pub fn pack_refs(mut self, pack_refs: bool) -> Self {
+        self.pack_refs = pack_refs;
+        self
+    }
+
+    /// Writes a commit-graph of the reachable commits
+    /// (`gc.writeCommitGraph`).
+    pub fn write_commit_graph(mut self, write_commit_graph: bool) -> Self {
+        self.write_commit_graph = write_commit_graph;
+        self
+    }
+
+    /// Returns whether unreachable objects are pruned.
+    pub fn get_prune(&self) -> bool {
+        self.prune
+    }
+
+    /// Returns the grace period for unreachable objects.
+    pub fn get_prune_expire(&self) -> Duration {
+        self.prune_expire
+    }
+
+    /// Returns the delta window size.
+    pub fn get_window(&self) -> usize {
+        self.window
+    }
+
+    /// Returns the maximum delta chain depth.
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns whether refs are packed.
+    pub fn get_pack_refs(&self) -> bool {
+        self.pack_refs
+    }
+
+    /// Returns whether a commit-graph is written.
+    pub fn get_write_commit_graph(&self) -> bool {
+        self.write_commit_graph
+    }
+}
+
+/// The outcome of [`Repository::gc`].
+#[derive(Debug, Clone, Default)]
+pub struct GcReport {
+    objects_packed: usize,
+    objects_pruned: usize,
+    refs_packed: usize,
+    pack_path: Option<PathBuf>,
+}
+
+impl GcReport {
+    /// Returns the number of objects written to the new pack.
+    pub fn objects_packed(&self) -> usize {
+        self.objects_packed
+    }
+
+    /// Returns the number of unreachable objects deleted.
+    pub fn objects_pruned(&self) -> usize {
+        self.objects_pruned
+    }
+
+    /// Returns the number of loose refs moved into `packed-refs`.
+    pub fn refs_packed(&self) -> usize {
+        self.refs_packed
+    }
+
+    /// Returns the path of the new `.pack` file, or `None` if there was
+    /// nothing to pack.
+    pub fn pack_path(&self) -> Option<&Path> {
+        self.pack_path.as_deref()
+    }
+}
+
+impl Repository {
+    /// Packs reachable objects and refs and prunes unreachable objects
+    /// (`git gc`).
+    ///
+    /// Every object reachable from HEAD, the refs, the reflogs or the index
+    /// is written into one new pack, with similar objects stored as deltas.
+    /// Blobs larger than `core.bigFileThreshold` are stored whole, so that
+    /// they can still be streamed.
+    /// Objects borrowed from alternate object directories are left where
+    /// they are, as with `git gc`, which packs local objects only.
+    /// The loose objects and old packs it replaces are then deleted.
+    /// Unreachable objects are deleted once their modification time is
+    /// older than the grace period; younger ones, and the objects they
+    /// refer to, are kept as loose objects. Finally, loose refs are moved
+    /// into `packed-refs` and a commit-graph is written, as by
+    /// [`Repository::write_commit_graph`].
+    ///
+    /// The objects are read again as the pack is written, so only the
+    /// objects in the delta window are held in memory, and blobs larger
+    /// than `core.bigFileThreshold` are streamed into the pack. Nothing is
+    /// deleted until the new pack is in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Options controlling pruning, delta compression, ref
+    ///   packing and the commit-graph.
+    ///
+    /// # Returns
+    ///
+    /// A summary of what was packed and pruned.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::InvalidRefName` if a ref or reflog cannot be read.
+    /// - `Error::ObjectNotFound` if a reachable object is missing.
+    /// - `Error::InvalidObject` if a reachable object is corrupt.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::{GcOptions, Repository};
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// let report = repo.gc(GcOptions::new()).unwrap();
+    /// println!(
+    ///     "packed {} objects, pruned {}",
+    ///     report.objects_packed(),
+    ///     report.objects_pruned()
+    /// );
+    /// ```
+    pub fn gc(&self, options: GcOptions) -> Result<GcReport> {
+        self.require_on_disk("gc")?;
+        let _lock = self.lock_for_write();
+        let store = self.object_store();
+        let mut report = GcReport::default();
+        let expire = SystemTime::now()
+            .checked_sub(options.prune_expire)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+
+        // Refuse to run on a broken repository rather than lose objects
+        let mut bad_ref = None;
+        let roots = self.object_roots(|message| {
+            bad_ref.get_or_insert(message);
+        })?;
+        if let Some(message) = bad_ref {
+            return Err(Error::InvalidRefName(message));
+        }
+
+        let mut writer = PackWriter::new(self.object_format())
+            .window(options.window)
+            .depth(options.depth)
+            .big_file_threshold(big_file_threshold(&self.config()?)?)
+            .with_store(self.object_store())
+            .with_fsync(self.fsync());
+        // Objects borrowed from alternates are walked but not packed
+        let borrows = !store.alternates().is_empty();
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<Oid> = roots.into_iter().map(|(oid, _, _)| oid).collect();
+        while let Some(oid) = stack.pop() {
+            if !reachable.insert(oid) {
+                continue;
+            }
+            // Blobs link to nothing, so they are only read into the pack
+            let (object_type, size) = store.read_header(&oid)?;
+            if object_type != ObjectType::Blob {
+                let raw = store.read(&oid)?;
+                for link in object_links(raw.object_type, &raw.content, self.object_format()) {
+                    if !reachable.contains(&link.oid) {
+                        stack.push(link.oid);
+                    }
+                }
+            }
+            if borrows && !store.exists_locally(&oid) {
+                continue;
+            }
+            writer.add_stored(oid, object_type, size);
+        }
+
+        let old_packs = store.packs()?;
+        let new_pack = if writer.is_empty() {
+            None
+        } else {
+            report.objects_packed = writer.len();
+            let pack = writer.write(self.git_dir().join("objects/pack"))?;
+            report.pack_path = Some(pack.pack_path().to_path_buf());
+            Some(pack)
+        };
+
+        // The multi-pack-index refers to the packs about to be removed
+        remove_multi_pack_index(&self.git_dir().join("objects/pack"))?;
+        store.close_packs();
+
+        for pack in old_packs {
+            if new_pack
+                .as_ref()
+                .is_some_and(|new| new.index_path() == pack.index_path())
+            {
+                continue;
+            }
+            self.gc_remove_pack(&store, &pack, &reachable, &options, expire)?;
+        }
+
+        report.objects_pruned = self.gc_loose_objects(&store, &reachable, &options, expire)?;
+
+        if options.pack_refs {
+            report.refs_packed = self.gc_pack_refs(&store)?;
+        }
+
+        if options.write_commit_graph {
+            self.write_commit_graph()?;
+        }
+
+        Ok(report)
+    }
+
+    /// Deletes a pack that the new pack replaces.
+    ///
+    /// Unreachable objects in a pack younger than the grace period are
+    /// written out as loose objects first, so that they are pruned only
+    /// once they expire. Packs with a `.keep` file are left alone.
+    fn gc_remove_pack(
+        &self,
+        store: &LooseObjectStore,
+        pack: &Pack,
+        reachable: &HashSet<Oid>,
+        options: &GcOptions,
+        expire: SystemTime,
+    ) -> Result<()> {
+        if pack.pack_path().with_extension("keep").exists() {
+            return Ok(());
+        }
+
+        if !options.prune || modified(pack.pack_path())? > expire {
+            for oid in pack.oids()? {
+                if reachable.contains(&oid) {
+                    continue;
+                }
+                if let Some(raw) = pack.read(&oid)? {
+                    store.write_loose(raw.object_type, &raw.content)?;
+                }
+            }
+            self.fsync().flush_batch()?;
+        }
+
+        // The index goes first, so that readers stop using the pack
+        remove_file(pack.index_path())?;
+        for extension in ["pack", "rev", "bitmap"] {
+            match remove_file(pack.pack_path().with_extension(extension)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the loose objects that are now packed, and the unreachable
+    /// ones older than the grace period.
+    ///
+    /// Unreachable objects that are still recent are kept together with
+    /// every unreachable object they refer to, so that they stay complete.
+    ///
+    /// # Returns
+    ///
+    /// The number of unreachable objects deleted.
+    fn gc_loose_objects(
+        &self,
+        store: &LooseObjectStore,
+        reachable: &HashSet<Oid>,
+        options: &GcOptions,
+        expire: SystemTime,
+    ) -> Result<usize> {
+        let mut unreachable = HashSet::new();
+        for oid in store.list_loose_objects()? {
+            if reachable.contains(&oid) {
+                remove_file(store.oid_to_path(&oid))?;
+            } else {
+                unreachable.insert(oid);
+            }
+        }
+
+        let mut stack = Vec::new();
+        for oid in &unreachable {
+            if !options.prune || modified(&store.oid_to_path(oid))? > expire {
+                stack.push(*oid);
+            }
+        }
+        let mut keep = HashSet::new();
+        while let Some(oid) = stack.pop() {
+            if !keep.insert(oid) {
+                continue;
+            }
+            // Unreadable objects have no known links
+            if let Ok(raw) = store.read(&oid) {
+                for link in object_links(raw.object_type, &raw.content, self.object_format()) {
+                    if unreachable.contains(&link.oid) && !keep.contains(&link.oid) {
+                        stack.push(link.oid);
+                    }
+                }
+            }
+        }
+
+        let mut pruned = 0;
+        for oid in unreachable.difference(&keep) {
+            remove_file(store.oid_to_path(oid))?;
+            pruned += 1;
+        }
+
+        // Remove the fan-out directories left empty
+        for entry in fs::read_dir(self.git_dir().join("objects"))? {
+            let entry = entry?;
+            if entry.file_name().len() == 2 && entry.file_type()?.is_dir() {
+                let _ = fs::remove_dir(entry.path());
+            }
+        }
+
+        Ok(pruned)
+    }
+
+    /// Moves every loose ref except symbolic refs into `packed-refs`, and
+    /// records the peeled target of annotated tags.
+    ///
+    /// # Returns
+    ///
+    /// The number of loose refs packed.
+    fn gc_pack_refs(&self, store: &LooseObjectStore) -> Result<usize> {
+        let refs = RefStore::new(self.git_dir()).with_fsync(self.fsync());
+        let mut packed: BTreeMap<String, PackedRef> = refs
+            .packed_refs()?
+            .into_iter()
+            .map(|packed| (packed.name.clone(), packed))
+            .collect();
+
+        let mut loose = Vec::new();
+        for name in refs.loose_references()? {
+            if let RefValue::Direct(oid) = refs.read_ref_file(&name)? {
+                packed.insert(
+                    name.clone(),
+                    PackedRef {
+                        name: name.clone(),
+                        oid,
+                        peeled: None,
+                    },
+                );
+                loose.push((name, oid));
+            }
+        }
+        for packed_ref in packed.values_mut() {
+            packed_ref.peeled = peel(store, &packed_ref.oid);
+        }
+
+        let packed: Vec<PackedRef> = packed.into_values().collect();
+        refs.write_packed_refs(&packed)?;
+
+        // Only delete refs that were not updated in the meantime
+        let refs_dir = self.git_dir().join("refs");
+        for (name, oid) in &loose {
+            if refs.read_ref_file(name)? == RefValue::Direct(*oid) {
+                fs::remove_file(self.git_dir().join(name))?;
+            }
+        }
+        remove_empty_dirs(&refs_dir, &refs_dir)?;
+
+        Ok(loose.len())
+    }
+}
+
+/// Returns the object an annotated tag ultimately points to, or `None` if
+/// `oid` is not a tag.
+fn peel(store: &LooseObjectStore, oid: &Oid) -> Option<Oid> {
+    let mut target = None;
+    let mut current = *oid;
+    for _ in 0..100 {
+        let raw = store.read(&current).ok()?;
+        if raw.object_type != ObjectType::Tag {
+            break;
+        }
+        current = *TagObject::parse(raw).ok()?.object();
+        target = Some(current);
+    }
+    target
+}
+
+/// Removes the multi-pack-index of a pack directory, with its bitmap and
+/// reverse index.
+fn remove_multi_pack_index(pack_dir: &Path) -> Result<()> {
+    let entries = match fs::read_dir(pack_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with("multi-pack-index")
+        {
+            remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the modification time of a file.
+fn modified(path: &Path) -> Result<SystemTime> {
+    Ok(fs::metadata(path)?.modified()?)
+}
+
+/// Removes the empty directories below `dir`, keeping `refs/heads` and
+/// `refs/tags`, which every repository has.
+fn remove_empty_dirs(dir: &Path, refs_dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        remove_empty_dirs(&path, refs_dir)?;
+        if path != refs_dir.join("heads")
+            && path != refs_dir.join("tags")
+            && fs::read_dir(&path)?.next().is_none()
+        {
+            fs::remove_dir(&path)?;
+        }
+    }
+    Ok(())
+}
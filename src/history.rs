@@ -0,0 +1,339 @@
+//! Commit history queries (`git merge-base`, `git commit-graph write`).
+//!
+//! Walks over the history read each commit's parents and commit time from
+//! the commit-graph when it covers the commit, and parse the commit object
+//! otherwise. A missing or invalid commit-graph only makes walks slower.
+
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::error::{Error, Result};
+use crate::objects::{
+    CommitGraph, CommitGraphWriter, LooseObjectStore, ObjectType, Oid, TagObject,
+};
+use crate::refs::RefStore;
+use crate::Repository;
+
+/// Reached from the first commit of a merge-base query.
+const PARENT1: u8 = 1;
+
+/// Reached from the second commit of a merge-base query.
+const PARENT2: u8 = 2;
+
+/// Reached from a common ancestor, so not a merge base itself.
+const STALE: u8 = 4;
+
+/// What a history walk needs to know about a commit.
+#[derive(Debug, Clone)]
+pub(crate) struct CommitNode {
+    /// The IDs of the commit's parents, in order.
+    pub(crate) parents: Vec<Oid>,
+    /// The committer timestamp.
+    pub(crate) commit_time: i64,
+    /// The generation number, or `u64::MAX` if the commit is not in the
+    /// commit-graph.
+    pub(crate) generation: u64,
+}
+
+/// Looks up commits for history walks, through the commit-graph if there
+/// is one.
+pub(crate) struct CommitNodes {
+    store: LooseObjectStore,
+    graph: Option<CommitGraph>,
+}
+
+impl CommitNodes {
+    /// Creates a lookup over an object database, ignoring its commit-graph
+    /// if it cannot be read.
+    pub(crate) fn new(store: LooseObjectStore) -> Self {
+        let graph = store.commit_graph();
+        CommitNodes { store, graph }
+    }
+
+    /// Returns the parents, commit time and generation of a commit.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::ObjectNotFound` if the commit is neither in the
+    ///   commit-graph nor in the object database.
+    /// - `Error::TypeMismatch` if the object is not a commit.
+    pub(crate) fn get(&self, oid: &Oid) -> Result<CommitNode> {
+        if let Some(graph) = &self.graph {
+            if let Ok(Some(commit)) = graph.get(oid) {
+                return Ok(CommitNode {
+                    parents: commit.parents().to_vec(),
+                    commit_time: commit.commit_time(),
+                    generation: commit.generation(),
+                });
+            }
+        }
+        let commit = self.store.read_commit(oid)?;
+        Ok(CommitNode {
+            parents: commit.parents().to_vec(),
+            commit_time: commit.committer().timestamp(),
+            generation: u64::MAX,
+        })
+    }
+
+    /// Returns all commits reachable from `start`, including itself.
+    pub(crate) fn ancestors(&self, start: Oid) -> Result<HashSet<Oid>> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![start];
+        while let Some(oid) = stack.pop() {
+            if seen.insert(oid) {
+                stack.extend(self.get(&oid)?.parents);
+            }
+        }
+        Ok(seen)
+    }
+
+    /// Finds the best common ancestors of two commits.
+    ///
+    /// Commits are visited newest first, marking each with the side it was
+    /// reached from. A commit reached from both sides is a merge base, and
+    /// its ancestors are marked stale. The walk ends once only stale
+    /// commits are left to visit.
+    ///
+    /// # Returns
+    ///
+    /// The merge bases, none an ancestor of another, newest first.
+    pub(crate) fn merge_bases(&self, one: Oid, two: Oid) -> Result<Vec<Oid>> {
+        if one == two {
+            return Ok(vec![one]);
+        }
+
+        let mut nodes: HashMap<Oid, CommitNode> = HashMap::new();
+        let mut flags: HashMap<Oid, u8> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        for (oid, flag) in [(one, PARENT1), (two, PARENT2)] {
+            let node = self.get(&oid)?;
+            queue.push(QueuedCommit::new(oid, &node));
+            nodes.insert(oid, node);
+            flags.insert(oid, flag);
+        }
+
+        let mut bases = Vec::new();
+        while queue.iter().any(|queued| flags[&queued.oid] & STALE == 0) {
+            let queued = queue.pop().expect("queue is not empty");
+            let mut flag = flags[&queued.oid];
+            if flag & (PARENT1 | PARENT2) == PARENT1 | PARENT2 && flag & STALE == 0 {
+                bases.push(queued.oid);
+                flag |= STALE;
+                flags.insert(queued.oid, flag);
+            }
+            for parent in nodes[&queued.oid].parents.clone() {
+                let parent_flag = flags.entry(parent).or_insert(0);
+                if *parent_flag & flag == flag {
+                    continue;
+                }
+                *parent_flag |= flag;
+                if let Entry::Vacant(entry) = nodes.entry(parent) {
+                    entry.insert(self.get(&parent)?);
+                }
+                queue.push(QueuedCommit::new(parent, &nodes[&parent]));
+            }
+        }
+
+        // A base found early can still be an ancestor of one found later
+        let mut redundant = HashSet::new();
+        for base in &bases {
+            for other in &bases {
+                if other != base && self.is_ancestor(*base, *other, &nodes)? {
+                    redundant.insert(*base);
+                }
+            }
+        }
+        bases.retain(|base| !redundant.contains(base));
+        bases.sort_by(|a, b| {
+            QueuedCommit::new(*b, &nodes[b]).cmp(&QueuedCommit::new(*a, &nodes[a]))
+        });
+        Ok(bases)
+    }
+
+    /// Returns `true` if `ancestor` is reachable from `descendant`.
+    ///
+    /// Commits with a lower generation than `ancestor` cannot reach it, so
+    /// the walk does not go past them.
+    fn is_ancestor(
+        &self,
+        ancestor: Oid,
+        descendant: Oid,
+        nodes: &HashMap<Oid, CommitNode>,
+    ) -> Result<bool> {
+        let cutoff = nodes[&ancestor].generation;
+        let mut seen = HashSet::new();
+        let mut stack = vec![descendant];
+        while let Some(oid) = stack.pop() {
+            if oid == ancestor {
+                return Ok(true);
+            }
+            if !seen.insert(oid) {
+                continue;
+            }
+            let node = match nodes.get(&oid) {
+                Some(node) => node.clone(),
+                None => self.get(&oid)?,
+            };
+            if cutoff != u64::MAX && node.generation < cutoff {
+                continue;
+            }
+            stack.extend(node.parents);
+        }
+        Ok(false)
+    }
+}
+
+/// A commit waiting to be visited by a merge-base walk.
+///
+/// Commits with higher generation numbers come first, then newer ones, as
+/// in Git.
+#[derive(PartialEq, Eq)]
+struct QueuedCommit {
+    generation: u64,
+    commit_time: i64,
+    oid: Oid,
+}
+
+impl QueuedCommit {
+    fn new(oid: Oid, node: &CommitNode) -> Self {
+        QueuedCommit {
+            generation: node.generation,
+            commit_time: node.commit_time,
+            oid,
+        }
+    }
+}
+
+impl PartialOrd for QueuedCommit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedCommit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.generation
+            .cmp(&other.generation)
+            .then(self.commit_time.cmp(&other.commit_time))
+            .then(other.oid.cmp(&self.oid))
+    }
+}
+
+impl Repository {
+    /// Finds the best common ancestor of two commits (`git merge-base`).
+    ///
+    /// When the commits have several best common ancestors, as after
+    /// criss-cross merges, the newest is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `one` - The full or abbreviated OID of the first commit.
+    /// * `two` - The full or abbreviated OID of the second commit.
+    ///
+    /// # Returns
+    ///
+    /// The merge base, or `None` if the commits share no history.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::ObjectNotFound` if a commit or one of its ancestors does
+    ///   not exist.
+    /// - `Error::TypeMismatch` if an OID does not name a commit.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::repository::Repository;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// match repo.merge_base("abc1234", "def5678").unwrap() {
+    ///     Some(base) => println!("merge base: {}", base),
+    ///     None => println!("no common history"),
+    /// }
+    /// ```
+    pub fn merge_base(&self, one: &str, two: &str) -> Result<Option<Oid>> {
+        let one = *self.commit(one)?.oid();
+        let two = *self.commit(two)?.oid();
+        let bases = CommitNodes::new(self.object_store()).merge_bases(one, two)?;
+        Ok(bases.into_iter().next())
+    }
+
+    /// Writes a commit-graph of every commit reachable from HEAD and the
+    /// refs (`git commit-graph write --reachable`).
+    ///
+    /// The graph is written to `.git/objects/info/commit-graph`, replacing
+    /// any commit-graph file or chain. Later history walks, such as
+    /// [`Repository::log`] and [`Repository::merge_base`], read parents
+    /// and commit times from it instead of parsing each commit. Commits
+    /// made after the graph is written are still found, just more slowly.
+    ///
+    /// # Returns
+    ///
+    /// The number of commits in the graph. Nothing is written if the
+    /// repository has no commits.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::ObjectNotFound` if a reachable commit is missing.
+    /// - `Error::InvalidObject` if a reachable commit or tag is corrupt.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::repository::Repository;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// let count = repo.write_commit_graph().unwrap();
+    /// println!("{} commits in the commit-graph", count);
+    /// ```
+    pub fn write_commit_graph(&self) -> Result<usize> {
+        self.require_on_disk("commit-graph")?;
+        let _lock = self.lock_for_write();
+        let store = self.object_store();
+        let refs = RefStore::new(self.git_dir());
+
+        let mut stack = Vec::new();
+        for name in std::iter::once("HEAD".to_string()).chain(refs.references()?) {
+            match refs.resolve_recursive(&name) {
+                Ok(resolved) => stack.extend(peel_to_commit(&store, resolved.oid)?),
+                // HEAD of a repository without commits points to an unborn branch
+                Err(Error::RefNotFound(_)) if name == "HEAD" => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut writer = CommitGraphWriter::new(self.object_format()).with_fsync(self.fsync());
+        let mut seen = HashSet::new();
+        while let Some(oid) = stack.pop() {
+            if !seen.insert(oid) {
+                continue;
+            }
+            let commit = store.read_commit(&oid)?;
+            stack.extend(commit.parents().iter().copied());
+            writer.add(&commit);
+        }
+
+        if !writer.is_empty() {
+            writer.write(self.git_dir().join("objects"))?;
+        }
+        Ok(writer.len())
+    }
+}
+
+/// Follows annotated tags to the commit they point to.
+///
+/// # Returns
+///
+/// The commit, or `None` if the tags point to another kind of object.
+fn peel_to_commit(store: &LooseObjectStore, oid: Oid) -> Result<Option<Oid>> {
+    let mut current = oid;
+    loop {
+        let raw = store.read(&current)?;
+        match raw.object_type {
+            ObjectType::Commit => return Ok(Some(current)),
+            ObjectType::Tag => current = *TagObject::parse(raw)?.object(),
+            _ => return Ok(None),
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! `.gitignore` handling.
+//!
+//! Patterns are collected from `core.excludesFile`, `.git/info/exclude` and
+//! the `.gitignore` files of the working tree, in increasing order of
+//! precedence. As in Git, the last matching pattern wins, and a file inside
+//! an ignored directory is always ignored.
+
+use std::path::{Path, PathBuf};
+
+use crate::config::load_config;
+use crate::infra::read_file;
+use crate::infra::wildmatch::{wildmatch, WM_PATHNAME};
+
+/// A single pattern from an ignore file.
+#[derive(Debug, Clone)]
+struct IgnorePattern {
+    /// Directory the pattern is relative to (empty or ending with `/`).
+    base: String,
+    /// The glob, without leading `!`, leading `/` or trailing `/`.
+    pattern: String,
+    /// Pattern started with `!` (re-includes matching paths).
+    negated: bool,
+    /// Pattern ended with `/` (matches directories only).
+    dir_only: bool,
+    /// Pattern has no `/`, so it matches the basename at any depth.
+    basename_only: bool,
+}
+
+impl IgnorePattern {
+    /// Parses one line of an ignore file.
+    fn parse(line: &str, base: &str) -> Option<Self> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let line = trim_trailing_spaces(line);
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, rest) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (
+                false,
+                line.strip_prefix('\\')
+                    .filter(|r| r.starts_with(['#', '!']))
+                    .unwrap_or(line),
+            ),
+        };
+
+        let (dir_only, rest) = match rest.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        if rest.is_empty() {
+            return None;
+        }
+
+        let basename_only = !rest.contains('/');
+        let pattern = rest.strip_prefix('/').unwrap_or(rest).to_string();
+
+        Some(IgnorePattern {
+            base: base.to_string(),
+            pattern,
+            negated,
+            dir_only,
+            basename_only,
+        })
+    }
+
+    /// Returns true if this pattern matches the given path.
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let Some(relative) = path.strip_prefix(self.base.as_str()) else {
+            return false;
+        };
+
+        if self.basename_only {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            wildmatch(self.pattern.as_bytes(), name.as_bytes(), WM_PATHNAME)
+        } else {
+            wildmatch(self.pattern.as_bytes(), relative.as_bytes(), WM_PATHNAME)
+        }
+    }
+}
+
+/// Removes unescaped trailing spaces.
+fn trim_trailing_spaces(line: &str) -> &str {
+    let mut end = line.len();
+    while end > 0 && line.as_bytes()[end - 1] == b' ' {
+        if end >= 2 && line.as_bytes()[end - 2] == b'\\' {
+            break;
+        }
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// The set of ignore rules that apply to a working tree.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IgnoreRules {
+    /// Patterns in increasing order of precedence.
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreRules {
+    /// Creates an empty rule set that ignores nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads all ignore rules for a working tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `work_dir` - The root of the working tree.
+    /// * `git_dir` - The `.git` directory.
+    /// * `files` - The working tree files (relative paths); every
+    ///   `.gitignore` among them is loaded.
+    pub fn load(work_dir: &Path, git_dir: &Path, files: &[PathBuf]) -> Self {
+        let mut rules = Self::new();
+
+        if let Some(path) = excludes_file(git_dir) {
+            rules.add_file(&path, "");
+        }
+        rules.add_file(&git_dir.join("info").join("exclude"), "");
+
+        // Shallower .gitignore files first, so deeper ones take precedence
+        let mut gitignores: Vec<String> = files
+            .iter()
+            .map(|p| path_to_slash(p))
+            .filter(|p| p == ".gitignore" || p.ends_with("/.gitignore"))
+            .collect();
+        gitignores.sort_by_key(|p| p.matches('/').count());
+        for gitignore in gitignores {
+            let base = &gitignore[..gitignore.len() - ".gitignore".len()];
+            rules.add_file(&work_dir.join(&gitignore), base);
+        }
+
+        rules
+    }
+
+    /// Adds patterns from the contents of an ignore file.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The file contents.
+    /// * `base` - The directory the file lives in (empty or ending with `/`).
+    pub fn add_patterns(&mut self, content: &str, base: &str) {
+        self.patterns.extend(
+            content
+                .lines()
+                .filter_map(|line| IgnorePattern::parse(line, base)),
+        );
+    }
+
+    /// Adds patterns from an ignore file, if it exists and is readable.
+    fn add_file(&mut self, path: &Path, base: &str) {
+        if let Ok(data) = read_file(path) {
+            self.add_patterns(&String::from_utf8_lossy(&data), base);
+        }
+    }
+
+    /// Returns true if the path is ignored.
+    ///
+    /// A path is ignored if any of its parent directories is ignored, or if
+    /// the last pattern matching the path itself is not negated.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path relative to the repository root.
+    /// * `is_dir` - Whether the path is a directory.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.ignored_ancestor(path).is_some() || self.matches(&path_to_slash(path), is_dir)
+    }
+
+    /// Returns the outermost ignored parent directory of a path, if any.
+    pub fn ignored_ancestor(&self, path: &Path) -> Option<PathBuf> {
+        if self.patterns.is_empty() {
+            return None;
+        }
+
+        let path = path_to_slash(path);
+        path.match_indices('/')
+            .map(|(i, _)| &path[..i])
+            .find(|dir| self.matches(dir, true))
+            .map(PathBuf::from)
+    }
+
+    /// Returns the verdict of the last pattern matching the path itself,
+    /// without looking at its parent directories.
+    ///
+    /// # Returns
+    ///
+    /// `Some(true)` if the last matching pattern is not negated,
+    /// `Some(false)` if it is negated, and `None` if no pattern matches.
+    pub fn last_match(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        self.last_match_str(&path_to_slash(path), is_dir)
+    }
+
+    /// Applies the patterns to a single path (last match wins).
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        self.last_match_str(path, is_dir).unwrap_or(false)
+    }
+
+    fn last_match_str(&self, path: &str, is_dir: bool) -> Option<bool> {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|p| p.matches(path, is_dir))
+            .map(|p| !p.negated)
+    }
+}
+
+/// Returns the global excludes file (`core.excludesFile` or the XDG default).
+fn excludes_file(git_dir: &Path) -> Option<PathBuf> {
+    let configured = load_config(git_dir)
+        .ok()
+        .and_then(|config| config.get("core", "excludesFile").map(str::to_string));
+
+    match configured {
+        Some(path) => Some(match path.strip_prefix("~/") {
+            Some(rest) => home_dir()?.join(rest),
+            None => PathBuf::from(path),
+        }),
+        None => match std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+            Some(xdg) => Some(PathBuf::from(xdg).join("git").join("ignore")),
+            None => Some(home_dir()?.join(".config").join("git").join("ignore")),
+        },
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// Converts a relative path to a `/`-separated string.
+pub(crate) fn path_to_slash(path: &Path) -> String {
+    let s = path.to_string_lossy();
+    if cfg!(windows) {
+        s.replace('\\', "/")
+    } else {
+        s.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn rules(content: &str) -> IgnoreRules {
+        let mut rules = IgnoreRules::new();
+        rules.add_patterns(content, "");
+        rules
+    }
+
+    // IG-001: Basename patterns match at any depth
+    #[test]
+    fn test_basename_pattern() {
+        let rules = rules("*.log\n");
+        assert!(rules.is_ignored(Path::new("debug.log"), false));
+        assert!(rules.is_ignored(Path::new("a/b/debug.log"), false));
+        assert!(!rules.is_ignored(Path::new("debug.txt"), false));
+    }
+
+    // IG-002: Patterns with a slash are anchored
+    #[test]
+    fn test_anchored_pattern() {
+        let rules = rules("/build\ndoc/*.html\n");
+        assert!(rules.is_ignored(Path::new("build"), false));
+        assert!(!rules.is_ignored(Path::new("src/build"), false));
+        assert!(rules.is_ignored(Path::new("doc/index.html"), false));
+        assert!(!rules.is_ignored(Path::new("doc/api/index.html"), false));
+    }
+
+    // IG-003: Directory-only patterns and files inside ignored directories
+    #[test]
+    fn test_directory_pattern() {
+        let rules = rules("target/\n");
+        assert!(rules.is_ignored(Path::new("target"), true));
+        assert!(!rules.is_ignored(Path::new("target"), false));
+        assert!(rules.is_ignored(Path::new("target/debug/app"), false));
+        assert_eq!(
+            rules.ignored_ancestor(Path::new("target/debug/app")),
+            Some(PathBuf::from("target"))
+        );
+    }
+
+    // IG-004: Negation re-includes, last match wins
+    #[test]
+    fn test_negation() {
+        let rules = rules("*.log\n!keep.log\n");
+        assert!(rules.is_ignored(Path::new("a.log"), false));
+        assert!(!rules.is_ignored(Path::new("keep.log"), false));
+
+        // Cannot re-include a file if its parent directory is excluded
+        let rules = super::tests::rules("out/\n!out/keep.txt\n");
+        assert!(rules.is_ignored(Path::new("out/keep.txt"), false));
+    }
+
+    // IG-005: Comments, blank lines, escapes and trailing spaces
+    #[test]
+    fn test_parse_special_lines() {
+        let rules = rules("# comment\n\n\\#hash\n\\!bang\ntrailing   \n");
+        assert!(!rules.is_ignored(Path::new("# comment"), false));
+        assert!(rules.is_ignored(Path::new("#hash"), false));
+        assert!(rules.is_ignored(Path::new("!bang"), false));
+        assert!(rules.is_ignored(Path::new("trailing"), false));
+    }
+
+    // IG-006: Nested .gitignore files and info/exclude
+    #[test]
+    fn test_load_nested() {
+        let temp = TempDir::new().unwrap();
+        let work_dir = temp.path();
+        let git_dir = work_dir.join(".git");
+        fs::create_dir_all(git_dir.join("info")).unwrap();
+        fs::write(git_dir.join("info/exclude"), "*.tmp\n").unwrap();
+        fs::write(work_dir.join(".gitignore"), "*.log\n").unwrap();
+        fs::create_dir_all(work_dir.join("sub")).unwrap();
+        fs::write(
+            work_dir.join("sub/.gitignore"),
+            "!important.log\nlocal.txt\n",
+        )
+        .unwrap();
+
+        let files = vec![PathBuf::from(".gitignore"), PathBuf::from("sub/.gitignore")];
+        let rules = IgnoreRules::load(work_dir, &git_dir, &files);
+
+        assert!(rules.is_ignored(Path::new("x.tmp"), false));
+        assert!(rules.is_ignored(Path::new("a.log"), false));
+        assert!(rules.is_ignored(Path::new("sub/a.log"), false));
+        assert!(!rules.is_ignored(Path::new("sub/important.log"), false));
+        assert!(rules.is_ignored(Path::new("sub/local.txt"), false));
+        assert!(!rules.is_ignored(Path::new("local.txt"), false));
+    }
+}
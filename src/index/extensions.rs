@@ -0,0 +1,586 @@
+//! Git index extensions.
+//!
+//! After the entries, an index file may carry extensions, each stored as a
+//! 4-byte signature, a 4-byte big-endian size and the data. Extensions
+//! whose signature starts with an uppercase letter are optional and may be
+//! ignored by readers that do not understand them; others are required.
+//!
+//! This module implements the extensions zerogit understands:
+//!
+//! - `TREE`: the cache-tree, caching the tree object ID of each directory
+//! - `REUC`: resolve-undo, recording conflict stages that were resolved
+//! - `UNTR`: the untracked cache (parsed header, data kept verbatim)
+//! - `EOIE` / `IEOT`: end-of-index-entry and index-entry-offset tables,
+//!   which are regenerated on write
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::infra::{path, ObjectFormat};
+use crate::objects::tree::FileMode;
+use crate::objects::Oid;
+
+/// Signature of the cache-tree extension.
+pub(crate) const SIG_TREE: &[u8; 4] = b"TREE";
+
+/// Signature of the resolve-undo extension.
+pub(crate) const SIG_REUC: &[u8; 4] = b"REUC";
+
+/// Signature of the untracked cache extension.
+pub(crate) const SIG_UNTR: &[u8; 4] = b"UNTR";
+
+/// Signature of the end-of-index-entry extension.
+pub(crate) const SIG_EOIE: &[u8; 4] = b"EOIE";
+
+/// Signature of the index-entry-offset-table extension.
+pub(crate) const SIG_IEOT: &[u8; 4] = b"IEOT";
+
+/// A node of the cache-tree extension.
+///
+/// Each node describes one directory: how many index entries it covers
+/// and, if still valid, the ID of the tree object built from them. Nodes
+/// are invalidated when an entry below them changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheTree {
+    /// The directory name (empty for the root), as raw bytes.
+    name: Vec<u8>,
+    /// The number of index entries covered, or -1 if invalid.
+    entry_count: i32,
+    /// The tree object ID (only for valid nodes).
+    oid: Option<Oid>,
+    /// The subdirectory nodes, ordered by name length, then name.
+    children: Vec<CacheTree>,
+}
+
+impl CacheTree {
+    /// Creates an invalid node with the given name and no children.
+    pub(crate) fn new(name: &[u8]) -> Self {
+        CacheTree {
+            name: name.to_vec(),
+            entry_count: -1,
+            oid: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Returns the directory name (empty for the root).
+    ///
+    /// Names that are not valid UTF-8 are converted lossily; use
+    /// [`name_bytes`](CacheTree::name_bytes) for the exact name.
+    pub fn name(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.name)
+    }
+
+    /// Returns the directory name as stored in the index.
+    pub fn name_bytes(&self) -> &[u8] {
+        &self.name
+    }
+
+    /// Returns true if the cached tree ID is up to date.
+    pub fn is_valid(&self) -> bool {
+        self.entry_count >= 0 && self.oid.is_some()
+    }
+
+    /// Returns the number of index entries covered, if valid.
+    pub fn entry_count(&self) -> Option<usize> {
+        self.is_valid().then_some(self.entry_count as usize)
+    }
+
+    /// Returns the tree object ID, if valid.
+    pub fn oid(&self) -> Option<&Oid> {
+        self.oid.as_ref().filter(|_| self.entry_count >= 0)
+    }
+
+    /// Returns the subdirectory nodes.
+    pub fn children(&self) -> &[CacheTree] {
+        &self.children
+    }
+
+    /// Finds the node of a directory, relative to this node.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The directory path (empty for this node).
+    pub fn find(&self, path: &Path) -> Option<&CacheTree> {
+        let mut node = self;
+        for component in path.iter() {
+            let name = path::to_bytes(Path::new(component));
+            node = node.children.iter().find(|c| c.name == *name)?;
+        }
+        Some(node)
+    }
+
+    /// Marks this node valid with the given tree ID.
+    pub(crate) fn set_valid(&mut self, entry_count: usize, oid: Oid) {
+        self.entry_count = entry_count as i32;
+        self.oid = Some(oid);
+    }
+
+    /// Removes and returns the child with the given name, if any.
+    pub(crate) fn take_child(&mut self, name: &[u8]) -> Option<CacheTree> {
+        let pos = self.children.iter().position(|c| c.name == name)?;
+        Some(self.children.remove(pos))
+    }
+
+    /// Replaces the children, keeping Git's order.
+    pub(crate) fn set_children(&mut self, mut children: Vec<CacheTree>) {
+        children.sort_by(|a, b| (a.name.len(), &a.name).cmp(&(b.name.len(), &b.name)));
+        self.children = children;
+    }
+
+    /// Invalidates the nodes on the way to a changed path.
+    ///
+    /// Every directory containing the path is invalidated. If the path
+    /// itself names a cached directory (e.g. a directory was replaced by a
+    /// file), that subtree is dropped.
+    pub(crate) fn invalidate(&mut self, path: &Path) {
+        self.entry_count = -1;
+        let mut components = path.iter().peekable();
+        let mut node = self;
+        while let Some(component) = components.next() {
+            let name = path::to_bytes(Path::new(component));
+            if components.peek().is_none() {
+                node.take_child(&name);
+                return;
+            }
+            match node.children.iter_mut().find(|c| c.name == *name) {
+                Some(child) => {
+                    child.entry_count = -1;
+                    node = child;
+                }
+                None => return,
+            }
+        }
+    }
+
+    /// Parses the data of a `TREE` extension.
+    pub(crate) fn parse(data: &[u8], version: u32, format: ObjectFormat) -> Result<Self> {
+        let mut pos = 0;
+        let tree = Self::parse_node(data, &mut pos, version, format)?;
+        if pos != data.len() {
+            return Err(extension_error(version, "TREE", "trailing data"));
+        }
+        Ok(tree)
+    }
+
+    /// Parses one node and its children.
+    fn parse_node(
+        data: &[u8],
+        pos: &mut usize,
+        version: u32,
+        format: ObjectFormat,
+    ) -> Result<Self> {
+        let err = |reason| extension_error(version, "TREE", reason);
+
+        let name = read_until(data, pos, 0)
+            .ok_or_else(|| err("truncated name"))?
+            .to_vec();
+        let counts = read_until(data, pos, b'\n').ok_or_else(|| err("truncated counts"))?;
+        let counts = std::str::from_utf8(counts).map_err(|_| err("invalid counts"))?;
+        let (entry_count, subtree_count) = counts
+            .split_once(' ')
+            .and_then(|(e, s)| Some((e.parse::<i32>().ok()?, s.parse::<usize>().ok()?)))
+            .ok_or_else(|| err("invalid counts"))?;
+
+        let oid = if entry_count >= 0 {
+            let bytes = data
+                .get(*pos..*pos + format.size())
+                .ok_or_else(|| err("truncated object id"))?;
+            *pos += format.size();
+            Some(Oid::from_slice(bytes)?)
+        } else {
+            None
+        };
+
+        let mut children = Vec::new();
+        for _ in 0..subtree_count {
+            children.push(Self::parse_node(data, pos, version, format)?);
+        }
+
+        Ok(CacheTree {
+            name,
+            entry_count,
+            oid,
+            children,
+        })
+    }
+
+    /// Serializes this node and its children as `TREE` extension data.
+    pub(crate) fn write(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.name);
+        buffer.push(0);
+        match self.oid() {
+            Some(oid) => {
+                let counts = format!("{} {}\n", self.entry_count, self.children.len());
+                buffer.extend_from_slice(counts.as_bytes());
+                buffer.extend_from_slice(oid.as_bytes());
+            }
+            None => {
+                let counts = format!("-1 {}\n", self.children.len());
+                buffer.extend_from_slice(counts.as_bytes());
+            }
+        }
+        for child in &self.children {
+            child.write(buffer);
+        }
+    }
+}
+
+/// A resolved conflict recorded by the resolve-undo extension.
+///
+/// When a conflicted path is resolved by staging it, the conflict stages
+/// are kept here so that the conflict can be recreated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveUndoEntry {
+    /// The path that was conflicted.
+    path: PathBuf,
+    /// The mode and object ID of stages 1 (base), 2 (ours) and 3 (theirs).
+    stages: [Option<(FileMode, Oid)>; 3],
+}
+
+impl ResolveUndoEntry {
+    /// Creates a resolve-undo entry.
+    pub(crate) fn new(path: PathBuf, stages: [Option<(FileMode, Oid)>; 3]) -> Self {
+        ResolveUndoEntry { path, stages }
+    }
+
+    /// Returns the path that was conflicted.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the mode and object ID of a conflict stage.
+    ///
+    /// # Arguments
+    ///
+    /// * `stage` - The stage number: 1 (base), 2 (ours) or 3 (theirs).
+    pub fn stage(&self, stage: u8) -> Option<(FileMode, Oid)> {
+        match stage {
+            1..=3 => self.stages[stage as usize - 1],
+            _ => None,
+        }
+    }
+
+    /// Parses the data of a `REUC` extension.
+    pub(crate) fn parse_all(data: &[u8], version: u32, format: ObjectFormat) -> Result<Vec<Self>> {
+        let err = |reason| extension_error(version, "REUC", reason);
+        let mut entries = Vec::new();
+        let mut pos = 0;
+
+        while pos < data.len() {
+            let path = read_until(data, &mut pos, 0).ok_or_else(|| err("truncated path"))?;
+            let path = path::from_bytes(path);
+
+            let mut modes = [None; 3];
+            for mode in &mut modes {
+                let text = read_until(data, &mut pos, 0).ok_or_else(|| err("truncated mode"))?;
+                if text != b"0" {
+                    let text = std::str::from_utf8(text).map_err(|_| err("invalid mode"))?;
+                    *mode = Some(FileMode::from_octal(text).ok_or_else(|| err("invalid mode"))?);
+                }
+            }
+
+            let mut stages = [None; 3];
+            for (stage, mode) in stages.iter_mut().zip(modes) {
+                let Some(mode) = mode else {
+                    continue;
+                };
+                let bytes = data
+                    .get(pos..pos + format.size())
+                    .ok_or_else(|| err("truncated object id"))?;
+                pos += format.size();
+                *stage = Some((mode, Oid::from_slice(bytes)?));
+            }
+
+            entries.push(ResolveUndoEntry { path, stages });
+        }
+
+        Ok(entries)
+    }
+
+    /// Serializes this entry as `REUC` extension data.
+    pub(crate) fn write(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&path::to_bytes(&self.path));
+        buffer.push(0);
+        for stage in &self.stages {
+            let mode = stage.map(|(mode, _)| mode.as_octal()).unwrap_or("0");
+            buffer.extend_from_slice(mode.as_bytes());
+            buffer.push(0);
+        }
+        for (_, oid) in self.stages.iter().flatten() {
+            buffer.extend_from_slice(oid.as_bytes());
+        }
+    }
+}
+
+/// The untracked cache extension.
+///
+/// Git uses this to avoid rescanning unchanged directories for untracked
+/// files. zerogit exposes its header and writes the data back unchanged;
+/// the cache is dropped whenever index entries are added or removed, since
+/// Git would otherwise trust stale directory listings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UntrackedCache {
+    /// Identifies the environment the cache was built in.
+    ident: String,
+    /// Flags controlling how directories were scanned.
+    dir_flags: u32,
+    /// The name of the per-directory exclude file (usually `.gitignore`).
+    exclude_per_dir: String,
+    /// The raw extension data.
+    data: Vec<u8>,
+}
+
+impl UntrackedCache {
+    /// Returns the identification string (location and system).
+    pub fn ident(&self) -> &str {
+        &self.ident
+    }
+
+    /// Returns the directory scanning flags.
+    pub fn dir_flags(&self) -> u32 {
+        self.dir_flags
+    }
+
+    /// Returns the name of the per-directory exclude file.
+    pub fn exclude_per_dir(&self) -> &str {
+        &self.exclude_per_dir
+    }
+
+    /// Returns the raw extension data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Parses the data of an `UNTR` extension.
+    pub(crate) fn parse(data: &[u8], version: u32, format: ObjectFormat) -> Result<Self> {
+        let err = || extension_error(version, "UNTR", "truncated header");
+        let mut pos = 0;
+
+        let ident_len = decode_varint(data, &mut pos).ok_or_else(err)?;
+        let ident = data.get(pos..pos + ident_len).ok_or_else(err)?;
+        let ident = String::from_utf8_lossy(ident)
+            .trim_end_matches('\0')
+            .to_string();
+        pos += ident_len;
+
+        // Two stat data blocks (info/exclude and core.excludesFile)
+        pos += 2 * 36;
+        let flags = data.get(pos..pos + 4).ok_or_else(err)?;
+        let dir_flags = u32::from_be_bytes(flags.try_into().expect("4 bytes"));
+        pos += 4;
+
+        // Two object IDs (info/exclude and core.excludesFile)
+        pos += 2 * format.size();
+        let exclude_per_dir = read_until(data, &mut pos, 0).ok_or_else(err)?;
+
+        Ok(UntrackedCache {
+            ident,
+            dir_flags,
+            exclude_per_dir: String::from_utf8_lossy(exclude_per_dir).into_owned(),
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// An optional extension zerogit does not understand, kept verbatim.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawExtension {
+    /// The 4-byte signature.
+    signature: [u8; 4],
+    /// The extension data.
+    data: Vec<u8>,
+}
+
+impl RawExtension {
+    /// Creates a raw extension.
+    pub(crate) fn new(signature: [u8; 4], data: Vec<u8>) -> Self {
+        RawExtension { signature, data }
+    }
+
+    /// Returns the signature.
+    pub fn signature(&self) -> &[u8; 4] {
+        &self.signature
+    }
+
+    /// Returns the extension data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Returns true if an extension may be ignored by readers that do not
+/// understand it.
+pub(crate) fn is_optional(signature: &[u8; 4]) -> bool {
+    signature[0].is_ascii_uppercase()
+}
+
+/// Reads bytes up to (and consumes) the delimiter.
+fn read_until<'a>(data: &'a [u8], pos: &mut usize, delimiter: u8) -> Option<&'a [u8]> {
+    let rest = data.get(*pos..)?;
+    let len = rest.iter().position(|&b| b == delimiter)?;
+    *pos += len + 1;
+    Some(&rest[..len])
+}
+
+/// Decodes Git's variable-length integer encoding (as in `varint.c`).
+pub(super) fn decode_varint(data: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut byte = *data.get(*pos)?;
+    *pos += 1;
+    let mut value = (byte & 0x7f) as usize;
+    while byte & 0x80 != 0 {
+        byte = *data.get(*pos)?;
+        *pos += 1;
+        value = ((value + 1) << 7) | (byte & 0x7f) as usize;
+    }
+    Some(value)
+}
+
+/// Encodes a value in Git's variable-length integer encoding.
+pub(super) fn encode_varint(mut value: usize, buffer: &mut Vec<u8>) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value != 0 {
+        value -= 1;
+        bytes.push(0x80 | (value & 0x7f) as u8);
+        value >>= 7;
+    }
+    buffer.extend(bytes.iter().rev());
+}
+
+/// Creates an InvalidIndex error for a malformed extension.
+fn extension_error(version: u32, signature: &str, reason: &str) -> Error {
+    Error::InvalidIndex {
+        version,
+        reason: format!("invalid {} extension: {}", signature, reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oid(byte: u8) -> Oid {
+        Oid::from_bytes([byte; 20])
+    }
+
+    // IX-001: Cache-tree round trip
+    #[test]
+    fn test_cache_tree_roundtrip() {
+        let mut root = CacheTree::new(b"");
+        root.set_valid(3, oid(1));
+        let mut src = CacheTree::new(b"src");
+        src.set_valid(2, oid(2));
+        let docs = CacheTree::new(b"docs");
+        root.set_children(vec![src, docs]);
+
+        let mut data = Vec::new();
+        root.write(&mut data);
+        let parsed = CacheTree::parse(&data, 2, ObjectFormat::Sha1).unwrap();
+
+        assert_eq!(parsed, root);
+        assert_eq!(parsed.children()[0].name(), "src");
+        assert_eq!(parsed.find(Path::new("src")).unwrap().oid(), Some(&oid(2)));
+        assert_eq!(parsed.find(Path::new("docs")).unwrap().oid(), None);
+        assert!(data.starts_with(b"\x003 2\n"));
+    }
+
+    // IX-002: Invalidation marks ancestors and drops replaced subtrees
+    #[test]
+    fn test_cache_tree_invalidate() {
+        let mut root = CacheTree::new(b"");
+        root.set_valid(2, oid(1));
+        let mut a = CacheTree::new(b"a");
+        a.set_valid(1, oid(2));
+        let mut b = CacheTree::new(b"b");
+        b.set_valid(1, oid(3));
+        root.set_children(vec![a, b]);
+
+        root.invalidate(Path::new("a/file.txt"));
+        assert!(!root.is_valid());
+        assert!(!root.find(Path::new("a")).unwrap().is_valid());
+        assert!(root.find(Path::new("b")).unwrap().is_valid());
+
+        root.invalidate(Path::new("b"));
+        assert!(root.find(Path::new("b")).is_none());
+    }
+
+    // IX-003: Resolve-undo round trip
+    #[test]
+    fn test_resolve_undo_roundtrip() {
+        let entries = vec![
+            ResolveUndoEntry::new(
+                PathBuf::from("file.txt"),
+                [
+                    Some((FileMode::Regular, oid(1))),
+                    Some((FileMode::Executable, oid(2))),
+                    Some((FileMode::Regular, oid(3))),
+                ],
+            ),
+            ResolveUndoEntry::new(
+                PathBuf::from("added.txt"),
+                [None, Some((FileMode::Regular, oid(4))), None],
+            ),
+        ];
+
+        let mut data = Vec::new();
+        for entry in &entries {
+            entry.write(&mut data);
+        }
+        assert!(data.starts_with(b"file.txt\x00100644\x00100755\x00100644\x00"));
+
+        let parsed = ResolveUndoEntry::parse_all(&data, 2, ObjectFormat::Sha1).unwrap();
+        assert_eq!(parsed, entries);
+        assert_eq!(parsed[1].stage(1), None);
+        assert_eq!(parsed[1].stage(2), Some((FileMode::Regular, oid(4))));
+    }
+
+    // IX-004: Untracked cache header is parsed
+    #[test]
+    fn test_untracked_cache_header() {
+        let mut data = Vec::new();
+        let ident = b"Location /repo, system Linux\0";
+        data.push(ident.len() as u8);
+        data.extend_from_slice(ident);
+        data.extend_from_slice(&[0u8; 72]);
+        data.extend_from_slice(&6u32.to_be_bytes());
+        data.extend_from_slice(&[0u8; 40]);
+        data.extend_from_slice(b".gitignore\0");
+        data.extend_from_slice(&[0u8; 3]);
+
+        let cache = UntrackedCache::parse(&data, 2, ObjectFormat::Sha1).unwrap();
+        assert_eq!(cache.ident(), "Location /repo, system Linux");
+        assert_eq!(cache.dir_flags(), 6);
+        assert_eq!(cache.exclude_per_dir(), ".gitignore");
+        assert_eq!(cache.data(), data.as_slice());
+    }
+
+    // IX-005: Varint decoding matches Git's encoding
+    #[test]
+    fn test_decode_varint() {
+        let mut pos = 0;
+        assert_eq!(decode_varint(&[0x7f], &mut pos), Some(127));
+        let mut pos = 0;
+        assert_eq!(decode_varint(&[0x80, 0x00], &mut pos), Some(128));
+        assert_eq!(pos, 2);
+        let mut pos = 0;
+        assert_eq!(decode_varint(&[0x80], &mut pos), None);
+    }
+
+    // IX-006: Varint encoding round trips
+    #[test]
+    fn test_encode_varint() {
+        let mut buffer = Vec::new();
+        encode_varint(128, &mut buffer);
+        assert_eq!(buffer, [0x80, 0x00]);
+
+        for value in [0, 1, 127, 128, 255, 16511, 16512, 1 << 30] {
+            let mut buffer = Vec::new();
+            encode_varint(value, &mut buffer);
+            let mut pos = 0;
+            assert_eq!(decode_varint(&buffer, &mut pos), Some(value));
+            assert_eq!(pos, buffer.len());
+        }
+    }
+}
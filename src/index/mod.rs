@@ -3,17 +3,34 @@
 //! The index file (`.git/index`) is a binary file that acts as a staging
 //! area between the working tree and the repository.
 
+mod extensions;
 mod reader;
+mod split;
 mod writer;
 
+use std::borrow::Cow;
+use std::fs::Metadata;
 use std::path::{Path, PathBuf};
 
+use crate::infra::{path, ObjectFormat};
 use crate::objects::tree::FileMode;
 use crate::objects::Oid;
 
-pub use reader::parse;
+pub use extensions::{CacheTree, RawExtension, ResolveUndoEntry, UntrackedCache};
+pub use reader::{parse, parse_with_format};
 pub use writer::write;
 
+/// Entry flag: the entry is assumed unchanged (`git update-index --assume-unchanged`).
+const FLAG_ASSUME_VALID: u16 = 0x8000;
+/// Entry flag: a second 16-bit word of extended flags follows (v3+).
+const FLAG_EXTENDED: u16 = 0x4000;
+/// Entry flag bits holding the name length.
+const FLAG_NAME_MASK: u16 = 0x0FFF;
+/// Extended flag: the entry is excluded from the working tree (sparse checkout).
+const EXTENDED_SKIP_WORKTREE: u16 = 0x4000;
+/// Extended flag: the entry was added with `git add --intent-to-add`.
+const EXTENDED_INTENT_TO_ADD: u16 = 0x2000;
+
 /// A Git index (staging area).
 ///
 /// The index contains information about the files that will be included
@@ -22,22 +39,62 @@ pub use writer::write;
 pub struct Index {
     /// Index file format version (2, 3, or 4).
     version: u32,
+    /// The object format of the object IDs and the checksum.
+    format: ObjectFormat,
     /// The entries in the index.
     entries: Vec<IndexEntry>,
+    /// The modification time of the index file (seconds, nanoseconds).
+    ///
+    /// Used to detect "racily clean" entries whose cached stat data
+    /// cannot be trusted.
+    timestamp: Option<(u64, u32)>,
+    /// The cache-tree extension (`TREE`).
+    cache_tree: Option<CacheTree>,
+    /// The resolve-undo extension (`REUC`).
+    resolve_undo: Vec<ResolveUndoEntry>,
+    /// The untracked cache extension (`UNTR`).
+    untracked_cache: Option<UntrackedCache>,
+    /// Whether to write the end-of-index-entry extension (`EOIE`).
+    end_of_index: bool,
+    /// Whether to write the index-entry-offset-table extension (`IEOT`).
+    entry_offsets: bool,
+    /// Optional extensions that are not understood, kept verbatim.
+    unknown_extensions: Vec<RawExtension>,
+    /// The split index state (`link` extension).
+    split: Option<split::SplitIndex>,
 }
 
 impl Index {
     /// Creates a new empty index with the given version.
     pub fn empty(version: u32) -> Self {
+        Self::new(version, Vec::new())
+    }
+
+    /// Creates a new Index from parsed data.
+    pub(crate) fn new(version: u32, entries: Vec<IndexEntry>) -> Self {
         Self {
             version,
-            entries: Vec::new(),
+            format: ObjectFormat::Sha1,
+            entries,
+            timestamp: None,
+            cache_tree: None,
+            resolve_undo: Vec::new(),
+            untracked_cache: None,
+            end_of_index: false,
+            entry_offsets: false,
+            unknown_extensions: Vec::new(),
+            split: None,
         }
     }
 
-    /// Creates a new Index from parsed data.
-    pub(crate) fn new(version: u32, entries: Vec<IndexEntry>) -> Self {
-        Self { version, entries }
+    /// Returns the modification time of the index file this index was read from.
+    pub(crate) fn timestamp(&self) -> Option<(u64, u32)> {
+        self.timestamp
+    }
+
+    /// Records the modification time of the index file.
+    pub(crate) fn set_timestamp(&mut self, metadata: &Metadata) {
+        self.timestamp = Some(file_mtime(metadata));
     }
 
     /// Returns the index format version.
@@ -47,6 +104,28 @@ impl Index {
         self.version
     }
 
+    /// Sets the format version the index is written in.
+    ///
+    /// Versions outside 2-4 are ignored.
+    pub(crate) fn set_version(&mut self, version: u32) {
+        if (2..=4).contains(&version) {
+            self.version = version;
+        }
+    }
+
+    /// Returns the object format of the repository the index belongs to.
+    ///
+    /// It sets the size of the object IDs in the entries and extensions,
+    /// and the hash function of the trailing checksum.
+    pub fn object_format(&self) -> ObjectFormat {
+        self.format
+    }
+
+    /// Sets the object format the index is written in.
+    pub(crate) fn set_object_format(&mut self, format: ObjectFormat) {
+        self.format = format;
+    }
+
     /// Returns the number of entries in the index.
     pub fn len(&self) -> usize {
         self.entries.len()
@@ -62,6 +141,49 @@ impl Index {
         &self.entries
     }
 
+    /// Returns the cache-tree, if the index has one.
+    ///
+    /// The cache-tree records the tree object ID of each directory as of
+    /// the last time it was written, so that unchanged directories need
+    /// not be rehashed when building a commit.
+    pub fn cache_tree(&self) -> Option<&CacheTree> {
+        self.cache_tree.as_ref()
+    }
+
+    /// Returns a mutable reference to the cache-tree, creating an empty
+    /// (invalid) one if needed.
+    pub(crate) fn cache_tree_mut(&mut self) -> &mut CacheTree {
+        self.cache_tree.get_or_insert_with(|| CacheTree::new(b""))
+    }
+
+    /// Returns the conflicts that were resolved by staging a path.
+    pub fn resolve_undo(&self) -> &[ResolveUndoEntry] {
+        &self.resolve_undo
+    }
+
+    /// Returns the untracked cache, if the index has one.
+    pub fn untracked_cache(&self) -> Option<&UntrackedCache> {
+        self.untracked_cache.as_ref()
+    }
+
+    /// Returns the optional extensions that zerogit does not understand.
+    ///
+    /// These are written back unchanged.
+    pub fn unknown_extensions(&self) -> &[RawExtension] {
+        &self.unknown_extensions
+    }
+
+    /// Records that the entry at a path changed.
+    ///
+    /// Invalidates the cache-tree along the path and drops the untracked
+    /// cache, whose directory listings may now be stale.
+    fn invalidate_path(&mut self, path: &Path) {
+        if let Some(cache_tree) = &mut self.cache_tree {
+            cache_tree.invalidate(path);
+        }
+        self.untracked_cache = None;
+    }
+
     /// Finds an entry by path.
     ///
     /// # Arguments
@@ -80,17 +202,57 @@ impl Index {
         self.entries.iter()
     }
 
+    /// Returns a mutable iterator over the entries.
+    ///
+    /// Callers must not change entry paths, as that would break the sort order.
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut IndexEntry> {
+        self.entries.iter_mut()
+    }
+
     /// Adds or updates an entry in the index.
     ///
-    /// If an entry with the same path already exists, it is replaced.
-    /// Entries are kept sorted by path.
+    /// If an entry with the same path and stage already exists, it is
+    /// replaced. Adding a stage 0 entry for a conflicted path resolves the
+    /// conflict: the conflict stages are removed and recorded in the
+    /// resolve-undo extension. Entries are kept sorted by path and stage.
     ///
     /// # Arguments
     ///
     /// * `entry` - The entry to add or update.
     pub fn add(&mut self, entry: IndexEntry) {
+        self.invalidate_path(&entry.path);
+
+        if entry.stage == 0 {
+            let conflicts: Vec<IndexEntry> = self
+                .entries
+                .iter()
+                .filter(|e| e.path == entry.path && e.stage != 0)
+                .cloned()
+                .collect();
+            if !conflicts.is_empty() {
+                let mut stages = [None; 3];
+                for conflict in &conflicts {
+                    stages[conflict.stage as usize - 1] = Some((conflict.mode, conflict.oid));
+                }
+                self.resolve_undo.retain(|r| r.path() != entry.path);
+                self.resolve_undo
+                    .push(ResolveUndoEntry::new(entry.path.clone(), stages));
+                self.entries
+                    .retain(|e| e.path != entry.path || e.stage == 0);
+            }
+        }
+
+        self.insert_entry(entry);
+    }
+
+    /// Inserts an entry at its sorted position, replacing any entry with
+    /// the same path and stage.
+    fn insert_entry(&mut self, entry: IndexEntry) {
         // Find or insert position
-        match self.entries.binary_search_by(|e| e.path.cmp(&entry.path)) {
+        match self
+            .entries
+            .binary_search_by(|e| (&e.path, e.stage).cmp(&(&entry.path, entry.stage)))
+        {
             Ok(pos) => {
                 // Replace existing entry
                 self.entries[pos] = entry;
@@ -113,6 +275,7 @@ impl Index {
     /// `true` if an entry was removed, `false` if no entry was found.
     pub fn remove(&mut self, path: &Path) -> bool {
         if let Some(pos) = self.entries.iter().position(|e| e.path == path) {
+            self.invalidate_path(path);
             self.entries.remove(pos);
             true
         } else {
@@ -123,6 +286,27 @@ impl Index {
     /// Clears all entries from the index.
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.cache_tree = None;
+        self.untracked_cache = None;
+    }
+
+    /// Returns an empty index to be refilled in place of this one.
+    ///
+    /// The version, object format, split-index link and the extensions
+    /// that do not describe the entries are kept, so that the index is
+    /// written back in the same shape. The cache-tree, resolve-undo and
+    /// untracked cache are dropped, as is the file timestamp: the new
+    /// entries were not read from the index file.
+    pub(crate) fn without_entries(&self) -> Self {
+        Self {
+            version: self.version,
+            format: self.format,
+            end_of_index: self.end_of_index,
+            entry_offsets: self.entry_offsets,
+            unknown_extensions: self.unknown_extensions.clone(),
+            split: self.split.clone(),
+            ..Self::empty(self.version)
+        }
     }
 }
 
@@ -133,8 +317,12 @@ impl Index {
 pub struct IndexEntry {
     /// The ctime (metadata change time) in seconds since epoch.
     ctime: u64,
+    /// The nanosecond part of the ctime.
+    ctime_nsec: u32,
     /// The mtime (modification time) in seconds since epoch.
     mtime: u64,
+    /// The nanosecond part of the mtime.
+    mtime_nsec: u32,
     /// The device ID.
     dev: u32,
     /// The inode number.
@@ -153,6 +341,12 @@ pub struct IndexEntry {
     path: PathBuf,
     /// Stage number (0 for normal, 1-3 for merge conflicts).
     stage: u8,
+    /// Whether the entry is marked assume-unchanged.
+    assume_valid: bool,
+    /// Whether the entry is excluded from the working tree.
+    skip_worktree: bool,
+    /// Whether the entry is only a placeholder for a file to be added.
+    intent_to_add: bool,
 }
 
 impl IndexEntry {
@@ -173,7 +367,9 @@ impl IndexEntry {
     ) -> Self {
         Self {
             ctime,
+            ctime_nsec: 0,
             mtime,
+            mtime_nsec: 0,
             dev,
             ino,
             mode,
@@ -183,19 +379,123 @@ impl IndexEntry {
             oid,
             path,
             stage,
+            assume_valid: false,
+            skip_worktree: false,
+            intent_to_add: false,
         }
     }
 
+    /// Creates a new IndexEntry from the file's filesystem metadata.
+    ///
+    /// All stat fields (including nanoseconds, device, inode, uid and gid
+    /// on Unix) are recorded so that later status checks can skip
+    /// re-hashing the file when nothing has changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file relative to the repository root.
+    /// * `oid` - The object ID of the file's blob.
+    /// * `metadata` - The metadata of the file in the working tree.
+    pub fn from_metadata(path: PathBuf, oid: Oid, metadata: &Metadata) -> Self {
+        let mut entry = Self::new(
+            0,
+            0,
+            0,
+            0,
+            mode_from_metadata(metadata),
+            0,
+            0,
+            0,
+            oid,
+            path,
+            0,
+        );
+        entry.update_stat(metadata);
+        entry
+    }
+
+    /// Replaces the cached stat data with the given metadata.
+    ///
+    /// The object ID, mode, path and stage are left untouched.
+    pub fn update_stat(&mut self, metadata: &Metadata) {
+        let stat = StatData::from_metadata(metadata);
+        self.ctime = stat.ctime.0;
+        self.ctime_nsec = stat.ctime.1;
+        self.mtime = stat.mtime.0;
+        self.mtime_nsec = stat.mtime.1;
+        self.dev = stat.dev;
+        self.ino = stat.ino;
+        self.uid = stat.uid;
+        self.gid = stat.gid;
+        self.size = stat.size;
+    }
+
+    /// Returns a copy of this entry at a different path.
+    pub(crate) fn with_path(&self, path: PathBuf) -> Self {
+        Self {
+            path,
+            ..self.clone()
+        }
+    }
+
+    /// Returns true if the cached stat data matches the given metadata.
+    ///
+    /// This mirrors Git's `ie_match_stat()`: when every field matches, the
+    /// file is assumed to be unchanged and its content is not re-hashed.
+    pub fn stat_matches(&self, metadata: &Metadata) -> bool {
+        let stat = StatData::from_metadata(metadata);
+        self.mtime == stat.mtime.0
+            && self.mtime_nsec == stat.mtime.1
+            && self.ctime == stat.ctime.0
+            && self.ctime_nsec == stat.ctime.1
+            && self.dev == stat.dev
+            && self.ino == stat.ino
+            && self.uid == stat.uid
+            && self.gid == stat.gid
+            && self.size == stat.size
+            && self.mode == mode_from_metadata(metadata)
+    }
+
+    /// Returns true if this entry is "racily clean".
+    ///
+    /// An entry whose mtime is not older than the index file itself may
+    /// have been modified after its stat data was recorded, within the
+    /// timestamp granularity of the filesystem. Such entries must always
+    /// be verified by hashing.
+    ///
+    /// # Arguments
+    ///
+    /// * `index_timestamp` - The mtime of the index file (seconds, nanoseconds).
+    pub fn is_racily_clean(&self, index_timestamp: (u64, u32)) -> bool {
+        (self.mtime, self.mtime_nsec) >= index_timestamp
+    }
+
+    /// Marks the cached stat data as not matching any file, by setting the
+    /// size to 0 as Git does, so that the entry is always hashed.
+    pub(crate) fn smudge(&mut self) {
+        self.size = 0;
+    }
+
     /// Returns the ctime (metadata change time) in seconds since epoch.
     pub fn ctime(&self) -> u64 {
         self.ctime
     }
 
+    /// Returns the nanosecond part of the ctime.
+    pub fn ctime_nsec(&self) -> u32 {
+        self.ctime_nsec
+    }
+
     /// Returns the mtime (modification time) in seconds since epoch.
     pub fn mtime(&self) -> u64 {
         self.mtime
     }
 
+    /// Returns the nanosecond part of the mtime.
+    pub fn mtime_nsec(&self) -> u32 {
+        self.mtime_nsec
+    }
+
     /// Returns the device ID.
     pub fn dev(&self) -> u32 {
         self.dev
@@ -236,6 +536,15 @@ impl IndexEntry {
         &self.path
     }
 
+    /// Returns the path as stored in the index: raw bytes separated by
+    /// `/`.
+    ///
+    /// On Unix this is exactly [`path`](IndexEntry::path), whatever the
+    /// encoding of the name.
+    pub fn path_bytes(&self) -> Cow<'_, [u8]> {
+        path::to_bytes(&self.path)
+    }
+
     /// Returns the stage number.
     ///
     /// - 0: Normal entry
@@ -250,6 +559,115 @@ impl IndexEntry {
     pub fn is_conflicted(&self) -> bool {
         self.stage != 0
     }
+
+    /// Returns true if the entry is marked assume-unchanged.
+    pub fn is_assume_valid(&self) -> bool {
+        self.assume_valid
+    }
+
+    /// Returns true if the entry has the skip-worktree bit set.
+    ///
+    /// Such entries are not expected to exist in the working tree, as is
+    /// the case for paths outside a sparse checkout.
+    pub fn is_skip_worktree(&self) -> bool {
+        self.skip_worktree
+    }
+
+    /// Sets or clears the skip-worktree bit.
+    pub(crate) fn set_skip_worktree(&mut self, skip_worktree: bool) {
+        self.skip_worktree = skip_worktree;
+    }
+
+    /// Returns true if the entry was recorded with `git add --intent-to-add`.
+    ///
+    /// The entry only reserves the path: it has the empty blob as content
+    /// and is left out of trees built from the index.
+    pub fn is_intent_to_add(&self) -> bool {
+        self.intent_to_add
+    }
+
+    /// Returns true if the entry needs the v3 extended flags word.
+    fn has_extended_flags(&self) -> bool {
+        self.skip_worktree || self.intent_to_add
+    }
+}
+
+/// Stat data as stored in an index entry.
+struct StatData {
+    ctime: (u64, u32),
+    mtime: (u64, u32),
+    dev: u32,
+    ino: u32,
+    uid: u32,
+    gid: u32,
+    size: u32,
+}
+
+impl StatData {
+    /// Extracts index stat data from filesystem metadata.
+    ///
+    /// Values are truncated to 32 bits, as in the on-disk index format.
+    #[cfg(unix)]
+    fn from_metadata(metadata: &Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+
+        StatData {
+            ctime: (metadata.ctime() as u64, metadata.ctime_nsec() as u32),
+            mtime: (metadata.mtime() as u64, metadata.mtime_nsec() as u32),
+            dev: metadata.dev() as u32,
+            ino: metadata.ino() as u32,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            size: metadata.len() as u32,
+        }
+    }
+
+    /// Extracts index stat data from filesystem metadata.
+    ///
+    /// Values are truncated to 32 bits, as in the on-disk index format.
+    #[cfg(not(unix))]
+    fn from_metadata(metadata: &Metadata) -> Self {
+        let mtime = file_mtime(metadata);
+        let ctime = metadata
+            .created()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| (d.as_secs(), d.subsec_nanos()))
+            .unwrap_or(mtime);
+
+        StatData {
+            ctime,
+            mtime,
+            dev: 0,
+            ino: 0,
+            uid: 0,
+            gid: 0,
+            size: metadata.len() as u32,
+        }
+    }
+}
+
+/// Returns the modification time of a file as (seconds, nanoseconds).
+fn file_mtime(metadata: &Metadata) -> (u64, u32) {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| (d.as_secs(), d.subsec_nanos()))
+        .unwrap_or((0, 0))
+}
+
+/// Determines the index file mode from filesystem metadata.
+#[allow(unused_variables)]
+pub(crate) fn mode_from_metadata(metadata: &Metadata) -> FileMode {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return FileMode::Executable;
+        }
+    }
+    FileMode::Regular
 }
 
 #[cfg(test)]
@@ -356,4 +774,99 @@ mod tests {
         entry.stage = 1;
         assert!(entry.is_conflicted());
     }
+
+    #[test]
+    fn test_entry_from_metadata_stat_matches() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let file = temp.path().join("file.txt");
+        std::fs::write(&file, b"hello").unwrap();
+        let metadata = std::fs::metadata(&file).unwrap();
+
+        let entry = IndexEntry::from_metadata(
+            PathBuf::from("file.txt"),
+            Oid::from_bytes(SHA1_A),
+            &metadata,
+        );
+        assert_eq!(entry.size(), 5);
+        assert_eq!(entry.stage(), 0);
+        assert!(entry.stat_matches(&metadata));
+
+        // Changing the size invalidates the stat data
+        std::fs::write(&file, b"hello world").unwrap();
+        let metadata = std::fs::metadata(&file).unwrap();
+        assert!(!entry.stat_matches(&metadata));
+    }
+
+    #[test]
+    fn test_entry_update_stat_keeps_oid() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let file = temp.path().join("file.txt");
+        std::fs::write(&file, b"hello").unwrap();
+        let metadata = std::fs::metadata(&file).unwrap();
+
+        let mut entry = make_entry("file.txt");
+        assert!(!entry.stat_matches(&metadata));
+
+        entry.update_stat(&metadata);
+        assert!(entry.stat_matches(&metadata));
+        assert_eq!(entry.oid(), &Oid::from_bytes(SHA1_A));
+        assert_eq!(entry.path(), Path::new("file.txt"));
+    }
+
+    #[test]
+    fn test_entry_racily_clean() {
+        let mut entry = make_entry("file.txt");
+        entry.mtime = 1700000000;
+        entry.mtime_nsec = 500;
+
+        assert!(!entry.is_racily_clean((1700000001, 0)));
+        assert!(!entry.is_racily_clean((1700000000, 501)));
+        assert!(entry.is_racily_clean((1700000000, 500)));
+        assert!(entry.is_racily_clean((1699999999, 999)));
+    }
+
+    #[test]
+    fn test_add_resolves_conflict_into_resolve_undo() {
+        let mut index = Index::empty(2);
+        for stage in 1..=3 {
+            let mut entry = make_entry("file.txt");
+            entry.stage = stage;
+            index.add(entry);
+        }
+        index.add(make_entry("other.txt"));
+        assert_eq!(index.len(), 4);
+        assert!(index.resolve_undo().is_empty());
+
+        index.add(make_entry("file.txt"));
+        assert_eq!(index.len(), 2);
+        assert!(index.iter().all(|e| e.stage() == 0));
+        assert_eq!(index.resolve_undo().len(), 1);
+        let resolved = &index.resolve_undo()[0];
+        assert_eq!(resolved.path(), Path::new("file.txt"));
+        assert_eq!(
+            resolved.stage(2),
+            Some((FileMode::Regular, Oid::from_bytes(SHA1_A)))
+        );
+    }
+
+    #[test]
+    fn test_changes_invalidate_extensions() {
+        let mut index = Index::empty(2);
+        index.add(make_entry("dir/file.txt"));
+        let cache_tree = index.cache_tree_mut();
+        cache_tree.set_valid(1, Oid::from_bytes(SHA1_A));
+        let mut dir = CacheTree::new(b"dir");
+        dir.set_valid(1, Oid::from_bytes(SHA1_A));
+        cache_tree.set_children(vec![dir]);
+        assert!(index.cache_tree().unwrap().is_valid());
+
+        index.add(make_entry("top.txt"));
+        let cache_tree = index.cache_tree().unwrap();
+        assert!(!cache_tree.is_valid());
+        assert!(cache_tree.find(Path::new("dir")).unwrap().is_valid());
+
+        index.remove(Path::new("dir/file.txt"));
+        let cache_tree = index.cache_tree().unwrap();
+        assert!(!cache_tree.find(Path::new("dir")).unwrap().is_valid());
+    }
 }
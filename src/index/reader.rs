@@ -3,14 +3,21 @@
 //! This module implements parsing of the Git index file format (versions 2, 3, 4).
 
 use std::io::{Cursor, Read, Seek, SeekFrom};
-use std::path::PathBuf;
 
 use crate::error::{Error, Result};
-use crate::objects::oid::OID_BYTES;
+use crate::infra::{path, ObjectFormat};
 use crate::objects::tree::FileMode;
 use crate::objects::Oid;
 
-use super::{Index, IndexEntry};
+use super::extensions::{
+    decode_varint, is_optional, CacheTree, RawExtension, ResolveUndoEntry, UntrackedCache,
+    SIG_EOIE, SIG_IEOT, SIG_REUC, SIG_TREE, SIG_UNTR,
+};
+use super::split::{SplitIndex, SIG_LINK};
+use super::{
+    Index, IndexEntry, EXTENDED_INTENT_TO_ADD, EXTENDED_SKIP_WORKTREE, FLAG_ASSUME_VALID,
+    FLAG_EXTENDED, FLAG_NAME_MASK,
+};
 
 /// The magic signature at the start of an index file: "DIRC"
 const INDEX_SIGNATURE: &[u8; 4] = b"DIRC";
@@ -21,7 +28,7 @@ const MIN_VERSION: u32 = 2;
 /// Maximum supported index version.
 const MAX_VERSION: u32 = 4;
 
-/// Parses a Git index file from raw bytes.
+/// Parses a Git index file of a SHA-1 repository from raw bytes.
 ///
 /// # Arguments
 ///
@@ -37,7 +44,27 @@ const MAX_VERSION: u32 = 4;
 /// - The signature is not "DIRC"
 /// - The version is not 2, 3, or 4
 /// - The data is truncated or malformed
+/// - An extension is malformed, or is required but not understood
 pub fn parse(data: &[u8]) -> Result<Index> {
+    parse_with_format(data, ObjectFormat::Sha1)
+}
+
+/// Parses a Git index file from raw bytes, with object IDs and checksum
+/// of the given format.
+///
+/// # Arguments
+///
+/// * `data` - The raw bytes of the index file.
+/// * `format` - The object format of the repository.
+///
+/// # Returns
+///
+/// The parsed Index on success, or an error if parsing fails.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidIndex` under the same conditions as [`parse`].
+pub fn parse_with_format(data: &[u8], format: ObjectFormat) -> Result<Index> {
     let mut cursor = Cursor::new(data);
 
     // Parse header
@@ -45,12 +72,81 @@ pub fn parse(data: &[u8]) -> Result<Index> {
 
     // Parse entries
     let mut entries = Vec::with_capacity(entry_count as usize);
+    let mut previous_name = Vec::new();
     for _ in 0..entry_count {
-        let entry = parse_entry(&mut cursor, version)?;
+        let entry = parse_entry(&mut cursor, version, format, &mut previous_name)?;
         entries.push(entry);
     }
 
-    Ok(Index::new(version, entries))
+    let mut index = Index::new(version, entries);
+    index.set_object_format(format);
+    let extensions_start = cursor.position() as usize;
+    let extensions_end = data.len().saturating_sub(format.size());
+    if extensions_start < extensions_end {
+        parse_extensions(&data[extensions_start..extensions_end], &mut index)?;
+    }
+
+    Ok(index)
+}
+
+/// Parses the extensions following the entries.
+///
+/// Each extension is a 4-byte signature, a 4-byte big-endian size and
+/// the data. Optional extensions that are not understood are kept so they
+/// can be written back.
+fn parse_extensions(data: &[u8], index: &mut Index) -> Result<()> {
+    let version = index.version;
+    let format = index.format;
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let header = data.get(pos..pos + 8).ok_or_else(|| Error::InvalidIndex {
+            version,
+            reason: "truncated extension header".to_string(),
+        })?;
+        let signature: [u8; 4] = header[..4].try_into().expect("4 bytes");
+        let size = u32::from_be_bytes(header[4..].try_into().expect("4 bytes")) as usize;
+        pos += 8;
+        let ext = data
+            .get(pos..pos + size)
+            .ok_or_else(|| Error::InvalidIndex {
+                version,
+                reason: format!(
+                    "truncated {} extension",
+                    String::from_utf8_lossy(&signature)
+                ),
+            })?;
+        pos += size;
+
+        match &signature {
+            SIG_TREE => index.cache_tree = Some(CacheTree::parse(ext, version, format)?),
+            SIG_REUC => {
+                index.resolve_undo = ResolveUndoEntry::parse_all(ext, version, format)?;
+            }
+            SIG_UNTR => {
+                index.untracked_cache = Some(UntrackedCache::parse(ext, version, format)?);
+            }
+            SIG_LINK => index.split = Some(SplitIndex::parse(ext, version, format)?),
+            SIG_EOIE => index.end_of_index = true,
+            SIG_IEOT => index.entry_offsets = true,
+            _ if is_optional(&signature) => {
+                index
+                    .unknown_extensions
+                    .push(RawExtension::new(signature, ext.to_vec()));
+            }
+            _ => {
+                return Err(Error::InvalidIndex {
+                    version,
+                    reason: format!(
+                        "unsupported required extension: {}",
+                        String::from_utf8_lossy(&signature)
+                    ),
+                })
+            }
+        }
+    }
+
+    Ok(())
 }
 
 /// Parses the index file header.
@@ -104,21 +200,32 @@ fn parse_header(cursor: &mut Cursor<&[u8]>) -> Result<(u32, u32)> {
 /// Parses a single index entry.
 ///
 /// Each entry has:
-/// - Fixed fields (62 bytes for v2, 64 bytes for v3+ with extended flags)
-/// - Variable-length name (NUL-terminated)
-/// - Padding to 8-byte boundary
-fn parse_entry(cursor: &mut Cursor<&[u8]>, version: u32) -> Result<IndexEntry> {
+/// - Fixed fields (62 bytes, plus 2 bytes of extended flags in v3+ when
+///   the extended bit is set)
+/// - The name: in v2/v3 the full path, NUL-terminated and padded to an
+///   8-byte boundary; in v4 a varint giving how many bytes to strip from
+///   the end of the previous entry's path, followed by the NUL-terminated
+///   suffix to append, without padding
+///
+/// For v4, `previous_name` holds the previous entry's path and is
+/// updated to this entry's path.
+fn parse_entry(
+    cursor: &mut Cursor<&[u8]>,
+    version: u32,
+    format: ObjectFormat,
+    previous_name: &mut Vec<u8>,
+) -> Result<IndexEntry> {
     let entry_start = cursor.position();
 
     // ctime (seconds)
     let ctime_sec = read_u32_be(cursor).map_err(|_| make_entry_error(version, "ctime_sec"))?;
-    // ctime (nanoseconds) - ignored
-    let _ctime_nsec = read_u32_be(cursor).map_err(|_| make_entry_error(version, "ctime_nsec"))?;
+    // ctime (nanoseconds)
+    let ctime_nsec = read_u32_be(cursor).map_err(|_| make_entry_error(version, "ctime_nsec"))?;
 
     // mtime (seconds)
     let mtime_sec = read_u32_be(cursor).map_err(|_| make_entry_error(version, "mtime_sec"))?;
-    // mtime (nanoseconds) - ignored
-    let _mtime_nsec = read_u32_be(cursor).map_err(|_| make_entry_error(version, "mtime_nsec"))?;
+    // mtime (nanoseconds)
+    let mtime_nsec = read_u32_be(cursor).map_err(|_| make_entry_error(version, "mtime_nsec"))?;
 
     // dev
     let dev = read_u32_be(cursor).map_err(|_| make_entry_error(version, "dev"))?;
@@ -139,65 +246,84 @@ fn parse_entry(cursor: &mut Cursor<&[u8]>, version: u32) -> Result<IndexEntry> {
     // file size
     let size = read_u32_be(cursor).map_err(|_| make_entry_error(version, "size"))?;
 
-    // SHA-1
-    let mut oid_bytes = [0u8; OID_BYTES];
+    // Object ID
+    let mut oid_bytes = vec![0u8; format.size()];
     cursor
         .read_exact(&mut oid_bytes)
         .map_err(|_| make_entry_error(version, "oid"))?;
-    let oid = Oid::from_bytes(oid_bytes);
+    let oid = Oid::from_slice(&oid_bytes)?;
 
     // flags (16 bits)
     let flags = read_u16_be(cursor).map_err(|_| make_entry_error(version, "flags"))?;
 
     // Extract name length from lower 12 bits
-    let name_len = (flags & 0x0FFF) as usize;
+    let name_len = (flags & FLAG_NAME_MASK) as usize;
 
     // Extract stage from bits 12-13
     let stage = ((flags >> 12) & 0x03) as u8;
 
     // Check for extended flag (bit 14, v3+ only)
-    let has_extended = version >= 3 && (flags & 0x4000) != 0;
+    if flags & FLAG_EXTENDED != 0 && version < 3 {
+        return Err(Error::InvalidIndex {
+            version,
+            reason: "extended flags in a version 2 index".to_string(),
+        });
+    }
 
     // Read extended flags if present
-    if has_extended {
-        let _extended_flags =
-            read_u16_be(cursor).map_err(|_| make_entry_error(version, "extended_flags"))?;
-        // Extended flags contain additional information like skip-worktree and intent-to-add
-        // For now, we just skip them
+    let extended_flags = if flags & FLAG_EXTENDED != 0 {
+        read_u16_be(cursor).map_err(|_| make_entry_error(version, "extended_flags"))?
+    } else {
+        0
+    };
+    if extended_flags & !(EXTENDED_SKIP_WORKTREE | EXTENDED_INTENT_TO_ADD) != 0 {
+        return Err(Error::InvalidIndex {
+            version,
+            reason: format!("unknown extended flags: {:#06x}", extended_flags),
+        });
     }
 
-    // Read name
-    // If name_len is 0xFFF (4095), the name is longer and continues until NUL
-    let name = if name_len == 0xFFF {
-        // Read until NUL byte
-        read_until_nul(cursor).map_err(|_| make_entry_error(version, "name (long)"))?
+    let name_start = cursor.position();
+    let name = if version >= 4 {
+        // Prefix-compressed relative to the previous entry's path
+        let strip =
+            read_varint(cursor).map_err(|_| make_entry_error(version, "name prefix length"))?;
+        let prefix_len =
+            previous_name
+                .len()
+                .checked_sub(strip)
+                .ok_or_else(|| Error::InvalidIndex {
+                    version,
+                    reason: format!("name prefix length {} is too long", strip),
+                })?;
+        let suffix = read_until_nul(cursor).map_err(|_| make_entry_error(version, "name"))?;
+        previous_name.truncate(prefix_len);
+        previous_name.extend_from_slice(&suffix);
+        previous_name.clone()
     } else {
-        // Read exactly name_len bytes
-        let mut name_buf = vec![0u8; name_len];
-        cursor
-            .read_exact(&mut name_buf)
-            .map_err(|_| make_entry_error(version, "name"))?;
-        String::from_utf8(name_buf).map_err(|_| Error::InvalidIndex {
-            version,
-            reason: "invalid UTF-8 in entry name".to_string(),
-        })?
+        // If name_len is 0xFFF (4095), the name is longer and continues until NUL
+        let name = if name_len == FLAG_NAME_MASK as usize {
+            read_until_nul(cursor).map_err(|_| make_entry_error(version, "name (long)"))?
+        } else {
+            let mut name_buf = vec![0u8; name_len];
+            cursor
+                .read_exact(&mut name_buf)
+                .map_err(|_| make_entry_error(version, "name"))?;
+            name_buf
+        };
+
+        // The entry is NUL-padded to a multiple of 8 bytes, with at
+        // least one NUL after the name
+        let entry_len = (name_start - entry_start + name.len() as u64 + 8) & !7;
+        cursor.seek(SeekFrom::Start(entry_start + entry_len)).ok();
+        name
     };
 
-    // Calculate padding
-    // Entry size is padded to a multiple of 8 bytes
-    // For v4, padding is different (path compression), but we don't support that fully
-    let _entry_size = (cursor.position() - entry_start) as usize;
-
-    // Find and skip NUL padding
-    // There's at least 1 NUL byte after the name, and then padding to 8-byte boundary
-    if name_len != 0xFFF {
-        // Skip the NUL terminator that's included in padding
-        skip_padding(cursor, entry_start, version)?;
-    }
-
-    Ok(IndexEntry::new(
-        ctime_sec as u64,
-        mtime_sec as u64,
+    Ok(IndexEntry {
+        ctime: ctime_sec as u64,
+        ctime_nsec,
+        mtime: mtime_sec as u64,
+        mtime_nsec,
         dev,
         ino,
         mode,
@@ -205,9 +331,12 @@ fn parse_entry(cursor: &mut Cursor<&[u8]>, version: u32) -> Result<IndexEntry> {
         gid,
         size,
         oid,
-        PathBuf::from(name),
+        path: path::from_bytes(&name),
         stage,
-    ))
+        assume_valid: flags & FLAG_ASSUME_VALID != 0,
+        skip_worktree: extended_flags & EXTENDED_SKIP_WORKTREE != 0,
+        intent_to_add: extended_flags & EXTENDED_INTENT_TO_ADD != 0,
+    })
 }
 
 /// Parses a mode value into a FileMode.
@@ -226,46 +355,8 @@ fn parse_mode(mode: u32, version: u32) -> Result<FileMode> {
     }
 }
 
-/// Skips padding bytes after an entry.
-fn skip_padding(cursor: &mut Cursor<&[u8]>, entry_start: u64, _version: u32) -> Result<()> {
-    // Entry must be padded to 8-byte boundary
-    // The minimum entry size is 62 bytes (v2) or 64 bytes (v3 with extended)
-    // Plus the name length + 1 (for NUL terminator)
-    // Padded to multiple of 8
-
-    let current_pos = cursor.position();
-    let entry_size = current_pos - entry_start;
-
-    // Skip NUL bytes until we're at 8-byte boundary
-    // There's at least 1 NUL after the name
-    let mut byte = [0u8; 1];
-    loop {
-        let pos = cursor.position();
-        let offset_from_start = pos - entry_start;
-
-        // Check if we've reached an 8-byte boundary (and read at least 1 byte of padding)
-        if offset_from_start > entry_size && offset_from_start % 8 == 0 {
-            break;
-        }
-
-        // Read one byte
-        if cursor.read_exact(&mut byte).is_err() {
-            break; // End of data
-        }
-
-        // Should be NUL padding
-        if byte[0] != 0 {
-            // Oops, went too far or format error - seek back
-            cursor.seek(SeekFrom::Current(-1)).ok();
-            break;
-        }
-    }
-
-    Ok(())
-}
-
-/// Reads a NUL-terminated string from the cursor.
-fn read_until_nul(cursor: &mut Cursor<&[u8]>) -> std::io::Result<String> {
+/// Reads a NUL-terminated byte string from the cursor.
+fn read_until_nul(cursor: &mut Cursor<&[u8]>) -> std::io::Result<Vec<u8>> {
     let mut bytes = Vec::new();
     let mut byte = [0u8; 1];
 
@@ -277,7 +368,16 @@ fn read_until_nul(cursor: &mut Cursor<&[u8]>) -> std::io::Result<String> {
         bytes.push(byte[0]);
     }
 
-    String::from_utf8(bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    Ok(bytes)
+}
+
+/// Reads a variable-length integer as used by v4 path compression.
+fn read_varint(cursor: &mut Cursor<&[u8]>) -> std::io::Result<usize> {
+    let data = *cursor.get_ref();
+    let mut pos = cursor.position() as usize;
+    let value = decode_varint(data, &mut pos).ok_or(std::io::ErrorKind::UnexpectedEof)?;
+    cursor.set_position(pos as u64);
+    Ok(value)
 }
 
 /// Reads a big-endian u32 from the cursor.
@@ -316,15 +416,16 @@ mod tests {
         data.extend_from_slice(&(entries.len() as u32).to_be_bytes());
 
         // Entries
+        let mut previous_name = "";
         for (name, sha1) in entries {
             let entry_start = data.len();
 
             // ctime_sec, ctime_nsec
             data.extend_from_slice(&1700000000u32.to_be_bytes());
-            data.extend_from_slice(&0u32.to_be_bytes());
+            data.extend_from_slice(&123u32.to_be_bytes());
             // mtime_sec, mtime_nsec
             data.extend_from_slice(&1700000001u32.to_be_bytes());
-            data.extend_from_slice(&0u32.to_be_bytes());
+            data.extend_from_slice(&456u32.to_be_bytes());
             // dev
             data.extend_from_slice(&100u32.to_be_bytes());
             // ino
@@ -342,6 +443,20 @@ mod tests {
             // flags (name length in lower 12 bits)
             let name_len = name.len().min(0xFFF) as u16;
             data.extend_from_slice(&name_len.to_be_bytes());
+            if version >= 4 {
+                // Prefix-compressed name (single-byte varint), no padding
+                let common = name
+                    .bytes()
+                    .zip(previous_name.bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                data.push((previous_name.len() - common) as u8);
+                data.extend_from_slice(&name.as_bytes()[common..]);
+                data.push(0);
+                previous_name = name;
+                continue;
+            }
+
             // name
             data.extend_from_slice(name.as_bytes());
 
@@ -442,6 +557,72 @@ mod tests {
         ));
     }
 
+    // I-006: v4 prefix-compressed names are expanded
+    #[test]
+    fn test_parse_v4_prefix_compression() {
+        let data = make_index(
+            4,
+            &[
+                ("dir/a.txt", &SHA1_A),
+                ("dir/b.txt", &SHA1_B),
+                ("dir2/c.txt", &SHA1_A),
+                ("e", &SHA1_B),
+            ],
+        );
+        let index = parse(&data).unwrap();
+
+        let paths: Vec<_> = index
+            .entries()
+            .iter()
+            .map(|e| e.path().to_str().unwrap())
+            .collect();
+        assert_eq!(paths, ["dir/a.txt", "dir/b.txt", "dir2/c.txt", "e"]);
+        assert_eq!(index.entries()[1].oid(), &Oid::from_bytes(SHA1_B));
+
+        // A strip length longer than the previous name is rejected
+        let mut data = make_index(4, &[("a", &SHA1_A)]);
+        data[12 + 62] = 5;
+        assert!(matches!(parse(&data), Err(Error::InvalidIndex { .. })));
+    }
+
+    // I-007: v3 extended flags are parsed, and rejected in v2
+    #[test]
+    fn test_parse_extended_flags() {
+        // Rebuild the first entry with the extended bit and flags word
+        let plain = make_index(3, &[("file.txt", &SHA1_A)]);
+        let mut data = make_index(3, &[("file.txt", &SHA1_A), ("next.txt", &SHA1_B)]);
+        data.truncate(12);
+        data.extend_from_slice(&plain[12..12 + 60]);
+        data.extend_from_slice(&(0x4000u16 | 8).to_be_bytes());
+        data.extend_from_slice(&0x6000u16.to_be_bytes());
+        data.extend_from_slice(b"file.txt");
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&make_index(3, &[("next.txt", &SHA1_B)])[12..]);
+
+        let index = parse(&data).unwrap();
+        assert_eq!(index.len(), 2);
+        let entry = &index.entries()[0];
+        assert_eq!(entry.path().to_str().unwrap(), "file.txt");
+        assert!(entry.is_skip_worktree());
+        assert!(entry.is_intent_to_add());
+        assert!(!entry.is_assume_valid());
+        assert!(!index.entries()[1].is_skip_worktree());
+
+        data[4..8].copy_from_slice(&2u32.to_be_bytes());
+        assert!(matches!(parse(&data), Err(Error::InvalidIndex { .. })));
+    }
+
+    // I-008: Names of 4095 bytes or more are padded like shorter names
+    #[test]
+    fn test_parse_long_name() {
+        let long = "x".repeat(5000);
+        let data = make_index(2, &[(&long, &SHA1_A), ("short.txt", &SHA1_B)]);
+        let index = parse(&data).unwrap();
+
+        assert_eq!(index.entries()[0].path().to_str().unwrap(), long);
+        assert_eq!(index.entries()[1].path().to_str().unwrap(), "short.txt");
+    }
+
     // Test parsing header only
     #[test]
     fn test_parse_header() {
@@ -486,7 +667,9 @@ mod tests {
 
         let entry = &index.entries()[0];
         assert_eq!(entry.ctime(), 1700000000);
+        assert_eq!(entry.ctime_nsec(), 123);
         assert_eq!(entry.mtime(), 1700000001);
+        assert_eq!(entry.mtime_nsec(), 456);
         assert_eq!(entry.dev(), 100);
         assert_eq!(entry.ino(), 12345);
         assert_eq!(entry.uid(), 1000);
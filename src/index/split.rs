@@ -0,0 +1,346 @@
+//! Split index support (`link` extension).
+//!
+//! With `core.splitIndex`, most entries live in a shared index file
+//! (`.git/sharedindex.<checksum>`), and `.git/index` only holds the
+//! entries that changed since, plus a `link` extension naming the shared
+//! index. The extension carries two EWAH bitmaps over the shared index
+//! positions: entries to delete, and entries replaced by the first
+//! entries of `.git/index` (which are stored with empty names). The
+//! remaining entries of `.git/index` are added.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::infra::{ewah, ObjectFormat};
+use crate::objects::Oid;
+
+use super::{Index, IndexEntry};
+
+/// Signature of the split index extension.
+pub(crate) const SIG_LINK: &[u8; 4] = b"link";
+
+/// The entries to write for a split index (each flagged if it is written
+/// without a name) and the `link` extension data.
+type SplitEntries<'a> = (Vec<(&'a IndexEntry, bool)>, Vec<u8>);
+
+/// The split index state of an index.
+#[derive(Debug, Clone)]
+pub(crate) struct SplitIndex {
+    /// The checksum of the shared index, or all zeros if there is none.
+    base_oid: Oid,
+    /// The entries of the shared index, once it has been merged.
+    base: Option<Vec<IndexEntry>>,
+    /// Shared index positions deleted, as read from the extension.
+    deleted: Vec<usize>,
+    /// Shared index positions replaced, as read from the extension.
+    replaced: Vec<usize>,
+}
+
+impl SplitIndex {
+    /// Creates the state for an index split against the given shared index.
+    pub(crate) fn new(base_oid: Oid, base: Vec<IndexEntry>) -> Self {
+        SplitIndex {
+            base_oid,
+            base: Some(base),
+            deleted: Vec::new(),
+            replaced: Vec::new(),
+        }
+    }
+
+    /// Returns the checksum of the shared index, if there is one.
+    pub(crate) fn base_oid(&self) -> Option<&Oid> {
+        Some(&self.base_oid).filter(|oid| !oid.is_zero())
+    }
+
+    /// Returns true if the shared index still has to be merged.
+    pub(crate) fn is_pending(&self) -> bool {
+        self.base.is_none() && self.base_oid().is_some()
+    }
+
+    /// Parses the data of a `link` extension.
+    pub(crate) fn parse(data: &[u8], version: u32, format: ObjectFormat) -> Result<Self> {
+        let err = || Error::InvalidIndex {
+            version,
+            reason: "corrupt link extension".to_string(),
+        };
+
+        let oid_bytes = data.get(..format.size()).ok_or_else(err)?;
+        let base_oid = Oid::from_slice(oid_bytes)?;
+        let mut pos = format.size();
+        let (deleted, replaced) = if pos == data.len() {
+            (Vec::new(), Vec::new())
+        } else {
+            let deleted = ewah::decode(data, &mut pos).ok_or_else(err)?;
+            let replaced = ewah::decode(data, &mut pos).ok_or_else(err)?;
+            if pos != data.len() {
+                return Err(err());
+            }
+            (deleted, replaced)
+        };
+
+        let mut split = SplitIndex {
+            base_oid,
+            base: None,
+            deleted,
+            replaced,
+        };
+        if split.base_oid().is_none() {
+            split.base = Some(Vec::new());
+        }
+        Ok(split)
+    }
+
+    /// Serializes `link` extension data for the given bitmaps.
+    fn write(&self, deleted: &[usize], replaced: &[usize], buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.base_oid.as_bytes());
+        ewah::encode(deleted, buffer);
+        ewah::encode(replaced, buffer);
+    }
+}
+
+impl Index {
+    /// Returns the checksum of the shared index this index is split
+    /// against, if it is a split index.
+    ///
+    /// Indexes read through a repository have the shared index merged in,
+    /// so [`Index::entries`] returns every entry. An index parsed with
+    /// [`parse`](super::parse) alone only holds the entries of the split
+    /// part.
+    pub fn shared_index(&self) -> Option<&Oid> {
+        self.split.as_ref().and_then(SplitIndex::base_oid)
+    }
+
+    /// Returns true if the shared index has not been merged yet.
+    pub(crate) fn has_pending_shared_index(&self) -> bool {
+        self.split.as_ref().is_some_and(SplitIndex::is_pending)
+    }
+
+    /// Merges the shared index into this (split) index.
+    ///
+    /// Applies the delete and replace bitmaps of the `link` extension to
+    /// the shared entries, then adds the remaining entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidIndex` if the bitmaps do not fit the shared
+    /// index or a replacement entry has a name.
+    pub(crate) fn merge_shared_index(&mut self, shared: Index) -> Result<()> {
+        let version = self.version;
+        let err = |reason: &str| Error::InvalidIndex {
+            version,
+            reason: format!("corrupt link extension: {}", reason),
+        };
+        let Some(split) = self.split.as_mut().filter(|s| s.is_pending()) else {
+            return Ok(());
+        };
+
+        let base = shared.entries;
+        let mut merged: Vec<Option<IndexEntry>> = base.iter().cloned().map(Some).collect();
+        let mut pending = std::mem::take(&mut self.entries).into_iter();
+
+        for &pos in &split.deleted {
+            let slot = merged
+                .get_mut(pos)
+                .ok_or_else(|| err("bad delete bitmap"))?;
+            *slot = None;
+        }
+        for &pos in &split.replaced {
+            let mut entry = pending
+                .next()
+                .ok_or_else(|| err("too few replacement entries"))?;
+            if !entry.path.as_os_str().is_empty() {
+                return Err(err("replacement entry has a name"));
+            }
+            let original = base.get(pos).ok_or_else(|| err("bad replace bitmap"))?;
+            entry.path = original.path.clone();
+            merged[pos] = Some(entry);
+        }
+
+        split.deleted.clear();
+        split.replaced.clear();
+        split.base = Some(base);
+        self.entries = merged.into_iter().flatten().collect();
+        for entry in pending {
+            self.insert_entry(entry);
+        }
+
+        Ok(())
+    }
+
+    /// Returns an index holding only the current entries, to be written
+    /// as a new shared index file.
+    pub(crate) fn share_entries(&self) -> Index {
+        let mut shared = Index::new(self.version, self.entries.clone());
+        shared.format = self.format;
+        shared
+    }
+
+    /// Sets the shared index this index is split against.
+    pub(crate) fn set_shared_index(&mut self, oid: Oid, base: Vec<IndexEntry>) {
+        self.split = Some(SplitIndex::new(oid, base));
+    }
+
+    /// Leaves split mode, so the whole index is written to `.git/index`.
+    pub(crate) fn remove_split_index(&mut self) {
+        self.split = None;
+    }
+
+    /// Returns the number of entries that are not in the shared index.
+    pub(crate) fn not_shared_count(&self) -> usize {
+        let base = match self.split.as_ref().and_then(|s| s.base.as_ref()) {
+            Some(base) => base,
+            None => return self.entries.len(),
+        };
+        let shared: HashSet<(&Path, u8)> = base.iter().map(|e| (e.path(), e.stage)).collect();
+        self.entries
+            .iter()
+            .filter(|e| !shared.contains(&(e.path(), e.stage)))
+            .count()
+    }
+
+    /// Returns the entries to write and the `link` extension data, if
+    /// the index is split against a shared index.
+    ///
+    /// For a merged split index, entries that differ from their shared
+    /// counterpart are written first, flagged to be written without names
+    /// and in shared index order, followed by the entries that are not
+    /// shared.
+    pub(crate) fn split_entries(&self) -> Option<SplitEntries<'_>> {
+        let split = self.split.as_ref().filter(|s| s.base_oid().is_some())?;
+        let mut link = Vec::new();
+
+        let Some(base) = &split.base else {
+            // Not merged: write back what was read
+            split.write(&split.deleted, &split.replaced, &mut link);
+            let entries = self.entries.iter().map(|e| (e, false)).collect();
+            return Some((entries, link));
+        };
+
+        let current: HashMap<(&Path, u8), &IndexEntry> = self
+            .entries
+            .iter()
+            .map(|e| ((e.path(), e.stage), e))
+            .collect();
+        let mut deleted = Vec::new();
+        let mut replaced = Vec::new();
+        let mut entries = Vec::new();
+        for (pos, old) in base.iter().enumerate() {
+            match current.get(&(old.path(), old.stage)) {
+                None => deleted.push(pos),
+                Some(&entry) if entry != old => {
+                    replaced.push(pos);
+                    entries.push((entry, true));
+                }
+                Some(_) => {}
+            }
+        }
+
+        let shared: HashSet<(&Path, u8)> = base.iter().map(|e| (e.path(), e.stage)).collect();
+        entries.extend(
+            self.entries
+                .iter()
+                .filter(|e| !shared.contains(&(e.path(), e.stage)))
+                .map(|e| (e, false)),
+        );
+
+        split.write(&deleted, &replaced, &mut link);
+        Some((entries, link))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{parse, write};
+    use crate::objects::oid::OID_BYTES;
+    use crate::objects::tree::FileMode;
+    use std::path::PathBuf;
+
+    fn make_entry(path: &str, id: u8) -> IndexEntry {
+        IndexEntry::new(
+            0,
+            0,
+            0,
+            0,
+            FileMode::Regular,
+            0,
+            0,
+            0,
+            Oid::from_bytes([id; OID_BYTES]),
+            PathBuf::from(path),
+            0,
+        )
+    }
+
+    /// Builds a split index whose shared index holds `a`, `b` and `c`.
+    fn make_split(entries: Vec<IndexEntry>) -> (Index, Index) {
+        let shared = Index::new(
+            2,
+            vec![make_entry("a", 1), make_entry("b", 2), make_entry("c", 3)],
+        );
+        let oid = Oid::from_bytes([9; OID_BYTES]);
+        let mut index = Index::new(2, entries);
+        index.set_shared_index(oid, shared.entries().to_vec());
+        (index, shared)
+    }
+
+    fn paths(index: &Index) -> Vec<&str> {
+        index.iter().map(|e| e.path().to_str().unwrap()).collect()
+    }
+
+    // SI-001: Only changed entries are written, and merging restores the rest
+    #[test]
+    fn test_split_roundtrip() {
+        let (index, shared) = make_split(vec![
+            make_entry("a", 1),
+            make_entry("b", 7),
+            make_entry("d", 4),
+        ]);
+        let data = write(&index);
+
+        let mut parsed = parse(&data).unwrap();
+        assert_eq!(
+            parsed.shared_index(),
+            Some(&Oid::from_bytes([9; OID_BYTES]))
+        );
+        assert!(parsed.has_pending_shared_index());
+        // The replacement for "b" (without a name) and the new "d"
+        assert_eq!(paths(&parsed), ["", "d"]);
+
+        parsed.merge_shared_index(shared).unwrap();
+        assert!(!parsed.has_pending_shared_index());
+        assert_eq!(parsed.entries(), index.entries());
+        assert_eq!(parsed.not_shared_count(), 1);
+
+        // Writing the merged index again gives the same split part
+        assert_eq!(write(&parsed), data);
+    }
+
+    // SI-002: An unmerged split index is written back unchanged
+    #[test]
+    fn test_unmerged_split_written_back() {
+        let (index, _) = make_split(vec![make_entry("b", 7), make_entry("c", 3)]);
+        let data = write(&index);
+
+        let parsed = parse(&data).unwrap();
+        assert_eq!(write(&parsed), data);
+    }
+
+    // SI-003: Corrupt link extensions are rejected
+    #[test]
+    fn test_merge_rejects_bad_bitmaps() {
+        let (index, _) = make_split(vec![make_entry("b", 7)]);
+        let mut parsed = parse(&write(&index)).unwrap();
+        let short = Index::new(2, vec![make_entry("a", 1)]);
+        assert!(matches!(
+            parsed.merge_shared_index(short),
+            Err(Error::InvalidIndex { .. })
+        ));
+
+        assert!(SplitIndex::parse(&[0; 10], 2, ObjectFormat::Sha1).is_err());
+        let null = SplitIndex::parse(&[0; OID_BYTES], 2, ObjectFormat::Sha1).unwrap();
+        assert!(null.base_oid().is_none());
+        assert!(!null.is_pending());
+    }
+}
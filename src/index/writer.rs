@@ -1,19 +1,27 @@
 //! Git index file writer.
 //!
-//! This module implements writing of the Git index file format (version 2).
+//! This module implements writing of the Git index file format (versions 2, 3, 4).
 
-use std::path::Path;
-
-use crate::infra::hash::sha1;
+use crate::infra::{digest, path};
 use crate::objects::tree::FileMode;
 
-use super::{Index, IndexEntry};
+use super::extensions::{encode_varint, SIG_EOIE, SIG_IEOT, SIG_REUC, SIG_TREE, SIG_UNTR};
+use super::split::SIG_LINK;
+use super::{
+    Index, IndexEntry, EXTENDED_INTENT_TO_ADD, EXTENDED_SKIP_WORKTREE, FLAG_ASSUME_VALID,
+    FLAG_EXTENDED, FLAG_NAME_MASK,
+};
 
 /// The magic signature at the start of an index file: "DIRC"
 const INDEX_SIGNATURE: &[u8; 4] = b"DIRC";
 
 /// Writes the index to bytes in Git index format.
 ///
+/// The index is written in its own version, except that versions 2 and 3
+/// are chosen by the entries, as Git does: version 3 when an entry has
+/// extended flags, and version 2 otherwise. A split index is written as its split part, with a `link` extension
+/// naming the shared index.
+///
 /// # Arguments
 ///
 /// * `index` - The index to write.
@@ -24,16 +32,45 @@ const INDEX_SIGNATURE: &[u8; 4] = b"DIRC";
 pub fn write(index: &Index) -> Vec<u8> {
     let mut buffer = Vec::new();
 
+    let mut version = index.version();
+    if version < 4 {
+        version = if index.entries().iter().any(IndexEntry::has_extended_flags) {
+            3
+        } else {
+            2
+        };
+    }
+
+    // A split index only writes the entries that are not shared; entries
+    // replacing a shared entry are written without a name
+    let (entries, link) = match index.split_entries() {
+        Some((entries, link)) => (entries, Some(link)),
+        None => (index.entries().iter().map(|e| (e, false)).collect(), None),
+    };
+
     // Write header
-    write_header(&mut buffer, index.version(), index.len() as u32);
+    write_header(&mut buffer, version, entries.len() as u32);
 
     // Write entries
-    for entry in index.entries() {
-        write_entry(&mut buffer, entry);
+    let mut previous_name: Vec<u8> = Vec::new();
+    for (entry, strip_name) in &entries {
+        let name = if *strip_name {
+            Vec::new()
+        } else {
+            path::to_bytes(entry.path()).into_owned()
+        };
+        if version >= 4 {
+            write_entry_v4(&mut buffer, entry, &name, &previous_name);
+            previous_name = name;
+        } else {
+            write_entry(&mut buffer, entry, &name);
+        }
     }
 
+    write_extensions(&mut buffer, index, entries.len(), link);
+
     // Calculate and append checksum
-    let checksum = sha1(&buffer);
+    let checksum = digest(index.object_format(), &buffer);
     buffer.extend_from_slice(&checksum);
 
     buffer
@@ -51,22 +88,118 @@ fn write_header(buffer: &mut Vec<u8>, version: u32, entry_count: u32) {
     buffer.extend_from_slice(&entry_count.to_be_bytes());
 }
 
-/// Writes a single index entry.
+/// Writes the extensions following the entries.
+///
+/// `EOIE` and `IEOT` are regenerated for the new layout: the offset table
+/// describes all `entry_count` written entries as a single block, and
+/// `EOIE` is always last.
+fn write_extensions(
+    buffer: &mut Vec<u8>,
+    index: &Index,
+    entry_count: usize,
+    link: Option<Vec<u8>>,
+) {
+    let entries_end = buffer.len();
+    let mut extensions: Vec<(&[u8; 4], Vec<u8>)> = Vec::new();
+
+    if index.entry_offsets && index.end_of_index && entry_count > 0 {
+        let mut data = Vec::new();
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(&12u32.to_be_bytes());
+        data.extend_from_slice(&(entry_count as u32).to_be_bytes());
+        extensions.push((SIG_IEOT, data));
+    }
+    if let Some(link) = link {
+        extensions.push((SIG_LINK, link));
+    }
+    if let Some(cache_tree) = &index.cache_tree {
+        let mut data = Vec::new();
+        cache_tree.write(&mut data);
+        extensions.push((SIG_TREE, data));
+    }
+    if !index.resolve_undo.is_empty() {
+        let mut data = Vec::new();
+        for entry in &index.resolve_undo {
+            entry.write(&mut data);
+        }
+        extensions.push((SIG_REUC, data));
+    }
+    if let Some(untracked_cache) = &index.untracked_cache {
+        extensions.push((SIG_UNTR, untracked_cache.data().to_vec()));
+    }
+    for raw in &index.unknown_extensions {
+        extensions.push((raw.signature(), raw.data().to_vec()));
+    }
+
+    // The EOIE hash covers the header of every other extension
+    let mut headers = Vec::new();
+    for (signature, data) in &extensions {
+        let start = buffer.len();
+        buffer.extend_from_slice(*signature);
+        buffer.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        headers.extend_from_slice(&buffer[start..]);
+        buffer.extend_from_slice(data);
+    }
+
+    if index.end_of_index {
+        let hash = digest(index.object_format(), &headers);
+        buffer.extend_from_slice(SIG_EOIE);
+        buffer.extend_from_slice(&(4 + hash.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(&(entries_end as u32).to_be_bytes());
+        buffer.extend_from_slice(&hash);
+    }
+}
+
+/// Writes a single index entry in the v2/v3 format.
 ///
 /// Each entry has:
-/// - Fixed fields (62 bytes for v2)
+/// - Fixed fields (62 bytes, plus 2 bytes of extended flags if needed)
 /// - Variable-length name (NUL-terminated)
 /// - Padding to 8-byte boundary
-fn write_entry(buffer: &mut Vec<u8>, entry: &IndexEntry) {
+fn write_entry(buffer: &mut Vec<u8>, entry: &IndexEntry, name: &[u8]) {
     let entry_start = buffer.len();
 
+    write_entry_fields(buffer, entry, name);
+
+    // name
+    buffer.extend_from_slice(name);
+
+    // Padding to 8-byte boundary
+    // Entry size is padded to a multiple of 8 bytes
+    // There's at least 1 NUL byte after the name
+    let entry_size = buffer.len() - entry_start;
+    let padding = (8 - (entry_size % 8)) % 8;
+    let padding = if padding == 0 { 8 } else { padding };
+    buffer.extend(std::iter::repeat(0u8).take(padding));
+}
+
+/// Writes a single index entry in the v4 format.
+///
+/// The name is stored as the number of bytes to remove from the end of
+/// the previous entry's name, followed by the NUL-terminated suffix to
+/// append. Entries are not padded.
+fn write_entry_v4(buffer: &mut Vec<u8>, entry: &IndexEntry, name: &[u8], previous_name: &[u8]) {
+    write_entry_fields(buffer, entry, name);
+
+    let common = name
+        .iter()
+        .zip(previous_name)
+        .take_while(|(a, b)| a == b)
+        .count();
+    encode_varint(previous_name.len() - common, buffer);
+    buffer.extend_from_slice(&name[common..]);
+    buffer.push(0);
+}
+
+/// Writes the fixed-size fields of an entry, up to and including the flags.
+fn write_entry_fields(buffer: &mut Vec<u8>, entry: &IndexEntry, name: &[u8]) {
     // ctime (seconds and nanoseconds)
     buffer.extend_from_slice(&(entry.ctime() as u32).to_be_bytes());
-    buffer.extend_from_slice(&0u32.to_be_bytes()); // ctime_nsec
+    buffer.extend_from_slice(&entry.ctime_nsec().to_be_bytes());
 
     // mtime (seconds and nanoseconds)
     buffer.extend_from_slice(&(entry.mtime() as u32).to_be_bytes());
-    buffer.extend_from_slice(&0u32.to_be_bytes()); // mtime_nsec
+    buffer.extend_from_slice(&entry.mtime_nsec().to_be_bytes());
 
     // dev
     buffer.extend_from_slice(&entry.dev().to_be_bytes());
@@ -87,26 +220,32 @@ fn write_entry(buffer: &mut Vec<u8>, entry: &IndexEntry) {
     // file size
     buffer.extend_from_slice(&entry.size().to_be_bytes());
 
-    // SHA-1
+    // Object ID
     buffer.extend_from_slice(entry.oid().as_bytes());
 
-    // flags (name length in lower 12 bits, stage in bits 12-13)
-    let path_bytes = path_to_unix_bytes(entry.path());
-    let name_len = path_bytes.len().min(0xFFF) as u16;
-    let stage = (entry.stage() as u16) << 12;
-    let flags = name_len | stage;
+    // flags (name length in lower 12 bits, stage in bits 12-13,
+    // extended in bit 14, assume-valid in bit 15)
+    let mut flags = name.len().min(FLAG_NAME_MASK as usize) as u16;
+    flags |= (entry.stage() as u16) << 12;
+    if entry.has_extended_flags() {
+        flags |= FLAG_EXTENDED;
+    }
+    if entry.is_assume_valid() {
+        flags |= FLAG_ASSUME_VALID;
+    }
     buffer.extend_from_slice(&flags.to_be_bytes());
 
-    // name
-    buffer.extend_from_slice(&path_bytes);
-
-    // Padding to 8-byte boundary
-    // Entry size is padded to a multiple of 8 bytes
-    // There's at least 1 NUL byte after the name
-    let entry_size = buffer.len() - entry_start;
-    let padding = (8 - (entry_size % 8)) % 8;
-    let padding = if padding == 0 { 8 } else { padding };
-    buffer.extend(std::iter::repeat(0u8).take(padding));
+    // extended flags (v3+)
+    if entry.has_extended_flags() {
+        let mut extended = 0u16;
+        if entry.is_skip_worktree() {
+            extended |= EXTENDED_SKIP_WORKTREE;
+        }
+        if entry.is_intent_to_add() {
+            extended |= EXTENDED_INTENT_TO_ADD;
+        }
+        buffer.extend_from_slice(&extended.to_be_bytes());
+    }
 }
 
 /// Converts a FileMode to its u32 representation.
@@ -120,19 +259,11 @@ fn file_mode_to_u32(mode: FileMode) -> u32 {
     }
 }
 
-/// Converts a path to Unix-style bytes (forward slashes).
-fn path_to_unix_bytes(path: &Path) -> Vec<u8> {
-    // Convert to Unix-style path with forward slashes
-    let path_str = path.to_string_lossy();
-    let unix_path = path_str.replace('\\', "/");
-    unix_path.into_bytes()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::index::reader::parse;
-    use crate::infra::hash::SHA1_SIZE;
+    use crate::infra::hash::{sha1, SHA1_SIZE};
     use crate::objects::Oid;
     use std::path::PathBuf;
 
@@ -328,18 +459,39 @@ mod tests {
     }
 
     // IW-009: Path with Windows separators
+    #[cfg(windows)]
     #[test]
     fn test_path_to_unix_bytes() {
-        let path = Path::new("dir\\subdir\\file.txt");
-        let bytes = path_to_unix_bytes(path);
-        assert_eq!(bytes, b"dir/subdir/file.txt");
+        let path = std::path::Path::new("dir\\subdir\\file.txt");
+        let bytes = path::to_bytes(path);
+        assert_eq!(&bytes[..], b"dir/subdir/file.txt");
+    }
+
+    // IW-009: Names are written byte for byte on Unix, where `\` and
+    // non-UTF-8 bytes are valid in file names
+    #[cfg(unix)]
+    #[test]
+    fn test_write_raw_names() {
+        let names: [&[u8]; 2] = [b"back\\slash", b"caf\xe9.txt"];
+        let entries = names
+            .iter()
+            .map(|name| IndexEntry {
+                path: path::from_bytes(name),
+                ..make_entry("x")
+            })
+            .collect();
+        let data = write(&Index::new(2, entries));
+        let parsed = parse(&data).unwrap();
+        let written: Vec<_> = parsed.iter().map(|e| e.path_bytes().into_owned()).collect();
+        assert_eq!(written, names);
     }
 
     // IW-010: Version 3 index
     #[test]
     fn test_write_v3_index() {
-        let entries = vec![make_entry("file.txt")];
-        let index = Index::new(3, entries);
+        let mut entry = make_entry("file.txt");
+        entry.skip_worktree = true;
+        let index = Index::new(3, vec![entry]);
         let data = write(&index);
 
         // Verify version
@@ -350,6 +502,18 @@ mod tests {
         assert_eq!(parsed.version(), 3);
     }
 
+    // IW-012: Full stat data (including nanoseconds) survives a roundtrip
+    #[test]
+    fn test_roundtrip_stat_data() {
+        let mut entry = make_entry("file.txt");
+        entry.ctime_nsec = 111_111_111;
+        entry.mtime_nsec = 999_999_999;
+        let index = Index::new(2, vec![entry.clone()]);
+
+        let parsed = parse(&write(&index)).unwrap();
+        assert_eq!(parsed.entries()[0], entry);
+    }
+
     // IW-011: Padding alignment
     #[test]
     fn test_entry_padding() {
@@ -367,4 +531,150 @@ mod tests {
         assert!(parse(&short_data).is_ok());
         assert!(parse(&long_data).is_ok());
     }
+
+    // IW-013: Extensions survive a roundtrip; EOIE and IEOT are regenerated
+    #[test]
+    fn test_roundtrip_extensions() {
+        let mut data = write(&Index::new(
+            2,
+            vec![make_entry("a.txt"), make_entry("b/c.txt")],
+        ));
+        data.truncate(data.len() - SHA1_SIZE);
+        let entries_end = data.len();
+
+        // Append TREE (invalid root), an unknown optional extension, IEOT and EOIE
+        let mut append = |signature: &[u8], ext: &[u8]| {
+            data.extend_from_slice(signature);
+            data.extend_from_slice(&(ext.len() as u32).to_be_bytes());
+            data.extend_from_slice(ext);
+        };
+        append(b"TREE", b"\0-1 0\n");
+        append(b"ZZZZ", b"opaque");
+        append(b"IEOT", &[0; 12]);
+        append(b"EOIE", &[0; 24]);
+        data.extend_from_slice(&sha1(&data));
+
+        let index = parse(&data).unwrap();
+        assert!(!index.cache_tree().unwrap().is_valid());
+        assert_eq!(index.unknown_extensions().len(), 1);
+        assert_eq!(index.unknown_extensions()[0].signature(), b"ZZZZ");
+        assert_eq!(index.unknown_extensions()[0].data(), b"opaque");
+
+        let written = write(&index);
+        let reparsed = parse(&written).unwrap();
+        assert_eq!(reparsed.unknown_extensions(), index.unknown_extensions());
+        assert_eq!(reparsed.cache_tree(), index.cache_tree());
+
+        // EOIE is last and points at the end of the entries
+        let eoie = &written[written.len() - SHA1_SIZE - 32..written.len() - SHA1_SIZE];
+        assert_eq!(&eoie[..4], b"EOIE");
+        assert_eq!(
+            u32::from_be_bytes(eoie[8..12].try_into().unwrap()) as usize,
+            entries_end
+        );
+        assert_eq!(
+            &written[entries_end..entries_end + 4],
+            b"IEOT",
+            "IEOT comes first"
+        );
+    }
+
+    // IW-015: v4 indexes are written with prefix-compressed names
+    #[test]
+    fn test_roundtrip_v4() {
+        let long = format!("{}/file.txt", "d".repeat(300));
+        let paths = ["a/b/one.txt", "a/b/two.txt", "a/c.txt", long.as_str(), "z"];
+        let entries: Vec<_> = paths.iter().map(|p| make_entry(p)).collect();
+        let index = Index::new(4, entries.clone());
+        let data = write(&index);
+
+        assert_eq!(u32::from_be_bytes(data[4..8].try_into().unwrap()), 4);
+        // The second name only stores the differing suffix: strip 7
+        // bytes ("one.txt") and append "two.txt"
+        let second = 12 + 62 + 1 + "a/b/one.txt".len() + 1 + 62;
+        assert_eq!(data[second], 7);
+        assert_eq!(&data[second + 1..second + 9], b"two.txt\0");
+
+        let parsed = parse(&data).unwrap();
+        assert_eq!(parsed.version(), 4);
+        assert_eq!(parsed.entries(), entries.as_slice());
+        assert!(data.len() < write(&Index::new(2, entries)).len());
+    }
+
+    // IW-016: Extended flags round trip and choose between v2 and v3
+    #[test]
+    fn test_roundtrip_extended_flags() {
+        let mut flagged = make_entry("sparse.txt");
+        flagged.skip_worktree = true;
+        flagged.assume_valid = true;
+        let mut added = make_entry("new.txt");
+        added.intent_to_add = true;
+        let entries = vec![added, flagged, make_entry("plain.txt")];
+
+        let data = write(&Index::new(2, entries.clone()));
+        assert_eq!(u32::from_be_bytes(data[4..8].try_into().unwrap()), 3);
+        let parsed = parse(&data).unwrap();
+        assert_eq!(parsed.entries(), entries.as_slice());
+
+        let parsed = parse(&write(&Index::new(4, entries.clone()))).unwrap();
+        assert_eq!(parsed.entries(), entries.as_slice());
+
+        // Without extended flags a v2 index stays v2, and a v3 index is
+        // downgraded to v2
+        for version in [2, 3] {
+            let data = write(&Index::new(version, vec![make_entry("plain.txt")]));
+            assert_eq!(u32::from_be_bytes(data[4..8].try_into().unwrap()), 2);
+        }
+    }
+
+    // IW-017: SHA-256 indexes use 32-byte object IDs and checksum
+    #[test]
+    fn test_roundtrip_sha256() {
+        use crate::index::reader::parse_with_format;
+        use crate::infra::sha256::sha256;
+        use crate::objects::ObjectFormat;
+
+        let oid = Oid::from_slice(&sha256(b"content")).unwrap();
+        let entry = IndexEntry::new(
+            1700000000,
+            1700000001,
+            100,
+            12345,
+            FileMode::Regular,
+            1000,
+            1000,
+            42,
+            oid,
+            PathBuf::from("a.txt"),
+            0,
+        );
+        let mut index = Index::new(2, vec![entry]);
+        index.set_object_format(ObjectFormat::Sha256);
+        index.cache_tree_mut().set_valid(1, oid);
+
+        let data = write(&index);
+        let (body, checksum) = data.split_at(data.len() - 32);
+        assert_eq!(checksum, sha256(body));
+
+        let parsed = parse_with_format(&data, ObjectFormat::Sha256).unwrap();
+        assert_eq!(parsed.object_format(), ObjectFormat::Sha256);
+        assert_eq!(parsed.entries()[0].oid(), &oid);
+        assert_eq!(parsed.cache_tree().unwrap().oid(), Some(&oid));
+        assert!(parse(&data).is_err());
+    }
+
+    // IW-014: Unknown required extensions are rejected
+    #[test]
+    fn test_required_extension_rejected() {
+        let mut data = write(&Index::new(2, vec![make_entry("a.txt")]));
+        data.truncate(data.len() - SHA1_SIZE);
+        data.extend_from_slice(b"zzzz");
+        data.extend_from_slice(&0u32.to_be_bytes());
+        data.extend_from_slice(&sha1(&data));
+
+        assert!(matches!(
+            parse(&data),
+            Err(crate::error::Error::InvalidIndex { .. })
+        ));
+    }
 }
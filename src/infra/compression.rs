@@ -1,7 +1,19 @@
 //! Zlib compression and decompression utilities.
 
+use std::io::{self, Read, Write};
+
+use miniz_oxide::deflate::core::{create_comp_flags_from_zip_params, CompressorOxide};
+use miniz_oxide::inflate::stream::InflateState;
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
+
 use crate::error::{Error, Result};
 
+/// Compression level used for objects (zlib's default).
+const LEVEL: i32 = 6;
+
+/// Size of the buffers used when streaming.
+const CHUNK_SIZE: usize = 64 * 1024;
+
 /// Compresses data using zlib.
 ///
 /// This function compresses the input data using the DEFLATE algorithm
@@ -16,7 +28,7 @@ use crate::error::{Error, Result};
 /// The compressed data as a byte vector.
 pub fn compress(data: &[u8]) -> Vec<u8> {
     // Use compression level 6 (default, good balance of speed and size)
-    miniz_oxide::deflate::compress_to_vec_zlib(data, 6)
+    miniz_oxide::deflate::compress_to_vec_zlib(data, LEVEL as u8)
 }
 
 /// Decompresses zlib-compressed data.
@@ -87,6 +99,159 @@ fn is_valid_zlib_header(cmf: u8, flg: u8) -> bool {
     check % 31 == 0
 }
 
+/// A reader that decompresses a zlib stream incrementally.
+///
+/// Only a fixed-size buffer of compressed input is held, so arbitrarily
+/// large streams can be read. Corrupt or truncated data is reported as an
+/// `io::ErrorKind::InvalidData` or `io::ErrorKind::UnexpectedEof` error.
+pub struct ZlibReader<R> {
+    inner: R,
+    state: Box<InflateState>,
+    input: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    eof: bool,
+    finished: bool,
+}
+
+impl<R: Read> ZlibReader<R> {
+    /// Creates a reader decompressing the zlib stream read from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(inner, CHUNK_SIZE)
+    }
+
+    /// Creates a reader that reads at most `capacity` compressed bytes
+    /// from `inner` at a time.
+    ///
+    /// A small capacity avoids reading far ahead when only the start of
+    /// the stream is needed.
+    pub fn with_capacity(inner: R, capacity: usize) -> Self {
+        ZlibReader {
+            inner,
+            state: InflateState::new_boxed(DataFormat::Zlib),
+            input: vec![0; capacity.max(1)].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+            eof: false,
+            finished: false,
+        }
+    }
+}
+
+impl<R: Read> Read for ZlibReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.finished {
+            return Ok(0);
+        }
+
+        loop {
+            if self.pos == self.len && !self.eof {
+                self.len = self.inner.read(&mut self.input)?;
+                self.pos = 0;
+                self.eof = self.len == 0;
+            }
+
+            let flush = if self.eof {
+                MZFlush::Finish
+            } else {
+                MZFlush::None
+            };
+            let input = &self.input[self.pos..self.len];
+            let result = miniz_oxide::inflate::stream::inflate(&mut self.state, input, buf, flush);
+            self.pos += result.bytes_consumed;
+
+            match result.status {
+                Ok(MZStatus::StreamEnd) => {
+                    self.finished = true;
+                    return Ok(result.bytes_written);
+                }
+                _ if result.bytes_written > 0 => return Ok(result.bytes_written),
+                Ok(_) | Err(MZError::Buf) if !self.eof => {}
+                Ok(_) | Err(MZError::Buf) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "truncated zlib stream",
+                    ))
+                }
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "corrupt zlib stream",
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// A writer that compresses data into a zlib stream incrementally.
+///
+/// [`ZlibWriter::finish`] must be called to write the end of the stream.
+pub struct ZlibWriter<W: Write> {
+    inner: W,
+    compressor: Box<CompressorOxide>,
+    output: Box<[u8]>,
+}
+
+impl<W: Write> ZlibWriter<W> {
+    /// Creates a writer compressing into `inner`.
+    pub fn new(inner: W) -> Self {
+        let flags = create_comp_flags_from_zip_params(LEVEL, 1, 0);
+        ZlibWriter {
+            inner,
+            compressor: Box::new(CompressorOxide::new(flags)),
+            output: vec![0; CHUNK_SIZE].into_boxed_slice(),
+        }
+    }
+
+    /// Compresses `data`, writing output as the buffer fills. With
+    /// `MZFlush::Finish`, runs until the end of the stream is written.
+    fn deflate(&mut self, mut data: &[u8], flush: MZFlush) -> io::Result<()> {
+        loop {
+            let result = miniz_oxide::deflate::stream::deflate(
+                &mut self.compressor,
+                data,
+                &mut self.output,
+                flush,
+            );
+            self.inner.write_all(&self.output[..result.bytes_written])?;
+            data = &data[result.bytes_consumed..];
+
+            match result.status {
+                Ok(MZStatus::StreamEnd) => return Ok(()),
+                Ok(_) | Err(MZError::Buf) if data.is_empty() && flush == MZFlush::None => {
+                    return Ok(())
+                }
+                Ok(_) => {}
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "zlib compression failed",
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Writes the end of the stream and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.deflate(&[], MZFlush::Finish)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ZlibWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.deflate(data, MZFlush::None)?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -235,4 +400,50 @@ mod tests {
         let compressed = compress(&original);
         assert!(compressed.len() < original.len());
     }
+
+    // C-009: ZlibWriter and ZlibReader round trip in small pieces
+    #[test]
+    fn test_stream_roundtrip() {
+        use std::io::{Read, Write};
+
+        let original: Vec<u8> = (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let mut writer = ZlibWriter::new(Vec::new());
+        for chunk in original.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let compressed = writer.finish().unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), original);
+
+        let mut reader = ZlibReader::new(compressed.as_slice());
+        let mut decompressed = Vec::new();
+        let mut buf = [0u8; 777];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            decompressed.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(decompressed, original);
+    }
+
+    // C-010: ZlibReader reports truncated and corrupted streams
+    #[test]
+    fn test_stream_reader_errors() {
+        use std::io::{ErrorKind, Read};
+
+        let compressed = compress(b"Hello, World! Hello, World!");
+        let mut out = Vec::new();
+        let err = ZlibReader::new(&compressed[..compressed.len() / 2])
+            .read_to_end(&mut out)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        let mut corrupted = compressed.clone();
+        corrupted[0] = 0;
+        let err = ZlibReader::new(corrupted.as_slice())
+            .read_to_end(&mut out)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
 }
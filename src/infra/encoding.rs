@@ -0,0 +1,150 @@
+//! Decoding of commit and tag messages.
+//!
+//! Git stores messages as the bytes the author typed. When they are not
+//! UTF-8, the object carries an `encoding` header naming the encoding
+//! (`i18n.commitEncoding`). UTF-8, ISO-8859-1, ISO-8859-15 and
+//! Windows-1252 are supported and decoded exactly. Other encodings are
+//! reported as unsupported by [`is_supported`]; their content, like
+//! invalid UTF-8, is decoded lossily as UTF-8 so that reading history never
+//! fails on a message.
+
+use std::borrow::Cow;
+
+/// Windows-1252 characters for bytes 0x80 to 0x9F (unassigned bytes map to
+/// the C1 control with the same value).
+const CP1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+/// Returns the value of the `encoding` header of a commit or tag object.
+///
+/// # Arguments
+///
+/// * `content` - The object content (headers, blank line, message).
+pub fn header_encoding(content: &[u8]) -> Option<&str> {
+    content
+        .split(|&b| b == b'\n')
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| line.strip_prefix(b"encoding "))
+        .and_then(|value| std::str::from_utf8(value).ok())
+}
+
+/// A supported encoding.
+enum Charset {
+    Utf8,
+    /// A single-byte encoding that matches ASCII below 0x80, with the
+    /// characters of the other bytes.
+    SingleByte(fn(u8) -> char),
+}
+
+/// Looks up an encoding by name, ignoring case, `-` and `_`.
+fn charset(encoding: &str) -> Option<Charset> {
+    let name: String = encoding
+        .chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    match name.as_str() {
+        "utf8" => Some(Charset::Utf8),
+        "iso88591" | "latin1" | "l1" => Some(Charset::SingleByte(char::from)),
+        "iso885915" | "latin9" => Some(Charset::SingleByte(latin9)),
+        "cp1252" | "windows1252" => Some(Charset::SingleByte(|b| match b {
+            0x80..=0x9f => CP1252_HIGH[usize::from(b - 0x80)],
+            _ => char::from(b),
+        })),
+        _ => None,
+    }
+}
+
+/// Returns `true` if content in the named encoding is decoded exactly.
+///
+/// The supported encodings are UTF-8, ISO-8859-1 (`latin1`), ISO-8859-15
+/// (`latin9`) and Windows-1252 (`cp1252`).
+pub fn is_supported(encoding: &str) -> bool {
+    charset(encoding).is_some()
+}
+
+/// Decodes object content written in the given encoding.
+///
+/// Content in an unsupported encoding is decoded lossily as UTF-8.
+///
+/// # Arguments
+///
+/// * `content` - The bytes to decode.
+/// * `encoding` - The encoding name from the `encoding` header, or `None`
+///   for UTF-8.
+pub fn decode<'a>(content: &'a [u8], encoding: Option<&str>) -> Cow<'a, str> {
+    let high = match charset(encoding.unwrap_or("UTF-8")) {
+        Some(Charset::SingleByte(high)) => high,
+        Some(Charset::Utf8) | None => return String::from_utf8_lossy(content),
+    };
+
+    if content.is_ascii() {
+        return Cow::Borrowed(std::str::from_utf8(content).expect("ASCII is UTF-8"));
+    }
+    Cow::Owned(content.iter().map(|&b| high(b)).collect())
+}
+
+/// Maps an ISO-8859-15 byte to its character.
+fn latin9(b: u8) -> char {
+    match b {
+        0xa4 => '\u{20AC}',
+        0xa6 => '\u{160}',
+        0xa8 => '\u{161}',
+        0xb4 => '\u{17D}',
+        0xb8 => '\u{17E}',
+        0xbc => '\u{152}',
+        0xbd => '\u{153}',
+        0xbe => '\u{178}',
+        _ => char::from(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // EN-001: The encoding header is found among the headers only
+    #[test]
+    fn test_header_encoding() {
+        let content = b"tree 1\nencoding ISO-8859-1\n\nencoding UTF-8\n";
+        assert_eq!(header_encoding(content), Some("ISO-8859-1"));
+        assert_eq!(header_encoding(b"tree 1\n\nencoding x\n"), None);
+    }
+
+    // EN-002: Single-byte encodings are decoded exactly
+    #[test]
+    fn test_decode_single_byte() {
+        assert_eq!(decode(b"Jos\xe9", Some("ISO-8859-1")), "José");
+        assert_eq!(decode(b"Jos\xe9", Some("latin1")), "José");
+        assert_eq!(decode(b"\x80 \xa4", Some("iso-8859-15")), "\u{80} €");
+        assert_eq!(decode(b"\x80 \x93q\x94", Some("windows-1252")), "€ “q”");
+        assert!(matches!(
+            decode(b"plain", Some("ISO-8859-1")),
+            Cow::Borrowed("plain")
+        ));
+    }
+
+    // EN-003: UTF-8 and unknown encodings never fail
+    #[test]
+    fn test_decode_utf8_and_unknown() {
+        assert_eq!(decode("José".as_bytes(), None), "José");
+        assert_eq!(decode(b"Jos\xe9", None), "Jos\u{FFFD}");
+        assert_eq!(decode(b"Jos\xe9", Some("x-unknown")), "Jos\u{FFFD}");
+    }
+
+    // EN-004: Unknown encodings are reported as unsupported
+    #[test]
+    fn test_is_supported() {
+        for name in ["UTF-8", "utf8", "ISO-8859-1", "Latin-9", "CP1252"] {
+            assert!(is_supported(name), "{}", name);
+        }
+        for name in ["Shift_JIS", "EUC-JP", "x-unknown", ""] {
+            assert!(!is_supported(name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! EWAH compressed bitmaps, as serialized by Git's `ewah/ewah_io.c`.
+//!
+//! A serialized bitmap is:
+//!
+//! - 4 bytes: the number of bits (big-endian)
+//! - 4 bytes: the number of 64-bit words that follow (big-endian)
+//! - the words (big-endian)
+//! - 4 bytes: the position of the last marker word (big-endian)
+//!
+//! The words are a sequence of marker words, each followed by literal
+//! words. A marker word holds a run bit (bit 0), the number of words in
+//! the run of that bit (bits 1-32) and the number of literal words that
+//! follow (bits 33-63). Bit `i` of the bitmap is bit `i % 64` of the
+//! `i / 64`-th uncompressed word.
+
+/// Number of bits in a word.
+const WORD_BITS: usize = 64;
+
+/// Largest run length a marker word can hold.
+const MAX_RUN: u64 = (1 << 32) - 1;
+
+/// Largest literal word count a marker word can hold.
+const MAX_LITERALS: u64 = (1 << 31) - 1;
+
+/// Decodes a serialized bitmap.
+///
+/// # Arguments
+///
+/// * `data` - The bytes to read from.
+/// * `pos` - The offset to start at; advanced past the bitmap.
+///
+/// # Returns
+///
+/// The positions of the set bits in ascending order, or `None` if the
+/// data is truncated or malformed.
+pub fn decode(data: &[u8], pos: &mut usize) -> Option<Vec<usize>> {
+    let bit_size = read_u32(data, pos)? as usize;
+    let word_count = read_u32(data, pos)? as usize;
+    let words_start = *pos;
+    let words_end = words_start.checked_add(word_count.checked_mul(8)?)?;
+    let words = data.get(words_start..words_end)?;
+    *pos = words_end;
+    let _last_marker = read_u32(data, pos)?;
+
+    let mut words = words
+        .chunks_exact(8)
+        .map(|w| u64::from_be_bytes(w.try_into().expect("8 bytes")));
+    let mut bits = Vec::new();
+    let mut offset = 0usize;
+    while let Some(marker) = words.next() {
+        let run_bit = marker & 1 != 0;
+        let run_len = ((marker >> 1) & MAX_RUN) as usize;
+        let literals = (marker >> 33) as usize;
+
+        let run_bits = run_len.checked_mul(WORD_BITS)?;
+        if run_bit {
+            bits.extend(offset..offset.checked_add(run_bits)?);
+        }
+        offset = offset.checked_add(run_bits)?;
+
+        for _ in 0..literals {
+            let word = words.next()?;
+            bits.extend(
+                (0..WORD_BITS)
+                    .filter(|i| word & (1 << i) != 0)
+                    .map(|i| offset + i),
+            );
+            offset += WORD_BITS;
+        }
+    }
+
+    // Runs of set bits may extend past the logical size
+    bits.retain(|&bit| bit < bit_size);
+    Some(bits)
+}
+
+/// Serializes a bitmap.
+///
+/// # Arguments
+///
+/// * `bits` - The positions of the set bits, in ascending order.
+/// * `buffer` - The buffer to append to.
+pub fn encode(bits: &[usize], buffer: &mut Vec<u8>) {
+    let bit_size = bits.last().map_or(0, |&last| last + 1);
+    let mut plain = vec![0u64; (bit_size + WORD_BITS - 1) / WORD_BITS];
+    for &bit in bits {
+        plain[bit / WORD_BITS] |= 1 << (bit % WORD_BITS);
+    }
+
+    let mut words = Vec::new();
+    let mut last_marker;
+    let mut i = 0;
+    loop {
+        // A run of all-zero or all-one words, then the literal words up
+        // to the next run
+        let run_word = plain.get(i).copied().filter(|&w| w == 0 || w == u64::MAX);
+        let run_bit = run_word == Some(u64::MAX);
+        let mut run_len = 0;
+        while run_len < MAX_RUN && plain.get(i) == run_word.as_ref() && run_word.is_some() {
+            run_len += 1;
+            i += 1;
+        }
+        let literals_start = i;
+        while (i - literals_start) < MAX_LITERALS as usize
+            && plain.get(i).is_some_and(|&w| w != 0 && w != u64::MAX)
+        {
+            i += 1;
+        }
+
+        last_marker = words.len();
+        let literals = (i - literals_start) as u64;
+        words.push(run_bit as u64 | (run_len << 1) | (literals << 33));
+        words.extend_from_slice(&plain[literals_start..i]);
+        if i >= plain.len() {
+            break;
+        }
+    }
+
+    buffer.extend_from_slice(&(bit_size as u32).to_be_bytes());
+    buffer.extend_from_slice(&(words.len() as u32).to_be_bytes());
+    for word in &words {
+        buffer.extend_from_slice(&word.to_be_bytes());
+    }
+    buffer.extend_from_slice(&(last_marker as u32).to_be_bytes());
+}
+
+/// Reads a big-endian u32, advancing `pos`.
+fn read_u32(data: &[u8], pos: &mut usize) -> Option<u32> {
+    let bytes = data.get(*pos..*pos + 4)?;
+    *pos += 4;
+    Some(u32::from_be_bytes(bytes.try_into().expect("4 bytes")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(bits: &[usize]) -> Vec<usize> {
+        let mut buffer = Vec::new();
+        encode(bits, &mut buffer);
+        let mut pos = 0;
+        let decoded = decode(&buffer, &mut pos).unwrap();
+        assert_eq!(pos, buffer.len());
+        decoded
+    }
+
+    // EW-001: Bitmaps round trip
+    #[test]
+    fn test_roundtrip() {
+        assert_eq!(roundtrip(&[]), Vec::<usize>::new());
+        assert_eq!(roundtrip(&[0]), [0]);
+        assert_eq!(roundtrip(&[1, 5, 63, 64, 1000]), [1, 5, 63, 64, 1000]);
+
+        // Long runs of set and clear words
+        let dense: Vec<usize> = (0..640).chain(5000..5003).collect();
+        assert_eq!(roundtrip(&dense), dense);
+    }
+
+    // EW-002: Runs are compressed
+    #[test]
+    fn test_encode_compresses_runs() {
+        let mut buffer = Vec::new();
+        encode(&[100_000], &mut buffer);
+        // Header, one marker, one literal, marker position
+        assert_eq!(buffer.len(), 8 + 16 + 4);
+        assert_eq!(
+            u32::from_be_bytes(buffer[0..4].try_into().unwrap()),
+            100_001
+        );
+    }
+
+    // EW-003: An empty bitmap as written by Git decodes
+    #[test]
+    fn test_decode_empty() {
+        let data = [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut pos = 0;
+        assert_eq!(decode(&data, &mut pos), Some(Vec::new()));
+        assert_eq!(pos, data.len());
+
+        let mut pos = 0;
+        assert_eq!(decode(&data[..10], &mut pos), None);
+    }
+}
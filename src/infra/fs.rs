@@ -3,6 +3,9 @@
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
 
 use crate::error::{Error, Result};
 
@@ -50,16 +53,7 @@ pub fn write_file_atomic<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<()> {
         }
     }
 
-    // Create a temporary file in the same directory
-    let temp_path = {
-        let mut temp = path.to_path_buf();
-        let file_name = path
-            .file_name()
-            .map(|s| s.to_string_lossy().to_string())
-            .unwrap_or_else(|| "temp".to_string());
-        temp.set_file_name(format!(".{}.tmp", file_name));
-        temp
-    };
+    let temp_path = temp_path(path);
 
     // Write to temporary file
     {
@@ -74,6 +68,45 @@ pub fn write_file_atomic<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<()> {
     Ok(())
 }
 
+/// Returns the path of a temporary file next to `path`, to be renamed to
+/// it once written.
+///
+/// The name is unique per process and call, so concurrent writers never
+/// share a temporary file.
+pub fn temp_path(path: &Path) -> PathBuf {
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let mut temp = path.to_path_buf();
+    let file_name = path
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "temp".to_string());
+    temp.set_file_name(format!(
+        ".{}.{}.{}.tmp",
+        file_name,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    temp
+}
+
+/// Removes a file, even if it is read-only.
+///
+/// Object files are read-only, which Windows, unlike Unix, refuses to
+/// remove.
+pub fn remove_file<P: AsRef<Path>>(path: P) -> std::io::Result<()> {
+    let path = path.as_ref();
+    #[cfg(windows)]
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        let mut permissions = metadata.permissions();
+        if permissions.readonly() {
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            fs::set_permissions(path, permissions)?;
+        }
+    }
+    fs::remove_file(path)
+}
+
 /// Lists all files in the working tree, excluding `.git` directory.
 ///
 /// Returns paths relative to the given root directory.
@@ -102,6 +135,115 @@ fn list_working_tree_recursive(
     current: &Path,
     files: &mut Vec<PathBuf>,
 ) -> Result<()> {
+    let (dir_files, subdirs) = scan_dir(root, current)?;
+    files.extend(dir_files);
+
+    for subdir in subdirs {
+        list_working_tree_recursive(root, &subdir, files)?;
+    }
+
+    Ok(())
+}
+
+/// Lists all files in the working tree using multiple threads.
+///
+/// Directories are read concurrently by a pool of `threads` workers. The
+/// result is sorted, so it is identical to [`list_working_tree`].
+///
+/// # Arguments
+///
+/// * `root` - The root directory to traverse.
+/// * `threads` - The number of worker threads (`0` = available CPUs).
+///
+/// # Returns
+///
+/// A vector of relative paths to all files in the working tree.
+pub fn list_working_tree_parallel<P: AsRef<Path>>(root: P, threads: usize) -> Result<Vec<PathBuf>> {
+    let root = root.as_ref();
+    let threads = super::resolve_threads(threads);
+    if threads <= 1 {
+        return list_working_tree(root);
+    }
+
+    /// Directories waiting to be scanned, shared by all workers.
+    struct WorkQueue {
+        dirs: Vec<PathBuf>,
+        /// Number of directories currently being scanned.
+        active: usize,
+        error: Option<Error>,
+    }
+
+    let queue = Mutex::new(WorkQueue {
+        dirs: vec![root.to_path_buf()],
+        active: 0,
+        error: None,
+    });
+    let wakeup = Condvar::new();
+    let files = Mutex::new(Vec::new());
+
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| {
+                let mut local = Vec::new();
+                loop {
+                    // Take the next directory, or stop once the queue is
+                    // drained and no worker can add more.
+                    let dir = {
+                        let mut q = queue.lock().unwrap();
+                        loop {
+                            if q.error.is_some() {
+                                break None;
+                            }
+                            if let Some(dir) = q.dirs.pop() {
+                                q.active += 1;
+                                break Some(dir);
+                            }
+                            if q.active == 0 {
+                                break None;
+                            }
+                            q = wakeup.wait(q).unwrap();
+                        }
+                    };
+                    let Some(dir) = dir else {
+                        wakeup.notify_all();
+                        break;
+                    };
+
+                    let scanned = scan_dir(root, &dir);
+
+                    let mut q = queue.lock().unwrap();
+                    q.active -= 1;
+                    match scanned {
+                        Ok((dir_files, subdirs)) => {
+                            local.extend(dir_files);
+                            q.dirs.extend(subdirs);
+                        }
+                        Err(e) => {
+                            q.error.get_or_insert(e);
+                        }
+                    }
+                    drop(q);
+                    wakeup.notify_all();
+                }
+                files.lock().unwrap().extend(local);
+            });
+        }
+    });
+
+    if let Some(e) = queue.into_inner().unwrap().error {
+        return Err(e);
+    }
+
+    let mut files = files.into_inner().unwrap();
+    files.sort();
+    Ok(files)
+}
+
+/// Reads a single directory of the working tree.
+///
+/// Returns the files in it (relative to `root`) and its subdirectories
+/// (as full paths), skipping `.git` and hidden entries.
+fn scan_dir(root: &Path, current: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
     let entries = fs::read_dir(current).map_err(|e| {
         if e.kind() == std::io::ErrorKind::NotFound {
             Error::PathNotFound(current.to_path_buf())
@@ -110,6 +252,9 @@ fn list_working_tree_recursive(
         }
     })?;
 
+    let mut files = Vec::new();
+    let mut subdirs = Vec::new();
+
     for entry in entries {
         let entry = entry?;
         let path = entry.path();
@@ -136,12 +281,12 @@ fn list_working_tree_recursive(
                 .map_err(|_| Error::PathNotFound(path.clone()))?;
             files.push(relative.to_path_buf());
         } else if file_type.is_dir() {
-            list_working_tree_recursive(root, &path, files)?;
+            subdirs.push(path);
         }
         // Skip symlinks and other special files
     }
 
-    Ok(())
+    Ok((files, subdirs))
 }
 
 /// Validates that a path does not escape its root directory (path traversal prevention).
@@ -367,4 +512,53 @@ mod tests {
         assert_eq!(files[1], PathBuf::from("m.txt"));
         assert_eq!(files[2], PathBuf::from("z.txt"));
     }
+
+    // FS-013: Parallel listing matches sequential listing
+    #[test]
+    fn test_list_working_tree_parallel_matches_sequential() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join(".git/config"), b"git config").unwrap();
+        fs::write(root.join(".hidden"), b"hidden").unwrap();
+        for d in 0..5 {
+            for sub in 0..3 {
+                let dir = root.join(format!("dir{}/sub{}", d, sub));
+                fs::create_dir_all(&dir).unwrap();
+                for f in 0..4 {
+                    fs::write(dir.join(format!("file{}.txt", f)), b"x").unwrap();
+                }
+            }
+        }
+        fs::write(root.join("top.txt"), b"top").unwrap();
+
+        let sequential = list_working_tree(root).unwrap();
+        let parallel = list_working_tree_parallel(root, 4).unwrap();
+
+        assert_eq!(sequential.len(), 61);
+        assert_eq!(parallel, sequential);
+    }
+
+    // FS-014: Parallel listing of a missing directory fails
+    #[test]
+    fn test_list_working_tree_parallel_not_found() {
+        let result = list_working_tree_parallel("/nonexistent/path", 4);
+        assert!(matches!(result, Err(Error::PathNotFound(_))));
+    }
+
+    // FS-015: Concurrent atomic writes to the same path do not collide
+    #[test]
+    fn test_write_file_atomic_concurrent() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("shared.txt");
+
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| write_file_atomic(&file_path, b"same content").unwrap());
+            }
+        });
+
+        assert_eq!(fs::read(&file_path).unwrap(), b"same content");
+    }
 }
@@ -0,0 +1,514 @@
+//! Flushing written files to disk, as `core.fsync` and `core.fsyncMethod`
+//! ask.
+//!
+//! Repository files are written to a temporary file and renamed into
+//! place. Without a flush, a power loss can leave the renamed file empty
+//! even though the rename itself survived, so the data is flushed before
+//! the rename and the directory after it.
+
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+use super::fs::temp_path;
+use crate::error::Result;
+
+/// A kind of file that `core.fsync` can ask to be flushed to disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsyncComponent {
+    /// Loose object files.
+    LooseObject,
+    /// Packfiles.
+    Pack,
+    /// Pack indexes.
+    PackMetadata,
+    /// The commit-graph.
+    CommitGraph,
+    /// The index and shared indexes.
+    Index,
+    /// Loose refs and `packed-refs`.
+    Reference,
+}
+
+impl FsyncComponent {
+    /// Every component.
+    const ALL: u8 = 0b11_1111;
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// Returns the components named by a `core.fsync` entry, or `None` if
+    /// the name is unknown.
+    fn parse_set(name: &str) -> Option<u8> {
+        use FsyncComponent::*;
+        let objects = LooseObject.bit() | Pack.bit();
+        let committed = objects | Reference.bit();
+        let set = match name {
+            "loose-object" => LooseObject.bit(),
+            "pack" => Pack.bit(),
+            "pack-metadata" => PackMetadata.bit(),
+            "commit-graph" => CommitGraph.bit(),
+            "index" => Index.bit(),
+            "reference" => Reference.bit(),
+            "objects" => objects,
+            "derived-metadata" => PackMetadata.bit() | CommitGraph.bit(),
+            "committed" => committed,
+            "added" => committed | Index.bit(),
+            "all" => Self::ALL,
+            _ => return None,
+        };
+        Some(set)
+    }
+}
+
+/// How files are flushed to disk (`core.fsyncMethod`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FsyncMethod {
+    /// Flushes each file, and the directory it is renamed into.
+    #[default]
+    Fsync,
+    /// Flushes the data of each file, but not the directory.
+    WriteoutOnly,
+    /// Like `Fsync`, except that loose objects are flushed together, with
+    /// each directory flushed once, before the index or a reference is
+    /// updated.
+    Batch,
+}
+
+impl FsyncMethod {
+    /// Parses a `core.fsyncMethod` value.
+    ///
+    /// # Returns
+    ///
+    /// The method, or `None` if the name is unknown.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fsync" => Some(FsyncMethod::Fsync),
+            "writeout-only" => Some(FsyncMethod::WriteoutOnly),
+            "batch" => Some(FsyncMethod::Batch),
+            _ => None,
+        }
+    }
+}
+
+/// Which files are flushed to disk after they are written, and how.
+///
+/// By default every component is flushed with [`FsyncMethod::Fsync`].
+/// This is stricter than Git, whose default (`committed,-loose-object`)
+/// flushes neither loose objects nor the index, so that a power loss
+/// cannot leave an empty object behind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FsyncSettings {
+    components: u8,
+    method: FsyncMethod,
+}
+
+impl Default for FsyncSettings {
+    fn default() -> Self {
+        FsyncSettings {
+            components: FsyncComponent::ALL,
+            method: FsyncMethod::Fsync,
+        }
+    }
+}
+
+impl FsyncSettings {
+    /// Applies a `core.fsync` value: a comma-separated list of components
+    /// to flush, on top of the default.
+    ///
+    /// As in Git, `none` clears the default, a component prefixed with `-`
+    /// is removed from it, and unknown components are ignored.
+    pub fn components(mut self, value: &str) -> Self {
+        let mut base = self.components;
+        let mut added = 0;
+        let mut removed = 0;
+        for name in value.split(',').map(str::trim) {
+            if name == "none" {
+                base = 0;
+            } else if let Some(name) = name.strip_prefix('-') {
+                removed |= FsyncComponent::parse_set(name).unwrap_or(0);
+            } else {
+                added |= FsyncComponent::parse_set(name).unwrap_or(0);
+            }
+        }
+        self.components = (base & !removed) | added;
+        self
+    }
+
+    /// Sets how files are flushed.
+    pub fn method(mut self, method: FsyncMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Returns `true` if files of a component are flushed.
+    pub fn syncs(&self, component: FsyncComponent) -> bool {
+        self.components & component.bit() != 0
+    }
+
+    /// Returns how files of a component are flushed, or `None` if they
+    /// are not. Batch mode only applies to loose objects, as in Git.
+    fn method_for(&self, component: FsyncComponent) -> Option<FsyncMethod> {
+        if !self.syncs(component) {
+            return None;
+        }
+        match self.method {
+            FsyncMethod::Batch if component != FsyncComponent::LooseObject => {
+                Some(FsyncMethod::Fsync)
+            }
+            method => Some(method),
+        }
+    }
+}
+
+/// Loose objects written in batch mode that are not flushed yet.
+#[derive(Debug, Default)]
+struct Pending {
+    files: Vec<PathBuf>,
+    dirs: BTreeSet<PathBuf>,
+}
+
+/// Writes repository files durably, with settings shared by the stores of
+/// a repository.
+#[derive(Debug, Default)]
+pub struct Fsync {
+    settings: RwLock<FsyncSettings>,
+    pending: Mutex<Pending>,
+}
+
+impl Fsync {
+    /// Returns the current settings.
+    pub fn settings(&self) -> FsyncSettings {
+        *self.settings.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Replaces the settings. Loose objects already waiting for a batch
+    /// flush stay pending.
+    pub fn set_settings(&self, settings: FsyncSettings) {
+        *self.settings.write().unwrap_or_else(|e| e.into_inner()) = settings;
+    }
+
+    /// Writes a file atomically, flushing it as the settings ask for its
+    /// component.
+    ///
+    /// # Arguments
+    ///
+    /// * `component` - The kind of file written.
+    /// * `path` - The path to write to. Missing parent directories are
+    ///   created.
+    /// * `data` - The data to write.
+    /// * `read_only` - Whether to make the file read-only, as Git does for
+    ///   object files.
+    pub fn write_file(
+        &self,
+        component: FsyncComponent,
+        path: &Path,
+        data: &[u8],
+        read_only: bool,
+    ) -> Result<()> {
+        let dirs = create_parent_dirs(path)?;
+        let temp = temp_path(path);
+        if let Err(e) = self.write_temp(component, &temp, data, read_only) {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+        fs::rename(&temp, path)?;
+        self.renamed(component, path, dirs)
+    }
+
+    /// Flushes a fully written temporary file as the settings ask and
+    /// renames it to `path`, creating missing parent directories.
+    ///
+    /// # Arguments
+    ///
+    /// * `component` - The kind of file written.
+    /// * `file` - The temporary file, open for writing.
+    /// * `temp` - The path of the temporary file.
+    /// * `path` - The path to rename it to.
+    /// * `read_only` - Whether to make the file read-only.
+    pub fn persist(
+        &self,
+        component: FsyncComponent,
+        file: File,
+        temp: &Path,
+        path: &Path,
+        read_only: bool,
+    ) -> Result<()> {
+        self.prepare(component, &file, read_only)?;
+        drop(file);
+        let dirs = create_parent_dirs(path)?;
+        fs::rename(temp, path)?;
+        self.renamed(component, path, dirs)
+    }
+
+    /// Flushes the loose objects written in batch mode, then each
+    /// directory they were written to, once.
+    ///
+    /// Called before anything that can refer to the objects, such as the
+    /// index or a reference, is written.
+    pub fn flush_batch(&self) -> Result<()> {
+        let pending = {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            std::mem::take(&mut *pending)
+        };
+        for path in &pending.files {
+            match File::open(path) {
+                Ok(file) => file.sync_data()?,
+                // Pruned since it was written
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        for dir in &pending.dirs {
+            sync_dir(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Writes and flushes the temporary file of [`Fsync::write_file`].
+    fn write_temp(
+        &self,
+        component: FsyncComponent,
+        temp: &Path,
+        data: &[u8],
+        read_only: bool,
+    ) -> Result<()> {
+        let mut file = File::create(temp)?;
+        file.write_all(data)?;
+        self.prepare(component, &file, read_only)
+    }
+
+    /// Flushes a written file before its rename and makes it read-only.
+    fn prepare(&self, component: FsyncComponent, file: &File, read_only: bool) -> Result<()> {
+        match self.settings().method_for(component) {
+            Some(FsyncMethod::Fsync) => file.sync_all()?,
+            Some(FsyncMethod::WriteoutOnly) => file.sync_data()?,
+            Some(FsyncMethod::Batch) | None => {}
+        }
+        if read_only {
+            let mut permissions = file.metadata()?.permissions();
+            permissions.set_readonly(true);
+            file.set_permissions(permissions)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the directories whose entries changed when a file was
+    /// renamed into place, or queues them for a batch flush.
+    fn renamed(&self, component: FsyncComponent, path: &Path, dirs: Vec<PathBuf>) -> Result<()> {
+        match self.settings().method_for(component) {
+            Some(FsyncMethod::Fsync) => {
+                for dir in &dirs {
+                    sync_dir(dir)?;
+                }
+            }
+            Some(FsyncMethod::Batch) => {
+                let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+                pending.files.push(path.to_path_buf());
+                pending.dirs.extend(dirs);
+            }
+            Some(FsyncMethod::WriteoutOnly) | None => {}
+        }
+        Ok(())
+    }
+}
+
+/// Creates the missing parent directories of a file.
+///
+/// # Returns
+///
+/// The directories that gain an entry: the parent of the file, each
+/// created directory's parent, up to the first one that existed.
+fn create_parent_dirs(path: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    let mut current = path.parent();
+    while let Some(dir) = current.filter(|dir| !dir.as_os_str().is_empty()) {
+        dirs.push(dir.to_path_buf());
+        if dir.exists() {
+            break;
+        }
+        current = dir.parent();
+    }
+    if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(dirs)
+}
+
+/// Flushes a directory, so that renames into it survive a power loss.
+///
+/// Directories cannot be opened for flushing on Windows, where the
+/// file system does not need it.
+fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn with_settings(settings: FsyncSettings) -> Fsync {
+        let fsync = Fsync::default();
+        fsync.set_settings(settings);
+        fsync
+    }
+
+    // FSY-001: core.fsync lists are applied to the default like Git
+    #[test]
+    fn test_components() {
+        use FsyncComponent::*;
+        let all = [
+            LooseObject,
+            Pack,
+            PackMetadata,
+            CommitGraph,
+            Index,
+            Reference,
+        ];
+        let synced = |value: &str| -> Vec<FsyncComponent> {
+            let settings = FsyncSettings::default().components(value);
+            all.into_iter().filter(|&c| settings.syncs(c)).collect()
+        };
+
+        // Unlike Git's default, loose objects and the index are flushed
+        assert_eq!(synced(""), all);
+        assert_eq!(synced("none"), []);
+        assert_eq!(synced("none,objects"), [LooseObject, Pack]);
+        assert_eq!(
+            synced("none, committed ,-pack"),
+            [LooseObject, Pack, Reference]
+        );
+        assert_eq!(synced("none,added"), [LooseObject, Pack, Index, Reference]);
+        assert_eq!(
+            synced("-derived-metadata,-index"),
+            [LooseObject, Pack, Reference]
+        );
+        assert_eq!(synced("none,unknown,reference"), [Reference]);
+        // Negations apply to the default, not to the components listed
+        assert_eq!(synced("-pack,objects"), all);
+    }
+
+    // FSY-002: core.fsyncMethod names and batch mode for other components
+    #[test]
+    fn test_methods() {
+        assert_eq!(FsyncMethod::from_name("fsync"), Some(FsyncMethod::Fsync));
+        assert_eq!(
+            FsyncMethod::from_name("writeout-only"),
+            Some(FsyncMethod::WriteoutOnly)
+        );
+        assert_eq!(FsyncMethod::from_name("batch"), Some(FsyncMethod::Batch));
+        assert_eq!(FsyncMethod::from_name("sometimes"), None);
+
+        let settings = FsyncSettings::default().method(FsyncMethod::Batch);
+        assert_eq!(
+            settings.method_for(FsyncComponent::LooseObject),
+            Some(FsyncMethod::Batch)
+        );
+        assert_eq!(
+            settings.method_for(FsyncComponent::Reference),
+            Some(FsyncMethod::Fsync)
+        );
+        let settings = settings.components("-index");
+        assert_eq!(settings.method_for(FsyncComponent::Index), None);
+    }
+
+    // FSY-003: Files are written atomically, read-only on request
+    #[test]
+    fn test_write_file() {
+        let temp = TempDir::new().unwrap();
+        for method in [FsyncMethod::Fsync, FsyncMethod::WriteoutOnly] {
+            let fsync = with_settings(FsyncSettings::default().method(method));
+            let path = temp.path().join(format!("{:?}/a/b.txt", method));
+            fsync
+                .write_file(FsyncComponent::LooseObject, &path, b"data", true)
+                .unwrap();
+            assert_eq!(fs::read(&path).unwrap(), b"data");
+            assert!(fs::metadata(&path).unwrap().permissions().readonly());
+            assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+        }
+
+        let fsync = with_settings(FsyncSettings::default().components("none"));
+        let path = temp.path().join("index");
+        fsync
+            .write_file(FsyncComponent::Index, &path, b"old", false)
+            .unwrap();
+        fsync
+            .write_file(FsyncComponent::Index, &path, b"new", false)
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert!(!fs::metadata(&path).unwrap().permissions().readonly());
+    }
+
+    // FSY-004: Batch mode defers loose objects until flushed
+    #[test]
+    fn test_batch() {
+        let temp = TempDir::new().unwrap();
+        let fsync = with_settings(FsyncSettings::default().method(FsyncMethod::Batch));
+        let objects = temp.path().join("objects");
+        for (i, dir) in ["ab", "cd", "ab"].iter().enumerate() {
+            let path = objects.join(dir).join(i.to_string());
+            fsync
+                .write_file(FsyncComponent::LooseObject, &path, b"x", true)
+                .unwrap();
+        }
+        fsync
+            .write_file(
+                FsyncComponent::Reference,
+                &temp.path().join("HEAD"),
+                b"x",
+                false,
+            )
+            .unwrap();
+        {
+            let pending = fsync.pending.lock().unwrap();
+            assert_eq!(pending.files.len(), 3);
+            // The new objects directory is flushed in its parent too
+            let dirs: Vec<&Path> = pending.dirs.iter().map(PathBuf::as_path).collect();
+            assert_eq!(
+                dirs,
+                [
+                    temp.path(),
+                    &objects,
+                    &objects.join("ab"),
+                    &objects.join("cd")
+                ]
+            );
+        }
+
+        fs::remove_file(objects.join("cd/1")).unwrap();
+        fsync.flush_batch().unwrap();
+        assert!(fsync.pending.lock().unwrap().files.is_empty());
+        assert!(fsync.pending.lock().unwrap().dirs.is_empty());
+    }
+
+    // FSY-005: Creating parent directories reports the changed directories
+    #[test]
+    fn test_create_parent_dirs() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("a/b/c.txt");
+        assert_eq!(
+            create_parent_dirs(&path).unwrap(),
+            [
+                temp.path().join("a/b"),
+                temp.path().join("a"),
+                temp.path().to_path_buf()
+            ]
+        );
+        assert!(temp.path().join("a/b").is_dir());
+        assert_eq!(
+            create_parent_dirs(&path).unwrap(),
+            [temp.path().join("a/b")]
+        );
+        assert!(create_parent_dirs(Path::new("file.txt"))
+            .unwrap()
+            .is_empty());
+    }
+}
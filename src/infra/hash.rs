@@ -1,4 +1,69 @@
-//! SHA-1 hash implementation (RFC 3174).
+//! SHA-1 hash implementation (RFC 3174), and object hashing in either
+//! object format.
+//!
+//! Object hashes go through collision detection (see [`super::sha1dc`]),
+//! so that objects crafted with a known SHA-1 collision attack are
+//! rejected instead of silently aliasing another object.
+
+use super::sha1dc;
+use super::sha256::{sha256, Sha256State, SHA256_SIZE};
+use crate::error::{Error, Result};
+
+/// The hash function a repository names its objects with
+/// (`extensions.objectFormat`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ObjectFormat {
+    /// SHA-1 with 20-byte object IDs, the default.
+    #[default]
+    Sha1,
+    /// SHA-256 with 32-byte object IDs.
+    Sha256,
+}
+
+impl ObjectFormat {
+    /// Returns the name used in `extensions.objectFormat`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ObjectFormat::Sha1 => "sha1",
+            ObjectFormat::Sha256 => "sha256",
+        }
+    }
+
+    /// Parses an `extensions.objectFormat` value.
+    ///
+    /// # Returns
+    ///
+    /// The format, or `None` if the name is not a known format.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sha1" => Some(ObjectFormat::Sha1),
+            "sha256" => Some(ObjectFormat::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Returns the size of an object ID in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            ObjectFormat::Sha1 => SHA1_SIZE,
+            ObjectFormat::Sha256 => SHA256_SIZE,
+        }
+    }
+
+    /// Returns the length of an object ID in hexadecimal.
+    pub fn hex_len(self) -> usize {
+        self.size() * 2
+    }
+
+    /// Returns the number that identifies the hash function in the
+    /// headers of reverse indexes and multi-pack-indexes.
+    pub(crate) fn hash_id(self) -> u32 {
+        match self {
+            ObjectFormat::Sha1 => 1,
+            ObjectFormat::Sha256 => 2,
+        }
+    }
+}
 
 /// SHA-1 hash size in bytes.
 pub const SHA1_SIZE: usize = 20;
@@ -13,12 +78,25 @@ const H4: u32 = 0xC3D2E1F0;
 /// SHA-1 round constants.
 const K: [u32; 4] = [0x5A827999, 0x6ED9EBA1, 0x8F1BBCDC, 0xCA62C1D6];
 
+/// Returns `f(b, c, d) + K` for SHA-1 step `t`.
+pub(super) fn round(t: usize, b: u32, c: u32, d: u32) -> u32 {
+    match t {
+        0..=19 => ((b & c) | ((!b) & d)).wrapping_add(K[0]),
+        20..=39 => (b ^ c ^ d).wrapping_add(K[1]),
+        40..=59 => ((b & c) | (b & d) | (c & d)).wrapping_add(K[2]),
+        60..=79 => (b ^ c ^ d).wrapping_add(K[3]),
+        _ => unreachable!(),
+    }
+}
+
 /// Internal state for SHA-1 computation.
 struct Sha1State {
     h: [u32; 5],
     buffer: [u8; 64],
     buffer_len: usize,
     total_len: u64,
+    detect_collisions: bool,
+    collision: bool,
 }
 
 impl Sha1State {
@@ -29,6 +107,17 @@ impl Sha1State {
             buffer: [0u8; 64],
             buffer_len: 0,
             total_len: 0,
+            detect_collisions: false,
+            collision: false,
+        }
+    }
+
+    /// Creates a new SHA-1 state that checks every block for a collision
+    /// attack.
+    fn with_collision_detection() -> Self {
+        Self {
+            detect_collisions: true,
+            ..Self::new()
         }
     }
 
@@ -92,22 +181,20 @@ impl Sha1State {
         let mut d = self.h[3];
         let mut e = self.h[4];
 
+        // States before the steps collision detection recompresses from
+        let mut saved = [[0u32; 5]; 2];
+
         // Main loop
         #[allow(clippy::needless_range_loop)]
         for i in 0..80 {
-            let (f, k) = match i {
-                0..=19 => ((b & c) | ((!b) & d), K[0]),
-                20..=39 => (b ^ c ^ d, K[1]),
-                40..=59 => ((b & c) | (b & d) | (c & d), K[2]),
-                60..=79 => (b ^ c ^ d, K[3]),
-                _ => unreachable!(),
-            };
+            if let Some(index) = sha1dc::SAVED_STEPS.iter().position(|&s| s == i) {
+                saved[index] = [a, b, c, d, e];
+            }
 
             let temp = a
                 .rotate_left(5)
-                .wrapping_add(f)
+                .wrapping_add(round(i, b, c, d))
                 .wrapping_add(e)
-                .wrapping_add(k)
                 .wrapping_add(w[i]);
             e = d;
             d = c;
@@ -122,10 +209,23 @@ impl Sha1State {
         self.h[2] = self.h[2].wrapping_add(c);
         self.h[3] = self.h[3].wrapping_add(d);
         self.h[4] = self.h[4].wrapping_add(e);
+
+        if self.detect_collisions
+            && !self.collision
+            && sha1dc::is_collision_block(&w, &saved, &self.h)
+        {
+            self.collision = true;
+        }
     }
 
     /// Finalizes the hash computation and returns the digest.
-    fn finalize(mut self) -> [u8; SHA1_SIZE] {
+    fn finalize(self) -> [u8; SHA1_SIZE] {
+        self.finalize_checked().0
+    }
+
+    /// Finalizes the hash computation and returns the digest, along with
+    /// whether a collision attack was detected in any block.
+    fn finalize_checked(mut self) -> ([u8; SHA1_SIZE], bool) {
         let bit_len = self.total_len * 8;
 
         // Append padding bit
@@ -154,7 +254,7 @@ impl Sha1State {
         for (i, &h) in self.h.iter().enumerate() {
             result[i * 4..i * 4 + 4].copy_from_slice(&h.to_be_bytes());
         }
-        result
+        (result, self.collision)
     }
 }
 
@@ -170,6 +270,101 @@ pub fn sha1(data: &[u8]) -> [u8; SHA1_SIZE] {
     state.finalize()
 }
 
+/// Computes the hash of the given data in an object format.
+///
+/// This is used for file checksums, such as the index trailer; it does
+/// not check for SHA-1 collision attacks.
+pub fn digest(format: ObjectFormat, data: &[u8]) -> Vec<u8> {
+    match format {
+        ObjectFormat::Sha1 => sha1(data).to_vec(),
+        ObjectFormat::Sha256 => sha256(data).to_vec(),
+    }
+}
+
+/// The CRC-32 (IEEE 802.3) lookup table, one entry per byte value.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Continues the CRC-32 checksum `crc` of earlier data with more data;
+/// start from 0. Data written in pieces can so be checksummed as it goes.
+///
+/// Pack indexes store this for every packed object so that the raw
+/// entry can be checked without inflating it.
+///
+/// Usage: `let crc = crc32_update(crc32_update(0, b"1234"), b"56789"); // 0xcbf43926`
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc = CRC32_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// Computes the 32-bit MurmurHash3 of the given data.
+///
+/// Changed-path Bloom filters in commit-graphs hash paths with this.
+/// Version 1 of those filters was computed by Git with the bytes read as
+/// signed `char`s, which gives different hashes for bytes of `0x80` and
+/// above; `signed_bytes` reproduces that.
+///
+/// Usage: `let hash = murmur3(0, b"Hello world!", false); // 0x627b0c2c`
+pub fn murmur3(seed: u32, data: &[u8], signed_bytes: bool) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+    let byte = |b: u8| {
+        if signed_bytes {
+            b as i8 as i32 as u32
+        } else {
+            b as u32
+        }
+    };
+
+    let mut hash = seed;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let k = byte(chunk[0]) | byte(chunk[1]) << 8 | byte(chunk[2]) << 16 | byte(chunk[3]) << 24;
+        let k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        hash ^= k;
+        hash = hash
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe654_6b64);
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let mut k = 0u32;
+        for (i, &b) in tail.iter().enumerate().rev() {
+            k ^= byte(b) << (8 * i);
+        }
+        hash ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
 /// Computes the SHA-1 hash of a Git object.
 ///
 /// Git objects are hashed as: `{type} {size}\0{content}`
@@ -177,12 +372,140 @@ pub fn sha1(data: &[u8]) -> [u8; SHA1_SIZE] {
 /// Usage: `let hash = hash_object("blob", b"hello");`
 ///
 /// The empty blob hash is `e69de29bb2d1d6434b8b29ae775ad8c2e48c5391`.
-pub fn hash_object(object_type: &str, content: &[u8]) -> [u8; SHA1_SIZE] {
-    let header = format!("{} {}\0", object_type, content.len());
-    let mut state = Sha1State::new();
-    state.update(header.as_bytes());
+///
+/// # Errors
+///
+/// Returns `Error::Sha1Collision` if the content contains a block of a
+/// known SHA-1 collision attack.
+pub fn hash_object(object_type: &str, content: &[u8]) -> Result<[u8; SHA1_SIZE]> {
+    let mut state = Sha1State::with_collision_detection();
+    state.update(format!("{} {}\0", object_type, content.len()).as_bytes());
     state.update(content);
-    state.finalize()
+    check_collision(state.finalize_checked())
+}
+
+/// Computes the hash of a Git object in an object format.
+///
+/// # Returns
+///
+/// The object ID bytes: 20 for SHA-1, 32 for SHA-256.
+///
+/// # Errors
+///
+/// Returns `Error::Sha1Collision` if the content contains a block of a
+/// known SHA-1 collision attack.
+pub fn hash_object_with(
+    format: ObjectFormat,
+    object_type: &str,
+    content: &[u8],
+) -> Result<Vec<u8>> {
+    match format {
+        ObjectFormat::Sha1 => hash_object(object_type, content).map(|hash| hash.to_vec()),
+        ObjectFormat::Sha256 => {
+            let mut hasher = ObjectHasher::with_format(format, object_type, content.len() as u64);
+            hasher.update(content);
+            hasher.finalize()
+        }
+    }
+}
+
+/// Turns a SHA-1 digest into an error if a collision attack was detected.
+fn check_collision((digest, collision): ([u8; SHA1_SIZE], bool)) -> Result<[u8; SHA1_SIZE]> {
+    if collision {
+        return Err(Error::Sha1Collision(
+            digest.iter().map(|b| format!("{:02x}", b)).collect(),
+        ));
+    }
+    Ok(digest)
+}
+
+/// The hash state of an [`ObjectHasher`].
+enum HasherState {
+    Sha1(Sha1State),
+    Sha256(Sha256State),
+}
+
+/// Incremental hash of a Git object.
+///
+/// The object header needs the content size, so it is given up front and
+/// the content is fed in pieces. This lets large blobs be hashed without
+/// holding them in memory.
+pub struct ObjectHasher {
+    state: HasherState,
+}
+
+impl ObjectHasher {
+    /// Starts hashing an object of the given type and content size in an
+    /// object format.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The object format of the repository.
+    /// * `object_type` - The object type name (`"blob"`, `"tree"`, ...).
+    /// * `size` - The size of the content in bytes.
+    pub fn with_format(format: ObjectFormat, object_type: &str, size: u64) -> Self {
+        let state = match format {
+            ObjectFormat::Sha1 => HasherState::Sha1(Sha1State::with_collision_detection()),
+            ObjectFormat::Sha256 => HasherState::Sha256(Sha256State::new()),
+        };
+        let mut hasher = ObjectHasher { state };
+        hasher.update(format!("{} {}\0", object_type, size).as_bytes());
+        hasher
+    }
+
+    /// Feeds the next piece of content.
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.state {
+            HasherState::Sha1(state) => state.update(data),
+            HasherState::Sha256(state) => state.update(data),
+        }
+    }
+
+    /// Returns the object ID bytes: 20 for SHA-1, 32 for SHA-256.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Sha1Collision` if the content contains a block of a
+    /// known SHA-1 collision attack.
+    pub fn finalize(self) -> Result<Vec<u8>> {
+        match self.state {
+            HasherState::Sha1(state) => Ok(check_collision(state.finalize_checked())?.to_vec()),
+            HasherState::Sha256(state) => Ok(state.finalize().to_vec()),
+        }
+    }
+}
+
+/// Incremental form of [`digest`], for checksums of files written in
+/// pieces, such as packs.
+pub struct FileHasher {
+    state: HasherState,
+}
+
+impl FileHasher {
+    /// Starts hashing data in an object format.
+    pub fn new(format: ObjectFormat) -> Self {
+        let state = match format {
+            ObjectFormat::Sha1 => HasherState::Sha1(Sha1State::new()),
+            ObjectFormat::Sha256 => HasherState::Sha256(Sha256State::new()),
+        };
+        FileHasher { state }
+    }
+
+    /// Feeds the next piece of data.
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.state {
+            HasherState::Sha1(state) => state.update(data),
+            HasherState::Sha256(state) => state.update(data),
+        }
+    }
+
+    /// Returns the hash of all the data fed, as [`digest`] would.
+    pub fn finalize(self) -> Vec<u8> {
+        match self.state {
+            HasherState::Sha1(state) => state.finalize().to_vec(),
+            HasherState::Sha256(state) => state.finalize().to_vec(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -194,6 +517,73 @@ mod tests {
         bytes.iter().map(|b| format!("{:02x}", b)).collect()
     }
 
+    /// Converts a hex string to bytes.
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Hashes `data` with collision detection.
+    fn sha1_checked(data: &[u8]) -> ([u8; SHA1_SIZE], bool) {
+        let mut state = Sha1State::with_collision_detection();
+        state.update(data);
+        state.finalize_checked()
+    }
+
+    /// First 320 bytes of shattered-1.pdf (https://shattered.io).
+    const SHATTERED_1: &str = concat!(
+        "255044462d312e330a25e2e3cfd30a0a0a312030206f626a0a3c3c2f57696474",
+        "682032203020522f4865696768742033203020522f547970652034203020522f",
+        "537562747970652035203020522f46696c7465722036203020522f436f6c6f72",
+        "53706163652037203020522f4c656e6774682038203020522f42697473506572",
+        "436f6d706f6e656e7420383e3e0a73747265616d0affd8fffe00245348412d31",
+        "20697320646561642121212121852fec092339759c39b1a1c63c4c97e1fffe01",
+        "7346dc9166b67e118f029ab621b2560ff9ca67cca8c7f85ba84c79030c2b3de2",
+        "18f86db3a90901d5df45c14f26fedfb3dc38e96ac22fe7bd728f0e45bce046d2",
+        "3c570feb141398bb552ef5a0a82be331fea48037b8b5d71f0e332edf93ac3500",
+        "eb4ddc0decc1a864790c782c76215660dd309791d06bd0af3f98cda4bc4629b1",
+    );
+
+    /// First 320 bytes of shattered-2.pdf, colliding with `SHATTERED_1`.
+    const SHATTERED_2: &str = concat!(
+        "255044462d312e330a25e2e3cfd30a0a0a312030206f626a0a3c3c2f57696474",
+        "682032203020522f4865696768742033203020522f547970652034203020522f",
+        "537562747970652035203020522f46696c7465722036203020522f436f6c6f72",
+        "53706163652037203020522f4c656e6774682038203020522f42697473506572",
+        "436f6d706f6e656e7420383e3e0a73747265616d0affd8fffe00245348412d31",
+        "20697320646561642121212121852fec092339759c39b1a1c63c4c97e1fffe01",
+        "7f46dc93a6b67e013b029aaa1db2560b45ca67d688c7f84b8c4c791fe02b3df6",
+        "14f86db1690901c56b45c1530afedfb76038e972722fe7ad728f0e4904e046c2",
+        "30570fe9d41398abe12ef5bc942be33542a4802d98b5d70f2a332ec37fac3514",
+        "e74ddc0f2cc1a874cd0c78305a21566461309789606bd0bf3f98cda8044629a1",
+    );
+
+    /// messageA of the SHA-mbles chosen-prefix collision (https://sha-mbles.github.io).
+    const SHA_MBLES_1: &str = concat!(
+        "99040d047fe81780012000ff4b65792069732070617274206f66206120636f6c",
+        "6c6973696f6e212049742773206120747261702179c61af0afcc054515d9274e",
+        "7307624b1dc7fb23988bb8de8b575dba7b9eab31c1674b6d974378a827732ff5",
+        "851c76a2e60772b5a47ce1eac40bb993c12d8c70e24a4f8d5fcdedc1b32c9cf1",
+        "9e31af2429759d42e4dfdb31719f587623ee552939b6dcdc459fca53553b70f8",
+        "7ede30a247ea3af6c759a2f20b320d760db64ff479084fd3ccb3cdd48362d96a",
+        "9c430617caff6c36c637e53fde28417f626fec54ed7943a46e5f5730f2bb38fb",
+        "1df6e0090010d00e24ad78bf92641993608e8d158a789f34c46fe1e6027f35a4",
+        "cbfb827076c50eca0e8b7cca69bb2c2b790259f9bf9570dd8d4437a3115faff7",
+        "c3cac09ad25266055c27104755178eaeff825a2caa2acfb5de64ce7641dc59a5",
+        "41a9fc9c756756e2e23dc713c8c24c9790aa6b0e38a7f55f14452a1ca2850ddd",
+        "9562fd9a18ad42496aa97008f74672f68ef461eb88b09933d626b4f918749cc0",
+        "27fddd6c425fc4216835d0134d15285bab2cb784a4f7cbb4fb514d4bf0f6237c",
+        "f00a9e9f132b9a066e6fd17f6c42987478586ff651af96747fb426b9872b9a88",
+        "e4063f59bb334cc00650f83a80c42751b71974d300fc2819a2e8f1e32c1b51cb",
+        "18e6bfc4db9baef675d4aaf5b1574a047f8f6dd2ec153a93412293974d928f88",
+        "ced9363cfef97ce2e742bf34c96b8ef3875676fea5cca8e5f7dea0bab2413d4d",
+        "e00ee71ee01f162bdb6d1eafd925e6aebaae6a354ef17cf205a404fbdb12fc45",
+        "4d41fdd95cf2459664a2ad032d1da60a73264075d7f1e0d6c1403ae7a0d861df",
+        "3fe5707188dd5e07d1589b9f8b6630553f8fc352b3e0c27da80bddba4c64020d",
+    );
+
     // H-001: Empty data hash
     #[test]
     fn test_sha1_empty() {
@@ -228,14 +618,14 @@ mod tests {
     // H-005: Git object format hash (empty blob)
     #[test]
     fn test_hash_object_empty_blob() {
-        let hash = hash_object("blob", b"");
+        let hash = hash_object("blob", b"").unwrap();
         assert_eq!(to_hex(&hash), "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391");
     }
 
     #[test]
     fn test_hash_object_hello_blob() {
         // "hello\n" blob - matches `echo "hello" | git hash-object --stdin`
-        let hash = hash_object("blob", b"hello\n");
+        let hash = hash_object("blob", b"hello\n").unwrap();
         assert_eq!(to_hex(&hash), "ce013625030ba8dba906f756967f9e9ca394464a");
     }
 
@@ -268,4 +658,154 @@ mod tests {
 
         assert_eq!(hash1, hash2);
     }
+
+    // H-006: ObjectHasher matches hash_object when fed in pieces
+    #[test]
+    fn test_object_hasher() {
+        let content = b"streamed content";
+        let mut hasher =
+            ObjectHasher::with_format(ObjectFormat::Sha1, "blob", content.len() as u64);
+        for chunk in content.chunks(3) {
+            hasher.update(chunk);
+        }
+        assert_eq!(
+            hasher.finalize().unwrap(),
+            hash_object("blob", content).unwrap()
+        );
+    }
+
+    // H-011: SHA-256 objects match `git hash-object` in a SHA-256 repository
+    #[test]
+    fn test_hash_object_sha256() {
+        let hash = hash_object_with(ObjectFormat::Sha256, "blob", b"").unwrap();
+        assert_eq!(
+            to_hex(&hash),
+            "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813"
+        );
+        let hash = hash_object_with(ObjectFormat::Sha256, "blob", b"hello\n").unwrap();
+        assert_eq!(
+            to_hex(&hash),
+            "2cf8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4"
+        );
+
+        let mut hasher = ObjectHasher::with_format(ObjectFormat::Sha256, "blob", 6);
+        hasher.update(b"hel");
+        hasher.update(b"lo\n");
+        assert_eq!(hasher.finalize().unwrap(), hash);
+    }
+
+    // H-012: Object format names and sizes
+    #[test]
+    fn test_object_format() {
+        for format in [ObjectFormat::Sha1, ObjectFormat::Sha256] {
+            assert_eq!(ObjectFormat::from_name(format.name()), Some(format));
+        }
+        assert_eq!(ObjectFormat::from_name("sha3"), None);
+        assert_eq!(ObjectFormat::default(), ObjectFormat::Sha1);
+        assert_eq!(ObjectFormat::Sha1.hex_len(), 40);
+        assert_eq!(ObjectFormat::Sha256.size(), 32);
+        assert_eq!(digest(ObjectFormat::Sha256, b"abc").len(), 32);
+    }
+
+    // H-007: Both halves of the SHAttered collision are detected
+    #[test]
+    fn test_detect_shattered() {
+        let first = from_hex(SHATTERED_1);
+        let second = from_hex(SHATTERED_2);
+        assert_ne!(first, second);
+        assert_eq!(sha1(&first), sha1(&second));
+
+        for data in [&first, &second] {
+            let (hash, collision) = sha1_checked(data);
+            assert!(collision);
+            assert_eq!(to_hex(&hash), "f92d74e3874587aaf443d1db961d4e26dde13e9c");
+        }
+    }
+
+    // H-008: A chosen-prefix collision (SHA-mbles) is detected
+    #[test]
+    fn test_detect_sha_mbles() {
+        let (hash, collision) = sha1_checked(&from_hex(SHA_MBLES_1));
+        assert!(collision);
+        assert_eq!(to_hex(&hash), "8ac60ba76f1999a1ab70223f225aefdc78d4ddc0");
+    }
+
+    // H-009: Ordinary data is not flagged, whatever its length
+    #[test]
+    fn test_no_false_positive() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        for len in [0, 55, 64, 320, 4096, data.len()] {
+            let (hash, collision) = sha1_checked(&data[..len]);
+            assert!(!collision);
+            assert_eq!(hash, sha1(&data[..len]));
+        }
+        // The SHAttered prefix with one byte flipped is no longer an attack
+        let mut tampered = from_hex(SHATTERED_1);
+        tampered[200] ^= 1;
+        assert!(!sha1_checked(&tampered).1);
+    }
+
+    // H-010: ObjectHasher reports a detected collision as an error
+    #[test]
+    fn test_object_hasher_collision() {
+        let mut state = Sha1State::with_collision_detection();
+        state.update(&from_hex(SHATTERED_1));
+        let hasher = ObjectHasher {
+            state: HasherState::Sha1(state),
+        };
+
+        match hasher.finalize() {
+            Err(Error::Sha1Collision(oid)) => {
+                assert_eq!(oid, "f92d74e3874587aaf443d1db961d4e26dde13e9c")
+            }
+            other => panic!("expected a collision, got {:?}", other),
+        }
+    }
+
+    // H-013: CRC-32 matches the standard check values
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32_update(0, b""), 0);
+        assert_eq!(crc32_update(0, b"123456789"), 0xcbf4_3926);
+        assert_eq!(
+            crc32_update(0, b"The quick brown fox jumps over the lazy dog"),
+            0x414f_a339
+        );
+        assert_eq!(
+            crc32_update(crc32_update(0, b"1234"), b"56789"),
+            0xcbf4_3926
+        );
+    }
+
+    // H-015: FileHasher matches digest when fed in pieces
+    #[test]
+    fn test_file_hasher() {
+        let data: Vec<u8> = (0..1_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        for format in [ObjectFormat::Sha1, ObjectFormat::Sha256] {
+            let mut hasher = FileHasher::new(format);
+            for chunk in data.chunks(77) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.finalize(), digest(format, &data));
+        }
+    }
+
+    // H-014: MurmurHash3 matches Git's test values
+    #[test]
+    fn test_murmur3() {
+        assert_eq!(murmur3(0, b"", false), 0);
+        assert_eq!(murmur3(0, b"Hello world!", false), 0x627b_0c2c);
+        assert_eq!(
+            murmur3(0, b"The quick brown fox jumps over the lazy dog", false),
+            0x2e4f_f723
+        );
+        let high = b"\x99\xaa\xbb\xcc\xdd\xee\xff";
+        assert_eq!(murmur3(0, high, false), 0xa183_ccfd);
+        // ASCII hashes the same either way
+        assert_eq!(
+            murmur3(7, b"dir/a.txt", true),
+            murmur3(7, b"dir/a.txt", false)
+        );
+        assert_ne!(murmur3(0, high, true), murmur3(0, high, false));
+    }
 }
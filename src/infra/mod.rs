@@ -1,9 +1,23 @@
-//! Infrastructure utilities (hashing, compression, filesystem).
+//! Infrastructure utilities (hashing, compression, encodings, bitmaps, filesystem, durable writes, paths, threading, globbing).
 
 pub mod compression;
+pub mod encoding;
+pub mod ewah;
 pub mod fs;
+pub mod fsync;
 pub mod hash;
+pub mod parallel;
+pub mod path;
+mod sha1dc;
+pub mod sha256;
+pub mod wildmatch;
 
-pub use compression::{compress, decompress};
-pub use fs::{list_working_tree, read_file, write_file_atomic};
-pub use hash::hash_object;
+pub use compression::{compress, decompress, ZlibReader, ZlibWriter};
+pub use fs::{
+    list_working_tree, list_working_tree_parallel, read_file, remove_file, write_file_atomic,
+};
+pub use fsync::{Fsync, FsyncComponent, FsyncMethod, FsyncSettings};
+pub use hash::{
+    crc32_update, digest, hash_object_with, murmur3, FileHasher, ObjectFormat, ObjectHasher,
+};
+pub use parallel::{parallel_map, resolve_threads, WriteGuard, WriteLock};
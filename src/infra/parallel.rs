@@ -0,0 +1,194 @@
+//! Helpers for running work on multiple threads.
+//!
+//! These use scoped std threads only, and always return results in input
+//! order so that callers produce deterministic output.
+
+use std::marker::PhantomData;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread::{self, ThreadId};
+
+/// Resolves a requested thread count.
+///
+/// `0` means "one thread per available CPU".
+pub fn resolve_threads(threads: usize) -> usize {
+    if threads == 0 {
+        thread::available_parallelism()
+            .map(NonZeroUsize::get)
+            .unwrap_or(1)
+    } else {
+        threads
+    }
+}
+
+/// Applies `f` to every item, using up to `threads` threads.
+///
+/// Items are handed out one at a time, so uneven workloads are balanced
+/// across threads. The returned vector is in the same order as `items`.
+///
+/// # Arguments
+///
+/// * `items` - The items to process.
+/// * `threads` - The maximum number of threads (`0` = available CPUs).
+/// * `f` - The function to apply to each item.
+pub fn parallel_map<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let threads = resolve_threads(threads).min(items.len());
+    if threads <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new((0..items.len()).map(|_| None).collect());
+
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| {
+                let mut local = Vec::new();
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= items.len() {
+                        break;
+                    }
+                    local.push((i, f(&items[i])));
+                }
+
+                let mut results = results.lock().unwrap();
+                for (i, r) in local {
+                    results[i] = Some(r);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("every item is processed exactly once"))
+        .collect()
+}
+
+/// A lock that the thread holding it may take again.
+///
+/// Operations that change the repository take it for their whole
+/// duration, and some call others that take it too (a checkout refreshes
+/// the index through a status).
+#[derive(Debug, Default)]
+pub struct WriteLock {
+    /// The thread holding the lock and how many times it took it.
+    owner: Mutex<Option<(ThreadId, usize)>>,
+    /// Signalled when the lock is released.
+    released: Condvar,
+}
+
+/// Holds a [`WriteLock`] until dropped, on the thread that took it.
+#[derive(Debug)]
+pub struct WriteGuard<'a> {
+    lock: &'a WriteLock,
+    /// Keeps the guard on the thread that owns the lock.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl WriteLock {
+    /// Creates an unlocked lock.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the lock, waiting for another thread holding it to release
+    /// it.
+    pub fn lock(&self) -> WriteGuard<'_> {
+        let current = thread::current().id();
+        let mut owner = self.owner.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            match owner.as_mut() {
+                None => {
+                    *owner = Some((current, 1));
+                    break;
+                }
+                Some((thread, depth)) if *thread == current => {
+                    *depth += 1;
+                    break;
+                }
+                Some(_) => {
+                    owner = self.released.wait(owner).unwrap_or_else(|e| e.into_inner());
+                }
+            }
+        }
+        WriteGuard {
+            lock: self,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        let mut owner = self.lock.owner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, depth)) = owner.as_mut() {
+            *depth -= 1;
+            if *depth == 0 {
+                *owner = None;
+                self.lock.released.notify_one();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // PAR-001: Results keep input order
+    #[test]
+    fn test_parallel_map_preserves_order() {
+        let items: Vec<u32> = (0..1000).collect();
+        let result = parallel_map(&items, 4, |x| x * 2);
+        let expected: Vec<u32> = (0..1000).map(|x| x * 2).collect();
+        assert_eq!(result, expected);
+    }
+
+    // PAR-002: Sequential and empty inputs
+    #[test]
+    fn test_parallel_map_single_thread_and_empty() {
+        assert_eq!(parallel_map(&[1, 2, 3], 1, |x| x + 1), vec![2, 3, 4]);
+        let empty: [u32; 0] = [];
+        assert!(parallel_map(&empty, 8, |x| *x).is_empty());
+    }
+
+    // PAR-003: Zero threads means available parallelism
+    #[test]
+    fn test_resolve_threads() {
+        assert_eq!(resolve_threads(3), 3);
+        assert!(resolve_threads(0) >= 1);
+    }
+
+    // PAR-004: The write lock is re-entrant and excludes other threads
+    #[test]
+    fn test_write_lock() {
+        let lock = WriteLock::new();
+        let counter = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        let _outer = lock.lock();
+                        let _inner = lock.lock();
+                        // Nobody else may change the counter in between
+                        let value = counter.load(Ordering::Relaxed);
+                        thread::yield_now();
+                        counter.store(value + 1, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        assert_eq!(counter.load(Ordering::Relaxed), 400);
+        assert!(lock.owner.lock().unwrap().is_none());
+    }
+}
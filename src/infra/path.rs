@@ -0,0 +1,85 @@
+//! Conversions between Git paths and platform paths.
+//!
+//! Git stores paths as raw bytes separated by `/`. On Unix a path is an
+//! arbitrary byte string too, so the conversions are lossless. Elsewhere,
+//! Git paths are taken to be UTF-8 (invalid sequences are replaced) and
+//! the platform separator is mapped to `/`.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// Converts a Git path to a platform path.
+///
+/// # Arguments
+///
+/// * `bytes` - The `/`-separated path as stored by Git.
+#[cfg(unix)]
+pub fn from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+/// Converts a Git path to a platform path.
+///
+/// # Arguments
+///
+/// * `bytes` - The `/`-separated path as stored by Git.
+#[cfg(not(unix))]
+pub fn from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Converts a platform path to a Git path.
+///
+/// # Arguments
+///
+/// * `path` - The path, relative to the repository root.
+///
+/// # Returns
+///
+/// The `/`-separated path as stored by Git.
+#[cfg(unix)]
+pub fn to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+/// Converts a platform path to a Git path.
+///
+/// # Arguments
+///
+/// * `path` - The path, relative to the repository root.
+///
+/// # Returns
+///
+/// The `/`-separated path as stored by Git.
+#[cfg(not(unix))]
+pub fn to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    Cow::Owned(path.to_string_lossy().replace('\\', "/").into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // PT-001: ASCII paths convert both ways
+    #[test]
+    fn test_roundtrip_ascii() {
+        let path = from_bytes(b"src/main.rs");
+        assert_eq!(path, Path::new("src/main.rs"));
+        assert_eq!(to_bytes(&path), &b"src/main.rs"[..]);
+    }
+
+    // PT-002: Non-UTF-8 paths round trip on Unix
+    #[cfg(unix)]
+    #[test]
+    fn test_roundtrip_non_utf8() {
+        let latin1 = b"caf\xe9/men\xfa.txt";
+        let path = from_bytes(latin1);
+        assert!(path.to_str().is_none());
+        assert_eq!(to_bytes(&path), &latin1[..]);
+    }
+}
@@ -0,0 +1,217 @@
+//! SHA-256 hash implementation (FIPS 180-4).
+
+/// SHA-256 hash size in bytes.
+pub const SHA256_SIZE: usize = 32;
+
+/// Initial hash values for SHA-256.
+const H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// SHA-256 round constants.
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Internal state for SHA-256 computation.
+pub(super) struct Sha256State {
+    h: [u32; 8],
+    buffer: [u8; 64],
+    buffer_len: usize,
+    total_len: u64,
+}
+
+impl Sha256State {
+    /// Creates a new SHA-256 state with initial values.
+    pub(super) fn new() -> Self {
+        Self {
+            h: H,
+            buffer: [0u8; 64],
+            buffer_len: 0,
+            total_len: 0,
+        }
+    }
+
+    /// Updates the hash state with input data.
+    pub(super) fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+
+        // If we have buffered data, try to complete a block
+        if self.buffer_len > 0 {
+            let to_copy = (64 - self.buffer_len).min(data.len());
+            self.buffer[self.buffer_len..self.buffer_len + to_copy]
+                .copy_from_slice(&data[..to_copy]);
+            self.buffer_len += to_copy;
+            data = &data[to_copy..];
+
+            if self.buffer_len == 64 {
+                let block = self.buffer;
+                self.process_block(&block);
+                self.buffer_len = 0;
+            }
+        }
+
+        // Process complete blocks
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            self.process_block(block.try_into().unwrap());
+        }
+
+        // Buffer remaining data
+        let remaining = blocks.remainder();
+        self.buffer[..remaining.len()].copy_from_slice(remaining);
+        self.buffer_len += remaining.len();
+    }
+
+    /// Processes a single 512-bit (64-byte) block.
+    fn process_block(&mut self, block: &[u8; 64]) {
+        let mut w = [0u32; 64];
+
+        // Prepare message schedule
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        // Main loop
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.h;
+        for (&k, &w) in K.iter().zip(&w) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let temp1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(k)
+                .wrapping_add(w);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        // Update hash values
+        for (h, v) in self.h.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    /// Finalizes the hash computation and returns the digest.
+    pub(super) fn finalize(mut self) -> [u8; SHA256_SIZE] {
+        let bit_len = self.total_len * 8;
+
+        // Append padding bit
+        self.buffer[self.buffer_len] = 0x80;
+        self.buffer_len += 1;
+
+        // If not enough room for length, process current block and start new one
+        if self.buffer_len > 56 {
+            self.buffer[self.buffer_len..64].fill(0);
+            let block = self.buffer;
+            self.process_block(&block);
+            self.buffer_len = 0;
+        }
+
+        // Pad with zeros, then append length in bits as big-endian u64
+        self.buffer[self.buffer_len..56].fill(0);
+        self.buffer[56..64].copy_from_slice(&bit_len.to_be_bytes());
+
+        let block = self.buffer;
+        self.process_block(&block);
+
+        // Produce final hash
+        let mut result = [0u8; SHA256_SIZE];
+        for (i, &h) in self.h.iter().enumerate() {
+            result[i * 4..i * 4 + 4].copy_from_slice(&h.to_be_bytes());
+        }
+        result
+    }
+}
+
+/// Computes the SHA-256 hash of the given data.
+///
+/// Usage: `let hash = sha256(b"hello world");`
+pub fn sha256(data: &[u8]) -> [u8; SHA256_SIZE] {
+    let mut state = Sha256State::new();
+    state.update(data);
+    state.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Converts a byte slice to a hex string.
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    // H256-001: Empty data hash
+    #[test]
+    fn test_sha256_empty() {
+        assert_eq!(
+            to_hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    // H256-002: Standard test vectors from FIPS 180-4
+    #[test]
+    fn test_sha256_vectors() {
+        assert_eq!(
+            to_hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            to_hex(&sha256(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    // H256-003: Large data hash
+    #[test]
+    fn test_sha256_large() {
+        let data = vec![b'a'; 1_000_000];
+        assert_eq!(
+            to_hex(&sha256(&data)),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    // H256-004: Incremental update matches a single call
+    #[test]
+    fn test_sha256_incremental() {
+        let data: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        for split in [0, 1, 55, 63, 64, 65, 500] {
+            let mut state = Sha256State::new();
+            state.update(&data[..split]);
+            for chunk in data[split..].chunks(37) {
+                state.update(chunk);
+            }
+            assert_eq!(state.finalize(), sha256(&data));
+        }
+    }
+}
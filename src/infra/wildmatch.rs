@@ -0,0 +1,348 @@
+//! Glob matching compatible with Git's `wildmatch()`.
+//!
+//! This is used for `.gitignore` patterns and pathspecs. It supports `*`,
+//! `?`, `[...]` (including ranges, negation and `[:class:]`), backslash
+//! escapes and, with [`WM_PATHNAME`], the `**` directory wildcard.
+
+/// Match case-insensitively (ASCII only).
+pub const WM_CASEFOLD: u32 = 1;
+/// Treat `/` specially: `*`, `?` and `[...]` do not match it, and `**`
+/// matches across directories.
+pub const WM_PATHNAME: u32 = 2;
+
+/// Result of matching a (sub)pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Match,
+    NoMatch,
+    /// The text ran out; no later `*` can help.
+    AbortAll,
+    /// A single `*` hit a `/`; only an enclosing `**` can help.
+    AbortToStarStar,
+}
+
+/// Matches `text` against a glob `pattern`.
+///
+/// # Arguments
+///
+/// * `pattern` - The glob pattern.
+/// * `text` - The text (usually a `/`-separated path) to match.
+/// * `flags` - A combination of [`WM_CASEFOLD`] and [`WM_PATHNAME`].
+///
+/// # Returns
+///
+/// `true` if the whole text matches the pattern.
+pub fn wildmatch(pattern: &[u8], text: &[u8], flags: u32) -> bool {
+    dowild(pattern, text, flags) == Outcome::Match
+}
+
+/// Returns the byte at `i`, or 0 past the end (like a C string).
+fn at(s: &[u8], i: usize) -> u8 {
+    s.get(i).copied().unwrap_or(0)
+}
+
+fn fold(c: u8, flags: u32) -> u8 {
+    if flags & WM_CASEFOLD != 0 {
+        c.to_ascii_lowercase()
+    } else {
+        c
+    }
+}
+
+fn dowild(p: &[u8], text: &[u8], flags: u32) -> Outcome {
+    let pathname = flags & WM_PATHNAME != 0;
+    let mut pi = 0;
+    let mut ti = 0;
+
+    while pi < p.len() {
+        let mut p_ch = p[pi];
+        let mut t_ch = at(text, ti);
+        if ti >= text.len() && p_ch != b'*' {
+            return Outcome::AbortAll;
+        }
+        t_ch = fold(t_ch, flags);
+        p_ch = fold(p_ch, flags);
+
+        match p_ch {
+            b'\\' => {
+                // Literal match with the following character
+                pi += 1;
+                if fold(at(p, pi), flags) != t_ch {
+                    return Outcome::NoMatch;
+                }
+            }
+            b'?' => {
+                if pathname && t_ch == b'/' {
+                    return Outcome::NoMatch;
+                }
+            }
+            b'*' => {
+                let match_slash;
+                pi += 1;
+                if at(p, pi) == b'*' {
+                    let first_star = pi - 1;
+                    while at(p, pi) == b'*' {
+                        pi += 1;
+                    }
+                    let at_segment_start = first_star == 0 || p[first_star - 1] == b'/';
+                    let at_segment_end =
+                        pi >= p.len() || p[pi] == b'/' || (p[pi] == b'\\' && at(p, pi + 1) == b'/');
+                    if at_segment_start && at_segment_end {
+                        // "**/" may match no directories at all
+                        if at(p, pi) == b'/'
+                            && dowild(&p[pi + 1..], &text[ti..], flags) == Outcome::Match
+                        {
+                            return Outcome::Match;
+                        }
+                        match_slash = true;
+                    } else {
+                        match_slash = !pathname;
+                    }
+                } else {
+                    // Without WM_PATHNAME, '*' == '**'
+                    match_slash = !pathname;
+                }
+
+                if pi >= p.len() {
+                    // Trailing "**" matches everything; trailing "*" only
+                    // if there are no more slashes.
+                    if !match_slash && text[ti..].contains(&b'/') {
+                        return Outcome::NoMatch;
+                    }
+                    return Outcome::Match;
+                } else if !match_slash && p[pi] == b'/' {
+                    // A single '*' followed by '/' matches the next directory
+                    match text[ti..].iter().position(|&c| c == b'/') {
+                        Some(offset) => ti += offset,
+                        None => return Outcome::NoMatch,
+                    }
+                    // The slash is consumed below
+                } else {
+                    loop {
+                        if ti >= text.len() {
+                            break;
+                        }
+                        let matched = dowild(&p[pi..], &text[ti..], flags);
+                        if matched != Outcome::NoMatch {
+                            if !match_slash || matched != Outcome::AbortToStarStar {
+                                return matched;
+                            }
+                        } else if !match_slash && text[ti] == b'/' {
+                            return Outcome::AbortToStarStar;
+                        }
+                        ti += 1;
+                    }
+                    return Outcome::AbortAll;
+                }
+            }
+            b'[' => {
+                pi += 1;
+                let mut p_ch = at(p, pi);
+                if p_ch == b'^' {
+                    p_ch = b'!';
+                }
+                let negated = p_ch == b'!';
+                if negated {
+                    pi += 1;
+                    p_ch = at(p, pi);
+                }
+                let mut prev_ch = 0u8;
+                let mut matched = false;
+                loop {
+                    if p_ch == 0 {
+                        return Outcome::AbortAll;
+                    }
+                    if p_ch == b'\\' {
+                        pi += 1;
+                        p_ch = at(p, pi);
+                        if p_ch == 0 {
+                            return Outcome::AbortAll;
+                        }
+                        if t_ch == fold(p_ch, flags) {
+                            matched = true;
+                        }
+                    } else if p_ch == b'-'
+                        && prev_ch != 0
+                        && at(p, pi + 1) != 0
+                        && at(p, pi + 1) != b']'
+                    {
+                        pi += 1;
+                        p_ch = at(p, pi);
+                        if p_ch == b'\\' {
+                            pi += 1;
+                            p_ch = at(p, pi);
+                            if p_ch == 0 {
+                                return Outcome::AbortAll;
+                            }
+                        }
+                        let raw_t = at(text, ti);
+                        if (prev_ch..=p_ch).contains(&raw_t)
+                            || (flags & WM_CASEFOLD != 0
+                                && ((prev_ch..=p_ch).contains(&raw_t.to_ascii_lowercase())
+                                    || (prev_ch..=p_ch).contains(&raw_t.to_ascii_uppercase())))
+                        {
+                            matched = true;
+                        }
+                        // Makes prev_ch reset to 0
+                        p_ch = 0;
+                    } else if p_ch == b'[' && at(p, pi + 1) == b':' {
+                        let start = pi + 2;
+                        let mut end = start;
+                        while at(p, end) != 0 && at(p, end) != b']' {
+                            end += 1;
+                        }
+                        if at(p, end) == 0 {
+                            return Outcome::AbortAll;
+                        }
+                        if end == start || p[end - 1] != b':' {
+                            // Didn't find ":]", so treat like a normal set
+                            p_ch = b'[';
+                            if t_ch == p_ch {
+                                matched = true;
+                            }
+                        } else {
+                            let class = &p[start..end - 1];
+                            match char_class_matches(class, at(text, ti), flags) {
+                                Some(true) => matched = true,
+                                Some(false) => {}
+                                None => return Outcome::AbortAll,
+                            }
+                            pi = end;
+                            p_ch = 0;
+                        }
+                    } else if t_ch == fold(p_ch, flags) {
+                        matched = true;
+                    }
+
+                    prev_ch = p_ch;
+                    pi += 1;
+                    p_ch = at(p, pi);
+                    if p_ch == b']' {
+                        break;
+                    }
+                }
+                if matched == negated || (pathname && t_ch == b'/') {
+                    return Outcome::NoMatch;
+                }
+            }
+            _ => {
+                if t_ch != p_ch {
+                    return Outcome::NoMatch;
+                }
+            }
+        }
+
+        pi += 1;
+        ti += 1;
+    }
+
+    if ti < text.len() {
+        Outcome::NoMatch
+    } else {
+        Outcome::Match
+    }
+}
+
+/// Matches a character against a POSIX class name, or `None` if the class
+/// is unknown.
+fn char_class_matches(class: &[u8], c: u8, flags: u32) -> Option<bool> {
+    let result = match class {
+        b"alnum" => c.is_ascii_alphanumeric(),
+        b"alpha" => c.is_ascii_alphabetic(),
+        b"blank" => c == b' ' || c == b'\t',
+        b"cntrl" => c.is_ascii_control(),
+        b"digit" => c.is_ascii_digit(),
+        b"graph" => c.is_ascii_graphic(),
+        b"lower" => c.is_ascii_lowercase() || (flags & WM_CASEFOLD != 0 && c.is_ascii_uppercase()),
+        b"print" => c.is_ascii_graphic() || c == b' ',
+        b"punct" => c.is_ascii_punctuation(),
+        b"space" => c.is_ascii_whitespace() || c == 0x0b,
+        b"upper" => c.is_ascii_uppercase() || (flags & WM_CASEFOLD != 0 && c.is_ascii_lowercase()),
+        b"xdigit" => c.is_ascii_hexdigit(),
+        _ => return None,
+    };
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wm(pattern: &str, text: &str) -> bool {
+        wildmatch(pattern.as_bytes(), text.as_bytes(), WM_PATHNAME)
+    }
+
+    fn wm_plain(pattern: &str, text: &str) -> bool {
+        wildmatch(pattern.as_bytes(), text.as_bytes(), 0)
+    }
+
+    // WM-001: Literals, '?' and '*'
+    #[test]
+    fn test_basic_patterns() {
+        assert!(wm("foo", "foo"));
+        assert!(!wm("foo", "bar"));
+        assert!(wm("???", "foo"));
+        assert!(!wm("??", "foo"));
+        assert!(wm("*", "foo"));
+        assert!(wm("f*", "foo"));
+        assert!(wm("*o", "foo"));
+        assert!(!wm("*f", "foo"));
+        assert!(wm("*.txt", "a.txt"));
+        assert!(!wm("*.txt", "a.rs"));
+        assert!(wm("", ""));
+        assert!(!wm("", "a"));
+    }
+
+    // WM-002: '*' does not cross '/' with WM_PATHNAME
+    #[test]
+    fn test_star_and_slash() {
+        assert!(!wm("*", "foo/bar"));
+        assert!(wm("*/bar", "foo/bar"));
+        assert!(!wm("*.txt", "dir/a.txt"));
+        assert!(wm_plain("*.txt", "dir/a.txt"));
+        assert!(!wm("foo?bar", "foo/bar"));
+        assert!(wm_plain("foo?bar", "foo/bar"));
+    }
+
+    // WM-003: '**' matches across directories
+    #[test]
+    fn test_double_star() {
+        assert!(wm("**/foo", "foo"));
+        assert!(wm("**/foo", "a/b/foo"));
+        assert!(wm("foo/**", "foo/a/b"));
+        assert!(!wm("foo/**", "foo"));
+        assert!(wm("a/**/b", "a/b"));
+        assert!(wm("a/**/b", "a/x/y/b"));
+        assert!(!wm("a/**/b", "a/x/y/c"));
+        assert!(wm("**", "a/b/c"));
+        assert!(wm("**/*.rs", "src/lib.rs"));
+    }
+
+    // WM-004: Bracket expressions
+    #[test]
+    fn test_brackets() {
+        assert!(wm("[abc]", "b"));
+        assert!(!wm("[abc]", "d"));
+        assert!(wm("[!abc]", "d"));
+        assert!(wm("[^abc]", "d"));
+        assert!(wm("[a-c]x", "bx"));
+        assert!(!wm("[a-c]x", "dx"));
+        assert!(wm("[]]", "]"));
+        assert!(wm("[[:digit:]]", "7"));
+        assert!(!wm("[[:digit:]]", "x"));
+        assert!(wm("[[:alpha:][:digit:]]", "x"));
+        assert!(!wm("a[/]b", "a/b"));
+        assert!(!wm("[[:bogus:]]", "a"));
+    }
+
+    // WM-005: Escapes and case folding
+    #[test]
+    fn test_escape_and_casefold() {
+        assert!(wm("\\*", "*"));
+        assert!(!wm("\\*", "a"));
+        assert!(wm("a\\?", "a?"));
+        assert!(!wm("README", "readme"));
+        assert!(wildmatch(b"README", b"readme", WM_CASEFOLD));
+        assert!(wildmatch(b"[A-C]", b"b", WM_CASEFOLD));
+    }
+}
@@ -7,7 +7,7 @@
 //!
 //! ## Features
 //!
-//! - Read Git repositories (loose objects only, no pack files yet)
+//! - Read Git repositories (loose objects and packfiles)
 //! - Navigate commits, trees, and blobs
 //! - Read branches and HEAD
 //! - Query working tree status
@@ -47,33 +47,62 @@
 //! - [`refs`] - References (HEAD, branches)
 //! - [`index`] - Index (staging area) operations
 //! - [`status`] - Working tree status
+//! - [`pathspec`] - Path patterns shared by add, reset, status, diff and log
+//! - [`staging`] - Removing and renaming tracked files
+//! - [`fsck`] - Object database integrity checks
+//! - [`gc`] - Packing objects and refs, pruning unreachable objects
+//! - [`history`] - Merge bases and commit-graph writing
 
 pub mod config;
 pub mod diff;
 pub mod error;
+pub mod fsck;
+pub mod gc;
+pub mod history;
 pub mod index;
 pub mod log;
 pub mod objects;
+pub mod pathspec;
 pub mod refs;
 pub mod repository;
+pub mod staging;
 pub mod status;
 
 // Internal modules (not part of public API)
+pub(crate) mod ignore;
 pub(crate) mod infra;
+pub(crate) mod sparse;
+#[cfg(test)]
+pub(crate) mod test_util;
 
 // Re-export primary types for convenient access
 pub use config::{Config, ConfigLevel};
 pub use error::{Error, Result};
-pub use repository::Repository;
+pub use repository::{InitOptions, Repository};
 
 // Re-export object types
-pub use objects::{Blob, Commit, FileMode, Object, Oid, Signature, Tree, TreeEntry};
+pub use objects::{
+    Blob, CacheOptions, CacheStats, Commit, FileMode, Object, ObjectFormat, Oid, Signature, Tree,
+    TreeEntry,
+};
 
 // Re-export reference types
 pub use refs::{Branch, Head, RemoteBranch, Tag};
 
+// Re-export pathspec type
+pub use pathspec::Pathspec;
+
+// Re-export staging types
+pub use staging::RemoveOptions;
+
+// Re-export fsck types
+pub use fsck::{FsckFinding, FsckFindingKind, FsckOptions, FsckReport};
+
+// Re-export garbage collection types
+pub use gc::{GcOptions, GcReport};
+
 // Re-export status types
-pub use status::{FileStatus, StatusEntry};
+pub use status::{FileStatus, StatusEntry, StatusOptions};
 
 // Re-export index types
 pub use index::{Index, IndexEntry};
@@ -82,4 +111,4 @@ pub use index::{Index, IndexEntry};
 pub use log::LogOptions;
 
 // Re-export diff types
-pub use diff::{DiffDelta, DiffStats, DiffStatus, TreeDiff};
+pub use diff::{DiffDelta, DiffOptions, DiffStats, DiffStatus, TreeDiff};
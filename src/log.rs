@@ -23,22 +23,31 @@
 //!     println!("{}", commit.unwrap().summary());
 //! }
 //! ```
+//!
+//! # Commit-graph
+//!
+//! When the repository has a commit-graph, the parents' commit times and
+//! trees are read from it instead of parsing each parent commit. If the
+//! commit-graph has changed-path Bloom filters, commits whose filters rule
+//! out every filtered path are skipped without comparing trees.
 
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashSet};
 use std::path::{Path, PathBuf};
 
 use crate::error::Result;
-use crate::objects::{Commit, LooseObjectStore, ObjectType, Oid, Tree};
+use crate::objects::{Commit, CommitGraph, LooseObjectStore, Oid, Tree};
+use crate::pathspec::Pathspec;
 
 /// A pending commit in the priority queue.
 ///
-/// Commits are ordered by timestamp (descending) for traversal.
+/// Commits are ordered by committer timestamp (descending) for traversal,
+/// as in Git.
 #[derive(Debug, Clone)]
 struct PendingCommit {
     /// The commit OID.
     oid: Oid,
-    /// The author timestamp for ordering.
+    /// The committer timestamp for ordering.
     timestamp: i64,
 }
 
@@ -79,8 +88,8 @@ impl Ord for PendingCommit {
 /// ```
 #[derive(Debug, Clone, Default)]
 pub struct LogOptions {
-    /// Filter by paths (commit must touch at least one of these).
-    paths: Vec<PathBuf>,
+    /// Filter by pathspec (commit must touch a matching path).
+    pathspec: Pathspec,
     /// Maximum number of commits to return.
     max_count: Option<usize>,
     /// Only include commits after this timestamp.
@@ -104,13 +113,14 @@ impl LogOptions {
     /// Adds a path to filter by.
     ///
     /// Only commits that modify files at this path will be included.
-    /// Can be called multiple times to add multiple paths.
+    /// Can be called multiple times to add multiple paths. This is a
+    /// shorthand for a literal [`Pathspec`] pattern.
     ///
     /// # Arguments
     ///
     /// * `path` - A file or directory path to filter by.
     pub fn path<P: AsRef<Path>>(mut self, path: P) -> Self {
-        self.paths.push(path.as_ref().to_path_buf());
+        self.pathspec.push_literal(path);
         self
     }
 
@@ -126,8 +136,23 @@ impl LogOptions {
         I: IntoIterator<Item = P>,
         P: AsRef<Path>,
     {
-        self.paths
-            .extend(paths.into_iter().map(|p| p.as_ref().to_path_buf()));
+        for path in paths {
+            self = self.path(path);
+        }
+        self
+    }
+
+    /// Adds a pathspec to filter by.
+    ///
+    /// Only commits that modify files matching the pathspec will be
+    /// included. Unlike [`LogOptions::path`], this supports globs and magic
+    /// such as `:(exclude)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pathspec` - The pathspec to filter by.
+    pub fn pathspec(mut self, pathspec: Pathspec) -> Self {
+        self.pathspec.extend(pathspec);
         self
     }
 
@@ -220,12 +245,18 @@ impl LogOptions {
 
     /// Returns true if path filtering is enabled.
     pub fn has_path_filter(&self) -> bool {
-        !self.paths.is_empty()
+        !self.pathspec.is_empty()
+    }
+
+    /// Returns the configured paths and patterns, as given.
+    #[deprecated(note = "paths are kept as a pathspec; use `get_pathspec`")]
+    pub fn get_paths(&self) -> Vec<PathBuf> {
+        self.pathspec.originals().map(PathBuf::from).collect()
     }
 
-    /// Returns the configured paths.
-    pub fn get_paths(&self) -> &[PathBuf] {
-        &self.paths
+    /// Returns the pathspec used for path filtering.
+    pub fn get_pathspec(&self) -> &Pathspec {
+        &self.pathspec
     }
 
     /// Returns the configured starting commit OID.
@@ -306,6 +337,11 @@ fn parse_date(s: &str) -> i64 {
 pub struct LogIterator {
     /// The loose object store for reading commits.
     store: LooseObjectStore,
+    /// The commit-graph, if the repository has a readable one.
+    graph: Option<CommitGraph>,
+    /// The filtered paths to check Bloom filters for, if the pathspec
+    /// allows it.
+    bloom_paths: Option<Vec<String>>,
     /// Priority queue of pending commits to visit.
     pending: BinaryHeap<PendingCommit>,
     /// Set of already visited commit OIDs to avoid duplicates.
@@ -335,54 +371,68 @@ impl LogIterator {
     /// * `start_oid` - The OID of the commit to start from.
     /// * `options` - Filtering options.
     pub fn with_options(objects_dir: PathBuf, start_oid: Oid, options: LogOptions) -> Result<Self> {
-        let store = LooseObjectStore::new(&objects_dir);
-        let mut pending = BinaryHeap::new();
-        let visited = HashSet::new();
+        Self::with_store(LooseObjectStore::new(objects_dir), start_oid, options)
+    }
 
-        // Read the initial commit to get its timestamp
-        let raw = store.read(&start_oid)?;
-        let commit = Commit::parse(start_oid, raw)?;
+    /// Creates a new LogIterator reading commits through an existing store,
+    /// sharing its object cache.
+    pub(crate) fn with_store(
+        store: LooseObjectStore,
+        start_oid: Oid,
+        options: LogOptions,
+    ) -> Result<Self> {
+        // An unreadable commit-graph only means commits are parsed instead
+        let graph = store.commit_graph();
+        let bloom_paths = graph
+            .as_ref()
+            .and_then(|_| options.pathspec.literal_paths())
+            .filter(|paths| !paths.is_empty());
+        let mut iter = LogIterator {
+            store,
+            graph,
+            bloom_paths,
+            pending: BinaryHeap::new(),
+            visited: HashSet::new(),
+            options,
+            count: 0,
+        };
 
-        pending.push(PendingCommit {
+        // Read the initial commit to get its timestamp
+        let timestamp = iter.commit_time(&start_oid)?;
+        iter.pending.push(PendingCommit {
             oid: start_oid,
-            timestamp: commit.author().timestamp(),
+            timestamp,
         });
 
-        Ok(LogIterator {
-            store,
-            pending,
-            visited,
-            options,
-            count: 0,
-        })
+        Ok(iter)
     }
 
-    /// Reads a commit by its OID.
-    fn read_commit(&self, oid: &Oid) -> Result<Commit> {
-        let raw = self.store.read(oid)?;
+    /// Returns the committer timestamp of a commit, from the commit-graph
+    /// if it has the commit.
+    fn commit_time(&self, oid: &Oid) -> Result<i64> {
+        if let Some(Ok(Some(commit))) = self.graph.as_ref().map(|graph| graph.get(oid)) {
+            return Ok(commit.commit_time());
+        }
+        Ok(self.read_commit(oid)?.committer().timestamp())
+    }
 
-        if raw.object_type != ObjectType::Commit {
-            return Err(crate::error::Error::TypeMismatch {
-                expected: "commit",
-                actual: raw.object_type.as_str(),
-            });
+    /// Returns the root tree of a commit, from the commit-graph if it has
+    /// the commit.
+    fn commit_tree(&self, oid: &Oid) -> Result<Oid> {
+        if let Some(Ok(Some(commit))) = self.graph.as_ref().map(|graph| graph.get(oid)) {
+            return Ok(*commit.tree());
         }
+        Ok(*self.read_commit(oid)?.tree())
+    }
 
-        Commit::parse(*oid, raw)
+    /// Reads a commit by its OID.
+    fn read_commit(&self, oid: &Oid) -> Result<Commit> {
+        self.store.read_commit(oid)
     }
 
     /// Reads a tree by its OID.
     fn read_tree(&self, oid: &Oid) -> Result<Tree> {
-        let raw = self.store.read(oid)?;
-
-        if raw.object_type != ObjectType::Tree {
-            return Err(crate::error::Error::TypeMismatch {
-                expected: "tree",
-                actual: raw.object_type.as_str(),
-            });
-        }
-
-        Tree::parse(raw)
+        self.store.read_tree(oid)
     }
 
     /// Flattens a tree into a map of path -> OID.
@@ -397,9 +447,9 @@ impl LogIterator {
 
         for entry in tree.entries() {
             let path = if prefix.as_os_str().is_empty() {
-                PathBuf::from(entry.name())
+                entry.name_path()
             } else {
-                prefix.join(entry.name())
+                prefix.join(entry.name_path())
             };
 
             if entry.is_directory() {
@@ -415,38 +465,22 @@ impl LogIterator {
         Ok(result)
     }
 
-    /// Checks if a path matches any of the configured filter paths.
+    /// Checks the commit's changed-path Bloom filter for the filtered paths.
     ///
-    /// A path matches if:
-    /// - It exactly matches a filter path
-    /// - It starts with a filter path (filter path is a directory prefix)
-    fn path_matches_filter(&self, path: &Path) -> bool {
-        let path_str = path.to_string_lossy();
-        for filter_path in &self.options.paths {
-            let filter_str = filter_path.to_string_lossy();
-            // Normalize to forward slashes for comparison
-            let path_normalized = path_str.replace('\\', "/");
-            let filter_normalized = filter_str.replace('\\', "/");
-
-            // Exact match
-            if path_normalized == filter_normalized {
-                return true;
-            }
+    /// Returns `true` only if the filter shows that the commit changed none
+    /// of them; without a usable filter the trees must be compared.
+    fn bloom_rules_out(&self, commit: &Commit) -> bool {
+        let (Some(graph), Some(paths)) = (&self.graph, &self.bloom_paths) else {
+            return false;
+        };
+        paths
+            .iter()
+            .all(|path| matches!(graph.maybe_changed(commit.oid(), path), Ok(Some(false))))
+    }
 
-            // Directory prefix match: filter ends with "/" or path starts with filter + "/"
-            if filter_normalized.ends_with('/') {
-                if path_normalized.starts_with(&filter_normalized) {
-                    return true;
-                }
-            } else {
-                // Check if filter is a directory prefix (path starts with filter/)
-                let prefix = format!("{}/", filter_normalized);
-                if path_normalized.starts_with(&prefix) {
-                    return true;
-                }
-            }
-        }
-        false
+    /// Checks if a path matches the configured pathspec.
+    fn path_matches_filter(&self, path: &Path) -> bool {
+        self.options.pathspec.matches(path)
     }
 
     /// Checks if a commit touches any of the configured filter paths.
@@ -462,8 +496,7 @@ impl LogIterator {
 
         // Get parent tree map (empty if no parent)
         let parent_map = if let Some(parent_oid) = commit.parents().first() {
-            let parent_commit = self.read_commit(parent_oid)?;
-            let parent_tree = self.read_tree(parent_commit.tree())?;
+            let parent_tree = self.read_tree(&self.commit_tree(parent_oid)?)?;
             self.flatten_tree_for_diff(&parent_tree, PathBuf::new())?
         } else {
             std::collections::HashMap::new()
@@ -517,7 +550,7 @@ impl LogIterator {
 
         // Check path filter
         if self.options.has_path_filter() {
-            if !self.commit_touches_paths(commit)? {
+            if self.bloom_rules_out(commit) || !self.commit_touches_paths(commit)? {
                 return Ok(false);
             }
         }
@@ -557,11 +590,11 @@ impl Iterator for LogIterator {
                 // Only add the first parent
                 if let Some(parent_oid) = commit.parents().first() {
                     if !self.visited.contains(parent_oid) {
-                        match self.read_commit(parent_oid) {
-                            Ok(parent_commit) => {
+                        match self.commit_time(parent_oid) {
+                            Ok(timestamp) => {
                                 self.pending.push(PendingCommit {
                                     oid: *parent_oid,
-                                    timestamp: parent_commit.author().timestamp(),
+                                    timestamp,
                                 });
                             }
                             Err(e) => return Some(Err(e)),
@@ -572,11 +605,11 @@ impl Iterator for LogIterator {
                 // Add all parents
                 for parent_oid in commit.parents() {
                     if !self.visited.contains(parent_oid) {
-                        match self.read_commit(parent_oid) {
-                            Ok(parent_commit) => {
+                        match self.commit_time(parent_oid) {
+                            Ok(timestamp) => {
                                 self.pending.push(PendingCommit {
                                     oid: *parent_oid,
-                                    timestamp: parent_commit.author().timestamp(),
+                                    timestamp,
                                 });
                             }
                             Err(e) => return Some(Err(e)),
@@ -606,7 +639,7 @@ impl Iterator for LogIterator {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::infra::hash_object;
+    use crate::infra::hash::hash_object;
     use miniz_oxide::deflate::compress_to_vec_zlib;
     use std::fs;
     use tempfile::TempDir;
@@ -621,7 +654,7 @@ mod tests {
         let mut raw = header.into_bytes();
         raw.extend_from_slice(content);
 
-        let oid = Oid::from_bytes(hash_object(object_type, content));
+        let oid = Oid::from_bytes(hash_object(object_type, content).unwrap());
         let compressed = compress_to_vec_zlib(&raw, 6);
 
         let hex = oid.to_hex();
@@ -1173,6 +1206,7 @@ mod tests {
 
     // Test LogOptions builder
     #[test]
+    #[allow(deprecated)]
     fn test_log_options_builder() {
         let options = LogOptions::new()
             .path("src/")
@@ -1183,6 +1217,7 @@ mod tests {
 
         assert!(options.has_path_filter());
         assert_eq!(options.get_paths().len(), 2);
+        assert_eq!(options.get_paths(), [Path::new("src/"), Path::new("tests/")]);
     }
 
     /// Helper to create a tree with file entries.
@@ -1487,4 +1522,44 @@ mod tests {
         assert_eq!(commits[0].summary(), "Update src/lib.rs");
         assert_eq!(commits[1].summary(), "Add src/lib.rs");
     }
+
+    // LO-014: pathspec filter with glob and exclude magic
+    #[test]
+    fn test_log_options_pathspec_filter() {
+        let temp = TempDir::new().unwrap();
+        let objects_dir = temp.path().join("objects");
+        fs::create_dir_all(&objects_dir).unwrap();
+
+        let tree1 = create_nested_tree(&objects_dir, &[("README.md", b"v1")]);
+        let tree2 = create_nested_tree(&objects_dir, &[("README.md", b"v1"), ("src/lib.rs", b"v1")]);
+        let tree3 = create_nested_tree(&objects_dir, &[("README.md", b"v1"), ("src/lib.rs", b"v1"), ("src/notes.md", b"v1")]);
+
+        let c1 = make_commit_content_with_time(&tree1.to_hex(), None, "Add README", 1000);
+        let c1_oid = create_loose_object(&objects_dir, c1.as_bytes(), "commit");
+
+        let c2 = make_commit_content_with_time(&tree2.to_hex(), Some(&c1_oid.to_hex()), "Add src/lib.rs", 2000);
+        let c2_oid = create_loose_object(&objects_dir, c2.as_bytes(), "commit");
+
+        let c3 = make_commit_content_with_time(&tree3.to_hex(), Some(&c2_oid.to_hex()), "Add src/notes.md", 3000);
+        let c3_oid = create_loose_object(&objects_dir, c3.as_bytes(), "commit");
+
+        // "*.md" matches at any depth since pathspec globs cross '/'
+        let options = LogOptions::new().pathspec(Pathspec::new(["*.md"]).unwrap());
+        assert!(options.has_path_filter());
+        let log = LogIterator::with_options(objects_dir.clone(), c3_oid, options).unwrap();
+        let summaries: Vec<_> = log
+            .filter_map(Result::ok)
+            .map(|c| c.summary().to_string())
+            .collect();
+        assert_eq!(summaries, ["Add src/notes.md", "Add README"]);
+
+        // Excluding markdown leaves only the commit touching lib.rs
+        let options = LogOptions::new().pathspec(Pathspec::new([":(exclude)*.md"]).unwrap());
+        let log = LogIterator::with_options(objects_dir, c3_oid, options).unwrap();
+        let summaries: Vec<_> = log
+            .filter_map(Result::ok)
+            .map(|c| c.summary().to_string())
+            .collect();
+        assert_eq!(summaries, ["Add src/lib.rs"]);
+    }
 }
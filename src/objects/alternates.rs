@@ -0,0 +1,216 @@
+//! Alternate object directories (`objects/info/alternates`).
+//!
+//! A repository borrows objects from other object directories listed one
+//! per line in `objects/info/alternates`, as set up by
+//! `git clone --reference` or `git clone --shared`, and in the
+//! `GIT_ALTERNATE_OBJECT_DIRECTORIES` environment variable.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::infra::path;
+
+/// The environment variable listing extra alternate object directories.
+pub(crate) const ALTERNATES_ENV: &str = "GIT_ALTERNATE_OBJECT_DIRECTORIES";
+
+/// How deep alternates files are followed, as in Git.
+const MAX_DEPTH: usize = 5;
+
+/// Resolves the alternate object directories of an objects directory.
+///
+/// The directories listed in `env`, separated as in `PATH`, come first,
+/// then those in `objects/info/alternates`. The alternates file of each
+/// alternate is followed in turn, up to five levels deep. Relative entries
+/// are relative to the objects directory whose alternates file lists
+/// them, or to the current directory for `env`.
+///
+/// # Arguments
+///
+/// * `objects_dir` - Path to the `.git/objects` directory.
+/// * `env` - The value of `GIT_ALTERNATE_OBJECT_DIRECTORIES`, if set.
+///
+/// # Returns
+///
+/// The canonical paths of the alternate object directories in lookup
+/// order. Directories that do not exist, repeats and `objects_dir` itself
+/// are skipped.
+pub(crate) fn resolve(objects_dir: &Path, env: Option<&OsStr>) -> Vec<PathBuf> {
+    let mut seen: Vec<PathBuf> = fs::canonicalize(objects_dir).into_iter().collect();
+    let mut dirs = Vec::new();
+    if let Some(env) = env {
+        for entry in std::env::split_paths(env) {
+            if !entry.as_os_str().is_empty() {
+                link(&entry, 0, &mut seen, &mut dirs);
+            }
+        }
+    }
+    read_alternates(objects_dir, 0, &mut seen, &mut dirs);
+    dirs
+}
+
+/// Adds the directories listed in the alternates file of an objects
+/// directory.
+fn read_alternates(
+    objects_dir: &Path,
+    depth: usize,
+    seen: &mut Vec<PathBuf>,
+    dirs: &mut Vec<PathBuf>,
+) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+    let Ok(content) = fs::read(objects_dir.join("info/alternates")) else {
+        return;
+    };
+    for line in content.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() || line[0] == b'#' {
+            continue;
+        }
+        let entry = match unquote(line) {
+            Some(unquoted) => path::from_bytes(&unquoted),
+            None => path::from_bytes(line),
+        };
+        link(&objects_dir.join(entry), depth, seen, dirs);
+    }
+}
+
+/// Adds an alternate object directory, then the ones it borrows from.
+fn link(dir: &Path, depth: usize, seen: &mut Vec<PathBuf>, dirs: &mut Vec<PathBuf>) {
+    let Ok(dir) = fs::canonicalize(dir) else {
+        return;
+    };
+    if !dir.is_dir() || seen.contains(&dir) {
+        return;
+    }
+    seen.push(dir.clone());
+    dirs.push(dir.clone());
+    read_alternates(&dir, depth + 1, seen, dirs);
+}
+
+/// Unquotes an entry that Git quoted C-style because of special
+/// characters.
+///
+/// # Returns
+///
+/// The unquoted entry, or `None` if it is not quoted or the quoting is
+/// malformed, in which case the entry is used as it is.
+fn unquote(entry: &[u8]) -> Option<Vec<u8>> {
+    let inner = entry.strip_prefix(b"\"")?.strip_suffix(b"\"")?;
+    let mut unquoted = Vec::with_capacity(inner.len());
+    let mut bytes = inner.iter().copied();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'"' => return None,
+            b'\\' => {
+                let escaped = match bytes.next()? {
+                    b'a' => 0x07,
+                    b'b' => 0x08,
+                    b'f' => 0x0c,
+                    b'n' => b'\n',
+                    b'r' => b'\r',
+                    b't' => b'\t',
+                    b'v' => 0x0b,
+                    c @ (b'\\' | b'"') => c,
+                    c @ b'0'..=b'3' => {
+                        let mut value = c - b'0';
+                        for _ in 0..2 {
+                            match bytes.next()? {
+                                d @ b'0'..=b'7' => value = value * 8 + (d - b'0'),
+                                _ => return None,
+                            }
+                        }
+                        value
+                    }
+                    _ => return None,
+                };
+                unquoted.push(escaped);
+            }
+            _ => unquoted.push(b),
+        }
+    }
+    Some(unquoted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Creates an objects directory whose alternates file has the given
+    /// content.
+    fn objects_dir(root: &Path, name: &str, alternates: &str) -> PathBuf {
+        let dir = root.join(name).join("objects");
+        fs::create_dir_all(dir.join("info")).unwrap();
+        if !alternates.is_empty() {
+            fs::write(dir.join("info/alternates"), alternates).unwrap();
+        }
+        fs::canonicalize(dir).unwrap()
+    }
+
+    // ALT-001: Absolute, relative, nested and quoted entries are resolved in order
+    #[test]
+    fn test_resolve_entries() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        let nested = objects_dir(root, "nested", "");
+        let cache = objects_dir(root, "cache", "../../nested/objects\n");
+        let quoted = objects_dir(root, "with space", "");
+        let local = objects_dir(
+            root,
+            "local",
+            &format!(
+                "# comment\n\n{}\n../../cache/objects\r\n\"../../with\\040space/objects\"\n../../missing/objects\n",
+                nested.display()
+            ),
+        );
+
+        // Repeats and missing directories are skipped
+        assert_eq!(resolve(&local, None), vec![nested, cache, quoted]);
+    }
+
+    // ALT-002: The environment comes first; cycles and self-references end
+    #[test]
+    fn test_resolve_env_and_cycles() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        let first = objects_dir(root, "first", "../../second/objects\n");
+        let second = objects_dir(root, "second", "../../first/objects\n../../local/objects\n");
+        let local = objects_dir(root, "local", "../../second/objects\n");
+        let env = std::env::join_paths([&first, &local]).unwrap();
+
+        assert_eq!(
+            resolve(&local, Some(&env)),
+            vec![first.clone(), second.clone()]
+        );
+        assert_eq!(resolve(&local, None), vec![second, first]);
+    }
+
+    // ALT-003: Alternates files are followed at most five levels deep
+    #[test]
+    fn test_resolve_depth_limit() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        let mut dirs = vec![objects_dir(root, "d7", "")];
+        for i in (0..7).rev() {
+            let next = format!("../../d{}/objects\n", i + 1);
+            dirs.insert(0, objects_dir(root, &format!("d{}", i), &next));
+        }
+
+        assert_eq!(resolve(&dirs[0], None), dirs[1..7].to_vec());
+    }
+
+    // ALT-004: C-style quoting is undone, malformed quoting is kept as is
+    #[test]
+    fn test_unquote() {
+        assert_eq!(
+            unquote(br#""a\tb\"c\\d\303\251""#).unwrap(),
+            b"a\tb\"c\\d\xc3\xa9"
+        );
+        assert_eq!(unquote(b"plain"), None);
+        assert_eq!(unquote(br#""bad\q""#), None);
+        assert_eq!(unquote(br#""short\12""#), None);
+        assert_eq!(unquote(br#""a"b""#), None);
+    }
+}
@@ -0,0 +1,534 @@
+//! Bounded in-memory caches of objects read from the object database.
+//!
+//! History walks read the same commits and trees over and over: every
+//! path-limited log step, tree flatten and tree diff starts from the root
+//! tree. An [`ObjectCache`] keeps recently used objects so later reads skip
+//! decompression and parsing:
+//!
+//! - Decompressed objects returned by [`LooseObjectStore::read`], and
+//!   parsed trees and commits, share one byte budget.
+//! - Delta bases resolved while reading packs have their own budget, like
+//!   Git's `core.deltaBaseCacheLimit`, so reading the objects of a delta
+//!   chain one after another does not re-apply the whole chain each time.
+//!
+//! Both caches evict the least recently used entries first. Objects are
+//! named by their content, so a cached object never goes stale.
+//!
+//! The open packs of each objects directory are kept alongside, so that
+//! packed reads do not list the `pack` directory and open every index
+//! again; the stores decide when a list is out of date.
+//!
+//! [`LooseObjectStore::read`]: super::LooseObjectStore::read
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::commit::Commit;
+use super::oid::Oid;
+use super::store::{ObjectType, PackList, RawObject};
+use super::tree::Tree;
+
+/// Default byte budget for decompressed and parsed objects.
+const DEFAULT_OBJECT_LIMIT: usize = 32 * 1024 * 1024;
+
+/// Default byte budget for delta bases (Git's `core.deltaBaseCacheLimit`).
+const DEFAULT_DELTA_BASE_LIMIT: usize = 96 * 1024 * 1024;
+
+/// Bytes charged for each entry on top of its content, for the key, the
+/// bookkeeping and the parsed structures.
+const ENTRY_OVERHEAD: usize = 64;
+
+/// Objects larger than this fraction of a budget are not cached, so one
+/// large blob cannot evict everything else.
+const MAX_ENTRY_FRACTION: usize = 8;
+
+/// Options for the object caches of a repository.
+///
+/// # Examples
+///
+/// ```
+/// use zerogit::CacheOptions;
+///
+/// let options = CacheOptions::new()
+///     .object_limit(8 * 1024 * 1024)
+///     .delta_base_limit(0);
+/// assert_eq!(options.get_object_limit(), 8 * 1024 * 1024);
+/// assert_eq!(options.get_delta_base_limit(), 0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheOptions {
+    /// Byte budget for decompressed and parsed objects.
+    object_limit: usize,
+    /// Byte budget for delta bases read from packs.
+    delta_base_limit: usize,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        CacheOptions {
+            object_limit: DEFAULT_OBJECT_LIMIT,
+            delta_base_limit: DEFAULT_DELTA_BASE_LIMIT,
+        }
+    }
+}
+
+impl CacheOptions {
+    /// Creates a new CacheOptions with default settings: 32 MiB for
+    /// objects and 96 MiB for delta bases.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the byte budget for decompressed and parsed objects.
+    ///
+    /// A budget of 0 disables the object cache.
+    pub fn object_limit(mut self, bytes: usize) -> Self {
+        self.object_limit = bytes;
+        self
+    }
+
+    /// Sets the byte budget for delta bases read from packs
+    /// (`core.deltaBaseCacheLimit`).
+    ///
+    /// A budget of 0 disables the delta base cache.
+    pub fn delta_base_limit(mut self, bytes: usize) -> Self {
+        self.delta_base_limit = bytes;
+        self
+    }
+
+    /// Returns the byte budget for decompressed and parsed objects.
+    pub fn get_object_limit(&self) -> usize {
+        self.object_limit
+    }
+
+    /// Returns the byte budget for delta bases.
+    pub fn get_delta_base_limit(&self) -> usize {
+        self.delta_base_limit
+    }
+}
+
+/// Counters of the object caches of a repository.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    object_hits: u64,
+    object_misses: u64,
+    object_bytes: usize,
+    delta_base_hits: u64,
+    delta_base_misses: u64,
+    delta_base_bytes: usize,
+}
+
+impl CacheStats {
+    /// Returns the number of object reads answered from the cache.
+    pub fn object_hits(&self) -> u64 {
+        self.object_hits
+    }
+
+    /// Returns the number of object reads that went to the object
+    /// database.
+    pub fn object_misses(&self) -> u64 {
+        self.object_misses
+    }
+
+    /// Returns the bytes charged to cached objects.
+    pub fn object_bytes(&self) -> usize {
+        self.object_bytes
+    }
+
+    /// Returns the number of delta bases found in the cache.
+    pub fn delta_base_hits(&self) -> u64 {
+        self.delta_base_hits
+    }
+
+    /// Returns the number of delta bases that had to be read from a pack.
+    pub fn delta_base_misses(&self) -> u64 {
+        self.delta_base_misses
+    }
+
+    /// Returns the bytes charged to cached delta bases.
+    pub fn delta_base_bytes(&self) -> usize {
+        self.delta_base_bytes
+    }
+}
+
+/// What an object cache entry holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ObjectKind {
+    Raw,
+    Tree,
+    Commit,
+}
+
+/// A cached object, decompressed or parsed.
+enum CachedObject {
+    Raw(RawObject),
+    Tree(Tree),
+    Commit(Box<Commit>),
+}
+
+/// A resolved delta base: the object type and content.
+type DeltaBase = (ObjectType, Arc<Vec<u8>>);
+
+/// The objects and delta bases read from one object database, and its
+/// open packs, shared by every store and pack opened on it.
+pub(crate) struct ObjectCache {
+    objects: Mutex<Lru<(Oid, ObjectKind), CachedObject>>,
+    delta_bases: Mutex<Lru<(PathBuf, u64), DeltaBase>>,
+    packs: Mutex<HashMap<PathBuf, Arc<PackList>>>,
+}
+
+impl ObjectCache {
+    /// Creates empty caches with the given budgets.
+    pub(crate) fn new(options: CacheOptions) -> Self {
+        ObjectCache {
+            objects: Mutex::new(Lru::new(options.object_limit)),
+            delta_bases: Mutex::new(Lru::new(options.delta_base_limit)),
+            packs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Changes the budgets, evicting entries that no longer fit.
+    pub(crate) fn set_options(&self, options: CacheOptions) {
+        lock(&self.objects).set_limit(options.object_limit);
+        lock(&self.delta_bases).set_limit(options.delta_base_limit);
+    }
+
+    /// Returns the current budgets.
+    pub(crate) fn options(&self) -> CacheOptions {
+        CacheOptions {
+            object_limit: lock(&self.objects).limit,
+            delta_base_limit: lock(&self.delta_bases).limit,
+        }
+    }
+
+    /// Returns the hit and miss counters and the bytes in use.
+    pub(crate) fn stats(&self) -> CacheStats {
+        let objects = lock(&self.objects);
+        let delta_bases = lock(&self.delta_bases);
+        CacheStats {
+            object_hits: objects.hits,
+            object_misses: objects.misses,
+            object_bytes: objects.used,
+            delta_base_hits: delta_bases.hits,
+            delta_base_misses: delta_bases.misses,
+            delta_base_bytes: delta_bases.used,
+        }
+    }
+
+    /// Drops every cached entry and open pack, and resets the counters.
+    pub(crate) fn clear(&self) {
+        lock(&self.objects).clear();
+        lock(&self.delta_bases).clear();
+        lock(&self.packs).clear();
+    }
+
+    /// Returns a cached decompressed object.
+    pub(crate) fn raw(&self, oid: &Oid) -> Option<RawObject> {
+        match lock(&self.objects).get(&(*oid, ObjectKind::Raw)) {
+            Some(CachedObject::Raw(raw)) => Some(raw.clone()),
+            _ => None,
+        }
+    }
+
+    /// Caches a decompressed object.
+    pub(crate) fn insert_raw(&self, oid: Oid, raw: &RawObject) {
+        let size = raw.content.len() + ENTRY_OVERHEAD;
+        let mut objects = lock(&self.objects);
+        if objects.fits(size) {
+            objects.insert((oid, ObjectKind::Raw), CachedObject::Raw(raw.clone()), size);
+        }
+    }
+
+    /// Returns a cached parsed tree.
+    pub(crate) fn tree(&self, oid: &Oid) -> Option<Tree> {
+        match lock(&self.objects).get(&(*oid, ObjectKind::Tree)) {
+            Some(CachedObject::Tree(tree)) => Some(tree.clone()),
+            _ => None,
+        }
+    }
+
+    /// Caches a parsed tree, charging per entry for the parsed names.
+    pub(crate) fn insert_tree(&self, oid: Oid, tree: &Tree, content_len: usize) {
+        let size = content_len + tree.len() * ENTRY_OVERHEAD + ENTRY_OVERHEAD;
+        let mut objects = lock(&self.objects);
+        if objects.fits(size) {
+            objects.insert(
+                (oid, ObjectKind::Tree),
+                CachedObject::Tree(tree.clone()),
+                size,
+            );
+        }
+    }
+
+    /// Returns a cached parsed commit.
+    pub(crate) fn commit(&self, oid: &Oid) -> Option<Commit> {
+        match lock(&self.objects).get(&(*oid, ObjectKind::Commit)) {
+            Some(CachedObject::Commit(commit)) => Some((**commit).clone()),
+            _ => None,
+        }
+    }
+
+    /// Caches a parsed commit.
+    pub(crate) fn insert_commit(&self, commit: &Commit, content_len: usize) {
+        let size = content_len + ENTRY_OVERHEAD;
+        let mut objects = lock(&self.objects);
+        if objects.fits(size) {
+            objects.insert(
+                (*commit.oid(), ObjectKind::Commit),
+                CachedObject::Commit(Box::new(commit.clone())),
+                size,
+            );
+        }
+    }
+
+    /// Returns the resolved object at an offset in a pack.
+    pub(crate) fn delta_base(&self, pack_path: &Path, offset: u64) -> Option<DeltaBase> {
+        lock(&self.delta_bases)
+            .get(&(pack_path.to_path_buf(), offset))
+            .cloned()
+    }
+
+    /// Caches the resolved object at an offset in a pack, for the deltas
+    /// based on it.
+    pub(crate) fn insert_delta_base(
+        &self,
+        pack_path: &Path,
+        offset: u64,
+        object_type: ObjectType,
+        content: &[u8],
+    ) {
+        let size = content.len() + ENTRY_OVERHEAD;
+        let mut delta_bases = lock(&self.delta_bases);
+        if delta_bases.fits(size) {
+            delta_bases.insert(
+                (pack_path.to_path_buf(), offset),
+                (object_type, Arc::new(content.to_vec())),
+                size,
+            );
+        }
+    }
+
+    /// Returns the packs last listed in an objects directory.
+    pub(crate) fn pack_list(&self, objects_dir: &Path) -> Option<Arc<PackList>> {
+        lock(&self.packs).get(objects_dir).cloned()
+    }
+
+    /// Keeps the packs listed in an objects directory, replacing the
+    /// previous list.
+    pub(crate) fn insert_pack_list(&self, objects_dir: &Path, packs: Arc<PackList>) {
+        lock(&self.packs).insert(objects_dir.to_path_buf(), packs);
+    }
+
+    /// Forgets the packs listed in an objects directory.
+    pub(crate) fn remove_pack_list(&self, objects_dir: &Path) {
+        lock(&self.packs).remove(objects_dir);
+    }
+}
+
+impl std::fmt::Debug for ObjectCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectCache")
+            .field("options", &self.options())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// Locks a cache, recovering it if another thread panicked while holding
+/// it; a cache entry is only ever replaced whole.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// A least-recently-used map bounded by the total size of its entries.
+struct Lru<K, V> {
+    /// The byte budget.
+    limit: usize,
+    /// Bytes charged to the current entries.
+    used: usize,
+    /// Incremented on every access, to order entries by last use.
+    clock: u64,
+    /// Entries with their size and the time of their last use.
+    entries: HashMap<K, LruEntry<V>>,
+    /// Keys by the time of their last use, oldest first.
+    order: BTreeMap<u64, K>,
+    hits: u64,
+    misses: u64,
+}
+
+struct LruEntry<V> {
+    value: V,
+    size: usize,
+    used_at: u64,
+}
+
+impl<K: Clone + Eq + Hash, V> Lru<K, V> {
+    fn new(limit: usize) -> Self {
+        Lru {
+            limit,
+            used: 0,
+            clock: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns `true` if an entry of this size may be cached.
+    fn fits(&self, size: usize) -> bool {
+        size <= self.limit / MAX_ENTRY_FRACTION
+    }
+
+    /// Looks up an entry, marking it as the most recently used.
+    fn get(&mut self, key: &K) -> Option<&V> {
+        let Some(entry) = self.entries.get_mut(key) else {
+            self.misses += 1;
+            return None;
+        };
+        self.hits += 1;
+        self.clock += 1;
+        let key = self
+            .order
+            .remove(&entry.used_at)
+            .expect("every entry is ordered");
+        entry.used_at = self.clock;
+        self.order.insert(self.clock, key);
+        Some(&entry.value)
+    }
+
+    /// Adds or replaces an entry, then evicts the least recently used
+    /// entries until the budget is met.
+    fn insert(&mut self, key: K, value: V, size: usize) {
+        self.remove(&key);
+        self.clock += 1;
+        self.order.insert(self.clock, key.clone());
+        self.entries.insert(
+            key,
+            LruEntry {
+                value,
+                size,
+                used_at: self.clock,
+            },
+        );
+        self.used += size;
+        self.evict();
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.used_at);
+            self.used -= entry.size;
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.used > self.limit {
+            let Some((_, key)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.used -= entry.size;
+            }
+        }
+    }
+
+    fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.evict();
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.used = 0;
+        self.hits = 0;
+        self.misses = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(content: &[u8]) -> RawObject {
+        RawObject {
+            object_type: ObjectType::Blob,
+            content: content.to_vec(),
+        }
+    }
+
+    fn oid(byte: u8) -> Oid {
+        Oid::from_bytes([byte; 20])
+    }
+
+    // OC-001: Least recently used entries are evicted first
+    #[test]
+    fn test_lru_eviction() {
+        let mut lru = Lru::new(30);
+        lru.insert("a", 1, 10);
+        lru.insert("b", 2, 10);
+        lru.insert("c", 3, 10);
+        assert_eq!(lru.get(&"a"), Some(&1));
+
+        lru.insert("d", 4, 10);
+        assert_eq!(lru.get(&"b"), None);
+        assert_eq!(lru.get(&"a"), Some(&1));
+        assert_eq!(lru.get(&"c"), Some(&3));
+        assert_eq!(lru.used, 30);
+
+        // Replacing an entry charges only its new size
+        lru.insert("c", 5, 5);
+        assert_eq!(lru.used, 25);
+        lru.set_limit(12);
+        assert_eq!(lru.entries.len(), 1);
+        assert_eq!(lru.get(&"a"), None);
+        assert_eq!(lru.get(&"c"), Some(&5));
+        assert_eq!((lru.hits, lru.misses), (4, 2));
+    }
+
+    // OC-002: Objects are cached within the budget and by kind
+    #[test]
+    fn test_object_cache() {
+        let cache = ObjectCache::new(CacheOptions::new().object_limit(8 * 200));
+        cache.insert_raw(oid(1), &raw(b"hello"));
+        assert_eq!(cache.raw(&oid(1)).unwrap().content, b"hello");
+        assert!(cache.tree(&oid(1)).is_none());
+        assert!(cache.raw(&oid(2)).is_none());
+
+        // Larger than an eighth of the budget
+        cache.insert_raw(oid(3), &raw(&[0u8; 200]));
+        assert!(cache.raw(&oid(3)).is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.object_hits(), stats.object_misses()), (1, 3));
+        assert_eq!(stats.object_bytes(), 5 + ENTRY_OVERHEAD);
+
+        cache.clear();
+        assert!(cache.raw(&oid(1)).is_none());
+        assert_eq!(cache.stats().object_bytes(), 0);
+    }
+
+    // OC-003: A zero budget disables caching
+    #[test]
+    fn test_disabled_cache() {
+        let cache = ObjectCache::new(CacheOptions::new().object_limit(0).delta_base_limit(0));
+        cache.insert_raw(oid(1), &raw(b""));
+        assert!(cache.raw(&oid(1)).is_none());
+        cache.insert_delta_base(Path::new("p.pack"), 12, ObjectType::Blob, b"base");
+        assert!(cache.delta_base(Path::new("p.pack"), 12).is_none());
+
+        cache.set_options(CacheOptions::new());
+        cache.insert_delta_base(Path::new("p.pack"), 12, ObjectType::Blob, b"base");
+        let (object_type, content) = cache.delta_base(Path::new("p.pack"), 12).unwrap();
+        assert_eq!(
+            (object_type, content.as_slice()),
+            (ObjectType::Blob, &b"base"[..])
+        );
+        assert!(cache.delta_base(Path::new("q.pack"), 12).is_none());
+    }
+}
@@ -3,6 +3,7 @@
 use super::oid::Oid;
 use super::store::{ObjectType, RawObject};
 use crate::error::{Error, Result};
+use crate::infra::encoding;
 
 /// A signature representing an author or committer.
 ///
@@ -136,6 +137,8 @@ pub struct Commit {
     committer: Signature,
     /// The commit message.
     message: String,
+    /// The encoding named by the `encoding` header, if any.
+    encoding: Option<String>,
 }
 
 impl Commit {
@@ -151,6 +154,10 @@ impl Commit {
     /// <message>
     /// ```
     ///
+    /// The signatures and message are decoded according to the `encoding`
+    /// header (UTF-8 if there is none); bytes that cannot be decoded are
+    /// replaced rather than rejected.
+    ///
     /// # Arguments
     ///
     /// * `oid` - The OID (SHA-1 hash) of this commit.
@@ -163,7 +170,8 @@ impl Commit {
             });
         }
 
-        let content = std::str::from_utf8(&raw.content).map_err(|_| Error::InvalidUtf8)?;
+        let header_encoding = encoding::header_encoding(&raw.content);
+        let content = encoding::decode(&raw.content, header_encoding);
 
         let mut tree: Option<Oid> = None;
         let mut parents = Vec::new();
@@ -221,6 +229,7 @@ impl Commit {
             author,
             committer,
             message,
+            encoding: header_encoding.map(str::to_string),
         })
     }
 
@@ -259,6 +268,25 @@ impl Commit {
         &self.message
     }
 
+    /// Returns the encoding named by the commit's `encoding` header, if
+    /// any.
+    ///
+    /// [`message`](Commit::message) is already decoded from it.
+    pub fn encoding(&self) -> Option<&str> {
+        self.encoding.as_deref()
+    }
+
+    /// Returns `false` if the `encoding` header names an encoding that
+    /// cannot be decoded exactly, in which case
+    /// [`message`](Commit::message) was decoded lossily as UTF-8.
+    ///
+    /// UTF-8, ISO-8859-1, ISO-8859-15 and Windows-1252 are supported.
+    pub fn is_encoding_supported(&self) -> bool {
+        self.encoding
+            .as_deref()
+            .map_or(true, encoding::is_supported)
+    }
+
     /// Returns the first line of the commit message (the summary).
     pub fn summary(&self) -> &str {
         self.message.lines().next().unwrap_or("")
@@ -454,6 +482,43 @@ mod tests {
         assert_eq!(commit.summary(), "Initial commit");
     }
 
+    // CM-010: Signatures and messages are decoded using the encoding header
+    #[test]
+    fn test_parse_encoding() {
+        let mut content = format!("tree {}\n", TREE_SHA).into_bytes();
+        content.extend_from_slice(b"author Jos\xe9 <jose@example.com> 1234567890 +0000\n");
+        content.extend_from_slice(b"committer Jos\xe9 <jose@example.com> 1234567890 +0000\n");
+        content.extend_from_slice(b"encoding ISO-8859-1\n\nCaf\xe9 cr\xe8me");
+        let raw = RawObject {
+            object_type: ObjectType::Commit,
+            content,
+        };
+        let commit = Commit::parse(dummy_oid(), raw).unwrap();
+
+        assert_eq!(commit.encoding(), Some("ISO-8859-1"));
+        assert_eq!(commit.author().name(), "José");
+        assert_eq!(commit.message(), "Café crème");
+        assert!(commit.is_encoding_supported());
+
+        let plain = Commit::parse(dummy_oid(), make_commit(&simple_commit())).unwrap();
+        assert_eq!(plain.encoding(), None);
+        assert!(plain.is_encoding_supported());
+
+        // Unsupported encodings are reported, and decoded lossily
+        let mut content = format!("tree {}\n", TREE_SHA).into_bytes();
+        content.extend_from_slice(b"author A <a@example.com> 1234567890 +0000\n");
+        content.extend_from_slice(b"committer A <a@example.com> 1234567890 +0000\n");
+        content.extend_from_slice(b"encoding Shift_JIS\n\n\x93\xfa\x96\x7b");
+        let raw = RawObject {
+            object_type: ObjectType::Commit,
+            content,
+        };
+        let commit = Commit::parse(dummy_oid(), raw).unwrap();
+        assert_eq!(commit.encoding(), Some("Shift_JIS"));
+        assert!(!commit.is_encoding_supported());
+        assert_eq!(commit.message(), "\u{FFFD}\u{FFFD}\u{FFFD}{");
+    }
+
     // Additional: Empty message
     #[test]
     fn test_empty_message() {
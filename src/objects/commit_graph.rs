@@ -0,0 +1,1085 @@
+//! Commit-graph files.
+//!
+//! A commit-graph (`objects/info/commit-graph`) stores the parents, root
+//! tree, commit time and generation number of many commits, so that
+//! history can be walked without inflating and parsing each commit. It
+//! starts with a header (the magic `CGPH`, version, hash version, chunk
+//! count and base graph count), followed by a table of chunk IDs and
+//! offsets. The chunks used here are:
+//!
+//! - `OIDF`: a fan-out table of 256 cumulative commit counts by first byte
+//! - `OIDL`: the sorted commit IDs
+//! - `CDAT`: for each commit, its tree, the positions of its first two
+//!   parents, its topological level and its commit time
+//! - `GDA2`: for each commit, its corrected commit date as an offset from
+//!   the commit time, if present
+//! - `GDO2`: 8-byte offsets too large for `GDA2`, if any
+//! - `EDGE`: the positions of the other parents of octopus merges
+//! - `BIDX`: for each commit, the end offset of its changed-path Bloom
+//!   filter in `BDAT`, if present
+//! - `BDAT`: the Bloom filter settings, then the filters themselves
+//! - `BASE`: the checksums of the graphs below this one in a chain
+//!
+//! A commit's changed-path Bloom filter holds every path that differs from
+//! its first parent, and every directory leading to one. A path missing
+//! from the filter was certainly not changed, so path-limited history can
+//! skip the commit without comparing trees.
+//!
+//! Instead of a single file, the graph may be split into a chain of
+//! layers listed, base first, in `objects/info/commit-graphs/commit-graph-chain`.
+//! Parent positions then count the commits of all layers below as well.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::commit::Commit;
+use super::oid::Oid;
+use super::pack::{be_u32, Fanout};
+use crate::error::{Error, Result};
+use crate::infra::{digest, murmur3, Fsync, FsyncComponent, ObjectFormat};
+
+/// Magic bytes at the start of a commit-graph.
+const GRAPH_MAGIC: &[u8; 4] = b"CGPH";
+
+/// Size of the commit-graph header.
+const HEADER_SIZE: usize = 8;
+
+/// Size of an entry of the chunk table (ID and offset).
+const CHUNK_ENTRY_SIZE: usize = 12;
+
+/// Chunk of the fan-out table.
+const CHUNK_OID_FANOUT: u32 = u32::from_be_bytes(*b"OIDF");
+
+/// Chunk of the sorted commit IDs.
+const CHUNK_OID_LOOKUP: u32 = u32::from_be_bytes(*b"OIDL");
+
+/// Chunk of commit data.
+const CHUNK_COMMIT_DATA: u32 = u32::from_be_bytes(*b"CDAT");
+
+/// Chunk of corrected commit date offsets.
+const CHUNK_GENERATION_DATA: u32 = u32::from_be_bytes(*b"GDA2");
+
+/// Chunk of corrected commit date offsets that do not fit in 31 bits.
+const CHUNK_GENERATION_OVERFLOW: u32 = u32::from_be_bytes(*b"GDO2");
+
+/// Chunk of octopus merge parents.
+const CHUNK_EXTRA_EDGES: u32 = u32::from_be_bytes(*b"EDGE");
+
+/// Chunk of changed-path Bloom filter end offsets.
+const CHUNK_BLOOM_INDEXES: u32 = u32::from_be_bytes(*b"BIDX");
+
+/// Chunk of changed-path Bloom filters.
+const CHUNK_BLOOM_DATA: u32 = u32::from_be_bytes(*b"BDAT");
+
+/// Chunk of base graph checksums.
+const CHUNK_BASE_GRAPHS: u32 = u32::from_be_bytes(*b"BASE");
+
+/// Size of the `BDAT` header (hash version, hash count, bits per entry).
+const BLOOM_HEADER_SIZE: usize = 12;
+
+/// Seeds of the two hashes each Bloom filter bit position is derived from.
+const BLOOM_SEEDS: [u32; 2] = [0x293a_e76f, 0x7e64_6e2c];
+
+/// Parent position of a missing parent.
+const PARENT_NONE: u32 = 0x7000_0000;
+
+/// Set on the second parent position when it indexes `EDGE` instead, and
+/// on the last `EDGE` entry of each commit.
+const PARENT_EXTRA: u32 = 0x8000_0000;
+
+/// Largest topological level that can be stored.
+const GENERATION_V1_MAX: u64 = 0x3fff_ffff;
+
+/// Set on a `GDA2` entry when it indexes `GDO2` instead.
+const GENERATION_OVERFLOW: u32 = 0x8000_0000;
+
+/// A commit as recorded in a commit-graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphCommit {
+    oid: Oid,
+    tree: Oid,
+    parents: Vec<Oid>,
+    commit_time: i64,
+    generation: u64,
+}
+
+impl GraphCommit {
+    /// Returns the ID of the commit.
+    pub fn oid(&self) -> &Oid {
+        &self.oid
+    }
+
+    /// Returns the ID of the commit's root tree.
+    pub fn tree(&self) -> &Oid {
+        &self.tree
+    }
+
+    /// Returns the IDs of the commit's parents, in order.
+    pub fn parents(&self) -> &[Oid] {
+        &self.parents
+    }
+
+    /// Returns the committer timestamp in seconds since the Unix epoch.
+    pub fn commit_time(&self) -> i64 {
+        self.commit_time
+    }
+
+    /// Returns the generation number of the commit.
+    ///
+    /// This is the corrected commit date if every file of the graph
+    /// records one, and the topological level otherwise. Either way, a
+    /// commit's generation is greater than those of its parents.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+/// One commit-graph file.
+#[derive(Debug)]
+struct Layer {
+    /// Path to the file.
+    path: PathBuf,
+    /// The contents of the file.
+    data: Vec<u8>,
+    /// Number of commits whose first byte is at most the table position.
+    fanout: Fanout,
+    /// Offset of the `OIDL` chunk.
+    oid_lookup: usize,
+    /// Offset of the `CDAT` chunk.
+    commit_data: usize,
+    /// Offset of the `GDA2` chunk, if present.
+    generation_data: Option<usize>,
+    /// Offset and entry count of the `GDO2` chunk, if present.
+    generation_overflow: Option<(usize, usize)>,
+    /// Offset and entry count of the `EDGE` chunk, if present.
+    extra_edges: Option<(usize, usize)>,
+    /// The changed-path Bloom filters, if present and readable.
+    bloom: Option<BloomFilters>,
+}
+
+/// Where the changed-path Bloom filters of a commit-graph file are, and
+/// how they were hashed.
+#[derive(Debug, Clone, Copy)]
+struct BloomFilters {
+    /// Offset of the `BIDX` chunk.
+    index: usize,
+    /// Offset of the first filter in the `BDAT` chunk.
+    data: usize,
+    /// Total size of the filters.
+    data_len: usize,
+    /// Hash version: 1 hashes bytes as signed, 2 as unsigned.
+    version: u32,
+    /// Number of bits set for each path.
+    hashes: u32,
+}
+
+impl BloomFilters {
+    /// Returns `true` if a filter may contain a path: all of its bits are
+    /// set.
+    fn contains(&self, filter: &[u8], path: &[u8]) -> bool {
+        let signed = self.version == 1;
+        let [first, second] = BLOOM_SEEDS.map(|seed| murmur3(seed, path, signed));
+        let bits = filter.len() as u64 * 8;
+        (0..self.hashes).all(|i| {
+            let pos = (first.wrapping_add(i.wrapping_mul(second)) as u64 % bits) as usize;
+            filter[pos / 8] & (1 << (pos % 8)) != 0
+        })
+    }
+}
+
+impl Layer {
+    /// Parses a commit-graph file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file.
+    /// * `data` - The contents of the file.
+    /// * `format` - The object format of the repository.
+    /// * `bases` - The checksums of the layers below, base first.
+    fn parse(
+        path: PathBuf,
+        data: Vec<u8>,
+        format: ObjectFormat,
+        bases: &[Vec<u8>],
+    ) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidCommitGraph {
+            path: path.clone(),
+            reason: reason.to_string(),
+        };
+        let hash_size = format.size();
+
+        if data.len() < HEADER_SIZE + hash_size {
+            return Err(invalid("commit-graph is too short"));
+        }
+        if &data[..4] != GRAPH_MAGIC || data[4] != 1 {
+            return Err(invalid("unsupported commit-graph version"));
+        }
+        if data[5] as u32 != format.hash_id() {
+            return Err(invalid("commit-graph uses a different object format"));
+        }
+        let chunk_count = data[6] as usize;
+        if data[7] as usize != bases.len() {
+            return Err(invalid("base graph count does not match the chain"));
+        }
+
+        // The table ends with an entry whose offset is the end of the last
+        // chunk, so each chunk's size is the distance to the next offset
+        let table_end = HEADER_SIZE + (chunk_count + 1) * CHUNK_ENTRY_SIZE;
+        let table = data
+            .get(HEADER_SIZE..table_end)
+            .ok_or_else(|| invalid("chunk table is truncated"))?;
+        let entries: Vec<(u32, usize)> = table
+            .chunks(CHUNK_ENTRY_SIZE)
+            .map(|entry| {
+                let mut offset = [0u8; 8];
+                offset.copy_from_slice(&entry[4..]);
+                (be_u32(entry), u64::from_be_bytes(offset) as usize)
+            })
+            .collect();
+        let data_end = data.len() - hash_size;
+        if entries[0].1 < table_end
+            || entries.windows(2).any(|w| w[0].1 > w[1].1)
+            || entries[chunk_count].1 > data_end
+        {
+            return Err(invalid("chunk offsets are out of order"));
+        }
+        let chunk = |id: u32| {
+            let i = entries[..chunk_count].iter().position(|e| e.0 == id)?;
+            Some((entries[i].1, entries[i + 1].1 - entries[i].1))
+        };
+        let required = |id: u32, name: &str| {
+            chunk(id).ok_or_else(|| invalid(&format!("missing {} chunk", name)))
+        };
+
+        let (fanout_offset, fanout_len) = required(CHUNK_OID_FANOUT, "fan-out")?;
+        if fanout_len != 256 * 4 {
+            return Err(invalid("fan-out chunk has the wrong size"));
+        }
+        let fanout = Fanout::parse(&data[fanout_offset..fanout_offset + fanout_len])
+            .ok_or_else(|| invalid("fan-out table is not sorted"))?;
+        let commits = fanout.len();
+
+        let (oid_lookup, lookup_len) = required(CHUNK_OID_LOOKUP, "commit ID")?;
+        let (commit_data, data_len) = required(CHUNK_COMMIT_DATA, "commit data")?;
+        if lookup_len < commits * hash_size || data_len < commits * (hash_size + 16) {
+            return Err(invalid(
+                "commit chunks are smaller than the fan-out table says",
+            ));
+        }
+        let generation_data = match chunk(CHUNK_GENERATION_DATA) {
+            Some((offset, len)) if len >= commits * 4 => Some(offset),
+            Some(_) => return Err(invalid("generation data chunk is too small")),
+            None => None,
+        };
+        let generation_overflow =
+            chunk(CHUNK_GENERATION_OVERFLOW).map(|(offset, len)| (offset, len / 8));
+        let extra_edges = chunk(CHUNK_EXTRA_EDGES).map(|(offset, len)| (offset, len / 4));
+
+        // Filters that cannot be used are ignored rather than rejected, as
+        // Git does, since they only make path-limited history faster
+        let bloom = match (chunk(CHUNK_BLOOM_INDEXES), chunk(CHUNK_BLOOM_DATA)) {
+            (Some((index, index_len)), Some((offset, len)))
+                if index_len == commits * 4 && len >= BLOOM_HEADER_SIZE =>
+            {
+                let version = be_u32(&data[offset..]);
+                let hashes = be_u32(&data[offset + 4..]);
+                (matches!(version, 1 | 2) && hashes > 0).then_some(BloomFilters {
+                    index,
+                    data: offset + BLOOM_HEADER_SIZE,
+                    data_len: len - BLOOM_HEADER_SIZE,
+                    version,
+                    hashes,
+                })
+            }
+            _ => None,
+        };
+
+        if !bases.is_empty() {
+            let (base_offset, base_len) = required(CHUNK_BASE_GRAPHS, "base graphs")?;
+            let expected = bases.concat();
+            if data[base_offset..base_offset + base_len] != expected[..] {
+                return Err(invalid("base graphs do not match the chain"));
+            }
+        }
+
+        Ok(Layer {
+            path,
+            data,
+            fanout,
+            oid_lookup,
+            commit_data,
+            generation_data,
+            generation_overflow,
+            extra_edges,
+            bloom,
+        })
+    }
+
+    /// Returns the number of commits in this file.
+    fn len(&self) -> usize {
+        self.fanout.len()
+    }
+
+    /// Returns the checksum at the end of the file.
+    fn checksum(&self, format: ObjectFormat) -> &[u8] {
+        &self.data[self.data.len() - format.size()..]
+    }
+
+    /// Returns the ID at a position of the sorted ID table.
+    fn oid_at(&self, pos: usize, format: ObjectFormat) -> Result<Oid> {
+        let size = format.size();
+        Oid::from_slice(&self.data[self.oid_lookup + pos * size..][..size])
+    }
+
+    /// Finds the position of a commit in the sorted ID table.
+    fn position(&self, oid: &Oid) -> Option<usize> {
+        let size = oid.as_bytes().len();
+        self.fanout
+            .search(oid, |pos, name| {
+                name.copy_from_slice(&self.data[self.oid_lookup + pos * size..][..size]);
+                Ok(())
+            })
+            .ok()
+            .flatten()
+    }
+
+    /// Returns the changed-path Bloom filter of the commit at a position,
+    /// or `None` if the file has no filters or this commit has none.
+    fn bloom_filter(&self, pos: usize) -> Result<Option<(&BloomFilters, &[u8])>> {
+        let Some(bloom) = &self.bloom else {
+            return Ok(None);
+        };
+        let start = match pos {
+            0 => 0,
+            _ => be_u32(&self.data[bloom.index + (pos - 1) * 4..]) as usize,
+        };
+        let end = be_u32(&self.data[bloom.index + pos * 4..]) as usize;
+        if start > end || end > bloom.data_len {
+            return Err(self.invalid("Bloom filter out of range"));
+        }
+        if start == end {
+            return Ok(None);
+        }
+        Ok(Some((
+            bloom,
+            &self.data[bloom.data + start..bloom.data + end],
+        )))
+    }
+
+    /// Returns an `InvalidCommitGraph` error for this file.
+    fn invalid(&self, reason: &str) -> Error {
+        Error::InvalidCommitGraph {
+            path: self.path.clone(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// A commit-graph, read from a single file or a chain of files.
+///
+/// The files are read into memory when the graph is opened, so lookups do
+/// no I/O.
+///
+/// # Examples
+///
+/// ```no_run
+/// use zerogit::objects::{CommitGraph, ObjectFormat, Oid};
+///
+/// let graph = CommitGraph::open(".git/objects", ObjectFormat::Sha1).unwrap();
+/// if let Some(graph) = graph {
+///     let oid = Oid::from_hex("0123456789abcdef0123456789abcdef01234567").unwrap();
+///     if let Some(commit) = graph.get(&oid).unwrap() {
+///         println!("{} parents", commit.parents().len());
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CommitGraph {
+    format: ObjectFormat,
+    /// The files of the graph, base first.
+    layers: Vec<Layer>,
+    /// Number of commits in the layers below each layer.
+    offsets: Vec<usize>,
+    /// Whether every layer records corrected commit dates.
+    corrected_dates: bool,
+}
+
+impl CommitGraph {
+    /// Opens the commit-graph of an object database.
+    ///
+    /// `objects/info/commit-graph` is used if it exists, and the chain in
+    /// `objects/info/commit-graphs` otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `objects_dir` - Path to the `.git/objects` directory.
+    /// * `format` - The object format of the repository.
+    ///
+    /// # Returns
+    ///
+    /// The graph, or `None` if the repository has no commit-graph.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidCommitGraph` if a file is malformed, uses
+    /// another object format, or does not match the chain.
+    pub fn open<P: AsRef<Path>>(objects_dir: P, format: ObjectFormat) -> Result<Option<Self>> {
+        let info_dir = objects_dir.as_ref().join("info");
+        let single = info_dir.join("commit-graph");
+        match fs::read(&single) {
+            Ok(data) => {
+                let layer = Layer::parse(single, data, format, &[])?;
+                return Ok(Some(Self::from_layers(vec![layer], format)));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let graphs_dir = info_dir.join("commit-graphs");
+        let chain_path = graphs_dir.join("commit-graph-chain");
+        let chain = match fs::read_to_string(&chain_path) {
+            Ok(chain) => chain,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut layers: Vec<Layer> = Vec::new();
+        let mut bases: Vec<Vec<u8>> = Vec::new();
+        for line in chain.lines().filter(|line| !line.is_empty()) {
+            let hash = Oid::from_hex(line)
+                .ok()
+                .filter(|oid| oid.format() == format)
+                .ok_or_else(|| Error::InvalidCommitGraph {
+                    path: chain_path.clone(),
+                    reason: format!("invalid graph name: {}", line),
+                })?;
+            let path = graphs_dir.join(format!("graph-{}.graph", line));
+            let data = fs::read(&path)?;
+            let layer = Layer::parse(path, data, format, &bases)?;
+            if layer.checksum(format) != hash.as_bytes() {
+                return Err(layer.invalid("checksum does not match the file name"));
+            }
+            bases.push(hash.as_bytes().to_vec());
+            layers.push(layer);
+        }
+        if layers.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self::from_layers(layers, format)))
+    }
+
+    /// Creates a graph from its parsed files, base first.
+    fn from_layers(layers: Vec<Layer>, format: ObjectFormat) -> Self {
+        let mut offsets = Vec::with_capacity(layers.len());
+        let mut total = 0;
+        for layer in &layers {
+            offsets.push(total);
+            total += layer.len();
+        }
+        let corrected_dates = layers.iter().all(|layer| layer.generation_data.is_some());
+        CommitGraph {
+            format,
+            layers,
+            offsets,
+            corrected_dates,
+        }
+    }
+
+    /// Returns the paths of the files of the graph, base first.
+    pub fn paths(&self) -> Vec<&Path> {
+        self.layers
+            .iter()
+            .map(|layer| layer.path.as_path())
+            .collect()
+    }
+
+    /// Returns the number of commits in the graph.
+    pub fn len(&self) -> usize {
+        self.offsets.last().copied().unwrap_or(0)
+            + self.layers.last().map_or(0, |layer| layer.len())
+    }
+
+    /// Returns `true` if the graph has no commits.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the graph contains the commit.
+    pub fn contains(&self, oid: &Oid) -> bool {
+        self.position(oid).is_some()
+    }
+
+    /// Lists the IDs of all commits in the graph, sorted.
+    pub fn oids(&self) -> Result<Vec<Oid>> {
+        let mut oids = Vec::with_capacity(self.len());
+        for layer in &self.layers {
+            for pos in 0..layer.len() {
+                oids.push(layer.oid_at(pos, self.format)?);
+            }
+        }
+        oids.sort();
+        Ok(oids)
+    }
+
+    /// Looks up a commit in the graph.
+    ///
+    /// # Returns
+    ///
+    /// The commit's tree, parents, commit time and generation number, or
+    /// `None` if the commit is not in the graph.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidCommitGraph` if a parent position or
+    /// generation offset is out of range.
+    pub fn get(&self, oid: &Oid) -> Result<Option<GraphCommit>> {
+        let Some(pos) = self.position(oid) else {
+            return Ok(None);
+        };
+        self.commit_at(pos).map(Some)
+    }
+
+    /// Checks a commit's changed-path Bloom filter for a path.
+    ///
+    /// The path and each directory leading to it must all be in the filter
+    /// for the commit to possibly have changed the path.
+    ///
+    /// # Arguments
+    ///
+    /// * `oid` - The commit.
+    /// * `path` - A path relative to the repository root, with `/`
+    ///   separators.
+    ///
+    /// # Returns
+    ///
+    /// `Some(false)` if the commit certainly did not change the path or
+    /// anything below it compared with its first parent, `Some(true)` if it
+    /// may have, or `None` if the commit is not in the graph or has no
+    /// filter.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidCommitGraph` if the filter is out of range.
+    pub fn maybe_changed(&self, oid: &Oid, path: &str) -> Result<Option<bool>> {
+        let Some((layer, local)) = self.position(oid).and_then(|pos| self.locate(pos)) else {
+            return Ok(None);
+        };
+        let Some((bloom, filter)) = layer.bloom_filter(local)? else {
+            return Ok(None);
+        };
+        let path = path.trim_end_matches('/');
+        let mut keys = path.match_indices('/').map(|(i, _)| &path[..i]);
+        Ok(Some(
+            keys.all(|dir| bloom.contains(filter, dir.as_bytes()))
+                && bloom.contains(filter, path.as_bytes()),
+        ))
+    }
+
+    /// Finds the position of a commit across all layers.
+    fn position(&self, oid: &Oid) -> Option<usize> {
+        if oid.format() != self.format {
+            return None;
+        }
+        self.layers
+            .iter()
+            .zip(&self.offsets)
+            .find_map(|(layer, offset)| Some(offset + layer.position(oid)?))
+    }
+
+    /// Returns the layer holding a position, and the position within it.
+    fn locate(&self, pos: usize) -> Option<(&Layer, usize)> {
+        let i = self.offsets.partition_point(|&offset| offset <= pos);
+        let layer = self.layers.get(i.checked_sub(1)?)?;
+        let local = pos - self.offsets[i - 1];
+        (local < layer.len()).then_some((layer, local))
+    }
+
+    /// Reads the commit at a position across all layers.
+    fn commit_at(&self, pos: usize) -> Result<GraphCommit> {
+        let size = self.format.size();
+        let (layer, local) = self.locate(pos).ok_or_else(|| Error::InvalidCommitGraph {
+            path: self.layers[0].path.clone(),
+            reason: "commit position out of range".to_string(),
+        })?;
+        let entry = &layer.data[layer.commit_data + local * (size + 16)..][..size + 16];
+        let tree = Oid::from_slice(&entry[..size])?;
+
+        // Parents may only be in this layer or the ones below it
+        let visible = pos - local + layer.len();
+        let parent_at = |parent: u32| -> Result<Oid> {
+            let parent = parent as usize;
+            if parent >= visible {
+                return Err(layer.invalid("parent position out of range"));
+            }
+            Ok(self.commit_oid(parent))
+        };
+        let mut parents = Vec::new();
+        let first = be_u32(&entry[size..]);
+        let second = be_u32(&entry[size + 4..]);
+        if first != PARENT_NONE {
+            parents.push(parent_at(first)?);
+        }
+        if second & PARENT_EXTRA != 0 {
+            let (edges, count) = layer
+                .extra_edges
+                .ok_or_else(|| layer.invalid("missing extra edges chunk"))?;
+            let mut index = (second & !PARENT_EXTRA) as usize;
+            loop {
+                if index >= count {
+                    return Err(layer.invalid("extra edge out of range"));
+                }
+                let edge = be_u32(&layer.data[edges + index * 4..]);
+                parents.push(parent_at(edge & !PARENT_EXTRA)?);
+                if edge & PARENT_EXTRA != 0 {
+                    break;
+                }
+                index += 1;
+            }
+        } else if second != PARENT_NONE {
+            parents.push(parent_at(second)?);
+        }
+
+        let level_and_time = be_u32(&entry[size + 8..]);
+        let commit_time =
+            (((level_and_time & 0x3) as u64) << 32 | be_u32(&entry[size + 12..]) as u64) as i64;
+        let generation = if self.corrected_dates {
+            let offset = be_u32(&layer.data[layer.generation_data.unwrap() + local * 4..]);
+            let offset = if offset & GENERATION_OVERFLOW == 0 {
+                offset as u64
+            } else {
+                let index = (offset & !GENERATION_OVERFLOW) as usize;
+                let (overflow, _) = layer
+                    .generation_overflow
+                    .filter(|&(_, count)| index < count)
+                    .ok_or_else(|| layer.invalid("generation overflow out of range"))?;
+                let mut buf = [0u8; 8];
+                buf.copy_from_slice(&layer.data[overflow + index * 8..][..8]);
+                u64::from_be_bytes(buf)
+            };
+            commit_time as u64 + offset
+        } else {
+            (level_and_time >> 2) as u64
+        };
+
+        Ok(GraphCommit {
+            oid: layer.oid_at(local, self.format)?,
+            tree,
+            parents,
+            commit_time,
+            generation,
+        })
+    }
+
+    /// Returns the ID of the commit at a valid position across all layers.
+    fn commit_oid(&self, pos: usize) -> Oid {
+        let (layer, local) = self.locate(pos).expect("position checked by caller");
+        layer
+            .oid_at(local, self.format)
+            .expect("object ID of the repository's format")
+    }
+}
+
+/// Writes a commit-graph file for a set of commits.
+///
+/// Every parent of an added commit must be added too. The file records
+/// both topological levels and corrected commit dates, as Git does by
+/// default.
+///
+/// # Examples
+///
+/// ```no_run
+/// use zerogit::objects::{CommitGraphWriter, ObjectFormat};
+/// use zerogit::Repository;
+///
+/// let repo = Repository::open(".").unwrap();
+/// let mut writer = CommitGraphWriter::new(ObjectFormat::Sha1);
+/// for commit in repo.log().unwrap() {
+///     writer.add(&commit.unwrap());
+/// }
+/// let path = writer.write(repo.git_dir().join("objects")).unwrap();
+/// println!("wrote {}", path.display());
+/// ```
+pub struct CommitGraphWriter {
+    format: ObjectFormat,
+    /// Tree, parents and commit time of each commit, sorted by ID.
+    commits: std::collections::BTreeMap<Oid, (Oid, Vec<Oid>, i64)>,
+    fsync: Arc<Fsync>,
+}
+
+impl CommitGraphWriter {
+    /// Creates a writer with no commits.
+    pub fn new(format: ObjectFormat) -> Self {
+        CommitGraphWriter {
+            format,
+            commits: std::collections::BTreeMap::new(),
+            fsync: Arc::default(),
+        }
+    }
+
+    /// Flushes the written file as the repository's `core.fsync` settings
+    /// ask, instead of always flushing it.
+    pub(crate) fn with_fsync(mut self, fsync: Arc<Fsync>) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// Adds a commit to the graph. Adding the same commit twice stores it
+    /// once.
+    pub fn add(&mut self, commit: &Commit) {
+        self.commits.insert(
+            *commit.oid(),
+            (
+                *commit.tree(),
+                commit.parents().to_vec(),
+                commit.committer().timestamp(),
+            ),
+        );
+    }
+
+    /// Returns the number of commits added.
+    pub fn len(&self) -> usize {
+        self.commits.len()
+    }
+
+    /// Returns `true` if no commits were added.
+    pub fn is_empty(&self) -> bool {
+        self.commits.is_empty()
+    }
+
+    /// Writes the graph to `objects/info/commit-graph`, replacing any
+    /// commit-graph file or chain.
+    ///
+    /// # Arguments
+    ///
+    /// * `objects_dir` - Path to the `.git/objects` directory.
+    ///
+    /// # Returns
+    ///
+    /// The path of the written file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ObjectNotFound` if the parent of an added commit
+    /// was not added.
+    pub fn write<P: AsRef<Path>>(&self, objects_dir: P) -> Result<PathBuf> {
+        let oids: Vec<&Oid> = self.commits.keys().collect();
+        let mut parent_positions = Vec::with_capacity(oids.len());
+        for (_, parents, _) in self.commits.values() {
+            let positions = parents
+                .iter()
+                .map(|parent| {
+                    oids.binary_search(&parent)
+                        .map_err(|_| Error::ObjectNotFound(parent.to_hex()))
+                })
+                .collect::<Result<Vec<usize>>>()?;
+            parent_positions.push(positions);
+        }
+        let (levels, corrected) = self.generations(&parent_positions);
+
+        let mut fanout = Vec::with_capacity(256 * 4);
+        for byte in 0..=255u8 {
+            let count = oids.partition_point(|oid| oid.as_bytes()[0] <= byte);
+            fanout.extend_from_slice(&(count as u32).to_be_bytes());
+        }
+        let mut lookup = Vec::with_capacity(oids.len() * self.format.size());
+        let mut commit_data = Vec::new();
+        let mut generation_data = Vec::new();
+        let mut generation_overflow = Vec::new();
+        let mut extra_edges = Vec::new();
+        for (pos, (oid, (tree, _, time))) in self.commits.iter().enumerate() {
+            lookup.extend_from_slice(oid.as_bytes());
+            commit_data.extend_from_slice(tree.as_bytes());
+
+            let parents = &parent_positions[pos];
+            let first = parents.first().map_or(PARENT_NONE, |&p| p as u32);
+            let second = match parents.len() {
+                0 | 1 => PARENT_NONE,
+                2 => parents[1] as u32,
+                _ => {
+                    let index = (extra_edges.len() / 4) as u32 | PARENT_EXTRA;
+                    for (i, &parent) in parents[1..].iter().enumerate() {
+                        let last = if i == parents.len() - 2 {
+                            PARENT_EXTRA
+                        } else {
+                            0
+                        };
+                        extra_edges.extend_from_slice(&(parent as u32 | last).to_be_bytes());
+                    }
+                    index
+                }
+            };
+            commit_data.extend_from_slice(&first.to_be_bytes());
+            commit_data.extend_from_slice(&second.to_be_bytes());
+            let time = (*time).max(0) as u64;
+            let level_and_time = (levels[pos] << 2) as u32 | ((time >> 32) & 0x3) as u32;
+            commit_data.extend_from_slice(&level_and_time.to_be_bytes());
+            commit_data.extend_from_slice(&(time as u32).to_be_bytes());
+
+            let offset = corrected[pos] - time;
+            if offset < GENERATION_OVERFLOW as u64 {
+                generation_data.extend_from_slice(&(offset as u32).to_be_bytes());
+            } else {
+                let index = (generation_overflow.len() / 8) as u32 | GENERATION_OVERFLOW;
+                generation_data.extend_from_slice(&index.to_be_bytes());
+                generation_overflow.extend_from_slice(&offset.to_be_bytes());
+            }
+        }
+
+        let mut chunks = vec![
+            (CHUNK_OID_FANOUT, fanout),
+            (CHUNK_OID_LOOKUP, lookup),
+            (CHUNK_COMMIT_DATA, commit_data),
+            (CHUNK_GENERATION_DATA, generation_data),
+        ];
+        if !generation_overflow.is_empty() {
+            chunks.push((CHUNK_GENERATION_OVERFLOW, generation_overflow));
+        }
+        if !extra_edges.is_empty() {
+            chunks.push((CHUNK_EXTRA_EDGES, extra_edges));
+        }
+
+        let mut data = GRAPH_MAGIC.to_vec();
+        data.push(1);
+        data.push(self.format.hash_id() as u8);
+        data.push(chunks.len() as u8);
+        data.push(0);
+        let mut offset = (HEADER_SIZE + (chunks.len() + 1) * CHUNK_ENTRY_SIZE) as u64;
+        for (id, chunk) in &chunks {
+            data.extend_from_slice(&id.to_be_bytes());
+            data.extend_from_slice(&offset.to_be_bytes());
+            offset += chunk.len() as u64;
+        }
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&offset.to_be_bytes());
+        for (_, chunk) in chunks {
+            data.extend_from_slice(&chunk);
+        }
+        let checksum = digest(self.format, &data);
+        data.extend_from_slice(&checksum);
+
+        let info_dir = objects_dir.as_ref().join("info");
+        let path = info_dir.join("commit-graph");
+        self.fsync
+            .write_file(FsyncComponent::CommitGraph, &path, &data, false)?;
+
+        // The new file covers the commits of any chain, which would only
+        // be read if it were removed
+        let graphs_dir = info_dir.join("commit-graphs");
+        if graphs_dir.exists() {
+            fs::remove_dir_all(&graphs_dir)?;
+        }
+        Ok(path)
+    }
+
+    /// Computes the topological level and corrected commit date of each
+    /// commit, parents first, without recursion.
+    fn generations(&self, parent_positions: &[Vec<usize>]) -> (Vec<u64>, Vec<u64>) {
+        let times: Vec<u64> = self
+            .commits
+            .values()
+            .map(|(_, _, time)| (*time).max(0) as u64)
+            .collect();
+        let mut levels = vec![0u64; times.len()];
+        let mut corrected = vec![0u64; times.len()];
+
+        for start in 0..times.len() {
+            let mut stack = vec![start];
+            while let Some(&pos) = stack.last() {
+                if levels[pos] != 0 {
+                    stack.pop();
+                    continue;
+                }
+                let pending: Vec<usize> = parent_positions[pos]
+                    .iter()
+                    .copied()
+                    .filter(|&parent| levels[parent] == 0)
+                    .collect();
+                if !pending.is_empty() {
+                    stack.extend(pending);
+                    continue;
+                }
+                let parents = &parent_positions[pos];
+                levels[pos] = parents
+                    .iter()
+                    .map(|&parent| levels[parent] + 1)
+                    .max()
+                    .unwrap_or(1)
+                    .min(GENERATION_V1_MAX);
+                corrected[pos] = parents
+                    .iter()
+                    .map(|&parent| corrected[parent] + 1)
+                    .fold(times[pos], u64::max);
+                stack.pop();
+            }
+        }
+        (levels, corrected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{ObjectType, RawObject};
+    use tempfile::TempDir;
+
+    /// Builds a commit with the given parents and commit time.
+    fn commit(n: u8, parents: &[&Commit], time: i64) -> Commit {
+        let mut content = format!(
+            "tree {}\n",
+            Oid::from_hex(&format!("{:02x}", n).repeat(20)).unwrap()
+        );
+        for parent in parents {
+            content.push_str(&format!("parent {}\n", parent.oid()));
+        }
+        content.push_str(&format!("author A <a@a> {} +0000\n", time));
+        content.push_str(&format!(
+            "committer C <c@c> {} +0000\n\nCommit {}\n",
+            time, n
+        ));
+        let oid = Oid::from_hex(&format!("{:02x}", 255 - n).repeat(20)).unwrap();
+        let raw = RawObject {
+            object_type: ObjectType::Commit,
+            content: content.into_bytes(),
+        };
+        Commit::parse(oid, raw).unwrap()
+    }
+
+    // CG-001: Written graphs read back with parents, times and generations
+    #[test]
+    fn test_commit_graph_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let root = commit(1, &[], 1_000);
+        let a = commit(2, &[&root], 2_000);
+        // Older than its parent, so its corrected date is pushed forward
+        let b = commit(3, &[&root], 500);
+        let c = commit(4, &[&root], 3_000);
+        let octopus = commit(5, &[&a, &b, &c], 1 << 33);
+
+        let mut writer = CommitGraphWriter::new(ObjectFormat::Sha1);
+        assert!(writer.is_empty());
+        for commit in [&root, &a, &b, &c, &octopus, &a] {
+            writer.add(commit);
+        }
+        assert_eq!(writer.len(), 5);
+        let path = writer.write(temp.path()).unwrap();
+        assert_eq!(path, temp.path().join("info/commit-graph"));
+
+        let graph = CommitGraph::open(temp.path(), ObjectFormat::Sha1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(graph.len(), 5);
+        assert_eq!(graph.paths(), vec![path.as_path()]);
+        let mut expected: Vec<Oid> = [&root, &a, &b, &c, &octopus]
+            .iter()
+            .map(|c| *c.oid())
+            .collect();
+        expected.sort();
+        assert_eq!(graph.oids().unwrap(), expected);
+
+        for commit in [&root, &a, &b, &c, &octopus] {
+            let entry = graph.get(commit.oid()).unwrap().unwrap();
+            assert_eq!(entry.oid(), commit.oid());
+            assert_eq!(entry.tree(), commit.tree());
+            assert_eq!(entry.parents(), commit.parents());
+            assert_eq!(entry.commit_time(), commit.committer().timestamp());
+        }
+        let generation = |commit: &Commit| graph.get(commit.oid()).unwrap().unwrap().generation();
+        assert_eq!(generation(&root), 1_000);
+        assert_eq!(generation(&b), 1_001);
+        assert_eq!(generation(&octopus), 1 << 33);
+
+        let missing = Oid::from_hex(&"01".repeat(20)).unwrap();
+        assert!(!graph.contains(&missing));
+        assert_eq!(graph.get(&missing).unwrap(), None);
+    }
+
+    // CG-002: Topological levels are used when corrected dates are absent
+    #[test]
+    fn test_commit_graph_topological_levels() {
+        let temp = TempDir::new().unwrap();
+        let root = commit(1, &[], 1_000);
+        let a = commit(2, &[&root], 900);
+        let merge = commit(3, &[&a, &root], 800);
+        let mut writer = CommitGraphWriter::new(ObjectFormat::Sha1);
+        for commit in [&root, &a, &merge] {
+            writer.add(commit);
+        }
+        let path = writer.write(temp.path()).unwrap();
+
+        // Rename the GDA2 chunk so that it is not recognized
+        let mut data = fs::read(&path).unwrap();
+        let entry = HEADER_SIZE + 3 * CHUNK_ENTRY_SIZE;
+        assert_eq!(&data[entry..entry + 4], b"GDA2");
+        data[entry..entry + 4].copy_from_slice(b"XXXX");
+        fs::write(&path, &data).unwrap();
+
+        let graph = CommitGraph::open(temp.path(), ObjectFormat::Sha1)
+            .unwrap()
+            .unwrap();
+        let levels: Vec<u64> = [&root, &a, &merge]
+            .iter()
+            .map(|c| graph.get(c.oid()).unwrap().unwrap().generation())
+            .collect();
+        assert_eq!(levels, vec![1, 2, 3]);
+    }
+
+    // CG-003: Missing parents and malformed files are rejected
+    #[test]
+    fn test_commit_graph_invalid() {
+        let temp = TempDir::new().unwrap();
+        assert!(CommitGraph::open(temp.path(), ObjectFormat::Sha1)
+            .unwrap()
+            .is_none());
+
+        let root = commit(1, &[], 1_000);
+        let child = commit(2, &[&root], 2_000);
+        let mut writer = CommitGraphWriter::new(ObjectFormat::Sha1);
+        writer.add(&child);
+        assert!(matches!(
+            writer.write(temp.path()),
+            Err(Error::ObjectNotFound(oid)) if oid == root.oid().to_hex()
+        ));
+        writer.add(&root);
+        let path = writer.write(temp.path()).unwrap();
+        let valid = fs::read(&path).unwrap();
+
+        let open = |data: &[u8]| {
+            fs::write(&path, data).unwrap();
+            CommitGraph::open(temp.path(), ObjectFormat::Sha1)
+        };
+        let is_invalid = |result: Result<Option<CommitGraph>>| matches!(result, Err(Error::InvalidCommitGraph { path: p, .. }) if p == path);
+        assert!(is_invalid(open(&valid[..10])));
+        assert!(is_invalid(CommitGraph::open(
+            temp.path(),
+            ObjectFormat::Sha256
+        )));
+        let mut data = valid.clone();
+        data[4] = 2;
+        assert!(is_invalid(open(&data)));
+        let mut data = valid.clone();
+        data[7] = 1;
+        assert!(is_invalid(open(&data)));
+
+        // A parent position past the end of the graph
+        let mut data = valid.clone();
+        let cdat = data.len() - 20 - 8 - 2 * 36;
+        let pos = graph_position(&valid, child.oid());
+        data[cdat + pos * 36 + 20..][..4].copy_from_slice(&7u32.to_be_bytes());
+        let graph = open(&data).unwrap().unwrap();
+        assert!(matches!(
+            graph.get(child.oid()),
+            Err(Error::InvalidCommitGraph { .. })
+        ));
+    }
+
+    /// Returns the position of a commit in a written file.
+    fn graph_position(data: &[u8], oid: &Oid) -> usize {
+        let oidl = HEADER_SIZE + 5 * CHUNK_ENTRY_SIZE + 256 * 4;
+        data[oidl..]
+            .chunks(20)
+            .position(|name| name == oid.as_bytes())
+            .unwrap()
+    }
+}
@@ -0,0 +1,346 @@
+//! Git's binary delta format, used for deltified objects in packfiles.
+//!
+//! A delta starts with the sizes of the base and of the result, each as a
+//! little-endian base-128 varint, followed by instructions:
+//!
+//! - `1xxxxxxx` copies a range of the base. The low four bits say which
+//!   offset bytes follow, the next three which size bytes follow; a size
+//!   of zero means `0x10000`.
+//! - `0xxxxxxx` (non-zero) inserts the next `x` bytes of the delta.
+
+use std::collections::HashMap;
+
+/// Length of the blocks of the base that are indexed for matching.
+const BLOCK_SIZE: usize = 16;
+
+/// Largest range a single copy instruction covers.
+const MAX_COPY_SIZE: usize = 0x10000;
+
+/// Largest run of bytes a single insert instruction carries.
+const MAX_INSERT_SIZE: usize = 0x7f;
+
+/// Most positions remembered for a single block hash, so that highly
+/// repetitive bases do not make matching quadratic.
+const MAX_BUCKET_LEN: usize = 64;
+
+/// Multiplier of the rolling block hash.
+const HASH_BASE: u32 = 0x0100_0193;
+
+/// An index of the blocks of a delta base.
+///
+/// Building the index is the expensive part of delta compression, so a
+/// base that is tried against several targets is indexed once.
+#[derive(Debug)]
+pub(crate) struct DeltaIndex {
+    /// Positions of the blocks of the base, by block hash.
+    blocks: HashMap<u32, Vec<u32>>,
+}
+
+impl DeltaIndex {
+    /// Indexes the non-overlapping blocks of `base`.
+    pub(crate) fn new(base: &[u8]) -> Self {
+        let mut blocks: HashMap<u32, Vec<u32>> = HashMap::new();
+        if base.len() <= u32::MAX as usize {
+            for pos in (0..base.len().saturating_sub(BLOCK_SIZE - 1)).step_by(BLOCK_SIZE) {
+                let bucket = blocks
+                    .entry(block_hash(&base[pos..pos + BLOCK_SIZE]))
+                    .or_default();
+                if bucket.len() < MAX_BUCKET_LEN {
+                    bucket.push(pos as u32);
+                }
+            }
+        }
+        DeltaIndex { blocks }
+    }
+
+    /// Computes a delta that turns `base` into `target`.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - The base the index was built from.
+    /// * `target` - The content the delta should produce.
+    /// * `max_size` - The largest delta worth keeping.
+    ///
+    /// # Returns
+    ///
+    /// The delta, or `None` if it would be larger than `max_size`.
+    pub(crate) fn delta(&self, base: &[u8], target: &[u8], max_size: usize) -> Option<Vec<u8>> {
+        let mut out = Vec::new();
+        push_varint(&mut out, base.len() as u64);
+        push_varint(&mut out, target.len() as u64);
+
+        let mut literal_start = 0;
+        let mut pos = 0;
+        let mut hash = match target.get(..BLOCK_SIZE) {
+            Some(block) => block_hash(block),
+            None => 0,
+        };
+        while pos + BLOCK_SIZE <= target.len() {
+            let (mut offset, mut len) = self.longest_match(base, &target[pos..], hash);
+            if len < BLOCK_SIZE {
+                if pos + BLOCK_SIZE < target.len() {
+                    hash = roll_hash(hash, target[pos], target[pos + BLOCK_SIZE]);
+                }
+                pos += 1;
+                if out.len() + pos - literal_start > max_size {
+                    return None;
+                }
+                continue;
+            }
+
+            // Grow the match backwards over bytes that would otherwise be
+            // inserted literally
+            let mut start = pos;
+            while start > literal_start && offset > 0 && base[offset - 1] == target[start - 1] {
+                offset -= 1;
+                start -= 1;
+                len += 1;
+            }
+            push_insert(&mut out, &target[literal_start..start]);
+            push_copy(&mut out, offset, len);
+            if out.len() > max_size {
+                return None;
+            }
+
+            pos = start + len;
+            literal_start = pos;
+            if let Some(block) = target.get(pos..pos + BLOCK_SIZE) {
+                hash = block_hash(block);
+            }
+        }
+        push_insert(&mut out, &target[literal_start..]);
+
+        (out.len() <= max_size).then_some(out)
+    }
+
+    /// Finds the longest prefix of `target` that occurs in `base` at an
+    /// indexed block, returning its offset in the base and its length.
+    fn longest_match(&self, base: &[u8], target: &[u8], hash: u32) -> (usize, usize) {
+        let mut best = (0, 0);
+        let Some(bucket) = self.blocks.get(&hash) else {
+            return best;
+        };
+        for &candidate in bucket {
+            let candidate = candidate as usize;
+            let len = base[candidate..]
+                .iter()
+                .zip(target)
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len > best.1 {
+                best = (candidate, len);
+            }
+        }
+        best
+    }
+}
+
+/// Applies a delta to its base.
+///
+/// # Returns
+///
+/// The resulting content, or the reason the delta is invalid.
+pub(crate) fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let mut pos = 0;
+    let base_size = read_varint(delta, &mut pos).ok_or("truncated delta header")?;
+    if base_size != base.len() as u64 {
+        return Err("delta base size does not match");
+    }
+    let result_size = read_varint(delta, &mut pos).ok_or("truncated delta header")?;
+    let mut result = Vec::with_capacity(result_size.min(delta.len() as u64 * 128) as usize);
+
+    while pos < delta.len() {
+        let cmd = delta[pos];
+        pos += 1;
+        if cmd & 0x80 != 0 {
+            let mut offset = 0usize;
+            let mut size = 0usize;
+            for i in 0..7 {
+                if cmd & (1 << i) == 0 {
+                    continue;
+                }
+                let byte = *delta.get(pos).ok_or("truncated copy instruction")? as usize;
+                pos += 1;
+                if i < 4 {
+                    offset |= byte << (8 * i);
+                } else {
+                    size |= byte << (8 * (i - 4));
+                }
+            }
+            if size == 0 {
+                size = MAX_COPY_SIZE;
+            }
+            let range = base
+                .get(offset..offset.saturating_add(size))
+                .ok_or("copy outside of the delta base")?;
+            result.extend_from_slice(range);
+        } else if cmd != 0 {
+            let data = delta
+                .get(pos..pos + cmd as usize)
+                .ok_or("truncated insert instruction")?;
+            result.extend_from_slice(data);
+            pos += cmd as usize;
+        } else {
+            return Err("unexpected delta opcode 0");
+        }
+        if result.len() as u64 > result_size {
+            return Err("delta result is larger than its header says");
+        }
+    }
+
+    if result.len() as u64 != result_size {
+        return Err("delta result is smaller than its header says");
+    }
+    Ok(result)
+}
+
+/// Reads the size of the result of a delta from its header.
+pub(crate) fn result_size(delta: &[u8]) -> Option<u64> {
+    let mut pos = 0;
+    read_varint(delta, &mut pos)?;
+    read_varint(delta, &mut pos)
+}
+
+/// Reads a little-endian base-128 varint.
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        if shift < 64 {
+            value |= ((byte & 0x7f) as u64) << shift;
+        }
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+/// Appends a little-endian base-128 varint.
+fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Appends insert instructions for `data`.
+fn push_insert(out: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(MAX_INSERT_SIZE) {
+        out.push(chunk.len() as u8);
+        out.extend_from_slice(chunk);
+    }
+}
+
+/// Appends copy instructions for `len` bytes of the base at `offset`.
+fn push_copy(out: &mut Vec<u8>, mut offset: usize, mut len: usize) {
+    while len > 0 {
+        let size = len.min(MAX_COPY_SIZE);
+        let cmd_pos = out.len();
+        let mut cmd = 0x80u8;
+        out.push(cmd);
+        for i in 0..4 {
+            let byte = (offset >> (8 * i)) as u8;
+            if byte != 0 {
+                cmd |= 1 << i;
+                out.push(byte);
+            }
+        }
+        // A size of 0x10000 is encoded by leaving out all size bytes
+        if size != MAX_COPY_SIZE {
+            for i in 0..3 {
+                let byte = (size >> (8 * i)) as u8;
+                if byte != 0 {
+                    cmd |= 0x10 << i;
+                    out.push(byte);
+                }
+            }
+        }
+        out[cmd_pos] = cmd;
+        offset += size;
+        len -= size;
+    }
+}
+
+/// Hashes a block of `BLOCK_SIZE` bytes.
+fn block_hash(block: &[u8]) -> u32 {
+    block.iter().fold(0u32, |hash, &b| {
+        hash.wrapping_mul(HASH_BASE).wrapping_add(b as u32)
+    })
+}
+
+/// Slides the block hash one byte forward, dropping `out` and adding `new`.
+fn roll_hash(hash: u32, out: u8, new: u8) -> u32 {
+    let top = HASH_BASE.wrapping_pow(BLOCK_SIZE as u32 - 1);
+    hash.wrapping_sub((out as u32).wrapping_mul(top))
+        .wrapping_mul(HASH_BASE)
+        .wrapping_add(new as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::noise;
+
+    /// Deltifies `target` against `base` with no size limit.
+    fn delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+        DeltaIndex::new(base)
+            .delta(base, target, usize::MAX)
+            .unwrap()
+    }
+
+    // DL-001: A delta reproduces the target from the base
+    #[test]
+    fn test_delta_round_trip() {
+        let base = noise(10_000, 1);
+        let mut target = base[..3_000].to_vec();
+        target.extend_from_slice(b"something new in the middle");
+        target.extend_from_slice(&base[3_100..]);
+        target.extend_from_slice(&noise(500, 2));
+
+        let d = delta(&base, &target);
+        assert!(d.len() < 1_000, "delta is {} bytes", d.len());
+        assert_eq!(apply_delta(&base, &d).unwrap(), target);
+        assert_eq!(result_size(&d), Some(target.len() as u64));
+    }
+
+    // DL-002: Copies longer than 0x10000 bytes and empty inputs are handled
+    #[test]
+    fn test_delta_edge_cases() {
+        let base = noise(200_000, 3);
+        let d = delta(&base, &base);
+        assert!(d.len() < 40);
+        assert_eq!(apply_delta(&base, &d).unwrap(), base);
+
+        for (base, target) in [(&b""[..], &b"abc"[..]), (&b"abc"[..], &b""[..])] {
+            assert_eq!(apply_delta(base, &delta(base, target)).unwrap(), target);
+        }
+    }
+
+    // DL-003: A delta larger than the limit is not produced
+    #[test]
+    fn test_delta_max_size() {
+        let base = noise(1_000, 4);
+        let target = noise(1_000, 5);
+        assert!(DeltaIndex::new(&base).delta(&base, &target, 500).is_none());
+    }
+
+    // DL-004: Malformed deltas are rejected
+    #[test]
+    fn test_apply_invalid_delta() {
+        let base = b"hello world";
+        // Wrong base size
+        assert!(apply_delta(base, &[5, 5, 0x90, 5]).is_err());
+        // Copy past the end of the base
+        assert!(apply_delta(base, &[11, 20, 0x91, 5, 20]).is_err());
+        // Opcode zero
+        assert!(apply_delta(base, &[11, 1, 0]).is_err());
+        // Result shorter than announced
+        assert!(apply_delta(base, &[11, 6, 0x90, 5]).is_err());
+        // Copying "hello" works
+        assert_eq!(apply_delta(base, &[11, 5, 0x90, 5]).unwrap(), b"hello");
+    }
+}
@@ -0,0 +1,450 @@
+//! Multi-pack-indexes.
+//!
+//! A multi-pack-index (`objects/pack/multi-pack-index`) indexes the
+//! objects of several packs at once, so that a lookup is one binary search
+//! instead of one per pack. It starts with a header (the magic `MIDX`,
+//! version, hash function ID, chunk count, base file count and pack
+//! count), followed by a table of chunk IDs and offsets. The chunks used
+//! here are:
+//!
+//! - `PNAM`: the names of the packs' `.idx` files, NUL-terminated
+//! - `OIDF`: a fan-out table of 256 cumulative object counts by first byte
+//! - `OIDL`: the sorted object IDs
+//! - `OOFF`: for each object, the number of its pack and its 4-byte offset
+//! - `LOFF`: 8-byte offsets for objects past 2 GiB, if any
+//!
+//! Each object is listed once, even if several packs contain it.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::oid::Oid;
+use super::pack::{be_u32, Fanout};
+use crate::error::{Error, Result};
+use crate::infra::ObjectFormat;
+
+/// Magic bytes at the start of a multi-pack-index.
+const MIDX_MAGIC: &[u8; 4] = b"MIDX";
+
+/// Size of the multi-pack-index header.
+const HEADER_SIZE: usize = 12;
+
+/// Size of an entry of the chunk table (ID and offset).
+const CHUNK_ENTRY_SIZE: usize = 12;
+
+/// Chunk of pack index names.
+const CHUNK_PACK_NAMES: u32 = u32::from_be_bytes(*b"PNAM");
+
+/// Chunk of the fan-out table.
+const CHUNK_OID_FANOUT: u32 = u32::from_be_bytes(*b"OIDF");
+
+/// Chunk of the sorted object IDs.
+const CHUNK_OID_LOOKUP: u32 = u32::from_be_bytes(*b"OIDL");
+
+/// Chunk of pack numbers and offsets.
+const CHUNK_OBJECT_OFFSETS: u32 = u32::from_be_bytes(*b"OOFF");
+
+/// Chunk of 8-byte offsets.
+const CHUNK_LARGE_OFFSETS: u32 = u32::from_be_bytes(*b"LOFF");
+
+/// A multi-pack-index.
+///
+/// Like [`Pack`](super::Pack), lookups read the file on demand, so
+/// opening it is cheap.
+#[derive(Debug)]
+pub struct MultiPackIndex {
+    /// Path to the `multi-pack-index` file.
+    path: PathBuf,
+    /// The open file, locked for each seek and read so that the index can
+    /// be shared between threads.
+    file: Mutex<File>,
+    /// The hash function object IDs are named with.
+    format: ObjectFormat,
+    /// The `.idx` file names of the packs, by pack number.
+    pack_names: Vec<String>,
+    /// Number of objects whose first byte is at most the table position.
+    fanout: Fanout,
+    /// File offset of the `OIDL` chunk.
+    oid_lookup: u64,
+    /// File offset of the `OOFF` chunk.
+    object_offsets: u64,
+    /// File offset and entry count of the `LOFF` chunk, if present.
+    large_offsets: Option<(u64, u64)>,
+}
+
+impl MultiPackIndex {
+    /// Opens a multi-pack-index.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the `multi-pack-index` file.
+    /// * `format` - The object format of the repository.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidPack` if the header, chunk table, pack names
+    /// or fan-out table are invalid, or the file uses another object
+    /// format.
+    pub fn open<P: AsRef<Path>>(path: P, format: ObjectFormat) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let invalid = |reason: &str| Error::InvalidPack {
+            path: path.clone(),
+            reason: reason.to_string(),
+        };
+        let mut file = File::open(&path)?;
+        let file_len = file.metadata()?.len();
+
+        let mut header = [0u8; HEADER_SIZE];
+        file.read_exact(&mut header)
+            .map_err(|_| invalid("multi-pack-index is too short"))?;
+        if &header[..4] != MIDX_MAGIC || header[4] != 1 {
+            return Err(invalid("unsupported multi-pack-index version"));
+        }
+        if header[5] as u32 != format.hash_id() {
+            return Err(invalid("multi-pack-index uses a different object format"));
+        }
+        if header[7] != 0 {
+            return Err(invalid("incremental multi-pack-indexes are not supported"));
+        }
+        let chunk_count = header[6] as usize;
+        let pack_count = be_u32(&header[8..]) as usize;
+
+        // The table ends with an entry whose offset is the end of the last
+        // chunk, so each chunk's size is the distance to the next offset
+        let mut table = vec![0u8; (chunk_count + 1) * CHUNK_ENTRY_SIZE];
+        file.read_exact(&mut table)
+            .map_err(|_| invalid("chunk table is truncated"))?;
+        let entries: Vec<(u32, u64)> = table
+            .chunks(CHUNK_ENTRY_SIZE)
+            .map(|entry| {
+                let mut offset = [0u8; 8];
+                offset.copy_from_slice(&entry[4..]);
+                (be_u32(entry), u64::from_be_bytes(offset))
+            })
+            .collect();
+        if entries.windows(2).any(|w| w[0].1 > w[1].1) || entries[chunk_count].1 > file_len {
+            return Err(invalid("chunk offsets are out of order"));
+        }
+        let chunk = |id: u32| {
+            let i = entries[..chunk_count].iter().position(|e| e.0 == id)?;
+            Some((entries[i].1, entries[i + 1].1 - entries[i].1))
+        };
+        let required = |id: u32, name: &str| {
+            chunk(id).ok_or_else(|| invalid(&format!("missing {} chunk", name)))
+        };
+
+        let (names_offset, names_len) = required(CHUNK_PACK_NAMES, "pack names")?;
+        let mut names = vec![0u8; names_len as usize];
+        file.seek(SeekFrom::Start(names_offset))?;
+        file.read_exact(&mut names)?;
+        let pack_names: Vec<String> = names
+            .split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect();
+        if pack_names.len() != pack_count {
+            return Err(invalid("pack name count does not match the header"));
+        }
+
+        let (fanout_offset, fanout_len) = required(CHUNK_OID_FANOUT, "fan-out")?;
+        if fanout_len != 256 * 4 {
+            return Err(invalid("fan-out chunk has the wrong size"));
+        }
+        let mut table = vec![0u8; 256 * 4];
+        file.seek(SeekFrom::Start(fanout_offset))?;
+        file.read_exact(&mut table)?;
+        let fanout = Fanout::parse(&table).ok_or_else(|| invalid("fan-out table is not sorted"))?;
+        let objects = fanout.len() as u64;
+
+        let (oid_lookup, lookup_len) = required(CHUNK_OID_LOOKUP, "object ID")?;
+        let (object_offsets, offsets_len) = required(CHUNK_OBJECT_OFFSETS, "object offset")?;
+        if lookup_len < objects * format.size() as u64 || offsets_len < objects * 8 {
+            return Err(invalid(
+                "object chunks are smaller than the fan-out table says",
+            ));
+        }
+        let large_offsets = chunk(CHUNK_LARGE_OFFSETS).map(|(offset, len)| (offset, len / 8));
+
+        Ok(MultiPackIndex {
+            path,
+            file: Mutex::new(file),
+            format,
+            pack_names,
+            fanout,
+            oid_lookup,
+            object_offsets,
+            large_offsets,
+        })
+    }
+
+    /// Returns the path of the `multi-pack-index` file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the `.idx` file names of the packs the index covers.
+    pub fn pack_names(&self) -> &[String] {
+        &self.pack_names
+    }
+
+    /// Returns the number of objects in the index.
+    pub fn len(&self) -> usize {
+        self.fanout.len()
+    }
+
+    /// Returns `true` if the index has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Lists the IDs of all objects in the index, sorted.
+    pub fn oids(&self) -> Result<Vec<Oid>> {
+        self.oids_in(0, self.len())
+    }
+
+    /// Finds the objects in the index whose ID starts with a hex prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - A lowercase hexadecimal prefix of at least 2 characters.
+    pub fn find_by_prefix(&self, prefix: &str) -> Result<Vec<Oid>> {
+        let Some(range) = self.fanout.prefix_range(prefix) else {
+            return Ok(Vec::new());
+        };
+        let mut oids = self.oids_in(range.start, range.end)?;
+        oids.retain(|oid| oid.to_hex().starts_with(prefix));
+        Ok(oids)
+    }
+
+    /// Returns `true` if the index contains the object.
+    pub fn contains(&self, oid: &Oid) -> Result<bool> {
+        Ok(self.position(oid)?.is_some())
+    }
+
+    /// Looks up which pack holds an object, and where.
+    ///
+    /// # Returns
+    ///
+    /// The `.idx` file name of the pack and the offset of the object in
+    /// it, or `None` if the object is not in the index.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidPack` if the entry is out of range.
+    pub fn find(&self, oid: &Oid) -> Result<Option<(&str, u64)>> {
+        let Some(pos) = self.position(oid)? else {
+            return Ok(None);
+        };
+        let mut entry = [0u8; 8];
+        self.read(self.object_offsets + pos as u64 * 8, &mut entry)?;
+        let pack = self
+            .pack_names
+            .get(be_u32(&entry) as usize)
+            .ok_or_else(|| self.invalid("pack number out of range"))?;
+
+        let offset = be_u32(&entry[4..]);
+        if offset & 0x8000_0000 == 0 {
+            return Ok(Some((pack, offset as u64)));
+        }
+        let index = (offset & 0x7fff_ffff) as u64;
+        let Some((large, _)) = self.large_offsets.filter(|&(_, count)| index < count) else {
+            return Err(self.invalid("large offset out of range"));
+        };
+        let mut buf = [0u8; 8];
+        self.read(large + index * 8, &mut buf)?;
+        Ok(Some((pack, u64::from_be_bytes(buf))))
+    }
+
+    /// Finds the position of an object in the sorted ID table.
+    fn position(&self, oid: &Oid) -> Result<Option<usize>> {
+        if oid.format() != self.format {
+            return Ok(None);
+        }
+        let size = self.format.size() as u64;
+        self.fanout.search(oid, |pos, name| {
+            self.read(self.oid_lookup + pos as u64 * size, name)
+        })
+    }
+
+    /// Reads the object IDs at positions `start..end` of the ID table.
+    fn oids_in(&self, start: usize, end: usize) -> Result<Vec<Oid>> {
+        let size = self.format.size();
+        let mut names = vec![0u8; (end - start) * size];
+        self.read(self.oid_lookup + (start * size) as u64, &mut names)?;
+        names.chunks(size).map(Oid::from_slice).collect()
+    }
+
+    /// Reads bytes of the file at an offset.
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(buf)
+            .map_err(|_| self.invalid("multi-pack-index is truncated"))
+    }
+
+    /// Returns an `InvalidPack` error for this index.
+    fn invalid(&self, reason: &str) -> Error {
+        Error::InvalidPack {
+            path: self.path.clone(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Builds a multi-pack-index over `(object, pack number, offset)`
+    /// entries, storing offsets past 2 GiB in a `LOFF` chunk.
+    fn build(packs: &[&str], entries: &[(Oid, u32, u64)]) -> Vec<u8> {
+        let mut entries = entries.to_vec();
+        entries.sort();
+
+        let mut names = Vec::new();
+        for name in packs {
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+        }
+        while names.len() % 4 != 0 {
+            names.push(0);
+        }
+        let mut fanout = Vec::new();
+        for byte in 0..=255u8 {
+            let count = entries.iter().filter(|e| e.0.as_bytes()[0] <= byte).count();
+            fanout.extend_from_slice(&(count as u32).to_be_bytes());
+        }
+        let mut lookup = Vec::new();
+        let mut offsets = Vec::new();
+        let mut large = Vec::new();
+        for (oid, pack, offset) in &entries {
+            lookup.extend_from_slice(oid.as_bytes());
+            offsets.extend_from_slice(&pack.to_be_bytes());
+            if *offset < 0x8000_0000 {
+                offsets.extend_from_slice(&(*offset as u32).to_be_bytes());
+            } else {
+                let index = (large.len() / 8) as u32 | 0x8000_0000;
+                offsets.extend_from_slice(&index.to_be_bytes());
+                large.extend_from_slice(&offset.to_be_bytes());
+            }
+        }
+
+        let mut chunks = vec![
+            (CHUNK_PACK_NAMES, names),
+            (CHUNK_OID_FANOUT, fanout),
+            (CHUNK_OID_LOOKUP, lookup),
+            (CHUNK_OBJECT_OFFSETS, offsets),
+        ];
+        if !large.is_empty() {
+            chunks.push((CHUNK_LARGE_OFFSETS, large));
+        }
+
+        let mut data = b"MIDX\x01\x01".to_vec();
+        data.push(chunks.len() as u8);
+        data.push(0);
+        data.extend_from_slice(&(packs.len() as u32).to_be_bytes());
+        let mut offset = (HEADER_SIZE + (chunks.len() + 1) * CHUNK_ENTRY_SIZE) as u64;
+        for (id, chunk) in &chunks {
+            data.extend_from_slice(&id.to_be_bytes());
+            data.extend_from_slice(&offset.to_be_bytes());
+            offset += chunk.len() as u64;
+        }
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&offset.to_be_bytes());
+        for (_, chunk) in chunks {
+            data.extend_from_slice(&chunk);
+        }
+        data.extend_from_slice(&[0; 20]);
+        data
+    }
+
+    fn oid(hex_byte: &str) -> Oid {
+        Oid::from_hex(&hex_byte.repeat(20)).unwrap()
+    }
+
+    // MX-001: Objects are found with their pack and offset, large or not
+    #[test]
+    fn test_midx_find() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("multi-pack-index");
+        let entries = [
+            (oid("a1"), 0, 12),
+            (oid("00"), 1, 0x1_2345_6789),
+            (oid("ff"), 1, 345),
+            (oid("a2"), 0, 0x8000_0000),
+        ];
+        fs::write(&path, build(&["pack-a.idx", "pack-b.idx"], &entries)).unwrap();
+
+        let midx = MultiPackIndex::open(&path, ObjectFormat::Sha1).unwrap();
+        assert_eq!(midx.path(), path);
+        assert_eq!(midx.pack_names(), ["pack-a.idx", "pack-b.idx"]);
+        assert_eq!(midx.len(), 4);
+        assert!(!midx.is_empty());
+        assert_eq!(
+            midx.oids().unwrap(),
+            vec![oid("00"), oid("a1"), oid("a2"), oid("ff")]
+        );
+        for (oid, pack, offset) in entries {
+            let name = midx.pack_names()[pack as usize].as_str();
+            assert_eq!(midx.find(&oid).unwrap(), Some((name, offset)));
+        }
+
+        assert_eq!(midx.find(&oid("a3")).unwrap(), None);
+        assert!(!midx.contains(&oid("01")).unwrap());
+        assert_eq!(midx.find_by_prefix("a").unwrap(), Vec::<Oid>::new());
+        assert_eq!(midx.find_by_prefix("a1a1").unwrap(), vec![oid("a1")]);
+        assert_eq!(midx.find_by_prefix("a2").unwrap(), vec![oid("a2")]);
+        let sha256 = Oid::from_hex(&"a1".repeat(32)).unwrap();
+        assert_eq!(midx.find(&sha256).unwrap(), None);
+    }
+
+    // MX-002: Malformed files and other object formats are rejected
+    #[test]
+    fn test_midx_invalid() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("multi-pack-index");
+        let valid = build(&["pack-a.idx"], &[(oid("a1"), 0, 12)]);
+        let open = |data: &[u8]| {
+            fs::write(&path, data).unwrap();
+            MultiPackIndex::open(&path, ObjectFormat::Sha1)
+        };
+        let is_invalid = |result: Result<MultiPackIndex>| matches!(result, Err(Error::InvalidPack { path: p, .. }) if p == path);
+
+        assert!(open(&valid).is_ok());
+        assert!(is_invalid(open(&valid[..8])));
+        assert!(is_invalid(open(&valid[..40])));
+        assert!(is_invalid(MultiPackIndex::open(
+            &path,
+            ObjectFormat::Sha256
+        )));
+
+        let mut data = valid.clone();
+        data[4] = 2;
+        assert!(is_invalid(open(&data)));
+
+        // A second pack in the header with only one name
+        let mut data = valid.clone();
+        data[11] = 2;
+        assert!(is_invalid(open(&data)));
+
+        // The fan-out chunk renamed, so it is missing
+        let mut data = valid.clone();
+        data[HEADER_SIZE + CHUNK_ENTRY_SIZE..][..4].copy_from_slice(b"XXXX");
+        assert!(is_invalid(open(&data)));
+
+        // A pack number past the pack names
+        let mut data = valid.clone();
+        let ooff = data.len() - 20 - 8;
+        data[ooff + 3] = 5;
+        fs::write(&path, &data).unwrap();
+        let midx = MultiPackIndex::open(&path, ObjectFormat::Sha1).unwrap();
+        assert!(matches!(
+            midx.find(&oid("a1")),
+            Err(Error::InvalidPack { .. })
+        ));
+    }
+}
@@ -1,16 +1,30 @@
 //! Git object types (blob, tree, commit, tag).
 
+mod alternates;
 pub mod blob;
+mod cache;
 pub mod commit;
+pub mod commit_graph;
+mod delta;
+pub mod midx;
 pub mod oid;
+pub mod pack;
 pub mod store;
 pub mod tag_object;
 pub mod tree;
 
+pub use crate::infra::ObjectFormat;
 pub use blob::Blob;
+pub(crate) use cache::ObjectCache;
+pub use cache::{CacheOptions, CacheStats};
 pub use commit::{Commit, Signature};
+pub use commit_graph::{CommitGraph, CommitGraphWriter, GraphCommit};
+pub use midx::MultiPackIndex;
 pub use oid::Oid;
-pub use store::{LooseObjectStore, ObjectType, RawObject};
+pub(crate) use pack::DEFAULT_BIG_FILE_THRESHOLD;
+pub use pack::{Pack, PackWriter};
+pub(crate) use store::MemoryObjects;
+pub use store::{LooseObjectStore, ObjectReader, ObjectType, RawObject};
 pub use tag_object::TagObject;
 pub use tree::{FileMode, Tree, TreeEntry};
 
@@ -25,7 +39,7 @@ pub enum Object {
     /// A tree object containing directory entries.
     Tree(Tree),
     /// A commit object containing commit metadata.
-    Commit(Commit),
+    Commit(Box<Commit>),
 }
 
 impl Object {
@@ -81,7 +95,7 @@ impl Object {
     /// Consumes this Object and returns the inner Commit if this is a Commit object.
     pub fn into_commit(self) -> Option<Commit> {
         match self {
-            Object::Commit(commit) => Some(commit),
+            Object::Commit(commit) => Some(*commit),
             _ => None,
         }
     }
@@ -101,7 +115,7 @@ impl From<Tree> for Object {
 
 impl From<Commit> for Object {
     fn from(commit: Commit) -> Self {
-        Object::Commit(commit)
+        Object::Commit(Box::new(commit))
     }
 }
 
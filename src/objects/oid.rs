@@ -1,9 +1,10 @@
-//! Object ID (SHA-1 hash) representation.
+//! Object ID (SHA-1 or SHA-256 hash) representation.
 
 use std::fmt;
 use std::str::FromStr;
 
 use crate::error::{Error, Result};
+use crate::infra::ObjectFormat;
 
 /// The length of a SHA-1 hash in bytes.
 pub const OID_BYTES: usize = 20;
@@ -11,21 +12,27 @@ pub const OID_BYTES: usize = 20;
 /// The length of a SHA-1 hash as a hexadecimal string.
 pub const OID_HEX_LEN: usize = 40;
 
-/// A Git object ID (SHA-1 hash).
+/// The length of the longest object ID (SHA-256) in bytes.
+pub const MAX_OID_BYTES: usize = 32;
+
+/// A Git object ID.
 ///
-/// This type represents a 20-byte SHA-1 hash that uniquely identifies
-/// a Git object (blob, tree, commit, or tag).
+/// This is the hash that uniquely identifies a Git object (blob, tree,
+/// commit, or tag): 20 bytes of SHA-1, or 32 bytes of SHA-256 in a
+/// repository with `extensions.objectFormat = sha256`.
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Oid {
-    bytes: [u8; OID_BYTES],
+    bytes: [u8; MAX_OID_BYTES],
+    len: u8,
 }
 
 impl Oid {
-    /// Creates an Oid from a 40-character hexadecimal string.
+    /// Creates an Oid from a hexadecimal string.
     ///
     /// # Arguments
     ///
-    /// * `hex` - A 40-character hexadecimal string (case-insensitive).
+    /// * `hex` - A 40-character (SHA-1) or 64-character (SHA-256)
+    ///   hexadecimal string (case-insensitive).
     ///
     /// # Returns
     ///
@@ -40,11 +47,12 @@ impl Oid {
     /// assert_eq!(oid.to_hex(), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
     /// ```
     pub fn from_hex(hex: &str) -> Result<Self> {
-        if hex.len() != OID_HEX_LEN {
+        let len = hex.len() / 2;
+        if hex.len() % 2 != 0 || ![OID_BYTES, MAX_OID_BYTES].contains(&len) {
             return Err(Error::InvalidOid(hex.to_string()));
         }
 
-        let mut bytes = [0u8; OID_BYTES];
+        let mut bytes = [0u8; MAX_OID_BYTES];
 
         for (i, chunk) in hex.as_bytes().chunks(2).enumerate() {
             let high =
@@ -54,10 +62,13 @@ impl Oid {
             bytes[i] = (high << 4) | low;
         }
 
-        Ok(Oid { bytes })
+        Ok(Oid {
+            bytes,
+            len: len as u8,
+        })
     }
 
-    /// Creates an Oid from a 20-byte array.
+    /// Creates a SHA-1 Oid from a 20-byte array.
     ///
     /// # Arguments
     ///
@@ -72,15 +83,76 @@ impl Oid {
     /// let oid = Oid::from_bytes(bytes);
     /// ```
     pub fn from_bytes(bytes: [u8; OID_BYTES]) -> Self {
-        Oid { bytes }
+        let mut oid = Oid::zero(ObjectFormat::Sha1);
+        oid.bytes[..OID_BYTES].copy_from_slice(&bytes);
+        oid
+    }
+
+    /// Creates an Oid from raw hash bytes of either object format.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - 20 bytes of SHA-1 or 32 bytes of SHA-256.
+    ///
+    /// # Returns
+    ///
+    /// The Oid on success, or `Error::InvalidOid` if the length matches
+    /// neither format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zerogit::objects::{ObjectFormat, Oid};
+    ///
+    /// let oid = Oid::from_slice(&[0xab; 32]).unwrap();
+    /// assert_eq!(oid.format(), ObjectFormat::Sha256);
+    /// ```
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        if ![OID_BYTES, MAX_OID_BYTES].contains(&bytes.len()) {
+            return Err(Error::InvalidOid(
+                bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+            ));
+        }
+        let mut oid = Oid {
+            bytes: [0u8; MAX_OID_BYTES],
+            len: bytes.len() as u8,
+        };
+        oid.bytes[..bytes.len()].copy_from_slice(bytes);
+        Ok(oid)
+    }
+
+    /// Returns the all-zero Oid of an object format.
+    ///
+    /// Git uses it to mean "no object", for example as the old value of a
+    /// newly created reference.
+    pub fn zero(format: ObjectFormat) -> Self {
+        Oid {
+            bytes: [0u8; MAX_OID_BYTES],
+            len: format.size() as u8,
+        }
+    }
+
+    /// Returns `true` if this is the all-zero Oid.
+    pub fn is_zero(&self) -> bool {
+        self.as_bytes().iter().all(|&b| b == 0)
+    }
+
+    /// Returns the object format this Oid belongs to, from its length.
+    pub fn format(&self) -> ObjectFormat {
+        if self.len as usize == OID_BYTES {
+            ObjectFormat::Sha1
+        } else {
+            ObjectFormat::Sha256
+        }
     }
 
     /// Returns the hexadecimal string representation of this Oid.
     ///
-    /// The returned string is always lowercase and 40 characters long.
+    /// The returned string is always lowercase, and 40 (SHA-1) or 64
+    /// (SHA-256) characters long.
     pub fn to_hex(&self) -> String {
-        let mut hex = String::with_capacity(OID_HEX_LEN);
-        for byte in &self.bytes {
+        let mut hex = String::with_capacity(self.len as usize * 2);
+        for byte in self.as_bytes() {
             hex.push(HEX_CHARS[(byte >> 4) as usize]);
             hex.push(HEX_CHARS[(byte & 0x0f) as usize]);
         }
@@ -95,9 +167,9 @@ impl Oid {
         self.to_hex()[..7].to_string()
     }
 
-    /// Returns a reference to the raw 20-byte array.
-    pub fn as_bytes(&self) -> &[u8; OID_BYTES] {
-        &self.bytes
+    /// Returns the raw hash bytes: 20 for SHA-1, 32 for SHA-256.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
     }
 }
 
@@ -276,6 +348,35 @@ mod tests {
         assert!(set.contains(&oid2));
     }
 
+    // O-010: SHA-256 object IDs round-trip through hex and bytes
+    #[test]
+    fn test_sha256() {
+        let hex = "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813";
+        let oid = Oid::from_hex(hex).unwrap();
+        assert_eq!(oid.format(), ObjectFormat::Sha256);
+        assert_eq!(oid.to_hex(), hex);
+        assert_eq!(oid.as_bytes().len(), 32);
+        assert_eq!(Oid::from_slice(oid.as_bytes()).unwrap(), oid);
+        assert_eq!(oid.short(), "473a0f4");
+
+        // A SHA-1 ID is never equal to a SHA-256 ID
+        let sha1 = Oid::from_slice(&oid.as_bytes()[..20]).unwrap();
+        assert_eq!(sha1.format(), ObjectFormat::Sha1);
+        assert_ne!(sha1, oid);
+
+        assert!(Oid::from_hex(&hex[..62]).is_err());
+        assert!(Oid::from_slice(&[0; 31]).is_err());
+    }
+
+    // O-011: The zero Oid of each format
+    #[test]
+    fn test_zero() {
+        assert_eq!(Oid::zero(ObjectFormat::Sha1).to_hex(), "0".repeat(40));
+        assert_eq!(Oid::zero(ObjectFormat::Sha256).to_hex(), "0".repeat(64));
+        assert!(Oid::zero(ObjectFormat::Sha256).is_zero());
+        assert!(!Oid::from_hex(EMPTY_SHA1).unwrap().is_zero());
+    }
+
     // Additional: Clone and Copy
     #[test]
     fn test_clone_copy() {
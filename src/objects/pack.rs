@@ -0,0 +1,1611 @@
+//! Packfiles and their version 2 indexes.
+//!
+//! A packfile (`objects/pack/pack-<checksum>.pack`) stores many objects
+//! in one file, each zlib-compressed and optionally as a delta against
+//! another object. The matching `.idx` file maps object IDs to offsets in
+//! the pack:
+//!
+//! - A header: the magic `\377tOc` and version 2
+//! - A fan-out table of 256 cumulative object counts by first byte
+//! - The sorted object IDs, then a CRC-32 and a 4-byte offset for each
+//! - 8-byte offsets for objects past 2 GiB, if any
+//! - The pack checksum and the checksum of the index itself
+//!
+//! An optional reverse index (`.rev` file) lists the index positions of
+//! the objects in the order they appear in the pack, so an offset can be
+//! mapped back to its object without reading every offset in the index:
+//!
+//! - A header: the magic `RIDX`, version 1 and the hash function ID
+//! - A 4-byte index position for each object, in pack order
+//! - The pack checksum and the checksum of the reverse index itself
+
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+use super::cache::ObjectCache;
+use super::delta::{self, DeltaIndex};
+use super::oid::Oid;
+use super::store::{LooseObjectStore, ObjectReader, ObjectType, RawObject};
+use crate::error::{Error, Result};
+use crate::infra::fs::temp_path;
+use crate::infra::{
+    compress, crc32_update, digest, hash_object_with, remove_file, FileHasher, Fsync,
+    FsyncComponent, ObjectFormat, ZlibReader, ZlibWriter,
+};
+
+/// Magic bytes at the start of a version 2 pack index.
+const INDEX_MAGIC: &[u8; 4] = b"\xfftOc";
+
+/// Magic bytes at the start of a packfile.
+const PACK_MAGIC: &[u8; 4] = b"PACK";
+
+/// Size of the index header (magic and version) plus the fan-out table.
+const INDEX_HEADER_SIZE: u64 = 8 + 256 * 4;
+
+/// Magic bytes at the start of a reverse index.
+const REVERSE_INDEX_MAGIC: &[u8; 4] = b"RIDX";
+
+/// Size of the reverse index header (magic, version and hash function).
+const REVERSE_INDEX_HEADER_SIZE: u64 = 12;
+
+/// Pack entry type of a delta against an object at an earlier offset.
+const OFS_DELTA: u8 = 6;
+
+/// Pack entry type of a delta against an object named by its ID.
+const REF_DELTA: u8 = 7;
+
+/// Longest delta chain followed before the pack is considered corrupt.
+const MAX_CHAIN_LEN: usize = 10_000;
+
+/// Objects smaller than this are never stored as deltas.
+const MIN_DELTA_SIZE: usize = 64;
+
+/// Default for `core.bigFileThreshold` (512 MiB), as in Git.
+pub(crate) const DEFAULT_BIG_FILE_THRESHOLD: u64 = 512 * 1024 * 1024;
+
+/// Returns the pack entry type code of an object type.
+fn type_code(object_type: ObjectType) -> u8 {
+    match object_type {
+        ObjectType::Commit => 1,
+        ObjectType::Tree => 2,
+        ObjectType::Blob => 3,
+        ObjectType::Tag => 4,
+    }
+}
+
+/// Returns the object type of a (non-delta) pack entry type code.
+fn object_type(code: u8) -> Option<ObjectType> {
+    match code {
+        1 => Some(ObjectType::Commit),
+        2 => Some(ObjectType::Tree),
+        3 => Some(ObjectType::Blob),
+        4 => Some(ObjectType::Tag),
+        _ => None,
+    }
+}
+
+/// The base of a deltified pack entry.
+enum DeltaBase {
+    /// The entry at this offset of the pack.
+    Offset(u64),
+    /// The object with this ID.
+    Oid(Oid),
+}
+
+/// The index positions of the objects of a pack, in pack order.
+#[derive(Debug)]
+enum ReverseIndex {
+    /// The `.rev` file, checked and read as needed.
+    File(Mutex<File>),
+    /// Built from the offsets in the index, for packs without a `.rev`
+    /// file.
+    Memory(Vec<u32>),
+}
+
+/// The header of a pack entry.
+struct EntryHeader {
+    /// The entry type code (1-4, `OFS_DELTA` or `REF_DELTA`).
+    code: u8,
+    /// The size of the inflated entry data.
+    size: u64,
+    /// Offset of the compressed data in the pack.
+    data_offset: u64,
+    /// The delta base, for deltified entries.
+    base: Option<DeltaBase>,
+}
+
+/// A reader over the open pack file of a `Pack`, from an offset.
+///
+/// Readers share the file handle: each read locks it and seeks to the
+/// reader's own position, so readers on different threads do not move
+/// each other.
+pub(crate) struct PackReader {
+    /// The open pack file.
+    file: Arc<Mutex<File>>,
+    /// Offset of the next byte to read.
+    pos: u64,
+}
+
+impl Read for PackReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.seek(SeekFrom::Start(self.pos))?;
+        let n = file.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+/// A fan-out table of 256 cumulative object counts by first byte, as
+/// found in pack indexes and multi-pack-indexes.
+#[derive(Debug)]
+pub(super) struct Fanout([u32; 256]);
+
+impl Fanout {
+    /// Parses a fan-out table from its 1024 bytes.
+    ///
+    /// # Returns
+    ///
+    /// The table, or `None` if the counts ever decrease.
+    pub(super) fn parse(data: &[u8]) -> Option<Self> {
+        let mut counts = [0u32; 256];
+        for (i, count) in counts.iter_mut().enumerate() {
+            *count = be_u32(data.get(i * 4..i * 4 + 4)?);
+        }
+        if counts.windows(2).any(|w| w[0] > w[1]) {
+            return None;
+        }
+        Some(Fanout(counts))
+    }
+
+    /// Returns the total number of objects.
+    pub(super) fn len(&self) -> usize {
+        self.0[255] as usize
+    }
+
+    /// Returns the positions of the IDs whose first byte is `first`.
+    pub(super) fn range(&self, first: u8) -> Range<usize> {
+        let start = match first {
+            0 => 0,
+            b => self.0[b as usize - 1] as usize,
+        };
+        start..self.0[first as usize] as usize
+    }
+
+    /// Returns the positions of the IDs that may start with a hex prefix,
+    /// or `None` if the prefix does not start with a hex byte.
+    pub(super) fn prefix_range(&self, prefix: &str) -> Option<Range<usize>> {
+        let first = prefix
+            .get(..2)
+            .and_then(|b| u8::from_str_radix(b, 16).ok())?;
+        Some(self.range(first))
+    }
+
+    /// Binary-searches the sorted ID table for an object.
+    ///
+    /// # Arguments
+    ///
+    /// * `oid` - The object to find.
+    /// * `read_name` - Reads the ID at a position into the buffer.
+    ///
+    /// # Returns
+    ///
+    /// The position of the object, or `None` if it is not in the table.
+    pub(super) fn search(
+        &self,
+        oid: &Oid,
+        mut read_name: impl FnMut(usize, &mut [u8]) -> Result<()>,
+    ) -> Result<Option<usize>> {
+        let Range {
+            start: mut low,
+            end: mut high,
+        } = self.range(oid.as_bytes()[0]);
+        let mut name = vec![0u8; oid.as_bytes().len()];
+        while low < high {
+            let mid = (low + high) / 2;
+            read_name(mid, &mut name)?;
+            match name.as_slice().cmp(oid.as_bytes()) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Ok(Some(mid)),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A packfile and its index.
+///
+/// Lookups read the index on demand, so opening a pack is cheap and
+/// memory use does not grow with the number of objects.
+#[derive(Debug)]
+pub struct Pack {
+    /// Path to the `.idx` file.
+    index_path: PathBuf,
+    /// Path to the `.pack` file.
+    pack_path: PathBuf,
+    /// The open index file, locked for each seek and read so that a pack
+    /// can be shared between threads.
+    index: Mutex<File>,
+    /// The hash function object IDs in the pack use.
+    format: ObjectFormat,
+    /// Number of objects whose first byte is at most the table position.
+    fanout: Fanout,
+    /// The open pack file, opened and checked on first read. It is shared
+    /// by the readers over it, each of which keeps its own position.
+    pack: OnceLock<Arc<Mutex<File>>>,
+    /// The reverse index, loaded on first use.
+    reverse: OnceLock<ReverseIndex>,
+    /// Delta bases resolved earlier, if caching. The cache also holds the
+    /// open packs, so it is not kept alive by them.
+    cache: Option<Weak<ObjectCache>>,
+}
+
+impl Pack {
+    /// Opens a pack by its index file.
+    ///
+    /// # Arguments
+    ///
+    /// * `index_path` - Path to the `.idx` file; the `.pack` file must be
+    ///   next to it.
+    /// * `format` - The object format of the repository.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidPack` if the index header is invalid.
+    pub fn open<P: AsRef<Path>>(index_path: P, format: ObjectFormat) -> Result<Self> {
+        let index_path = index_path.as_ref().to_path_buf();
+        let mut index = File::open(&index_path)?;
+        let mut header = vec![0u8; INDEX_HEADER_SIZE as usize];
+        index
+            .read_exact(&mut header)
+            .map_err(|_| Error::InvalidPack {
+                path: index_path.clone(),
+                reason: "index file is too short".to_string(),
+            })?;
+        if &header[..4] != INDEX_MAGIC || header[4..8] != 2u32.to_be_bytes() {
+            return Err(Error::InvalidPack {
+                path: index_path,
+                reason: "unsupported index version".to_string(),
+            });
+        }
+
+        let Some(fanout) = Fanout::parse(&header[8..]) else {
+            return Err(Error::InvalidPack {
+                path: index_path,
+                reason: "fan-out table is not sorted".to_string(),
+            });
+        };
+
+        Ok(Pack {
+            pack_path: index_path.with_extension("pack"),
+            index_path,
+            index: Mutex::new(index),
+            format,
+            fanout,
+            pack: OnceLock::new(),
+            reverse: OnceLock::new(),
+            cache: None,
+        })
+    }
+
+    /// Keeps resolved delta bases in a cache shared with other packs.
+    pub(crate) fn with_cache(mut self, cache: Arc<ObjectCache>) -> Self {
+        self.cache = Some(Arc::downgrade(&cache));
+        self
+    }
+
+    /// Returns the path of the `.idx` file.
+    pub fn index_path(&self) -> &Path {
+        &self.index_path
+    }
+
+    /// Returns the path of the `.pack` file.
+    pub fn pack_path(&self) -> &Path {
+        &self.pack_path
+    }
+
+    /// Returns the number of objects in the pack.
+    pub fn len(&self) -> usize {
+        self.fanout.len()
+    }
+
+    /// Returns `true` if the pack has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Lists the IDs of all objects in the pack, sorted.
+    pub fn oids(&self) -> Result<Vec<Oid>> {
+        self.oids_in(0, self.len())
+    }
+
+    /// Finds the objects in the pack whose ID starts with a hex prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - A lowercase hexadecimal prefix of at least 2 characters.
+    pub fn find_by_prefix(&self, prefix: &str) -> Result<Vec<Oid>> {
+        let Some(range) = self.fanout.prefix_range(prefix) else {
+            return Ok(Vec::new());
+        };
+        let mut oids = self.oids_in(range.start, range.end)?;
+        oids.retain(|oid| oid.to_hex().starts_with(prefix));
+        Ok(oids)
+    }
+
+    /// Returns `true` if the pack contains the object.
+    pub fn contains(&self, oid: &Oid) -> Result<bool> {
+        Ok(self.position(oid)?.is_some())
+    }
+
+    /// Reads an object from the pack, resolving deltas.
+    ///
+    /// # Returns
+    ///
+    /// The object, or `None` if it is not in the pack.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidPack` if the entry or a delta base is corrupt.
+    pub fn read(&self, oid: &Oid) -> Result<Option<RawObject>> {
+        match self.find_offset(oid)? {
+            Some(offset) => self.read_at_offset(offset).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the object stored at an offset in the pack, resolving deltas.
+    pub(crate) fn read_at_offset(&self, offset: u64) -> Result<RawObject> {
+        let (object_type, content) = self.read_at(offset)?;
+        Ok(RawObject {
+            object_type,
+            content,
+        })
+    }
+
+    /// Opens a reader over the content of the object stored at an offset,
+    /// if the entry holds the whole object.
+    ///
+    /// The entry data is inflated from the pack file as it is read, so
+    /// the object is never held in memory.
+    ///
+    /// # Returns
+    ///
+    /// The object type, its size and the reader, or `None` if the entry
+    /// is a delta.
+    pub(crate) fn stream_at_offset(
+        &self,
+        offset: u64,
+    ) -> Result<Option<(ObjectType, u64, ZlibReader<PackReader>)>> {
+        let entry = self.entry_header(offset)?;
+        if entry.base.is_some() {
+            return Ok(None);
+        }
+        let object_type = object_type(entry.code).ok_or_else(|| self.corrupt(offset))?;
+        let reader = self.reader_at(entry.data_offset)?;
+        Ok(Some((object_type, entry.size, ZlibReader::new(reader))))
+    }
+
+    /// Reads the type and size of an object in the pack.
+    ///
+    /// For deltified objects only the delta headers are inflated, not the
+    /// bases.
+    ///
+    /// # Returns
+    ///
+    /// The object type and content size, or `None` if the object is not
+    /// in the pack.
+    pub fn read_header(&self, oid: &Oid) -> Result<Option<(ObjectType, u64)>> {
+        match self.find_offset(oid)? {
+            Some(offset) => self.read_header_at_offset(offset).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the type and size of the object stored at an offset in the
+    /// pack.
+    pub(crate) fn read_header_at_offset(&self, offset: u64) -> Result<(ObjectType, u64)> {
+        let mut entry = self.entry_header(offset)?;
+        let mut size = None;
+        for _ in 0..MAX_CHAIN_LEN {
+            let Some(base) = &entry.base else {
+                let object_type = object_type(entry.code).ok_or_else(|| self.corrupt(offset))?;
+                return Ok((object_type, size.unwrap_or(entry.size)));
+            };
+            if size.is_none() {
+                let head = self.inflate_prefix(entry.data_offset, 32)?;
+                size = Some(delta::result_size(&head).ok_or_else(|| self.corrupt(offset))?);
+            }
+            let base_offset = self.base_offset(base, offset)?;
+            entry = self.entry_header(base_offset)?;
+        }
+        Err(self.invalid("delta chain is too long"))
+    }
+
+    /// Looks up the offset of an object in the pack.
+    pub fn find_offset(&self, oid: &Oid) -> Result<Option<u64>> {
+        match self.position(oid)? {
+            Some(pos) => self.offset_at(pos).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Finds the object stored at an offset in the pack.
+    ///
+    /// The reverse index (`.rev` file) is used when there is one;
+    /// otherwise one is built from the offsets in the index the first time.
+    /// Either way it is kept with the pack, so each lookup is a binary
+    /// search.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the object, or `None` if no object starts at the offset.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidPack` if the reverse index is invalid or
+    /// belongs to another pack.
+    pub fn oid_at_offset(&self, offset: u64) -> Result<Option<Oid>> {
+        match self.position_at_offset(offset)? {
+            Some(pos) => Ok(Some(self.oids_in(pos, pos + 1)?.remove(0))),
+            None => Ok(None),
+        }
+    }
+
+    /// Finds the object a deltified object in the pack is stored against,
+    /// as `%(deltabase)` of `git cat-file --batch-check` does.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the delta base, or `None` if the object is not in the
+    /// pack or is stored whole.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidPack` if the entry is corrupt or its base is
+    /// not the start of an entry.
+    pub fn delta_base(&self, oid: &Oid) -> Result<Option<Oid>> {
+        match self.find_offset(oid)? {
+            Some(offset) => self.delta_base_at_offset(offset),
+            None => Ok(None),
+        }
+    }
+
+    /// Finds the delta base of the entry at an offset in the pack.
+    pub(crate) fn delta_base_at_offset(&self, offset: u64) -> Result<Option<Oid>> {
+        match self.entry_header(offset)?.base {
+            None => Ok(None),
+            Some(DeltaBase::Oid(oid)) => Ok(Some(oid)),
+            Some(DeltaBase::Offset(base_offset)) => match self.oid_at_offset(base_offset)? {
+                Some(oid) => Ok(Some(oid)),
+                None => Err(self.corrupt(offset)),
+            },
+        }
+    }
+
+    /// Binary-searches the reverse index for the object at an offset,
+    /// returning its position in the ID table.
+    fn position_at_offset(&self, offset: u64) -> Result<Option<usize>> {
+        let reverse = self.reverse_index()?;
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            let pos = self.reverse_position(reverse, mid)?;
+            match self.offset_at(pos)?.cmp(&offset) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Ok(Some(pos)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the reverse index, loading it on first use.
+    fn reverse_index(&self) -> Result<&ReverseIndex> {
+        if let Some(reverse) = self.reverse.get() {
+            return Ok(reverse);
+        }
+        let reverse = match self.open_reverse_index()? {
+            Some(file) => ReverseIndex::File(Mutex::new(file)),
+            None => {
+                let offsets = self.offsets()?;
+                let mut order: Vec<u32> = (0..self.len() as u32).collect();
+                order.sort_by_key(|&pos| offsets[pos as usize]);
+                ReverseIndex::Memory(order)
+            }
+        };
+        Ok(self.reverse.get_or_init(|| reverse))
+    }
+
+    /// Returns the ID table position of the object at a rank in pack
+    /// order.
+    fn reverse_position(&self, reverse: &ReverseIndex, rank: usize) -> Result<usize> {
+        let pos = match reverse {
+            ReverseIndex::Memory(order) => order[rank] as usize,
+            ReverseIndex::File(file) => {
+                let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+                let mut buf = [0u8; 4];
+                file.seek(SeekFrom::Start(REVERSE_INDEX_HEADER_SIZE + rank as u64 * 4))?;
+                file.read_exact(&mut buf)
+                    .map_err(|_| self.invalid("reverse index is truncated"))?;
+                be_u32(&buf) as usize
+            }
+        };
+        if pos >= self.len() {
+            return Err(self.invalid("reverse index position out of range"));
+        }
+        Ok(pos)
+    }
+
+    /// Opens the reverse index of the pack and checks its header, size
+    /// and pack checksum.
+    ///
+    /// # Returns
+    ///
+    /// The open `.rev` file, or `None` if the pack has none.
+    fn open_reverse_index(&self) -> Result<Option<File>> {
+        let path = self.index_path.with_extension("rev");
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let invalid = |reason: &str| Error::InvalidPack {
+            path: path.clone(),
+            reason: reason.to_string(),
+        };
+
+        let mut header = [0u8; REVERSE_INDEX_HEADER_SIZE as usize];
+        file.read_exact(&mut header)
+            .map_err(|_| invalid("reverse index is too short"))?;
+        if &header[..4] != REVERSE_INDEX_MAGIC || be_u32(&header[4..]) != 1 {
+            return Err(invalid("unsupported reverse index version"));
+        }
+        if be_u32(&header[8..]) != self.format.hash_id() {
+            return Err(invalid("reverse index uses a different object format"));
+        }
+        let size = self.format.size() as u64;
+        let expected = REVERSE_INDEX_HEADER_SIZE + self.len() as u64 * 4 + 2 * size;
+        if file.metadata()?.len() != expected {
+            return Err(invalid("reverse index size does not match the index"));
+        }
+        let mut checksum = vec![0u8; size as usize];
+        file.seek(SeekFrom::Start(expected - 2 * size))?;
+        file.read_exact(&mut checksum)?;
+        if checksum != self.pack_checksum()? {
+            return Err(invalid("reverse index belongs to another pack"));
+        }
+        Ok(Some(file))
+    }
+
+    /// Reads the checksum of the pack from the index trailer.
+    fn pack_checksum(&self) -> Result<Vec<u8>> {
+        let size = self.format.size() as u64;
+        let len = {
+            let index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+            index.metadata()?.len()
+        };
+        let offset = len
+            .checked_sub(2 * size)
+            .ok_or_else(|| self.invalid("index file is truncated"))?;
+        let mut checksum = vec![0u8; size as usize];
+        self.read_index(offset, &mut checksum)?;
+        Ok(checksum)
+    }
+
+    /// Finds the position of an object in the sorted ID table.
+    fn position(&self, oid: &Oid) -> Result<Option<usize>> {
+        if oid.format() != self.format {
+            return Ok(None);
+        }
+        self.fanout.search(oid, |pos, name| {
+            self.read_index(self.name_offset(pos), name)
+        })
+    }
+
+    /// Reads the object IDs at positions `start..end` of the ID table.
+    fn oids_in(&self, start: usize, end: usize) -> Result<Vec<Oid>> {
+        let size = self.format.size();
+        let mut names = vec![0u8; (end - start) * size];
+        self.read_index(self.name_offset(start), &mut names)?;
+        names.chunks(size).map(Oid::from_slice).collect()
+    }
+
+    /// Returns the offset in the index of the ID at a position.
+    fn name_offset(&self, pos: usize) -> u64 {
+        INDEX_HEADER_SIZE + (pos * self.format.size()) as u64
+    }
+
+    /// Returns the offset in the index of the table of 4-byte offsets.
+    fn offset_table(&self) -> u64 {
+        let n = self.len() as u64;
+        INDEX_HEADER_SIZE + n * self.format.size() as u64 + n * 4
+    }
+
+    /// Reads the pack offset of the object at a position.
+    fn offset_at(&self, pos: usize) -> Result<u64> {
+        let mut buf = [0u8; 4];
+        self.read_index(self.offset_table() + pos as u64 * 4, &mut buf)?;
+        self.resolve_offset(be_u32(&buf))
+    }
+
+    /// Reads the pack offsets of all objects, in ID table order.
+    fn offsets(&self) -> Result<Vec<u64>> {
+        let mut table = vec![0u8; self.len() * 4];
+        self.read_index(self.offset_table(), &mut table)?;
+        table
+            .chunks(4)
+            .map(|entry| self.resolve_offset(be_u32(entry)))
+            .collect()
+    }
+
+    /// Resolves an entry of the offset table, reading the 8-byte offset it
+    /// refers to for objects past 2 GiB.
+    fn resolve_offset(&self, offset: u32) -> Result<u64> {
+        if offset & 0x8000_0000 == 0 {
+            return Ok(offset as u64);
+        }
+        let large = self.offset_table() + self.len() as u64 * 4 + (offset & 0x7fff_ffff) as u64 * 8;
+        let mut buf = [0u8; 8];
+        self.read_index(large, &mut buf)?;
+        Ok(u64::from_be_bytes(buf))
+    }
+
+    /// Reads bytes of the index file at an offset.
+    fn read_index(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let mut index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        index.seek(SeekFrom::Start(offset))?;
+        index
+            .read_exact(buf)
+            .map_err(|_| self.invalid("index file is truncated"))
+    }
+
+    /// Returns a reader over the pack file from an offset, opening the
+    /// file and checking its header on first use.
+    fn reader_at(&self, offset: u64) -> Result<PackReader> {
+        let file = match self.pack.get() {
+            Some(file) => file,
+            None => {
+                let mut pack = File::open(&self.pack_path)?;
+                let mut header = [0u8; 12];
+                pack.read_exact(&mut header)
+                    .map_err(|_| self.invalid("pack file is too short"))?;
+                if &header[..4] != PACK_MAGIC || !matches!(be_u32(&header[4..]), 2 | 3) {
+                    return Err(self.invalid("unsupported pack version"));
+                }
+                self.pack.get_or_init(|| Arc::new(Mutex::new(pack)))
+            }
+        };
+        Ok(PackReader {
+            file: Arc::clone(file),
+            pos: offset,
+        })
+    }
+
+    /// Reads the entry at an offset, applying its chain of deltas.
+    ///
+    /// With a delta base cache, the chain is only followed down to the
+    /// first base already resolved, and every base resolved on the way
+    /// back up is cached for the next object of the chain.
+    fn read_at(&self, offset: u64) -> Result<(ObjectType, Vec<u8>)> {
+        let mut chain = Vec::new();
+        let mut entry_offset = offset;
+        let (object_type, mut content) = loop {
+            if !chain.is_empty() {
+                let cached = self
+                    .cache
+                    .as_ref()
+                    .and_then(Weak::upgrade)
+                    .and_then(|cache| cache.delta_base(&self.pack_path, entry_offset));
+                if let Some((object_type, content)) = cached {
+                    break (object_type, content.to_vec());
+                }
+            }
+            let entry = self.entry_header(entry_offset)?;
+            match &entry.base {
+                None => {
+                    let object_type =
+                        object_type(entry.code).ok_or_else(|| self.corrupt(entry_offset))?;
+                    let data = self.inflate(entry.data_offset, entry.size, entry_offset)?;
+                    if !chain.is_empty() {
+                        self.cache_delta_base(entry_offset, object_type, &data);
+                    }
+                    break (object_type, data);
+                }
+                Some(base) => {
+                    if chain.len() == MAX_CHAIN_LEN {
+                        return Err(self.invalid("delta chain is too long"));
+                    }
+                    let base_offset = self.base_offset(base, entry_offset)?;
+                    chain.push((entry, entry_offset));
+                    entry_offset = base_offset;
+                }
+            }
+        };
+
+        for (depth, (entry, entry_offset)) in chain.iter().enumerate().rev() {
+            let data = self.inflate(entry.data_offset, entry.size, *entry_offset)?;
+            content = delta::apply_delta(&content, &data).map_err(|reason| Error::InvalidPack {
+                path: self.pack_path.clone(),
+                reason: format!("bad delta at {}: {}", self.describe(*entry_offset), reason),
+            })?;
+            if depth > 0 {
+                self.cache_delta_base(*entry_offset, object_type, &content);
+            }
+        }
+        Ok((object_type, content))
+    }
+
+    /// Caches the resolved object at an offset as a delta base.
+    fn cache_delta_base(&self, offset: u64, object_type: ObjectType, content: &[u8]) {
+        if let Some(cache) = self.cache.as_ref().and_then(Weak::upgrade) {
+            cache.insert_delta_base(&self.pack_path, offset, object_type, content);
+        }
+    }
+
+    /// Resolves the offset of a delta base.
+    fn base_offset(&self, base: &DeltaBase, offset: u64) -> Result<u64> {
+        match base {
+            DeltaBase::Offset(base_offset) => Ok(*base_offset),
+            DeltaBase::Oid(oid) => self.find_offset(oid)?.ok_or_else(|| Error::InvalidPack {
+                path: self.pack_path.clone(),
+                reason: format!("delta base {} of offset {} is not in the pack", oid, offset),
+            }),
+        }
+    }
+
+    /// Parses the header of the entry at an offset.
+    fn entry_header(&self, offset: u64) -> Result<EntryHeader> {
+        let mut buf = [0u8; 64];
+        let mut pack = self.reader_at(offset)?;
+        let mut len = 0;
+        while len < buf.len() {
+            match pack.read(&mut buf[len..])? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        let buf = &buf[..len];
+        let byte = |pos: usize| buf.get(pos).copied().ok_or_else(|| self.corrupt(offset));
+
+        let mut pos = 0;
+        let mut c = byte(pos)?;
+        pos += 1;
+        let code = (c >> 4) & 7;
+        let mut size = (c & 15) as u64;
+        let mut shift = 4;
+        while c & 0x80 != 0 {
+            c = byte(pos)?;
+            pos += 1;
+            if shift > 57 {
+                return Err(self.corrupt(offset));
+            }
+            size |= ((c & 0x7f) as u64) << shift;
+            shift += 7;
+        }
+
+        let base = match code {
+            OFS_DELTA => {
+                c = byte(pos)?;
+                pos += 1;
+                let mut distance = (c & 0x7f) as u64;
+                while c & 0x80 != 0 {
+                    c = byte(pos)?;
+                    pos += 1;
+                    if distance >= 1 << 56 {
+                        return Err(self.corrupt(offset));
+                    }
+                    distance = ((distance + 1) << 7) | (c & 0x7f) as u64;
+                }
+                if distance == 0 || distance > offset {
+                    return Err(self.corrupt(offset));
+                }
+                Some(DeltaBase::Offset(offset - distance))
+            }
+            REF_DELTA => {
+                let size = self.format.size();
+                let name = buf
+                    .get(pos..pos + size)
+                    .ok_or_else(|| self.corrupt(offset))?;
+                pos += size;
+                Some(DeltaBase::Oid(Oid::from_slice(name)?))
+            }
+            _ => None,
+        };
+
+        Ok(EntryHeader {
+            code,
+            size,
+            data_offset: offset + pos as u64,
+            base,
+        })
+    }
+
+    /// Inflates `size` bytes of entry data starting at an offset.
+    fn inflate(&self, data_offset: u64, size: u64, offset: u64) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        ZlibReader::new(self.reader_at(data_offset)?)
+            .take(size)
+            .read_to_end(&mut data)
+            .map_err(|_| self.corrupt(offset))?;
+        if data.len() as u64 != size {
+            return Err(self.corrupt(offset));
+        }
+        Ok(data)
+    }
+
+    /// Inflates up to `len` bytes of entry data starting at an offset.
+    fn inflate_prefix(&self, data_offset: u64, len: u64) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        ZlibReader::with_capacity(self.reader_at(data_offset)?, 64)
+            .take(len)
+            .read_to_end(&mut data)
+            .map_err(|_| self.invalid("corrupt delta header"))?;
+        Ok(data)
+    }
+
+    /// Returns an error for a corrupt entry.
+    fn corrupt(&self, offset: u64) -> Error {
+        Error::InvalidPack {
+            path: self.pack_path.clone(),
+            reason: format!("corrupt entry at {}", self.describe(offset)),
+        }
+    }
+
+    /// Describes an entry for an error: its offset, and the object stored
+    /// there if the reverse index finds one.
+    fn describe(&self, offset: u64) -> String {
+        match self.oid_at_offset(offset) {
+            Ok(Some(oid)) => format!("offset {} ({})", offset, oid),
+            _ => format!("offset {}", offset),
+        }
+    }
+
+    /// Returns an `InvalidPack` error for this pack.
+    fn invalid(&self, reason: &str) -> Error {
+        Error::InvalidPack {
+            path: self.pack_path.clone(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// The Oid, CRC-32 and offset of an entry written to a pack.
+type WrittenEntry = (Oid, u32, u64);
+
+/// Where the content of an object queued for a pack comes from.
+enum PackContent {
+    /// The content given to [`PackWriter::add`].
+    Memory(Vec<u8>),
+    /// Read from the writer's object store when the pack is written.
+    Stored,
+}
+
+/// An object queued for a pack.
+struct PackObject {
+    oid: Oid,
+    object_type: ObjectType,
+    size: u64,
+    content: PackContent,
+}
+
+/// The packfile being written, hashed as it goes and with a CRC-32 of the
+/// current entry.
+struct PackOutput {
+    file: BufWriter<File>,
+    hasher: FileHasher,
+    offset: u64,
+    crc: u32,
+}
+
+impl PackOutput {
+    /// Starts a new entry, returning its offset.
+    fn start_entry(&mut self) -> u64 {
+        self.crc = 0;
+        self.offset
+    }
+
+    /// Writes the trailing checksum and returns the file with it.
+    fn finish(self) -> Result<(File, Vec<u8>)> {
+        let checksum = self.hasher.finalize();
+        let mut file = self.file.into_inner().map_err(|e| e.into_error())?;
+        file.write_all(&checksum)?;
+        Ok((file, checksum))
+    }
+}
+
+impl Write for PackOutput {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = self.file.write(data)?;
+        self.hasher.update(&data[..n]);
+        self.crc = crc32_update(self.crc, &data[..n]);
+        self.offset += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Writes objects into a new packfile and index.
+///
+/// Objects are stored as deltas against similar objects of the same type
+/// where that saves space. Candidates are found with a sliding window over
+/// the objects sorted by type and size, as `git pack-objects` does.
+/// Objects larger than the big file threshold are stored whole, so that
+/// they can be streamed from the pack.
+///
+/// The pack is written to a temporary file as the objects are compressed,
+/// so only the objects in the window are held in memory, besides those
+/// given to [`PackWriter::add`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use zerogit::objects::{ObjectFormat, ObjectType, PackWriter};
+///
+/// let mut writer = PackWriter::new(ObjectFormat::Sha1).window(20);
+/// writer.add(ObjectType::Blob, b"hello\n".to_vec()).unwrap();
+/// let pack = writer.write(".git/objects/pack").unwrap();
+/// println!("wrote {}", pack.pack_path().display());
+/// ```
+pub struct PackWriter {
+    format: ObjectFormat,
+    window: usize,
+    depth: usize,
+    big_file_threshold: u64,
+    objects: Vec<PackObject>,
+    seen: HashSet<Oid>,
+    store: Option<LooseObjectStore>,
+    fsync: Arc<Fsync>,
+}
+
+impl PackWriter {
+    /// Creates a writer with a window of 10 objects, a maximum delta
+    /// chain depth of 50 and a big file threshold of 512 MiB, Git's
+    /// defaults.
+    pub fn new(format: ObjectFormat) -> Self {
+        PackWriter {
+            format,
+            window: 10,
+            depth: 50,
+            big_file_threshold: DEFAULT_BIG_FILE_THRESHOLD,
+            objects: Vec::new(),
+            seen: HashSet::new(),
+            store: None,
+            fsync: Arc::default(),
+        }
+    }
+
+    /// Sets how many preceding objects are tried as delta bases for each
+    /// object. A window of 0 disables delta compression.
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the longest chain of deltas an object may need to be read.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Sets the size above which objects are neither deltified nor used as
+    /// delta bases (`core.bigFileThreshold`).
+    pub fn big_file_threshold(mut self, big_file_threshold: u64) -> Self {
+        self.big_file_threshold = big_file_threshold;
+        self
+    }
+
+    /// Reads the objects added with [`PackWriter::add_stored`] from a
+    /// store.
+    pub(crate) fn with_store(mut self, store: LooseObjectStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Flushes the written files as the repository's `core.fsync`
+    /// settings ask, instead of always flushing them.
+    pub(crate) fn with_fsync(mut self, fsync: Arc<Fsync>) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// Returns the window size.
+    pub fn get_window(&self) -> usize {
+        self.window
+    }
+
+    /// Returns the maximum delta chain depth.
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the big file threshold.
+    pub fn get_big_file_threshold(&self) -> u64 {
+        self.big_file_threshold
+    }
+
+    /// Adds an object to the pack. Adding the same object twice stores it
+    /// once.
+    ///
+    /// # Returns
+    ///
+    /// The Oid of the object.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Sha1Collision` if the content is part of a known
+    /// SHA-1 collision attack.
+    pub fn add(&mut self, object_type: ObjectType, content: Vec<u8>) -> Result<Oid> {
+        let oid = Oid::from_slice(&hash_object_with(
+            self.format,
+            object_type.as_str(),
+            &content,
+        )?)?;
+        if self.seen.insert(oid) {
+            self.objects.push(PackObject {
+                oid,
+                object_type,
+                size: content.len() as u64,
+                content: PackContent::Memory(content),
+            });
+        }
+        Ok(oid)
+    }
+
+    /// Adds an object of the store given to [`PackWriter::with_store`].
+    ///
+    /// The content is only read, and checked against the Oid, when the
+    /// pack is written; objects above the big file threshold are streamed
+    /// into the pack rather than read into memory.
+    pub(crate) fn add_stored(&mut self, oid: Oid, object_type: ObjectType, size: u64) {
+        if self.seen.insert(oid) {
+            self.objects.push(PackObject {
+                oid,
+                object_type,
+                size,
+                content: PackContent::Stored,
+            });
+        }
+    }
+
+    /// Returns the number of objects added.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Returns `true` if no objects were added.
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Writes the pack and its index into a directory.
+    ///
+    /// The files are named `pack-<checksum>.pack` and `pack-<checksum>.idx`,
+    /// and are read-only, as Git writes them. The pack is written to a
+    /// temporary file and renamed once complete, and the index is written
+    /// last, so readers never see an index without its pack.
+    ///
+    /// # Arguments
+    ///
+    /// * `pack_dir` - The directory to write to, usually `.git/objects/pack`.
+    ///
+    /// # Returns
+    ///
+    /// The written pack.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::ObjectNotFound` if a stored object is missing.
+    /// - `Error::InvalidObject` if a stored object does not match its Oid.
+    pub fn write<P: AsRef<Path>>(mut self, pack_dir: P) -> Result<Pack> {
+        let pack_dir = pack_dir.as_ref();
+        self.objects.sort_by(|a, b| {
+            type_code(a.object_type)
+                .cmp(&type_code(b.object_type))
+                .then(b.size.cmp(&a.size))
+        });
+
+        fs::create_dir_all(pack_dir)?;
+        let temp = temp_path(&pack_dir.join("pack"));
+        let (file, checksum, mut entries) = match self.write_pack(&temp) {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = remove_file(&temp);
+                return Err(e);
+            }
+        };
+
+        let name = format!("pack-{}", Oid::from_slice(&checksum)?.to_hex());
+        let pack_path = pack_dir.join(format!("{}.pack", name));
+        self.fsync
+            .persist(FsyncComponent::Pack, file, &temp, &pack_path, true)?;
+
+        entries.sort_by_key(|entry| entry.0);
+        let index = build_index(&entries, &checksum, self.format);
+        let index_path = pack_dir.join(format!("{}.idx", name));
+        self.fsync
+            .write_file(FsyncComponent::PackMetadata, &index_path, &index, true)?;
+        Pack::open(index_path, self.format)
+    }
+
+    /// Writes the sorted objects into a temporary pack file, choosing a
+    /// delta base for each object among the objects before it in the
+    /// window.
+    ///
+    /// # Returns
+    ///
+    /// The file, the pack checksum, and the Oid, CRC-32 and offset of each
+    /// entry.
+    fn write_pack(&mut self, temp: &Path) -> Result<(File, Vec<u8>, Vec<WrittenEntry>)> {
+        let mut out = PackOutput {
+            file: BufWriter::new(File::create(temp)?),
+            hasher: FileHasher::new(self.format),
+            offset: 0,
+            crc: 0,
+        };
+        out.write_all(PACK_MAGIC)?;
+        out.write_all(&2u32.to_be_bytes())?;
+        out.write_all(&(self.objects.len() as u32).to_be_bytes())?;
+
+        let mut entries = Vec::with_capacity(self.objects.len());
+        let mut offsets = Vec::with_capacity(self.objects.len());
+        let mut depths = vec![0usize; self.objects.len()];
+        let mut window: VecDeque<(usize, Vec<u8>, DeltaIndex)> = VecDeque::new();
+
+        for i in 0..self.objects.len() {
+            let offset = out.start_entry();
+            let object = &self.objects[i];
+            let deltifiable =
+                object.size >= MIN_DELTA_SIZE as u64 && object.size <= self.big_file_threshold;
+            if !deltifiable {
+                self.write_whole(i, &mut out)?;
+            } else {
+                let target = self.take_content(i)?;
+                let object = &self.objects[i];
+                let mut best: Option<(usize, Vec<u8>)> = None;
+                for (base, base_content, index) in window.iter().rev() {
+                    if self.objects[*base].object_type != object.object_type
+                        || depths[*base] >= self.depth
+                        || base_content.len() / 32 > target.len()
+                    {
+                        continue;
+                    }
+                    // A delta must save at least half of the object
+                    let max_size = match &best {
+                        Some((_, delta)) => delta.len() - 1,
+                        None => target.len() / 2,
+                    };
+                    if let Some(delta) = index.delta(base_content, &target, max_size) {
+                        best = Some((*base, delta));
+                    }
+                }
+
+                let mut header = Vec::new();
+                match &best {
+                    Some((base, delta)) => {
+                        depths[i] = depths[*base] + 1;
+                        push_entry_header(&mut header, OFS_DELTA, delta.len() as u64);
+                        push_offset_distance(&mut header, offset - offsets[*base]);
+                        out.write_all(&header)?;
+                        out.write_all(&compress(delta))?;
+                    }
+                    None => {
+                        push_entry_header(&mut header, type_code(object.object_type), object.size);
+                        out.write_all(&header)?;
+                        out.write_all(&compress(&target))?;
+                    }
+                }
+
+                if self.window > 0 {
+                    if window.len() == self.window {
+                        window.pop_front();
+                    }
+                    let index = DeltaIndex::new(&target);
+                    window.push_back((i, target, index));
+                }
+            }
+            entries.push((self.objects[i].oid, out.crc, offset));
+            offsets.push(offset);
+        }
+
+        let (file, checksum) = out.finish()?;
+        Ok((file, checksum, entries))
+    }
+
+    /// Takes the content of an object out of the queue, reading it from
+    /// the store if it was added with [`PackWriter::add_stored`].
+    fn take_content(&mut self, i: usize) -> Result<Vec<u8>> {
+        if let PackContent::Memory(content) = &mut self.objects[i].content {
+            return Ok(std::mem::take(content));
+        }
+        let object = &self.objects[i];
+        let raw = self.stored(&object.oid)?.read(&object.oid)?;
+        let oid = hash_object_with(self.format, raw.object_type.as_str(), &raw.content)?;
+        if raw.object_type != object.object_type || oid != object.oid.as_bytes() {
+            return Err(Error::InvalidObject {
+                oid: object.oid.to_hex(),
+                reason: "hash mismatch".to_string(),
+            });
+        }
+        Ok(raw.content)
+    }
+
+    /// Writes an object that is not deltified as a whole entry. Stored
+    /// objects are streamed from the store.
+    fn write_whole(&mut self, i: usize, out: &mut PackOutput) -> Result<()> {
+        let object = &mut self.objects[i];
+        let mut header = Vec::new();
+        push_entry_header(&mut header, type_code(object.object_type), object.size);
+        if let PackContent::Memory(content) = &mut object.content {
+            let content = std::mem::take(content);
+            out.write_all(&header)?;
+            out.write_all(&compress(&content))?;
+            return Ok(());
+        }
+
+        let object = &self.objects[i];
+        let mut reader = self.stored(&object.oid)?.read_stream(&object.oid)?;
+        if reader.object_type() != object.object_type || reader.size() != object.size {
+            return Err(Error::InvalidObject {
+                oid: object.oid.to_hex(),
+                reason: "hash mismatch".to_string(),
+            });
+        }
+        out.write_all(&header)?;
+        let mut writer = ZlibWriter::new(&mut *out);
+        io::copy(&mut reader, &mut writer).map_err(ObjectReader::into_error)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Returns the store objects added with [`PackWriter::add_stored`]
+    /// are read from.
+    fn stored(&self, oid: &Oid) -> Result<&LooseObjectStore> {
+        self.store
+            .as_ref()
+            .ok_or_else(|| Error::ObjectNotFound(oid.to_hex()))
+    }
+}
+
+impl std::fmt::Debug for PackWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PackWriter")
+            .field("format", &self.format)
+            .field("window", &self.window)
+            .field("depth", &self.depth)
+            .field("big_file_threshold", &self.big_file_threshold)
+            .field("objects", &self.objects.len())
+            .finish()
+    }
+}
+
+/// Builds a version 2 index for entries sorted by Oid.
+fn build_index(entries: &[WrittenEntry], pack_checksum: &[u8], format: ObjectFormat) -> Vec<u8> {
+    let mut index = Vec::new();
+    index.extend_from_slice(INDEX_MAGIC);
+    index.extend_from_slice(&2u32.to_be_bytes());
+
+    let mut fanout = [0u32; 256];
+    for (oid, _, _) in entries {
+        fanout[oid.as_bytes()[0] as usize] += 1;
+    }
+    let mut total = 0;
+    for count in fanout {
+        total += count;
+        index.extend_from_slice(&total.to_be_bytes());
+    }
+
+    for (oid, _, _) in entries {
+        index.extend_from_slice(oid.as_bytes());
+    }
+    for (_, crc, _) in entries {
+        index.extend_from_slice(&crc.to_be_bytes());
+    }
+    let mut large = Vec::new();
+    for (_, _, offset) in entries {
+        if *offset < 0x8000_0000 {
+            index.extend_from_slice(&(*offset as u32).to_be_bytes());
+        } else {
+            index.extend_from_slice(&(0x8000_0000 | (large.len() / 8) as u32).to_be_bytes());
+            large.extend_from_slice(&offset.to_be_bytes());
+        }
+    }
+    index.extend_from_slice(&large);
+    index.extend_from_slice(pack_checksum);
+
+    let checksum = digest(format, &index);
+    index.extend_from_slice(&checksum);
+    index
+}
+
+/// Appends a pack entry header: the type and the inflated size.
+fn push_entry_header(out: &mut Vec<u8>, code: u8, mut size: u64) {
+    let mut c = (code << 4) | (size & 15) as u8;
+    size >>= 4;
+    while size != 0 {
+        out.push(c | 0x80);
+        c = (size & 0x7f) as u8;
+        size >>= 7;
+    }
+    out.push(c);
+}
+
+/// Appends the distance back to the base of an `OFS_DELTA` entry.
+fn push_offset_distance(out: &mut Vec<u8>, mut distance: u64) {
+    let mut bytes = vec![(distance & 0x7f) as u8];
+    distance >>= 7;
+    while distance != 0 {
+        distance -= 1;
+        bytes.push(0x80 | (distance & 0x7f) as u8);
+        distance >>= 7;
+    }
+    bytes.reverse();
+    out.extend_from_slice(&bytes);
+}
+
+/// Reads a big-endian u32 from the start of a slice.
+pub(super) fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::noise;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Writes a pack with a base blob, an edited copy of it, a small blob
+    /// and a commit, returning the pack and the objects.
+    fn sample_pack(dir: &Path, format: ObjectFormat) -> (Pack, Vec<(Oid, ObjectType, Vec<u8>)>) {
+        let base = noise(8_000, 1);
+        let mut edited = base.clone();
+        edited[4_000..4_010].copy_from_slice(b"0123456789");
+        let objects = vec![
+            (ObjectType::Blob, base),
+            (ObjectType::Blob, edited),
+            (ObjectType::Blob, b"small\n".to_vec()),
+            (ObjectType::Commit, b"tree 0\n\nmessage\n".to_vec()),
+        ];
+
+        let mut writer = PackWriter::new(format);
+        let objects: Vec<_> = objects
+            .into_iter()
+            .map(|(t, c)| (writer.add(t, c.clone()).unwrap(), t, c))
+            .collect();
+        assert_eq!(writer.len(), 4);
+        (writer.write(dir).unwrap(), objects)
+    }
+
+    // PK-001: Written objects read back, including deltified ones
+    #[test]
+    fn test_pack_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let (pack, objects) = sample_pack(temp.path(), ObjectFormat::Sha1);
+
+        assert_eq!(pack.len(), 4);
+        let mut expected: Vec<Oid> = objects.iter().map(|(oid, _, _)| *oid).collect();
+        expected.sort();
+        assert_eq!(pack.oids().unwrap(), expected);
+
+        for (oid, object_type, content) in &objects {
+            let raw = pack.read(oid).unwrap().unwrap();
+            assert_eq!(raw.object_type, *object_type);
+            assert_eq!(&raw.content, content);
+            assert_eq!(
+                pack.read_header(oid).unwrap(),
+                Some((*object_type, content.len() as u64))
+            );
+        }
+
+        // The edited blob is stored as a small delta
+        let size = fs::metadata(pack.pack_path()).unwrap().len();
+        assert!(size < 9_000, "pack is {} bytes", size);
+    }
+
+    // PK-002: Lookups of absent objects and prefixes
+    #[test]
+    fn test_pack_lookup() {
+        let temp = TempDir::new().unwrap();
+        let (pack, objects) = sample_pack(temp.path(), ObjectFormat::Sha1);
+
+        let absent = Oid::from_hex("0000000000000000000000000000000000000001").unwrap();
+        assert!(!pack.contains(&absent).unwrap());
+        assert!(pack.read(&absent).unwrap().is_none());
+        assert!(pack.read_header(&absent).unwrap().is_none());
+
+        let oid = objects[0].0;
+        assert!(pack.contains(&oid).unwrap());
+        assert_eq!(pack.find_by_prefix(&oid.to_hex()[..6]).unwrap(), vec![oid]);
+        assert!(pack.find_by_prefix("zz").unwrap().is_empty());
+    }
+
+    // PK-003: SHA-256 packs use 32-byte names and checksums
+    #[test]
+    fn test_pack_sha256() {
+        let temp = TempDir::new().unwrap();
+        let (pack, objects) = sample_pack(temp.path(), ObjectFormat::Sha256);
+
+        let name = pack
+            .pack_path()
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        assert_eq!(name.len(), "pack-".len() + 64);
+        for (oid, _, content) in &objects {
+            assert_eq!(&pack.read(oid).unwrap().unwrap().content, content);
+        }
+    }
+
+    // PK-004: Entry headers and offset distances use Git's encodings
+    #[test]
+    fn test_entry_encodings() {
+        let mut out = Vec::new();
+        push_entry_header(&mut out, 3, 100);
+        assert_eq!(out, [0xb4, 0x06]);
+
+        let mut out = Vec::new();
+        push_offset_distance(&mut out, 127);
+        assert_eq!(out, [0x7f]);
+        let mut out = Vec::new();
+        push_offset_distance(&mut out, 128);
+        assert_eq!(out, [0x80, 0x00]);
+    }
+
+    // PK-005: A corrupt index or pack is reported as InvalidPack
+    #[test]
+    fn test_pack_corrupt() {
+        let temp = TempDir::new().unwrap();
+        let (pack, objects) = sample_pack(temp.path(), ObjectFormat::Sha1);
+
+        let bad_index = temp.path().join("bad.idx");
+        fs::write(&bad_index, b"not an index").unwrap();
+        assert!(matches!(
+            Pack::open(&bad_index, ObjectFormat::Sha1),
+            Err(Error::InvalidPack { .. })
+        ));
+
+        let mut data = fs::read(pack.pack_path()).unwrap();
+        let end = data.len() - 20;
+        for byte in &mut data[12..end] {
+            *byte = 0xff;
+        }
+        // The pack is read-only, so it is replaced rather than overwritten
+        fs::remove_file(pack.pack_path()).unwrap();
+        fs::write(pack.pack_path(), data).unwrap();
+        assert!(matches!(
+            pack.read(&objects[0].0),
+            Err(Error::InvalidPack { .. })
+        ));
+    }
+
+    // PK-006: Offsets map back to objects through a checked reverse index,
+    // or one built from the index
+    #[test]
+    fn test_oid_at_offset() {
+        let temp = TempDir::new().unwrap();
+        let (pack, objects) = sample_pack(temp.path(), ObjectFormat::Sha1);
+        let oids = pack.oids().unwrap();
+        let offsets: Vec<u64> = oids
+            .iter()
+            .map(|oid| pack.find_offset(oid).unwrap().unwrap())
+            .collect();
+
+        let check = |pack: &Pack| {
+            for (oid, offset) in oids.iter().zip(&offsets) {
+                assert_eq!(pack.oid_at_offset(*offset).unwrap(), Some(*oid));
+            }
+            assert_eq!(pack.oid_at_offset(0).unwrap(), None);
+            assert_eq!(pack.oid_at_offset(offsets[0] + 1).unwrap(), None);
+        };
+        check(&pack);
+        // The edited blob is an OFS_DELTA against the base blob
+        assert_eq!(pack.delta_base(&objects[1].0).unwrap(), Some(objects[0].0));
+        assert_eq!(pack.delta_base(&objects[0].0).unwrap(), None);
+
+        // Index positions in pack order, as `git index-pack --rev-index`
+        // writes them, then the pack checksum and a checksum of the file
+        let mut order: Vec<usize> = (0..oids.len()).collect();
+        order.sort_by_key(|&pos| offsets[pos]);
+        let mut rev = b"RIDX\0\0\0\x01\0\0\0\x01".to_vec();
+        for pos in order {
+            rev.extend_from_slice(&(pos as u32).to_be_bytes());
+        }
+        let data = fs::read(pack.pack_path()).unwrap();
+        rev.extend_from_slice(&data[data.len() - 20..]);
+        rev.extend_from_slice(&[0u8; 20]);
+        let rev_path = pack.index_path().with_extension("rev");
+        let reopen = |rev: &[u8]| {
+            fs::write(&rev_path, rev).unwrap();
+            Pack::open(pack.index_path(), ObjectFormat::Sha1).unwrap()
+        };
+        let with_rev = reopen(&rev);
+        check(&with_rev);
+        assert_eq!(
+            with_rev.delta_base(&objects[1].0).unwrap(),
+            Some(objects[0].0)
+        );
+        // The reverse index is kept open once loaded
+        fs::remove_file(&rev_path).unwrap();
+        check(&with_rev);
+
+        // A reverse index for another object format or pack is rejected
+        let mut other = rev.clone();
+        other[11] = 2;
+        assert!(matches!(
+            reopen(&other).oid_at_offset(offsets[0]),
+            Err(Error::InvalidPack { .. })
+        ));
+        let mut other = rev.clone();
+        other[rev.len() - 40] ^= 1;
+        assert!(matches!(
+            reopen(&other).oid_at_offset(offsets[0]),
+            Err(Error::InvalidPack { reason, .. }) if reason.contains("another pack")
+        ));
+        let mut other = rev.clone();
+        other.truncate(rev.len() - 4);
+        assert!(matches!(
+            reopen(&other).oid_at_offset(offsets[0]),
+            Err(Error::InvalidPack { .. })
+        ));
+    }
+
+    // PK-007: Stored objects are read, or streamed, as the pack is written
+    #[test]
+    fn test_pack_from_store() {
+        let temp = TempDir::new().unwrap();
+        let objects_dir = temp.path().join("objects");
+        let store = LooseObjectStore::new(&objects_dir);
+        let base = noise(8_000, 3);
+        let mut edited = base.clone();
+        edited[4_000..4_010].copy_from_slice(b"0123456789");
+        let objects: Vec<(Oid, Vec<u8>)> = [base, edited, noise(40_000, 4), b"small\n".to_vec()]
+            .into_iter()
+            .map(|content| (store.write(ObjectType::Blob, &content).unwrap(), content))
+            .collect();
+
+        let mut writer = PackWriter::new(ObjectFormat::Sha1)
+            .big_file_threshold(16_000)
+            .with_store(LooseObjectStore::new(&objects_dir));
+        for (oid, content) in &objects {
+            writer.add_stored(*oid, ObjectType::Blob, content.len() as u64);
+        }
+        let pack = writer.write(temp.path().join("pack")).unwrap();
+        for (oid, content) in &objects {
+            assert_eq!(&pack.read(oid).unwrap().unwrap().content, content);
+        }
+        // The big blob is whole and the edited one a delta
+        let offset = pack.find_offset(&objects[2].0).unwrap().unwrap();
+        assert!(pack.stream_at_offset(offset).unwrap().is_some());
+        let size = fs::metadata(pack.pack_path()).unwrap().len();
+        assert!(size < 50_000, "pack is {} bytes", size);
+
+        // An object whose file holds other content of the same size fails
+        // the write, whether it is read or streamed, and leaves no files
+        let good = store.write(ObjectType::Blob, &[b'a'; 100]).unwrap();
+        let bad = store.write(ObjectType::Blob, &[b'b'; 100]).unwrap();
+        fs::remove_file(store.oid_to_path(&bad)).unwrap();
+        fs::copy(store.oid_to_path(&good), store.oid_to_path(&bad)).unwrap();
+        for threshold in [DEFAULT_BIG_FILE_THRESHOLD, 0] {
+            let pack_dir = temp.path().join(format!("bad-{}", threshold));
+            let mut writer = PackWriter::new(ObjectFormat::Sha1)
+                .big_file_threshold(threshold)
+                .with_store(LooseObjectStore::new(&objects_dir));
+            writer.add_stored(bad, ObjectType::Blob, 100);
+            assert!(matches!(
+                writer.write(&pack_dir),
+                Err(Error::InvalidObject { oid, .. }) if oid == bad.to_hex()
+            ));
+            assert_eq!(fs::read_dir(&pack_dir).unwrap().count(), 0);
+        }
+    }
+}
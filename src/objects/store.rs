@@ -1,11 +1,35 @@
 //! Git loose object store implementation.
 
-use std::fs;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
-
-use super::oid::{Oid, OID_HEX_LEN};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::SystemTime;
+
+use super::alternates::{self, ALTERNATES_ENV};
+use super::cache::ObjectCache;
+use super::commit::Commit;
+use super::commit_graph::CommitGraph;
+use super::midx::MultiPackIndex;
+use super::oid::Oid;
+use super::pack::Pack;
+use super::tree::Tree;
 use crate::error::{Error, Result};
-use crate::infra::{compress, decompress, hash_object, read_file, write_file_atomic};
+use crate::infra::{
+    compress, decompress, hash_object_with, read_file, Fsync, FsyncComponent, ObjectFormat,
+    ObjectHasher, ZlibReader, ZlibWriter,
+};
+
+/// Size of the buffer used when streaming object content.
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Longest object header accepted when streaming (`<type> <size>`).
+const MAX_HEADER_LEN: usize = 32;
+
+/// Compressed bytes read at a time when only the header is needed.
+const HEADER_READ_SIZE: usize = 512;
 
 /// The type of a Git object.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -52,14 +76,92 @@ pub struct RawObject {
     pub content: Vec<u8>,
 }
 
+/// The objects of an in-memory object database, uncompressed.
+pub(crate) type MemoryObjects = RwLock<HashMap<Oid, RawObject>>;
+
+/// The packs of an objects directory and its multi-pack-index, opened
+/// once and kept in the object cache until the `pack` directory changes.
+#[derive(Debug, Default)]
+pub(crate) struct PackList {
+    /// Modification time of the `pack` directory before it was listed.
+    mtime: Option<SystemTime>,
+    /// The packs by index file name, sorted.
+    packs: Vec<(String, Arc<Pack>)>,
+    /// The multi-pack-index, if there is a valid one whose packs all
+    /// exist.
+    midx: Option<Arc<MultiPackIndex>>,
+}
+
+impl PackList {
+    /// Returns the pack with the given index file name.
+    fn get(&self, name: &str) -> Option<&Arc<Pack>> {
+        self.packs
+            .binary_search_by(|(pack_name, _)| pack_name.as_str().cmp(name))
+            .ok()
+            .map(|pos| &self.packs[pos].1)
+    }
+
+    /// Returns the multi-pack-index with the packs it does not cover.
+    fn lookup(&self) -> (Option<&MultiPackIndex>, Vec<Arc<Pack>>) {
+        let midx = self.midx.as_deref();
+        let covered = midx.map_or(&[][..], |midx| midx.pack_names());
+        let uncovered = self
+            .packs
+            .iter()
+            .filter(|(name, _)| !covered.contains(name))
+            .map(|(_, pack)| Arc::clone(pack))
+            .collect();
+        (midx, uncovered)
+    }
+
+    /// Returns true if every pack a multi-pack-index refers to is listed.
+    fn covers(&self, midx: &MultiPackIndex) -> bool {
+        midx.pack_names()
+            .iter()
+            .all(|name| self.get(name).is_some())
+    }
+
+    /// Returns true if both lists name the same packs.
+    fn same_packs(&self, other: &PackList) -> bool {
+        self.packs.len() == other.packs.len()
+            && self
+                .packs
+                .iter()
+                .zip(&other.packs)
+                .all(|((a, _), (b, _))| a == b)
+    }
+}
+
 /// A store for reading loose Git objects.
 ///
 /// Loose objects are stored in `.git/objects/` as individual zlib-compressed
-/// files, with the path determined by the object's SHA-1 hash.
+/// files, with the path determined by the object's SHA-1 hash. Objects that
+/// are not loose are looked up in the packs in `.git/objects/pack/`, through
+/// the multi-pack-index for the packs it covers; new objects are always
+/// written loose.
+///
+/// Objects found in neither are read from the alternate object directories
+/// listed in `objects/info/alternates` and `GIT_ALTERNATE_OBJECT_DIRECTORIES`,
+/// which are never written to.
+///
+/// A store opened through a `Repository` shares the repository's object
+/// cache, so objects read once are not decompressed and parsed again, and
+/// the packs are opened once rather than for every read.
 #[derive(Debug)]
 pub struct LooseObjectStore {
     /// Path to the objects directory (e.g., `.git/objects`).
     objects_dir: PathBuf,
+    /// The hash function objects are named with.
+    format: ObjectFormat,
+    /// Recently read objects and delta bases, if caching.
+    cache: Option<Arc<ObjectCache>>,
+    /// The objects, if the store is held in memory instead of on disk.
+    memory: Option<Arc<MemoryObjects>>,
+    /// Flushes written objects to disk.
+    fsync: Arc<Fsync>,
+    /// The stores of the alternate object directories, resolved on first
+    /// use.
+    alternates: OnceLock<Vec<LooseObjectStore>>,
 }
 
 impl LooseObjectStore {
@@ -69,9 +171,144 @@ impl LooseObjectStore {
     ///
     /// * `objects_dir` - Path to the `.git/objects` directory.
     pub fn new<P: AsRef<Path>>(objects_dir: P) -> Self {
+        Self::with_format(objects_dir, ObjectFormat::Sha1)
+    }
+
+    /// Creates a new LooseObjectStore whose objects are named with the
+    /// given hash function.
+    ///
+    /// # Arguments
+    ///
+    /// * `objects_dir` - Path to the `.git/objects` directory.
+    /// * `format` - The object format of the repository.
+    pub fn with_format<P: AsRef<Path>>(objects_dir: P, format: ObjectFormat) -> Self {
         LooseObjectStore {
             objects_dir: objects_dir.as_ref().to_path_buf(),
+            format,
+            cache: None,
+            memory: None,
+            fsync: Arc::default(),
+            alternates: OnceLock::new(),
+        }
+    }
+
+    /// Creates a store that keeps its objects in memory.
+    ///
+    /// Stores created from the same `objects` share their contents. The
+    /// store has no objects directory and never touches the file system.
+    pub(crate) fn in_memory(objects: Arc<MemoryObjects>, format: ObjectFormat) -> Self {
+        LooseObjectStore {
+            objects_dir: PathBuf::new(),
+            format,
+            cache: None,
+            memory: Some(objects),
+            fsync: Arc::default(),
+            alternates: OnceLock::from(Vec::new()),
+        }
+    }
+
+    /// Returns `true` if the store keeps its objects in memory.
+    pub fn is_in_memory(&self) -> bool {
+        self.memory.is_some()
+    }
+
+    /// Reads objects through a cache shared with other stores.
+    pub(crate) fn with_cache(mut self, cache: Arc<ObjectCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Flushes written objects as the repository's `core.fsync` settings
+    /// ask, instead of always flushing each object.
+    pub(crate) fn with_fsync(mut self, fsync: Arc<Fsync>) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// Returns the object format of the store.
+    pub fn format(&self) -> ObjectFormat {
+        self.format
+    }
+
+    /// Returns the path of the objects directory.
+    ///
+    /// The path is empty for a store held in memory.
+    pub fn objects_dir(&self) -> &Path {
+        &self.objects_dir
+    }
+
+    /// Returns the alternate object directories the store borrows objects
+    /// from.
+    ///
+    /// The directories are read from `objects/info/alternates`, following
+    /// the alternates files of each alternate up to five levels deep, and
+    /// from `GIT_ALTERNATE_OBJECT_DIRECTORIES`, which comes first. They
+    /// are resolved once per store, the first time they are needed.
+    ///
+    /// # Returns
+    ///
+    /// The canonical paths of the directories in lookup order, without
+    /// repeats and without ones that do not exist.
+    pub fn alternates(&self) -> Vec<&Path> {
+        self.alternate_stores()
+            .iter()
+            .map(|store| store.objects_dir())
+            .collect()
+    }
+
+    /// Returns the read-only stores of the alternate object directories,
+    /// sharing this store's cache.
+    fn alternate_stores(&self) -> &[LooseObjectStore] {
+        self.alternates.get_or_init(|| {
+            let env = std::env::var_os(ALTERNATES_ENV);
+            alternates::resolve(&self.objects_dir, env.as_deref())
+                .into_iter()
+                .map(|dir| LooseObjectStore {
+                    objects_dir: dir,
+                    format: self.format,
+                    cache: self.cache.clone(),
+                    memory: None,
+                    fsync: Arc::clone(&self.fsync),
+                    // Nested alternates are already in the resolved list
+                    alternates: OnceLock::from(Vec::new()),
+                })
+                .collect()
+        })
+    }
+
+    /// Looks an object up in the alternate object directories, in order.
+    ///
+    /// # Returns
+    ///
+    /// The result from the first alternate that has the object, or
+    /// `Error::ObjectNotFound` if none does.
+    fn read_alternate<T>(
+        &self,
+        oid: &Oid,
+        read: impl Fn(&LooseObjectStore) -> Result<T>,
+    ) -> Result<T> {
+        for store in self.alternate_stores() {
+            match read(store) {
+                Err(Error::ObjectNotFound(_)) => continue,
+                result => return result,
+            }
+        }
+        Err(Error::ObjectNotFound(oid.to_hex()))
+    }
+
+    /// Opens the commit-graph of the object database.
+    ///
+    /// # Returns
+    ///
+    /// The commit-graph, or `None` if there is none, it cannot be read,
+    /// or the store is held in memory.
+    pub(crate) fn commit_graph(&self) -> Option<CommitGraph> {
+        if self.memory.is_some() {
+            return None;
         }
+        CommitGraph::open(&self.objects_dir, self.format)
+            .ok()
+            .flatten()
     }
 
     /// Converts an Oid to the path of its loose object file.
@@ -108,8 +345,29 @@ impl LooseObjectStore {
                 reason: "missing null byte in header".to_string(),
             })?;
 
-        // Parse the header
-        let header = std::str::from_utf8(&data[..null_pos]).map_err(|_| Error::InvalidObject {
+        let (object_type, size) = Self::parse_header(&data[..null_pos], oid)?;
+
+        let content = &data[null_pos + 1..];
+        if content.len() as u64 != size {
+            return Err(Error::InvalidObject {
+                oid: oid.to_hex(),
+                reason: format!(
+                    "size mismatch: header says {} but content is {} bytes",
+                    size,
+                    content.len()
+                ),
+            });
+        }
+
+        Ok(RawObject {
+            object_type,
+            content: content.to_vec(),
+        })
+    }
+
+    /// Parses an object header (`<type> <size>`, without the null byte).
+    fn parse_header(header: &[u8], oid: &Oid) -> Result<(ObjectType, u64)> {
+        let header = std::str::from_utf8(header).map_err(|_| Error::InvalidObject {
             oid: oid.to_hex(),
             reason: "invalid UTF-8 in header".to_string(),
         })?;
@@ -132,28 +390,13 @@ impl LooseObjectStore {
             reason: format!("unknown object type: {}", type_str),
         })?;
 
-        // Parse and validate the size
-        let size: usize = size_str.parse().map_err(|_| Error::InvalidObject {
+        // Parse the size
+        let size: u64 = size_str.parse().map_err(|_| Error::InvalidObject {
             oid: oid.to_hex(),
             reason: format!("invalid size: {}", size_str),
         })?;
 
-        let content = &data[null_pos + 1..];
-        if content.len() != size {
-            return Err(Error::InvalidObject {
-                oid: oid.to_hex(),
-                reason: format!(
-                    "size mismatch: header says {} but content is {} bytes",
-                    size,
-                    content.len()
-                ),
-            });
-        }
-
-        Ok(RawObject {
-            object_type,
-            content: content.to_vec(),
-        })
+        Ok((object_type, size))
     }
 
     /// Reads and parses a Git object by its Oid.
@@ -167,11 +410,458 @@ impl LooseObjectStore {
     /// The parsed object on success, or an error if the object cannot be read
     /// or is invalid.
     pub fn read(&self, oid: &Oid) -> Result<RawObject> {
-        let compressed = self.read_raw(oid)?;
+        if let Some(raw) = self.cache.as_ref().and_then(|cache| cache.raw(oid)) {
+            return Ok(raw);
+        }
+        let raw = self.read_uncached(oid)?;
+        if let Some(cache) = &self.cache {
+            cache.insert_raw(*oid, &raw);
+        }
+        Ok(raw)
+    }
+
+    /// Reads and parses a tree.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::ObjectNotFound` if the object does not exist.
+    /// - `Error::TypeMismatch` if the object is not a tree.
+    /// - `Error::InvalidObject` if the tree is malformed.
+    pub fn read_tree(&self, oid: &Oid) -> Result<Tree> {
+        if let Some(tree) = self.cache.as_ref().and_then(|cache| cache.tree(oid)) {
+            return Ok(tree);
+        }
+        let raw = self.read_uncached(oid)?;
+        if raw.object_type != ObjectType::Tree {
+            return Err(Error::TypeMismatch {
+                expected: "tree",
+                actual: raw.object_type.as_str(),
+            });
+        }
+        let content_len = raw.content.len();
+        let tree = Tree::parse_with_format(raw, oid.format())?;
+        if let Some(cache) = &self.cache {
+            cache.insert_tree(*oid, &tree, content_len);
+        }
+        Ok(tree)
+    }
+
+    /// Reads and parses a commit.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::ObjectNotFound` if the object does not exist.
+    /// - `Error::TypeMismatch` if the object is not a commit.
+    /// - `Error::InvalidObject` if the commit is malformed.
+    pub fn read_commit(&self, oid: &Oid) -> Result<Commit> {
+        if let Some(commit) = self.cache.as_ref().and_then(|cache| cache.commit(oid)) {
+            return Ok(commit);
+        }
+        let raw = self.read_uncached(oid)?;
+        if raw.object_type != ObjectType::Commit {
+            return Err(Error::TypeMismatch {
+                expected: "commit",
+                actual: raw.object_type.as_str(),
+            });
+        }
+        let content_len = raw.content.len();
+        let commit = Commit::parse(*oid, raw)?;
+        if let Some(cache) = &self.cache {
+            cache.insert_commit(&commit, content_len);
+        }
+        Ok(commit)
+    }
+
+    /// Reads an object from the object database, bypassing the object
+    /// cache.
+    fn read_uncached(&self, oid: &Oid) -> Result<RawObject> {
+        if let Some(objects) = &self.memory {
+            return Self::read_memory(objects, oid);
+        }
+        let compressed = match self.read_raw(oid) {
+            Ok(compressed) => compressed,
+            Err(Error::ObjectNotFound(_)) => {
+                return match self.read_packed(oid) {
+                    Err(Error::ObjectNotFound(_)) => {
+                        self.read_alternate(oid, |store| store.read_uncached(oid))
+                    }
+                    result => result,
+                };
+            }
+            Err(e) => return Err(e),
+        };
         let decompressed = decompress(&compressed)?;
         Self::parse_raw_object(&decompressed, oid)
     }
 
+    /// Reads an object held in memory.
+    fn read_memory(objects: &MemoryObjects, oid: &Oid) -> Result<RawObject> {
+        let objects = objects.read().unwrap_or_else(|e| e.into_inner());
+        objects
+            .get(oid)
+            .cloned()
+            .ok_or_else(|| Error::ObjectNotFound(oid.to_hex()))
+    }
+
+    /// Reads an object from the first pack that contains it.
+    fn read_packed(&self, oid: &Oid) -> Result<RawObject> {
+        self.find_packed(oid, |pack, offset| pack.read_at_offset(offset))?
+            .ok_or_else(|| Error::ObjectNotFound(oid.to_hex()))
+    }
+
+    /// Finds the pack that holds an object and reads the object with
+    /// `read`, given the pack and the object's offset in it.
+    ///
+    /// If the object is not found, or its pack has been removed, the
+    /// packs are listed again and, if they changed, searched once more.
+    ///
+    /// # Returns
+    ///
+    /// What `read` returns, or `None` if no pack holds the object.
+    fn find_packed<T>(
+        &self,
+        oid: &Oid,
+        read: impl Fn(&Pack, u64) -> Result<T>,
+    ) -> Result<Option<T>> {
+        let packs = self.pack_list(false)?;
+        let found = self.search_packs(&packs, oid, &read);
+        match &found {
+            Ok(None) => {}
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound => {}
+            _ => return found,
+        }
+        let fresh = self.pack_list(true)?;
+        if fresh.same_packs(&packs) {
+            return found;
+        }
+        self.search_packs(&fresh, oid, &read)
+    }
+
+    /// Searches a list of packs for an object and reads it with `read`.
+    ///
+    /// The multi-pack-index is searched first; only the packs it does not
+    /// cover are searched one by one.
+    fn search_packs<T>(
+        &self,
+        packs: &PackList,
+        oid: &Oid,
+        read: &impl Fn(&Pack, u64) -> Result<T>,
+    ) -> Result<Option<T>> {
+        let (midx, uncovered) = packs.lookup();
+        if let Some(midx) = midx {
+            if let Some((name, offset)) = midx.find(oid)? {
+                if let Some(pack) = packs.get(name) {
+                    return read(pack, offset).map(Some);
+                }
+            }
+        }
+        for pack in uncovered {
+            if let Some(offset) = pack.find_offset(oid)? {
+                return read(&pack, offset).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the packs in the `pack` directory and the multi-pack-index.
+    ///
+    /// With an object cache, they are opened once and shared by every
+    /// store on the cache. The packs are listed again when the
+    /// modification time of the `pack` directory changes or `refresh` is
+    /// set, keeping the packs that are already open. The multi-pack-index
+    /// is opened again only if the directory changed, as writing one
+    /// changes it.
+    fn pack_list(&self, refresh: bool) -> Result<Arc<PackList>> {
+        if self.memory.is_some() {
+            return Ok(Arc::default());
+        }
+        let mtime = fs::metadata(self.objects_dir.join("pack"))
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let cached = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.pack_list(&self.objects_dir));
+        if let Some(cached) = cached
+            .as_ref()
+            .filter(|list| !refresh && list.mtime == mtime)
+        {
+            return Ok(Arc::clone(cached));
+        }
+
+        let mut packs = Vec::new();
+        for path in self.pack_index_paths()? {
+            let name = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            let pack = match cached.as_ref().and_then(|list| list.get(&name)) {
+                Some(pack) => Arc::clone(pack),
+                None => Arc::new(self.open_pack(&path)?),
+            };
+            packs.push((name, pack));
+        }
+        let mut list = PackList {
+            mtime,
+            packs,
+            midx: None,
+        };
+        // Writing a multi-pack-index changes the directory, so one already
+        // open is kept while the directory is unchanged
+        let open_midx = || {
+            MultiPackIndex::open(self.objects_dir.join("pack/multi-pack-index"), self.format)
+                .ok()
+                .map(Arc::new)
+        };
+        list.midx = cached
+            .as_ref()
+            .filter(|cached| cached.mtime == mtime)
+            .and_then(|cached| cached.midx.clone())
+            .filter(|midx| list.covers(midx))
+            .or_else(|| (!list.packs.is_empty()).then(open_midx).flatten())
+            .filter(|midx| list.covers(midx));
+        let list = Arc::new(list);
+        if let Some(cache) = &self.cache {
+            cache.insert_pack_list(&self.objects_dir, Arc::clone(&list));
+        }
+        Ok(list)
+    }
+
+    /// Returns the multi-pack-index lookups go through, if there is a
+    /// valid one whose packs all exist.
+    ///
+    /// Stores on the same object cache share one open multi-pack-index.
+    pub fn multi_pack_index(&self) -> Result<Option<Arc<MultiPackIndex>>> {
+        Ok(self.pack_list(false)?.midx.clone())
+    }
+
+    /// Opens the packs in the `pack` directory.
+    ///
+    /// Index files without a matching `.pack` file are skipped.
+    ///
+    /// # Returns
+    ///
+    /// The packs, sorted by file name.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidPack` if an index is invalid.
+    pub fn packs(&self) -> Result<Vec<Pack>> {
+        self.pack_index_paths()?
+            .iter()
+            .map(|path| self.open_pack(path))
+            .collect()
+    }
+
+    /// Closes the packs kept open in the object cache, so that they can be
+    /// deleted; they are opened again by the next packed read.
+    pub(crate) fn close_packs(&self) {
+        if let Some(cache) = &self.cache {
+            cache.remove_pack_list(&self.objects_dir);
+        }
+    }
+
+    /// Opens a pack by its index file, sharing the store's delta base
+    /// cache.
+    fn open_pack<P: AsRef<Path>>(&self, index_path: P) -> Result<Pack> {
+        let pack = Pack::open(index_path, self.format)?;
+        Ok(match &self.cache {
+            Some(cache) => pack.with_cache(Arc::clone(cache)),
+            None => pack,
+        })
+    }
+
+    /// Lists the `.idx` files in the `pack` directory that have a matching
+    /// `.pack` file, sorted.
+    fn pack_index_paths(&self) -> Result<Vec<PathBuf>> {
+        if self.memory.is_some() {
+            return Ok(Vec::new());
+        }
+        let entries = match fs::read_dir(self.objects_dir.join("pack")) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut index_paths = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "idx")
+                && path.with_extension("pack").is_file()
+            {
+                index_paths.push(path);
+            }
+        }
+        index_paths.sort();
+        Ok(index_paths)
+    }
+
+    /// Opens a Git object for streaming by its Oid.
+    ///
+    /// Only the header is read up front; the content is decompressed as
+    /// it is read from the returned reader, so objects of any size can be
+    /// read in constant memory. Packed objects stored as deltas are the
+    /// exception: their delta chain is resolved in memory first.
+    ///
+    /// # Arguments
+    ///
+    /// * `oid` - The object ID to read.
+    ///
+    /// # Returns
+    ///
+    /// A reader over the object content.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::ObjectNotFound` if the object does not exist.
+    /// - `Error::DecompressionFailed` if the header cannot be decompressed.
+    /// - `Error::InvalidObject` if the header is invalid.
+    pub fn read_stream(&self, oid: &Oid) -> Result<ObjectReader> {
+        if let Some(objects) = &self.memory {
+            return Ok(ObjectReader::from_object(
+                *oid,
+                Self::read_memory(objects, oid)?,
+            ));
+        }
+        let file = match self.open(oid) {
+            Ok(file) => file,
+            Err(Error::ObjectNotFound(_)) => {
+                // Whole packed entries stream from the pack; only deltas
+                // are resolved in memory
+                let packed = self.find_packed(oid, |pack, offset| {
+                    Ok(match pack.stream_at_offset(offset)? {
+                        Some((object_type, size, inner)) => {
+                            ObjectReader::new(*oid, object_type, size, Box::new(inner))
+                        }
+                        None => ObjectReader::from_object(*oid, pack.read_at_offset(offset)?),
+                    })
+                })?;
+                return match packed {
+                    Some(reader) => Ok(reader),
+                    None => self.read_alternate(oid, |store| store.read_stream(oid)),
+                };
+            }
+            Err(e) => return Err(e),
+        };
+        let mut inner = ZlibReader::new(file);
+        let (object_type, size) = Self::read_header_from(&mut inner, oid)?;
+
+        Ok(ObjectReader::new(*oid, object_type, size, Box::new(inner)))
+    }
+
+    /// Reads the type and size of a Git object by its Oid.
+    ///
+    /// Only the few bytes needed for the header are read and
+    /// decompressed, so this is cheap even for very large objects.
+    ///
+    /// # Arguments
+    ///
+    /// * `oid` - The object ID to read.
+    ///
+    /// # Returns
+    ///
+    /// The object type and the content size in bytes.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::ObjectNotFound` if the object does not exist.
+    /// - `Error::DecompressionFailed` if the header cannot be decompressed.
+    /// - `Error::InvalidObject` if the header is invalid.
+    pub fn read_header(&self, oid: &Oid) -> Result<(ObjectType, u64)> {
+        if let Some(objects) = &self.memory {
+            let objects = objects.read().unwrap_or_else(|e| e.into_inner());
+            return objects
+                .get(oid)
+                .map(|object| (object.object_type, object.content.len() as u64))
+                .ok_or_else(|| Error::ObjectNotFound(oid.to_hex()));
+        }
+        let file = match self.open(oid) {
+            Ok(file) => file,
+            Err(Error::ObjectNotFound(_)) => {
+                let packed =
+                    self.find_packed(oid, |pack, offset| pack.read_header_at_offset(offset))?;
+                return match packed {
+                    Some(header) => Ok(header),
+                    None => self.read_alternate(oid, |store| store.read_header(oid)),
+                };
+            }
+            Err(e) => return Err(e),
+        };
+        let mut inner = ZlibReader::with_capacity(file, HEADER_READ_SIZE);
+        Self::read_header_from(&mut inner, oid)
+    }
+
+    /// Finds the object a packed object is stored as a delta against
+    /// (`%(deltabase)` of `git cat-file --batch-check`).
+    ///
+    /// # Returns
+    ///
+    /// The ID of the delta base, or `None` if the object is loose or
+    /// stored whole.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::ObjectNotFound` if the object does not exist.
+    /// - `Error::InvalidPack` if the pack entry or the reverse index of its
+    ///   pack is invalid.
+    pub fn delta_base(&self, oid: &Oid) -> Result<Option<Oid>> {
+        if let Some(objects) = &self.memory {
+            let objects = objects.read().unwrap_or_else(|e| e.into_inner());
+            return match objects.contains_key(oid) {
+                true => Ok(None),
+                false => Err(Error::ObjectNotFound(oid.to_hex())),
+            };
+        }
+        if self.oid_to_path(oid).exists() {
+            return Ok(None);
+        }
+        match self.find_packed(oid, |pack, offset| pack.delta_base_at_offset(offset))? {
+            Some(base) => Ok(base),
+            None => self.read_alternate(oid, |store| store.delta_base(oid)),
+        }
+    }
+
+    /// Opens the loose object file for an object.
+    fn open(&self, oid: &Oid) -> Result<File> {
+        File::open(self.oid_to_path(oid)).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::ObjectNotFound(oid.to_hex()),
+            _ => Error::Io(e),
+        })
+    }
+
+    /// Reads and parses the header of an object from its decompressed
+    /// stream, leaving the stream at the start of the content.
+    fn read_header_from<R: Read>(
+        inner: &mut ZlibReader<R>,
+        oid: &Oid,
+    ) -> Result<(ObjectType, u64)> {
+        let mut header = Vec::new();
+        let mut byte = [0u8; 1];
+        loop {
+            let n = inner
+                .read(&mut byte)
+                .map_err(|_| Error::DecompressionFailed)?;
+            if n == 0 {
+                return Err(Error::InvalidObject {
+                    oid: oid.to_hex(),
+                    reason: "missing null byte in header".to_string(),
+                });
+            }
+            if byte[0] == 0 {
+                break;
+            }
+            if header.len() == MAX_HEADER_LEN {
+                return Err(Error::InvalidObject {
+                    oid: oid.to_hex(),
+                    reason: "header too long".to_string(),
+                });
+            }
+            header.push(byte[0]);
+        }
+        Self::parse_header(&header, oid)
+    }
+
     /// Checks if an object exists in the store.
     ///
     /// # Arguments
@@ -180,14 +870,42 @@ impl LooseObjectStore {
     ///
     /// # Returns
     ///
-    /// `true` if the object exists, `false` otherwise.
+    /// `true` if the object exists, loose or packed, here or in an
+    /// alternate object directory, `false` otherwise.
     pub fn exists(&self, oid: &Oid) -> bool {
+        self.exists_locally(oid)
+            || self
+                .alternate_stores()
+                .iter()
+                .any(|store| store.exists_locally(oid))
+    }
+
+    /// Checks if an object exists in the store itself, ignoring the
+    /// alternate object directories.
+    ///
+    /// # Arguments
+    ///
+    /// * `oid` - The object ID to check.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the object exists loose or packed in this store's objects
+    /// directory, `false` otherwise.
+    pub fn exists_locally(&self, oid: &Oid) -> bool {
+        if let Some(objects) = &self.memory {
+            let objects = objects.read().unwrap_or_else(|e| e.into_inner());
+            return objects.contains_key(oid);
+        }
         self.oid_to_path(oid).exists()
+            || self
+                .find_packed(oid, |_, _| Ok(()))
+                .is_ok_and(|found| found.is_some())
     }
 
     /// Finds objects whose Oid starts with the given prefix.
     ///
-    /// This is used to resolve abbreviated SHA-1 hashes.
+    /// This is used to resolve abbreviated SHA-1 hashes. Objects in the
+    /// alternate object directories are included.
     ///
     /// # Arguments
     ///
@@ -202,7 +920,7 @@ impl LooseObjectStore {
             return Err(Error::InvalidOid(prefix.to_string()));
         }
 
-        if prefix.len() > OID_HEX_LEN {
+        if prefix.len() > self.format.hex_len() {
             return Err(Error::InvalidOid(prefix.to_string()));
         }
 
@@ -212,6 +930,11 @@ impl LooseObjectStore {
         }
 
         let prefix_lower = prefix.to_lowercase();
+        if self.memory.is_some() {
+            let mut matches = self.list_loose_objects()?;
+            matches.retain(|oid| oid.to_hex().starts_with(&prefix_lower));
+            return Ok(matches);
+        }
         let dir_prefix = &prefix_lower[..2];
         let file_prefix = if prefix_lower.len() > 2 {
             &prefix_lower[2..]
@@ -219,37 +942,117 @@ impl LooseObjectStore {
             ""
         };
 
+        let mut matches = Vec::new();
+
         let subdir = self.objects_dir.join(dir_prefix);
-        if !subdir.exists() {
-            return Ok(Vec::new());
+        if subdir.exists() {
+            let entries = fs::read_dir(&subdir)?;
+            for entry in entries {
+                let entry = entry?;
+                let file_name = entry.file_name();
+                let name = file_name.to_string_lossy();
+
+                if name.starts_with(file_prefix) {
+                    let full_hex = format!("{}{}", dir_prefix, name);
+                    if full_hex.len() == self.format.hex_len() {
+                        if let Ok(oid) = Oid::from_hex(&full_hex) {
+                            matches.push(oid);
+                        }
+                    }
+                }
+            }
         }
 
-        let mut matches = Vec::new();
+        let pack_list = self.pack_list(false)?;
+        let (midx, packs) = pack_list.lookup();
+        let mut packed = match &midx {
+            Some(midx) => midx.find_by_prefix(&prefix_lower)?,
+            None => Vec::new(),
+        };
+        for pack in packs {
+            packed.extend(pack.find_by_prefix(&prefix_lower)?);
+        }
+        for store in self.alternate_stores() {
+            packed.extend(store.find_objects_by_prefix(&prefix_lower)?);
+        }
+        for oid in packed {
+            if !matches.contains(&oid) {
+                matches.push(oid);
+            }
+        }
 
-        let entries = fs::read_dir(&subdir)?;
-        for entry in entries {
-            let entry = entry?;
-            let file_name = entry.file_name();
-            let name = file_name.to_string_lossy();
-
-            if name.starts_with(file_prefix) {
-                let full_hex = format!("{}{}", dir_prefix, name);
-                if full_hex.len() == OID_HEX_LEN {
-                    if let Ok(oid) = Oid::from_hex(&full_hex) {
-                        matches.push(oid);
+        Ok(matches)
+    }
+
+    /// Lists every object in the store, loose or packed.
+    ///
+    /// Objects in the alternate object directories are not listed.
+    ///
+    /// # Returns
+    ///
+    /// The Oids of all objects, sorted and without duplicates.
+    pub fn list_objects(&self) -> Result<Vec<Oid>> {
+        let mut oids = self.list_loose_objects()?;
+        let pack_list = self.pack_list(false)?;
+        let (midx, packs) = pack_list.lookup();
+        if let Some(midx) = midx {
+            oids.extend(midx.oids()?);
+        }
+        for pack in packs {
+            oids.extend(pack.oids()?);
+        }
+        oids.sort();
+        oids.dedup();
+        Ok(oids)
+    }
+
+    /// Lists every loose object in the store.
+    ///
+    /// Files whose names are not object IDs of the store's format (such as
+    /// temporary files) are skipped, as are the `info` and `pack`
+    /// directories.
+    ///
+    /// # Returns
+    ///
+    /// The Oids of all loose objects, sorted.
+    pub fn list_loose_objects(&self) -> Result<Vec<Oid>> {
+        if let Some(objects) = &self.memory {
+            let objects = objects.read().unwrap_or_else(|e| e.into_inner());
+            let mut oids: Vec<Oid> = objects.keys().copied().collect();
+            oids.sort();
+            return Ok(oids);
+        }
+        let mut oids = Vec::new();
+        if !self.objects_dir.is_dir() {
+            return Ok(oids);
+        }
+
+        for dir in fs::read_dir(&self.objects_dir)? {
+            let dir = dir?;
+            let dir_name = dir.file_name();
+            let dir_name = dir_name.to_string_lossy();
+            if dir_name.len() != 2 || !dir.file_type()?.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(dir.path())? {
+                let hex = format!("{}{}", dir_name, entry?.file_name().to_string_lossy());
+                if hex.len() == self.format.hex_len() {
+                    if let Ok(oid) = Oid::from_hex(&hex) {
+                        oids.push(oid);
                     }
                 }
             }
         }
 
-        Ok(matches)
+        oids.sort();
+        Ok(oids)
     }
 
     /// Writes a Git object to the store.
     ///
     /// This function:
     /// 1. Creates the raw object data with Git header: `<type> <size>\0<content>`
-    /// 2. Computes the SHA-1 hash of the raw data
+    /// 2. Computes the hash of the raw data in the store's object format
     /// 3. Compresses the data using zlib
     /// 4. Writes to the correct path based on the hash
     ///
@@ -262,35 +1065,337 @@ impl LooseObjectStore {
     ///
     /// The Oid of the written object.
     pub fn write(&self, object_type: ObjectType, content: &[u8]) -> Result<Oid> {
+        self.write_object(object_type, content, true)
+    }
+
+    /// Writes a Git object to the store as a loose object, even if a pack
+    /// already contains it.
+    ///
+    /// This keeps an object available when the pack holding it is about
+    /// to be deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `object_type` - The type of object (blob, tree, commit, tag).
+    /// * `content` - The content of the object.
+    ///
+    /// # Returns
+    ///
+    /// The Oid of the written object.
+    pub fn write_loose(&self, object_type: ObjectType, content: &[u8]) -> Result<Oid> {
+        self.write_object(object_type, content, false)
+    }
+
+    /// Writes a loose object unless it already exists loose or, with
+    /// `check_packs`, in a pack.
+    fn write_object(
+        &self,
+        object_type: ObjectType,
+        content: &[u8],
+        check_packs: bool,
+    ) -> Result<Oid> {
         // Create the raw object data with header
         let header = format!("{} {}\0", object_type.as_str(), content.len());
         let mut raw = header.into_bytes();
         raw.extend_from_slice(content);
 
         // Compute the hash
-        let hash = hash_object(object_type.as_str(), content);
-        let oid = Oid::from_bytes(hash);
+        let hash = hash_object_with(self.format, object_type.as_str(), content)?;
+        let oid = Oid::from_slice(&hash)?;
+
+        if let Some(objects) = &self.memory {
+            let mut objects = objects.write().unwrap_or_else(|e| e.into_inner());
+            objects.entry(oid).or_insert_with(|| RawObject {
+                object_type,
+                content: content.to_vec(),
+            });
+            return Ok(oid);
+        }
 
         // Check if object already exists (idempotent)
         let path = self.oid_to_path(&oid);
-        if path.exists() {
+        if path.exists() || (check_packs && self.exists(&oid)) {
             return Ok(oid);
         }
 
         // Compress the data
         let compressed = compress(&raw);
 
-        // Write to the object store
-        write_file_atomic(&path, &compressed)?;
+        // Write to the object store, read-only as Git does
+        self.fsync
+            .write_file(FsyncComponent::LooseObject, &path, &compressed, true)?;
 
         Ok(oid)
     }
+
+    /// Writes a Git object to the store from a reader.
+    ///
+    /// The content is hashed and compressed while it is copied into a
+    /// temporary file, which is then renamed into place. Unlike
+    /// [`LooseObjectStore::write`], the content is never held in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `object_type` - The type of object (blob, tree, commit, tag).
+    /// * `size` - The size of the content, needed for the object header.
+    /// * `reader` - The reader providing exactly `size` bytes of content.
+    ///
+    /// # Returns
+    ///
+    /// The Oid of the written object.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if reading or writing fails, or if the reader
+    /// provides more or fewer than `size` bytes.
+    pub fn write_stream<R: Read>(
+        &self,
+        object_type: ObjectType,
+        size: u64,
+        reader: R,
+    ) -> Result<Oid> {
+        static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        if self.memory.is_some() {
+            let mut content = Vec::new();
+            reader
+                .take(size.saturating_add(1))
+                .read_to_end(&mut content)?;
+            if content.len() as u64 != size {
+                return Err(Error::Io(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("expected {} bytes of content, got {}", size, content.len()),
+                )));
+            }
+            return self.write(object_type, &content);
+        }
+
+        fs::create_dir_all(&self.objects_dir)?;
+        let temp_path = self.objects_dir.join(format!(
+            "tmp_obj_{}_{}",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let (oid, file) = match self.write_temp(&temp_path, object_type, size, reader) {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                return Err(e);
+            }
+        };
+
+        // Keep an existing copy of the object (idempotent)
+        if self.exists(&oid) {
+            drop(file);
+            fs::remove_file(&temp_path)?;
+        } else {
+            let path = self.oid_to_path(&oid);
+            let persisted =
+                self.fsync
+                    .persist(FsyncComponent::LooseObject, file, &temp_path, &path, true);
+            if let Err(e) = persisted {
+                let _ = fs::remove_file(&temp_path);
+                return Err(e);
+            }
+        }
+
+        Ok(oid)
+    }
+
+    /// Hashes and compresses an object into a temporary file.
+    ///
+    /// # Returns
+    ///
+    /// The Oid of the object and the temporary file, not yet flushed.
+    fn write_temp<R: Read>(
+        &self,
+        temp_path: &Path,
+        object_type: ObjectType,
+        size: u64,
+        mut reader: R,
+    ) -> Result<(Oid, File)> {
+        let mut writer = ZlibWriter::new(File::create(temp_path)?);
+        writer.write_all(format!("{} {}\0", object_type.as_str(), size).as_bytes())?;
+
+        let mut hasher = ObjectHasher::with_format(self.format, object_type.as_str(), size);
+        let mut buffer = vec![0u8; STREAM_BUFFER_SIZE];
+        let mut copied = 0u64;
+        loop {
+            let n = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            copied += n as u64;
+            if copied > size {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+            writer.write_all(&buffer[..n])?;
+        }
+        if copied != size {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("expected {} bytes of content, got {}", size, copied),
+            )));
+        }
+
+        let file = writer.finish()?;
+        Ok((Oid::from_slice(&hasher.finalize()?)?, file))
+    }
+}
+
+/// A reader over the content of an object.
+///
+/// Created by [`LooseObjectStore::read_stream`]. The content of a loose
+/// object or of a whole pack entry is decompressed as it is read; a
+/// deltified packed object is resolved up front. Either way it is hashed
+/// along the way; once the last
+/// byte has been read, the hash is checked against the object ID and an
+/// `io::ErrorKind::InvalidData` error is returned if they differ or the
+/// content does not match the size in the header.
+pub struct ObjectReader {
+    oid: Oid,
+    object_type: ObjectType,
+    size: u64,
+    remaining: u64,
+    hasher: Option<ObjectHasher>,
+    inner: Box<dyn Read + Send>,
+}
+
+impl ObjectReader {
+    /// Creates a reader over the content read from `inner`, which must
+    /// yield exactly `size` bytes.
+    fn new(oid: Oid, object_type: ObjectType, size: u64, inner: Box<dyn Read + Send>) -> Self {
+        ObjectReader {
+            oid,
+            object_type,
+            size,
+            remaining: size,
+            hasher: Some(ObjectHasher::with_format(
+                oid.format(),
+                object_type.as_str(),
+                size,
+            )),
+            inner,
+        }
+    }
+
+    /// Creates a reader over an object that is already in memory.
+    fn from_object(oid: Oid, object: RawObject) -> Self {
+        let size = object.content.len() as u64;
+        ObjectReader::new(
+            oid,
+            object.object_type,
+            size,
+            Box::new(io::Cursor::new(object.content)),
+        )
+    }
+
+    /// Returns the ID of the object being read.
+    pub fn oid(&self) -> &Oid {
+        &self.oid
+    }
+
+    /// Returns the type of the object.
+    pub fn object_type(&self) -> ObjectType {
+        self.object_type
+    }
+
+    /// Returns the size of the content in bytes, from the object header.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Converts an error from reading an object back into the error it
+    /// carries, such as `Error::InvalidObject` for corrupt content.
+    pub(crate) fn into_error(e: io::Error) -> Error {
+        if !e.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            return Error::Io(e);
+        }
+        match e.into_inner().map(|inner| inner.downcast::<Error>()) {
+            Some(Ok(error)) => *error,
+            _ => Error::Io(io::ErrorKind::InvalidData.into()),
+        }
+    }
+
+    /// Returns an `InvalidData` error for this object.
+    fn invalid(&self, reason: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            Error::InvalidObject {
+                oid: self.oid.to_hex(),
+                reason: reason.to_string(),
+            },
+        )
+    }
+
+    /// Checks that the content ends here and hashes to the object ID.
+    fn verify(&mut self) -> io::Result<()> {
+        let Some(hasher) = self.hasher.take() else {
+            return Ok(());
+        };
+        if self.inner.read(&mut [0u8; 1])? != 0 {
+            return Err(self.invalid("content is longer than the header says"));
+        }
+        match hasher.finalize() {
+            Ok(hash) if hash == self.oid.as_bytes() => {}
+            Ok(hash) => {
+                let actual = Oid::from_slice(&hash)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                return Err(self.invalid(&format!("hash mismatch: content hashes to {}", actual)));
+            }
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+        Ok(())
+    }
+}
+
+impl Read for ObjectReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let limit = buf
+            .len()
+            .min(self.remaining.min(usize::MAX as u64) as usize);
+        if limit == 0 {
+            if !buf.is_empty() {
+                self.verify()?;
+            }
+            return Ok(0);
+        }
+
+        let n = self.inner.read(&mut buf[..limit])?;
+        if n == 0 {
+            return Err(self.invalid("content is shorter than the header says"));
+        }
+        self.remaining -= n as u64;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..n]);
+        }
+        if self.remaining == 0 {
+            self.verify()?;
+        }
+        Ok(n)
+    }
+}
+
+impl std::fmt::Debug for ObjectReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectReader")
+            .field("oid", &self.oid)
+            .field("object_type", &self.object_type)
+            .field("size", &self.size)
+            .finish_non_exhaustive()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::infra::hash_object;
+    use crate::infra::hash::hash_object;
+    use crate::objects::PackWriter;
+    use crate::test_util::noise;
     use miniz_oxide::deflate::compress_to_vec_zlib;
     use tempfile::TempDir;
 
@@ -302,7 +1407,7 @@ mod tests {
         raw.extend_from_slice(content);
 
         // Hash it
-        let oid = Oid::from_bytes(hash_object(object_type, content));
+        let oid = Oid::from_bytes(hash_object(object_type, content).unwrap());
 
         // Compress it
         let compressed = compress_to_vec_zlib(&raw, 6);
@@ -612,4 +1717,390 @@ mod tests {
         let obj = store.read(&oid).unwrap();
         assert_eq!(obj.content, content);
     }
+
+    // S-017: write_stream() and read_stream() match write() and read()
+    #[test]
+    fn test_stream_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = LooseObjectStore::new(temp_dir.path().join("objects"));
+
+        let content: Vec<u8> = (0..300_000u32).map(|i| (i % 253) as u8).collect();
+        let oid = store
+            .write_stream(ObjectType::Blob, content.len() as u64, content.as_slice())
+            .unwrap();
+        assert_eq!(oid, Oid::from_bytes(hash_object("blob", &content).unwrap()));
+        assert_eq!(store.read(&oid).unwrap().content, content);
+
+        // Writing the same content again keeps the object and no temp files
+        store
+            .write_stream(ObjectType::Blob, content.len() as u64, content.as_slice())
+            .unwrap();
+        let leftovers = fs::read_dir(temp_dir.path().join("objects"))
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().path().is_file())
+            .count();
+        assert_eq!(leftovers, 0);
+
+        let mut reader = store.read_stream(&oid).unwrap();
+        assert_eq!(reader.object_type(), ObjectType::Blob);
+        assert_eq!(reader.size(), content.len() as u64);
+        let mut read_back = Vec::new();
+        reader.read_to_end(&mut read_back).unwrap();
+        assert_eq!(read_back, content);
+    }
+
+    // S-018: write_stream() rejects a reader of the wrong size
+    #[test]
+    fn test_write_stream_size_mismatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = LooseObjectStore::new(temp_dir.path().join("objects"));
+
+        assert!(matches!(
+            store.write_stream(ObjectType::Blob, 10, &b"short"[..]),
+            Err(Error::Io(_))
+        ));
+        assert!(matches!(
+            store.write_stream(ObjectType::Blob, 2, &b"too long"[..]),
+            Err(Error::Io(_))
+        ));
+        let entries = fs::read_dir(temp_dir.path().join("objects")).unwrap();
+        assert_eq!(entries.count(), 0);
+    }
+
+    // S-019: read_stream() verifies the hash at the end of the content
+    #[test]
+    fn test_read_stream_detects_corruption() {
+        let temp_dir = TempDir::new().unwrap();
+        let objects_dir = temp_dir.path().join("objects");
+        let oid = create_loose_object(&objects_dir, b"original", "blob");
+        let store = LooseObjectStore::new(&objects_dir);
+
+        // Replace the object with different content under the same name
+        let path = store.oid_to_path(&oid);
+        fs::write(&path, compress_to_vec_zlib(b"blob 8\0tampered", 6)).unwrap();
+
+        let mut reader = store.read_stream(&oid).unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        assert!(matches!(
+            store.read_stream(&Oid::from_bytes([1; 20])),
+            Err(Error::ObjectNotFound(_))
+        ));
+    }
+
+    // S-020: read_header() decompresses only the header
+    #[test]
+    fn test_read_header() {
+        let temp_dir = TempDir::new().unwrap();
+        let objects_dir = temp_dir.path().join("objects");
+        let store = LooseObjectStore::new(&objects_dir);
+
+        let tree = create_loose_object(&objects_dir, b"", "tree");
+        assert_eq!(store.read_header(&tree).unwrap(), (ObjectType::Tree, 0));
+
+        // Incompressible content, with the end of the stream cut off
+        let content = noise(100_000, 1);
+        let oid = create_loose_object(&objects_dir, &content, "blob");
+        let path = store.oid_to_path(&oid);
+        let compressed = fs::read(&path).unwrap();
+        fs::write(&path, &compressed[..compressed.len() / 2]).unwrap();
+
+        assert_eq!(
+            store.read_header(&oid).unwrap(),
+            (ObjectType::Blob, content.len() as u64)
+        );
+        assert!(store.read(&oid).is_err());
+
+        assert!(matches!(
+            store.read_header(&Oid::from_bytes([1; 20])),
+            Err(Error::ObjectNotFound(_))
+        ));
+    }
+
+    // S-021: list_objects() returns every loose object, sorted
+    #[test]
+    fn test_list_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        let objects_dir = temp_dir.path().join("objects");
+        let store = LooseObjectStore::new(&objects_dir);
+        assert!(store.list_objects().unwrap().is_empty());
+
+        let mut oids = vec![
+            store.write(ObjectType::Blob, b"one").unwrap(),
+            store.write(ObjectType::Blob, b"two").unwrap(),
+            store.write(ObjectType::Tree, b"").unwrap(),
+        ];
+        oids.sort();
+        fs::create_dir_all(objects_dir.join("pack")).unwrap();
+        let first_dir = store.oid_to_path(&oids[0]).parent().unwrap().to_path_buf();
+        fs::write(first_dir.join("tmp_obj_123"), b"junk").unwrap();
+
+        assert_eq!(store.list_objects().unwrap(), oids);
+    }
+
+    // S-022: Packed objects are found by every lookup, and never rewritten loose
+    #[test]
+    fn test_packed_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        let objects_dir = temp_dir.path().join("objects");
+        let store = LooseObjectStore::new(&objects_dir);
+        let loose = store.write(ObjectType::Blob, b"loose").unwrap();
+
+        let mut writer = PackWriter::new(ObjectFormat::Sha1);
+        let packed = writer.add(ObjectType::Blob, b"packed".to_vec()).unwrap();
+        writer.write(objects_dir.join("pack")).unwrap();
+
+        assert!(store.exists(&packed));
+        assert_eq!(store.read(&packed).unwrap().content, b"packed");
+        assert_eq!(store.read_header(&packed).unwrap(), (ObjectType::Blob, 6));
+        let mut content = Vec::new();
+        store
+            .read_stream(&packed)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, b"packed");
+        assert_eq!(
+            store.find_objects_by_prefix(&packed.to_hex()[..7]).unwrap(),
+            vec![packed]
+        );
+
+        let mut all = vec![loose, packed];
+        all.sort();
+        assert_eq!(store.list_objects().unwrap(), all);
+        assert_eq!(store.list_loose_objects().unwrap(), vec![loose]);
+
+        assert_eq!(store.write(ObjectType::Blob, b"packed").unwrap(), packed);
+        assert!(!store.oid_to_path(&packed).exists());
+    }
+
+    // S-023: An in-memory store reads, writes and lists objects without files
+    #[test]
+    fn test_in_memory_store() {
+        let objects = Arc::new(MemoryObjects::default());
+        let store = LooseObjectStore::in_memory(Arc::clone(&objects), ObjectFormat::Sha1);
+        assert!(store.is_in_memory());
+        assert_eq!(store.objects_dir(), Path::new(""));
+
+        let oid = store.write(ObjectType::Blob, b"hello").unwrap();
+        assert_eq!(oid.to_hex(), "b6fc4c620b67d95f953a5c1c1230aaab5db5a1b0");
+        let streamed = store
+            .write_stream(ObjectType::Blob, 5, &b"world"[..])
+            .unwrap();
+        assert!(store
+            .write_stream(ObjectType::Blob, 4, &b"world"[..])
+            .is_err());
+
+        // Stores over the same objects see each other's writes
+        let other = LooseObjectStore::in_memory(objects, ObjectFormat::Sha1);
+        assert_eq!(other.read(&oid).unwrap().content, b"hello");
+        assert_eq!(other.read_header(&streamed).unwrap(), (ObjectType::Blob, 5));
+        let mut content = Vec::new();
+        other
+            .read_stream(&streamed)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, b"world");
+
+        let mut oids = vec![oid, streamed];
+        oids.sort();
+        assert_eq!(other.list_objects().unwrap(), oids);
+        assert_eq!(other.find_objects_by_prefix("b6fc").unwrap(), vec![oid]);
+        assert!(other.packs().unwrap().is_empty());
+        assert!(other.commit_graph().is_none());
+        assert!(matches!(
+            LooseObjectStore::in_memory(Default::default(), ObjectFormat::Sha1).read(&oid),
+            Err(Error::ObjectNotFound(_))
+        ));
+    }
+
+    // S-024: Objects in alternates are read and found, but never listed or written
+    #[test]
+    fn test_alternates() {
+        let temp_dir = TempDir::new().unwrap();
+        let shared = LooseObjectStore::new(temp_dir.path().join("shared"));
+        let borrowed = shared.write(ObjectType::Blob, b"borrowed").unwrap();
+        let objects_dir = temp_dir.path().join("objects");
+        fs::create_dir_all(objects_dir.join("info")).unwrap();
+        fs::write(objects_dir.join("info/alternates"), "../shared\n").unwrap();
+
+        let store = LooseObjectStore::new(&objects_dir);
+        let local = store.write(ObjectType::Blob, b"local").unwrap();
+        assert_eq!(
+            store.alternates(),
+            [fs::canonicalize(shared.objects_dir()).unwrap()]
+        );
+        assert_eq!(store.read(&borrowed).unwrap().content, b"borrowed");
+        assert_eq!(store.read_header(&borrowed).unwrap(), (ObjectType::Blob, 8));
+        let mut content = Vec::new();
+        store
+            .read_stream(&borrowed)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, b"borrowed");
+        assert!(store.exists(&borrowed));
+        assert!(!store.exists_locally(&borrowed));
+        assert_eq!(
+            store
+                .find_objects_by_prefix(&borrowed.to_hex()[..4])
+                .unwrap(),
+            vec![borrowed]
+        );
+        assert_eq!(store.list_objects().unwrap(), vec![local]);
+
+        // Writing a borrowed object keeps it borrowed
+        assert_eq!(
+            store.write(ObjectType::Blob, b"borrowed").unwrap(),
+            borrowed
+        );
+        assert!(!store.oid_to_path(&borrowed).exists());
+        assert!(!shared.exists(&local));
+    }
+
+    // S-025: read_stream() inflates whole packed entries as they are read
+    #[test]
+    fn test_read_stream_packed() {
+        let temp_dir = TempDir::new().unwrap();
+        let objects_dir = temp_dir.path().join("objects");
+        let store = LooseObjectStore::new(&objects_dir);
+        let big = noise(256 << 10, 1);
+        let mut edited = big.clone();
+        edited[1000] ^= 1;
+
+        let mut writer = PackWriter::new(ObjectFormat::Sha1);
+        let whole = writer.add(ObjectType::Blob, big.clone()).unwrap();
+        let delta = writer.add(ObjectType::Blob, edited.clone()).unwrap();
+        let pack = writer.write(objects_dir.join("pack")).unwrap();
+        let offset = |oid: &Oid| pack.find_offset(oid).unwrap().unwrap();
+        let whole_offset = offset(&whole);
+        let stored = [whole_offset, offset(&delta)]
+            .map(|offset| pack.stream_at_offset(offset).unwrap().is_some());
+        assert_eq!(stored.iter().filter(|&&s| s).count(), 1);
+
+        for (oid, content) in [(whole, &big), (delta, &edited)] {
+            let mut reader = store.read_stream(&oid).unwrap();
+            assert_eq!(reader.size(), content.len() as u64);
+            let mut read = Vec::new();
+            reader.read_to_end(&mut read).unwrap();
+            assert_eq!(&read, content);
+        }
+
+        // With the pack cut short after the start of the stored entry, its
+        // first bytes can still be read before the stream fails
+        let (stored_oid, stored_offset, content) = if stored[0] {
+            (whole, whole_offset, &big)
+        } else {
+            (delta, offset(&delta), &edited)
+        };
+        let data = fs::read(pack.pack_path()).unwrap();
+        fs::remove_file(pack.pack_path()).unwrap();
+        fs::write(
+            pack.pack_path(),
+            &data[..stored_offset as usize + (128 << 10)],
+        )
+        .unwrap();
+        let mut reader = store.read_stream(&stored_oid).unwrap();
+        let mut start = vec![0u8; 32 << 10];
+        reader.read_exact(&mut start).unwrap();
+        assert_eq!(start, content[..32 << 10]);
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    // S-026: Packs are opened once per cache and kept open across listings
+    #[test]
+    fn test_pack_reuse() {
+        use crate::objects::CacheOptions;
+
+        let temp_dir = TempDir::new().unwrap();
+        let objects_dir = temp_dir.path().join("objects");
+        let write_pack = |content: &[u8]| {
+            let mut writer = PackWriter::new(ObjectFormat::Sha1);
+            let oid = writer.add(ObjectType::Blob, content.to_vec()).unwrap();
+            (oid, writer.write(objects_dir.join("pack")).unwrap())
+        };
+        let (first, first_pack) = write_pack(b"first");
+        let (second, _) = write_pack(b"second");
+        let open_packs = |store: &LooseObjectStore| -> Vec<Arc<Pack>> {
+            let list = store.pack_list(false).unwrap();
+            list.packs
+                .iter()
+                .map(|(_, pack)| Arc::clone(pack))
+                .collect()
+        };
+
+        // Every store on the cache shares the open packs
+        let cache = Arc::new(ObjectCache::new(CacheOptions::default()));
+        let store = LooseObjectStore::new(&objects_dir).with_cache(Arc::clone(&cache));
+        for oid in [first, second] {
+            assert_eq!(store.read_header(&oid).unwrap().0, ObjectType::Blob);
+        }
+        let other = LooseObjectStore::new(&objects_dir).with_cache(Arc::clone(&cache));
+        let opened = open_packs(&store);
+        assert_eq!(opened.len(), 2);
+        assert!(opened
+            .iter()
+            .zip(open_packs(&other))
+            .all(|(pack, shared)| Arc::ptr_eq(pack, &shared)));
+
+        // A new pack is found, and the packs already open are kept
+        let (third, _) = write_pack(b"third");
+        assert_eq!(store.read(&third).unwrap().content, b"third");
+        let listed = open_packs(&other);
+        assert_eq!(listed.len(), 3);
+        assert!(opened
+            .iter()
+            .all(|pack| listed.iter().any(|listed| Arc::ptr_eq(pack, listed))));
+
+        // An open pack keeps its files open, so it is still read after
+        // they are removed
+        let pack = opened
+            .iter()
+            .find(|pack| pack.index_path() == first_pack.index_path())
+            .unwrap();
+        fs::remove_file(first_pack.index_path()).unwrap();
+        fs::remove_file(first_pack.pack_path()).unwrap();
+        assert_eq!(pack.read(&first).unwrap().unwrap().content, b"first");
+
+        // Once the packs are listed again, the removed pack is dropped
+        let missing = Oid::from_hex("0000000000000000000000000000000000000001").unwrap();
+        assert!(matches!(
+            store.read_header(&missing),
+            Err(Error::ObjectNotFound(_))
+        ));
+        assert!(matches!(
+            store.read_header(&first),
+            Err(Error::ObjectNotFound(_))
+        ));
+        assert_eq!(other.read_header(&second).unwrap(), (ObjectType::Blob, 6));
+        assert_eq!(open_packs(&other).len(), 2);
+    }
+
+    // S-027: delta_base() names the base of deltified packed objects only
+    #[test]
+    fn test_delta_base() {
+        let temp_dir = TempDir::new().unwrap();
+        let objects_dir = temp_dir.path().join("objects");
+        let store = LooseObjectStore::new(&objects_dir);
+        let base: Vec<u8> = (0..4_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let mut edited = base.clone();
+        edited[2_000] ^= 1;
+
+        let mut writer = PackWriter::new(ObjectFormat::Sha1);
+        let base_oid = writer.add(ObjectType::Blob, base).unwrap();
+        let edited_oid = writer.add(ObjectType::Blob, edited).unwrap();
+        writer.write(objects_dir.join("pack")).unwrap();
+        let loose = store.write(ObjectType::Blob, b"loose").unwrap();
+
+        assert_eq!(store.delta_base(&edited_oid).unwrap(), Some(base_oid));
+        assert_eq!(store.delta_base(&base_oid).unwrap(), None);
+        assert_eq!(store.delta_base(&loose).unwrap(), None);
+        let missing = Oid::from_hex("0000000000000000000000000000000000000001").unwrap();
+        assert!(matches!(
+            store.delta_base(&missing),
+            Err(Error::ObjectNotFound(_))
+        ));
+    }
 }
@@ -4,6 +4,7 @@ use super::commit::Signature;
 use super::oid::Oid;
 use super::store::{ObjectType, RawObject};
 use crate::error::{Error, Result};
+use crate::infra::encoding;
 
 /// An annotated tag object.
 ///
@@ -27,6 +28,8 @@ pub struct TagObject {
     tagger: Signature,
     /// The tag message.
     message: String,
+    /// The encoding named by the `encoding` header, if any.
+    encoding: Option<String>,
 }
 
 impl TagObject {
@@ -41,6 +44,10 @@ impl TagObject {
     ///
     /// <message>
     /// ```
+    ///
+    /// The tagger and message are decoded according to the `encoding`
+    /// header (UTF-8 if there is none); bytes that cannot be decoded are
+    /// replaced rather than rejected.
     pub fn parse(raw: RawObject) -> Result<Self> {
         if raw.object_type != ObjectType::Tag {
             return Err(Error::TypeMismatch {
@@ -49,7 +56,8 @@ impl TagObject {
             });
         }
 
-        let content = std::str::from_utf8(&raw.content).map_err(|_| Error::InvalidUtf8)?;
+        let header_encoding = encoding::header_encoding(&raw.content);
+        let content = encoding::decode(&raw.content, header_encoding);
 
         let mut object: Option<Oid> = None;
         let mut object_type: Option<String> = None;
@@ -111,6 +119,7 @@ impl TagObject {
             tag_name,
             tagger,
             message,
+            encoding: header_encoding.map(str::to_string),
         })
     }
 
@@ -139,6 +148,24 @@ impl TagObject {
         &self.message
     }
 
+    /// Returns the encoding named by the tag's `encoding` header, if any.
+    ///
+    /// [`message`](TagObject::message) is already decoded from it.
+    pub fn encoding(&self) -> Option<&str> {
+        self.encoding.as_deref()
+    }
+
+    /// Returns `false` if the `encoding` header names an encoding that
+    /// cannot be decoded exactly, in which case
+    /// [`message`](TagObject::message) was decoded lossily as UTF-8.
+    ///
+    /// UTF-8, ISO-8859-1, ISO-8859-15 and Windows-1252 are supported.
+    pub fn is_encoding_supported(&self) -> bool {
+        self.encoding
+            .as_deref()
+            .map_or(true, encoding::is_supported)
+    }
+
     /// Returns the first line of the tag message.
     pub fn summary(&self) -> &str {
         self.message.lines().next().unwrap_or("")
@@ -317,4 +344,21 @@ mod tests {
         let result = TagObject::parse(raw);
         assert!(matches!(result, Err(Error::InvalidObject { .. })));
     }
+
+    #[test]
+    fn test_parse_encoding() {
+        let mut content = format!("object {}\ntype commit\ntag v1.0.0\n", OBJECT_SHA).into_bytes();
+        content.extend_from_slice(b"tagger Jos\xe9 <jose@example.com> 1234567890 +0000\n");
+        content.extend_from_slice(b"encoding latin1\n\nVersi\xf3n 1.0");
+        let raw = RawObject {
+            object_type: ObjectType::Tag,
+            content,
+        };
+        let tag = TagObject::parse(raw).unwrap();
+
+        assert_eq!(tag.encoding(), Some("latin1"));
+        assert_eq!(tag.tagger().name(), "José");
+        assert_eq!(tag.message(), "Versión 1.0");
+        assert!(tag.is_encoding_supported());
+    }
 }
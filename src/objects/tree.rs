@@ -1,8 +1,13 @@
 //! Git tree object implementation.
 
-use super::oid::{Oid, OID_BYTES};
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+use super::oid::Oid;
 use super::store::{ObjectType, RawObject};
+use super::ObjectFormat;
 use crate::error::{Error, Result};
+use crate::infra::path;
 
 /// File mode for tree entries.
 ///
@@ -70,8 +75,8 @@ impl FileMode {
 pub struct TreeEntry {
     /// The file mode of the entry.
     mode: FileMode,
-    /// The name of the entry (file or directory name).
-    name: String,
+    /// The name of the entry (file or directory name), as raw bytes.
+    name: Vec<u8>,
     /// The object ID that this entry points to.
     oid: Oid,
 }
@@ -83,10 +88,27 @@ impl TreeEntry {
     }
 
     /// Returns the name of the entry.
-    pub fn name(&self) -> &str {
+    ///
+    /// Names that are not valid UTF-8 are converted lossily; use
+    /// [`name_bytes`](TreeEntry::name_bytes) or
+    /// [`name_path`](TreeEntry::name_path) for the exact name.
+    pub fn name(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.name)
+    }
+
+    /// Returns the name of the entry as stored in the tree.
+    pub fn name_bytes(&self) -> &[u8] {
         &self.name
     }
 
+    /// Returns the name of the entry as a path component.
+    ///
+    /// On Unix the conversion is lossless, whatever the encoding of the
+    /// name.
+    pub fn name_path(&self) -> PathBuf {
+        path::from_bytes(&self.name)
+    }
+
     /// Returns the object ID of the entry.
     pub fn oid(&self) -> &Oid {
         &self.oid
@@ -114,7 +136,7 @@ pub struct Tree {
 }
 
 impl Tree {
-    /// Parses a Tree from a RawObject.
+    /// Parses a Tree from a RawObject of a SHA-1 repository.
     ///
     /// Tree objects have a binary format where each entry is:
     /// `<mode> <name>\0<20-byte-sha1>`
@@ -127,6 +149,23 @@ impl Tree {
     ///
     /// The parsed Tree on success, or an error if parsing fails.
     pub fn parse(raw: RawObject) -> Result<Self> {
+        Self::parse_with_format(raw, ObjectFormat::Sha1)
+    }
+
+    /// Parses a Tree from a RawObject, with object IDs of the given format.
+    ///
+    /// Each entry ends with the raw object ID: 20 bytes for SHA-1, 32 for
+    /// SHA-256. A tree's entries use the same format as the tree itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw` - The raw object to parse.
+    /// * `format` - The object format of the repository.
+    ///
+    /// # Returns
+    ///
+    /// The parsed Tree on success, or an error if parsing fails.
+    pub fn parse_with_format(raw: RawObject, format: ObjectFormat) -> Result<Self> {
         if raw.object_type != ObjectType::Tree {
             return Err(Error::TypeMismatch {
                 expected: "tree",
@@ -169,29 +208,21 @@ impl Tree {
                 }
             })?;
 
-            let name_bytes = &content[pos..pos + null_pos];
-            let name = std::str::from_utf8(name_bytes)
-                .map_err(|_| Error::InvalidObject {
-                    oid: String::new(),
-                    reason: "invalid UTF-8 in entry name".to_string(),
-                })?
-                .to_string();
+            let name = content[pos..pos + null_pos].to_vec();
 
             pos += null_pos + 1; // Skip name and null byte
 
-            // Read the 20-byte SHA-1
-            if pos + OID_BYTES > content.len() {
-                return Err(Error::InvalidObject {
-                    oid: String::new(),
-                    reason: "truncated SHA-1 in tree entry".to_string(),
-                });
-            }
+            // Read the object ID
+            let oid_bytes =
+                content
+                    .get(pos..pos + format.size())
+                    .ok_or_else(|| Error::InvalidObject {
+                        oid: String::new(),
+                        reason: "truncated object ID in tree entry".to_string(),
+                    })?;
+            let oid = Oid::from_slice(oid_bytes)?;
 
-            let mut oid_bytes = [0u8; OID_BYTES];
-            oid_bytes.copy_from_slice(&content[pos..pos + OID_BYTES]);
-            let oid = Oid::from_bytes(oid_bytes);
-
-            pos += OID_BYTES;
+            pos += format.size();
 
             entries.push(TreeEntry { mode, name, oid });
         }
@@ -218,13 +249,13 @@ impl Tree {
     ///
     /// # Arguments
     ///
-    /// * `name` - The name to search for.
+    /// * `name` - The name to search for (a `&str` or raw bytes).
     ///
     /// # Returns
     ///
     /// The entry if found, or `None` if not found.
-    pub fn get(&self, name: &str) -> Option<&TreeEntry> {
-        self.entries.iter().find(|e| e.name == name)
+    pub fn get<N: AsRef<[u8]>>(&self, name: N) -> Option<&TreeEntry> {
+        self.entries.iter().find(|e| e.name == name.as_ref())
     }
 
     /// Returns an iterator over the entries.
@@ -404,6 +435,48 @@ mod tests {
         assert!(!dir.is_file());
     }
 
+    // T-010: Non-UTF-8 names are kept byte for byte
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_name() {
+        let mut content = b"100644 caf\xe9.txt\0".to_vec();
+        content.extend_from_slice(&SHA1_A);
+        let raw = RawObject {
+            object_type: ObjectType::Tree,
+            content,
+        };
+        let tree = Tree::parse(raw).unwrap();
+
+        let entry = tree.get(b"caf\xe9.txt").unwrap();
+        assert_eq!(entry.name_bytes(), b"caf\xe9.txt");
+        assert_eq!(entry.name(), "caf\u{FFFD}.txt");
+        assert_eq!(
+            crate::infra::path::to_bytes(&entry.name_path()),
+            &b"caf\xe9.txt"[..]
+        );
+        assert!(tree.get("café.txt").is_none());
+    }
+
+    // T-011: SHA-256 trees have 32-byte object IDs
+    #[test]
+    fn test_parse_sha256() {
+        let oid = Oid::from_hex(&"ab".repeat(32)).unwrap();
+        let mut content = b"100644 a.txt\0".to_vec();
+        content.extend_from_slice(oid.as_bytes());
+        content.extend_from_slice(b"40000 dir\0");
+        content.extend_from_slice(oid.as_bytes());
+        let raw = RawObject {
+            object_type: ObjectType::Tree,
+            content,
+        };
+
+        let tree = Tree::parse_with_format(raw.clone(), ObjectFormat::Sha256).unwrap();
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree.get("a.txt").unwrap().oid(), &oid);
+        assert_eq!(tree.get("dir").unwrap().mode(), FileMode::Directory);
+        assert!(Tree::parse(raw).is_err());
+    }
+
     // Additional: Empty tree
     #[test]
     fn test_empty_tree() {
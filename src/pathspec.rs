@@ -0,0 +1,469 @@
+//! Pathspecs: patterns that select paths in the repository.
+//!
+//! A pathspec is a list of patterns, as accepted by `git add`, `git status`,
+//! `git diff` and `git log`. Each pattern matches a path exactly, as a
+//! leading directory, or as a glob. Patterns may start with "magic" that
+//! changes how they match:
+//!
+//! | Long form      | Short form | Meaning                                        |
+//! | -------------- | ---------- | ---------------------------------------------- |
+//! | `:(top)`       | `:/`       | Relative to the repository root                |
+//! | `:(exclude)`   | `:!`, `:^` | Remove matching paths from the result          |
+//! | `:(icase)`     |            | Match case-insensitively                       |
+//! | `:(literal)`   |            | Treat `*`, `?` and `[` literally               |
+//! | `:(glob)`      |            | `*` does not match `/`; `**` matches any depth |
+//!
+//! Paths are always relative to the repository root, so `top` has no
+//! effect.
+
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::ignore::path_to_slash;
+use crate::infra::wildmatch::{wildmatch, WM_CASEFOLD, WM_PATHNAME};
+
+/// A single parsed pathspec pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PathspecItem {
+    /// The pattern as given, including magic (for error messages).
+    original: String,
+    /// The pattern without magic (empty matches everything).
+    pattern: String,
+    /// Length of the leading part of the pattern without glob characters.
+    nowildcard_len: usize,
+    exclude: bool,
+    icase: bool,
+    literal: bool,
+    glob: bool,
+}
+
+impl PathspecItem {
+    /// Parses one pattern and its magic.
+    fn parse(spec: &str) -> Result<Self> {
+        let mut item = PathspecItem {
+            original: spec.to_string(),
+            pattern: String::new(),
+            nowildcard_len: 0,
+            exclude: false,
+            icase: false,
+            literal: false,
+            glob: false,
+        };
+
+        let pattern = if let Some(rest) = spec.strip_prefix(":(") {
+            let (magic, pattern) = rest
+                .split_once(')')
+                .ok_or_else(|| Error::InvalidPathspec(spec.to_string()))?;
+            for word in magic.split(',').map(str::trim).filter(|w| !w.is_empty()) {
+                match word {
+                    "top" => {}
+                    "exclude" => item.exclude = true,
+                    "icase" => item.icase = true,
+                    "literal" => item.literal = true,
+                    "glob" => item.glob = true,
+                    _ => return Err(Error::InvalidPathspec(spec.to_string())),
+                }
+            }
+            pattern
+        } else if let Some(rest) = spec.strip_prefix(':') {
+            let mut rest = rest;
+            loop {
+                match rest.as_bytes().first() {
+                    Some(b'/') => {}
+                    Some(b'!') | Some(b'^') => item.exclude = true,
+                    Some(b':') => {
+                        rest = &rest[1..];
+                        break;
+                    }
+                    _ => break,
+                }
+                rest = &rest[1..];
+            }
+            rest
+        } else {
+            spec
+        };
+
+        if item.literal && item.glob {
+            return Err(Error::InvalidPathspec(spec.to_string()));
+        }
+
+        let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+        item.pattern = if pattern == "." {
+            String::new()
+        } else {
+            pattern.to_string()
+        };
+        item.nowildcard_len = if item.literal {
+            item.pattern.len()
+        } else {
+            item.pattern
+                .find(['*', '?', '[', '\\'])
+                .unwrap_or(item.pattern.len())
+        };
+        Ok(item)
+    }
+
+    /// Returns true if this pattern matches the path.
+    fn matches(&self, path: &str) -> bool {
+        self.match_kind(path).is_some()
+    }
+
+    /// Returns how this pattern matches the path, if it does.
+    fn match_kind(&self, path: &str) -> Option<MatchKind> {
+        if self.pattern.is_empty() {
+            return Some(MatchKind::Recursive);
+        }
+
+        let (pattern, path) = if self.icase {
+            (self.pattern.to_lowercase(), path.to_lowercase())
+        } else {
+            (self.pattern.clone(), path.to_string())
+        };
+
+        // Exact match or leading directory
+        if let Some(rest) = path.strip_prefix(pattern.as_str()) {
+            if rest.is_empty() {
+                return Some(MatchKind::Exact);
+            }
+            if pattern.ends_with('/') || rest.starts_with('/') {
+                return Some(MatchKind::Recursive);
+            }
+        }
+
+        if self.nowildcard_len >= pattern.len() {
+            return None;
+        }
+        if path.as_bytes().get(..self.nowildcard_len)
+            != Some(&pattern.as_bytes()[..self.nowildcard_len])
+        {
+            return None;
+        }
+
+        let mut flags = if self.icase { WM_CASEFOLD } else { 0 };
+        if self.glob {
+            flags |= WM_PATHNAME;
+        }
+        wildmatch(pattern.as_bytes(), path.as_bytes(), flags).then_some(MatchKind::Pattern)
+    }
+}
+
+/// How a pattern matched a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchKind {
+    /// The path is below a directory named by the pattern.
+    Recursive,
+    /// The path matches the pattern as a glob.
+    Pattern,
+    /// The path is exactly the pattern.
+    Exact,
+}
+
+/// A list of patterns selecting paths in the repository.
+///
+/// An empty pathspec matches every path. Otherwise a path matches if it
+/// matches at least one pattern without `exclude` magic (or there are only
+/// `exclude` patterns) and no `exclude` pattern.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use zerogit::Pathspec;
+///
+/// let pathspec = Pathspec::new(["src", "*.md", ":!src/generated"]).unwrap();
+/// assert!(pathspec.matches(Path::new("src/lib.rs")));
+/// assert!(pathspec.matches(Path::new("docs/guide.md")));
+/// assert!(!pathspec.matches(Path::new("src/generated/api.rs")));
+/// assert!(!pathspec.matches(Path::new("Cargo.toml")));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pathspec {
+    /// The parsed patterns.
+    items: Vec<PathspecItem>,
+}
+
+impl Pathspec {
+    /// Parses a list of patterns.
+    ///
+    /// # Arguments
+    ///
+    /// * `specs` - The patterns, with optional magic.
+    ///
+    /// # Returns
+    ///
+    /// The parsed pathspec.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::InvalidPathspec` if a pattern has unknown or conflicting magic.
+    pub fn new<I, S>(specs: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let items = specs
+            .into_iter()
+            .map(|spec| PathspecItem::parse(spec.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Pathspec { items })
+    }
+
+    /// Creates a pathspec matching a single path (and everything below it)
+    /// literally, without glob matching or magic.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path relative to the repository root.
+    pub fn literal<P: AsRef<Path>>(path: P) -> Self {
+        let mut pathspec = Self::default();
+        pathspec.push_literal(path);
+        pathspec
+    }
+
+    /// Adds a literal path pattern.
+    pub(crate) fn push_literal<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path_to_slash(path.as_ref());
+        let mut item = PathspecItem::parse(":(literal)").expect("valid magic");
+        item.original = path.clone();
+        item.nowildcard_len = path.len();
+        item.pattern = path;
+        self.items.push(item);
+    }
+
+    /// Returns the patterns as given, with their magic.
+    pub(crate) fn originals(&self) -> impl Iterator<Item = &str> {
+        self.items.iter().map(|item| item.original.as_str())
+    }
+
+    /// Adds the patterns of another pathspec.
+    pub(crate) fn extend(&mut self, other: Pathspec) {
+        self.items.extend(other.items);
+    }
+
+    /// Returns the patterns as plain paths, if every pattern selects a path
+    /// and everything below it: no wildcards, `exclude` or `icase`.
+    ///
+    /// Changed-path Bloom filters can only be checked for such pathspecs.
+    pub(crate) fn literal_paths(&self) -> Option<Vec<String>> {
+        self.items
+            .iter()
+            .map(|item| {
+                let path = item.pattern.trim_end_matches('/');
+                let plain = !item.exclude
+                    && !item.icase
+                    && !path.is_empty()
+                    && item.nowildcard_len >= item.pattern.len();
+                plain.then(|| path.to_string())
+            })
+            .collect()
+    }
+
+    /// Returns true if the pathspec has no patterns (and matches everything).
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns true if the path is selected by this pathspec.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path relative to the repository root.
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.items.is_empty() {
+            return true;
+        }
+
+        let path = path_to_slash(path);
+        let mut has_positive = false;
+        let mut included = false;
+        for item in &self.items {
+            if item.exclude {
+                if item.matches(&path) {
+                    return false;
+                }
+            } else {
+                has_positive = true;
+                included = included || item.matches(&path);
+            }
+        }
+        included || !has_positive
+    }
+
+    /// Returns the first non-exclude pattern that matches none of the paths.
+    pub(crate) fn unmatched<'a, I>(&self, paths: I) -> Option<&str>
+    where
+        I: IntoIterator<Item = &'a Path>,
+    {
+        let mut matched = vec![false; self.items.len()];
+        for path in paths {
+            let path = path_to_slash(path);
+            for (item, matched) in self.items.iter().zip(matched.iter_mut()) {
+                *matched = *matched || item.matches(&path);
+            }
+        }
+        self.items
+            .iter()
+            .zip(matched)
+            .find(|(item, matched)| !item.exclude && !matched)
+            .map(|(item, _)| item.original.as_str())
+    }
+
+    /// Returns the first non-exclude pattern that only matches the paths
+    /// as a leading directory, i.e. would need a recursive operation.
+    pub(crate) fn recursive_only<'a, I>(&self, paths: I) -> Option<&str>
+    where
+        I: IntoIterator<Item = &'a Path>,
+    {
+        let mut best: Vec<Option<MatchKind>> = vec![None; self.items.len()];
+        for path in paths {
+            let path = path_to_slash(path);
+            for (item, best) in self.items.iter().zip(best.iter_mut()) {
+                *best = (*best).max(item.match_kind(&path));
+            }
+        }
+        self.items
+            .iter()
+            .zip(best)
+            .find(|(item, best)| !item.exclude && *best == Some(MatchKind::Recursive))
+            .map(|(item, _)| item.original.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ps(specs: &[&str]) -> Pathspec {
+        Pathspec::new(specs).unwrap()
+    }
+
+    fn m(pathspec: &Pathspec, path: &str) -> bool {
+        pathspec.matches(Path::new(path))
+    }
+
+    // PS-001: Exact paths and leading directories
+    #[test]
+    fn test_exact_and_directory() {
+        let p = ps(&["src", "README"]);
+        assert!(m(&p, "src"));
+        assert!(m(&p, "src/lib.rs"));
+        assert!(m(&p, "src/a/b.rs"));
+        assert!(!m(&p, "srcx/lib.rs"));
+        assert!(m(&p, "README"));
+        assert!(!m(&p, "README.md"));
+
+        let p = ps(&["docs/"]);
+        assert!(m(&p, "docs/a.md"));
+        assert!(!m(&p, "docs"));
+    }
+
+    // PS-002: Empty pathspecs and "." match everything
+    #[test]
+    fn test_match_all() {
+        assert!(m(&Pathspec::default(), "any/path"));
+        assert!(m(&ps(&["."]), "any/path"));
+        assert!(m(&ps(&[""]), "any/path"));
+        assert!(m(&ps(&["./src"]), "src/lib.rs"));
+    }
+
+    // PS-003: Globs ('*' matches '/' unless glob magic)
+    #[test]
+    fn test_glob() {
+        let p = ps(&["*.md"]);
+        assert!(m(&p, "README.md"));
+        assert!(m(&p, "docs/guide.md"));
+        assert!(!m(&p, "main.rs"));
+
+        let p = ps(&["src/*.rs"]);
+        assert!(m(&p, "src/lib.rs"));
+        assert!(m(&p, "src/a/mod.rs"));
+
+        let p = ps(&[":(glob)src/*.rs"]);
+        assert!(m(&p, "src/lib.rs"));
+        assert!(!m(&p, "src/a/mod.rs"));
+        let p = ps(&[":(glob)src/**/*.rs"]);
+        assert!(m(&p, "src/a/mod.rs"));
+        assert!(m(&p, "src/lib.rs"));
+    }
+
+    // PS-004: Literal magic and literal file names with glob characters
+    #[test]
+    fn test_literal() {
+        let p = ps(&[":(literal)*.md"]);
+        assert!(m(&p, "*.md"));
+        assert!(!m(&p, "README.md"));
+
+        // A literal prefix match wins even for glob patterns
+        assert!(m(&ps(&["a[1]"]), "a[1]"));
+        assert!(m(&Pathspec::literal("f*"), "f*"));
+        assert!(!m(&Pathspec::literal("f*"), "foo"));
+    }
+
+    // PS-005: Exclude magic in long and short form
+    #[test]
+    fn test_exclude() {
+        let p = ps(&["src", ":!src/gen"]);
+        assert!(m(&p, "src/lib.rs"));
+        assert!(!m(&p, "src/gen/api.rs"));
+        assert!(!m(&p, "docs/a.md"));
+
+        // Only excludes: everything else matches
+        let p = ps(&[":^*.log"]);
+        assert!(m(&p, "a.txt"));
+        assert!(!m(&p, "debug.log"));
+
+        let p = ps(&[":(exclude,icase)*.LOG"]);
+        assert!(!m(&p, "debug.log"));
+        assert!(m(&p, "debug.txt"));
+    }
+
+    // PS-006: icase and top magic
+    #[test]
+    fn test_icase_and_top() {
+        let p = ps(&[":(icase)Docs"]);
+        assert!(m(&p, "docs/a.md"));
+        assert!(m(&p, "DOCS/b.md"));
+        assert!(!m(&ps(&["Docs"]), "docs/a.md"));
+
+        assert!(m(&ps(&[":/src"]), "src/lib.rs"));
+        assert!(m(&ps(&[":(top)src"]), "src/lib.rs"));
+        assert!(m(&ps(&[":/:src"]), "src/lib.rs"));
+    }
+
+    // PS-007: Invalid magic is rejected
+    #[test]
+    fn test_invalid_magic() {
+        assert!(matches!(
+            Pathspec::new([":(bogus)x"]),
+            Err(Error::InvalidPathspec(_))
+        ));
+        assert!(matches!(
+            Pathspec::new([":(literal,glob)x"]),
+            Err(Error::InvalidPathspec(_))
+        ));
+        assert!(matches!(
+            Pathspec::new([":(exclude"]),
+            Err(Error::InvalidPathspec(_))
+        ));
+    }
+
+    // PS-008: Unmatched patterns are reported
+    #[test]
+    fn test_unmatched() {
+        let p = ps(&["src", "missing", ":!docs"]);
+        let paths = [Path::new("src/lib.rs"), Path::new("README")];
+        assert_eq!(p.unmatched(paths), Some("missing"));
+        assert_eq!(ps(&["src"]).unmatched(paths), None);
+    }
+
+    // PS-009: Patterns matching only as a leading directory are reported
+    #[test]
+    fn test_recursive_only() {
+        let paths = [Path::new("src/lib.rs"), Path::new("README")];
+        assert_eq!(ps(&["src"]).recursive_only(paths), Some("src"));
+        assert_eq!(ps(&["."]).recursive_only(paths), Some("."));
+        assert_eq!(ps(&["README", "src/*"]).recursive_only(paths), None);
+        assert_eq!(ps(&[":!src"]).recursive_only(paths), None);
+    }
+}
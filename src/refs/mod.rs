@@ -9,5 +9,6 @@ pub mod tag;
 pub use branch::{Branch, BranchList};
 pub use head::Head;
 pub use remote_branch::RemoteBranch;
-pub use resolver::{RefStore, RefValue, ResolvedRef};
+pub(crate) use resolver::MemoryRefs;
+pub use resolver::{PackedRef, RefStore, RefValue, ResolvedRef};
 pub use tag::Tag;
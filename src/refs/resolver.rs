@@ -1,11 +1,17 @@
 //! Git reference resolution.
 
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 
 use crate::error::{Error, Result};
+use crate::infra::{Fsync, FsyncComponent};
 use crate::objects::Oid;
 
+/// Header written at the top of `packed-refs`, listing its traits.
+const PACKED_REFS_HEADER: &str = "# pack-refs with: peeled fully-peeled sorted \n";
+
 /// The result of resolving a reference.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RefValue {
@@ -24,15 +30,37 @@ pub struct ResolvedRef {
     pub oid: Oid,
 }
 
+/// A reference stored in the `packed-refs` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedRef {
+    /// The name of the reference (e.g., "refs/tags/v1.0").
+    pub name: String,
+    /// The object ID this reference points to.
+    pub oid: Oid,
+    /// For a reference to an annotated tag, the object the tag
+    /// ultimately points to.
+    pub peeled: Option<Oid>,
+}
+
+/// The references of an in-memory repository, `HEAD` included, by name.
+pub(crate) type MemoryRefs = RwLock<BTreeMap<String, RefValue>>;
+
 /// A store for reading and resolving Git references.
 ///
 /// References are stored in the `.git` directory as either:
 /// - Loose refs: Individual files under `.git/refs/`
-/// - Packed refs: A single file `.git/packed-refs` (not yet implemented)
+/// - Packed refs: A single file `.git/packed-refs`
+///
+/// A loose ref takes precedence over a packed ref of the same name.
+/// The references of an in-memory repository are all loose.
 #[derive(Debug)]
 pub struct RefStore {
     /// Path to the `.git` directory.
     git_dir: PathBuf,
+    /// The references, if the store is held in memory instead of on disk.
+    memory: Option<Arc<MemoryRefs>>,
+    /// Flushes written references, and the objects they point to, to disk.
+    fsync: Arc<Fsync>,
 }
 
 impl RefStore {
@@ -44,9 +72,48 @@ impl RefStore {
     pub fn new<P: AsRef<Path>>(git_dir: P) -> Self {
         RefStore {
             git_dir: git_dir.as_ref().to_path_buf(),
+            memory: None,
+            fsync: Arc::default(),
+        }
+    }
+
+    /// Creates a store that keeps its references in memory.
+    ///
+    /// Stores created from the same `refs` share their contents.
+    pub(crate) fn in_memory(refs: Arc<MemoryRefs>) -> Self {
+        RefStore {
+            git_dir: PathBuf::new(),
+            memory: Some(refs),
+            fsync: Arc::default(),
         }
     }
 
+    /// Flushes written references as the repository's `core.fsync`
+    /// settings ask. Loose objects pending a batch flush are flushed
+    /// before a reference is written, so that it never points to an
+    /// object lost in a crash.
+    pub(crate) fn with_fsync(mut self, fsync: Arc<Fsync>) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// Writes the file of a reference or `packed-refs`.
+    fn write_file(&self, path: &Path, content: &str) -> Result<()> {
+        self.fsync.flush_batch()?;
+        self.fsync
+            .write_file(FsyncComponent::Reference, path, content.as_bytes(), false)
+    }
+
+    /// Returns the names of the in-memory references starting with
+    /// `prefix`, with the prefix removed, in sorted order.
+    fn memory_refs(refs: &MemoryRefs, prefix: &str) -> Vec<String> {
+        let refs = refs.read().unwrap_or_else(|e| e.into_inner());
+        refs.keys()
+            .filter_map(|name| name.strip_prefix(prefix))
+            .map(str::to_string)
+            .collect()
+    }
+
     /// Reads the raw content of a reference file.
     ///
     /// # Arguments
@@ -56,16 +123,29 @@ impl RefStore {
     /// # Returns
     ///
     /// The parsed reference value, or an error if the ref doesn't exist.
+    /// A ref without a loose file is looked up in `packed-refs`.
     pub fn read_ref_file(&self, name: &str) -> Result<RefValue> {
+        if let Some(refs) = &self.memory {
+            let refs = refs.read().unwrap_or_else(|e| e.into_inner());
+            return refs
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::RefNotFound(name.to_string()));
+        }
         let ref_path = self.git_dir.join(name);
 
-        let content = fs::read_to_string(&ref_path).map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                Error::RefNotFound(name.to_string())
-            } else {
-                Error::Io(e)
+        let content = match fs::read_to_string(&ref_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return self
+                    .packed_refs()?
+                    .into_iter()
+                    .find(|packed| packed.name == name)
+                    .map(|packed| RefValue::Direct(packed.oid))
+                    .ok_or_else(|| Error::RefNotFound(name.to_string()));
             }
-        })?;
+            Err(e) => return Err(Error::Io(e)),
+        };
 
         let content = content.trim();
 
@@ -155,17 +235,47 @@ impl RefStore {
     ///
     /// A vector of branch names (without the `refs/heads/` prefix).
     pub fn branches(&self) -> Result<Vec<String>> {
-        let heads_dir = self.git_dir.join("refs/heads");
+        self.list_refs("refs/heads")
+    }
 
-        if !heads_dir.exists() {
+    /// Lists every reference under `refs/`, loose or packed.
+    ///
+    /// # Returns
+    ///
+    /// A sorted vector of full reference names (e.g., "refs/heads/main").
+    pub fn references(&self) -> Result<Vec<String>> {
+        let mut refs = self.loose_references()?;
+        refs.extend(self.packed_refs()?.into_iter().map(|packed| packed.name));
+
+        refs.sort();
+        refs.dedup();
+        Ok(refs)
+    }
+
+    /// Lists every loose reference under `refs/`.
+    ///
+    /// # Returns
+    ///
+    /// A sorted vector of full reference names (e.g., "refs/heads/main").
+    pub fn loose_references(&self) -> Result<Vec<String>> {
+        if let Some(refs) = &self.memory {
+            let mut names = Self::memory_refs(refs, "refs/");
+            for name in &mut names {
+                name.insert_str(0, "refs/");
+            }
+            return Ok(names);
+        }
+        let refs_dir = self.git_dir.join("refs");
+
+        if !refs_dir.exists() {
             return Ok(Vec::new());
         }
 
-        let mut branches = Vec::new();
-        Self::collect_refs_recursive(&heads_dir, "", &mut branches)?;
+        let mut refs = Vec::new();
+        Self::collect_refs_recursive(&refs_dir, "refs", &mut refs)?;
 
-        branches.sort();
-        Ok(branches)
+        refs.sort();
+        Ok(refs)
     }
 
     /// Recursively collects reference names from a directory.
@@ -208,17 +318,7 @@ impl RefStore {
     ///
     /// A vector of tag names (without the `refs/tags/` prefix).
     pub fn tags(&self) -> Result<Vec<String>> {
-        let tags_dir = self.git_dir.join("refs/tags");
-
-        if !tags_dir.exists() {
-            return Ok(Vec::new());
-        }
-
-        let mut tags = Vec::new();
-        Self::collect_refs_recursive(&tags_dir, "", &mut tags)?;
-
-        tags.sort();
-        Ok(tags)
+        self.list_refs("refs/tags")
     }
 
     /// Lists all remote names in the repository.
@@ -227,29 +327,46 @@ impl RefStore {
     ///
     /// A vector of remote names (e.g., "origin", "upstream").
     pub fn remotes(&self) -> Result<Vec<String>> {
-        let remotes_dir = self.git_dir.join("refs/remotes");
-
-        if !remotes_dir.exists() {
-            return Ok(Vec::new());
+        if let Some(refs) = &self.memory {
+            let mut remotes: Vec<String> = Self::memory_refs(refs, "refs/remotes/")
+                .into_iter()
+                .filter_map(|name| name.split_once('/').map(|(remote, _)| remote.to_string()))
+                .collect();
+            remotes.dedup();
+            return Ok(remotes);
         }
+        let remotes_dir = self.git_dir.join("refs/remotes");
 
         let mut remotes = Vec::new();
-        let entries = fs::read_dir(&remotes_dir).map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                Error::PathNotFound(remotes_dir.clone())
-            } else {
-                Error::Io(e)
+        if remotes_dir.exists() {
+            let entries = fs::read_dir(&remotes_dir).map_err(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    Error::PathNotFound(remotes_dir.clone())
+                } else {
+                    Error::Io(e)
+                }
+            })?;
+
+            for entry in entries {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    remotes.push(entry.file_name().to_string_lossy().to_string());
+                }
             }
-        })?;
+        }
 
-        for entry in entries {
-            let entry = entry?;
-            if entry.file_type()?.is_dir() {
-                remotes.push(entry.file_name().to_string_lossy().to_string());
+        for packed in self.packed_refs()? {
+            if let Some((remote, _)) = packed
+                .name
+                .strip_prefix("refs/remotes/")
+                .and_then(|name| name.split_once('/'))
+            {
+                remotes.push(remote.to_string());
             }
         }
 
         remotes.sort();
+        remotes.dedup();
         Ok(remotes)
     }
 
@@ -260,36 +377,246 @@ impl RefStore {
     /// A vector of tuples (remote_name, branch_name) without the `refs/remotes/` prefix.
     /// For example: `[("origin", "main"), ("origin", "develop"), ("upstream", "main")]`
     pub fn remote_branches(&self) -> Result<Vec<(String, String)>> {
-        let remotes_dir = self.git_dir.join("refs/remotes");
+        // Names are sorted by full name (remote/branch)
+        let result = self
+            .list_refs("refs/remotes")?
+            .into_iter()
+            .filter_map(|name| {
+                name.split_once('/')
+                    .map(|(remote, branch)| (remote.to_string(), branch.to_string()))
+            })
+            .collect();
+
+        Ok(result)
+    }
+
+    /// Lists the references under a directory such as `refs/heads`, loose
+    /// or packed, without the directory prefix.
+    fn list_refs(&self, dir: &str) -> Result<Vec<String>> {
+        if let Some(refs) = &self.memory {
+            return Ok(Self::memory_refs(refs, &format!("{}/", dir)));
+        }
+        let mut names = Vec::new();
+
+        let loose_dir = self.git_dir.join(dir);
+        if loose_dir.exists() {
+            Self::collect_refs_recursive(&loose_dir, "", &mut names)?;
+        }
 
-        if !remotes_dir.exists() {
+        let prefix = format!("{}/", dir);
+        for packed in self.packed_refs()? {
+            if let Some(name) = packed.name.strip_prefix(&prefix) {
+                names.push(name.to_string());
+            }
+        }
+
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// Reads the `packed-refs` file.
+    ///
+    /// # Returns
+    ///
+    /// The packed references in file order, or an empty vector if there is
+    /// no `packed-refs` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidRefName` if a line cannot be parsed.
+    pub fn packed_refs(&self) -> Result<Vec<PackedRef>> {
+        if self.memory.is_some() {
             return Ok(Vec::new());
         }
+        let content = match fs::read_to_string(self.git_dir.join("packed-refs")) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::Io(e)),
+        };
+
+        let mut refs: Vec<PackedRef> = Vec::new();
+        for line in content.lines() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let unexpected =
+                || Error::InvalidRefName(format!("unexpected line in packed-refs: {}", line));
+
+            // A peeled value belongs to the ref on the line before
+            if let Some(peeled) = line.strip_prefix('^') {
+                let last = refs.last_mut().ok_or_else(unexpected)?;
+                last.peeled = Some(Oid::from_hex(peeled).map_err(|_| unexpected())?);
+                continue;
+            }
 
-        let mut result = Vec::new();
+            let (hex, name) = line.split_once(' ').ok_or_else(unexpected)?;
+            refs.push(PackedRef {
+                name: name.to_string(),
+                oid: Oid::from_hex(hex).map_err(|_| unexpected())?,
+                peeled: None,
+            });
+        }
 
-        // Iterate over each remote directory
-        let remotes = self.remotes()?;
-        for remote in remotes {
-            let remote_dir = remotes_dir.join(&remote);
-            if remote_dir.is_dir() {
-                let mut branches = Vec::new();
-                Self::collect_refs_recursive(&remote_dir, "", &mut branches)?;
+        Ok(refs)
+    }
 
-                for branch in branches {
-                    result.push((remote.clone(), branch));
-                }
+    /// Replaces the `packed-refs` file with the given references.
+    ///
+    /// The references are written sorted by name, and the file is replaced
+    /// atomically so that readers never see a partial file.
+    ///
+    /// # Arguments
+    ///
+    /// * `refs` - The references to pack.
+    pub fn write_packed_refs(&self, refs: &[PackedRef]) -> Result<()> {
+        if self.memory.is_some() {
+            for packed in refs {
+                self.write_ref(&packed.name, &RefValue::Direct(packed.oid))?;
+            }
+            return Ok(());
+        }
+        let mut refs: Vec<&PackedRef> = refs.iter().collect();
+        refs.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut content = String::from(PACKED_REFS_HEADER);
+        for packed in refs {
+            content.push_str(&format!("{} {}\n", packed.oid, packed.name));
+            if let Some(peeled) = &packed.peeled {
+                content.push_str(&format!("^{}\n", peeled));
             }
         }
 
-        // Sort by full name (remote/branch)
-        result.sort_by(|a, b| {
-            let a_full = format!("{}/{}", a.0, a.1);
-            let b_full = format!("{}/{}", b.0, b.1);
-            a_full.cmp(&b_full)
-        });
+        self.write_file(&self.git_dir.join("packed-refs"), &content)
+    }
 
-        Ok(result)
+    /// Removes a reference from the `packed-refs` file.
+    ///
+    /// The other lines of the file, including its header, are kept as
+    /// they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The full reference name (e.g., "refs/heads/old").
+    ///
+    /// # Returns
+    ///
+    /// `true` if the reference was packed and has been removed.
+    pub fn delete_packed_ref(&self, name: &str) -> Result<bool> {
+        if self.memory.is_some() {
+            return Ok(false);
+        }
+        let path = self.git_dir.join("packed-refs");
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(Error::Io(e)),
+        };
+
+        let mut kept = String::new();
+        let mut found = false;
+        let mut removing = false;
+        for line in content.lines() {
+            // Drop the peeled value of the removed ref along with it
+            if removing && line.starts_with('^') {
+                continue;
+            }
+            removing = !line.starts_with('#')
+                && line
+                    .split_once(' ')
+                    .is_some_and(|(_, ref_name)| ref_name == name);
+            if removing {
+                found = true;
+                continue;
+            }
+            kept.push_str(line);
+            kept.push('\n');
+        }
+
+        if found {
+            self.write_file(&path, &kept)?;
+        }
+        Ok(found)
+    }
+
+    /// Checks if a reference exists, loose or packed.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The full reference name (e.g., "refs/heads/main").
+    pub fn ref_exists(&self, name: &str) -> Result<bool> {
+        if let Some(refs) = &self.memory {
+            let refs = refs.read().unwrap_or_else(|e| e.into_inner());
+            return Ok(refs.contains_key(name));
+        }
+        Ok(self.git_dir.join(name).exists()
+            || self.packed_refs()?.iter().any(|packed| packed.name == name))
+    }
+
+    /// Writes a loose reference, replacing any previous value.
+    ///
+    /// The file is replaced atomically, and missing parent directories
+    /// (for nested names like `refs/heads/feature/foo`) are created.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The reference name (e.g., "HEAD" or "refs/heads/main").
+    /// * `value` - The object ID or reference to point to.
+    pub fn write_ref(&self, name: &str, value: &RefValue) -> Result<()> {
+        if let Some(refs) = &self.memory {
+            let mut refs = refs.write().unwrap_or_else(|e| e.into_inner());
+            refs.insert(name.to_string(), value.clone());
+            return Ok(());
+        }
+        let content = match value {
+            RefValue::Direct(oid) => format!("{}\n", oid.to_hex()),
+            RefValue::Symbolic(target) => format!("ref: {}\n", target),
+        };
+        self.write_file(&self.git_dir.join(name), &content)
+    }
+
+    /// Deletes a reference, loose and packed.
+    ///
+    /// The packed copy is removed first so that it does not show through
+    /// once the loose file is gone. Directories left empty by a nested
+    /// name are removed, up to the category directory such as
+    /// `refs/heads`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The full reference name (e.g., "refs/heads/old").
+    ///
+    /// # Returns
+    ///
+    /// `true` if the reference existed and has been deleted.
+    pub fn delete_ref(&self, name: &str) -> Result<bool> {
+        if let Some(refs) = &self.memory {
+            let mut refs = refs.write().unwrap_or_else(|e| e.into_inner());
+            return Ok(refs.remove(name).is_some());
+        }
+
+        let was_packed = self.delete_packed_ref(name)?;
+        let path = self.git_dir.join(name);
+        if !path.exists() {
+            return Ok(was_packed);
+        }
+        fs::remove_file(&path)?;
+
+        let refs_dir = self.git_dir.join("refs");
+        let mut parent = path.parent();
+        while let Some(dir) = parent {
+            if dir == refs_dir || dir.parent() == Some(refs_dir.as_path()) {
+                break;
+            }
+            if dir.read_dir()?.next().is_none() {
+                fs::remove_dir(dir)?;
+            } else {
+                break;
+            }
+            parent = dir.parent();
+        }
+
+        Ok(true)
     }
 
     /// Resolves a reference by name.
@@ -563,4 +890,176 @@ mod tests {
         assert!(branches.contains(&"feature/my-feature".to_string()));
         assert!(branches.contains(&"feature/other".to_string()));
     }
+    // Additional: references() lists every ref with its full name
+    #[test]
+    fn test_references() {
+        let temp = setup_git_dir();
+        let git_dir = temp.path();
+
+        fs::create_dir_all(git_dir.join("refs/remotes/origin")).unwrap();
+        fs::write(git_dir.join("refs/heads/main"), format!("{}\n", TEST_OID)).unwrap();
+        fs::write(git_dir.join("refs/tags/v1.0"), format!("{}\n", TEST_OID2)).unwrap();
+        fs::write(
+            git_dir.join("refs/remotes/origin/main"),
+            format!("{}\n", TEST_OID),
+        )
+        .unwrap();
+
+        let store = RefStore::new(git_dir);
+        assert_eq!(
+            store.references().unwrap(),
+            vec![
+                "refs/heads/main".to_string(),
+                "refs/remotes/origin/main".to_string(),
+                "refs/tags/v1.0".to_string(),
+            ]
+        );
+    }
+
+    // Additional: packed refs are read, listed and shadowed by loose refs
+    #[test]
+    fn test_packed_refs() {
+        let temp = setup_git_dir();
+        let git_dir = temp.path();
+
+        fs::write(
+            git_dir.join("packed-refs"),
+            format!(
+                "# pack-refs with: peeled fully-peeled sorted \n\
+                 {0} refs/heads/main\n\
+                 {0} refs/remotes/origin/main\n\
+                 {1} refs/tags/v1.0\n\
+                 ^{0}\n",
+                TEST_OID, TEST_OID2
+            ),
+        )
+        .unwrap();
+        fs::write(git_dir.join("refs/heads/main"), format!("{}\n", TEST_OID2)).unwrap();
+        fs::write(git_dir.join("refs/heads/loose"), format!("{}\n", TEST_OID)).unwrap();
+
+        let store = RefStore::new(git_dir);
+        let packed = store.packed_refs().unwrap();
+        assert_eq!(packed.len(), 3);
+        assert_eq!(packed[2].name, "refs/tags/v1.0");
+        assert_eq!(
+            packed[2].peeled.map(|oid| oid.to_hex()).as_deref(),
+            Some(TEST_OID)
+        );
+
+        // The loose ref wins over the packed one
+        assert_eq!(store.resolve("main").unwrap().oid.to_hex(), TEST_OID2);
+        assert_eq!(store.resolve("v1.0").unwrap().oid.to_hex(), TEST_OID2);
+        assert_eq!(store.branches().unwrap(), vec!["loose", "main"]);
+        assert_eq!(store.tags().unwrap(), vec!["v1.0"]);
+        assert_eq!(store.remotes().unwrap(), vec!["origin"]);
+        assert_eq!(
+            store.remote_branches().unwrap(),
+            vec![("origin".to_string(), "main".to_string())]
+        );
+        assert_eq!(store.references().unwrap().len(), 4);
+    }
+
+    // Additional: packed refs are written sorted and deleted with their peeled line
+    #[test]
+    fn test_write_and_delete_packed_refs() {
+        let temp = setup_git_dir();
+        let store = RefStore::new(temp.path());
+        let oid = Oid::from_hex(TEST_OID).unwrap();
+        let tag = Oid::from_hex(TEST_OID2).unwrap();
+
+        assert!(!store.delete_packed_ref("refs/heads/main").unwrap());
+        store
+            .write_packed_refs(&[
+                PackedRef {
+                    name: "refs/tags/v1.0".to_string(),
+                    oid: tag,
+                    peeled: Some(oid),
+                },
+                PackedRef {
+                    name: "refs/heads/main".to_string(),
+                    oid,
+                    peeled: None,
+                },
+            ])
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(temp.path().join("packed-refs")).unwrap(),
+            format!(
+                "{}{} refs/heads/main\n{} refs/tags/v1.0\n^{}\n",
+                PACKED_REFS_HEADER, TEST_OID, TEST_OID2, TEST_OID
+            )
+        );
+
+        assert!(store.delete_packed_ref("refs/tags/v1.0").unwrap());
+        assert_eq!(
+            fs::read_to_string(temp.path().join("packed-refs")).unwrap(),
+            format!("{}{} refs/heads/main\n", PACKED_REFS_HEADER, TEST_OID)
+        );
+        assert!(matches!(
+            store.read_ref_file("refs/tags/v1.0"),
+            Err(Error::RefNotFound(_))
+        ));
+    }
+
+    // Additional: in-memory refs are written, listed and deleted without files
+    #[test]
+    fn test_in_memory_refs() {
+        let store = RefStore::in_memory(Default::default());
+        let oid = Oid::from_hex(TEST_OID).unwrap();
+        assert!(matches!(store.head(), Err(Error::RefNotFound(_))));
+
+        store
+            .write_ref("HEAD", &RefValue::Symbolic("refs/heads/main".to_string()))
+            .unwrap();
+        for name in [
+            "refs/heads/main",
+            "refs/heads/feature/x",
+            "refs/tags/v1.0",
+            "refs/remotes/origin/main",
+        ] {
+            store.write_ref(name, &RefValue::Direct(oid)).unwrap();
+        }
+
+        assert_eq!(store.head().unwrap().name, "refs/heads/main");
+        assert_eq!(store.current_branch().unwrap(), Some("main".to_string()));
+        assert_eq!(store.branches().unwrap(), vec!["feature/x", "main"]);
+        assert_eq!(store.tags().unwrap(), vec!["v1.0"]);
+        assert_eq!(store.remotes().unwrap(), vec!["origin"]);
+        assert_eq!(store.references().unwrap().len(), 4);
+        assert!(store.packed_refs().unwrap().is_empty());
+
+        assert!(store.ref_exists("refs/heads/feature/x").unwrap());
+        assert!(store.delete_ref("refs/heads/feature/x").unwrap());
+        assert!(!store.delete_ref("refs/heads/feature/x").unwrap());
+        assert_eq!(store.branches().unwrap(), vec!["main"]);
+    }
+
+    // Additional: refs written to disk get parent directories, and deleting
+    // them cleans up empty directories below the category directory
+    #[test]
+    fn test_write_and_delete_ref() {
+        let temp = setup_git_dir();
+        let store = RefStore::new(temp.path());
+        let oid = Oid::from_hex(TEST_OID).unwrap();
+
+        store
+            .write_ref("refs/heads/feature/x", &RefValue::Direct(oid))
+            .unwrap();
+        store
+            .write_ref(
+                "HEAD",
+                &RefValue::Symbolic("refs/heads/feature/x".to_string()),
+            )
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(temp.path().join("HEAD")).unwrap(),
+            "ref: refs/heads/feature/x\n"
+        );
+        assert_eq!(store.head().unwrap().oid, oid);
+
+        assert!(store.delete_ref("refs/heads/feature/x").unwrap());
+        assert!(!temp.path().join("refs/heads/feature").exists());
+        assert!(temp.path().join("refs/heads").is_dir());
+        assert!(!store.delete_ref("refs/heads/feature/x").unwrap());
+    }
 }
@@ -1,30 +1,126 @@
 //! Git repository operations.
 
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::error::{Error, Result};
-use crate::index::{self, Index, IndexEntry};
-use crate::infra::{read_file, write_file_atomic};
+use crate::history::CommitNodes;
+use crate::index::{self, CacheTree, Index, IndexEntry};
+use crate::infra::{
+    list_working_tree_parallel, parallel_map, path, read_file, write_file_atomic, Fsync,
+    FsyncComponent, FsyncMethod, FsyncSettings, WriteGuard, WriteLock,
+};
 use crate::log::{LogIterator, LogOptions};
 use crate::objects::tree::FileMode;
-use crate::objects::{Blob, Commit, LooseObjectStore, Object, ObjectType, Oid, TagObject, Tree};
-use crate::refs::{Branch, Head, RefStore, RemoteBranch, Tag};
-use crate::status::{compute_status, flatten_tree, StatusEntry};
+use crate::objects::{
+    Blob, CacheOptions, CacheStats, Commit, LooseObjectStore, MemoryObjects, Object, ObjectCache,
+    ObjectFormat, ObjectReader, ObjectType, Oid, TagObject, Tree, DEFAULT_BIG_FILE_THRESHOLD,
+};
+use crate::pathspec::Pathspec;
+use crate::refs::{Branch, Head, MemoryRefs, RefStore, RefValue, RemoteBranch, Tag};
+use crate::sparse::outside_sparse_set;
+use crate::status::{
+    entry_modified, entry_stat_clean, file_modified, flatten_tree, flatten_tree_with_modes,
+    format_porcelain, refresh_index, status_in, BranchInfo, PorcelainOptions, StatusEntry,
+    StatusOptions,
+};
 
 use std::fs;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+
+/// Options for [`Repository::init_with_options`].
+///
+/// # Examples
+///
+/// ```
+/// use zerogit::{InitOptions, ObjectFormat};
+///
+/// let options = InitOptions::new().object_format(ObjectFormat::Sha256);
+/// assert!(!options.get_bare());
+/// assert_eq!(options.get_object_format(), ObjectFormat::Sha256);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InitOptions {
+    /// Create a bare repository.
+    bare: bool,
+    /// The hash function to name objects with.
+    object_format: ObjectFormat,
+}
+
+impl InitOptions {
+    /// Creates a new InitOptions with default settings: a non-bare SHA-1
+    /// repository.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a bare repository (`git init --bare`).
+    pub fn bare(mut self, bare: bool) -> Self {
+        self.bare = bare;
+        self
+    }
+
+    /// Sets the hash function objects are named with
+    /// (`git init --object-format`).
+    ///
+    /// SHA-256 repositories are readable by Git 2.29 and later.
+    pub fn object_format(mut self, format: ObjectFormat) -> Self {
+        self.object_format = format;
+        self
+    }
+
+    /// Returns whether a bare repository is created.
+    pub fn get_bare(&self) -> bool {
+        self.bare
+    }
+
+    /// Returns the object format of the new repository.
+    pub fn get_object_format(&self) -> ObjectFormat {
+        self.object_format
+    }
+}
+
+/// The object database, references and index of a repository held in
+/// memory, shared by every handle.
+#[derive(Debug, Default)]
+struct MemoryBackend {
+    objects: Arc<MemoryObjects>,
+    refs: Arc<MemoryRefs>,
+    /// The index, or `None` until one is written.
+    index: Mutex<Option<Index>>,
+}
 
 /// A Git repository.
 ///
 /// This is the main entry point for interacting with a Git repository.
 /// It provides access to objects, references, and the index.
+///
+/// A `Repository` is `Send` and `Sync`, so one can be shared between
+/// threads behind an `Arc`, or each thread can take its own handle from
+/// [`Repository::clone_handle`]. Reads run concurrently; operations that
+/// change the index, refs or working tree wait for each other.
 #[derive(Debug)]
 pub struct Repository {
     /// The root directory of the working tree.
     work_dir: PathBuf,
     /// The path to the `.git` directory.
     git_dir: PathBuf,
+    /// The hash function objects are named with.
+    object_format: ObjectFormat,
+    /// Recently read objects, shared by every object store opened on the
+    /// repository.
+    cache: Arc<ObjectCache>,
+    /// Held by operations that write, shared by every handle.
+    write_lock: Arc<WriteLock>,
+    /// Flushes written files as `core.fsync` asks, shared by every handle
+    /// so that loose objects pending a batch flush are flushed by the
+    /// operation that wrote them.
+    fsync: Arc<Fsync>,
+    /// The contents of an in-memory repository, which has no `.git`
+    /// directory or working tree.
+    memory: Option<Arc<MemoryBackend>>,
 }
 
 impl Repository {
@@ -61,6 +157,21 @@ impl Repository {
         Ok(())
     }
 
+    /// Reads the object format from `extensions.objectFormat` in the
+    /// repository config, defaulting to SHA-1.
+    fn read_object_format(git_dir: &Path) -> Result<ObjectFormat> {
+        let config_path = git_dir.join("config");
+        if !config_path.is_file() {
+            return Ok(ObjectFormat::Sha1);
+        }
+        let config = crate::config::Config::from_file(config_path)?;
+        match config.get("extensions", "objectFormat") {
+            None => Ok(ObjectFormat::Sha1),
+            Some(name) => ObjectFormat::from_name(&name.to_ascii_lowercase())
+                .ok_or_else(|| Error::UnsupportedObjectFormat(name.to_string())),
+        }
+    }
+
     /// Opens an existing Git repository.
     ///
     /// The path can point to either:
@@ -75,6 +186,12 @@ impl Repository {
     ///
     /// A `Repository` instance, or an error if the path is not a valid Git repository.
     ///
+    /// # Errors
+    ///
+    /// - `Error::NotARepository` if the path is not a valid Git repository.
+    /// - `Error::UnsupportedObjectFormat` if `extensions.objectFormat` names
+    ///   an unknown hash function.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -111,8 +228,17 @@ impl Repository {
 
         // Validate that it's a proper git directory
         Self::validate_git_dir(&git_dir)?;
-
-        Ok(Repository { work_dir, git_dir })
+        let object_format = Self::read_object_format(&git_dir)?;
+
+        Ok(Repository {
+            work_dir,
+            git_dir,
+            object_format,
+            cache: Arc::new(ObjectCache::new(CacheOptions::new())),
+            write_lock: Arc::new(WriteLock::new()),
+            fsync: Arc::default(),
+            memory: None,
+        })
     }
 
     /// Discovers a Git repository by searching upward from the given path.
@@ -150,9 +276,15 @@ impl Repository {
 
             // Check if .git exists and is valid
             if git_dir.is_dir() && Self::validate_git_dir(&git_dir).is_ok() {
+                let object_format = Self::read_object_format(&git_dir)?;
                 return Ok(Repository {
                     work_dir: current,
                     git_dir,
+                    object_format,
+                    cache: Arc::new(ObjectCache::new(CacheOptions::new())),
+                    write_lock: Arc::new(WriteLock::new()),
+                    fsync: Arc::default(),
+                    memory: None,
                 });
             }
 
@@ -196,7 +328,7 @@ impl Repository {
     /// println!("Created repository at: {}", repo.path().display());
     /// ```
     pub fn init<P: AsRef<Path>>(path: P) -> Result<Self> {
-        Self::init_impl(path.as_ref(), false)
+        Self::init_with_options(path, &InitOptions::new())
     }
 
     /// Initializes a new bare Git repository at the given path.
@@ -227,11 +359,44 @@ impl Repository {
     /// println!("Created bare repository at: {}", repo.path().display());
     /// ```
     pub fn init_bare<P: AsRef<Path>>(path: P) -> Result<Self> {
-        Self::init_impl(path.as_ref(), true)
+        Self::init_with_options(path, &InitOptions::new().bare(true))
     }
 
-    /// Internal implementation for repository initialization.
-    fn init_impl(path: &Path, bare: bool) -> Result<Self> {
+    /// Initializes a new Git repository at the given path with options.
+    ///
+    /// This is equivalent to `git init`, with `--bare` and
+    /// `--object-format` given through `options`. A SHA-256 repository
+    /// is created with `core.repositoryformatversion = 1` and
+    /// `extensions.objectFormat = sha256`, as Git does.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path where the repository should be created.
+    /// * `options` - Options controlling the new repository.
+    ///
+    /// # Returns
+    ///
+    /// A `Repository` instance pointing to the newly created repository.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::AlreadyARepository` if a Git repository already exists at the path.
+    /// - `Error::Io` if directory or file creation fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::{InitOptions, ObjectFormat, Repository};
+    ///
+    /// let options = InitOptions::new().object_format(ObjectFormat::Sha256);
+    /// let repo = Repository::init_with_options("path/to/new/repo", &options).unwrap();
+    /// assert_eq!(repo.object_format(), ObjectFormat::Sha256);
+    /// ```
+    pub fn init_with_options<P: AsRef<Path>>(path: P, options: &InitOptions) -> Result<Self> {
+        let path = path.as_ref();
+        let bare = options.bare;
+        let object_format = options.object_format;
+
         // Create the base directory if it doesn't exist
         fs::create_dir_all(path)?;
 
@@ -266,19 +431,121 @@ impl Repository {
         let head_content = "ref: refs/heads/main\n";
         fs::write(git_dir.join("HEAD"), head_content)?;
 
-        // Create minimal config file
-        let config_content = if bare {
-            "[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = true\n"
-        } else {
-            "[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = false\n"
+        // Create minimal config file; extensions need format version 1
+        let version = match object_format {
+            ObjectFormat::Sha1 => 0,
+            ObjectFormat::Sha256 => 1,
         };
+        let mut config_content = format!(
+            "[core]\n\trepositoryformatversion = {}\n\tfilemode = true\n\tbare = {}\n",
+            version, bare
+        );
+        if object_format != ObjectFormat::Sha1 {
+            config_content.push_str(&format!(
+                "[extensions]\n\tobjectformat = {}\n",
+                object_format.name()
+            ));
+        }
         fs::write(git_dir.join("config"), config_content)?;
 
-        Ok(Repository { work_dir, git_dir })
+        Ok(Repository {
+            work_dir,
+            git_dir,
+            object_format,
+            cache: Arc::new(ObjectCache::new(CacheOptions::new())),
+            write_lock: Arc::new(WriteLock::new()),
+            fsync: Arc::default(),
+            memory: None,
+        })
+    }
+
+    /// Creates an empty repository held entirely in memory.
+    ///
+    /// The object database, references and index live in memory and are
+    /// dropped with the last handle; nothing is read from or written to
+    /// disk, so histories for tests can be built in microseconds. HEAD
+    /// points to the unborn branch `main`, and the configuration is empty.
+    ///
+    /// There is no working tree: content is staged with
+    /// [`Repository::add_content`], and commits, branches, tags, checkout
+    /// (which only updates HEAD and the index), diffs between trees and
+    /// the index, and log work as on disk. Operations that need files,
+    /// such as `status`, `add`, `gc` or `fsck`, return
+    /// `Error::UnsupportedInMemory`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zerogit::Repository;
+    ///
+    /// let repo = Repository::in_memory();
+    /// repo.add_content("README.md", b"# Hello\n").unwrap();
+    /// let oid = repo
+    ///     .create_commit("Initial commit", "Test User", "test@example.com")
+    ///     .unwrap();
+    /// assert_eq!(repo.head().unwrap().oid(), &oid);
+    /// assert!(repo.is_in_memory());
+    /// ```
+    pub fn in_memory() -> Self {
+        Self::in_memory_with_options(&InitOptions::new())
+    }
+
+    /// Creates an empty repository held entirely in memory, with options.
+    ///
+    /// See [`Repository::in_memory`]. The object format is taken from
+    /// `options`; `bare` is ignored, since an in-memory repository never
+    /// has a working tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Options controlling the new repository.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zerogit::{InitOptions, ObjectFormat, Repository};
+    ///
+    /// let options = InitOptions::new().object_format(ObjectFormat::Sha256);
+    /// let repo = Repository::in_memory_with_options(&options);
+    /// assert_eq!(repo.object_format(), ObjectFormat::Sha256);
+    /// ```
+    pub fn in_memory_with_options(options: &InitOptions) -> Self {
+        let memory = MemoryBackend::default();
+        let head = RefValue::Symbolic("refs/heads/main".to_string());
+        let mut refs = memory.refs.write().unwrap_or_else(|e| e.into_inner());
+        refs.insert("HEAD".to_string(), head);
+        drop(refs);
+
+        Repository {
+            work_dir: PathBuf::new(),
+            git_dir: PathBuf::new(),
+            object_format: options.object_format,
+            cache: Arc::new(ObjectCache::new(CacheOptions::new())),
+            write_lock: Arc::new(WriteLock::new()),
+            fsync: Arc::default(),
+            memory: Some(Arc::new(memory)),
+        }
+    }
+
+    /// Returns `true` if the repository is held in memory, created by
+    /// [`Repository::in_memory`].
+    pub fn is_in_memory(&self) -> bool {
+        self.memory.is_some()
+    }
+
+    /// Fails for an in-memory repository, which has no files for the
+    /// named operation to work on.
+    pub(crate) fn require_on_disk(&self, operation: &'static str) -> Result<()> {
+        match self.memory {
+            Some(_) => Err(Error::UnsupportedInMemory(operation)),
+            None => Ok(()),
+        }
     }
 
     /// Returns the path to the repository root (working directory).
     ///
+    /// The path is empty for an in-memory repository.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -293,6 +560,8 @@ impl Repository {
 
     /// Returns the path to the `.git` directory.
     ///
+    /// The path is empty for an in-memory repository.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -305,10 +574,28 @@ impl Repository {
         &self.git_dir
     }
 
+    /// Returns the hash function the repository names objects with.
+    ///
+    /// This is `extensions.objectFormat`, SHA-1 unless the repository was
+    /// created with `--object-format=sha256`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::repository::Repository;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// println!("Object format: {}", repo.object_format().name());
+    /// ```
+    pub fn object_format(&self) -> ObjectFormat {
+        self.object_format
+    }
+
     /// Returns the repository configuration.
     ///
     /// This loads configuration from all levels (system, global, local) with
     /// proper precedence (local overrides global, global overrides system).
+    /// An in-memory repository has an empty configuration.
     ///
     /// # Examples
     ///
@@ -323,12 +610,16 @@ impl Repository {
     /// }
     /// ```
     pub fn config(&self) -> Result<crate::config::Config> {
+        if self.memory.is_some() {
+            return Ok(crate::config::Config::new());
+        }
         crate::config::load_config(&self.git_dir)
     }
 
     /// Returns only the repository-local configuration.
     ///
     /// This loads only the `.git/config` file, ignoring global and system configs.
+    /// An in-memory repository has an empty configuration.
     ///
     /// # Examples
     ///
@@ -339,12 +630,136 @@ impl Repository {
     /// let config = repo.config_local().unwrap();
     /// ```
     pub fn config_local(&self) -> Result<crate::config::Config> {
+        if self.memory.is_some() {
+            return Ok(crate::config::Config::new());
+        }
         crate::config::Config::from_file(self.git_dir.join("config"))
     }
 
+    /// Returns another handle to the same repository.
+    ///
+    /// The handle shares the object caches and the write lock of this
+    /// one, so it is cheap to create, objects read through one handle are
+    /// cached for all, and writes through different handles are
+    /// serialized. Use one handle per thread, or share one `Repository`
+    /// behind an `Arc`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::thread;
+    /// use zerogit::Repository;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// let workers: Vec<_> = (0..4)
+    ///     .map(|_| {
+    ///         let repo = repo.clone_handle();
+    ///         thread::spawn(move || repo.log().unwrap().count())
+    ///     })
+    ///     .collect();
+    /// for worker in workers {
+    ///     println!("{} commits", worker.join().unwrap());
+    /// }
+    /// ```
+    pub fn clone_handle(&self) -> Repository {
+        Repository {
+            work_dir: self.work_dir.clone(),
+            git_dir: self.git_dir.clone(),
+            object_format: self.object_format,
+            cache: Arc::clone(&self.cache),
+            write_lock: Arc::clone(&self.write_lock),
+            fsync: Arc::clone(&self.fsync),
+            memory: self.memory.clone(),
+        }
+    }
+
+    /// Waits for other writes through any handle to finish, and holds off
+    /// new ones until the guard is dropped.
+    ///
+    /// The lock is re-entrant, so a write may call another write. The
+    /// `core.fsync` and `core.fsyncMethod` settings are read again for
+    /// each write; if the configuration cannot be read, the previous
+    /// settings, at first flushing everything, are kept.
+    pub(crate) fn lock_for_write(&self) -> WriteGuard<'_> {
+        let guard = self.write_lock.lock();
+        if self.memory.is_none() {
+            if let Ok(config) = self.config() {
+                self.fsync.set_settings(fsync_settings(&config));
+            }
+        }
+        guard
+    }
+
+    /// Returns the writer that flushes files as `core.fsync` asks.
+    pub(crate) fn fsync(&self) -> Arc<Fsync> {
+        Arc::clone(&self.fsync)
+    }
+
     /// Returns a reference to the loose object store.
-    fn object_store(&self) -> LooseObjectStore {
-        LooseObjectStore::new(self.git_dir.join("objects"))
+    pub(crate) fn object_store(&self) -> LooseObjectStore {
+        let store = match &self.memory {
+            Some(memory) => {
+                LooseObjectStore::in_memory(Arc::clone(&memory.objects), self.object_format)
+            }
+            None => LooseObjectStore::with_format(self.git_dir.join("objects"), self.object_format),
+        };
+        store
+            .with_cache(Arc::clone(&self.cache))
+            .with_fsync(Arc::clone(&self.fsync))
+    }
+
+    /// Changes the memory budgets of the object caches.
+    ///
+    /// Objects read from the repository are kept in memory, decompressed
+    /// and, for trees and commits, parsed, so walking history does not
+    /// read the same objects again. Delta bases resolved while reading
+    /// packs are cached separately. Both caches evict the least recently
+    /// used entries once over budget; shrinking a budget evicts at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The byte budgets of the caches.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::{CacheOptions, Repository};
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// repo.set_cache_options(CacheOptions::new().object_limit(256 * 1024 * 1024));
+    /// ```
+    pub fn set_cache_options(&self, options: CacheOptions) {
+        self.cache.set_options(options);
+    }
+
+    /// Returns the memory budgets of the object caches.
+    pub fn cache_options(&self) -> CacheOptions {
+        self.cache.options()
+    }
+
+    /// Returns the hit and miss counts and the memory in use of the
+    /// object caches.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::Repository;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// for commit in repo.log().unwrap() {
+    ///     commit.unwrap();
+    /// }
+    /// let stats = repo.cache_stats();
+    /// println!("{} hits, {} bytes", stats.object_hits(), stats.object_bytes());
+    /// ```
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Empties the object caches, closes the open packs and resets the
+    /// counters.
+    pub fn clear_cache(&self) {
+        self.cache.clear();
     }
 
     /// Resolves a short (abbreviated) OID to a full OID.
@@ -373,7 +788,7 @@ impl Repository {
     /// ```
     pub fn resolve_short_oid(&self, short_oid: &str) -> Result<Oid> {
         // If it's already a full OID, just parse it
-        if short_oid.len() == 40 {
+        if short_oid.len() == self.object_format.hex_len() {
             return Oid::from_hex(short_oid);
         }
 
@@ -417,17 +832,7 @@ impl Repository {
     /// ```
     pub fn commit(&self, oid_str: &str) -> Result<Commit> {
         let oid = self.resolve_short_oid(oid_str)?;
-        let store = self.object_store();
-        let raw = store.read(&oid)?;
-
-        if raw.object_type != ObjectType::Commit {
-            return Err(Error::TypeMismatch {
-                expected: "commit",
-                actual: raw.object_type.as_str(),
-            });
-        }
-
-        Commit::parse(oid, raw)
+        self.object_store().read_commit(&oid)
     }
 
     /// Retrieves a tree by its OID.
@@ -458,17 +863,7 @@ impl Repository {
     /// ```
     pub fn tree(&self, oid_str: &str) -> Result<Tree> {
         let oid = self.resolve_short_oid(oid_str)?;
-        let store = self.object_store();
-        let raw = store.read(&oid)?;
-
-        if raw.object_type != ObjectType::Tree {
-            return Err(Error::TypeMismatch {
-                expected: "tree",
-                actual: raw.object_type.as_str(),
-            });
-        }
-
-        Tree::parse(raw)
+        self.object_store().read_tree(&oid)
     }
 
     /// Retrieves a blob by its OID.
@@ -510,6 +905,92 @@ impl Repository {
         Blob::parse(raw)
     }
 
+    /// Opens a blob for streaming by its OID.
+    ///
+    /// Unlike [`Repository::blob`], the content is not loaded into memory:
+    /// it is decompressed as it is read, and its hash is verified once
+    /// the end is reached. Use this for large files.
+    ///
+    /// # Arguments
+    ///
+    /// * `oid_str` - The full or abbreviated OID as a hexadecimal string.
+    ///
+    /// # Returns
+    ///
+    /// A reader over the blob content.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::ObjectNotFound` if the object does not exist.
+    /// - `Error::TypeMismatch` if the object is not a blob.
+    ///
+    /// Reading from the returned reader fails with an
+    /// `io::ErrorKind::InvalidData` error if the object is corrupt.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use zerogit::repository::Repository;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// let mut reader = repo.blob_reader("abc1234").unwrap();
+    /// let mut out = File::create("asset.bin").unwrap();
+    /// std::io::copy(&mut reader, &mut out).unwrap();
+    /// ```
+    pub fn blob_reader(&self, oid_str: &str) -> Result<ObjectReader> {
+        let oid = self.resolve_short_oid(oid_str)?;
+        let reader = self.object_store().read_stream(&oid)?;
+
+        if reader.object_type() != ObjectType::Blob {
+            return Err(Error::TypeMismatch {
+                expected: "blob",
+                actual: reader.object_type().as_str(),
+            });
+        }
+
+        Ok(reader)
+    }
+
+    /// Writes a blob to the object store from a reader.
+    ///
+    /// The content is hashed and compressed while it is copied, so it is
+    /// never held in memory. The blob is not staged.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader providing the content.
+    /// * `size` - The size of the content in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The OID of the written blob.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if reading or writing fails, or if the reader
+    /// provides more or fewer than `size` bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use zerogit::repository::Repository;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// let file = File::open("asset.bin").unwrap();
+    /// let size = file.metadata().unwrap().len();
+    /// let oid = repo.write_blob_from_reader(file, size).unwrap();
+    /// println!("Wrote blob {}", oid);
+    /// ```
+    pub fn write_blob_from_reader<R: std::io::Read>(&self, reader: R, size: u64) -> Result<Oid> {
+        let oid = self
+            .object_store()
+            .write_stream(ObjectType::Blob, size, reader)?;
+        self.fsync.flush_batch()?;
+        Ok(oid)
+    }
+
     /// Retrieves a Git object by its OID.
     ///
     /// This method returns the object as a unified `Object` enum,
@@ -548,8 +1029,8 @@ impl Repository {
 
         match raw.object_type {
             ObjectType::Blob => Ok(Object::Blob(Blob::parse(raw)?)),
-            ObjectType::Tree => Ok(Object::Tree(Tree::parse(raw)?)),
-            ObjectType::Commit => Ok(Object::Commit(Commit::parse(oid, raw)?)),
+            ObjectType::Tree => Ok(Object::Tree(Tree::parse_with_format(raw, oid.format())?)),
+            ObjectType::Commit => Ok(Object::Commit(Box::new(Commit::parse(oid, raw)?))),
             ObjectType::Tag => Err(Error::InvalidObject {
                 oid: oid.to_hex(),
                 reason: "tag objects are not yet supported".to_string(),
@@ -557,9 +1038,49 @@ impl Repository {
         }
     }
 
+    /// Returns the type and size of a Git object without reading its
+    /// content.
+    ///
+    /// Only the object header is decompressed, which makes this suitable
+    /// for listing sizes (like `git ls-tree -l` or
+    /// `git cat-file --batch-check`) of large objects.
+    ///
+    /// # Arguments
+    ///
+    /// * `oid_str` - The full or abbreviated OID as a hexadecimal string.
+    ///
+    /// # Returns
+    ///
+    /// The object type and the content size in bytes.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::ObjectNotFound` if the object does not exist.
+    /// - `Error::InvalidObject` if the object header is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::repository::Repository;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// let tree = repo.tree("abc1234").unwrap();
+    /// for entry in tree.iter().filter(|e| e.is_file()) {
+    ///     let (_, size) = repo.read_header(&entry.oid().to_hex()).unwrap();
+    ///     println!("{:>8} {}", size, entry.name());
+    /// }
+    /// ```
+    pub fn read_header(&self, oid_str: &str) -> Result<(ObjectType, u64)> {
+        let oid = self.resolve_short_oid(oid_str)?;
+        self.object_store().read_header(&oid)
+    }
+
     /// Returns a reference to the ref store.
     fn ref_store(&self) -> RefStore {
-        RefStore::new(&self.git_dir)
+        match &self.memory {
+            Some(memory) => RefStore::in_memory(Arc::clone(&memory.refs)),
+            None => RefStore::new(&self.git_dir).with_fsync(Arc::clone(&self.fsync)),
+        }
     }
 
     /// Returns the current HEAD state.
@@ -662,7 +1183,7 @@ impl Repository {
     /// }
     /// ```
     pub fn log_from(&self, start_oid: Oid) -> Result<LogIterator> {
-        LogIterator::new(self.git_dir.join("objects"), start_oid)
+        LogIterator::with_store(self.object_store(), start_oid, LogOptions::default())
     }
 
     /// Returns an iterator over the commit history with filtering options.
@@ -702,7 +1223,7 @@ impl Repository {
         } else {
             *self.head()?.oid()
         };
-        LogIterator::with_options(self.git_dir.join("objects"), start_oid, options)
+        LogIterator::with_store(self.object_store(), start_oid, options)
     }
 
     /// Returns the status of the working tree.
@@ -732,10 +1253,43 @@ impl Repository {
     ///         FileStatus::Added => println!("A  {}", entry.path().display()),
     ///         FileStatus::StagedModified => println!("M  {}", entry.path().display()),
     ///         FileStatus::StagedDeleted => println!("D  {}", entry.path().display()),
+    ///         FileStatus::Renamed => println!("R  {}", entry.path().display()),
+    ///         FileStatus::Conflicted => println!("UU {}", entry.path().display()),
+    ///         FileStatus::Ignored => println!("!! {}", entry.path().display()),
     ///     }
     /// }
     /// ```
     pub fn status(&self) -> Result<Vec<StatusEntry>> {
+        self.status_with_options(StatusOptions::default())
+    }
+
+    /// Returns the status of the working tree with the given options.
+    ///
+    /// Tracked files whose cached stat data in the index matches the working
+    /// tree are not re-hashed. With [`StatusOptions::refresh`], stale stat
+    /// data of unchanged files is updated and the index is written back.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Options controlling the status computation.
+    ///
+    /// # Returns
+    ///
+    /// A vector of `StatusEntry` representing all files with changes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::repository::Repository;
+    /// use zerogit::status::StatusOptions;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// let entries = repo
+    ///     .status_with_options(StatusOptions::new().refresh(true))
+    ///     .unwrap();
+    /// ```
+    pub fn status_with_options(&self, options: StatusOptions) -> Result<Vec<StatusEntry>> {
+        self.require_on_disk("status")?;
         let store = self.object_store();
 
         // Get HEAD tree OID (if HEAD exists and points to a commit)
@@ -748,36 +1302,388 @@ impl Repository {
         // Read and parse index (if exists)
         let index_path = self.git_dir.join("index");
         let parsed_index = if index_path.exists() {
-            let index_data = read_file(&index_path)?;
-            Some(index::parse(&index_data)?)
+            // A refreshed index is written back, so no other write may
+            // change it in between
+            let _lock = options.get_refresh().then(|| self.lock_for_write());
+            let mut idx = self.read_index()?;
+            if options.get_refresh() && refresh_index(&self.work_dir, &mut idx, &options)? {
+                self.write_index(&idx)?;
+            }
+            Some(idx)
         } else {
             None
         };
 
-        compute_status(
+        status_in(
             &self.work_dir,
+            &self.git_dir,
             &store,
             head_tree_oid.as_ref(),
             parsed_index.as_ref(),
+            &options,
         )
     }
 
+    /// Returns the status of the working tree in Git's porcelain format.
+    ///
+    /// This produces the same bytes as `git status --porcelain` (or
+    /// `--porcelain=v2`, `--branch` and `-z`, depending on `options`) run at
+    /// the top of the working tree. To match Git's defaults, enable
+    /// [`StatusOptions::renames`] and use [`UntrackedFiles::Normal`].
+    ///
+    /// [`UntrackedFiles::Normal`]: crate::status::UntrackedFiles::Normal
+    ///
+    /// # Arguments
+    ///
+    /// * `status_options` - Options controlling which entries are reported.
+    /// * `options` - Options controlling the output format.
+    ///
+    /// # Returns
+    ///
+    /// The formatted output.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::io::Write;
+    /// use zerogit::repository::Repository;
+    /// use zerogit::status::{PorcelainOptions, PorcelainVersion, StatusOptions, UntrackedFiles};
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// let output = repo
+    ///     .status_porcelain(
+    ///         StatusOptions::new()
+    ///             .renames(true)
+    ///             .untracked_files(UntrackedFiles::Normal),
+    ///         PorcelainOptions::new()
+    ///             .version(PorcelainVersion::V2)
+    ///             .branch(true),
+    ///     )
+    ///     .unwrap();
+    /// std::io::stdout().write_all(&output).unwrap();
+    /// ```
+    pub fn status_porcelain(
+        &self,
+        status_options: StatusOptions,
+        options: PorcelainOptions,
+    ) -> Result<Vec<u8>> {
+        let entries = self.status_with_options(status_options)?;
+        let branch = if options.get_branch() {
+            Some(self.branch_info()?)
+        } else {
+            None
+        };
+        Ok(format_porcelain(&entries, branch.as_ref(), &options))
+    }
+
+    /// Returns the state of the current branch and its upstream.
+    ///
+    /// The upstream is taken from `branch.<name>.remote` and
+    /// `branch.<name>.merge`. If the upstream branch exists, the number of
+    /// commits ahead of and behind it is counted.
+    ///
+    /// # Returns
+    ///
+    /// The branch information used for status headers.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::RefNotFound` if HEAD doesn't exist.
+    /// - Other errors if commits cannot be read while counting.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::repository::Repository;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// let info = repo.branch_info().unwrap();
+    /// if let (Some(upstream), Some((ahead, behind))) = (info.upstream(), info.ahead_behind()) {
+    ///     println!("{}: ahead {}, behind {}", upstream, ahead, behind);
+    /// }
+    /// ```
+    pub fn branch_info(&self) -> Result<BranchInfo> {
+        let store = self.ref_store();
+
+        let (oid, branch) = match store.read_ref_file("HEAD")? {
+            crate::refs::RefValue::Symbolic(target) => {
+                let oid = store.resolve_recursive(&target).ok().map(|r| r.oid);
+                let name = target
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(&target)
+                    .to_string();
+                (oid, Some(name))
+            }
+            crate::refs::RefValue::Direct(oid) => (Some(oid), None),
+        };
+
+        let mut info = BranchInfo::new(oid, branch.clone());
+        let Some(branch) = branch else {
+            return Ok(info);
+        };
+
+        let config = self.config()?;
+        let remote = config.get_subsection("branch", &branch, "remote");
+        let merge = config.get_subsection("branch", &branch, "merge");
+        let upstream_ref = match (remote, merge) {
+            (Some("."), Some(merge)) => Some(merge.to_string()),
+            (Some(remote), Some(merge)) => config
+                .get_subsection("remote", remote, "fetch")
+                .and_then(|refspec| map_refspec(refspec, merge)),
+            _ => None,
+        };
+
+        if let Some(upstream_ref) = upstream_ref {
+            let short_name = ["refs/heads/", "refs/remotes/", "refs/"]
+                .iter()
+                .find_map(|prefix| upstream_ref.strip_prefix(prefix))
+                .unwrap_or(&upstream_ref)
+                .to_string();
+
+            let upstream_oid = store.resolve_recursive(&upstream_ref).ok().map(|r| r.oid);
+            let ahead_behind = match (oid, upstream_oid) {
+                (Some(local), Some(upstream)) => Some(self.ahead_behind(local, upstream)?),
+                _ => None,
+            };
+            info = info.with_upstream(short_name, ahead_behind);
+        }
+
+        Ok(info)
+    }
+
+    /// Counts the commits reachable from `local` but not `upstream`, and
+    /// vice versa.
+    fn ahead_behind(&self, local: Oid, upstream: Oid) -> Result<(usize, usize)> {
+        if local == upstream {
+            return Ok((0, 0));
+        }
+        let nodes = CommitNodes::new(self.object_store());
+        let ours = nodes.ancestors(local)?;
+        let theirs = nodes.ancestors(upstream)?;
+        Ok((
+            ours.difference(&theirs).count(),
+            theirs.difference(&ours).count(),
+        ))
+    }
+
+    /// Returns the index (staging area).
+    ///
+    /// A split index is returned merged with its shared index, so all
+    /// entries are present.
+    ///
+    /// # Returns
+    ///
+    /// The index, or an empty index if the repository has none yet.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::InvalidIndex` if the index or its shared index is malformed
+    ///   or missing.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::repository::Repository;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// for entry in repo.index().unwrap().iter() {
+    ///     println!("{}", entry.path().display());
+    /// }
+    /// ```
+    pub fn index(&self) -> Result<Index> {
+        self.read_index()
+    }
+
     /// Reads the current index, or creates an empty one if it doesn't exist.
-    fn read_index(&self) -> Result<Index> {
+    ///
+    /// The modification time of the index file is recorded so that racily
+    /// clean entries can be detected. A split index is merged with its
+    /// shared index. A new index uses the version from `index.version`,
+    /// defaulting to 2. An in-memory repository keeps its index in memory.
+    pub(crate) fn read_index(&self) -> Result<Index> {
+        if let Some(memory) = &self.memory {
+            let index = memory.index.lock().unwrap_or_else(|e| e.into_inner());
+            return Ok(index.clone().unwrap_or_else(|| {
+                let mut idx = Index::empty(2);
+                idx.set_object_format(self.object_format);
+                idx
+            }));
+        }
         let index_path = self.git_dir.join("index");
         if index_path.exists() {
+            let metadata = fs::metadata(&index_path)?;
             let index_data = read_file(&index_path)?;
-            index::parse(&index_data)
+            let mut idx = index::parse_with_format(&index_data, self.object_format)?;
+            idx.set_timestamp(&metadata);
+            if let Some(oid) = idx
+                .shared_index()
+                .filter(|_| idx.has_pending_shared_index())
+            {
+                let shared = self.read_shared_index(oid, idx.version())?;
+                idx.merge_shared_index(shared)?;
+            }
+            Ok(idx)
         } else {
-            Ok(Index::empty(2))
+            let config = self.config()?;
+            let mut idx = Index::empty(configured_index_version(&config).unwrap_or(2));
+            idx.set_object_format(self.object_format);
+            Ok(idx)
+        }
+    }
+
+    /// Reads the shared index `sharedindex.<oid>` of a split index.
+    fn read_shared_index(&self, oid: &Oid, version: u32) -> Result<Index> {
+        let name = format!("sharedindex.{}", oid.to_hex());
+        let data = read_file(self.git_dir.join(&name)).map_err(|_| Error::InvalidIndex {
+            version,
+            reason: format!("could not read {}", name),
+        })?;
+        if !data.ends_with(oid.as_bytes()) {
+            return Err(Error::InvalidIndex {
+                version,
+                reason: format!("checksum mismatch in {}", name),
+            });
         }
+        index::parse_with_format(&data, self.object_format)
     }
 
     /// Writes the index to disk.
-    fn write_index(&self, idx: &Index) -> Result<()> {
+    ///
+    /// If `index.version` is set, the index is written in that version;
+    /// versions 2 and 3 are chosen by whether any entry has extended
+    /// flags.
+    /// A split index (`core.splitIndex`, or an index that was already
+    /// split) only writes the entries that differ from the shared index,
+    /// unless more than `splitIndex.maxPercentChange` percent (default 20)
+    /// of the entries are not shared; then a new shared index is written,
+    /// and the old ones older than `splitIndex.sharedIndexExpire` are
+    /// deleted.
+    /// Racily clean entries whose files have changed are smudged first.
+    /// An in-memory repository keeps its index in memory.
+    pub(crate) fn write_index(&self, idx: &Index) -> Result<()> {
+        if let Some(memory) = &self.memory {
+            *memory.index.lock().unwrap_or_else(|e| e.into_inner()) = Some(idx.clone());
+            return Ok(());
+        }
         let index_path = self.git_dir.join("index");
-        let data = index::write(idx);
-        write_file_atomic(&index_path, &data)
+        let config = self.config()?;
+        // The index must not refer to blobs a crash could lose
+        self.fsync.flush_batch()?;
+
+        let smudged = self.smudge_racily_clean(idx)?;
+        let idx = smudged.as_ref().unwrap_or(idx);
+
+        let version = configured_index_version(&config).filter(|&v| v != idx.version());
+        let split = match config.get("core", "splitIndex") {
+            Some(_) => config.get_bool("core", "splitIndex")?,
+            None => idx.shared_index().is_some(),
+        };
+        let unsplit = !split && idx.shared_index().is_some();
+        let reshare = split && (idx.shared_index().is_none() || too_many_not_shared(idx, &config)?);
+
+        let mut shared = None;
+        let data = if version.is_some() || unsplit || reshare {
+            let mut idx = idx.clone();
+            if let Some(version) = version {
+                idx.set_version(version);
+            }
+            if unsplit {
+                idx.remove_split_index();
+            }
+            if reshare {
+                shared = Some(self.write_shared_index(&mut idx)?);
+            }
+            index::write(&idx)
+        } else {
+            index::write(idx)
+        };
+        self.fsync
+            .write_file(FsyncComponent::Index, &index_path, &data, false)?;
+        if let Some(oid) = shared {
+            self.expire_shared_indexes(&oid, &config)?;
+        }
+        Ok(())
+    }
+
+    /// Returns a copy of the index with its racily clean entries smudged,
+    /// or `None` if no entry needs it.
+    ///
+    /// Once the index is rewritten, its modification time moves past
+    /// entries whose files changed within the timestamp tick their stat
+    /// data was recorded in, and they would look clean from then on. As
+    /// Git's `ce_smudge_racily_clean_entry` does, entries whose stat data
+    /// still matches but whose content differs get a size of 0, so that
+    /// they are always hashed.
+    fn smudge_racily_clean(&self, idx: &Index) -> Result<Option<Index>> {
+        let Some(timestamp) = idx.timestamp() else {
+            return Ok(None);
+        };
+        let mut modified = HashSet::new();
+        for entry in idx.iter() {
+            if racily_modified(&self.work_dir, entry, timestamp)? {
+                modified.insert(entry.path().to_path_buf());
+            }
+        }
+        if modified.is_empty() {
+            return Ok(None);
+        }
+
+        let mut idx = idx.clone();
+        for entry in idx.iter_mut() {
+            if entry.stage() == 0 && modified.contains(entry.path()) {
+                entry.smudge();
+            }
+        }
+        Ok(Some(idx))
+    }
+
+    /// Writes the entries of the index as a new shared index and splits
+    /// the index against it, returning the shared index's id.
+    fn write_shared_index(&self, idx: &mut Index) -> Result<Oid> {
+        let shared = idx.share_entries();
+        let data = index::write(&shared);
+        let oid = Oid::from_slice(&data[data.len() - self.object_format.size()..])?;
+
+        let path = self.git_dir.join(format!("sharedindex.{}", oid.to_hex()));
+        if !path.exists() {
+            self.fsync
+                .write_file(FsyncComponent::Index, &path, &data, false)?;
+        }
+        idx.set_shared_index(oid, shared.entries().to_vec());
+        Ok(oid)
+    }
+
+    /// Deletes the shared indexes other than `current` that are older than
+    /// `splitIndex.sharedIndexExpire` (default two weeks), as Git does
+    /// after writing a new shared index.
+    ///
+    /// Like Git, a shared index that cannot be deleted is left behind.
+    fn expire_shared_indexes(&self, current: &Oid, config: &crate::config::Config) -> Result<()> {
+        let Some(expire) = shared_index_expire(config)? else {
+            return Ok(());
+        };
+        let Some(cutoff) = std::time::SystemTime::now().checked_sub(expire) else {
+            return Ok(());
+        };
+        let current = format!("sharedindex.{}", current.to_hex());
+        for entry in fs::read_dir(&self.git_dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if !name.starts_with("sharedindex.") || name == current {
+                continue;
+            }
+            let expired = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified <= cutoff);
+            if expired {
+                let _ = crate::infra::remove_file(entry.path());
+            }
+        }
+        Ok(())
     }
 
     /// Adds a file to the staging area (index).
@@ -795,7 +1701,10 @@ impl Repository {
     ///
     /// # Errors
     ///
-    /// - `Error::PathNotFound` if the file does not exist.
+    /// - `Error::PathNotFound` if the file does not exist, or the path is
+    ///   empty, absolute or contains `.`, `..` or `.git` components.
+    /// - `Error::OutsideSparseCheckout` if sparse checkout is enabled and
+    ///   the path is outside the sparse set.
     ///
     /// # Examples
     ///
@@ -806,7 +1715,10 @@ impl Repository {
     /// repo.add("src/main.rs").unwrap();
     /// ```
     pub fn add<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.require_on_disk("add")?;
+        let _lock = self.lock_for_write();
         let path = path.as_ref();
+        check_index_path(path)?;
         let full_path = self.work_dir.join(path);
 
         // Check if file exists
@@ -814,64 +1726,97 @@ impl Repository {
             return Err(Error::PathNotFound(path.to_path_buf()));
         }
 
-        // Read file content
-        let content = read_file(&full_path)?;
+        if let Some(sparse) = self.sparse_checkout()? {
+            if !sparse.includes(path) {
+                return Err(Error::OutsideSparseCheckout(path.to_path_buf()));
+            }
+        }
 
         // Get file metadata
         let metadata = std::fs::metadata(&full_path)?;
 
-        // Write blob to object store
+        // Write blob to object store, streaming large files
         let store = self.object_store();
-        let oid = store.write(ObjectType::Blob, &content)?;
-
-        // Determine file mode
-        #[cfg(unix)]
-        let mode = {
-            use std::os::unix::fs::PermissionsExt;
-            if metadata.permissions().mode() & 0o111 != 0 {
-                FileMode::Executable
-            } else {
-                FileMode::Regular
+        let threshold = big_file_threshold(&self.config()?)?;
+        let oid = write_blob_from_file(&store, &full_path, &metadata, threshold)?;
+
+        // Create index entry with full stat data
+        let entry = IndexEntry::from_metadata(path.to_path_buf(), oid, &metadata);
+
+        // Read current index, add entry, and write back
+        let mut idx = self.read_index()?;
+        idx.add(entry);
+        self.write_index(&idx)?;
+
+        Ok(())
+    }
+
+    /// Stages content for a path without reading the working tree.
+    ///
+    /// This is equivalent to `git update-index --add --cacheinfo`: the
+    /// content is written as a blob and the path is staged as a regular
+    /// file with empty stat data. It is how files are added to an
+    /// in-memory repository, and works the same on disk, where the
+    /// working tree is left untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to stage, relative to the repository root.
+    /// * `content` - The content of the file.
+    ///
+    /// # Returns
+    ///
+    /// The OID of the blob written for the content.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::PathNotFound` if the path is empty, absolute or contains
+    ///   `.`, `..` or `.git` components, as for [`Repository::add`].
+    /// - `Error::OutsideSparseCheckout` if sparse checkout is enabled and
+    ///   the path is outside the sparse set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use zerogit::Repository;
+    ///
+    /// let repo = Repository::in_memory();
+    /// let oid = repo.add_content("src/lib.rs", b"pub fn f() {}\n").unwrap();
+    /// let index = repo.index().unwrap();
+    /// assert_eq!(index.get(Path::new("src/lib.rs")).unwrap().oid(), &oid);
+    /// ```
+    pub fn add_content<P: AsRef<Path>>(&self, path: P, content: &[u8]) -> Result<Oid> {
+        let _lock = self.lock_for_write();
+        let path = path.as_ref();
+        check_index_path(path)?;
+
+        if let Some(sparse) = self.sparse_checkout()? {
+            if !sparse.includes(path) {
+                return Err(Error::OutsideSparseCheckout(path.to_path_buf()));
             }
-        };
-        #[cfg(not(unix))]
-        let mode = FileMode::Regular;
-
-        // Get timestamps
-        let mtime = metadata
-            .modified()
-            .ok()
-            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-            .map(|d| d.as_secs())
-            .unwrap_or(0);
-        let ctime = metadata
-            .created()
-            .ok()
-            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-            .map(|d| d.as_secs())
-            .unwrap_or(mtime);
-
-        // Create index entry
+        }
+
+        let oid = self.object_store().write(ObjectType::Blob, content)?;
         let entry = IndexEntry::new(
-            ctime,
-            mtime,
-            0,                         // dev (not portable, use 0)
-            0,                         // ino (not portable, use 0)
-            mode,
-            0,                         // uid (not portable, use 0)
-            0,                         // gid (not portable, use 0)
+            0,
+            0,
+            0,
+            0,
+            FileMode::Regular,
+            0,
+            0,
             content.len() as u32,
             oid,
             path.to_path_buf(),
-            0,                         // stage (normal entry)
+            0,
         );
 
-        // Read current index, add entry, and write back
         let mut idx = self.read_index()?;
         idx.add(entry);
         self.write_index(&idx)?;
 
-        Ok(())
+        Ok(oid)
     }
 
     /// Adds all modified and untracked files to the staging area.
@@ -891,6 +1836,43 @@ impl Repository {
     /// repo.add_all().unwrap();
     /// ```
     pub fn add_all(&self) -> Result<()> {
+        self.add_all_with_options(StatusOptions::default())
+    }
+
+    /// Adds all modified and untracked files to the staging area, using the
+    /// given options.
+    ///
+    /// The working tree scan and blob hashing run on
+    /// [`StatusOptions::threads`] threads. Files whose cached stat data in
+    /// the index shows them unchanged are not re-read. Only paths matching
+    /// [`StatusOptions::pathspec`] are staged or removed. With sparse
+    /// checkout enabled, paths outside the sparse set are left alone.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Options controlling the working tree scan.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::PathNotFound` if a pathspec item matches no file in the
+    ///   working tree, the index or HEAD.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::repository::Repository;
+    /// use zerogit::status::StatusOptions;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// repo.add_all_with_options(StatusOptions::new().threads(0)).unwrap();
+    /// ```
+    pub fn add_all_with_options(&self, options: StatusOptions) -> Result<()> {
+        self.require_on_disk("add")?;
+        let _lock = self.lock_for_write();
         use std::collections::BTreeMap;
 
         let store = self.object_store();
@@ -909,65 +1891,62 @@ impl Repository {
         }
 
         // Get working tree files
-        let working_files = crate::infra::list_working_tree(&self.work_dir)?;
+        let threads = options.get_threads();
+        let working_files = list_working_tree_parallel(&self.work_dir, threads)?;
+
+        let pathspec = options.get_pathspec();
+        let known_paths = working_files
+            .iter()
+            .map(PathBuf::as_path)
+            .chain(idx.iter().map(IndexEntry::path))
+            .chain(head_files.keys().map(PathBuf::as_path));
+        if let Some(spec) = pathspec.unmatched(known_paths) {
+            return Err(Error::PathNotFound(PathBuf::from(spec)));
+        }
+        let sparse = self.sparse_checkout()?;
+        let working_files: Vec<PathBuf> = working_files
+            .into_iter()
+            .filter(|path| pathspec.matches(path))
+            .filter(|path| sparse.as_ref().map_or(true, |s| s.includes(path)))
+            .collect();
+
+        // Hash and write blobs for all changed working tree files
+        let threshold = big_file_threshold(&self.config()?)?;
+        let index_timestamp = idx.timestamp();
+        let existing_entries: std::collections::HashMap<&Path, &IndexEntry> =
+            idx.iter().map(|e| (e.path(), e)).collect();
+        let stage = |path: &PathBuf| -> Result<Option<IndexEntry>> {
+            if let Some(existing) = existing_entries.get(path.as_path()) {
+                if entry_stat_clean(&self.work_dir, existing, index_timestamp) {
+                    return Ok(None);
+                }
+            }
 
-        // Add all working tree files
-        for path in &working_files {
             let full_path = self.work_dir.join(path);
-            let content = read_file(&full_path)?;
             let metadata = std::fs::metadata(&full_path)?;
 
             // Write blob
-            let oid = store.write(ObjectType::Blob, &content)?;
-
-            // Determine file mode
-            #[cfg(unix)]
-            let mode = {
-                use std::os::unix::fs::PermissionsExt;
-                if metadata.permissions().mode() & 0o111 != 0 {
-                    FileMode::Executable
-                } else {
-                    FileMode::Regular
-                }
-            };
-            #[cfg(not(unix))]
-            let mode = FileMode::Regular;
+            let oid = write_blob_from_file(&store, &full_path, &metadata, threshold)?;
 
-            // Get timestamps
-            let mtime = metadata
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs())
-                .unwrap_or(0);
-            let ctime = metadata
-                .created()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs())
-                .unwrap_or(mtime);
+            let entry = IndexEntry::from_metadata(path.clone(), oid, &metadata);
+            Ok(Some(entry))
+        };
 
-            let entry = IndexEntry::new(
-                ctime,
-                mtime,
-                0,
-                0,
-                mode,
-                0,
-                0,
-                content.len() as u32,
-                oid,
-                path.clone(),
-                0,
-            );
+        let staged = parallel_map(&working_files, threads, stage);
 
-            idx.add(entry);
+        for entry in staged {
+            if let Some(entry) = entry? {
+                idx.add(entry);
+            }
         }
 
         // Handle deleted files: remove from index files that are in HEAD but not in working tree
         let working_set: std::collections::HashSet<_> = working_files.into_iter().collect();
         for head_path in head_files.keys() {
-            if !working_set.contains(head_path) {
+            let outside = idx
+                .get(head_path)
+                .is_some_and(|entry| outside_sparse_set(sparse.as_ref(), entry));
+            if !working_set.contains(head_path) && pathspec.matches(head_path) && !outside {
                 idx.remove(head_path);
             }
         }
@@ -977,6 +1956,37 @@ impl Repository {
         Ok(())
     }
 
+    /// Adds files matching a pathspec to the staging area.
+    ///
+    /// This is equivalent to `git add -A -- <pathspec>`: matching new and
+    /// modified files are staged, and matching deleted files are removed
+    /// from the index.
+    ///
+    /// # Arguments
+    ///
+    /// * `pathspec` - The pathspec selecting the files to add.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::PathNotFound` if a pathspec item matches no file.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::{Pathspec, Repository};
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// repo.add_pathspec(&Pathspec::new(["src/*.rs", ":!src/generated.rs"]).unwrap())
+    ///     .unwrap();
+    /// ```
+    pub fn add_pathspec(&self, pathspec: &Pathspec) -> Result<()> {
+        self.add_all_with_options(StatusOptions::new().pathspec(pathspec.clone()))
+    }
+
     /// Resets the staging area to match HEAD.
     ///
     /// This removes all staged changes, reverting the index to the state
@@ -1004,8 +2014,37 @@ impl Repository {
     /// repo.reset(Some("src/main.rs")).unwrap();
     /// ```
     pub fn reset<P: AsRef<Path>>(&self, path: Option<P>) -> Result<()> {
-        use std::collections::BTreeMap;
+        match path {
+            Some(p) => self.reset_pathspec(&Pathspec::literal(p)),
+            None => self.reset_pathspec(&Pathspec::default()),
+        }
+    }
 
+    /// Resets index entries matching a pathspec to match HEAD.
+    ///
+    /// This is equivalent to `git reset -- <pathspec>`. Matching entries
+    /// that exist in HEAD are restored with HEAD's blob and mode; matching
+    /// entries that do not exist in HEAD are removed from the index. An
+    /// empty pathspec resets the whole index.
+    ///
+    /// # Arguments
+    ///
+    /// * `pathspec` - The pathspec selecting the entries to reset.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` on success.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::{Pathspec, Repository};
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// repo.reset_pathspec(&Pathspec::new(["*.txt"]).unwrap()).unwrap();
+    /// ```
+    pub fn reset_pathspec(&self, pathspec: &Pathspec) -> Result<()> {
+        let _lock = self.lock_for_write();
         let store = self.object_store();
         let mut idx = self.read_index()?;
 
@@ -1016,59 +2055,46 @@ impl Repository {
                 .map(|commit| *commit.tree())
         });
 
-        let mut head_files: BTreeMap<PathBuf, Oid> = BTreeMap::new();
+        let mut head_files: BTreeMap<PathBuf, (Oid, FileMode)> = BTreeMap::new();
         if let Some(tree_oid) = head_tree_oid {
-            flatten_tree(&store, &tree_oid, Path::new(""), &mut head_files)?;
+            flatten_tree_with_modes(&store, &tree_oid, Path::new(""), &mut head_files)?;
         }
 
-        match path {
-            Some(p) => {
-                // Reset specific path
-                let path = p.as_ref();
-                if let Some(head_oid) = head_files.get(path) {
-                    // File exists in HEAD, restore it to index
-                    let raw = store.read(head_oid)?;
-                    let entry = IndexEntry::new(
-                        0, // ctime (will be updated on next add)
-                        0, // mtime
-                        0,
-                        0,
-                        FileMode::Regular, // Simplified: assume regular file
-                        0,
-                        0,
-                        raw.content.len() as u32,
-                        *head_oid,
-                        path.to_path_buf(),
-                        0,
-                    );
-                    idx.add(entry);
-                } else {
-                    // File doesn't exist in HEAD, remove from index
-                    idx.remove(path);
-                }
-            }
-            None => {
-                // Reset all: rebuild index from HEAD
-                idx.clear();
-
-                for (path, oid) in &head_files {
-                    let raw = store.read(oid)?;
-                    let entry = IndexEntry::new(
-                        0,
-                        0,
-                        0,
-                        0,
-                        FileMode::Regular,
-                        0,
-                        0,
-                        raw.content.len() as u32,
-                        *oid,
-                        path.clone(),
-                        0,
-                    );
-                    idx.add(entry);
-                }
+        // Remove matching entries that differ from HEAD, including all
+        // conflict stages
+        let stale: Vec<PathBuf> = idx
+            .iter()
+            .filter(|entry| pathspec.matches(entry.path()))
+            .filter(|entry| {
+                entry.stage() != 0
+                    || head_files.get(entry.path()) != Some(&(*entry.oid(), entry.mode()))
+            })
+            .map(|entry| entry.path().to_path_buf())
+            .collect();
+        for path in &stale {
+            while idx.remove(path) {}
+        }
+
+        // Restore matching HEAD entries that are no longer in the index
+        for (path, (oid, mode)) in &head_files {
+            if !pathspec.matches(path) || idx.get(path).is_some() {
+                continue;
             }
+            let raw = store.read(oid)?;
+            let entry = IndexEntry::new(
+                0, // ctime (will be updated on next add)
+                0, // mtime
+                0,
+                0,
+                *mode,
+                0,
+                0,
+                raw.content.len() as u32,
+                *oid,
+                path.clone(),
+                0,
+            );
+            idx.add(entry);
         }
 
         self.write_index(&idx)?;
@@ -1078,108 +2104,101 @@ impl Repository {
 
     /// Builds a tree object from the current index.
     ///
-    /// This creates tree objects for all directories in the index,
-    /// constructing them bottom-up to handle nested directories.
+    /// Directories whose cache-tree node is still valid are not rebuilt;
+    /// the cached tree ID is used instead. The cache-tree is updated with
+    /// every tree that was built, so it should be written back with the
+    /// index.
     ///
     /// # Returns
     ///
     /// The OID of the root tree object.
-    fn build_tree_from_index(&self, idx: &Index) -> Result<Oid> {
+    fn build_tree_from_index(&self, idx: &mut Index) -> Result<Oid> {
         let store = self.object_store();
+        let entries = idx.entries().to_vec();
+        let (oid, _) =
+            Self::write_cache_tree(&store, &entries, Path::new(""), idx.cache_tree_mut())?;
+        Ok(oid)
+    }
 
-        // Group entries by directory
-        // Key: directory path (empty string for root)
-        // Value: Vec<(name, mode, oid)>
-        let mut dir_entries: BTreeMap<PathBuf, Vec<(String, FileMode, Oid)>> = BTreeMap::new();
-
-        // First, collect all blobs by their parent directory
-        for entry in idx.entries() {
-            let path = entry.path();
-            let parent = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
-            let name = path
-                .file_name()
-                .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_default();
-
-            dir_entries
-                .entry(parent)
-                .or_default()
-                .push((name, entry.mode(), *entry.oid()));
+    /// Writes the tree for the directory `dir`, whose entries start at the
+    /// front of `entries`, reusing `node` if it is valid.
+    ///
+    /// # Returns
+    ///
+    /// The tree OID and the number of index entries it covers.
+    fn write_cache_tree(
+        store: &LooseObjectStore,
+        entries: &[IndexEntry],
+        dir: &Path,
+        node: &mut CacheTree,
+    ) -> Result<(Oid, usize)> {
+        let in_dir = |entry: &IndexEntry| entry.path().starts_with(dir);
+
+        if let (Some(oid), Some(count)) = (node.oid(), node.entry_count()) {
+            // Only trust the cache if it covers exactly this directory
+            let covers = count > 0
+                && count <= entries.len()
+                && entries[..count].iter().all(in_dir)
+                && !entries.get(count).is_some_and(in_dir);
+            if covers {
+                return Ok((*oid, count));
+            }
         }
 
-        // Process directories from deepest to shallowest
-        // We need to build subtrees first, then include them in parent trees
-        let mut tree_oids: BTreeMap<PathBuf, Oid> = BTreeMap::new();
-
-        // Get all unique directory paths (including intermediate ones)
-        let mut all_dirs: Vec<PathBuf> = dir_entries.keys().cloned().collect();
+        let mut tree_entries: Vec<(Vec<u8>, FileMode, Oid)> = Vec::new();
+        let mut children = Vec::new();
+        let mut i = 0;
+        while let Some(entry) = entries.get(i).filter(|e| in_dir(e)) {
+            let rel = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+            let mut components = rel.iter();
+            let name = components
+                .next()
+                .map(|c| path::to_bytes(Path::new(c)).into_owned())
+                .unwrap_or_default();
 
-        // Also add parent directories that might only contain subdirectories
-        for entry in idx.entries() {
-            let mut current = entry.path().parent();
-            while let Some(p) = current {
-                if !p.as_os_str().is_empty() && !all_dirs.contains(&p.to_path_buf()) {
-                    all_dirs.push(p.to_path_buf());
+            if components.next().is_some() {
+                let mut child = node
+                    .take_child(&name)
+                    .unwrap_or_else(|| CacheTree::new(&name));
+                let child_dir = dir.join(path::from_bytes(&name));
+                let (oid, count) =
+                    Self::write_cache_tree(store, &entries[i..], &child_dir, &mut child)?;
+                tree_entries.push((name, FileMode::Directory, oid));
+                children.push(child);
+                i += count;
+            } else {
+                // Intent-to-add entries only reserve the path
+                if !entry.is_intent_to_add() {
+                    tree_entries.push((name, entry.mode(), *entry.oid()));
                 }
-                current = p.parent();
+                i += 1;
             }
         }
 
-        // Sort directories by depth (deepest first)
-        all_dirs.sort_by(|a, b| {
-            let depth_a = a.components().count();
-            let depth_b = b.components().count();
-            depth_b.cmp(&depth_a) // Reverse order: deepest first
-        });
-
-        // Add root directory if not present
-        if !all_dirs.contains(&PathBuf::new()) {
-            all_dirs.push(PathBuf::new());
-        }
-
-        // Process each directory
-        for dir in all_dirs {
-            let mut entries: Vec<(String, FileMode, Oid)> = Vec::new();
-
-            // Add file entries for this directory
-            if let Some(file_entries) = dir_entries.get(&dir) {
-                entries.extend(file_entries.iter().cloned());
-            }
-
-            // Add subdirectory entries (trees we've already built)
-            for (subdir_path, tree_oid) in &tree_oids {
-                if let Some(parent) = subdir_path.parent() {
-                    if parent == dir {
-                        let name = subdir_path
-                            .file_name()
-                            .map(|n| n.to_string_lossy().to_string())
-                            .unwrap_or_default();
-                        entries.push((name, FileMode::Directory, *tree_oid));
-                    }
+        // Git sorts tree entries as if directory names ended with '/'
+        tree_entries.sort_by(|a, b| {
+            let key = |(name, mode, _): &(Vec<u8>, FileMode, Oid)| {
+                let mut key = name.clone();
+                if *mode == FileMode::Directory {
+                    key.push(b'/');
                 }
-            }
-
-            // Sort entries by name (Git requires this)
-            entries.sort_by(|a, b| a.0.cmp(&b.0));
-
-            // Build tree object content
-            let tree_content = Self::build_tree_content(&entries);
-            let tree_oid = store.write(ObjectType::Tree, &tree_content)?;
+                key
+            };
+            key(a).cmp(&key(b))
+        });
 
-            tree_oids.insert(dir.clone(), tree_oid);
-        }
+        let tree_content = Self::build_tree_content(&tree_entries);
+        let oid = store.write(ObjectType::Tree, &tree_content)?;
+        node.set_children(children);
+        node.set_valid(i, oid);
 
-        // Return the root tree OID
-        tree_oids
-            .get(&PathBuf::new())
-            .copied()
-            .ok_or_else(|| Error::EmptyCommit)
+        Ok((oid, i))
     }
 
     /// Builds the binary content of a tree object.
     ///
     /// Tree format: `<mode> <name>\0<20-byte-sha1>` for each entry
-    fn build_tree_content(entries: &[(String, FileMode, Oid)]) -> Vec<u8> {
+    fn build_tree_content(entries: &[(Vec<u8>, FileMode, Oid)]) -> Vec<u8> {
         let mut content = Vec::new();
 
         for (name, mode, oid) in entries {
@@ -1190,7 +2209,7 @@ impl Repository {
             };
             content.extend_from_slice(mode_str.as_bytes());
             content.push(b' ');
-            content.extend_from_slice(name.as_bytes());
+            content.extend_from_slice(name);
             content.push(0);
             content.extend_from_slice(oid.as_bytes());
         }
@@ -1249,13 +2268,11 @@ impl Repository {
         match store.read_ref_file("HEAD")? {
             crate::refs::RefValue::Symbolic(target) => {
                 // HEAD points to a branch, update the branch
-                let branch_path = self.git_dir.join(&target);
-                write_file_atomic(&branch_path, format!("{}\n", new_oid.to_hex()).as_bytes())?;
+                store.write_ref(&target, &RefValue::Direct(*new_oid))?;
             }
             crate::refs::RefValue::Direct(_) => {
                 // HEAD is detached, update HEAD directly
-                let head_path = self.git_dir.join("HEAD");
-                write_file_atomic(&head_path, format!("{}\n", new_oid.to_hex()).as_bytes())?;
+                store.write_ref("HEAD", &RefValue::Direct(*new_oid))?;
             }
         }
 
@@ -1302,16 +2319,18 @@ impl Repository {
         author_name: &str,
         author_email: &str,
     ) -> Result<Oid> {
+        let _lock = self.lock_for_write();
         // Read the current index
-        let idx = self.read_index()?;
+        let mut idx = self.read_index()?;
 
         // Check if there are any staged changes
         if idx.is_empty() {
             return Err(Error::EmptyCommit);
         }
 
-        // Build tree from index
-        let tree_oid = self.build_tree_from_index(&idx)?;
+        // Build tree from index, then save the updated cache-tree
+        let tree_oid = self.build_tree_from_index(&mut idx)?;
+        self.write_index(&idx)?;
 
         // Get parent commit (current HEAD, if exists)
         let parent_oid = self.head().ok().map(|h| *h.oid());
@@ -1348,36 +2367,44 @@ impl Repository {
     }
 
     /// Validates a branch name according to Git rules.
+    fn validate_branch_name(name: &str) -> Result<()> {
+        Self::validate_ref_name("branch", name)
+    }
+
+    /// Validates a branch or tag name according to Git rules.
     ///
-    /// A valid branch name:
+    /// A valid name:
     /// - Cannot be empty
     /// - Cannot start or end with `/`
     /// - Cannot contain `..`, `~`, `^`, `:`, `?`, `*`, `[`, `\`, or control characters
     /// - Cannot start with `-`
     /// - Cannot end with `.lock`
-    fn validate_branch_name(name: &str) -> Result<()> {
+    fn validate_ref_name(kind: &str, name: &str) -> Result<()> {
         if name.is_empty() {
-            return Err(Error::InvalidRefName("branch name cannot be empty".to_string()));
+            return Err(Error::InvalidRefName(format!(
+                "{} name cannot be empty",
+                kind
+            )));
         }
 
         if name.starts_with('-') {
             return Err(Error::InvalidRefName(format!(
-                "branch name cannot start with '-': {}",
-                name
+                "{} name cannot start with '-': {}",
+                kind, name
             )));
         }
 
         if name.starts_with('/') || name.ends_with('/') {
             return Err(Error::InvalidRefName(format!(
-                "branch name cannot start or end with '/': {}",
-                name
+                "{} name cannot start or end with '/': {}",
+                kind, name
             )));
         }
 
         if name.ends_with(".lock") {
             return Err(Error::InvalidRefName(format!(
-                "branch name cannot end with '.lock': {}",
-                name
+                "{} name cannot end with '.lock': {}",
+                kind, name
             )));
         }
 
@@ -1385,24 +2412,24 @@ impl Repository {
         for c in invalid_chars {
             if name.contains(c) {
                 return Err(Error::InvalidRefName(format!(
-                    "branch name contains invalid character '{}': {}",
-                    c, name
+                    "{} name contains invalid character '{}': {}",
+                    kind, c, name
                 )));
             }
         }
 
         if name.contains("..") {
             return Err(Error::InvalidRefName(format!(
-                "branch name cannot contain '..': {}",
-                name
+                "{} name cannot contain '..': {}",
+                kind, name
             )));
         }
 
         // Check for control characters
         if name.chars().any(|c| c.is_control()) {
             return Err(Error::InvalidRefName(format!(
-                "branch name cannot contain control characters: {}",
-                name
+                "{} name cannot contain control characters: {}",
+                kind, name
             )));
         }
 
@@ -1442,6 +2469,7 @@ impl Repository {
     /// let branch = repo.create_branch("hotfix", Some(oid)).unwrap();
     /// ```
     pub fn create_branch(&self, name: &str, target: Option<Oid>) -> Result<Branch> {
+        let _lock = self.lock_for_write();
         // Validate branch name
         Self::validate_branch_name(name)?;
 
@@ -1451,19 +2479,16 @@ impl Repository {
             None => *self.head()?.oid(),
         };
 
-        // Check if branch already exists
-        let branch_path = self.git_dir.join("refs/heads").join(name);
-        if branch_path.exists() {
-            return Err(Error::RefAlreadyExists(format!("refs/heads/{}", name)));
-        }
-
-        // Ensure parent directories exist (for nested branch names like feature/foo)
-        if let Some(parent) = branch_path.parent() {
-            fs::create_dir_all(parent)?;
+        // Check if branch already exists, loose or packed
+        let ref_name = format!("refs/heads/{}", name);
+        let store = self.ref_store();
+        if store.ref_exists(&ref_name)? {
+            return Err(Error::RefAlreadyExists(ref_name));
         }
 
-        // Write the branch ref file
-        write_file_atomic(&branch_path, format!("{}\n", target_oid.to_hex()).as_bytes())?;
+        // Write the branch ref, creating directories for nested names
+        // like feature/foo
+        store.write_ref(&ref_name, &RefValue::Direct(target_oid))?;
 
         Ok(Branch::new(name, target_oid))
     }
@@ -1492,6 +2517,7 @@ impl Repository {
     /// repo.delete_branch("feature/old-feature").unwrap();
     /// ```
     pub fn delete_branch(&self, name: &str) -> Result<()> {
+        let _lock = self.lock_for_write();
         // Check if this is the current branch
         let store = self.ref_store();
         if let Ok(Some(current)) = store.current_branch() {
@@ -1500,28 +2526,11 @@ impl Repository {
             }
         }
 
-        // Check if branch exists
-        let branch_path = self.git_dir.join("refs/heads").join(name);
-        if !branch_path.exists() {
-            return Err(Error::RefNotFound(format!("refs/heads/{}", name)));
-        }
-
-        // Delete the branch ref file
-        fs::remove_file(&branch_path)?;
-
-        // Clean up empty parent directories (for nested branch names)
-        let mut parent = branch_path.parent();
-        let refs_heads = self.git_dir.join("refs/heads");
-        while let Some(dir) = parent {
-            if dir == refs_heads {
-                break;
-            }
-            if dir.read_dir()?.next().is_none() {
-                fs::remove_dir(dir)?;
-            } else {
-                break;
-            }
-            parent = dir.parent();
+        // Delete the loose and packed refs, cleaning up the empty parent
+        // directories of nested branch names
+        let ref_name = format!("refs/heads/{}", name);
+        if !store.delete_ref(&ref_name)? {
+            return Err(Error::RefNotFound(ref_name));
         }
 
         Ok(())
@@ -1530,7 +2539,14 @@ impl Repository {
     /// Checks if the working tree has uncommitted changes.
     ///
     /// Returns `true` if there are modified, staged, or untracked files.
+    /// Without a working tree, only staged changes count.
     fn has_uncommitted_changes(&self) -> Result<bool> {
+        if self.memory.is_some() {
+            return match self.head() {
+                Ok(_) => Ok(!self.diff_head_to_index()?.is_empty()),
+                Err(_) => Ok(!self.read_index()?.is_empty()),
+            };
+        }
         let status = self.status()?;
         Ok(!status.is_empty())
     }
@@ -1538,6 +2554,8 @@ impl Repository {
     /// Checks out a branch or commit.
     ///
     /// This updates the working tree to match the target and updates HEAD.
+    /// An in-memory repository has no working tree, so only the index and
+    /// HEAD are updated.
     ///
     /// # Arguments
     ///
@@ -1566,6 +2584,7 @@ impl Repository {
     /// repo.checkout("abc1234").unwrap();
     /// ```
     pub fn checkout(&self, target: &str) -> Result<()> {
+        let _lock = self.lock_for_write();
         // Check for uncommitted changes
         if self.has_uncommitted_changes()? {
             return Err(Error::DirtyWorkingTree);
@@ -1575,15 +2594,15 @@ impl Repository {
 
         // Try to resolve as a branch first
         let branch_ref = format!("refs/heads/{}", target);
-        let (new_head_content, target_oid) = if let Ok(resolved) = store.resolve_recursive(&branch_ref) {
+        let (new_head, target_oid) = if let Ok(resolved) = store.resolve_recursive(&branch_ref) {
             // It's a branch - update HEAD to be symbolic
-            (format!("ref: {}\n", branch_ref), resolved.oid)
+            (RefValue::Symbolic(branch_ref), resolved.oid)
         } else if let Ok(resolved) = store.resolve(target) {
             // It's a known ref - detached HEAD
-            (format!("{}\n", resolved.oid.to_hex()), resolved.oid)
+            (RefValue::Direct(resolved.oid), resolved.oid)
         } else if let Ok(oid) = self.resolve_short_oid(target) {
             // It's a commit OID (full or short) - detached HEAD
-            (format!("{}\n", oid.to_hex()), oid)
+            (RefValue::Direct(oid), oid)
         } else {
             return Err(Error::RefNotFound(target.to_string()));
         };
@@ -1593,15 +2612,46 @@ impl Repository {
         let tree_oid = *commit.tree();
 
         // Update working tree and index
-        self.checkout_tree(&tree_oid)?;
+        if self.memory.is_some() {
+            self.checkout_index(&tree_oid)?;
+        } else {
+            self.checkout_tree(&tree_oid)?;
+        }
 
         // Update HEAD
-        let head_path = self.git_dir.join("HEAD");
-        write_file_atomic(&head_path, new_head_content.as_bytes())?;
+        store.write_ref("HEAD", &new_head)?;
 
         Ok(())
     }
 
+    /// Replaces the index with the entries of a tree object, for a
+    /// repository without a working tree.
+    fn checkout_index(&self, tree_oid: &Oid) -> Result<()> {
+        let store = self.object_store();
+        let mut files: BTreeMap<PathBuf, (Oid, FileMode)> = BTreeMap::new();
+        flatten_tree_with_modes(&store, tree_oid, Path::new(""), &mut files)?;
+
+        let mut idx = self.read_index()?.without_entries();
+        for (path, (oid, mode)) in files {
+            let (_, size) = store.read_header(&oid)?;
+            idx.add(IndexEntry::new(
+                0,
+                0,
+                0,
+                0,
+                mode,
+                0,
+                0,
+                size as u32,
+                oid,
+                path,
+                0,
+            ));
+        }
+
+        self.write_index(&idx)
+    }
+
     /// Updates the working tree and index to match a tree object.
     fn checkout_tree(&self, tree_oid: &Oid) -> Result<()> {
         let store = self.object_store();
@@ -1614,17 +2664,38 @@ impl Repository {
         });
 
         // Flatten both trees for comparison
-        let mut current_files: BTreeMap<PathBuf, Oid> = BTreeMap::new();
+        let mut current_files: BTreeMap<PathBuf, (Oid, FileMode)> = BTreeMap::new();
         if let Some(current_oid) = &current_tree {
-            flatten_tree(&store, current_oid, Path::new(""), &mut current_files)?;
+            flatten_tree_with_modes(&store, current_oid, Path::new(""), &mut current_files)?;
         }
 
-        let mut target_files: BTreeMap<PathBuf, Oid> = BTreeMap::new();
-        flatten_tree(&store, tree_oid, Path::new(""), &mut target_files)?;
+        let mut target_files: BTreeMap<PathBuf, (Oid, FileMode)> = BTreeMap::new();
+        flatten_tree_with_modes(&store, tree_oid, Path::new(""), &mut target_files)?;
 
-        // Remove files that exist in current but not in target
+        // Keep the previous index so that stat data of untouched files is preserved
+        let old_idx = self.read_index()?;
+        let sparse = self.sparse_checkout()?;
+        let skipped = |path: &Path| old_idx.get(path).is_some_and(IndexEntry::is_skip_worktree);
+        let excluded = |path: &Path| sparse.as_ref().is_some_and(|s| !s.includes(path));
+        let mut kept: HashSet<&Path> = HashSet::new();
+
+        // Remove files that exist in current but not in target (or that
+        // are now outside the sparse checkout)
         for path in current_files.keys() {
-            if !target_files.contains_key(path) {
+            if skipped(path) {
+                continue;
+            }
+            if !target_files.contains_key(path) || excluded(path) {
+                // Files leaving the sparse checkout with local changes are
+                // kept, as Git does
+                if target_files.contains_key(path) {
+                    if let Some(entry) = old_idx.get(path) {
+                        if entry_modified(&self.work_dir, entry, old_idx.timestamp())? {
+                            kept.insert(path);
+                            continue;
+                        }
+                    }
+                }
                 let full_path = self.work_dir.join(path);
                 if full_path.exists() {
                     fs::remove_file(&full_path)?;
@@ -1645,15 +2716,19 @@ impl Repository {
             }
         }
 
+        let mut written: HashSet<&Path> = HashSet::new();
+
         // Create/update files in target tree
-        for (path, oid) in &target_files {
+        for (path, (oid, mode)) in &target_files {
             let full_path = self.work_dir.join(path);
 
-            // Skip if file already has the correct content
-            if let Some(current_oid) = current_files.get(path) {
-                if current_oid == oid {
-                    continue;
-                }
+            if excluded(path) {
+                continue;
+            }
+
+            // Skip if file already has the correct content and mode
+            if current_files.get(path) == Some(&(*oid, *mode)) && !skipped(path) {
+                continue;
             }
 
             // Ensure parent directories exist
@@ -1663,42 +2738,57 @@ impl Repository {
 
             // Read blob content and write to working tree
             let raw = store.read(oid)?;
-            write_file_atomic(&full_path, &raw.content)?;
+            write_worktree_file(&full_path, &raw.content, *mode)?;
+            written.insert(path);
         }
 
-        // Rebuild index from target tree
-        let mut idx = Index::empty(2);
-        for (path, oid) in &target_files {
-            let full_path = self.work_dir.join(path);
-            let metadata = fs::metadata(&full_path)?;
-
-            let mtime = metadata
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs())
-                .unwrap_or(0);
-            let ctime = metadata
-                .created()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs())
-                .unwrap_or(mtime);
-
-            let raw = store.read(oid)?;
-            let entry = IndexEntry::new(
-                ctime,
-                mtime,
-                0,
-                0,
-                FileMode::Regular, // Simplified for now
-                0,
-                0,
-                raw.content.len() as u32,
-                *oid,
-                path.clone(),
-                0,
-            );
+        // Rebuild index from target tree, keeping the split-index link and
+        // extensions of the previous one
+        let mut idx = old_idx.without_entries();
+        for (path, (oid, mode)) in &target_files {
+            let mut entry = if written.contains(path.as_path()) {
+                // Freshly written: the stat data describes exactly this
+                // blob. A symbolic link keeps none, as stat data follows
+                // links.
+                let mut entry = IndexEntry::new(0, 0, 0, 0, *mode, 0, 0, 0, *oid, path.clone(), 0);
+                if *mode != FileMode::Symlink {
+                    entry.update_stat(&fs::metadata(self.work_dir.join(path))?);
+                }
+                entry
+            } else {
+                match old_idx.get(path) {
+                    // Untouched file: its old stat data is still accurate,
+                    // unless it was racily clean in the old index
+                    Some(old) if old.oid() == oid && old.mode() == *mode => {
+                        let mut entry = old.clone();
+                        let racy = old_idx.timestamp().is_some_and(|timestamp| {
+                            racily_modified(&self.work_dir, old, timestamp).unwrap_or(true)
+                        });
+                        if racy {
+                            entry.smudge();
+                        }
+                        entry
+                    }
+                    // Unknown state: leave stat data empty to force hashing
+                    _ => {
+                        let raw = store.read(oid)?;
+                        IndexEntry::new(
+                            0,
+                            0,
+                            0,
+                            0,
+                            *mode,
+                            0,
+                            0,
+                            raw.content.len() as u32,
+                            *oid,
+                            path.clone(),
+                            0,
+                        )
+                    }
+                }
+            };
+            entry.set_skip_worktree(excluded(path) && !kept.contains(path.as_path()));
             idx.add(entry);
         }
 
@@ -1840,6 +2930,268 @@ impl Repository {
 
         Ok(result)
     }
+
+    /// Creates a lightweight tag pointing to the specified object.
+    ///
+    /// This is equivalent to `git tag <name> [<target>]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the tag to create (without `refs/tags/` prefix).
+    /// * `target` - The object OID to point to. If `None`, uses current HEAD.
+    ///
+    /// # Returns
+    ///
+    /// The created `Tag` on success.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::InvalidRefName` if the tag name is invalid.
+    /// - `Error::RefAlreadyExists` if a tag with this name already exists.
+    /// - `Error::ObjectNotFound` if the target object does not exist.
+    /// - `Error::RefNotFound` if HEAD cannot be resolved (when target is None).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zerogit::Repository;
+    ///
+    /// let repo = Repository::in_memory();
+    /// repo.add_content("a.txt", b"a\n").unwrap();
+    /// let oid = repo.create_commit("Add a", "Test User", "test@example.com").unwrap();
+    ///
+    /// let tag = repo.create_tag("v1.0", None).unwrap();
+    /// assert_eq!(tag.target(), &oid);
+    /// assert_eq!(repo.tags().unwrap()[0].name(), "v1.0");
+    /// ```
+    pub fn create_tag(&self, name: &str, target: Option<Oid>) -> Result<Tag> {
+        let _lock = self.lock_for_write();
+        Self::validate_ref_name("tag", name)?;
+
+        let target_oid = match target {
+            Some(oid) => oid,
+            None => *self.head()?.oid(),
+        };
+        if !self.object_store().exists(&target_oid) {
+            return Err(Error::ObjectNotFound(target_oid.to_hex()));
+        }
+
+        let ref_name = format!("refs/tags/{}", name);
+        let store = self.ref_store();
+        if store.ref_exists(&ref_name)? {
+            return Err(Error::RefAlreadyExists(ref_name));
+        }
+        store.write_ref(&ref_name, &RefValue::Direct(target_oid))?;
+
+        Ok(Tag::lightweight(name, target_oid))
+    }
+}
+
+/// Returns `core.bigFileThreshold`: files larger than this are streamed
+/// into the object store instead of being read into memory, and packed
+/// without deltas.
+pub(crate) fn big_file_threshold(config: &crate::config::Config) -> Result<u64> {
+    match config.get("core", "bigFileThreshold") {
+        Some(_) => Ok(config.get_int("core", "bigFileThreshold")?.max(0) as u64),
+        None => Ok(DEFAULT_BIG_FILE_THRESHOLD),
+    }
+}
+
+/// Returns the `core.fsync` and `core.fsyncMethod` settings.
+///
+/// Without `core.fsync`, every component is flushed, including the loose
+/// objects and index that Git's default (`committed,-loose-object`)
+/// leaves out; unknown components and methods are ignored.
+fn fsync_settings(config: &crate::config::Config) -> FsyncSettings {
+    let mut settings = FsyncSettings::default();
+    if let Some(components) = config.get("core", "fsync") {
+        settings = settings.components(components);
+    }
+    if let Some(method) = config
+        .get("core", "fsyncMethod")
+        .and_then(FsyncMethod::from_name)
+    {
+        settings = settings.method(method);
+    }
+    settings
+}
+
+/// Returns true if a racily clean entry no longer matches its file even
+/// though its stat data does, so it must be smudged before the index is
+/// written again.
+///
+/// Only tracked regular files are checked; the file is hashed only if the
+/// entry is racily clean against `timestamp` and its stat data matches.
+fn racily_modified(work_dir: &Path, entry: &IndexEntry, timestamp: (u64, u32)) -> Result<bool> {
+    if entry.stage() != 0
+        || entry.size() == 0
+        || entry.is_assume_valid()
+        || entry.is_skip_worktree()
+        || entry.is_intent_to_add()
+        || !matches!(entry.mode(), FileMode::Regular | FileMode::Executable)
+        || !entry.is_racily_clean(timestamp)
+    {
+        return Ok(false);
+    }
+    match fs::symlink_metadata(work_dir.join(entry.path())) {
+        Ok(metadata) if entry.stat_matches(&metadata) => {
+            file_modified(work_dir, entry.path(), entry.oid())
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Checks that a path can be staged: relative to the working tree, with no
+/// `.`, `..` or `.git` components.
+///
+/// # Errors
+///
+/// Returns `Error::PathNotFound` for a path that cannot be staged, as
+/// `safe_join` does for paths that leave the working tree.
+fn check_index_path(path: &Path) -> Result<()> {
+    let valid = !path.as_os_str().is_empty()
+        && path.components().all(|component| match component {
+            Component::Normal(name) => !name.eq_ignore_ascii_case(".git"),
+            _ => false,
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::PathNotFound(path.to_path_buf()))
+    }
+}
+
+/// Writes a working tree file as a blob, streaming it if it is larger
+/// than `threshold` bytes.
+fn write_blob_from_file(
+    store: &LooseObjectStore,
+    full_path: &Path,
+    metadata: &fs::Metadata,
+    threshold: u64,
+) -> Result<Oid> {
+    if metadata.len() > threshold {
+        let file = fs::File::open(full_path)?;
+        store.write_stream(ObjectType::Blob, metadata.len(), file)
+    } else {
+        store.write(ObjectType::Blob, &read_file(full_path)?)
+    }
+}
+
+/// Writes a blob to the working tree as a file of the given mode: an
+/// executable file, or a symbolic link to the path the blob holds where
+/// symbolic links are supported.
+pub(crate) fn write_worktree_file(full_path: &Path, content: &[u8], mode: FileMode) -> Result<()> {
+    #[cfg(unix)]
+    if mode == FileMode::Symlink {
+        let temp = crate::infra::fs::temp_path(full_path);
+        std::os::unix::fs::symlink(path::from_bytes(content), &temp)?;
+        if let Err(e) = fs::rename(&temp, full_path) {
+            let _ = fs::remove_file(&temp);
+            return Err(e.into());
+        }
+        return Ok(());
+    }
+    write_file_atomic(full_path, content)?;
+    #[cfg(unix)]
+    if mode == FileMode::Executable {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = fs::metadata(full_path)?.permissions();
+        permissions.set_mode(permissions.mode() | 0o111);
+        fs::set_permissions(full_path, permissions)?;
+    }
+    Ok(())
+}
+
+/// Returns the index format version requested by `index.version`.
+///
+/// Like Git, a value outside 2-4 is ignored.
+fn configured_index_version(config: &crate::config::Config) -> Option<u32> {
+    config
+        .get("index", "version")
+        .and_then(|value| value.trim().parse::<u32>().ok())
+        .filter(|version| (2..=4).contains(version))
+}
+
+/// Returns true if too many entries of a split index are not in the
+/// shared index, according to `splitIndex.maxPercentChange`.
+///
+/// Like Git, 0 always writes a new shared index, 100 never does, and
+/// values outside 0-100 fall back to the default of 20.
+fn too_many_not_shared(idx: &Index, config: &crate::config::Config) -> Result<bool> {
+    let max_percent = match config.get("splitIndex", "maxPercentChange") {
+        Some(_) => config.get_int("splitIndex", "maxPercentChange")?,
+        None => -1,
+    };
+    let max_percent = match max_percent {
+        0 => return Ok(true),
+        100 => return Ok(false),
+        1..=99 => max_percent,
+        _ => 20,
+    };
+    Ok((idx.len() as i64) * max_percent < (idx.not_shared_count() as i64) * 100)
+}
+
+/// Returns how old an unreferenced shared index must be before it is
+/// deleted, according to `splitIndex.sharedIndexExpire`, or `None` if
+/// shared indexes never expire.
+///
+/// Like Git, the value is `now`, `never`, or a relative date such as
+/// `2.weeks.ago` (the default).
+fn shared_index_expire(config: &crate::config::Config) -> Result<Option<Duration>> {
+    const DEFAULT_EXPIRE: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+    let Some(value) = config.get("splitIndex", "sharedIndexExpire") else {
+        return Ok(Some(DEFAULT_EXPIRE));
+    };
+    parse_expiry(value).ok_or_else(|| {
+        Error::ConfigNotFound(format!("invalid splitIndex.sharedIndexExpire: {}", value))
+    })
+}
+
+/// Parses an expiry date of the form `now`, `never` or `<n>.<unit>.ago`
+/// (also written with spaces), returning how far in the past it is.
+fn parse_expiry(value: &str) -> Option<Option<Duration>> {
+    let value = value.trim().to_ascii_lowercase();
+    match value.as_str() {
+        "now" => return Some(Some(Duration::ZERO)),
+        "never" | "false" => return Some(None),
+        _ => {}
+    }
+    let words: Vec<&str> = value
+        .split(['.', ' '])
+        .filter(|word| !word.is_empty())
+        .collect();
+    let [count, unit, "ago"] = words.as_slice() else {
+        return None;
+    };
+    let count: u64 = count.parse().ok()?;
+    let seconds = match unit.strip_suffix('s').unwrap_or(unit) {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 24 * 60 * 60,
+        "week" => 7 * 24 * 60 * 60,
+        "month" => 30 * 24 * 60 * 60,
+        "year" => 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Some(Duration::from_secs(count.checked_mul(seconds)?)))
+}
+
+/// Maps a reference through a fetch refspec (e.g. `+refs/heads/*:refs/remotes/origin/*`).
+///
+/// Returns the destination reference, or `None` if the refspec doesn't
+/// match.
+fn map_refspec(refspec: &str, name: &str) -> Option<String> {
+    let refspec = refspec.strip_prefix('+').unwrap_or(refspec);
+    let (src, dst) = refspec.split_once(':')?;
+    match (src.split_once('*'), dst.split_once('*')) {
+        (Some((src_prefix, src_suffix)), Some((dst_prefix, dst_suffix))) => {
+            let matched = name.strip_prefix(src_prefix)?.strip_suffix(src_suffix)?;
+            Some(format!("{}{}{}", dst_prefix, matched, dst_suffix))
+        }
+        (None, None) if src == name && !dst.is_empty() => Some(dst.to_string()),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
@@ -2011,7 +3363,7 @@ mod tests {
     // Object retrieval tests (RP-010 to RP-013, RP-030 to RP-034)
     // =========================================================================
 
-    use crate::infra::hash_object;
+    use crate::infra::hash::hash_object;
     use miniz_oxide::deflate::compress_to_vec_zlib;
 
     /// Helper to create a loose object in the .git/objects directory.
@@ -2021,7 +3373,7 @@ mod tests {
         let mut raw = header.into_bytes();
         raw.extend_from_slice(content);
 
-        let oid = Oid::from_bytes(hash_object(object_type, content));
+        let oid = Oid::from_bytes(hash_object(object_type, content).unwrap());
         let compressed = compress_to_vec_zlib(&raw, 6);
 
         let hex = oid.to_hex();
@@ -2658,6 +4010,105 @@ mod tests {
         assert!(src_tree.get("main.rs").is_some());
     }
 
+    // Additional: create_commit records and reuses the cache-tree
+    #[test]
+    fn test_create_commit_uses_cache_tree() {
+        let temp = TempDir::new().unwrap();
+        create_git_dir(temp.path());
+        let git_dir = temp.path().join(".git");
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+
+        let repo = Repository::open(temp.path()).unwrap();
+        fs::create_dir_all(temp.path().join("src")).unwrap();
+        fs::write(temp.path().join("README.md"), "# Test").unwrap();
+        fs::write(temp.path().join("src/main.rs"), "fn main() {}").unwrap();
+        repo.add("README.md").unwrap();
+        repo.add("src/main.rs").unwrap();
+        let commit_oid = repo
+            .create_commit("Add files", "Test User", "test@example.com")
+            .unwrap();
+
+        // The written index caches every tree of the commit
+        let commit = repo.commit(&commit_oid.to_hex()).unwrap();
+        let tree = repo.tree(&commit.tree().to_hex()).unwrap();
+        let src_oid = *tree.get("src").unwrap().oid();
+        let idx = repo.read_index().unwrap();
+        let cache_tree = idx.cache_tree().unwrap();
+        assert_eq!(cache_tree.oid(), Some(commit.tree()));
+        assert_eq!(cache_tree.entry_count(), Some(2));
+        assert_eq!(
+            cache_tree.find(Path::new("src")).unwrap().oid(),
+            Some(&src_oid)
+        );
+
+        // Changing README.md only invalidates the root, so the src tree is
+        // taken from the cache rather than written again
+        let hex = src_oid.to_hex();
+        let src_object = git_dir.join("objects").join(&hex[..2]).join(&hex[2..]);
+        fs::remove_file(&src_object).unwrap();
+        fs::write(temp.path().join("README.md"), "# Changed").unwrap();
+        repo.add("README.md").unwrap();
+        let idx = repo.read_index().unwrap();
+        let cache_tree = idx.cache_tree().unwrap();
+        assert!(!cache_tree.is_valid());
+        assert!(cache_tree.find(Path::new("src")).unwrap().is_valid());
+
+        let commit_oid = repo
+            .create_commit("Change README", "Test User", "test@example.com")
+            .unwrap();
+        let commit = repo.commit(&commit_oid.to_hex()).unwrap();
+        let raw = repo.object_store().read(commit.tree()).unwrap();
+        let tree = Tree::parse(raw).unwrap();
+        assert_eq!(tree.get("src").unwrap().oid(), &src_oid);
+        assert!(!src_object.exists());
+    }
+
+    // Additional: write_index smudges racily clean entries whose file changed
+    #[test]
+    fn test_write_index_smudges_racily_clean_entries() {
+        let temp = TempDir::new().unwrap();
+        create_git_dir(temp.path());
+        fs::create_dir_all(temp.path().join(".git/refs/heads")).unwrap();
+        let repo = Repository::open(temp.path()).unwrap();
+        let file = temp.path().join("racy.txt");
+
+        // racy.txt is staged as "aaaa", then rewritten at the same size in
+        // the same timestamp tick, so its stat data still matches
+        let oid = repo
+            .object_store()
+            .write(ObjectType::Blob, b"aaaa")
+            .unwrap();
+        fs::write(&file, "bbbb").unwrap();
+        let metadata = fs::symlink_metadata(&file).unwrap();
+        let mut idx = Index::empty(2);
+        idx.add(IndexEntry::from_metadata(
+            PathBuf::from("racy.txt"),
+            oid,
+            &metadata,
+        ));
+        idx.set_timestamp(&metadata);
+
+        // Rewriting the index moves its time past the entry, so the entry
+        // is smudged to keep it from looking clean
+        repo.write_index(&idx).unwrap();
+        let idx = repo.read_index().unwrap();
+        let entry = idx.get(Path::new("racy.txt")).unwrap();
+        assert_eq!(entry.size(), 0);
+        assert_eq!(entry.oid(), &oid);
+        let status = repo.status().unwrap();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].path(), Path::new("racy.txt"));
+        assert_eq!(status[0].status(), crate::status::FileStatus::Modified);
+
+        // An unchanged racily clean entry keeps its stat data
+        repo.add("racy.txt").unwrap();
+        let mut idx = repo.read_index().unwrap();
+        idx.set_timestamp(&fs::symlink_metadata(&file).unwrap());
+        repo.write_index(&idx).unwrap();
+        let idx = repo.read_index().unwrap();
+        assert_eq!(idx.get(Path::new("racy.txt")).unwrap().size(), 4);
+    }
+
     // Additional: create_commit chain (parent linking)
     #[test]
     fn test_create_commit_chain() {
@@ -2721,7 +4172,7 @@ mod tests {
     fn test_build_tree_content() {
         let oid = Oid::from_hex("da39a3ee5e6b4b0d3255bfef95601890afd80709").unwrap();
         let entries = vec![
-            ("file.txt".to_string(), FileMode::Regular, oid),
+            (b"file.txt".to_vec(), FileMode::Regular, oid),
         ];
 
         let content = Repository::build_tree_content(&entries);
@@ -3086,6 +4537,77 @@ mod tests {
         assert!(matches!(result, Err(Error::DirtyWorkingTree)));
     }
 
+    // Additional: checkout and sparse checkout write executable files and
+    // symbolic links with their modes
+    #[cfg(unix)]
+    #[test]
+    fn test_checkout_file_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        let work_dir = temp.path();
+        let repo = Repository::init(work_dir).unwrap();
+        fs::write(work_dir.join("README.md"), "readme\n").unwrap();
+        repo.add("README.md").unwrap();
+        let first = repo
+            .create_commit("First commit", "Test User", "test@example.com")
+            .unwrap();
+        repo.create_branch("first", Some(first)).unwrap();
+
+        let script = work_dir.join("bin/run.sh");
+        fs::create_dir(work_dir.join("bin")).unwrap();
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        repo.add("bin/run.sh").unwrap();
+        repo.create_commit("Add script", "Test User", "test@example.com")
+            .unwrap();
+        let mode = |path: &str| {
+            repo.read_index()
+                .unwrap()
+                .get(Path::new(path))
+                .unwrap()
+                .mode()
+        };
+        let executable = || fs::metadata(&script).unwrap().permissions().mode() & 0o111 != 0;
+
+        repo.checkout("first").unwrap();
+        assert!(!work_dir.join("bin").exists());
+        repo.checkout("main").unwrap();
+        assert!(executable());
+        assert_eq!(mode("bin/run.sh"), FileMode::Executable);
+        assert!(repo.status().unwrap().is_empty());
+
+        // Files leaving and re-entering the sparse checkout
+        let link = work_dir.join("bin/link");
+        std::os::unix::fs::symlink("run.sh", &link).unwrap();
+        let target = repo
+            .object_store()
+            .write(ObjectType::Blob, b"run.sh")
+            .unwrap();
+        let mut idx = repo.read_index().unwrap();
+        idx.add(IndexEntry::new(
+            0,
+            0,
+            0,
+            0,
+            FileMode::Symlink,
+            0,
+            0,
+            6,
+            target,
+            PathBuf::from("bin/link"),
+            0,
+        ));
+        repo.write_index(&idx).unwrap();
+        repo.sparse_checkout_set(Vec::<&str>::new(), true).unwrap();
+        assert!(!work_dir.join("bin").exists());
+        repo.sparse_checkout_set(["bin"], true).unwrap();
+        assert!(executable());
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("run.sh"));
+        assert_eq!(mode("bin/run.sh"), FileMode::Executable);
+        assert_eq!(mode("bin/link"), FileMode::Symlink);
+    }
+
     // Additional: checkout nonexistent target returns RefNotFound
     #[test]
     fn test_checkout_nonexistent() {
@@ -3121,4 +4643,85 @@ mod tests {
         assert!(Repository::validate_branch_name("foo[bar").is_err());
         assert!(Repository::validate_branch_name("foo\\bar").is_err());
     }
+
+    // Additional: map_refspec tests
+    #[test]
+    fn test_map_refspec() {
+        let fetch = "+refs/heads/*:refs/remotes/origin/*";
+        assert_eq!(
+            map_refspec(fetch, "refs/heads/main"),
+            Some("refs/remotes/origin/main".to_string())
+        );
+        assert_eq!(
+            map_refspec(fetch, "refs/heads/feature/x"),
+            Some("refs/remotes/origin/feature/x".to_string())
+        );
+        assert_eq!(map_refspec(fetch, "refs/tags/v1"), None);
+        assert_eq!(
+            map_refspec("refs/heads/main:refs/remotes/up/main", "refs/heads/main"),
+            Some("refs/remotes/up/main".to_string())
+        );
+        assert_eq!(map_refspec("refs/heads/main", "refs/heads/main"), None);
+    }
+
+    // Additional: handles share one multi-pack-index, which is dropped once
+    // a pack it covers is removed
+    #[test]
+    fn test_multi_pack_index_shared_by_handles() {
+        let temp = TempDir::new().unwrap();
+        let repo = Repository::init(temp.path()).unwrap();
+        let pack_dir = repo.git_dir().join("objects/pack");
+        fs::create_dir_all(&pack_dir).unwrap();
+        let fixture =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/midx/.git/objects/pack");
+        for entry in fs::read_dir(fixture).unwrap() {
+            let entry = entry.unwrap();
+            fs::copy(entry.path(), pack_dir.join(entry.file_name())).unwrap();
+        }
+        let handle = repo.clone_handle();
+        let midx = repo.object_store().multi_pack_index().unwrap().unwrap();
+        let oids = handle.object_store().list_objects().unwrap();
+        for store in [repo.object_store(), handle.object_store()] {
+            for oid in &oids {
+                store.read_header(oid).unwrap();
+            }
+            let shared = store.multi_pack_index().unwrap().unwrap();
+            assert!(Arc::ptr_eq(&midx, &shared));
+        }
+
+        // A lookup that misses lists the packs again
+        let removed =
+            crate::objects::Pack::open(pack_dir.join(&midx.pack_names()[0]), ObjectFormat::Sha1)
+                .unwrap();
+        let removed_oids = removed.oids().unwrap();
+        crate::infra::remove_file(removed.index_path()).unwrap();
+        crate::infra::remove_file(removed.pack_path()).unwrap();
+        let missing = Oid::from_hex("0000000000000000000000000000000000000001").unwrap();
+        assert!(matches!(
+            repo.object_store().read_header(&missing),
+            Err(Error::ObjectNotFound(_))
+        ));
+        assert!(handle.object_store().multi_pack_index().unwrap().is_none());
+        for oid in oids.iter().filter(|oid| !removed_oids.contains(oid)) {
+            handle.object_store().read_header(oid).unwrap();
+        }
+    }
+
+    // Additional: parse_expiry accepts the forms splitIndex.sharedIndexExpire uses
+    #[test]
+    fn test_parse_expiry() {
+        let day = 24 * 60 * 60;
+        assert_eq!(parse_expiry("now"), Some(Some(Duration::ZERO)));
+        assert_eq!(parse_expiry("never"), Some(None));
+        assert_eq!(
+            parse_expiry("2.weeks.ago"),
+            Some(Some(Duration::from_secs(14 * day)))
+        );
+        assert_eq!(
+            parse_expiry("1 day ago"),
+            Some(Some(Duration::from_secs(day)))
+        );
+        assert_eq!(parse_expiry("2.weeks"), None);
+        assert_eq!(parse_expiry("soon"), None);
+    }
 }
@@ -0,0 +1,439 @@
+//! Sparse checkout (`git sparse-checkout`).
+//!
+//! The sparse set is stored in `.git/info/sparse-checkout` and enabled by
+//! `core.sparseCheckout`. Index entries outside it carry the skip-worktree
+//! bit and have no file in the working tree.
+//!
+//! In cone mode (`core.sparseCheckoutCone`), the set is a list of
+//! directories: every file below them is included, as are the files
+//! directly inside their parent directories and at the top level. Without
+//! cone mode, the file holds `.gitignore`-style patterns: the last pattern
+//! matching a path (or failing that, one of its parent directories)
+//! decides, and paths no pattern matches are excluded.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::config::set_in_file;
+use crate::error::Result;
+use crate::ignore::{path_to_slash, IgnoreRules};
+use crate::index::IndexEntry;
+use crate::infra::{read_file, write_file_atomic};
+use crate::objects::FileMode;
+use crate::repository::write_worktree_file;
+use crate::staging::remove_empty_parents;
+use crate::status::entry_modified;
+use crate::Repository;
+
+/// Characters escaped with a backslash in cone mode patterns.
+const GLOB_SPECIAL: &[char] = &['*', '?', '[', '\\'];
+
+/// The set of paths included in a sparse checkout.
+#[derive(Debug, Clone)]
+pub(crate) struct SparseCheckout {
+    patterns: Patterns,
+}
+
+#[derive(Debug, Clone)]
+enum Patterns {
+    /// Directories included recursively, and their parent directories
+    /// (whose files are included, but not their subdirectories).
+    Cone {
+        recursive: BTreeSet<String>,
+        parents: BTreeSet<String>,
+    },
+    /// Patterns in `.gitignore` syntax.
+    NonCone {
+        lines: Vec<String>,
+        rules: IgnoreRules,
+    },
+}
+
+impl SparseCheckout {
+    /// Creates a cone mode sparse set from directory names.
+    ///
+    /// Leading and trailing slashes are ignored, and directories inside
+    /// another listed directory are dropped.
+    pub(crate) fn cone<I, S>(dirs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let dirs: BTreeSet<String> = dirs
+            .into_iter()
+            .map(|dir| dir.as_ref().trim_matches('/').to_string())
+            .filter(|dir| !dir.is_empty())
+            .collect();
+        let recursive: BTreeSet<String> = dirs
+            .iter()
+            .filter(|dir| !parent_dirs(dir).any(|parent| dirs.contains(parent)))
+            .cloned()
+            .collect();
+        let parents = recursive
+            .iter()
+            .flat_map(|dir| parent_dirs(dir))
+            .map(str::to_string)
+            .collect();
+
+        SparseCheckout {
+            patterns: Patterns::Cone { recursive, parents },
+        }
+    }
+
+    /// Creates a sparse set from `.gitignore`-style patterns.
+    pub(crate) fn non_cone<I, S>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let lines: Vec<String> = patterns
+            .into_iter()
+            .map(|line| line.as_ref().to_string())
+            .collect();
+        let mut rules = IgnoreRules::new();
+        rules.add_patterns(&lines.join("\n"), "");
+
+        SparseCheckout {
+            patterns: Patterns::NonCone { lines, rules },
+        }
+    }
+
+    /// Parses the contents of `.git/info/sparse-checkout`.
+    ///
+    /// In cone mode, a file that does not have the cone mode shape is
+    /// read as plain patterns, as Git does.
+    pub(crate) fn parse(content: &str, cone: bool) -> Self {
+        cone.then(|| Self::parse_cone(content))
+            .flatten()
+            .unwrap_or_else(|| Self::non_cone(content.lines()))
+    }
+
+    fn parse_cone(content: &str) -> Option<Self> {
+        let mut positive = BTreeSet::new();
+        let mut negative = BTreeSet::new();
+        for line in content.lines() {
+            let line = line.trim_end_matches(['\r', ' ']);
+            if line.is_empty() || line.starts_with('#') || line == "/*" || line == "!/*/" {
+                continue;
+            }
+            if let Some(dir) = line.strip_prefix("!/").and_then(|l| l.strip_suffix("/*/")) {
+                negative.insert(unescape(dir)?);
+            } else if let Some(dir) = line.strip_prefix('/').and_then(|l| l.strip_suffix('/')) {
+                positive.insert(unescape(dir)?);
+            } else {
+                return None;
+            }
+        }
+
+        let mut sparse = Self::cone(positive.difference(&negative));
+        if let Patterns::Cone { parents, .. } = &mut sparse.patterns {
+            parents.extend(positive.intersection(&negative).cloned());
+        }
+        Some(sparse)
+    }
+
+    /// Returns true if this is a cone mode sparse set.
+    pub(crate) fn is_cone(&self) -> bool {
+        matches!(self.patterns, Patterns::Cone { .. })
+    }
+
+    /// Returns the contents to write to `.git/info/sparse-checkout`.
+    pub(crate) fn to_file_content(&self) -> String {
+        match &self.patterns {
+            Patterns::Cone { recursive, parents } => {
+                let mut content = String::from("/*\n!/*/\n");
+                for dir in parents {
+                    let dir = escape(dir);
+                    content.push_str(&format!("/{}/\n!/{}/*/\n", dir, dir));
+                }
+                for dir in recursive {
+                    content.push_str(&format!("/{}/\n", escape(dir)));
+                }
+                content
+            }
+            Patterns::NonCone { lines, .. } => {
+                lines.iter().map(|line| format!("{}\n", line)).collect()
+            }
+        }
+    }
+
+    /// Returns true if a file is inside the sparse set.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file path, relative to the repository root.
+    pub(crate) fn includes(&self, path: &Path) -> bool {
+        match &self.patterns {
+            Patterns::Cone { recursive, parents } => {
+                let path = path_to_slash(path);
+                let Some((parent, _)) = path.rsplit_once('/') else {
+                    return true;
+                };
+                parents.contains(parent)
+                    || std::iter::once(parent)
+                        .chain(parent_dirs(parent))
+                        .any(|dir| recursive.contains(dir))
+            }
+            Patterns::NonCone { rules, .. } => {
+                if let Some(included) = rules.last_match(path, false) {
+                    return included;
+                }
+                path.ancestors()
+                    .skip(1)
+                    .take_while(|dir| !dir.as_os_str().is_empty())
+                    .find_map(|dir| rules.last_match(dir, true))
+                    .unwrap_or(false)
+            }
+        }
+    }
+}
+
+/// Returns the proper parent directories of a `/`-separated path, deepest
+/// first.
+fn parent_dirs(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('/').rev().map(move |(i, _)| &path[..i])
+}
+
+/// Escapes the glob characters of a directory name.
+fn escape(dir: &str) -> String {
+    let mut escaped = String::with_capacity(dir.len());
+    for c in dir.chars() {
+        if GLOB_SPECIAL.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Removes the escaping of a cone mode directory name, or returns `None`
+/// if it contains an unescaped glob character.
+fn unescape(dir: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(dir.len());
+    let mut chars = dir.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next()?),
+            c if GLOB_SPECIAL.contains(&c) => return None,
+            c => unescaped.push(c),
+        }
+    }
+    (!unescaped.is_empty()).then_some(unescaped)
+}
+
+impl Repository {
+    /// Restricts the working tree to a sparse set of paths.
+    ///
+    /// This is equivalent to `git sparse-checkout set` (with `--cone` or
+    /// `--no-cone`). The set is written to `.git/info/sparse-checkout` and
+    /// `core.sparseCheckout` / `core.sparseCheckoutCone` are set (in
+    /// `.git/config.worktree` if `extensions.worktreeConfig` is enabled).
+    /// Tracked files outside the set are removed from the working tree and
+    /// their index entries get the skip-worktree bit; files with unstaged
+    /// changes are left in place. Files that come back into the set are
+    /// checked out again, with their executable bit or as symbolic links.
+    ///
+    /// While sparse checkout is enabled, [`checkout`](Repository::checkout)
+    /// does not write files outside the set, [`status`](Repository::status)
+    /// reports skip-worktree entries as unmodified, and
+    /// [`add`](Repository::add) refuses paths outside the set.
+    ///
+    /// # Arguments
+    ///
+    /// * `patterns` - In cone mode, the directories to include (files at
+    ///   the top level are always included); otherwise, patterns in
+    ///   `.gitignore` syntax selecting the files to include.
+    /// * `cone` - Whether to use cone mode.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` on success.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::Io` if the working tree, the index or the configuration
+    ///   cannot be updated.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::Repository;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// repo.sparse_checkout_set(["src/core", "docs"], true).unwrap();
+    /// ```
+    pub fn sparse_checkout_set<I, S>(&self, patterns: I, cone: bool) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.require_on_disk("sparse checkout")?;
+        let _lock = self.lock_for_write();
+        let sparse = if cone {
+            SparseCheckout::cone(patterns)
+        } else {
+            SparseCheckout::non_cone(patterns)
+        };
+
+        let info_dir = self.git_dir().join("info");
+        fs::create_dir_all(&info_dir)?;
+        write_file_atomic(
+            info_dir.join("sparse-checkout"),
+            sparse.to_file_content().as_bytes(),
+        )?;
+
+        let worktree_config = self
+            .config()?
+            .get_bool("extensions", "worktreeConfig")
+            .unwrap_or(false);
+        let config_path = self.git_dir().join(if worktree_config {
+            "config.worktree"
+        } else {
+            "config"
+        });
+        set_in_file(&config_path, "core", "sparseCheckout", "true")?;
+        set_in_file(
+            &config_path,
+            "core",
+            "sparseCheckoutCone",
+            if sparse.is_cone() { "true" } else { "false" },
+        )?;
+
+        let mut idx = self.read_index()?;
+        let timestamp = idx.timestamp();
+        let store = self.object_store();
+        let work_dir = self.path();
+        for entry in idx.iter_mut() {
+            if entry.is_conflicted() {
+                continue;
+            }
+            let full_path = work_dir.join(entry.path());
+            let included = sparse.includes(entry.path());
+
+            if included && entry.is_skip_worktree() {
+                entry.set_skip_worktree(false);
+                // Never overwrite a file that is already there
+                if fs::symlink_metadata(&full_path).is_ok() {
+                    continue;
+                }
+                if let Some(parent) = full_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let raw = store.read(entry.oid())?;
+                write_worktree_file(&full_path, &raw.content, entry.mode())?;
+                if entry.mode() != FileMode::Symlink {
+                    entry.update_stat(&fs::metadata(&full_path)?);
+                }
+            } else if !included && !entry.is_skip_worktree() {
+                match fs::symlink_metadata(&full_path) {
+                    Ok(metadata) if !metadata.is_dir() => {
+                        // Keep files with local changes, as Git does
+                        if entry_modified(work_dir, entry, timestamp)? {
+                            continue;
+                        }
+                        fs::remove_file(&full_path)?;
+                        remove_empty_parents(work_dir, entry.path());
+                    }
+                    _ => {}
+                }
+                entry.set_skip_worktree(true);
+            }
+        }
+
+        self.write_index(&idx)
+    }
+
+    /// Returns the sparse set, if sparse checkout is enabled.
+    pub(crate) fn sparse_checkout(&self) -> Result<Option<SparseCheckout>> {
+        let config = self.config()?;
+        if !config.get_bool("core", "sparseCheckout").unwrap_or(false) {
+            return Ok(None);
+        }
+        let Ok(data) = read_file(self.git_dir().join("info").join("sparse-checkout")) else {
+            return Ok(None);
+        };
+        let cone = config
+            .get_bool("core", "sparseCheckoutCone")
+            .unwrap_or(false);
+
+        Ok(Some(SparseCheckout::parse(
+            &String::from_utf8_lossy(&data),
+            cone,
+        )))
+    }
+}
+
+/// Returns true if an index entry lies outside the sparse checkout: it has
+/// the skip-worktree bit, or the sparse set (if any) excludes its path.
+pub(crate) fn outside_sparse_set(sparse: Option<&SparseCheckout>, entry: &IndexEntry) -> bool {
+    entry.is_skip_worktree() || sparse.is_some_and(|s| !s.includes(entry.path()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn includes(sparse: &SparseCheckout, path: &str) -> bool {
+        sparse.includes(Path::new(path))
+    }
+
+    // SP-001: Cone mode includes the top level, recursive directories and
+    // the files of their parents
+    #[test]
+    fn test_cone_includes() {
+        let sparse = SparseCheckout::cone(["a/b/", "/c", "a/b/d"]);
+        assert!(includes(&sparse, "README"));
+        assert!(includes(&sparse, "a/file"));
+        assert!(includes(&sparse, "a/b/file"));
+        assert!(includes(&sparse, "a/b/d/e/file"));
+        assert!(includes(&sparse, "c/x/y"));
+        assert!(!includes(&sparse, "a/other/file"));
+        assert!(!includes(&sparse, "ab/file"));
+        assert!(!includes(&sparse, "d/file"));
+    }
+
+    // SP-002: Cone mode patterns are written as Git writes them and parse
+    // back to the same set
+    #[test]
+    fn test_cone_file_roundtrip() {
+        let sparse = SparseCheckout::cone(["a/b", "c", "x*y"]);
+        let content = sparse.to_file_content();
+        assert_eq!(content, "/*\n!/*/\n/a/\n!/a/*/\n/a/b/\n/c/\n/x\\*y/\n");
+
+        let parsed = SparseCheckout::parse(&content, true);
+        assert!(parsed.is_cone());
+        assert_eq!(parsed.to_file_content(), content);
+        assert!(includes(&parsed, "x*y/file"));
+        assert!(!includes(&parsed, "xzy/file"));
+    }
+
+    // SP-003: Files that do not have the cone shape are read as patterns
+    #[test]
+    fn test_cone_fallback() {
+        let parsed = SparseCheckout::parse("/*\n!/*/\n*.md\n", true);
+        assert!(!parsed.is_cone());
+        assert!(includes(&parsed, "docs/guide.md"));
+        assert!(!includes(&parsed, "docs/guide.txt"));
+    }
+
+    // SP-004: Without cone mode, the last matching pattern decides, and
+    // parent directories are consulted when the path itself is undecided
+    #[test]
+    fn test_non_cone_includes() {
+        let sparse = SparseCheckout::non_cone(["/*", "!/*/", "/src/", "!/src/gen/", "*.md"]);
+        assert!(includes(&sparse, "Cargo.toml"));
+        assert!(includes(&sparse, "src/lib.rs"));
+        assert!(includes(&sparse, "src/a/b.rs"));
+        assert!(!includes(&sparse, "src/gen/out.rs"));
+        assert!(includes(&sparse, "src/gen/README.md"));
+        assert!(!includes(&sparse, "tests/a.rs"));
+        assert_eq!(
+            sparse.to_file_content(),
+            "/*\n!/*/\n/src/\n!/src/gen/\n*.md\n"
+        );
+
+        let empty = SparseCheckout::non_cone(Vec::<String>::new());
+        assert!(!includes(&empty, "Cargo.toml"));
+    }
+}
@@ -0,0 +1,359 @@
+//! Removing and renaming tracked files (`git rm` / `git mv`).
+//!
+//! Both operations keep the index and the working tree in sync: removed
+//! files are dropped from the index and (unless `cached`) deleted from the
+//! working tree, and renamed files are moved on disk and in the index.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::index::{Index, IndexEntry};
+use crate::objects::{FileMode, Oid};
+use crate::pathspec::Pathspec;
+use crate::status::{entry_modified, entry_stat_clean, flatten_tree_with_modes};
+use crate::Repository;
+
+/// Options for [`Repository::remove`].
+///
+/// # Examples
+///
+/// ```
+/// use zerogit::RemoveOptions;
+///
+/// let options = RemoveOptions::new().cached(true).recursive(true);
+/// assert!(options.get_cached());
+/// assert!(options.get_recursive());
+/// assert!(!options.get_force());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RemoveOptions {
+    /// Only remove from the index, keeping the working tree files.
+    cached: bool,
+    /// Allow removing every file below a directory.
+    recursive: bool,
+    /// Remove even if that loses uncommitted content.
+    force: bool,
+}
+
+impl RemoveOptions {
+    /// Creates a new RemoveOptions with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only removes the files from the index (`git rm --cached`).
+    ///
+    /// The files are left in the working tree as untracked files.
+    pub fn cached(mut self, cached: bool) -> Self {
+        self.cached = cached;
+        self
+    }
+
+    /// Allows a pathspec naming a directory to remove everything below it
+    /// (`git rm -r`).
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Removes files even if they have staged or unstaged changes
+    /// (`git rm --force`).
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Returns whether only the index is updated.
+    pub fn get_cached(&self) -> bool {
+        self.cached
+    }
+
+    /// Returns whether directories are removed recursively.
+    pub fn get_recursive(&self) -> bool {
+        self.recursive
+    }
+
+    /// Returns whether uncommitted content may be lost.
+    pub fn get_force(&self) -> bool {
+        self.force
+    }
+}
+
+impl Repository {
+    /// Removes tracked files from the index and the working tree.
+    ///
+    /// This is equivalent to `git rm`. Unless forced, files whose content
+    /// would be lost are refused: with `cached`, a file whose index
+    /// content differs from both HEAD and the working tree; otherwise, a
+    /// file with any staged or unstaged change. Conflicted entries can
+    /// always be removed. Directories left empty are removed as well. An
+    /// in-memory repository only supports `cached` removal.
+    ///
+    /// Every path is checked before anything is changed, and the index is
+    /// written before any file is deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `pathspec` - The tracked files to remove.
+    /// * `options` - Options controlling the removal.
+    ///
+    /// # Returns
+    ///
+    /// The removed paths, sorted.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::InvalidPathspec` if the pathspec is empty.
+    /// - `Error::PathNotFound` if a pathspec item matches no tracked file.
+    /// - `Error::NotRecursive` if an item names a directory and
+    ///   `recursive` is not set.
+    /// - `Error::UncommittedChanges` if a file has uncommitted changes and
+    ///   `force` is not set, with the first such path and whether its
+    ///   changes are staged, local or both.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::{Pathspec, RemoveOptions, Repository};
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// repo.remove(&Pathspec::new(["build"]).unwrap(), RemoveOptions::new().recursive(true))
+    ///     .unwrap();
+    /// ```
+    pub fn remove(&self, pathspec: &Pathspec, options: RemoveOptions) -> Result<Vec<PathBuf>> {
+        if !options.cached {
+            self.require_on_disk("remove")?;
+        }
+        let _lock = self.lock_for_write();
+        if pathspec.is_empty() {
+            return Err(Error::InvalidPathspec(String::new()));
+        }
+
+        let mut idx = self.read_index()?;
+        let tracked: Vec<&Path> = idx.iter().map(IndexEntry::path).collect();
+        if let Some(spec) = pathspec.unmatched(tracked.iter().copied()) {
+            return Err(Error::PathNotFound(PathBuf::from(spec)));
+        }
+        if !options.recursive {
+            if let Some(spec) = pathspec.recursive_only(tracked.iter().copied()) {
+                return Err(Error::NotRecursive(spec.to_string()));
+            }
+        }
+
+        let mut paths: Vec<PathBuf> = tracked
+            .into_iter()
+            .filter(|path| pathspec.matches(path))
+            .map(Path::to_path_buf)
+            .collect();
+        paths.dedup();
+
+        if !options.force {
+            self.check_removable(&idx, &paths, options.cached)?;
+        }
+
+        for path in &paths {
+            while idx.remove(path) {}
+        }
+        self.write_index(&idx)?;
+
+        if !options.cached {
+            for path in &paths {
+                let full_path = self.path().join(path);
+                match fs::symlink_metadata(&full_path) {
+                    Ok(metadata) if !metadata.is_dir() => fs::remove_file(&full_path)?,
+                    _ => continue,
+                }
+                remove_empty_parents(self.path(), path);
+            }
+        }
+
+        Ok(paths)
+    }
+
+    /// Renames a tracked file or directory in the index and working tree.
+    ///
+    /// This is equivalent to `git mv`. If `to` is an existing directory,
+    /// `from` is moved into it. The working tree is renamed first; if the
+    /// index cannot be written afterwards, the rename is undone.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The tracked file or directory to rename.
+    /// * `to` - The new path, or an existing directory to move into.
+    ///
+    /// # Returns
+    ///
+    /// The destination path.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::PathNotFound` if `from` does not exist.
+    /// - `Error::InvalidRename` if `from` is not tracked or is conflicted,
+    ///   `to` is `from`, the destination exists, or the destination is
+    ///   inside `from`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zerogit::Repository;
+    ///
+    /// let repo = Repository::open("path/to/repo").unwrap();
+    /// repo.rename("src/old.rs", "src/new.rs").unwrap();
+    /// ```
+    pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> Result<PathBuf> {
+        self.require_on_disk("rename")?;
+        let _lock = self.lock_for_write();
+        let from = from.as_ref();
+        let mut to = to.as_ref().to_path_buf();
+        let work_dir = self.path();
+
+        let source = work_dir.join(from);
+        let source_metadata =
+            fs::symlink_metadata(&source).map_err(|_| Error::PathNotFound(from.to_path_buf()))?;
+        // Checked first, as a directory renamed to itself would otherwise be
+        // moved into itself
+        if to == from {
+            return Err(Error::InvalidRename {
+                from: from.to_path_buf(),
+                to,
+                reason: "source and destination are the same",
+            });
+        }
+        if work_dir.join(&to).is_dir() {
+            if let Some(name) = from.file_name() {
+                to.push(name);
+            }
+        }
+        let invalid = |reason| Error::InvalidRename {
+            from: from.to_path_buf(),
+            to: to.clone(),
+            reason,
+        };
+
+        if to.starts_with(from) {
+            return Err(invalid("destination is inside the source"));
+        }
+        if fs::symlink_metadata(work_dir.join(&to)).is_ok() {
+            return Err(invalid("destination exists"));
+        }
+
+        let mut idx = self.read_index()?;
+        let moved: Vec<IndexEntry> = idx
+            .iter()
+            .filter(|entry| {
+                entry.path() == from || (source_metadata.is_dir() && entry.path().starts_with(from))
+            })
+            .cloned()
+            .collect();
+        if moved.is_empty() {
+            return Err(invalid("source is not under version control"));
+        }
+        if moved.iter().any(IndexEntry::is_conflicted) {
+            return Err(invalid("source is conflicted"));
+        }
+
+        // Remember which entries were clean so their stat data can be
+        // refreshed after the move
+        let timestamp = idx.timestamp();
+        let clean: Vec<bool> = moved
+            .iter()
+            .map(|entry| entry_stat_clean(work_dir, entry, timestamp))
+            .collect();
+
+        let destination = work_dir.join(&to);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&source, &destination)?;
+
+        for (entry, clean) in moved.iter().zip(clean) {
+            let suffix = entry.path().strip_prefix(from).unwrap_or(Path::new(""));
+            let path = if suffix.as_os_str().is_empty() {
+                to.clone()
+            } else {
+                to.join(suffix)
+            };
+            let mut renamed = entry.with_path(path);
+            if clean {
+                if let Ok(metadata) = fs::metadata(work_dir.join(renamed.path())) {
+                    renamed.update_stat(&metadata);
+                }
+            }
+            idx.remove(entry.path());
+            idx.add(renamed);
+        }
+
+        if let Err(e) = self.write_index(&idx) {
+            let _ = fs::rename(&destination, &source);
+            return Err(e);
+        }
+        remove_empty_parents(work_dir, from);
+
+        Ok(to)
+    }
+
+    /// Checks that removing the paths does not lose uncommitted content.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UncommittedChanges` for the first path whose changes
+    /// would be lost, with the reason Git gives.
+    fn check_removable(&self, idx: &Index, paths: &[PathBuf], cached: bool) -> Result<()> {
+        // Without a working tree there is no local content to lose
+        if self.is_in_memory() {
+            return Ok(());
+        }
+        let mut head_files: BTreeMap<PathBuf, (Oid, FileMode)> = BTreeMap::new();
+        if let Ok(head) = self.head() {
+            let commit = self.commit(&head.oid().to_hex())?;
+            let store = self.object_store();
+            flatten_tree_with_modes(&store, commit.tree(), Path::new(""), &mut head_files)?;
+        }
+
+        for path in paths {
+            let Some(entry) = idx.get(path) else {
+                continue;
+            };
+            if entry.is_conflicted() {
+                continue;
+            }
+            // Files that are already gone from the working tree (or were
+            // replaced by a directory) have nothing left to lose
+            match fs::symlink_metadata(self.path().join(path)) {
+                Ok(metadata) if !metadata.is_dir() => {}
+                _ => continue,
+            }
+
+            let staged = head_files.get(path) != Some(&(*entry.oid(), entry.mode()));
+            let local = entry_modified(self.path(), entry, idx.timestamp())?;
+            let reason = match (staged, local) {
+                (true, true) => "has staged content different from both the file and HEAD",
+                (true, false) if !cached => "has changes staged in the index",
+                (false, true) if !cached => "has local modifications",
+                _ => continue,
+            };
+            return Err(Error::UncommittedChanges {
+                path: path.clone(),
+                reason,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes the now-empty parent directories of a removed path, up to (but
+/// not including) the working tree root.
+pub(crate) fn remove_empty_parents(work_dir: &Path, path: &Path) {
+    let mut dir = path.parent();
+    while let Some(parent) = dir.filter(|p| !p.as_os_str().is_empty()) {
+        match fs::remove_dir(work_dir.join(parent)) {
+            Ok(()) => dir = parent.parent(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => dir = parent.parent(),
+            Err(_) => break,
+        }
+    }
+}
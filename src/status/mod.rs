@@ -0,0 +1,1462 @@
+//! Git status implementation.
+//!
+//! This module implements working tree status detection by comparing
+//! HEAD, Index, and the working tree.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::ignore::IgnoreRules;
+use crate::index::{mode_from_metadata, Index, IndexEntry};
+use crate::infra::{hash_object_with, list_working_tree_parallel, parallel_map, path, read_file};
+use crate::objects::tree::FileMode;
+use crate::objects::{LooseObjectStore, Oid};
+use crate::pathspec::Pathspec;
+
+pub mod porcelain;
+
+pub use porcelain::{format_porcelain, BranchInfo, PorcelainOptions, PorcelainVersion};
+
+/// The status of a file in the working tree.
+///
+/// This is a single summary of an entry's [`StatusEntry::index_state`] and
+/// [`StatusEntry::worktree_state`]; unstaged changes take precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    /// File is new and not tracked by Git.
+    Untracked,
+    /// File has been added to the index (staged for commit).
+    Added,
+    /// File has been modified in the working tree compared to the index.
+    Modified,
+    /// File has been deleted from the working tree.
+    Deleted,
+    /// File has been modified and staged.
+    StagedModified,
+    /// File has been deleted and staged.
+    StagedDeleted,
+    /// File has been renamed in the index.
+    Renamed,
+    /// File has unresolved merge conflicts.
+    Conflicted,
+    /// File is ignored by a `.gitignore` rule.
+    Ignored,
+}
+
+impl FileStatus {
+    /// Returns true if the file is staged (in index but different from HEAD).
+    pub fn is_staged(&self) -> bool {
+        matches!(
+            self,
+            FileStatus::Added
+                | FileStatus::StagedModified
+                | FileStatus::StagedDeleted
+                | FileStatus::Renamed
+        )
+    }
+
+    /// Returns true if the file has unstaged changes.
+    pub fn is_unstaged(&self) -> bool {
+        matches!(
+            self,
+            FileStatus::Modified | FileStatus::Deleted | FileStatus::Untracked
+        )
+    }
+}
+
+/// The state of a file on one side of a status comparison.
+///
+/// Each [`StatusEntry`] has two states, like the `XY` columns of
+/// `git status --porcelain`: the index compared to HEAD, and the working
+/// tree compared to the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryState {
+    /// No change.
+    Unmodified,
+    /// File was added.
+    Added,
+    /// File content or mode was modified.
+    Modified,
+    /// File was deleted.
+    Deleted,
+    /// File was renamed (index side only).
+    Renamed,
+    /// File changed type (e.g. regular file to symlink).
+    TypeChanged,
+    /// File is unmerged (part of a merge conflict).
+    Unmerged,
+    /// File is not tracked.
+    Untracked,
+    /// File is ignored.
+    Ignored,
+}
+
+impl EntryState {
+    /// Returns the character used for this state in `git status --short`.
+    pub fn as_char(&self) -> char {
+        match self {
+            EntryState::Unmodified => ' ',
+            EntryState::Added => 'A',
+            EntryState::Modified => 'M',
+            EntryState::Deleted => 'D',
+            EntryState::Renamed => 'R',
+            EntryState::TypeChanged => 'T',
+            EntryState::Unmerged => 'U',
+            EntryState::Untracked => '?',
+            EntryState::Ignored => '!',
+        }
+    }
+}
+
+/// Which untracked files to report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UntrackedFiles {
+    /// Do not report untracked files.
+    No,
+    /// Report untracked files, collapsing directories that contain no
+    /// tracked files into a single `dir/` entry (Git's default).
+    Normal,
+    /// Report every untracked file individually.
+    All,
+}
+
+/// Options for computing the working tree status.
+///
+/// The defaults match [`Repository::status`](crate::Repository::status):
+/// every untracked file is listed, ignored files are hidden and renames are
+/// not detected.
+///
+/// # Examples
+///
+/// ```
+/// use zerogit::status::{StatusOptions, UntrackedFiles};
+/// use zerogit::Pathspec;
+///
+/// let options = StatusOptions::new()
+///     .refresh(true)
+///     .threads(4)
+///     .untracked_files(UntrackedFiles::Normal)
+///     .renames(true)
+///     .pathspec(Pathspec::new(["src"]).unwrap());
+/// assert!(options.get_refresh());
+/// assert_eq!(options.get_threads(), 4);
+/// ```
+#[derive(Debug, Clone)]
+pub struct StatusOptions {
+    /// Refresh stale stat data in the index and write it back.
+    refresh: bool,
+    /// Number of threads for scanning and hashing (0 = available CPUs).
+    threads: usize,
+    /// Which untracked files to report.
+    untracked_files: UntrackedFiles,
+    /// Report ignored files.
+    ignored: bool,
+    /// Detect renames between HEAD and the index.
+    renames: bool,
+    /// Restrict the status to these paths.
+    pathspec: Pathspec,
+}
+
+impl Default for StatusOptions {
+    fn default() -> Self {
+        Self {
+            refresh: false,
+            threads: 1,
+            untracked_files: UntrackedFiles::All,
+            ignored: false,
+            renames: false,
+            pathspec: Pathspec::default(),
+        }
+    }
+}
+
+impl StatusOptions {
+    /// Creates a new `StatusOptions` with default values.
+    ///
+    /// By default the index is not refreshed and a single thread is used.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether to refresh the index before computing the status.
+    ///
+    /// When enabled, entries whose stat data no longer matches the working
+    /// tree but whose content is unchanged (e.g. after `touch`) get their
+    /// cached stat data updated, and the index is written back to disk.
+    /// Subsequent status calls can then skip hashing those files.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to refresh the index.
+    pub fn refresh(mut self, enabled: bool) -> Self {
+        self.refresh = enabled;
+        self
+    }
+
+    /// Sets the number of threads used to scan the working tree and hash files.
+    ///
+    /// `1` (the default) runs everything on the calling thread, and `0` uses
+    /// one thread per available CPU. The result does not depend on the
+    /// thread count.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The number of threads.
+    pub fn threads(mut self, n: usize) -> Self {
+        self.threads = n;
+        self
+    }
+
+    /// Sets which untracked files to report.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The untracked files mode (default: [`UntrackedFiles::All`]).
+    pub fn untracked_files(mut self, mode: UntrackedFiles) -> Self {
+        self.untracked_files = mode;
+        self
+    }
+
+    /// Sets whether to report ignored files.
+    ///
+    /// Ignored files are never reported as untracked. When enabled, they are
+    /// reported with [`FileStatus::Ignored`].
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to report ignored files.
+    pub fn ignored(mut self, enabled: bool) -> Self {
+        self.ignored = enabled;
+        self
+    }
+
+    /// Sets whether to detect renames between HEAD and the index.
+    ///
+    /// A file deleted from the index and a file added with identical
+    /// content are reported as a single [`EntryState::Renamed`] entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to detect renames.
+    pub fn renames(mut self, enabled: bool) -> Self {
+        self.renames = enabled;
+        self
+    }
+
+    /// Restricts the status to paths matching a pathspec.
+    ///
+    /// Can be called multiple times; the patterns are combined.
+    ///
+    /// # Arguments
+    ///
+    /// * `pathspec` - The paths to report.
+    pub fn pathspec(mut self, pathspec: Pathspec) -> Self {
+        self.pathspec.extend(pathspec);
+        self
+    }
+
+    /// Returns whether the index will be refreshed.
+    pub fn get_refresh(&self) -> bool {
+        self.refresh
+    }
+
+    /// Returns the configured number of threads (0 = available CPUs).
+    pub fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Returns the untracked files mode.
+    pub fn get_untracked_files(&self) -> UntrackedFiles {
+        self.untracked_files
+    }
+
+    /// Returns whether ignored files are reported.
+    pub fn get_ignored(&self) -> bool {
+        self.ignored
+    }
+
+    /// Returns whether renames are detected.
+    pub fn get_renames(&self) -> bool {
+        self.renames
+    }
+
+    /// Returns the pathspec restricting the status.
+    pub fn get_pathspec(&self) -> &Pathspec {
+        &self.pathspec
+    }
+}
+
+/// A status entry representing a file and its status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusEntry {
+    /// The path of the file relative to the repository root.
+    ///
+    /// Collapsed untracked or ignored directories end with `/`.
+    path: PathBuf,
+    /// The status of the file.
+    status: FileStatus,
+    /// The state of the index compared to HEAD.
+    index_state: EntryState,
+    /// The state of the working tree compared to the index.
+    worktree_state: EntryState,
+    /// The original path, for renamed entries.
+    orig_path: Option<PathBuf>,
+    /// The mode and blob of the file in HEAD (the rename source for renames).
+    head: Option<(FileMode, Oid)>,
+    /// The mode and blob of the file in the index.
+    index: Option<(FileMode, Oid)>,
+    /// The mode of the file in the working tree, if it exists.
+    worktree_mode: Option<FileMode>,
+    /// The unmerged index entries for stages 1 (base), 2 (ours) and 3 (theirs).
+    stages: [Option<(FileMode, Oid)>; 3],
+}
+
+impl StatusEntry {
+    /// Creates a new StatusEntry.
+    ///
+    /// The index and working tree states are derived from `status`.
+    pub fn new(path: PathBuf, status: FileStatus) -> Self {
+        let (index_state, worktree_state) = match status {
+            FileStatus::Untracked => (EntryState::Untracked, EntryState::Untracked),
+            FileStatus::Added => (EntryState::Added, EntryState::Unmodified),
+            FileStatus::Modified => (EntryState::Unmodified, EntryState::Modified),
+            FileStatus::Deleted => (EntryState::Unmodified, EntryState::Deleted),
+            FileStatus::StagedModified => (EntryState::Modified, EntryState::Unmodified),
+            FileStatus::StagedDeleted => (EntryState::Deleted, EntryState::Unmodified),
+            FileStatus::Renamed => (EntryState::Renamed, EntryState::Unmodified),
+            FileStatus::Conflicted => (EntryState::Unmerged, EntryState::Unmerged),
+            FileStatus::Ignored => (EntryState::Ignored, EntryState::Ignored),
+        };
+        Self {
+            path,
+            status,
+            index_state,
+            worktree_state,
+            orig_path: None,
+            head: None,
+            index: None,
+            worktree_mode: None,
+            stages: [None; 3],
+        }
+    }
+
+    /// Creates a StatusEntry from separate index and working tree states.
+    pub(crate) fn with_states(
+        path: PathBuf,
+        index_state: EntryState,
+        worktree_state: EntryState,
+    ) -> Self {
+        let status = summarize(index_state, worktree_state);
+        Self {
+            path,
+            status,
+            index_state,
+            worktree_state,
+            orig_path: None,
+            head: None,
+            index: None,
+            worktree_mode: None,
+            stages: [None; 3],
+        }
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the status of the file.
+    pub fn status(&self) -> FileStatus {
+        self.status
+    }
+
+    /// Returns the state of the index compared to HEAD (the `X` column).
+    pub fn index_state(&self) -> EntryState {
+        self.index_state
+    }
+
+    /// Returns the state of the working tree compared to the index (the `Y` column).
+    pub fn worktree_state(&self) -> EntryState {
+        self.worktree_state
+    }
+
+    /// Returns the original path of a renamed file.
+    pub fn orig_path(&self) -> Option<&Path> {
+        self.orig_path.as_deref()
+    }
+
+    /// Returns true if the file has unresolved merge conflicts.
+    pub fn is_conflicted(&self) -> bool {
+        self.status == FileStatus::Conflicted
+    }
+
+    /// Returns the mode of the file in HEAD.
+    ///
+    /// For renamed entries this is the mode of the original path.
+    pub fn head_mode(&self) -> Option<FileMode> {
+        self.head.map(|(mode, _)| mode)
+    }
+
+    /// Returns the blob OID of the file in HEAD.
+    ///
+    /// For renamed entries this is the blob of the original path.
+    pub fn head_oid(&self) -> Option<&Oid> {
+        self.head.as_ref().map(|(_, oid)| oid)
+    }
+
+    /// Returns the mode of the file in the index.
+    pub fn index_mode(&self) -> Option<FileMode> {
+        self.index.map(|(mode, _)| mode)
+    }
+
+    /// Returns the blob OID of the file in the index.
+    pub fn index_oid(&self) -> Option<&Oid> {
+        self.index.as_ref().map(|(_, oid)| oid)
+    }
+
+    /// Returns the mode of the file in the working tree.
+    ///
+    /// This is only known for tracked files that exist in the working tree.
+    pub fn worktree_mode(&self) -> Option<FileMode> {
+        self.worktree_mode
+    }
+
+    /// Returns the mode and blob OID of an unmerged index entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `stage` - The merge stage: 1 (base), 2 (ours) or 3 (theirs).
+    ///
+    /// # Returns
+    ///
+    /// The mode and OID, or `None` if the stage is absent or the entry is
+    /// not conflicted.
+    pub fn conflict_stage(&self, stage: u8) -> Option<(FileMode, &Oid)> {
+        let slot = self.stages.get(usize::from(stage).checked_sub(1)?)?;
+        slot.as_ref().map(|(mode, oid)| (*mode, oid))
+    }
+}
+
+/// Returns true if the two states describe an unmerged path.
+///
+/// Conflicts use Git's `XY` codes: `DD`, `AU`, `UD`, `UA`, `DU`, `AA`, `UU`.
+fn is_conflict(index_state: EntryState, worktree_state: EntryState) -> bool {
+    use EntryState::*;
+    matches!(
+        (index_state, worktree_state),
+        (Unmerged, _) | (_, Unmerged) | (Deleted, Deleted) | (Added, Added)
+    )
+}
+
+/// Collapses index and working tree states into a single [`FileStatus`].
+fn summarize(index_state: EntryState, worktree_state: EntryState) -> FileStatus {
+    use EntryState::*;
+    if is_conflict(index_state, worktree_state) {
+        return FileStatus::Conflicted;
+    }
+    match (index_state, worktree_state) {
+        (Untracked, _) => FileStatus::Untracked,
+        (Ignored, _) => FileStatus::Ignored,
+        (_, Deleted) => FileStatus::Deleted,
+        (_, Modified) | (_, TypeChanged) => FileStatus::Modified,
+        (Added, _) => FileStatus::Added,
+        (Deleted, _) => FileStatus::StagedDeleted,
+        (Renamed, _) => FileStatus::Renamed,
+        _ => FileStatus::StagedModified,
+    }
+}
+
+/// Returns the conflict `XY` states for a set of unmerged stages.
+///
+/// `stages` is a bit mask: 1 = base, 2 = ours, 4 = theirs.
+fn conflict_states(stages: u8) -> (EntryState, EntryState) {
+    use EntryState::*;
+    match stages {
+        1 => (Deleted, Deleted),
+        2 => (Added, Unmerged),
+        3 => (Unmerged, Deleted),
+        4 => (Unmerged, Added),
+        5 => (Deleted, Unmerged),
+        6 => (Added, Added),
+        _ => (Unmerged, Unmerged),
+    }
+}
+
+/// Flattens a tree into a map of path -> Oid.
+///
+/// This recursively walks the tree and collects all blob entries
+/// with their full paths.
+pub fn flatten_tree(
+    store: &LooseObjectStore,
+    tree_oid: &Oid,
+    prefix: &Path,
+    result: &mut BTreeMap<PathBuf, Oid>,
+) -> Result<()> {
+    let mut entries = BTreeMap::new();
+    flatten_tree_with_modes(store, tree_oid, prefix, &mut entries)?;
+    result.extend(entries.into_iter().map(|(path, (oid, _))| (path, oid)));
+    Ok(())
+}
+
+/// Flattens a tree into a map of path -> (Oid, mode).
+///
+/// Like [`flatten_tree`], but also records the mode of each entry.
+pub(crate) fn flatten_tree_with_modes(
+    store: &LooseObjectStore,
+    tree_oid: &Oid,
+    prefix: &Path,
+    result: &mut BTreeMap<PathBuf, (Oid, FileMode)>,
+) -> Result<()> {
+    let tree = store.read_tree(tree_oid)?;
+
+    for entry in tree.iter() {
+        let entry_path = prefix.join(entry.name_path());
+
+        if entry.is_directory() {
+            // Recursively flatten subdirectory
+            flatten_tree_with_modes(store, entry.oid(), &entry_path, result)?;
+        } else {
+            // Add blob entry
+            result.insert(entry_path, (*entry.oid(), entry.mode()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks if a file in the working tree has been modified compared to a blob OID.
+///
+/// This hashes the file content with the expected OID's object format and
+/// compares the result with the expected OID.
+pub fn file_modified(work_dir: &Path, path: &Path, expected_oid: &Oid) -> Result<bool> {
+    let full_path = work_dir.join(path);
+
+    // If file doesn't exist, it's definitely different
+    if !full_path.exists() {
+        return Ok(true);
+    }
+
+    // Read file content and compute hash
+    let content = read_file(&full_path)?;
+    let actual_hash = hash_object_with(expected_oid.format(), "blob", &content)?;
+    let actual_oid = Oid::from_slice(&actual_hash)?;
+
+    Ok(&actual_oid != expected_oid)
+}
+
+/// Checks if a tracked file in the working tree differs from its index entry.
+///
+/// A symbolic link is modified if its target differs from the blob. For
+/// files, a change of the executable bit counts as a modification.
+/// Otherwise, if the cached stat data of the entry matches the file on disk
+/// and the entry is not racily clean, the file is assumed to be unchanged
+/// and is not read at all. Otherwise this falls back to hashing the file
+/// content.
+///
+/// # Arguments
+///
+/// * `work_dir` - The root of the working tree.
+/// * `entry` - The index entry of the file.
+/// * `index_timestamp` - The mtime of the index file, or `None` if unknown
+///   (in which case every entry is treated as racily clean).
+pub fn entry_modified(
+    work_dir: &Path,
+    entry: &IndexEntry,
+    index_timestamp: Option<(u64, u32)>,
+) -> Result<bool> {
+    // A symbolic link is compared by its target, not the file it points to
+    if entry.mode() == FileMode::Symlink {
+        let Ok(target) = fs::read_link(work_dir.join(entry.path())) else {
+            return Ok(true);
+        };
+        let hash = hash_object_with(entry.oid().format(), "blob", &path::to_bytes(&target))?;
+        return Ok(Oid::from_slice(&hash)? != *entry.oid());
+    }
+
+    let metadata = match fs::metadata(work_dir.join(entry.path())) {
+        Ok(metadata) => metadata,
+        Err(_) => return Ok(true),
+    };
+
+    if mode_changed(entry, &metadata) {
+        return Ok(true);
+    }
+
+    if stat_clean(entry, &metadata, index_timestamp) {
+        return Ok(false);
+    }
+
+    file_modified(work_dir, entry.path(), entry.oid())
+}
+
+/// Returns true if the file on disk is known to be unchanged from its index
+/// entry based on stat data alone, without reading its content.
+pub(crate) fn entry_stat_clean(
+    work_dir: &Path,
+    entry: &IndexEntry,
+    index_timestamp: Option<(u64, u32)>,
+) -> bool {
+    fs::metadata(work_dir.join(entry.path()))
+        .map(|metadata| stat_clean(entry, &metadata, index_timestamp))
+        .unwrap_or(false)
+}
+
+/// Returns true if the executable bit of a regular file differs from the
+/// index entry.
+fn mode_changed(entry: &IndexEntry, metadata: &fs::Metadata) -> bool {
+    cfg!(unix)
+        && metadata.is_file()
+        && matches!(entry.mode(), FileMode::Regular | FileMode::Executable)
+        && mode_from_metadata(metadata) != entry.mode()
+}
+
+/// Returns true if the entry's stat data can be trusted to match the file.
+fn stat_clean(
+    entry: &IndexEntry,
+    metadata: &fs::Metadata,
+    index_timestamp: Option<(u64, u32)>,
+) -> bool {
+    match index_timestamp {
+        Some(ts) => entry.stat_matches(metadata) && !entry.is_racily_clean(ts),
+        None => false,
+    }
+}
+
+/// Refreshes the cached stat data of index entries.
+///
+/// Entries whose stat data does not match the working tree (or is racily
+/// clean) are re-hashed; if the content is unchanged, the stat data is
+/// updated from the file on disk. Modified and missing files are left alone.
+///
+/// # Arguments
+///
+/// * `work_dir` - The root of the working tree.
+/// * `index` - The index to refresh.
+/// * `options` - Status options (the thread count is used for hashing).
+///
+/// # Returns
+///
+/// `true` if any entry was updated and the index should be written back.
+pub fn refresh_index(work_dir: &Path, index: &mut Index, options: &StatusOptions) -> Result<bool> {
+    let index_timestamp = index.timestamp();
+
+    // Hash candidates in parallel; apply the updates afterwards.
+    let updates = parallel_map(index.entries(), options.get_threads(), |entry| {
+        if entry.is_conflicted() || entry.is_skip_worktree() {
+            return Ok(None);
+        }
+
+        let metadata = match fs::metadata(work_dir.join(entry.path())) {
+            Ok(metadata) => metadata,
+            Err(_) => return Ok(None),
+        };
+
+        if stat_clean(entry, &metadata, index_timestamp) || mode_changed(entry, &metadata) {
+            return Ok(None);
+        }
+
+        if file_modified(work_dir, entry.path(), entry.oid())? {
+            Ok(None)
+        } else {
+            Ok(Some(metadata))
+        }
+    })
+    .into_iter()
+    .collect::<Result<Vec<_>>>()?;
+
+    let mut changed = false;
+    for (entry, update) in index.iter_mut().zip(updates) {
+        if let Some(metadata) = update {
+            entry.update_stat(&metadata);
+            changed = true;
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Computes the status of the working tree.
+///
+/// This compares three trees:
+/// - HEAD tree: The tree of the current commit
+/// - Index: The staging area
+/// - Working tree: The actual files on disk
+///
+/// # Arguments
+///
+/// * `work_dir` - The root of the working tree.
+/// * `store` - The object store for reading trees and blobs.
+/// * `head_tree_oid` - The OID of the HEAD commit's tree (None if no commits yet).
+/// * `index` - The parsed index file (None if no index exists).
+///
+/// Tracked files whose cached stat data matches the working tree are not
+/// re-hashed (see [`entry_modified`]).
+///
+/// # Returns
+///
+/// A vector of StatusEntry representing all files with changes.
+pub fn compute_status(
+    work_dir: &Path,
+    store: &LooseObjectStore,
+    head_tree_oid: Option<&Oid>,
+    index: Option<&Index>,
+) -> Result<Vec<StatusEntry>> {
+    compute_status_with_options(
+        work_dir,
+        store,
+        head_tree_oid,
+        index,
+        &StatusOptions::default(),
+    )
+}
+
+/// Computes the status of the working tree with the given options.
+///
+/// Like [`compute_status`], but the working tree scan and the hashing of
+/// tracked files run on [`StatusOptions::threads`] threads, and the other
+/// options control which entries are reported. The result is sorted by path
+/// and independent of the thread count.
+///
+/// Ignore rules are read from `work_dir/.git`; see [`compute_status`] for
+/// the other arguments.
+///
+/// # Arguments
+///
+/// * `work_dir` - The root of the working tree.
+/// * `store` - The object store for reading trees and blobs.
+/// * `head_tree_oid` - The OID of the HEAD commit's tree (None if no commits yet).
+/// * `index` - The parsed index file (None if no index exists).
+/// * `options` - Options controlling the status computation.
+///
+/// # Returns
+///
+/// A vector of StatusEntry representing all files with changes.
+pub fn compute_status_with_options(
+    work_dir: &Path,
+    store: &LooseObjectStore,
+    head_tree_oid: Option<&Oid>,
+    index: Option<&Index>,
+    options: &StatusOptions,
+) -> Result<Vec<StatusEntry>> {
+    status_in(
+        work_dir,
+        &work_dir.join(".git"),
+        store,
+        head_tree_oid,
+        index,
+        options,
+    )
+}
+
+/// Computes the status of a working tree whose `.git` directory is `git_dir`.
+pub(crate) fn status_in(
+    work_dir: &Path,
+    git_dir: &Path,
+    store: &LooseObjectStore,
+    head_tree_oid: Option<&Oid>,
+    index: Option<&Index>,
+    options: &StatusOptions,
+) -> Result<Vec<StatusEntry>> {
+    let threads = options.get_threads();
+
+    // Flatten HEAD tree into path -> (OID, mode) map
+    let mut head_files: BTreeMap<PathBuf, (Oid, FileMode)> = BTreeMap::new();
+    if let Some(tree_oid) = head_tree_oid {
+        flatten_tree_with_modes(store, tree_oid, Path::new(""), &mut head_files)?;
+    }
+    head_files.retain(|path, _| options.pathspec.matches(path));
+
+    let index_timestamp = index.and_then(|idx| idx.timestamp());
+
+    // Build index maps: path -> IndexEntry for merged entries, and
+    // path -> stage mask for unmerged ones
+    let mut index_files: BTreeMap<PathBuf, &IndexEntry> = BTreeMap::new();
+    let mut conflicts: BTreeMap<PathBuf, [Option<(FileMode, Oid)>; 3]> = BTreeMap::new();
+    let mut tracked_dirs: HashSet<PathBuf> = HashSet::new();
+    for entry in index.into_iter().flat_map(|idx| idx.iter()) {
+        tracked_dirs.extend(entry.path().ancestors().skip(1).map(Path::to_path_buf));
+        if !options.pathspec.matches(entry.path()) {
+            continue;
+        }
+        if entry.is_conflicted() {
+            let stages = conflicts.entry(entry.path().to_path_buf()).or_default();
+            stages[usize::from(entry.stage()) - 1] = Some((entry.mode(), *entry.oid()));
+        } else {
+            index_files.insert(entry.path().to_path_buf(), entry);
+        }
+    }
+
+    // Get working tree files
+    let all_working_files = list_working_tree_parallel(work_dir, threads)?;
+    let working_files: HashSet<PathBuf> = all_working_files
+        .iter()
+        .filter(|path| options.pathspec.matches(path))
+        .cloned()
+        .collect();
+
+    // Check tracked files present in the working tree for modifications
+    // (entries outside a sparse checkout are taken as unmodified)
+    let candidates: Vec<&IndexEntry> = index_files
+        .values()
+        .filter(|entry| !entry.is_skip_worktree() && working_files.contains(entry.path()))
+        .copied()
+        .collect();
+    let modified = parallel_map(&candidates, threads, |entry| {
+        entry_modified(work_dir, entry, index_timestamp)
+    });
+    let mut worktree_modified: HashMap<&Path, bool> = HashMap::new();
+    for (entry, result) in candidates.iter().zip(modified) {
+        worktree_modified.insert(entry.path(), result?);
+    }
+
+    let mut entries = Vec::new();
+
+    // Unmerged paths
+    for (path, stages) in &conflicts {
+        let mask = (0..3)
+            .filter(|&i| stages[i].is_some())
+            .fold(0, |mask, i| mask | 1 << i);
+        let (x, y) = conflict_states(mask);
+        entries.push(StatusEntry {
+            worktree_mode: worktree_mode(work_dir, path, &working_files),
+            stages: *stages,
+            ..StatusEntry::with_states(path.clone(), x, y)
+        });
+    }
+
+    // Tracked paths: HEAD vs index, index vs working tree
+    let tracked: BTreeSet<&PathBuf> = head_files.keys().chain(index_files.keys()).collect();
+    for path in tracked {
+        if conflicts.contains_key(path) {
+            continue;
+        }
+
+        let in_head = head_files.get(path);
+        let in_index = index_files.get(path);
+
+        let index_state = match (in_head, in_index) {
+            (None, Some(_)) => EntryState::Added,
+            (Some(_), None) => EntryState::Deleted,
+            (Some((head_oid, head_mode)), Some(entry)) => {
+                if !same_kind(*head_mode, entry.mode()) {
+                    EntryState::TypeChanged
+                } else if head_oid != entry.oid() || *head_mode != entry.mode() {
+                    EntryState::Modified
+                } else {
+                    EntryState::Unmodified
+                }
+            }
+            (None, None) => continue,
+        };
+
+        let worktree_state = match in_index {
+            Some(entry) if entry.is_skip_worktree() => EntryState::Unmodified,
+            Some(_) if !working_files.contains(path) => EntryState::Deleted,
+            Some(_) if worktree_modified[path.as_path()] => EntryState::Modified,
+            _ => EntryState::Unmodified,
+        };
+
+        if index_state != EntryState::Unmodified || worktree_state != EntryState::Unmodified {
+            entries.push(StatusEntry {
+                head: in_head.map(|(oid, mode)| (*mode, *oid)),
+                index: in_index.map(|entry| (entry.mode(), *entry.oid())),
+                worktree_mode: worktree_mode(work_dir, path, &working_files),
+                ..StatusEntry::with_states(path.clone(), index_state, worktree_state)
+            });
+        }
+    }
+
+    if options.get_renames() {
+        detect_renames(&mut entries, &head_files, &index_files);
+    }
+
+    // Untracked and ignored files
+    let report_untracked = options.get_untracked_files() != UntrackedFiles::No;
+    if report_untracked || options.get_ignored() {
+        let ignore = IgnoreRules::load(work_dir, git_dir, &all_working_files);
+        let collapse = options.get_untracked_files() == UntrackedFiles::Normal;
+
+        let mut untracked: BTreeSet<PathBuf> = BTreeSet::new();
+        let mut ignored: BTreeSet<PathBuf> = BTreeSet::new();
+        for path in &all_working_files {
+            if index_files.contains_key(path)
+                || conflicts.contains_key(path)
+                || !working_files.contains(path)
+            {
+                continue;
+            }
+
+            if ignore.is_ignored(path, false) {
+                if options.get_ignored() {
+                    let reported = match ignore.ignored_ancestor(path) {
+                        Some(dir) if collapse => dir_entry_path(&dir),
+                        _ => path.clone(),
+                    };
+                    ignored.insert(reported);
+                }
+            } else if report_untracked {
+                let reported = match untracked_ancestor(path, &tracked_dirs) {
+                    Some(dir) if collapse => dir_entry_path(&dir),
+                    _ => path.clone(),
+                };
+                untracked.insert(reported);
+            }
+        }
+
+        entries.extend(
+            untracked
+                .into_iter()
+                .map(|p| StatusEntry::new(p, FileStatus::Untracked)),
+        );
+        entries.extend(
+            ignored
+                .into_iter()
+                .map(|p| StatusEntry::new(p, FileStatus::Ignored)),
+        );
+    }
+
+    // Sort by path for consistent output (tracked before untracked/ignored)
+    entries.sort_by(|a, b| {
+        a.path
+            .cmp(&b.path)
+            .then_with(|| sort_rank(a).cmp(&sort_rank(b)))
+    });
+
+    Ok(entries)
+}
+
+/// Orders entries for the same path: tracked, then untracked, then ignored.
+fn sort_rank(entry: &StatusEntry) -> u8 {
+    match entry.status {
+        FileStatus::Untracked => 1,
+        FileStatus::Ignored => 2,
+        _ => 0,
+    }
+}
+
+/// Returns the mode of a tracked file in the working tree, if it exists.
+fn worktree_mode(
+    work_dir: &Path,
+    path: &Path,
+    working_files: &HashSet<PathBuf>,
+) -> Option<FileMode> {
+    if !working_files.contains(path) {
+        return None;
+    }
+    let metadata = fs::symlink_metadata(work_dir.join(path)).ok()?;
+    if metadata.file_type().is_symlink() {
+        Some(FileMode::Symlink)
+    } else {
+        Some(mode_from_metadata(&metadata))
+    }
+}
+
+/// Returns true if two modes are the same kind of object (file, symlink, submodule).
+fn same_kind(a: FileMode, b: FileMode) -> bool {
+    let kind = |mode: FileMode| match mode {
+        FileMode::Regular | FileMode::Executable => 0,
+        FileMode::Symlink => 1,
+        FileMode::Submodule => 2,
+        FileMode::Directory => 3,
+    };
+    kind(a) == kind(b)
+}
+
+/// Returns the outermost parent directory of `path` that contains no
+/// tracked files, if any.
+fn untracked_ancestor(path: &Path, tracked_dirs: &HashSet<PathBuf>) -> Option<PathBuf> {
+    let mut ancestors: Vec<&Path> = path.ancestors().skip(1).collect();
+    ancestors.pop(); // the empty root path
+    ancestors
+        .into_iter()
+        .rev()
+        .find(|dir| !tracked_dirs.contains(*dir))
+        .map(Path::to_path_buf)
+}
+
+/// Returns the reported path of a collapsed directory (`dir/`).
+fn dir_entry_path(dir: &Path) -> PathBuf {
+    let mut bytes = path::to_bytes(dir).into_owned();
+    bytes.push(b'/');
+    path::from_bytes(&bytes)
+}
+
+/// Pairs staged deletions with staged additions of identical content.
+///
+/// Each added entry whose blob matches a deleted HEAD entry becomes a
+/// renamed entry, and the deleted entry is dropped.
+fn detect_renames(
+    entries: &mut Vec<StatusEntry>,
+    head_files: &BTreeMap<PathBuf, (Oid, FileMode)>,
+    index_files: &BTreeMap<PathBuf, &IndexEntry>,
+) {
+    // Deleted paths grouped by blob, in path order
+    let mut deleted_by_oid: HashMap<Oid, Vec<PathBuf>> = HashMap::new();
+    for entry in entries.iter().rev() {
+        if entry.index_state == EntryState::Deleted && !entry.is_conflicted() {
+            if let Some((oid, _)) = head_files.get(&entry.path) {
+                deleted_by_oid
+                    .entry(*oid)
+                    .or_default()
+                    .push(entry.path.clone());
+            }
+        }
+    }
+    if deleted_by_oid.is_empty() {
+        return;
+    }
+
+    let mut renamed_from: HashSet<PathBuf> = HashSet::new();
+    for entry in entries.iter_mut() {
+        if entry.index_state != EntryState::Added || entry.is_conflicted() {
+            continue;
+        }
+        let Some(index_entry) = index_files.get(&entry.path) else {
+            continue;
+        };
+        if let Some(source) = deleted_by_oid
+            .get_mut(index_entry.oid())
+            .and_then(|paths| paths.pop())
+        {
+            *entry = StatusEntry {
+                orig_path: Some(source.clone()),
+                head: head_files.get(&source).map(|(oid, mode)| (*mode, *oid)),
+                index: entry.index,
+                worktree_mode: entry.worktree_mode,
+                ..StatusEntry::with_states(
+                    entry.path.clone(),
+                    EntryState::Renamed,
+                    entry.worktree_state,
+                )
+            };
+            renamed_from.insert(source);
+        }
+    }
+
+    entries.retain(|e| !(e.index_state == EntryState::Deleted && renamed_from.contains(&e.path)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ignore::path_to_slash;
+    use crate::infra::hash::hash_object;
+    use crate::objects::tree::FileMode;
+    use miniz_oxide::deflate::compress_to_vec_zlib;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Creates a loose object and returns its OID.
+    fn create_object(objects_dir: &Path, content: &[u8], object_type: &str) -> Oid {
+        let header = format!("{} {}\0", object_type, content.len());
+        let mut raw = header.into_bytes();
+        raw.extend_from_slice(content);
+
+        let oid = Oid::from_bytes(hash_object(object_type, content).unwrap());
+        let compressed = compress_to_vec_zlib(&raw, 6);
+
+        let hex = oid.to_hex();
+        let object_path = objects_dir.join(&hex[..2]).join(&hex[2..]);
+        fs::create_dir_all(object_path.parent().unwrap()).unwrap();
+        fs::write(&object_path, &compressed).unwrap();
+
+        oid
+    }
+
+    /// Creates a tree object with the given entries.
+    fn create_tree(objects_dir: &Path, entries: &[(&str, FileMode, &Oid)]) -> Oid {
+        let mut content = Vec::new();
+        for (name, mode, oid) in entries {
+            content.extend_from_slice(mode.as_octal().as_bytes());
+            content.push(b' ');
+            content.extend_from_slice(name.as_bytes());
+            content.push(0);
+            content.extend_from_slice(oid.as_bytes());
+        }
+        create_object(objects_dir, &content, "tree")
+    }
+
+    // Test flatten_tree
+    #[test]
+    fn test_flatten_tree_simple() {
+        let temp = TempDir::new().unwrap();
+        let objects_dir = temp.path().join("objects");
+        fs::create_dir_all(&objects_dir).unwrap();
+
+        // Create a blob
+        let blob_oid = create_object(&objects_dir, b"hello", "blob");
+
+        // Create a tree with one entry
+        let tree_oid = create_tree(&objects_dir, &[("file.txt", FileMode::Regular, &blob_oid)]);
+
+        let store = LooseObjectStore::new(&objects_dir);
+        let mut result = BTreeMap::new();
+        flatten_tree(&store, &tree_oid, Path::new(""), &mut result).unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result.get(Path::new("file.txt")), Some(&blob_oid));
+    }
+
+    #[test]
+    fn test_flatten_tree_nested() {
+        let temp = TempDir::new().unwrap();
+        let objects_dir = temp.path().join("objects");
+        fs::create_dir_all(&objects_dir).unwrap();
+
+        // Create blobs
+        let blob1_oid = create_object(&objects_dir, b"content1", "blob");
+        let blob2_oid = create_object(&objects_dir, b"content2", "blob");
+
+        // Create subtree
+        let subtree_oid = create_tree(
+            &objects_dir,
+            &[("nested.txt", FileMode::Regular, &blob2_oid)],
+        );
+
+        // Create root tree
+        let root_tree_oid = create_tree(
+            &objects_dir,
+            &[
+                ("file.txt", FileMode::Regular, &blob1_oid),
+                ("subdir", FileMode::Directory, &subtree_oid),
+            ],
+        );
+
+        let store = LooseObjectStore::new(&objects_dir);
+        let mut result = BTreeMap::new();
+        flatten_tree(&store, &root_tree_oid, Path::new(""), &mut result).unwrap();
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result.get(Path::new("file.txt")), Some(&blob1_oid));
+        assert!(
+            result.get(Path::new("subdir/nested.txt")) == Some(&blob2_oid)
+                || result.get(Path::new("subdir\\nested.txt")) == Some(&blob2_oid)
+        );
+    }
+
+    // Test file_modified
+    #[test]
+    fn test_file_modified_unchanged() {
+        let temp = TempDir::new().unwrap();
+        let work_dir = temp.path();
+
+        let content = b"hello world";
+        fs::write(work_dir.join("file.txt"), content).unwrap();
+
+        let expected_oid = Oid::from_bytes(hash_object("blob", content).unwrap());
+
+        let modified = file_modified(work_dir, Path::new("file.txt"), &expected_oid).unwrap();
+        assert!(!modified);
+    }
+
+    #[test]
+    fn test_file_modified_changed() {
+        let temp = TempDir::new().unwrap();
+        let work_dir = temp.path();
+
+        fs::write(work_dir.join("file.txt"), b"new content").unwrap();
+
+        let old_oid = Oid::from_bytes(hash_object("blob", b"old content").unwrap());
+
+        let modified = file_modified(work_dir, Path::new("file.txt"), &old_oid).unwrap();
+        assert!(modified);
+    }
+
+    #[test]
+    fn test_file_modified_deleted() {
+        let temp = TempDir::new().unwrap();
+        let work_dir = temp.path();
+
+        let oid = Oid::from_bytes(hash_object("blob", b"content").unwrap());
+
+        // File doesn't exist
+        let modified = file_modified(work_dir, Path::new("nonexistent.txt"), &oid).unwrap();
+        assert!(modified);
+    }
+
+    // Test compute_status scenarios
+    #[test]
+    fn test_compute_status_untracked() {
+        let temp = TempDir::new().unwrap();
+        let work_dir = temp.path();
+        let objects_dir = work_dir.join(".git/objects");
+        fs::create_dir_all(&objects_dir).unwrap();
+
+        // Create a file in working tree
+        fs::write(work_dir.join("new_file.txt"), b"content").unwrap();
+
+        let store = LooseObjectStore::new(&objects_dir);
+        let entries = compute_status(work_dir, &store, None, None).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path(), Path::new("new_file.txt"));
+        assert_eq!(entries[0].status(), FileStatus::Untracked);
+    }
+
+    #[test]
+    fn test_file_status_methods() {
+        assert!(FileStatus::Added.is_staged());
+        assert!(FileStatus::StagedModified.is_staged());
+        assert!(FileStatus::StagedDeleted.is_staged());
+        assert!(!FileStatus::Modified.is_staged());
+        assert!(!FileStatus::Deleted.is_staged());
+        assert!(!FileStatus::Untracked.is_staged());
+
+        assert!(FileStatus::Modified.is_unstaged());
+        assert!(FileStatus::Deleted.is_unstaged());
+        assert!(FileStatus::Untracked.is_unstaged());
+        assert!(!FileStatus::Added.is_unstaged());
+        assert!(!FileStatus::StagedModified.is_unstaged());
+        assert!(!FileStatus::StagedDeleted.is_unstaged());
+    }
+
+    #[test]
+    fn test_status_entry() {
+        let entry = StatusEntry::new(PathBuf::from("test.txt"), FileStatus::Modified);
+        assert_eq!(entry.path(), Path::new("test.txt"));
+        assert_eq!(entry.status(), FileStatus::Modified);
+    }
+
+    // Stat cache: matching stat data skips hashing entirely
+    #[test]
+    fn test_entry_modified_uses_stat_cache() {
+        let temp = TempDir::new().unwrap();
+        let work_dir = temp.path();
+        fs::write(work_dir.join("file.txt"), b"content").unwrap();
+        let metadata = fs::metadata(work_dir.join("file.txt")).unwrap();
+
+        // Deliberately record a wrong OID: if the file were hashed, it would
+        // be reported as modified.
+        let wrong_oid = Oid::from_bytes(hash_object("blob", b"other").unwrap());
+        let entry = IndexEntry::from_metadata(PathBuf::from("file.txt"), wrong_oid, &metadata);
+
+        let future = (entry.mtime() + 10, 0);
+        assert!(!entry_modified(work_dir, &entry, Some(future)).unwrap());
+    }
+
+    // A change of the executable bit is a modification
+    #[cfg(unix)]
+    #[test]
+    fn test_entry_modified_mode_change() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        let work_dir = temp.path();
+        let file = work_dir.join("run.sh");
+        fs::write(&file, b"echo").unwrap();
+        let oid = Oid::from_bytes(hash_object("blob", b"echo").unwrap());
+        let entry =
+            IndexEntry::from_metadata(PathBuf::from("run.sh"), oid, &fs::metadata(&file).unwrap());
+        assert!(!entry_modified(work_dir, &entry, None).unwrap());
+
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(entry_modified(work_dir, &entry, None).unwrap());
+
+        // Refreshing must not hide the mode change
+        let mut index = Index::new(2, vec![entry]);
+        assert!(!refresh_index(work_dir, &mut index, &StatusOptions::new()).unwrap());
+    }
+
+    // Stat cache: racily clean entries are always hashed
+    #[test]
+    fn test_entry_modified_racily_clean() {
+        let temp = TempDir::new().unwrap();
+        let work_dir = temp.path();
+        fs::write(work_dir.join("file.txt"), b"content").unwrap();
+        let metadata = fs::metadata(work_dir.join("file.txt")).unwrap();
+
+        let wrong_oid = Oid::from_bytes(hash_object("blob", b"other").unwrap());
+        let entry = IndexEntry::from_metadata(PathBuf::from("file.txt"), wrong_oid, &metadata);
+
+        // Index written in the same instant as the file
+        let racy = (entry.mtime(), entry.mtime_nsec());
+        assert!(entry_modified(work_dir, &entry, Some(racy)).unwrap());
+
+        // Unknown index timestamp
+        assert!(entry_modified(work_dir, &entry, None).unwrap());
+    }
+
+    #[test]
+    fn test_entry_modified_missing_file() {
+        let temp = TempDir::new().unwrap();
+        let oid = Oid::from_bytes(hash_object("blob", b"content").unwrap());
+        let entry = IndexEntry::new(
+            0,
+            0,
+            0,
+            0,
+            FileMode::Regular,
+            0,
+            0,
+            7,
+            oid,
+            PathBuf::from("missing.txt"),
+            0,
+        );
+
+        assert!(entry_modified(temp.path(), &entry, Some((u64::MAX, 0))).unwrap());
+    }
+
+    #[test]
+    fn test_refresh_index_updates_stale_stat() {
+        let temp = TempDir::new().unwrap();
+        let work_dir = temp.path();
+        fs::write(work_dir.join("same.txt"), b"same").unwrap();
+        fs::write(work_dir.join("changed.txt"), b"new").unwrap();
+
+        let same_oid = Oid::from_bytes(hash_object("blob", b"same").unwrap());
+        let old_oid = Oid::from_bytes(hash_object("blob", b"old").unwrap());
+        let entry = |path: &str, oid: Oid| {
+            IndexEntry::new(
+                0,
+                0,
+                0,
+                0,
+                FileMode::Regular,
+                0,
+                0,
+                0,
+                oid,
+                PathBuf::from(path),
+                0,
+            )
+        };
+        let mut index = Index::empty(2);
+        index.add(entry("same.txt", same_oid));
+        index.add(entry("changed.txt", old_oid));
+
+        assert!(refresh_index(work_dir, &mut index, &StatusOptions::new().threads(2)).unwrap());
+
+        let same_meta = fs::metadata(work_dir.join("same.txt")).unwrap();
+        let changed_meta = fs::metadata(work_dir.join("changed.txt")).unwrap();
+        assert!(index
+            .get(Path::new("same.txt"))
+            .unwrap()
+            .stat_matches(&same_meta));
+        assert!(!index
+            .get(Path::new("changed.txt"))
+            .unwrap()
+            .stat_matches(&changed_meta));
+    }
+
+    #[test]
+    fn test_status_options() {
+        assert!(!StatusOptions::new().get_refresh());
+        assert!(StatusOptions::new().refresh(true).get_refresh());
+        assert_eq!(StatusOptions::new().get_threads(), 1);
+        assert_eq!(StatusOptions::new().threads(8).get_threads(), 8);
+    }
+
+    #[test]
+    fn test_status_entry_states() {
+        let entry = StatusEntry::new(PathBuf::from("a"), FileStatus::StagedModified);
+        assert_eq!(entry.index_state(), EntryState::Modified);
+        assert_eq!(entry.worktree_state(), EntryState::Unmodified);
+        assert_eq!(entry.orig_path(), None);
+
+        let entry = StatusEntry::new(PathBuf::from("b"), FileStatus::Untracked);
+        assert_eq!(entry.index_state().as_char(), '?');
+        assert_eq!(entry.worktree_state().as_char(), '?');
+
+        let entry = StatusEntry::with_states(
+            PathBuf::from("c"),
+            EntryState::Modified,
+            EntryState::Modified,
+        );
+        assert_eq!(entry.status(), FileStatus::Modified);
+        assert!(!entry.is_conflicted());
+    }
+
+    #[test]
+    fn test_summarize() {
+        use EntryState::*;
+        assert_eq!(summarize(Added, Unmodified), FileStatus::Added);
+        assert_eq!(summarize(Added, Modified), FileStatus::Modified);
+        assert_eq!(summarize(Modified, Deleted), FileStatus::Deleted);
+        assert_eq!(summarize(Deleted, Unmodified), FileStatus::StagedDeleted);
+        assert_eq!(summarize(Renamed, Unmodified), FileStatus::Renamed);
+        assert_eq!(
+            summarize(TypeChanged, Unmodified),
+            FileStatus::StagedModified
+        );
+        assert_eq!(summarize(Unmodified, TypeChanged), FileStatus::Modified);
+        assert_eq!(summarize(Unmerged, Unmerged), FileStatus::Conflicted);
+    }
+
+    #[test]
+    fn test_conflict_states() {
+        let codes: Vec<String> = (1..=7)
+            .map(|mask| {
+                let (x, y) = conflict_states(mask);
+                assert!(is_conflict(x, y));
+                format!("{}{}", x.as_char(), y.as_char())
+            })
+            .collect();
+        assert_eq!(codes, ["DD", "AU", "UD", "UA", "DU", "AA", "UU"]);
+    }
+
+    #[test]
+    fn test_status_options_pathspec() {
+        assert!(StatusOptions::new().get_pathspec().is_empty());
+
+        let options = StatusOptions::new()
+            .pathspec(Pathspec::new(["src"]).unwrap())
+            .pathspec(Pathspec::new(["*.md"]).unwrap());
+        let pathspec = options.get_pathspec();
+        assert!(pathspec.matches(Path::new("src/lib.rs")));
+        assert!(pathspec.matches(Path::new("docs/guide.md")));
+        assert!(!pathspec.matches(Path::new("Cargo.toml")));
+    }
+
+    #[test]
+    fn test_compute_status_untracked_modes() {
+        let temp = TempDir::new().unwrap();
+        let work_dir = temp.path();
+        let objects_dir = work_dir.join(".git/objects");
+        fs::create_dir_all(&objects_dir).unwrap();
+        fs::create_dir_all(work_dir.join("new/sub")).unwrap();
+        fs::write(work_dir.join("new/a.txt"), b"a").unwrap();
+        fs::write(work_dir.join("new/sub/b.txt"), b"b").unwrap();
+        fs::write(work_dir.join("top.txt"), b"t").unwrap();
+
+        let store = LooseObjectStore::new(&objects_dir);
+        let paths = |mode| {
+            let options = StatusOptions::new().untracked_files(mode);
+            compute_status_with_options(work_dir, &store, None, None, &options)
+                .unwrap()
+                .into_iter()
+                .map(|e| path_to_slash(e.path()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            paths(UntrackedFiles::All),
+            ["new/a.txt", "new/sub/b.txt", "top.txt"]
+        );
+        assert_eq!(paths(UntrackedFiles::Normal), ["new/", "top.txt"]);
+        assert!(paths(UntrackedFiles::No).is_empty());
+    }
+
+    #[test]
+    fn test_compute_status_parallel_is_deterministic() {
+        let temp = TempDir::new().unwrap();
+        let work_dir = temp.path();
+        let objects_dir = work_dir.join(".git/objects");
+        fs::create_dir_all(&objects_dir).unwrap();
+
+        for i in 0..50 {
+            fs::create_dir_all(work_dir.join(format!("d{}", i % 5))).unwrap();
+            fs::write(work_dir.join(format!("d{}/f{}.txt", i % 5, i)), b"x").unwrap();
+        }
+
+        let store = LooseObjectStore::new(&objects_dir);
+        let sequential = compute_status(work_dir, &store, None, None).unwrap();
+        let options = StatusOptions::new().threads(4);
+        let parallel = compute_status_with_options(work_dir, &store, None, None, &options).unwrap();
+
+        assert_eq!(sequential.len(), 50);
+        assert_eq!(parallel, sequential);
+    }
+}
@@ -0,0 +1,747 @@
+//! Porcelain status output.
+//!
+//! This module formats status entries exactly like `git status --porcelain`
+//! (version 1) and `git status --porcelain=v2`, optionally with the branch
+//! header (`--branch`) and NUL termination (`-z`).
+
+use std::cmp::Ordering;
+
+use super::{EntryState, FileStatus, StatusEntry};
+use crate::infra::path::to_bytes;
+use crate::objects::tree::FileMode;
+use crate::objects::{ObjectFormat, Oid};
+
+/// The porcelain format version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PorcelainVersion {
+    /// `git status --porcelain` (`XY path`).
+    #[default]
+    V1,
+    /// `git status --porcelain=v2`, which also reports modes and object IDs.
+    V2,
+}
+
+/// Options for [`format_porcelain`].
+///
+/// # Examples
+///
+/// ```
+/// use zerogit::status::{PorcelainOptions, PorcelainVersion};
+///
+/// let options = PorcelainOptions::new()
+///     .version(PorcelainVersion::V2)
+///     .branch(true)
+///     .null_terminated(true);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PorcelainOptions {
+    /// The output format version.
+    version: PorcelainVersion,
+    /// Print the branch header.
+    branch: bool,
+    /// Terminate entries with NUL instead of LF and don't quote paths.
+    null_terminated: bool,
+    /// Quote paths containing non-ASCII bytes (`core.quotePath`).
+    quote_path: bool,
+}
+
+impl Default for PorcelainOptions {
+    fn default() -> Self {
+        Self {
+            version: PorcelainVersion::V1,
+            branch: false,
+            null_terminated: false,
+            quote_path: true,
+        }
+    }
+}
+
+impl PorcelainOptions {
+    /// Creates options for porcelain v1 output without a branch header.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the output format version.
+    pub fn version(mut self, version: PorcelainVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Enables the branch header (`git status --branch`).
+    pub fn branch(mut self, enabled: bool) -> Self {
+        self.branch = enabled;
+        self
+    }
+
+    /// Enables NUL termination (`git status -z`).
+    ///
+    /// Paths are written verbatim, and renames are written as two
+    /// NUL-terminated paths instead of `orig -> path`.
+    pub fn null_terminated(mut self, enabled: bool) -> Self {
+        self.null_terminated = enabled;
+        self
+    }
+
+    /// Controls whether paths with non-ASCII bytes are quoted, like Git's
+    /// `core.quotePath` (default: `true`).
+    pub fn quote_path(mut self, enabled: bool) -> Self {
+        self.quote_path = enabled;
+        self
+    }
+
+    /// Returns the output format version.
+    pub fn get_version(&self) -> PorcelainVersion {
+        self.version
+    }
+
+    /// Returns whether the branch header is printed.
+    pub fn get_branch(&self) -> bool {
+        self.branch
+    }
+
+    /// Returns whether entries are NUL-terminated.
+    pub fn get_null_terminated(&self) -> bool {
+        self.null_terminated
+    }
+
+    /// Returns whether non-ASCII paths are quoted.
+    pub fn get_quote_path(&self) -> bool {
+        self.quote_path
+    }
+}
+
+/// The branch state shown in the porcelain branch header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchInfo {
+    /// The commit HEAD points to (None on an unborn branch).
+    oid: Option<Oid>,
+    /// The current branch name (None if HEAD is detached).
+    head: Option<String>,
+    /// The short name of the upstream branch (e.g. `origin/main`).
+    upstream: Option<String>,
+    /// Commits ahead of and behind the upstream (None if it doesn't exist).
+    ahead_behind: Option<(usize, usize)>,
+}
+
+impl BranchInfo {
+    /// Creates branch info without an upstream.
+    ///
+    /// # Arguments
+    ///
+    /// * `oid` - The commit HEAD points to, or `None` on an unborn branch.
+    /// * `head` - The current branch name, or `None` if HEAD is detached.
+    pub fn new(oid: Option<Oid>, head: Option<String>) -> Self {
+        BranchInfo {
+            oid,
+            head,
+            upstream: None,
+            ahead_behind: None,
+        }
+    }
+
+    /// Sets the upstream branch.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The short upstream name (e.g. `origin/main`).
+    /// * `ahead_behind` - The number of commits ahead of and behind the
+    ///   upstream, or `None` if the upstream branch is gone.
+    pub fn with_upstream(
+        mut self,
+        name: impl Into<String>,
+        ahead_behind: Option<(usize, usize)>,
+    ) -> Self {
+        self.upstream = Some(name.into());
+        self.ahead_behind = ahead_behind;
+        self
+    }
+
+    /// Returns the commit HEAD points to.
+    pub fn oid(&self) -> Option<&Oid> {
+        self.oid.as_ref()
+    }
+
+    /// Returns the current branch name.
+    pub fn head(&self) -> Option<&str> {
+        self.head.as_deref()
+    }
+
+    /// Returns the short name of the upstream branch.
+    pub fn upstream(&self) -> Option<&str> {
+        self.upstream.as_deref()
+    }
+
+    /// Returns the commits ahead of and behind the upstream.
+    pub fn ahead_behind(&self) -> Option<(usize, usize)> {
+        self.ahead_behind
+    }
+}
+
+/// Formats status entries in Git's porcelain format.
+///
+/// Tracked entries are written first, then untracked and then ignored ones,
+/// each group sorted by path, as Git does. Rename entries require
+/// [`StatusOptions::renames`](super::StatusOptions::renames), and `git
+/// status` collapses untracked directories by default
+/// ([`UntrackedFiles::Normal`](super::UntrackedFiles::Normal)).
+///
+/// # Arguments
+///
+/// * `entries` - The status entries.
+/// * `branch` - The branch state; the header is only printed if this is
+///   given and [`PorcelainOptions::branch`] is enabled.
+/// * `options` - The output format options.
+///
+/// # Returns
+///
+/// The output bytes.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+/// use zerogit::status::{format_porcelain, FileStatus, PorcelainOptions, StatusEntry};
+///
+/// let entries = vec![StatusEntry::new(PathBuf::from("new.txt"), FileStatus::Untracked)];
+/// let output = format_porcelain(&entries, None, &PorcelainOptions::new());
+/// assert_eq!(output, b"?? new.txt\n");
+/// ```
+pub fn format_porcelain(
+    entries: &[StatusEntry],
+    branch: Option<&BranchInfo>,
+    options: &PorcelainOptions,
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    let eol = if options.null_terminated {
+        b'\0'
+    } else {
+        b'\n'
+    };
+
+    if let Some(branch) = branch.filter(|_| options.branch) {
+        match options.version {
+            PorcelainVersion::V1 => write_branch_v1(&mut out, branch, eol),
+            PorcelainVersion::V2 => write_branch_v2(&mut out, branch, eol),
+        }
+    }
+
+    let mut sorted: Vec<&StatusEntry> = entries.iter().collect();
+    sorted.sort_by(|a, b| {
+        group(a)
+            .cmp(&group(b))
+            .then_with(|| compare_paths(&a.path, &b.path))
+    });
+
+    for entry in sorted {
+        match options.version {
+            PorcelainVersion::V1 => write_entry_v1(&mut out, entry, options),
+            PorcelainVersion::V2 => write_entry_v2(&mut out, entry, options),
+        }
+        out.push(eol);
+    }
+
+    out
+}
+
+/// Returns the output group of an entry: tracked, untracked or ignored.
+fn group(entry: &StatusEntry) -> u8 {
+    match entry.status {
+        FileStatus::Untracked => 1,
+        FileStatus::Ignored => 2,
+        _ => 0,
+    }
+}
+
+/// Compares paths byte-wise, like Git's `strcmp()` ordering.
+fn compare_paths(a: &std::path::Path, b: &std::path::Path) -> Ordering {
+    to_bytes(a).cmp(&to_bytes(b))
+}
+
+/// Writes the `## branch...upstream [ahead N, behind M]` header.
+fn write_branch_v1(out: &mut Vec<u8>, branch: &BranchInfo, eol: u8) {
+    out.extend_from_slice(b"## ");
+    match &branch.head {
+        None => out.extend_from_slice(b"HEAD (no branch)"),
+        Some(name) => {
+            if branch.oid.is_none() {
+                out.extend_from_slice(b"No commits yet on ");
+            }
+            out.extend_from_slice(name.as_bytes());
+            if let Some(upstream) = &branch.upstream {
+                out.extend_from_slice(b"...");
+                out.extend_from_slice(upstream.as_bytes());
+                match branch.ahead_behind {
+                    None => out.extend_from_slice(b" [gone]"),
+                    Some((0, 0)) => {}
+                    Some((0, behind)) => out.extend(format!(" [behind {}]", behind).bytes()),
+                    Some((ahead, 0)) => out.extend(format!(" [ahead {}]", ahead).bytes()),
+                    Some((ahead, behind)) => {
+                        out.extend(format!(" [ahead {}, behind {}]", ahead, behind).bytes())
+                    }
+                }
+            }
+        }
+    }
+    out.push(eol);
+}
+
+/// Writes the `# branch.*` header lines.
+fn write_branch_v2(out: &mut Vec<u8>, branch: &BranchInfo, eol: u8) {
+    let oid = branch
+        .oid
+        .map(|oid| oid.to_hex())
+        .unwrap_or_else(|| "(initial)".to_string());
+    out.extend(format!("# branch.oid {}", oid).bytes());
+    out.push(eol);
+
+    out.extend(format!("# branch.head {}", branch.head().unwrap_or("(detached)")).bytes());
+    out.push(eol);
+
+    if let Some(upstream) = &branch.upstream {
+        out.extend(format!("# branch.upstream {}", upstream).bytes());
+        out.push(eol);
+        if let Some((ahead, behind)) = branch.ahead_behind {
+            out.extend(format!("# branch.ab +{} -{}", ahead, behind).bytes());
+            out.push(eol);
+        }
+    }
+}
+
+/// Writes a v1 entry (`XY path` or `XY orig -> path`) without the terminator.
+fn write_entry_v1(out: &mut Vec<u8>, entry: &StatusEntry, options: &PorcelainOptions) {
+    let (x, y) = match entry.status {
+        FileStatus::Untracked => ('?', '?'),
+        FileStatus::Ignored => ('!', '!'),
+        _ => (entry.index_state.as_char(), entry.worktree_state.as_char()),
+    };
+    out.extend(format!("{}{} ", x, y).bytes());
+
+    if options.null_terminated {
+        out.extend_from_slice(&to_bytes(&entry.path));
+        if let Some(orig) = &entry.orig_path {
+            out.push(0);
+            out.extend_from_slice(&to_bytes(orig));
+        }
+    } else {
+        if let Some(orig) = &entry.orig_path {
+            out.extend(quote_path(&to_bytes(orig), true, options.quote_path));
+            out.extend_from_slice(b" -> ");
+        }
+        out.extend(quote_path(&to_bytes(&entry.path), true, options.quote_path));
+    }
+}
+
+/// Writes a v2 entry without the terminator.
+fn write_entry_v2(out: &mut Vec<u8>, entry: &StatusEntry, options: &PorcelainOptions) {
+    let path = |p: &std::path::Path| {
+        let p = to_bytes(p);
+        if options.null_terminated {
+            p.into_owned()
+        } else {
+            quote_path(&p, false, options.quote_path)
+        }
+    };
+
+    match entry.status {
+        FileStatus::Untracked | FileStatus::Ignored => {
+            let sign = if entry.status == FileStatus::Untracked {
+                "? "
+            } else {
+                "! "
+            };
+            out.extend_from_slice(sign.as_bytes());
+            out.extend(path(&entry.path));
+            return;
+        }
+        FileStatus::Conflicted => {
+            let stage = |i: usize| entry.stages[i];
+            let format = zero_format(entry.stages.iter().flatten().map(|(_, oid)| oid));
+            out.extend(
+                format!(
+                    "u {} {} {:06o} {:06o} {:06o} {:06o} {} {} {} ",
+                    key_v2(entry),
+                    submodule_token(entry),
+                    mode_bits(stage(0).map(|(mode, _)| mode)),
+                    mode_bits(stage(1).map(|(mode, _)| mode)),
+                    mode_bits(stage(2).map(|(mode, _)| mode)),
+                    mode_bits(entry.worktree_mode),
+                    oid_hex(stage(0).map(|(_, oid)| oid), format),
+                    oid_hex(stage(1).map(|(_, oid)| oid), format),
+                    oid_hex(stage(2).map(|(_, oid)| oid), format),
+                )
+                .bytes(),
+            );
+            out.extend(path(&entry.path));
+            return;
+        }
+        _ => {}
+    }
+
+    let index = entry.index;
+    // Like Git, fill in unchanged columns from the index column
+    let head = match entry.index_state {
+        EntryState::Unmodified => index,
+        _ => entry.head,
+    };
+    let worktree_mode = match entry.worktree_state {
+        EntryState::Unmodified => index.map(|(mode, _)| mode),
+        EntryState::Deleted => None,
+        _ => entry.worktree_mode,
+    };
+
+    let format = zero_format(head.iter().chain(&index).map(|(_, oid)| oid));
+    let prefix = if entry.orig_path.is_some() { '2' } else { '1' };
+    out.extend(
+        format!(
+            "{} {} {} {:06o} {:06o} {:06o} {} {} ",
+            prefix,
+            key_v2(entry),
+            submodule_token(entry),
+            mode_bits(head.map(|(mode, _)| mode)),
+            mode_bits(index.map(|(mode, _)| mode)),
+            mode_bits(worktree_mode),
+            oid_hex(head.map(|(_, oid)| oid), format),
+            oid_hex(index.map(|(_, oid)| oid), format),
+        )
+        .bytes(),
+    );
+
+    if let Some(orig) = &entry.orig_path {
+        // Only exact renames are detected
+        out.extend_from_slice(b"R100 ");
+        out.extend(path(&entry.path));
+        out.push(if options.null_terminated { 0 } else { b'\t' });
+        out.extend(path(orig));
+    } else {
+        out.extend(path(&entry.path));
+    }
+}
+
+/// Returns the v2 `XY` field, which uses `.` for unmodified.
+fn key_v2(entry: &StatusEntry) -> String {
+    let column = |state: EntryState| match state {
+        EntryState::Unmodified => '.',
+        state => state.as_char(),
+    };
+    format!(
+        "{}{}",
+        column(entry.index_state),
+        column(entry.worktree_state)
+    )
+}
+
+/// Returns the v2 submodule field.
+///
+/// Submodule commit and working tree changes are not inspected.
+fn submodule_token(entry: &StatusEntry) -> &'static str {
+    let is_submodule = [entry.head, entry.index]
+        .iter()
+        .chain(entry.stages.iter())
+        .any(|slot| matches!(slot, Some((FileMode::Submodule, _))));
+    if is_submodule {
+        "S..."
+    } else {
+        "N..."
+    }
+}
+
+/// Returns the numeric mode, or 0 if absent.
+fn mode_bits(mode: Option<FileMode>) -> u32 {
+    mode.and_then(|mode| u32::from_str_radix(mode.as_octal(), 8).ok())
+        .unwrap_or(0)
+}
+
+/// Returns the object format of the entry's OIDs, for the zero OID.
+fn zero_format<'a>(mut oids: impl Iterator<Item = &'a Oid>) -> ObjectFormat {
+    oids.next().map(Oid::format).unwrap_or_default()
+}
+
+/// Returns the hex OID, or all zeros if absent.
+fn oid_hex(oid: Option<Oid>, format: ObjectFormat) -> String {
+    oid.unwrap_or_else(|| Oid::zero(format)).to_hex()
+}
+
+/// Quotes a path like Git's `quote_path()`.
+///
+/// Paths containing control characters, `"`, `\` or (with `quote_non_ascii`)
+/// non-ASCII bytes are C-quoted. With `quote_space`, a path containing a
+/// space is also wrapped in double quotes.
+fn quote_path(bytes: &[u8], quote_space: bool, quote_non_ascii: bool) -> Vec<u8> {
+    let needs_escape =
+        |b: u8| b < 0x20 || b == b'"' || b == b'\\' || b == 0x7f || (b >= 0x80 && quote_non_ascii);
+
+    if !bytes.iter().any(|&b| needs_escape(b)) {
+        if quote_space && bytes.contains(&b' ') {
+            let mut quoted = Vec::with_capacity(bytes.len() + 2);
+            quoted.push(b'"');
+            quoted.extend_from_slice(bytes);
+            quoted.push(b'"');
+            return quoted;
+        }
+        return bytes.to_vec();
+    }
+
+    let mut quoted = vec![b'"'];
+    for &b in bytes {
+        if !needs_escape(b) {
+            quoted.push(b);
+            continue;
+        }
+        quoted.push(b'\\');
+        match b {
+            0x07 => quoted.push(b'a'),
+            0x08 => quoted.push(b'b'),
+            b'\t' => quoted.push(b't'),
+            b'\n' => quoted.push(b'n'),
+            0x0b => quoted.push(b'v'),
+            0x0c => quoted.push(b'f'),
+            b'\r' => quoted.push(b'r'),
+            b'"' | b'\\' => quoted.push(b),
+            _ => quoted.extend(format!("{:03o}", b).bytes()),
+        }
+    }
+    quoted.push(b'"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn oid(byte: u8) -> Oid {
+        Oid::from_bytes([byte; 20])
+    }
+
+    fn format(entries: &[StatusEntry], options: &PorcelainOptions) -> String {
+        String::from_utf8(format_porcelain(entries, None, options)).unwrap()
+    }
+
+    // PO-001: Path quoting follows Git's quote_path()
+    #[test]
+    fn test_quote_path() {
+        let quote =
+            |p: &str, sp: bool| String::from_utf8(quote_path(p.as_bytes(), sp, true)).unwrap();
+        assert_eq!(quote("plain.txt", true), "plain.txt");
+        assert_eq!(quote("a b", true), "\"a b\"");
+        assert_eq!(quote("a b", false), "a b");
+        assert_eq!(quote("q\"t", false), "\"q\\\"t\"");
+        assert_eq!(quote("tab\tx", false), "\"tab\\tx\"");
+        assert_eq!(quote("back\\slash", false), "\"back\\\\slash\"");
+        assert_eq!(quote("\u{1}", false), "\"\\001\"");
+        assert_eq!(quote("ü", false), "\"\\303\\274\"");
+        assert_eq!(quote_path("ü".as_bytes(), false, false), "ü".as_bytes());
+        // Non-UTF-8 bytes (here Latin-1) are quoted byte by byte
+        assert_eq!(quote_path(b"caf\xe9", false, true), b"\"caf\\351\"");
+        assert_eq!(quote_path(b"caf\xe9", false, false), b"caf\xe9");
+    }
+
+    // PO-002: v1 entries, ordering and renames
+    #[test]
+    fn test_format_v1() {
+        let mut renamed = StatusEntry::new(PathBuf::from("new name"), FileStatus::Renamed);
+        renamed.orig_path = Some(PathBuf::from("old"));
+        let entries = vec![
+            StatusEntry::new(PathBuf::from("a.txt"), FileStatus::Untracked),
+            StatusEntry::with_states(
+                PathBuf::from("b.txt"),
+                EntryState::Modified,
+                EntryState::Modified,
+            ),
+            StatusEntry::new(PathBuf::from("a-ignored"), FileStatus::Ignored),
+            StatusEntry::new(PathBuf::from("c.txt"), FileStatus::Deleted),
+            renamed,
+        ];
+
+        assert_eq!(
+            format(&entries, &PorcelainOptions::new()),
+            "MM b.txt\n D c.txt\nR  old -> \"new name\"\n?? a.txt\n!! a-ignored\n"
+        );
+        assert_eq!(
+            format(&entries, &PorcelainOptions::new().null_terminated(true)),
+            "MM b.txt\0 D c.txt\0R  new name\0old\0?? a.txt\0!! a-ignored\0"
+        );
+    }
+
+    // PO-003: Paths are ordered byte-wise, not by component
+    #[test]
+    fn test_format_path_order() {
+        let entries = vec![
+            StatusEntry::new(PathBuf::from("a/b"), FileStatus::Modified),
+            StatusEntry::new(PathBuf::from("a.txt"), FileStatus::Modified),
+        ];
+        assert_eq!(
+            format(&entries, &PorcelainOptions::new()),
+            " M a.txt\n M a/b\n"
+        );
+    }
+
+    // PO-004: v2 ordinary, renamed, unmerged, untracked entries
+    #[test]
+    fn test_format_v2() {
+        let v2 = PorcelainOptions::new().version(PorcelainVersion::V2);
+        let zero = "0".repeat(40);
+        let (h1, h2, h3) = (oid(1).to_hex(), oid(2).to_hex(), oid(3).to_hex());
+
+        let staged = StatusEntry {
+            head: Some((FileMode::Regular, oid(1))),
+            index: Some((FileMode::Regular, oid(2))),
+            ..StatusEntry::with_states(
+                PathBuf::from("staged"),
+                EntryState::Modified,
+                EntryState::Unmodified,
+            )
+        };
+        assert_eq!(
+            format(&[staged], &v2),
+            format!("1 M. N... 100644 100644 100644 {} {} staged\n", h1, h2)
+        );
+
+        let chmod = StatusEntry {
+            head: Some((FileMode::Regular, oid(1))),
+            index: Some((FileMode::Regular, oid(1))),
+            worktree_mode: Some(FileMode::Executable),
+            ..StatusEntry::with_states(
+                PathBuf::from("chmod"),
+                EntryState::Unmodified,
+                EntryState::Modified,
+            )
+        };
+        assert_eq!(
+            format(&[chmod], &v2),
+            format!("1 .M N... 100644 100644 100755 {} {} chmod\n", h1, h1)
+        );
+
+        let added = StatusEntry {
+            index: Some((FileMode::Regular, oid(2))),
+            ..StatusEntry::with_states(
+                PathBuf::from("added"),
+                EntryState::Added,
+                EntryState::Deleted,
+            )
+        };
+        assert_eq!(
+            format(&[added], &v2),
+            format!("1 AD N... 000000 100644 000000 {} {} added\n", zero, h2)
+        );
+
+        let renamed = StatusEntry {
+            orig_path: Some(PathBuf::from("from")),
+            head: Some((FileMode::Executable, oid(1))),
+            index: Some((FileMode::Executable, oid(1))),
+            ..StatusEntry::with_states(
+                PathBuf::from("to"),
+                EntryState::Renamed,
+                EntryState::Unmodified,
+            )
+        };
+        assert_eq!(
+            format(std::slice::from_ref(&renamed), &v2),
+            format!(
+                "2 R. N... 100755 100755 100755 {} {} R100 to\tfrom\n",
+                h1, h1
+            )
+        );
+        assert_eq!(
+            format(&[renamed], &v2.clone().null_terminated(true)),
+            format!(
+                "2 R. N... 100755 100755 100755 {} {} R100 to\0from\0",
+                h1, h1
+            )
+        );
+
+        let conflict = StatusEntry {
+            stages: [
+                None,
+                Some((FileMode::Regular, oid(2))),
+                Some((FileMode::Regular, oid(3))),
+            ],
+            worktree_mode: Some(FileMode::Regular),
+            ..StatusEntry::with_states(PathBuf::from("both"), EntryState::Added, EntryState::Added)
+        };
+        assert_eq!(
+            format(&[conflict], &v2),
+            format!(
+                "u AA N... 000000 100644 100644 100644 {} {} {} both\n",
+                zero, h2, h3
+            )
+        );
+
+        let others = vec![
+            StatusEntry::new(PathBuf::from("dir/"), FileStatus::Untracked),
+            StatusEntry::new(PathBuf::from("x.log"), FileStatus::Ignored),
+        ];
+        assert_eq!(format(&others, &v2), "? dir/\n! x.log\n");
+    }
+
+    // PO-005: Branch headers
+    #[test]
+    fn test_branch_headers() {
+        let header = |info: BranchInfo, version| {
+            let options = PorcelainOptions::new().version(version).branch(true);
+            String::from_utf8(format_porcelain(&[], Some(&info), &options)).unwrap()
+        };
+        let main = || BranchInfo::new(Some(oid(1)), Some("main".to_string()));
+
+        assert_eq!(header(main(), PorcelainVersion::V1), "## main\n");
+        assert_eq!(
+            header(
+                main().with_upstream("origin/main", Some((2, 0))),
+                PorcelainVersion::V1
+            ),
+            "## main...origin/main [ahead 2]\n"
+        );
+        assert_eq!(
+            header(
+                main().with_upstream("origin/main", Some((0, 3))),
+                PorcelainVersion::V1
+            ),
+            "## main...origin/main [behind 3]\n"
+        );
+        assert_eq!(
+            header(
+                main().with_upstream("origin/main", Some((1, 1))),
+                PorcelainVersion::V1
+            ),
+            "## main...origin/main [ahead 1, behind 1]\n"
+        );
+        assert_eq!(
+            header(
+                main().with_upstream("origin/main", None),
+                PorcelainVersion::V1
+            ),
+            "## main...origin/main [gone]\n"
+        );
+        assert_eq!(
+            header(
+                BranchInfo::new(None, Some("main".to_string())),
+                PorcelainVersion::V1
+            ),
+            "## No commits yet on main\n"
+        );
+        assert_eq!(
+            header(BranchInfo::new(Some(oid(1)), None), PorcelainVersion::V1),
+            "## HEAD (no branch)\n"
+        );
+
+        assert_eq!(
+            header(
+                main().with_upstream("origin/main", Some((0, 0))),
+                PorcelainVersion::V2
+            ),
+            format!(
+                "# branch.oid {}\n# branch.head main\n# branch.upstream origin/main\n# branch.ab +0 -0\n",
+                oid(1)
+            )
+        );
+        assert_eq!(
+            header(BranchInfo::new(None, None), PorcelainVersion::V2),
+            "# branch.oid (initial)\n# branch.head (detached)\n"
+        );
+
+        // Without the branch option, no header is printed
+        let info = main();
+        assert!(format_porcelain(&[], Some(&info), &PorcelainOptions::new()).is_empty());
+    }
+}
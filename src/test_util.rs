@@ -0,0 +1,14 @@
+//! Helpers shared by the unit tests and, through `#[path]`, the
+//! integration tests.
+
+/// Generates `len` pseudo-random bytes that neither compress nor repeat
+/// in any short window.
+pub(crate) fn noise(len: usize, seed: u32) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        })
+        .collect()
+}
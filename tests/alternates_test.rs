@@ -0,0 +1,185 @@
+//! Integration tests for alternate object directories
+//! (`objects/info/alternates`, `GIT_ALTERNATE_OBJECT_DIRECTORIES`).
+//!
+//! Test cases: AL-001 to AL-004
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::TempDir;
+use zerogit::objects::LooseObjectStore;
+use zerogit::repository::Repository;
+use zerogit::{FsckOptions, GcOptions, Oid};
+
+/// Writes, stages and commits a file.
+fn commit_file(repo: &Repository, path: &str, content: &str) -> Oid {
+    fs::write(repo.path().join(path), content).unwrap();
+    repo.add(path).unwrap();
+    repo.create_commit(&format!("Add {}", path), "Test User", "test@example.com")
+        .unwrap()
+}
+
+/// Creates a cache repository with two commits.
+fn create_cache() -> (TempDir, Repository) {
+    let temp = TempDir::new().unwrap();
+    let repo = Repository::init(temp.path()).unwrap();
+    commit_file(&repo, "a.txt", "cached a\n");
+    commit_file(&repo, "b.txt", "cached b\n");
+    (temp, repo)
+}
+
+/// Creates a repository in `dir` that borrows objects through the given
+/// alternates file, and checks out `target` as branch `cache`, like
+/// `git clone --reference`.
+fn create_worker(dir: &Path, alternates: &str, target: Oid) -> Repository {
+    Repository::init(dir).unwrap();
+    let objects_dir = dir.join(".git/objects");
+    fs::create_dir_all(objects_dir.join("info")).unwrap();
+    fs::write(objects_dir.join("info/alternates"), alternates).unwrap();
+    fs::write(dir.join(".git/refs/heads/cache"), format!("{}\n", target)).unwrap();
+    let repo = Repository::open(dir).unwrap();
+    repo.checkout("cache").unwrap();
+    repo
+}
+
+/// Returns the summaries of the commits reachable from HEAD.
+fn summaries(repo: &Repository) -> Vec<String> {
+    repo.log()
+        .unwrap()
+        .map(|c| c.unwrap().summary().to_string())
+        .collect()
+}
+
+/// Lists the loose objects of a repository.
+fn loose_objects(repo: &Repository) -> Vec<Oid> {
+    LooseObjectStore::new(repo.git_dir().join("objects"))
+        .list_loose_objects()
+        .unwrap()
+}
+
+// AL-001: A clone reads borrowed objects and writes new ones locally
+#[test]
+fn test_al001_reference_clone() {
+    let (cache_temp, cache) = create_cache();
+    let head = *cache.head().unwrap().oid();
+    let cached = loose_objects(&cache);
+    let alternates = format!("{}\n", cache_temp.path().join(".git/objects").display());
+    let temp = TempDir::new().unwrap();
+    let repo = create_worker(temp.path(), &alternates, head);
+
+    assert!(loose_objects(&repo).is_empty());
+    assert_eq!(summaries(&repo), ["Add b.txt", "Add a.txt"]);
+    assert_eq!(
+        fs::read_to_string(temp.path().join("a.txt")).unwrap(),
+        "cached a\n"
+    );
+    assert!(repo.status().unwrap().is_empty());
+    assert_eq!(repo.resolve_short_oid(&head.to_hex()[..7]).unwrap(), head);
+    let tree = repo.commit(&head.to_hex()).unwrap().tree().to_hex();
+    let blob = *repo.tree(&tree).unwrap().get("a.txt").unwrap().oid();
+    assert_eq!(repo.blob(&blob.to_hex()).unwrap().content(), b"cached a\n");
+
+    // New objects go to the clone; existing ones are not copied
+    let new = commit_file(&repo, "c.txt", "worker c\n");
+    commit_file(&repo, "d.txt", "cached a\n");
+    assert_eq!(loose_objects(&repo).len(), 5);
+    assert!(!loose_objects(&repo).contains(&blob));
+    assert_eq!(loose_objects(&cache), cached);
+    assert!(cache.commit(&new.to_hex()).is_err());
+    assert_eq!(summaries(&repo).len(), 4);
+    assert!(repo.fsck(FsckOptions::new()).unwrap().is_ok());
+}
+
+// AL-002: Relative and nested alternates reach packed objects
+#[test]
+fn test_al002_nested_relative_alternates() {
+    let root = TempDir::new().unwrap();
+    let (cache_temp, cache) = create_cache();
+    let head = *cache.head().unwrap().oid();
+    cache.gc(GcOptions::new()).unwrap();
+    assert!(loose_objects(&cache).is_empty());
+
+    // mirror borrows from the cache, the worker from the mirror
+    let mirror = root.path().join("mirror.git/objects");
+    fs::create_dir_all(mirror.join("info")).unwrap();
+    fs::write(
+        mirror.join("info/alternates"),
+        format!("{}\n", cache_temp.path().join(".git/objects").display()),
+    )
+    .unwrap();
+    let worker_dir = root.path().join("worker");
+    fs::create_dir(&worker_dir).unwrap();
+    let alternates = "# borrowed\n../../../mirror.git/objects\n";
+    create_worker(&worker_dir, alternates, head);
+
+    let repo = Repository::open(&worker_dir).unwrap();
+    let store = LooseObjectStore::new(repo.git_dir().join("objects"));
+    assert_eq!(
+        store.alternates(),
+        [
+            fs::canonicalize(&mirror).unwrap(),
+            fs::canonicalize(cache_temp.path().join(".git/objects")).unwrap()
+        ]
+    );
+    assert_eq!(summaries(&repo), ["Add b.txt", "Add a.txt"]);
+    assert_eq!(repo.resolve_short_oid(&head.to_hex()[..7]).unwrap(), head);
+    assert!(repo.status().unwrap().is_empty());
+}
+
+// AL-003: gc packs only local objects and prunes none of the borrowed ones
+#[test]
+fn test_al003_gc_and_fsck() {
+    let (cache_temp, cache) = create_cache();
+    let head = *cache.head().unwrap().oid();
+    let cached = loose_objects(&cache);
+    let alternates = format!("{}\n", cache_temp.path().join(".git/objects").display());
+    let temp = TempDir::new().unwrap();
+    let repo = create_worker(temp.path(), &alternates, head);
+    let new = commit_file(&repo, "c.txt", "worker c\n");
+
+    let report = repo
+        .gc(GcOptions::new().prune_expire(Duration::ZERO))
+        .unwrap();
+    assert_eq!(report.objects_packed(), 3);
+    assert_eq!(report.objects_pruned(), 0);
+    assert!(loose_objects(&repo).is_empty());
+    assert_eq!(loose_objects(&cache), cached);
+
+    assert_eq!(summaries(&repo), ["Add c.txt", "Add b.txt", "Add a.txt"]);
+    assert_eq!(repo.commit(&new.to_hex()).unwrap().parents(), [head]);
+    let report = repo.fsck(FsckOptions::new()).unwrap();
+    assert!(report.is_ok(), "{:?}", report.findings());
+    assert_eq!(report.objects_checked(), 3);
+
+    // Without the alternates, the borrowed objects are missing
+    fs::remove_file(repo.git_dir().join("objects/info/alternates")).unwrap();
+    let repo = Repository::open(repo.path()).unwrap();
+    assert!(!repo.fsck(FsckOptions::new()).unwrap().is_ok());
+}
+
+// AL-004: GIT_ALTERNATE_OBJECT_DIRECTORIES adds alternates
+#[test]
+fn test_al004_environment() {
+    let cache_temp = TempDir::new().unwrap();
+    let cache = Repository::init(cache_temp.path()).unwrap();
+    let head = commit_file(&cache, "env.txt", "only borrowed through the environment\n");
+
+    std::env::set_var(
+        "GIT_ALTERNATE_OBJECT_DIRECTORIES",
+        cache_temp.path().join(".git/objects"),
+    );
+    let temp = TempDir::new().unwrap();
+    let checkout = std::panic::catch_unwind(|| {
+        let repo = create_worker(temp.path(), "", head);
+        summaries(&repo)
+    });
+    std::env::remove_var("GIT_ALTERNATE_OBJECT_DIRECTORIES");
+
+    assert_eq!(checkout.unwrap(), ["Add env.txt"]);
+    assert_eq!(
+        fs::read_to_string(temp.path().join("env.txt")).unwrap(),
+        "only borrowed through the environment\n"
+    );
+    let repo = Repository::open(temp.path()).unwrap();
+    assert!(repo.commit(&head.to_hex()).is_err());
+}
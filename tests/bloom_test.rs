@@ -0,0 +1,228 @@
+//! Integration tests for changed-path Bloom filters in commit-graphs.
+//!
+//! Test cases: BL-001 to BL-003
+
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use zerogit::log::LogOptions;
+use zerogit::objects::{CommitGraph, ObjectFormat};
+use zerogit::{Error, Oid, Pathspec, Repository};
+
+fn fixture_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/bloom")
+}
+
+fn graph_path(repo_path: &Path) -> PathBuf {
+    repo_path.join(".git/objects/info/commit-graph")
+}
+
+/// Helper to copy the fixture to a temporary directory for modification.
+fn copy_fixture_to_temp() -> TempDir {
+    fn copy_dir_all(src: &Path, dst: &Path) {
+        fs::create_dir_all(dst).unwrap();
+        for entry in fs::read_dir(src).unwrap() {
+            let entry = entry.unwrap();
+            let dst_path = dst.join(entry.file_name());
+            if entry.path().is_dir() {
+                copy_dir_all(&entry.path(), &dst_path);
+            } else {
+                fs::copy(entry.path(), &dst_path).unwrap();
+            }
+        }
+    }
+
+    let temp = tempfile::tempdir().unwrap();
+    copy_dir_all(&fixture_path(), temp.path());
+    temp
+}
+
+/// Maps each commit summary of the fixture to its OID.
+fn commits_by_summary(repo: &Repository) -> HashMap<String, Oid> {
+    repo.log()
+        .unwrap()
+        .map(|c| {
+            let commit = c.unwrap();
+            (commit.summary().to_string(), *commit.oid())
+        })
+        .collect()
+}
+
+/// Returns the summaries of the commits that changed paths in a pathspec.
+fn log_paths(repo: &Repository, pathspec: &[&str]) -> Vec<String> {
+    let options = LogOptions::new().pathspec(Pathspec::new(pathspec).unwrap());
+    repo.log_with_options(options)
+        .unwrap()
+        .map(|c| c.unwrap().summary().to_string())
+        .collect()
+}
+
+/// Finds a chunk of a commit-graph file by its ID.
+fn chunk_range(data: &[u8], id: &[u8; 4]) -> Range<usize> {
+    let offset = |entry: usize| {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&data[entry + 4..entry + 12]);
+        u64::from_be_bytes(bytes) as usize
+    };
+    let mut entry = 8;
+    while &data[entry..entry + 4] != id {
+        entry += 12;
+    }
+    offset(entry)..offset(entry + 12)
+}
+
+/// Rewrites the commit-graph of a copied fixture, which Git made
+/// read-only.
+fn rewrite_graph(repo_path: &Path, edit: impl FnOnce(&mut Vec<u8>)) {
+    let path = graph_path(repo_path);
+    let mut data = fs::read(&path).unwrap();
+    edit(&mut data);
+    fs::remove_file(&path).unwrap();
+    fs::write(&path, data).unwrap();
+}
+
+// BL-001: Filters written by Git rule out the paths a commit did not change
+#[test]
+fn test_bl001_filters_match_changed_paths() {
+    let repo = Repository::open(fixture_path()).unwrap();
+    let graph = CommitGraph::open(fixture_path().join(".git/objects"), ObjectFormat::Sha1)
+        .unwrap()
+        .unwrap();
+    let commits = commits_by_summary(&repo);
+
+    let paths = [
+        "dir",
+        "dir/",
+        "dir/a.txt",
+        "dir/sub",
+        "dir/sub/b.txt",
+        "dir/ünïcödé.txt",
+        "other.txt",
+        "many",
+        "many/1.txt",
+        "missing.txt",
+        "dir/missing.txt",
+    ];
+    let changed: [(&str, &[&str]); 7] = [
+        ("Add a", &["dir", "dir/", "dir/a.txt"]),
+        ("Add b", &["dir", "dir/", "dir/sub", "dir/sub/b.txt"]),
+        ("Add other", &["other.txt"]),
+        ("Edit a", &["dir", "dir/", "dir/a.txt"]),
+        ("Add non-ASCII", &["dir", "dir/", "dir/ünïcödé.txt"]),
+        // Too many changes for a filter, so every path may have changed
+        ("Many files", &paths),
+        ("Edit b", &["dir", "dir/", "dir/sub", "dir/sub/b.txt"]),
+    ];
+    // The one false positive Git also reports for these paths
+    let false_positives = [("Add non-ASCII", "many")];
+    for (summary, changed) in changed {
+        for path in paths {
+            assert_eq!(
+                graph.maybe_changed(&commits[summary], path).unwrap(),
+                Some(changed.contains(&path) || false_positives.contains(&(summary, path))),
+                "{} {}",
+                summary,
+                path
+            );
+        }
+    }
+
+    // The commit made after the commit-graph has no filter
+    let after = commits["After commit-graph"];
+    assert_eq!(graph.maybe_changed(&after, "other.txt").unwrap(), None);
+}
+
+// BL-002: Path-limited log gives the same commits with or without filters
+#[test]
+fn test_bl002_log_with_filters() {
+    let temp = copy_fixture_to_temp();
+    let repo = Repository::open(temp.path()).unwrap();
+    let cases: [(&[&str], &[&str]); 7] = [
+        (&["dir/a.txt"], &["Edit a", "Add a"]),
+        (
+            &["dir"],
+            &["Edit b", "Add non-ASCII", "Edit a", "Add b", "Add a"],
+        ),
+        (&["dir/sub/"], &["Edit b", "Add b"]),
+        (&["other.txt"], &["After commit-graph", "Add other"]),
+        (
+            &["many/7.txt", "dir/ünïcödé.txt"],
+            &["Many files", "Add non-ASCII"],
+        ),
+        (
+            &["*.txt", ":!many"],
+            &[
+                "After commit-graph",
+                "Edit b",
+                "Add non-ASCII",
+                "Edit a",
+                "Add other",
+                "Add b",
+                "Add a",
+            ],
+        ),
+        (&["missing.txt"], &[]),
+    ];
+    for (pathspec, expected) in cases {
+        assert_eq!(log_paths(&repo, pathspec), expected, "{:?}", pathspec);
+    }
+
+    fs::remove_file(graph_path(temp.path())).unwrap();
+    for (pathspec, expected) in cases {
+        assert_eq!(log_paths(&repo, pathspec), expected, "{:?}", pathspec);
+    }
+}
+
+// BL-003: Filters are trusted when readable and ignored when out of range
+#[test]
+fn test_bl003_filters_consulted_and_fallback() {
+    let temp = copy_fixture_to_temp();
+    let repo = Repository::open(temp.path()).unwrap();
+    let commits = commits_by_summary(&repo);
+
+    // Empty every filter: commits in the graph are then skipped for plain
+    // paths, but not for pathspecs the filters cannot answer
+    rewrite_graph(temp.path(), |data| {
+        let bdat = chunk_range(data, b"BDAT");
+        data[bdat.start + 12..bdat.end].fill(0);
+    });
+    assert_eq!(log_paths(&repo, &["dir/a.txt"]), Vec::<String>::new());
+    assert_eq!(log_paths(&repo, &["other.txt"]), vec!["After commit-graph"]);
+    assert_eq!(log_paths(&repo, &["dir/a.tx?"]), vec!["Edit a", "Add a"]);
+    assert_eq!(
+        log_paths(&repo, &[":(icase)DIR/A.TXT"]),
+        vec!["Edit a", "Add a"]
+    );
+
+    // Filter offsets past the end of the data fall back to tree comparison
+    rewrite_graph(temp.path(), |data| {
+        let bidx = chunk_range(data, b"BIDX");
+        data[bidx].fill(0xff);
+    });
+    let graph = CommitGraph::open(temp.path().join(".git/objects"), ObjectFormat::Sha1)
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        graph.maybe_changed(&commits["Edit a"], "dir/a.txt"),
+        Err(Error::InvalidCommitGraph { .. })
+    ));
+    assert_eq!(log_paths(&repo, &["dir/a.txt"]), vec!["Edit a", "Add a"]);
+
+    // Without BIDX and BDAT the commit-graph is still used, without filters
+    rewrite_graph(temp.path(), |data| {
+        let entry = data.windows(4).position(|w| w == b"BIDX").unwrap();
+        data[entry..entry + 4].copy_from_slice(b"XXXX");
+    });
+    let graph = CommitGraph::open(temp.path().join(".git/objects"), ObjectFormat::Sha1)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        graph
+            .maybe_changed(&commits["Edit a"], "dir/a.txt")
+            .unwrap(),
+        None
+    );
+    assert_eq!(log_paths(&repo, &["dir/a.txt"]), vec!["Edit a", "Add a"]);
+}
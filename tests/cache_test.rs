@@ -0,0 +1,125 @@
+//! Integration tests for the object and delta base caches.
+//!
+//! Test cases: CA-001 to CA-003
+
+use std::fs;
+use tempfile::TempDir;
+use zerogit::log::LogOptions;
+use zerogit::repository::Repository;
+use zerogit::{CacheOptions, GcOptions, Oid};
+
+/// Helper to create a repository with six commits growing `a.txt` and
+/// editing `b.txt`.
+fn create_repo_with_history() -> (TempDir, Repository) {
+    let temp = TempDir::new().unwrap();
+    let repo = Repository::init(temp.path()).unwrap();
+    for i in 1..=6 {
+        let lines: String = (0..i * 100).map(|n| format!("line {}\n", n)).collect();
+        fs::write(temp.path().join("a.txt"), lines).unwrap();
+        fs::write(temp.path().join("b.txt"), format!("b{}\n", i % 2)).unwrap();
+        repo.add("a.txt").unwrap();
+        repo.add("b.txt").unwrap();
+        repo.create_commit(&format!("Commit {}", i), "Test User", "test@example.com")
+            .unwrap();
+    }
+    (temp, repo)
+}
+
+/// Returns the summaries of the commits that changed `b.txt`.
+fn log_b(repo: &Repository) -> Vec<String> {
+    repo.log_with_options(LogOptions::new().path("b.txt"))
+        .unwrap()
+        .map(|c| c.unwrap().summary().to_string())
+        .collect()
+}
+
+/// Returns the OIDs of every version of `a.txt`, newest first.
+fn a_versions(repo: &Repository) -> Vec<Oid> {
+    repo.log()
+        .unwrap()
+        .map(|c| {
+            let tree = repo.tree(&c.unwrap().tree().to_hex()).unwrap();
+            *tree.get("a.txt").unwrap().oid()
+        })
+        .collect()
+}
+
+// CA-001: A second history walk is answered from the object cache
+#[test]
+fn test_ca001_history_walk_reuses_objects() {
+    let (_temp, repo) = create_repo_with_history();
+    repo.clear_cache();
+
+    let expected: Vec<String> = (1..=6).rev().map(|i| format!("Commit {}", i)).collect();
+    assert_eq!(log_b(&repo), expected);
+    let first = repo.cache_stats();
+    assert!(first.object_misses() > 0);
+    assert!(first.object_bytes() > 0);
+
+    assert_eq!(log_b(&repo), expected);
+    let second = repo.cache_stats();
+    assert_eq!(second.object_misses(), first.object_misses());
+    assert!(second.object_hits() > first.object_hits());
+}
+
+// CA-002: Delta bases are reused across reads of a packed delta chain
+#[test]
+fn test_ca002_delta_base_cache() {
+    let (temp, repo) = create_repo_with_history();
+    let versions = a_versions(&repo);
+    let expected: Vec<Vec<u8>> = versions
+        .iter()
+        .map(|oid| repo.blob(&oid.to_hex()).unwrap().content().to_vec())
+        .collect();
+    repo.gc(GcOptions::new()).unwrap();
+
+    // Only the delta base cache, so every read goes to the pack
+    let repo = Repository::open(temp.path()).unwrap();
+    repo.set_cache_options(CacheOptions::new().object_limit(0));
+    for _ in 0..2 {
+        for (oid, content) in versions.iter().zip(&expected) {
+            assert_eq!(repo.blob(&oid.to_hex()).unwrap().content(), content);
+        }
+    }
+    let stats = repo.cache_stats();
+    assert!(stats.delta_base_hits() > 0);
+    assert!(stats.delta_base_bytes() > 0);
+    assert_eq!(stats.object_bytes(), 0);
+
+    // Without any cache the objects read the same
+    repo.set_cache_options(CacheOptions::new().object_limit(0).delta_base_limit(0));
+    assert_eq!(repo.cache_stats().delta_base_bytes(), 0);
+    for (oid, content) in versions.iter().zip(&expected) {
+        assert_eq!(repo.blob(&oid.to_hex()).unwrap().content(), content);
+    }
+    assert_eq!(repo.cache_stats().delta_base_bytes(), 0);
+}
+
+// CA-003: Budgets bound the memory in use and can be changed at any time
+#[test]
+fn test_ca003_cache_budgets() {
+    let (_temp, repo) = create_repo_with_history();
+    assert_eq!(repo.cache_options(), CacheOptions::new());
+
+    repo.set_cache_options(CacheOptions::new().object_limit(0));
+    repo.clear_cache();
+    let expected = log_b(&repo);
+    let stats = repo.cache_stats();
+    assert_eq!((stats.object_hits(), stats.object_bytes()), (0, 0));
+
+    repo.set_cache_options(CacheOptions::new());
+    assert_eq!(log_b(&repo), expected);
+    let full = repo.cache_stats().object_bytes();
+    assert!(full > 0);
+
+    // Shrinking the budget evicts the least recently used objects at once
+    let limit = full / 2;
+    repo.set_cache_options(CacheOptions::new().object_limit(limit));
+    assert_eq!(repo.cache_options().get_object_limit(), limit);
+    assert!(repo.cache_stats().object_bytes() <= limit);
+    assert_eq!(log_b(&repo), expected);
+    assert!(repo.cache_stats().object_bytes() <= limit);
+
+    repo.clear_cache();
+    assert_eq!(repo.cache_stats(), Default::default());
+}
@@ -0,0 +1,277 @@
+//! Integration tests for commit-graph reading and writing, and the history
+//! walks that use it.
+//!
+//! Test cases: GR-001 to GR-006
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use zerogit::objects::{CommitGraph, LooseObjectStore, ObjectFormat, ObjectType};
+use zerogit::{Error, GcOptions, Oid, Repository};
+
+fn fixture_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/commitgraph")
+}
+
+fn info_dir(repo_path: &Path) -> PathBuf {
+    repo_path.join(".git/objects/info")
+}
+
+/// Helper to copy the fixture to a temporary directory for modification.
+fn copy_fixture_to_temp() -> TempDir {
+    fn copy_dir_all(src: &Path, dst: &Path) {
+        fs::create_dir_all(dst).unwrap();
+        for entry in fs::read_dir(src).unwrap() {
+            let entry = entry.unwrap();
+            let dst_path = dst.join(entry.file_name());
+            if entry.path().is_dir() {
+                copy_dir_all(&entry.path(), &dst_path);
+            } else {
+                fs::copy(entry.path(), &dst_path).unwrap();
+            }
+        }
+    }
+
+    let temp = tempfile::tempdir().unwrap();
+    copy_dir_all(&fixture_path(), temp.path());
+    temp
+}
+
+/// Returns the summaries of `git log`, newest first.
+fn log_summaries(repo: &Repository) -> Vec<String> {
+    repo.log()
+        .unwrap()
+        .map(|c| c.unwrap().summary().to_string())
+        .collect()
+}
+
+/// Maps each commit summary of the fixture to its OID.
+fn commits_by_summary(repo: &Repository) -> HashMap<String, Oid> {
+    repo.log()
+        .unwrap()
+        .map(|c| {
+            let commit = c.unwrap();
+            (commit.summary().to_string(), *commit.oid())
+        })
+        .collect()
+}
+
+/// Finds the merge base of two commits named by summary, as a summary.
+fn merge_base(repo: &Repository, one: &str, two: &str) -> Option<String> {
+    let commits = commits_by_summary(repo);
+    let base = repo
+        .merge_base(&commits[one].to_hex(), &commits[two].to_hex())
+        .unwrap()?;
+    Some(repo.commit(&base.to_hex()).unwrap().summary().to_string())
+}
+
+const EXPECTED_LOG: [&str; 10] = [
+    "After commit-graph",
+    "Octopus",
+    "Main 2",
+    "Y",
+    "X",
+    "Merge feature",
+    "Feature 2",
+    "Main 1",
+    "Feature 1",
+    "Base",
+];
+
+// GR-001: A commit-graph chain written by Git reads back like the commits
+#[test]
+fn test_gr001_read_commit_graph_chain() {
+    let repo = Repository::open(fixture_path()).unwrap();
+    let graph = CommitGraph::open(fixture_path().join(".git/objects"), ObjectFormat::Sha1)
+        .unwrap()
+        .unwrap();
+    assert_eq!(graph.paths().len(), 2);
+    // Every commit but the one made after the chain was written
+    assert_eq!(graph.len(), 9);
+
+    let commits = commits_by_summary(&repo);
+    assert!(!graph.contains(&commits["After commit-graph"]));
+    assert_eq!(graph.get(&commits["After commit-graph"]).unwrap(), None);
+
+    let mut oids: Vec<Oid> = Vec::new();
+    for (summary, oid) in &commits {
+        if summary == "After commit-graph" {
+            continue;
+        }
+        let commit = repo.commit(&oid.to_hex()).unwrap();
+        let entry = graph.get(oid).unwrap().unwrap();
+        assert_eq!(entry.oid(), oid);
+        assert_eq!(entry.tree(), commit.tree());
+        assert_eq!(entry.parents(), commit.parents(), "{}", summary);
+        assert_eq!(entry.commit_time(), commit.committer().timestamp());
+        for parent in entry.parents() {
+            let parent = graph.get(parent).unwrap().unwrap();
+            assert!(entry.generation() > parent.generation());
+        }
+        oids.push(*oid);
+    }
+    oids.sort();
+    assert_eq!(graph.oids().unwrap(), oids);
+
+    let octopus = graph.get(&commits["Octopus"]).unwrap().unwrap();
+    assert_eq!(octopus.parents().len(), 3);
+}
+
+// GR-002: log gives the same commits, newest first, with or without a graph
+#[test]
+fn test_gr002_log_with_commit_graph() {
+    let repo = Repository::open(fixture_path()).unwrap();
+    assert_eq!(log_summaries(&repo), EXPECTED_LOG);
+
+    let temp = copy_fixture_to_temp();
+    fs::remove_dir_all(info_dir(temp.path()).join("commit-graphs")).unwrap();
+    let repo = Repository::open(temp.path()).unwrap();
+    assert_eq!(log_summaries(&repo), EXPECTED_LOG);
+
+    // Path filters compare each commit with its first parent's tree
+    let touching: Vec<String> = repo
+        .log_with_options(zerogit::log::LogOptions::new().path("main.txt"))
+        .unwrap()
+        .map(|c| c.unwrap().summary().to_string())
+        .collect();
+    assert_eq!(touching, vec!["Main 2", "Main 1"]);
+}
+
+// GR-003: merge_base finds the best common ancestor
+#[test]
+fn test_gr003_merge_base() {
+    let temp = copy_fixture_to_temp();
+    let repo = Repository::open(temp.path()).unwrap();
+    let check = |repo: &Repository| {
+        let base = |one, two| merge_base(repo, one, two);
+        assert_eq!(base("Feature 2", "Main 1").as_deref(), Some("Base"));
+        assert_eq!(base("X", "Y").as_deref(), Some("Merge feature"));
+        assert_eq!(base("Main 2", "Feature 1").as_deref(), Some("Feature 1"));
+        assert_eq!(base("Octopus", "Y").as_deref(), Some("Y"));
+        assert_eq!(base("Y", "After commit-graph").as_deref(), Some("Y"));
+        assert_eq!(base("Base", "Base").as_deref(), Some("Base"));
+    };
+    check(&repo);
+    fs::remove_dir_all(info_dir(temp.path()).join("commit-graphs")).unwrap();
+    check(&repo);
+
+    // A root commit with no history in common
+    let commits = commits_by_summary(&repo);
+    let tree = repo.commit(&commits["Base"].to_hex()).unwrap();
+    let orphan = LooseObjectStore::new(temp.path().join(".git/objects"))
+        .write(
+            ObjectType::Commit,
+            format!(
+                "tree {}\nauthor T <t@t> 1700000900 +0000\ncommitter T <t@t> 1700000900 +0000\n\nOrphan\n",
+                tree.tree()
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(
+        repo.merge_base(&orphan.to_hex(), &commits["Octopus"].to_hex())
+            .unwrap(),
+        None
+    );
+
+    assert!(matches!(
+        repo.merge_base(&tree.tree().to_hex(), &commits["Base"].to_hex()),
+        Err(Error::TypeMismatch { .. })
+    ));
+}
+
+// GR-004: write_commit_graph replaces the chain with one file Git agrees with
+#[test]
+fn test_gr004_write_commit_graph() {
+    let temp = copy_fixture_to_temp();
+    let repo = Repository::open(temp.path()).unwrap();
+    let objects_dir = temp.path().join(".git/objects");
+    let chain = CommitGraph::open(&objects_dir, ObjectFormat::Sha1)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(repo.write_commit_graph().unwrap(), 10);
+    assert!(!info_dir(temp.path()).join("commit-graphs").exists());
+    let graph = CommitGraph::open(&objects_dir, ObjectFormat::Sha1)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        graph.paths(),
+        vec![info_dir(temp.path()).join("commit-graph").as_path()]
+    );
+    assert_eq!(graph.len(), 10);
+
+    // Corrected commit dates match the ones Git computed
+    for oid in chain.oids().unwrap() {
+        assert_eq!(graph.get(&oid).unwrap(), chain.get(&oid).unwrap());
+    }
+    let commits = commits_by_summary(&repo);
+    assert!(graph.contains(&commits["After commit-graph"]));
+    assert_eq!(log_summaries(&repo), EXPECTED_LOG);
+    assert_eq!(
+        merge_base(&repo, "X", "Y").as_deref(),
+        Some("Merge feature")
+    );
+
+    // A repository without commits writes nothing
+    let empty = TempDir::new().unwrap();
+    let repo = Repository::init(empty.path()).unwrap();
+    assert_eq!(repo.write_commit_graph().unwrap(), 0);
+    assert!(!info_dir(empty.path()).join("commit-graph").exists());
+}
+
+// GR-005: A corrupt commit-graph is an error to open but walks still work
+#[test]
+fn test_gr005_corrupt_commit_graph_fallback() {
+    let temp = copy_fixture_to_temp();
+    let repo = Repository::open(temp.path()).unwrap();
+    let objects_dir = temp.path().join(".git/objects");
+    let graph_path = info_dir(temp.path()).join("commit-graph");
+    fs::write(&graph_path, b"CGPH\x09\x01\x00\x00").unwrap();
+
+    assert!(matches!(
+        CommitGraph::open(&objects_dir, ObjectFormat::Sha1),
+        Err(Error::InvalidCommitGraph { path, .. }) if path == graph_path
+    ));
+    assert_eq!(log_summaries(&repo), EXPECTED_LOG);
+    assert_eq!(
+        merge_base(&repo, "X", "Y").as_deref(),
+        Some("Merge feature")
+    );
+
+    // A chain naming a layer whose contents do not match
+    fs::remove_file(&graph_path).unwrap();
+    let chain_path = info_dir(temp.path()).join("commit-graphs/commit-graph-chain");
+    let layers = fs::read_to_string(&chain_path).unwrap();
+    let reversed: Vec<&str> = layers.lines().rev().collect();
+    fs::write(&chain_path, reversed.join("\n")).unwrap();
+    assert!(matches!(
+        CommitGraph::open(&objects_dir, ObjectFormat::Sha1),
+        Err(Error::InvalidCommitGraph { .. })
+    ));
+    assert_eq!(log_summaries(&repo), EXPECTED_LOG);
+}
+
+// GR-006: ahead/behind counts and gc use the commit-graph
+#[test]
+fn test_gr006_ahead_behind_and_gc() {
+    let temp = copy_fixture_to_temp();
+    let repo = Repository::open(temp.path()).unwrap();
+    let mut config = fs::read_to_string(temp.path().join(".git/config")).unwrap();
+    config.push_str("[branch \"main\"]\n\tremote = .\n\tmerge = refs/heads/x\n");
+    fs::write(temp.path().join(".git/config"), config).unwrap();
+
+    let info = repo.branch_info().unwrap();
+    assert_eq!(info.ahead_behind(), Some((4, 0)));
+
+    repo.gc(GcOptions::new().write_commit_graph(false)).unwrap();
+    assert!(!info_dir(temp.path()).join("commit-graph").exists());
+    repo.gc(GcOptions::new()).unwrap();
+    let graph = CommitGraph::open(temp.path().join(".git/objects"), ObjectFormat::Sha1)
+        .unwrap()
+        .unwrap();
+    assert_eq!(graph.len(), 10);
+    assert_eq!(repo.branch_info().unwrap().ahead_behind(), Some((4, 0)));
+    assert_eq!(log_summaries(&repo), EXPECTED_LOG);
+}
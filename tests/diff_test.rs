@@ -2,8 +2,8 @@
 
 use std::fs;
 use std::path::Path;
-use zerogit::diff::DiffStatus;
-use zerogit::Repository;
+use zerogit::diff::{DiffOptions, DiffStatus};
+use zerogit::{Pathspec, Repository};
 
 /// Path to the diff test fixture
 const DIFF_FIXTURE: &str = "tests/fixtures/diff";
@@ -799,3 +799,45 @@ fn test_wd008_status_consistency() {
         "Added count in diff should be >= untracked count in status"
     );
 }
+
+// WD-009: Pathspecs limit workdir, index and tree diffs
+#[test]
+fn test_wd009_pathspec_filter() {
+    let temp = copy_fixture_to_temp(DIFF_FIXTURE);
+    let repo = Repository::open(temp.path()).unwrap();
+
+    fs::write(temp.path().join("file1.txt"), "staged change\n").unwrap();
+    repo.add("file1.txt").unwrap();
+    fs::write(temp.path().join("file3.txt"), "unstaged change\n").unwrap();
+    fs::write(temp.path().join("new.txt"), "new\n").unwrap();
+
+    let paths = |diff: zerogit::TreeDiff| -> Vec<String> {
+        diff.deltas()
+            .iter()
+            .map(|d| d.path().to_string_lossy().into_owned())
+            .collect()
+    };
+
+    let options = DiffOptions::new().pathspec(Pathspec::new(["file3.txt"]).unwrap());
+    assert_eq!(
+        paths(repo.diff_index_to_workdir_with_options(&options).unwrap()),
+        ["file3.txt"]
+    );
+    assert!(repo
+        .diff_head_to_index_with_options(&options)
+        .unwrap()
+        .is_empty());
+
+    let options = DiffOptions::new().pathspec(Pathspec::new(["*.txt", ":!new.txt"]).unwrap());
+    let mut all = paths(repo.diff_head_to_workdir_with_options(&options).unwrap());
+    all.sort();
+    assert_eq!(all, ["file1.txt", "file3.txt"]);
+
+    // An empty pathspec matches everything
+    assert_eq!(
+        repo.diff_head_to_workdir_with_options(&DiffOptions::new())
+            .unwrap()
+            .len(),
+        repo.diff_head_to_workdir().unwrap().len()
+    );
+}
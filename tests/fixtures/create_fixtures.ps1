@@ -150,4 +150,98 @@ git commit -m "Add main2"
 git merge feature --no-ff -m "Merge feature"
 Set-Location ..
 
+# midx: multi-pack-index と .rev ファイルのあるリポジトリ
+if (Test-Path "midx") { Remove-Item -Recurse -Force "midx" }
+New-Item -ItemType Directory -Path "midx" | Out-Null
+Set-Location "midx"
+git init
+git config user.email "test@example.com"
+git config user.name "Test User"
+git config pack.writeReverseIndex true
+# コミットごとにパックを作成（ルーズオブジェクトは削除）
+foreach ($i in 1..3) {
+    1..($i * 100) | Set-Content -Path "numbers.txt"
+    Set-Content -Path "file$i.txt" -Value "file $i"
+    git add .
+    git commit -m "Commit $i"
+    git repack -d
+}
+git multi-pack-index write
+# multi-pack-index に含まれないパック
+Set-Content -Path "after.txt" -Value "after"
+git add after.txt
+git commit -m "After multi-pack-index"
+git repack -d
+Set-Location ..
+
+# commitgraph: 2層の commit-graph チェーン（タコ足マージを含む）と、その後のコミットを持つリポジトリ
+if (Test-Path "commitgraph") { Remove-Item -Recurse -Force "commitgraph" }
+New-Item -ItemType Directory -Path "commitgraph" | Out-Null
+Set-Location "commitgraph"
+git init
+git config user.email "test@example.com"
+git config user.name "Test User"
+# 日時を固定してコミットする（メッセージ、ファイル、Unix 時刻）
+function Commit-At($Message, $File, $Time) {
+    Set-Content -Path $File -Value $Message
+    git add $File
+    $env:GIT_AUTHOR_DATE = "$Time +0000"
+    $env:GIT_COMMITTER_DATE = "$Time +0000"
+    git commit -m $Message
+}
+Commit-At "Base" "base.txt" 1700000000
+git checkout -b feature
+Commit-At "Feature 1" "feature.txt" 1700000100
+Commit-At "Feature 2" "feature.txt" 1700000200
+git checkout main
+Commit-At "Main 1" "main.txt" 1700000150
+$env:GIT_AUTHOR_DATE = "1700000300 +0000"
+$env:GIT_COMMITTER_DATE = "1700000300 +0000"
+git merge --no-ff feature -m "Merge feature"
+git commit-graph write --reachable --split
+git checkout -b x
+Commit-At "X" "x.txt" 1700000400
+git checkout main
+git checkout -b y
+Commit-At "Y" "y.txt" 1700000500
+git checkout main
+Commit-At "Main 2" "main.txt" 1700000600
+$env:GIT_AUTHOR_DATE = "1700000700 +0000"
+$env:GIT_COMMITTER_DATE = "1700000700 +0000"
+git merge x y -m "Octopus"
+git commit-graph write --reachable --split=no-merge
+# commit-graph に含まれないコミット
+Commit-At "After commit-graph" "after.txt" 1700000800
+Remove-Item Env:GIT_AUTHOR_DATE
+Remove-Item Env:GIT_COMMITTER_DATE
+Set-Location ..
+
+# bloom: 変更パスの Bloom フィルタ付き commit-graph を持つリポジトリ
+if (Test-Path "bloom") { Remove-Item -Recurse -Force "bloom" }
+New-Item -ItemType Directory -Path "bloom/dir/sub" | Out-Null
+Set-Location "bloom"
+git init
+git config user.email "test@example.com"
+git config user.name "Test User"
+Commit-At "Add a" "dir/a.txt" 1700000000
+Commit-At "Add b" "dir/sub/b.txt" 1700000100
+Commit-At "Add other" "other.txt" 1700000200
+Commit-At "Edit a" "dir/a.txt" 1700000300
+# 0x80 以上のバイトを含むパス
+Commit-At "Add non-ASCII" "dir/ünïcödé.txt" 1700000400
+# 512 を超えるパスを変更したコミット（フィルタは全ビットが立つ）
+New-Item -ItemType Directory -Path "many" | Out-Null
+foreach ($i in 1..600) { Set-Content -Path "many/$i.txt" -Value $i }
+git add many
+$env:GIT_AUTHOR_DATE = "1700000500 +0000"
+$env:GIT_COMMITTER_DATE = "1700000500 +0000"
+git commit -m "Many files"
+Commit-At "Edit b" "dir/sub/b.txt" 1700000600
+git commit-graph write --reachable --changed-paths
+# commit-graph に含まれないコミット
+Commit-At "After commit-graph" "other.txt" 1700000700
+Remove-Item Env:GIT_AUTHOR_DATE
+Remove-Item Env:GIT_COMMITTER_DATE
+Set-Location ..
+
 Write-Host "Fixtures created successfully"
@@ -44,4 +44,85 @@ git commit -m "Feature commit"
 git checkout main
 cd ..
 
+# midx: multi-pack-index と .rev ファイルのあるリポジトリ
+rm -rf midx
+mkdir -p midx && cd midx
+git init
+git config user.email "test@example.com"
+git config user.name "Test User"
+git config pack.writeReverseIndex true
+# コミットごとにパックを作成（ルーズオブジェクトは削除）
+for i in 1 2 3; do
+    seq 1 $((i * 100)) > numbers.txt
+    echo "file $i" > "file$i.txt"
+    git add .
+    git commit -m "Commit $i"
+    git repack -d
+done
+git multi-pack-index write
+# multi-pack-index に含まれないパック
+echo "after" > after.txt
+git add after.txt
+git commit -m "After multi-pack-index"
+git repack -d
+cd ..
+
+# commitgraph: 2層の commit-graph チェーン（タコ足マージを含む）と、その後のコミットを持つリポジトリ
+rm -rf commitgraph
+mkdir -p commitgraph && cd commitgraph
+git init
+git config user.email "test@example.com"
+git config user.name "Test User"
+# 日時を固定してコミットする（メッセージ、ファイル、Unix 時刻）
+commit_at() {
+    echo "$1" > "$2"
+    git add "$2"
+    GIT_AUTHOR_DATE="$3 +0000" GIT_COMMITTER_DATE="$3 +0000" git commit -m "$1"
+}
+commit_at "Base" base.txt 1700000000
+git checkout -b feature
+commit_at "Feature 1" feature.txt 1700000100
+commit_at "Feature 2" feature.txt 1700000200
+git checkout main
+commit_at "Main 1" main.txt 1700000150
+GIT_AUTHOR_DATE="1700000300 +0000" GIT_COMMITTER_DATE="1700000300 +0000" \
+    git merge --no-ff feature -m "Merge feature"
+git commit-graph write --reachable --split
+git checkout -b x
+commit_at "X" x.txt 1700000400
+git checkout main
+git checkout -b y
+commit_at "Y" y.txt 1700000500
+git checkout main
+commit_at "Main 2" main.txt 1700000600
+GIT_AUTHOR_DATE="1700000700 +0000" GIT_COMMITTER_DATE="1700000700 +0000" \
+    git merge x y -m "Octopus"
+git commit-graph write --reachable --split=no-merge
+# commit-graph に含まれないコミット
+commit_at "After commit-graph" after.txt 1700000800
+cd ..
+
+# bloom: 変更パスの Bloom フィルタ付き commit-graph を持つリポジトリ
+rm -rf bloom
+mkdir -p bloom/dir/sub && cd bloom
+git init
+git config user.email "test@example.com"
+git config user.name "Test User"
+commit_at "Add a" dir/a.txt 1700000000
+commit_at "Add b" dir/sub/b.txt 1700000100
+commit_at "Add other" other.txt 1700000200
+commit_at "Edit a" dir/a.txt 1700000300
+# 0x80 以上のバイトを含むパス
+commit_at "Add non-ASCII" "dir/ünïcödé.txt" 1700000400
+# 512 を超えるパスを変更したコミット（フィルタは全ビットが立つ）
+mkdir many
+for i in $(seq 1 600); do echo "$i" > "many/$i.txt"; done
+git add many
+GIT_AUTHOR_DATE="1700000500 +0000" GIT_COMMITTER_DATE="1700000500 +0000" git commit -m "Many files"
+commit_at "Edit b" dir/sub/b.txt 1700000600
+git commit-graph write --reachable --changed-paths
+# commit-graph に含まれないコミット
+commit_at "After commit-graph" other.txt 1700000700
+cd ..
+
 echo "Fixtures created successfully"
@@ -0,0 +1,263 @@
+//! Integration tests for object database integrity checks (fsck).
+//!
+//! Test cases: FK-001 to FK-006
+
+use std::fs;
+use tempfile::TempDir;
+use zerogit::objects::{LooseObjectStore, ObjectType};
+use zerogit::repository::Repository;
+use zerogit::{FsckFindingKind, FsckOptions, Oid};
+
+/// Helper to create a repository with one commit of `a.txt` and `dir/b.txt`.
+fn create_repo_with_commit() -> (TempDir, Repository, Oid) {
+    let temp = TempDir::new().unwrap();
+    let repo = Repository::init(temp.path()).unwrap();
+    fs::write(temp.path().join("a.txt"), "a\n").unwrap();
+    fs::create_dir(temp.path().join("dir")).unwrap();
+    fs::write(temp.path().join("dir/b.txt"), "b\n").unwrap();
+    repo.add("a.txt").unwrap();
+    repo.add("dir/b.txt").unwrap();
+    let commit = repo
+        .create_commit("Initial commit", "Test User", "test@example.com")
+        .unwrap();
+    (temp, repo, commit)
+}
+
+/// Returns the path of a loose object file.
+fn object_path(repo: &Repository, oid: &Oid) -> std::path::PathBuf {
+    LooseObjectStore::new(repo.git_dir().join("objects")).oid_to_path(oid)
+}
+
+/// Returns the Oid of the blob stored for `path` in HEAD.
+fn blob_oid(repo: &Repository, path: &str) -> Oid {
+    repo.index()
+        .unwrap()
+        .get(std::path::Path::new(path))
+        .map(|entry| *entry.oid())
+        .unwrap()
+}
+
+// FK-001: A healthy repository has no findings
+#[test]
+fn test_fk001_clean_repository() {
+    let (_temp, repo, _) = create_repo_with_commit();
+
+    let report = repo.fsck(FsckOptions::new()).unwrap();
+    assert!(report.is_ok());
+    assert!(report.findings().is_empty(), "{:?}", report.findings());
+    // Two blobs, two trees and a commit
+    assert_eq!(report.objects_checked(), 5);
+}
+
+// FK-002: Unreadable objects and objects that do not match their name are reported
+#[test]
+fn test_fk002_corrupt_and_hash_mismatch() {
+    let (temp, repo, commit) = create_repo_with_commit();
+    let a = blob_oid(&repo, "a.txt");
+    let b = blob_oid(&repo, "dir/b.txt");
+
+    // A blob stored under the name of another blob
+    fs::write(temp.path().join("c.txt"), "c\n").unwrap();
+    repo.add("c.txt").unwrap();
+    let c = blob_oid(&repo, "c.txt");
+    fs::remove_file(object_path(&repo, &c)).unwrap();
+    fs::copy(object_path(&repo, &a), object_path(&repo, &c)).unwrap();
+
+    // Garbage instead of zlib data; object files are read-only, so they
+    // are replaced rather than overwritten
+    fs::remove_file(object_path(&repo, &a)).unwrap();
+    fs::write(object_path(&repo, &a), b"not zlib").unwrap();
+
+    // A valid object stored under the wrong name
+    fs::remove_file(object_path(&repo, &b)).unwrap();
+    fs::copy(object_path(&repo, &commit), object_path(&repo, &b)).unwrap();
+
+    let report = repo.fsck(FsckOptions::new()).unwrap();
+    assert!(!report.is_ok());
+    let corrupt = report
+        .findings()
+        .iter()
+        .find(|f| f.kind() == FsckFindingKind::Corrupt)
+        .unwrap();
+    assert_eq!(corrupt.oid(), Some(&a));
+
+    let mismatch = report
+        .findings()
+        .iter()
+        .find(|f| f.kind() == FsckFindingKind::HashMismatch && f.oid() == Some(&b))
+        .unwrap();
+    assert_eq!(mismatch.object_type(), Some(ObjectType::Commit));
+    assert!(mismatch.message().contains(&commit.to_hex()));
+
+    // Blobs are hashed as they are streamed
+    let mismatch = report
+        .findings()
+        .iter()
+        .find(|f| f.kind() == FsckFindingKind::HashMismatch && f.oid() == Some(&c))
+        .unwrap();
+    assert_eq!(mismatch.object_type(), Some(ObjectType::Blob));
+    assert!(mismatch.message().contains(&a.to_hex()));
+
+    // Corrupt objects exist, so they are not also reported as missing
+    assert!(report
+        .findings()
+        .iter()
+        .all(|f| f.kind() != FsckFindingKind::Missing));
+}
+
+// FK-003: Objects referenced by refs, the index or other objects must exist
+#[test]
+fn test_fk003_missing_objects() {
+    let (temp, repo, commit) = create_repo_with_commit();
+    let b = blob_oid(&repo, "dir/b.txt");
+    fs::remove_file(object_path(&repo, &b)).unwrap();
+
+    let missing_ref = "1234567890123456789012345678901234567890";
+    fs::write(
+        temp.path().join(".git/refs/heads/lost"),
+        format!("{}\n", missing_ref),
+    )
+    .unwrap();
+
+    let report = repo.fsck(FsckOptions::new()).unwrap();
+    assert!(!report.is_ok());
+    let missing: Vec<_> = report
+        .findings()
+        .iter()
+        .filter(|f| f.kind() == FsckFindingKind::Missing)
+        .collect();
+    assert_eq!(missing.len(), 2, "{:?}", report.findings());
+
+    let blob = missing.iter().find(|f| f.oid() == Some(&b)).unwrap();
+    assert_eq!(blob.object_type(), Some(ObjectType::Blob));
+    let by_ref = missing
+        .iter()
+        .find(|f| f.oid().map(Oid::to_hex).as_deref() == Some(missing_ref))
+        .unwrap();
+    assert_eq!(by_ref.message(), "referenced by refs/heads/lost");
+
+    // The commit itself is fine
+    assert!(report.findings().iter().all(|f| f.oid() != Some(&commit)));
+}
+
+// FK-004: Unreachable objects are reported as dangling or unreachable
+#[test]
+fn test_fk004_dangling_and_unreachable() {
+    let (_temp, repo, _) = create_repo_with_commit();
+    let store = LooseObjectStore::new(repo.git_dir().join("objects"));
+
+    // A tree and the blob only it refers to, neither reachable
+    let blob = store.write(ObjectType::Blob, b"orphan\n").unwrap();
+    let mut tree_content = b"100644 orphan.txt\0".to_vec();
+    tree_content.extend_from_slice(blob.as_bytes());
+    let tree = store.write(ObjectType::Tree, &tree_content).unwrap();
+
+    let report = repo.fsck(FsckOptions::new()).unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.findings().len(), 1, "{:?}", report.findings());
+    assert_eq!(report.findings()[0].kind(), FsckFindingKind::Dangling);
+    assert_eq!(report.findings()[0].oid(), Some(&tree));
+    assert_eq!(report.findings()[0].object_type(), Some(ObjectType::Tree));
+
+    let report = repo.fsck(FsckOptions::new().unreachable(true)).unwrap();
+    let mut unreachable: Vec<_> = report
+        .findings()
+        .iter()
+        .filter(|f| f.kind() == FsckFindingKind::Unreachable)
+        .map(|f| *f.oid().unwrap())
+        .collect();
+    unreachable.sort();
+    let mut expected = vec![blob, tree];
+    expected.sort();
+    assert_eq!(unreachable, expected);
+
+    let report = repo.fsck(FsckOptions::new().dangling(false)).unwrap();
+    assert!(report.findings().is_empty());
+    let report = repo.fsck(FsckOptions::new().connectivity(false)).unwrap();
+    assert!(report.findings().is_empty());
+}
+
+// FK-005: Malformed commits and trees are reported with the reason
+#[test]
+fn test_fk005_malformed_objects() {
+    let (temp, repo, commit) = create_repo_with_commit();
+    let store = LooseObjectStore::new(repo.git_dir().join("objects"));
+    let tree = *repo.commit(&commit.to_hex()).unwrap().tree();
+
+    let bad_commit = store
+        .write(
+            ObjectType::Commit,
+            format!(
+                "tree {}\nparent {}\nauthor Test User <test@example.com> 1700000000\n\
+                 committer Test User <test@example.com> 1700000000 +0000\n\nbad\n",
+                tree, commit
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+    let mut unsorted = b"100644 b\0".to_vec();
+    unsorted.extend_from_slice(blob_oid(&repo, "a.txt").as_bytes());
+    unsorted.extend_from_slice(b"100644 a\0");
+    unsorted.extend_from_slice(blob_oid(&repo, "a.txt").as_bytes());
+    let bad_tree = store.write(ObjectType::Tree, &unsorted).unwrap();
+    // A commit whose "tree" is a blob
+    let wrong_type = store
+        .write(
+            ObjectType::Commit,
+            format!(
+                "tree {}\nauthor T <t@t> 1 +0000\ncommitter T <t@t> 1 +0000\n\nx\n",
+                blob_oid(&repo, "a.txt")
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+    for (name, oid) in [("bad", bad_commit), ("typo", wrong_type)] {
+        fs::write(
+            temp.path().join(".git/refs/heads").join(name),
+            format!("{}\n", oid),
+        )
+        .unwrap();
+    }
+
+    let report = repo.fsck(FsckOptions::new()).unwrap();
+    assert!(!report.is_ok());
+    let malformed = |oid: &Oid| {
+        report
+            .findings()
+            .iter()
+            .find(|f| f.kind() == FsckFindingKind::Malformed && f.oid() == Some(oid))
+            .map(|f| f.message().to_string())
+    };
+    assert_eq!(
+        malformed(&bad_commit).unwrap(),
+        "invalid author/committer line - bad date"
+    );
+    assert_eq!(malformed(&bad_tree).unwrap(), "not properly sorted");
+    assert!(malformed(&wrong_type)
+        .unwrap()
+        .ends_with("is a blob, not a tree"));
+}
+
+// FK-006: Unreadable refs and reflogs are reported; an unborn HEAD is not
+#[test]
+fn test_fk006_bad_refs() {
+    let temp = TempDir::new().unwrap();
+    let repo = Repository::init(temp.path()).unwrap();
+    assert!(repo.fsck(FsckOptions::new()).unwrap().findings().is_empty());
+
+    fs::write(temp.path().join(".git/refs/heads/broken"), "nonsense\n").unwrap();
+    fs::create_dir_all(temp.path().join(".git/logs")).unwrap();
+    fs::write(temp.path().join(".git/logs/HEAD"), "garbage\n").unwrap();
+
+    let report = repo.fsck(FsckOptions::new()).unwrap();
+    assert!(!report.is_ok());
+    let messages: Vec<_> = report
+        .findings()
+        .iter()
+        .filter(|f| f.kind() == FsckFindingKind::BadRef)
+        .map(|f| f.message())
+        .collect();
+    assert_eq!(messages.len(), 2, "{:?}", messages);
+    assert!(messages[0].starts_with("refs/heads/broken: "));
+    assert_eq!(messages[1], "logs/HEAD: invalid reflog entry on line 1");
+}
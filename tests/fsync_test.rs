@@ -0,0 +1,128 @@
+//! Integration tests for durable writes (`core.fsync`, `core.fsyncMethod`).
+//!
+//! Test cases: FY-001 to FY-003
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::TempDir;
+use zerogit::objects::LooseObjectStore;
+use zerogit::repository::Repository;
+use zerogit::{FsckOptions, GcOptions, Oid};
+
+/// Helper to create a repository with the given `[core]` settings.
+fn create_repo(core: &str) -> (TempDir, Repository) {
+    let temp = TempDir::new().unwrap();
+    Repository::init(temp.path()).unwrap();
+    let mut config = OpenOptions::new()
+        .append(true)
+        .open(temp.path().join(".git/config"))
+        .unwrap();
+    write!(config, "[core]\n{}", core).unwrap();
+    let repo = Repository::open(temp.path()).unwrap();
+    (temp, repo)
+}
+
+/// Writes, stages and commits a file.
+fn commit_file(repo: &Repository, path: &str, content: &str) -> Oid {
+    let full_path = repo.path().join(path);
+    if let Some(parent) = full_path.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    fs::write(&full_path, content).unwrap();
+    repo.add(path).unwrap();
+    repo.create_commit(&format!("Add {}", path), "Test User", "test@example.com")
+        .unwrap()
+}
+
+/// Returns `true` if a file is read-only.
+fn is_read_only(path: &Path) -> bool {
+    fs::metadata(path).unwrap().permissions().readonly()
+}
+
+// FY-001: Object files are read-only, the index and refs are not
+#[test]
+fn test_fy001_read_only_objects() {
+    let (_temp, repo) = create_repo("");
+    let first = commit_file(&repo, "a.txt", "a\n");
+    commit_file(&repo, "dir/b.txt", "b\n");
+    let blob = repo.write_blob_from_reader(&b"streamed"[..], 8).unwrap();
+
+    let store = LooseObjectStore::new(repo.git_dir().join("objects"));
+    let objects = store.list_loose_objects().unwrap();
+    assert_eq!(objects.len(), 8);
+    assert!(objects.contains(&blob));
+    for oid in &objects {
+        assert!(is_read_only(&store.oid_to_path(oid)), "{}", oid);
+    }
+    assert!(!is_read_only(&repo.git_dir().join("index")));
+    assert!(!is_read_only(&repo.git_dir().join("refs/heads/main")));
+
+    // Read-only objects are still packed and pruned
+    let report = repo
+        .gc(GcOptions::new().prune_expire(Duration::ZERO))
+        .unwrap();
+    assert_eq!(report.objects_pruned(), 1);
+    assert!(store.list_loose_objects().unwrap().is_empty());
+    let pack_path = report.pack_path().unwrap();
+    assert!(is_read_only(pack_path));
+    assert!(is_read_only(&pack_path.with_extension("idx")));
+    assert!(!is_read_only(&repo.git_dir().join("packed-refs")));
+    assert_eq!(repo.log_from(first).unwrap().count(), 1);
+}
+
+// FY-002: Every method and component list writes a working repository
+#[test]
+fn test_fy002_methods_and_components() {
+    let settings = [
+        "",
+        "\tfsync = none\n",
+        "\tfsync = committed,-loose-object\n",
+        "\tfsync = added\n\tfsyncMethod = fsync\n",
+        "\tfsyncMethod = writeout-only\n",
+        "\tfsync = all\n\tfsyncMethod = batch\n",
+        "\tfsync = unknown\n\tfsyncMethod = unknown\n",
+    ];
+    for core in settings {
+        let (_temp, repo) = create_repo(core);
+        commit_file(&repo, "a.txt", "a\n");
+        let head = commit_file(&repo, "dir/b.txt", "b\n");
+        repo.create_branch("feature", None).unwrap();
+        repo.checkout("feature").unwrap();
+        commit_file(&repo, "c.txt", "c\n");
+        repo.write_commit_graph().unwrap();
+        repo.gc(GcOptions::new()).unwrap();
+
+        assert_eq!(repo.log().unwrap().count(), 3, "{:?}", core);
+        assert_eq!(
+            repo.commit(&head.to_hex()).unwrap().summary(),
+            "Add dir/b.txt"
+        );
+        assert!(repo.status().unwrap().is_empty(), "{:?}", core);
+        assert!(repo.fsck(FsckOptions::new()).unwrap().is_ok(), "{:?}", core);
+    }
+}
+
+// FY-003: In batch mode objects are readable at once, through any handle
+#[test]
+fn test_fy003_batch_mode() {
+    let (temp, repo) = create_repo("\tfsyncMethod = batch\n");
+    for i in 0..20 {
+        fs::write(temp.path().join(format!("file{}.txt", i)), i.to_string()).unwrap();
+    }
+    repo.add_all().unwrap();
+    assert_eq!(repo.index().unwrap().len(), 20);
+    let oid = repo
+        .create_commit("Many files", "Test User", "test@example.com")
+        .unwrap();
+
+    // Handles share the pending objects; a separate repository reads them
+    let handle = repo.clone_handle();
+    let blob = handle.write_blob_from_reader(&b"batch"[..], 5).unwrap();
+    let reopened = Repository::open(temp.path()).unwrap();
+    assert_eq!(reopened.head().unwrap().oid(), &oid);
+    assert_eq!(reopened.blob(&blob.to_hex()).unwrap().content(), b"batch");
+    let tree = reopened.tree(&reopened.commit(&oid.to_hex()).unwrap().tree().to_hex());
+    assert_eq!(tree.unwrap().len(), 20);
+}
@@ -0,0 +1,276 @@
+//! Integration tests for garbage collection (gc).
+//!
+//! Test cases: GC-001 to GC-006
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Duration;
+use tempfile::TempDir;
+use zerogit::objects::{LooseObjectStore, ObjectType, PackWriter};
+use zerogit::repository::Repository;
+use zerogit::{Error, FsckOptions, GcOptions, Oid};
+
+#[path = "../src/test_util.rs"]
+mod test_util;
+
+/// Helper to create a repository with three commits editing `a.txt` and
+/// `dir/b.txt`.
+fn create_repo_with_history() -> (TempDir, Repository, Oid) {
+    let temp = TempDir::new().unwrap();
+    let repo = Repository::init(temp.path()).unwrap();
+    fs::create_dir(temp.path().join("dir")).unwrap();
+    let mut commit = None;
+    for i in 1..=3 {
+        let lines: String = (0..i * 100).map(|n| format!("line {}\n", n)).collect();
+        fs::write(temp.path().join("a.txt"), lines).unwrap();
+        fs::write(temp.path().join("dir/b.txt"), format!("b{}\n", i)).unwrap();
+        repo.add("a.txt").unwrap();
+        repo.add("dir/b.txt").unwrap();
+        commit = Some(
+            repo.create_commit(&format!("Commit {}", i), "Test User", "test@example.com")
+                .unwrap(),
+        );
+    }
+    (temp, repo, commit.unwrap())
+}
+
+/// Returns the object store of a repository.
+fn store(repo: &Repository) -> LooseObjectStore {
+    LooseObjectStore::new(repo.git_dir().join("objects"))
+}
+
+/// Counts the files in `.git/objects/pack` with the given extension.
+fn count_pack_files(git_dir: &Path, extension: &str) -> usize {
+    fs::read_dir(git_dir.join("objects/pack"))
+        .map(|entries| {
+            entries
+                .filter(|e| {
+                    e.as_ref()
+                        .unwrap()
+                        .path()
+                        .extension()
+                        .is_some_and(|ext| ext == extension)
+                })
+                .count()
+        })
+        .unwrap_or(0)
+}
+
+// GC-001: Reachable objects are packed and their loose copies removed
+#[test]
+fn test_gc001_packs_reachable_objects() {
+    let (_temp, repo, head) = create_repo_with_history();
+    let before = store(&repo).list_objects().unwrap();
+    // Three blobs of a.txt, three of b.txt, six trees and three commits
+    assert_eq!(before.len(), 15);
+
+    let report = repo.gc(GcOptions::new()).unwrap();
+    assert_eq!(report.objects_packed(), 15);
+    assert_eq!(report.objects_pruned(), 0);
+    assert!(report.pack_path().unwrap().exists());
+    assert_eq!(count_pack_files(repo.git_dir(), "idx"), 1);
+
+    let store = store(&repo);
+    assert!(store.list_loose_objects().unwrap().is_empty());
+    assert_eq!(store.list_objects().unwrap(), before);
+
+    // The repository reads the same from the pack
+    let log: Vec<_> = repo.log().unwrap().map(|c| c.unwrap()).collect();
+    assert_eq!(log.len(), 3);
+    assert_eq!(log[0].oid(), &head);
+    assert!(repo.status().unwrap().is_empty());
+    let report = repo.fsck(FsckOptions::new()).unwrap();
+    assert!(report.findings().is_empty(), "{:?}", report.findings());
+    assert_eq!(report.objects_checked(), 15);
+
+    // Running again replaces the pack rather than adding one
+    repo.gc(GcOptions::new()).unwrap();
+    assert_eq!(count_pack_files(repo.git_dir(), "idx"), 1);
+    assert_eq!(count_pack_files(repo.git_dir(), "pack"), 1);
+}
+
+// GC-002: Unreachable objects are kept during the grace period, then pruned
+#[test]
+fn test_gc002_prune_unreachable() {
+    let (_temp, repo, _) = create_repo_with_history();
+    let store = store(&repo);
+
+    // A tree and the blob only it refers to, neither reachable
+    let blob = store.write(ObjectType::Blob, b"orphan\n").unwrap();
+    let mut tree_content = b"100644 orphan.txt\0".to_vec();
+    tree_content.extend_from_slice(blob.as_bytes());
+    let tree = store.write(ObjectType::Tree, &tree_content).unwrap();
+
+    let report = repo.gc(GcOptions::new()).unwrap();
+    assert_eq!(report.objects_packed(), 15);
+    assert_eq!(report.objects_pruned(), 0);
+    let mut expected = vec![blob, tree];
+    expected.sort();
+    assert_eq!(store.list_loose_objects().unwrap(), expected);
+
+    let report = repo
+        .gc(GcOptions::new().prune(false).prune_expire(Duration::ZERO))
+        .unwrap();
+    assert_eq!(report.objects_pruned(), 0);
+    assert_eq!(store.list_loose_objects().unwrap().len(), 2);
+
+    let report = repo
+        .gc(GcOptions::new().prune_expire(Duration::ZERO))
+        .unwrap();
+    assert_eq!(report.objects_pruned(), 2);
+    assert!(store.list_loose_objects().unwrap().is_empty());
+    assert!(!store.exists(&blob));
+    assert!(!store.exists(&tree));
+}
+
+// GC-003: Loose refs are moved into packed-refs and still work
+#[test]
+fn test_gc003_pack_refs() {
+    let (temp, repo, head) = create_repo_with_history();
+    let git_dir = temp.path().join(".git");
+    repo.create_branch("feature/x", None).unwrap();
+    let tag = store(&repo)
+        .write(
+            ObjectType::Tag,
+            format!(
+                "object {}\ntype commit\ntag v1.0\ntagger T <t@t> 1700000000 +0000\n\nRelease\n",
+                head
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+    fs::write(git_dir.join("refs/tags/v1.0"), format!("{}\n", tag)).unwrap();
+    fs::write(git_dir.join("refs/tags/light"), format!("{}\n", head)).unwrap();
+
+    let report = repo.gc(GcOptions::new()).unwrap();
+    assert_eq!(report.refs_packed(), 4);
+    let packed = fs::read_to_string(git_dir.join("packed-refs")).unwrap();
+    assert!(packed.starts_with("# pack-refs with: peeled fully-peeled sorted \n"));
+    assert!(packed.contains(&format!("{} refs/tags/v1.0\n^{}\n", tag, head)));
+    assert!(packed.contains(&format!("{} refs/heads/feature/x\n", head)));
+    assert!(!git_dir.join("refs/heads/main").exists());
+    assert!(!git_dir.join("refs/heads/feature").exists());
+    assert!(git_dir.join("refs/heads").is_dir());
+    assert!(git_dir.join("refs/tags").is_dir());
+
+    assert_eq!(repo.head().unwrap().oid(), &head);
+    assert_eq!(repo.branches().unwrap().len(), 2);
+    let tags = repo.tags().unwrap();
+    assert_eq!(tags.len(), 2);
+    assert!(tags.iter().all(|t| t.target() == &head));
+
+    // Branches behave the same whether loose or packed
+    assert!(matches!(
+        repo.create_branch("feature/x", None),
+        Err(Error::RefAlreadyExists(_))
+    ));
+    repo.delete_branch("feature/x").unwrap();
+    assert_eq!(repo.branches().unwrap().len(), 1);
+    assert!(matches!(
+        repo.delete_branch("feature/x"),
+        Err(Error::RefNotFound(_))
+    ));
+
+    // New commits update a loose ref that shadows the packed one
+    fs::write(temp.path().join("c.txt"), "c\n").unwrap();
+    repo.add("c.txt").unwrap();
+    let next = repo
+        .create_commit("Commit 4", "Test User", "test@example.com")
+        .unwrap();
+    assert_eq!(repo.head().unwrap().oid(), &next);
+    assert!(git_dir.join("refs/heads/main").exists());
+}
+
+// GC-004: Old packs are replaced; their unreachable objects are kept loose
+#[test]
+fn test_gc004_replaces_old_packs() {
+    let (_temp, repo, _) = create_repo_with_history();
+    let store = store(&repo);
+
+    let mut writer = PackWriter::new(repo.object_format());
+    let orphan = writer
+        .add(ObjectType::Blob, b"packed orphan\n".to_vec())
+        .unwrap();
+    let old_pack = writer.write(repo.git_dir().join("objects/pack")).unwrap();
+
+    let report = repo.gc(GcOptions::new()).unwrap();
+    assert_eq!(report.objects_packed(), 15);
+    assert!(!old_pack.index_path().exists());
+    assert!(!old_pack.pack_path().exists());
+    assert_eq!(count_pack_files(repo.git_dir(), "idx"), 1);
+    assert_eq!(store.list_loose_objects().unwrap(), vec![orphan]);
+    assert_eq!(store.read(&orphan).unwrap().content, b"packed orphan\n");
+}
+
+// GC-005: A repository with missing objects or bad refs is left untouched
+#[test]
+fn test_gc005_refuses_broken_repository() {
+    let (temp, repo, _) = create_repo_with_history();
+    let store = store(&repo);
+    let before = store.list_loose_objects().unwrap();
+
+    let missing = "1234567890123456789012345678901234567890";
+    fs::write(
+        temp.path().join(".git/refs/heads/lost"),
+        format!("{}\n", missing),
+    )
+    .unwrap();
+    assert!(matches!(
+        repo.gc(GcOptions::new()),
+        Err(Error::ObjectNotFound(oid)) if oid == missing
+    ));
+
+    fs::write(temp.path().join(".git/refs/heads/lost"), "nonsense\n").unwrap();
+    assert!(matches!(
+        repo.gc(GcOptions::new()),
+        Err(Error::InvalidRefName(_))
+    ));
+
+    assert_eq!(store.list_loose_objects().unwrap(), before);
+    assert_eq!(count_pack_files(repo.git_dir(), "pack"), 0);
+    assert!(!temp.path().join(".git/packed-refs").exists());
+}
+
+// GC-006: Blobs above core.bigFileThreshold are packed whole
+#[test]
+fn test_gc006_big_file_threshold() {
+    let noise = test_util::noise(16_000, 7);
+    let mut edited = noise.clone();
+    edited[8_000..8_010].copy_from_slice(b"0123456789");
+    let pack_size = |config: &str| {
+        let temp = TempDir::new().unwrap();
+        let repo = Repository::init(temp.path()).unwrap();
+        let mut blobs = Vec::new();
+        for content in [&noise, &edited] {
+            fs::write(temp.path().join("big.bin"), content).unwrap();
+            repo.add("big.bin").unwrap();
+            repo.create_commit("Change big.bin", "Test User", "test@example.com")
+                .unwrap();
+            blobs.push(*repo.index().unwrap().entries()[0].oid());
+        }
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(temp.path().join(".git/config"))
+            .unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        drop(file);
+
+        let report = repo.gc(GcOptions::new()).unwrap();
+        for (oid, content) in blobs.iter().zip([&noise, &edited]) {
+            let mut read = Vec::new();
+            repo.blob_reader(&oid.to_hex())
+                .unwrap()
+                .read_to_end(&mut read)
+                .unwrap();
+            assert_eq!(&read, content);
+        }
+        assert!(repo.fsck(FsckOptions::new()).unwrap().is_ok());
+        fs::metadata(report.pack_path().unwrap()).unwrap().len()
+    };
+
+    // By default the edited blob is a small delta; above the threshold
+    // both blobs are stored whole
+    assert!(pack_size("") < 20_000);
+    assert!(pack_size("[core]\n\tbigFileThreshold = 8k\n") > 32_000);
+}
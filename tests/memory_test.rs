@@ -0,0 +1,255 @@
+//! Integration tests for in-memory repositories.
+//!
+//! Test cases: MR-001 to MR-004
+
+use std::path::Path;
+use zerogit::log::LogOptions;
+use zerogit::{
+    DiffStatus, Error, FsckOptions, GcOptions, InitOptions, ObjectFormat, Oid, Pathspec,
+    RemoveOptions, Repository, StatusOptions,
+};
+
+/// Stages files and commits them.
+fn commit(repo: &Repository, message: &str, files: &[(&str, &str)]) -> Oid {
+    for (path, content) in files {
+        repo.add_content(path, content.as_bytes()).unwrap();
+    }
+    repo.create_commit(message, "Test User", "test@example.com")
+        .unwrap()
+}
+
+/// Returns the summaries of the commits reachable from a commit.
+fn log_from(repo: &Repository, oid: Oid) -> Vec<String> {
+    repo.log_from(oid)
+        .unwrap()
+        .map(|c| c.unwrap().summary().to_string())
+        .collect()
+}
+
+// MR-001: Commits, branches, tags and log work without a file system
+#[test]
+fn test_mr001_history() {
+    let repo = Repository::in_memory();
+    assert!(repo.is_in_memory());
+    assert_eq!(repo.path(), Path::new(""));
+    assert!(repo.head().is_err());
+    assert!(repo.index().unwrap().is_empty());
+
+    let first = commit(&repo, "First", &[("README.md", "# Test\n")]);
+    let second = commit(&repo, "Second", &[("src/lib.rs", "pub fn f() {}\n")]);
+    assert_eq!(repo.head().unwrap().branch_name(), Some("main"));
+    assert_eq!(repo.commit(&second.to_hex()).unwrap().parents(), &[first]);
+
+    // Branch off the first commit and commit there
+    repo.create_branch("feature/x", Some(first)).unwrap();
+    repo.checkout("feature/x").unwrap();
+    assert!(repo.index().unwrap().get(Path::new("src/lib.rs")).is_none());
+    let feature = commit(&repo, "Feature", &[("feature.txt", "x\n")]);
+    repo.create_tag("v1.0", Some(second)).unwrap();
+    repo.create_tag("wip", None).unwrap();
+
+    let branches: Vec<(String, bool)> = repo
+        .branches()
+        .unwrap()
+        .iter()
+        .map(|b| (b.name().to_string(), b.is_current()))
+        .collect();
+    assert_eq!(
+        branches,
+        vec![("feature/x".to_string(), true), ("main".to_string(), false)]
+    );
+    let tags: Vec<(String, Oid)> = repo
+        .tags()
+        .unwrap()
+        .iter()
+        .map(|t| (t.name().to_string(), *t.target()))
+        .collect();
+    assert_eq!(
+        tags,
+        vec![("v1.0".to_string(), second), ("wip".to_string(), feature)]
+    );
+    assert!(matches!(
+        repo.create_tag("v1.0", None),
+        Err(Error::RefAlreadyExists(_))
+    ));
+    assert!(matches!(
+        repo.create_tag("bad..name", None),
+        Err(Error::InvalidRefName(_))
+    ));
+
+    assert_eq!(log_from(&repo, feature), vec!["Feature", "First"]);
+    assert_eq!(log_from(&repo, second), vec!["Second", "First"]);
+    let options = LogOptions::new().path("src/lib.rs");
+    let touched: Vec<Oid> = repo
+        .log_with_options(options.clone())
+        .unwrap()
+        .map(|c| *c.unwrap().oid())
+        .collect();
+    assert!(touched.is_empty());
+    assert_eq!(
+        repo.merge_base(&feature.to_hex(), &second.to_hex())
+            .unwrap(),
+        Some(first)
+    );
+
+    // Back on main, the index follows HEAD again
+    repo.checkout("main").unwrap();
+    assert!(repo.index().unwrap().get(Path::new("src/lib.rs")).is_some());
+    assert_eq!(repo.log_with_options(options).unwrap().count(), 1);
+    repo.delete_branch("feature/x").unwrap();
+    assert_eq!(repo.branches().unwrap().len(), 1);
+    assert_eq!(repo.resolve_short_oid(&first.to_hex()[..7]).unwrap(), first);
+}
+
+// MR-002: Trees, commits and the index can be diffed
+#[test]
+fn test_mr002_diff() {
+    let repo = Repository::in_memory();
+    commit(&repo, "First", &[("a.txt", "a\n"), ("b.txt", "b\n")]);
+    let second = commit(&repo, "Second", &[("a.txt", "a2\n"), ("c.txt", "c\n")]);
+
+    let diff = repo
+        .commit_diff(&repo.commit(&second.to_hex()).unwrap())
+        .unwrap();
+    let changes: Vec<(String, DiffStatus)> = diff
+        .iter()
+        .map(|d| (d.path().display().to_string(), d.status()))
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            ("a.txt".to_string(), DiffStatus::Modified),
+            ("c.txt".to_string(), DiffStatus::Added),
+        ]
+    );
+
+    // Staged changes, then unstaged again
+    repo.add_content("b.txt", b"b2\n").unwrap();
+    repo.remove(
+        &Pathspec::new(["c.txt"]).unwrap(),
+        RemoveOptions::new().cached(true),
+    )
+    .unwrap();
+    let staged: Vec<(String, DiffStatus)> = repo
+        .diff_head_to_index()
+        .unwrap()
+        .iter()
+        .map(|d| (d.path().display().to_string(), d.status()))
+        .collect();
+    assert_eq!(
+        staged,
+        vec![
+            ("b.txt".to_string(), DiffStatus::Modified),
+            ("c.txt".to_string(), DiffStatus::Deleted),
+        ]
+    );
+    assert!(matches!(
+        repo.checkout(&second.to_hex()),
+        Err(Error::DirtyWorkingTree)
+    ));
+    repo.reset(None::<&str>).unwrap();
+    assert!(repo.diff_head_to_index().unwrap().is_empty());
+
+    // A detached HEAD moves with new commits
+    repo.checkout(&second.to_hex()).unwrap();
+    assert!(repo.head().unwrap().is_detached());
+    let third = commit(&repo, "Third", &[("d.txt", "d\n")]);
+    assert_eq!(repo.head().unwrap().oid(), &third);
+}
+
+// MR-003: Handles share one repository, separate repositories share nothing
+#[test]
+fn test_mr003_handles_and_formats() {
+    let repo = Repository::in_memory();
+    let handle = repo.clone_handle();
+    assert!(handle.is_in_memory());
+    let oid = commit(&handle, "First", &[("a.txt", "a\n")]);
+    assert_eq!(repo.head().unwrap().oid(), &oid);
+    assert_eq!(
+        repo.blob(&repo.index().unwrap().entries()[0].oid().to_hex())
+            .unwrap()
+            .content(),
+        b"a\n"
+    );
+
+    let other = Repository::in_memory();
+    assert!(other.head().is_err());
+    assert!(matches!(
+        other.commit(&oid.to_hex()),
+        Err(Error::ObjectNotFound(_))
+    ));
+
+    // Objects are named with the repository's hash function
+    let options = InitOptions::new().object_format(ObjectFormat::Sha256);
+    let sha256 = Repository::in_memory_with_options(&options);
+    let oid = commit(&sha256, "First", &[("a.txt", "a\n")]);
+    assert_eq!(oid.format(), ObjectFormat::Sha256);
+    assert_eq!(sha256.log().unwrap().count(), 1);
+    let blob = sha256.write_blob_from_reader(&b"streamed"[..], 8).unwrap();
+    let mut content = Vec::new();
+    std::io::Read::read_to_end(
+        &mut sha256.blob_reader(&blob.to_hex()).unwrap(),
+        &mut content,
+    )
+    .unwrap();
+    assert_eq!(content, b"streamed");
+}
+
+// MR-004: Operations that need files fail without touching the disk
+#[test]
+fn test_mr004_unsupported_operations() {
+    let repo = Repository::in_memory();
+    commit(&repo, "First", &[("a.txt", "a\n")]);
+
+    let unsupported = |result: Result<(), Error>| {
+        assert!(
+            matches!(result, Err(Error::UnsupportedInMemory(_))),
+            "{:?}",
+            result
+        )
+    };
+    unsupported(repo.status().map(drop));
+    unsupported(repo.status_with_options(StatusOptions::new()).map(drop));
+    unsupported(repo.add("a.txt"));
+    unsupported(repo.add_all());
+    unsupported(repo.diff_index_to_workdir().map(drop));
+    unsupported(repo.diff_head_to_workdir().map(drop));
+    unsupported(
+        repo.remove(&Pathspec::new(["a.txt"]).unwrap(), RemoveOptions::new())
+            .map(drop),
+    );
+    unsupported(repo.rename("a.txt", "b.txt").map(drop));
+    unsupported(repo.sparse_checkout_set(["src"], true));
+    unsupported(repo.gc(GcOptions::new()).map(drop));
+    unsupported(repo.fsck(FsckOptions::new()).map(drop));
+    unsupported(repo.write_commit_graph().map(drop));
+
+    assert!(repo.config().unwrap().get("user", "name").is_none());
+    for path in [
+        "",
+        "/abs.txt",
+        "../up.txt",
+        "./a.txt",
+        ".git/config",
+        "a/.GIT/x",
+    ] {
+        assert!(
+            matches!(repo.add_content(path, b"x"), Err(Error::PathNotFound(_))),
+            "{}",
+            path
+        );
+    }
+    assert_eq!(repo.index().unwrap().len(), 1);
+
+    // An on-disk repository refuses the same paths with the same error
+    let temp = tempfile::TempDir::new().unwrap();
+    let disk = Repository::init(temp.path()).unwrap();
+    for path in [".git/config", "../up.txt"] {
+        assert!(
+            matches!(disk.add(path), Err(Error::PathNotFound(_))),
+            "{}",
+            path
+        );
+    }
+    assert!(disk.index().unwrap().is_empty());
+}
@@ -0,0 +1,237 @@
+//! Integration tests for multi-pack-index and pack reverse index reading.
+//!
+//! Test cases: MI-001 to MI-005
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use zerogit::objects::{LooseObjectStore, MultiPackIndex, ObjectFormat, Pack};
+use zerogit::{FsckOptions, GcOptions, Repository};
+
+fn fixture_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/midx")
+}
+
+fn pack_dir(repo_path: &Path) -> PathBuf {
+    repo_path.join(".git/objects/pack")
+}
+
+/// Helper to copy the fixture to a temporary directory for modification.
+fn copy_fixture_to_temp() -> TempDir {
+    fn copy_dir_all(src: &Path, dst: &Path) {
+        fs::create_dir_all(dst).unwrap();
+        for entry in fs::read_dir(src).unwrap() {
+            let entry = entry.unwrap();
+            let dst_path = dst.join(entry.file_name());
+            if entry.path().is_dir() {
+                copy_dir_all(&entry.path(), &dst_path);
+            } else {
+                fs::copy(entry.path(), &dst_path).unwrap();
+            }
+        }
+    }
+
+    let temp = tempfile::tempdir().unwrap();
+    copy_dir_all(&fixture_path(), temp.path());
+    temp
+}
+
+/// Opens every pack of a repository.
+fn packs(repo_path: &Path) -> Vec<Pack> {
+    LooseObjectStore::new(repo_path.join(".git/objects"))
+        .packs()
+        .unwrap()
+}
+
+// MI-001: The multi-pack-index locates each object in the pack it names
+#[test]
+fn test_mi001_multi_pack_index_lookup() {
+    let midx = MultiPackIndex::open(
+        pack_dir(&fixture_path()).join("multi-pack-index"),
+        ObjectFormat::Sha1,
+    )
+    .unwrap();
+    let packs = packs(&fixture_path());
+    assert_eq!(packs.len(), 4);
+    assert_eq!(midx.pack_names().len(), 3);
+
+    let covered: Vec<&Pack> = packs
+        .iter()
+        .filter(|pack| {
+            let name = pack.index_path().file_name().unwrap().to_str().unwrap();
+            midx.pack_names().iter().any(|n| n == name)
+        })
+        .collect();
+    assert_eq!(covered.len(), 3);
+    assert_eq!(
+        midx.len(),
+        covered.iter().map(|pack| pack.len()).sum::<usize>()
+    );
+
+    let oids = midx.oids().unwrap();
+    assert!(oids.windows(2).all(|w| w[0] < w[1]));
+    for oid in &oids {
+        let (name, offset) = midx.find(oid).unwrap().unwrap();
+        let pack = covered
+            .iter()
+            .find(|pack| pack.index_path().ends_with(name))
+            .unwrap();
+        assert_eq!(pack.find_offset(oid).unwrap(), Some(offset));
+    }
+
+    // Objects of the pack written after the multi-pack-index are not in it
+    let uncovered = packs
+        .iter()
+        .find(|pack| !covered.iter().any(|c| c.index_path() == pack.index_path()))
+        .unwrap();
+    for oid in uncovered.oids().unwrap() {
+        assert!(!midx.contains(&oid).unwrap());
+        assert_eq!(midx.find(&oid).unwrap(), None);
+    }
+
+    let prefix = &oids[0].to_hex()[..4];
+    assert!(midx.find_by_prefix(prefix).unwrap().contains(&oids[0]));
+}
+
+// MI-002: The repository reads objects from covered and uncovered packs
+#[test]
+fn test_mi002_repository_reads_through_multi_pack_index() {
+    let repo = Repository::open(fixture_path()).unwrap();
+
+    let log: Vec<_> = repo.log().unwrap().map(|c| c.unwrap()).collect();
+    let summaries: Vec<&str> = log.iter().map(|c| c.summary()).collect();
+    assert_eq!(
+        summaries,
+        vec!["After multi-pack-index", "Commit 3", "Commit 2", "Commit 1"]
+    );
+    assert!(repo.status().unwrap().is_empty());
+
+    let store = LooseObjectStore::new(fixture_path().join(".git/objects"));
+    let mut all: Vec<_> = packs(&fixture_path())
+        .iter()
+        .flat_map(|pack| pack.oids().unwrap())
+        .collect();
+    all.sort();
+    all.dedup();
+    assert_eq!(store.list_objects().unwrap(), all);
+    for oid in &all {
+        assert!(store.exists(oid));
+        let (object_type, size) = store.read_header(oid).unwrap();
+        let raw = store.read(oid).unwrap();
+        assert_eq!(raw.object_type, object_type);
+        assert_eq!(raw.content.len() as u64, size);
+        assert_eq!(
+            store.find_objects_by_prefix(&oid.to_hex()[..8]).unwrap(),
+            vec![*oid]
+        );
+    }
+
+    let report = repo.fsck(FsckOptions::new()).unwrap();
+    assert!(report.findings().is_empty(), "{:?}", report.findings());
+    assert_eq!(report.objects_checked(), all.len());
+}
+
+// MI-003: Offsets map back to objects with and without a reverse index
+#[test]
+fn test_mi003_reverse_index() {
+    let temp = copy_fixture_to_temp();
+    for pack in packs(temp.path()) {
+        assert!(pack.index_path().with_extension("rev").exists());
+        let oids = pack.oids().unwrap();
+        let offsets: Vec<u64> = oids
+            .iter()
+            .map(|oid| pack.find_offset(oid).unwrap().unwrap())
+            .collect();
+
+        for (oid, offset) in oids.iter().zip(&offsets) {
+            assert_eq!(pack.oid_at_offset(*offset).unwrap(), Some(*oid));
+        }
+        // The pack header is not an object
+        assert_eq!(pack.oid_at_offset(0).unwrap(), None);
+        assert_eq!(pack.oid_at_offset(offsets[0] + 1).unwrap(), None);
+
+        // Without the .rev file the offsets are scanned instead
+        fs::remove_file(pack.index_path().with_extension("rev")).unwrap();
+        for (oid, offset) in oids.iter().zip(&offsets) {
+            assert_eq!(pack.oid_at_offset(*offset).unwrap(), Some(*oid));
+        }
+    }
+
+    // A reverse index that does not match its pack is an error
+    let pack = &packs(temp.path())[0];
+    let rev = pack.index_path().with_extension("rev");
+    fs::write(&rev, b"RIDX\0\0\0\x01\0\0\0\x01").unwrap();
+    assert!(matches!(
+        pack.oid_at_offset(12),
+        Err(zerogit::Error::InvalidPack { path, .. }) if path == rev
+    ));
+}
+
+// MI-004: A missing, corrupt or stale multi-pack-index falls back to the packs
+#[test]
+fn test_mi004_fallback_to_pack_indexes() {
+    let temp = copy_fixture_to_temp();
+    let midx_path = pack_dir(temp.path()).join("multi-pack-index");
+    let all = LooseObjectStore::new(temp.path().join(".git/objects"))
+        .list_objects()
+        .unwrap();
+    let check = |expected: &[zerogit::Oid]| {
+        let store = LooseObjectStore::new(temp.path().join(".git/objects"));
+        assert_eq!(store.list_objects().unwrap(), expected);
+        for oid in expected {
+            store.read(oid).unwrap();
+        }
+    };
+
+    let midx = fs::read(&midx_path).unwrap();
+    fs::remove_file(&midx_path).unwrap();
+    check(&all);
+
+    fs::write(&midx_path, &midx[..100]).unwrap();
+    check(&all);
+    let mut bad_version = midx.clone();
+    bad_version[4] = 9;
+    fs::write(&midx_path, &bad_version).unwrap();
+    check(&all);
+
+    // With a pack it names gone, the multi-pack-index is ignored and the
+    // remaining packs are searched directly
+    fs::write(&midx_path, &midx).unwrap();
+    let removed = MultiPackIndex::open(&midx_path, ObjectFormat::Sha1)
+        .unwrap()
+        .pack_names()[0]
+        .clone();
+    let removed_pack =
+        Pack::open(pack_dir(temp.path()).join(&removed), ObjectFormat::Sha1).unwrap();
+    let removed_oids = removed_pack.oids().unwrap();
+    fs::remove_file(removed_pack.pack_path()).unwrap();
+    fs::remove_file(removed_pack.index_path()).unwrap();
+    let remaining: Vec<_> = all
+        .iter()
+        .filter(|oid| !removed_oids.contains(oid))
+        .copied()
+        .collect();
+    check(&remaining);
+}
+
+// MI-005: gc replaces the packs and removes the multi-pack-index
+#[test]
+fn test_mi005_gc_removes_multi_pack_index() {
+    let temp = copy_fixture_to_temp();
+    let repo = Repository::open(temp.path()).unwrap();
+    let before = LooseObjectStore::new(temp.path().join(".git/objects"))
+        .list_objects()
+        .unwrap();
+
+    let report = repo.gc(GcOptions::new()).unwrap();
+    assert_eq!(report.objects_packed(), before.len());
+
+    let remaining: Vec<_> = fs::read_dir(pack_dir(temp.path()))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(remaining.len(), 2, "{:?}", remaining);
+    assert!(remaining.iter().all(|name| name.starts_with("pack-")));
+    assert_eq!(repo.log().unwrap().count(), 4);
+    assert!(repo.fsck(FsckOptions::new()).unwrap().is_ok());
+}
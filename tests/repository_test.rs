@@ -355,3 +355,89 @@ fn test_rp013_init_creates_parent_dirs() {
     // Verify the repository was created
     assert!(repo_path.join(".git/HEAD").is_file());
 }
+
+// RP-014: Repository::read_header returns the type and size of objects
+#[test]
+fn test_rp014_read_header() {
+    use zerogit::objects::ObjectType;
+
+    let repo = Repository::open(SIMPLE_FIXTURE).unwrap();
+    let head = repo.head().unwrap().oid().to_hex();
+    let commit = repo.commit(&head).unwrap();
+
+    let (object_type, _) = repo.read_header(&head[..7]).unwrap();
+    assert_eq!(object_type, ObjectType::Commit);
+
+    let tree_oid = commit.tree().to_hex();
+    let (object_type, _) = repo.read_header(&tree_oid).unwrap();
+    assert_eq!(object_type, ObjectType::Tree);
+
+    for entry in repo.tree(&tree_oid).unwrap().iter() {
+        let oid = entry.oid().to_hex();
+        let (object_type, size) = repo.read_header(&oid).unwrap();
+        assert_eq!(object_type, ObjectType::Blob);
+        assert_eq!(size, repo.blob(&oid).unwrap().size() as u64);
+    }
+
+    assert!(matches!(
+        repo.read_header("0000000000000000000000000000000000000000"),
+        Err(Error::ObjectNotFound(_))
+    ));
+}
+
+// RP-015: Repository::init_with_options creates a SHA-256 repository
+#[test]
+fn test_rp015_init_sha256() {
+    use zerogit::{InitOptions, ObjectFormat};
+
+    let temp = TempDir::new().unwrap();
+    let options = InitOptions::new().object_format(ObjectFormat::Sha256);
+    let repo = Repository::init_with_options(temp.path(), &options).unwrap();
+    assert_eq!(repo.object_format(), ObjectFormat::Sha256);
+
+    fs::write(temp.path().join("hello.txt"), "hello\n").unwrap();
+    fs::create_dir(temp.path().join("dir")).unwrap();
+    fs::write(temp.path().join("dir/nested.txt"), "nested\n").unwrap();
+    repo.add("hello.txt").unwrap();
+    repo.add("dir/nested.txt").unwrap();
+    let commit_oid = repo
+        .create_commit("Initial commit", "Test User", "test@example.com")
+        .unwrap();
+    assert_eq!(commit_oid.to_hex().len(), 64);
+
+    // Reopening reads the object format from the config
+    let repo = Repository::open(temp.path()).unwrap();
+    assert_eq!(repo.object_format(), ObjectFormat::Sha256);
+    assert_eq!(repo.head().unwrap().oid(), &commit_oid);
+
+    let commit = repo.commit(&commit_oid.to_hex()).unwrap();
+    let tree = repo.tree(&commit.tree().to_hex()).unwrap();
+    let blob_oid = tree.get("hello.txt").unwrap().oid().to_hex();
+    assert_eq!(
+        blob_oid,
+        "2cf8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4"
+    );
+    assert_eq!(repo.blob(&blob_oid).unwrap().content(), b"hello\n");
+    assert_eq!(repo.blob(&blob_oid[..10]).unwrap().content(), b"hello\n");
+
+    assert!(repo.status().unwrap().is_empty());
+    let index = repo.index().unwrap();
+    assert_eq!(index.object_format(), ObjectFormat::Sha256);
+    assert_eq!(index.len(), 2);
+}
+
+// RP-016: Repository::open rejects an unknown object format
+#[test]
+fn test_rp016_open_unsupported_object_format() {
+    let temp = TempDir::new().unwrap();
+    Repository::init(temp.path()).unwrap();
+    let config = temp.path().join(".git/config");
+    let mut content = fs::read_to_string(&config).unwrap();
+    content.push_str("[extensions]\n\tobjectFormat = sha3\n");
+    fs::write(&config, content).unwrap();
+
+    assert!(matches!(
+        Repository::open(temp.path()),
+        Err(Error::UnsupportedObjectFormat(name)) if name == "sha3"
+    ));
+}
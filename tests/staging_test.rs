@@ -1,12 +1,14 @@
-//! Integration tests for staging area operations (add, add_all, reset).
+//! Integration tests for staging area operations (add, add_all, reset, remove, rename).
 //!
-//! Test cases: W-001 to W-003
+//! Test cases: W-001 to W-014
 
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 use tempfile::TempDir;
 use zerogit::repository::Repository;
-use zerogit::status::FileStatus;
+use zerogit::status::{FileStatus, StatusOptions};
+use zerogit::{Error, Pathspec, RemoveOptions};
 
 /// Helper to create a minimal git repository for testing.
 fn create_test_repo() -> TempDir {
@@ -300,6 +302,495 @@ fn test_w003_reset_empty_repo() {
     assert_eq!(status[0].status(), FileStatus::Untracked);
 }
 
+// W-004: add_all_with_options() with multiple threads stages everything
+#[test]
+fn test_w004_add_all_parallel() {
+    let temp = create_test_repo();
+    let path = temp.path();
+
+    for d in 0..4 {
+        fs::create_dir(path.join(format!("dir{}", d))).unwrap();
+        for f in 0..10 {
+            fs::write(
+                path.join(format!("dir{}/file{}.txt", d, f)),
+                format!("content {} {}", d, f),
+            )
+            .unwrap();
+        }
+    }
+
+    let repo = Repository::open(path).unwrap();
+    repo.add_all_with_options(StatusOptions::new().threads(4))
+        .unwrap();
+
+    let status = repo
+        .status_with_options(StatusOptions::new().threads(4))
+        .unwrap();
+    assert_eq!(status.len(), 40);
+    assert!(status.iter().all(|e| e.status() == FileStatus::Added));
+    assert_eq!(status, repo.status().unwrap());
+
+    // Running again is a no-op for unchanged files
+    repo.add_all_with_options(StatusOptions::new().threads(4))
+        .unwrap();
+    assert_eq!(status, repo.status().unwrap());
+}
+
+// W-005: add_pathspec() stages only matching files
+#[test]
+fn test_w005_add_pathspec() {
+    let temp = create_test_repo();
+    let path = temp.path();
+    fs::create_dir(path.join("src")).unwrap();
+    fs::write(path.join("src/lib.rs"), "lib").unwrap();
+    fs::write(path.join("src/gen.rs"), "gen").unwrap();
+    fs::write(path.join("README.md"), "readme").unwrap();
+
+    let repo = Repository::open(path).unwrap();
+    repo.add_pathspec(&Pathspec::new(["*.rs", ":!src/gen.rs"]).unwrap())
+        .unwrap();
+
+    let status = repo.status().unwrap();
+    let staged: Vec<_> = status
+        .iter()
+        .filter(|e| e.status() == FileStatus::Added)
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    assert_eq!(staged, [Path::new("src/lib.rs")]);
+
+    // A pathspec item matching nothing is an error
+    let result = repo.add_pathspec(&Pathspec::new(["missing"]).unwrap());
+    assert!(matches!(result, Err(Error::PathNotFound(p)) if p == Path::new("missing")));
+}
+
+// W-006: reset_pathspec() unstages only matching entries
+#[test]
+fn test_w006_reset_pathspec() {
+    let temp = create_repo_with_commit();
+    let path = temp.path();
+    fs::write(path.join("file.txt"), "changed\n").unwrap();
+    fs::write(path.join("a.md"), "a").unwrap();
+    fs::write(path.join("b.rs"), "b").unwrap();
+
+    let repo = Repository::open(path).unwrap();
+    repo.add_all().unwrap();
+    repo.reset_pathspec(&Pathspec::new([":(exclude)b.rs"]).unwrap())
+        .unwrap();
+
+    let status = repo.status().unwrap();
+    let find = |name: &str| {
+        status
+            .iter()
+            .find(|e| e.path() == Path::new(name))
+            .map(|e| e.status())
+    };
+    assert_eq!(find("file.txt"), Some(FileStatus::Modified));
+    assert_eq!(find("a.md"), Some(FileStatus::Untracked));
+    assert_eq!(find("b.rs"), Some(FileStatus::Added));
+}
+
+/// Helper to create a repository with committed files in nested directories.
+fn create_repo_with_tree() -> (TempDir, Repository) {
+    let temp = create_test_repo();
+    let path = temp.path();
+    fs::create_dir_all(path.join("src/util")).unwrap();
+    fs::write(path.join("README.md"), "readme\n").unwrap();
+    fs::write(path.join("src/lib.rs"), "lib\n").unwrap();
+    fs::write(path.join("src/util/mod.rs"), "util\n").unwrap();
+
+    let repo = Repository::open(path).unwrap();
+    repo.add_all().unwrap();
+    repo.create_commit("Initial commit", "Test User", "test@example.com")
+        .unwrap();
+    (temp, repo)
+}
+
+/// Returns the paths tracked in the index.
+fn tracked(repo: &Repository) -> Vec<String> {
+    repo.index()
+        .unwrap()
+        .iter()
+        .map(|e| e.path().to_string_lossy().into_owned())
+        .collect()
+}
+
+// W-007: remove() untracks files and protects uncommitted content
+#[test]
+fn test_w007_remove() {
+    let (temp, repo) = create_repo_with_tree();
+    let path = temp.path();
+    let spec = |s: &str| Pathspec::new([s]).unwrap();
+
+    // Unknown paths and directories without recursive are refused
+    assert!(matches!(
+        repo.remove(&spec("missing"), RemoveOptions::new()),
+        Err(Error::PathNotFound(_))
+    ));
+    assert!(matches!(
+        repo.remove(&spec("src"), RemoveOptions::new()),
+        Err(Error::NotRecursive(s)) if s == "src"
+    ));
+
+    // Local modifications are refused unless cached or forced
+    fs::write(path.join("README.md"), "changed\n").unwrap();
+    assert!(matches!(
+        repo.remove(&spec("README.md"), RemoveOptions::new()),
+        Err(Error::UncommittedChanges { path, reason })
+            if path == Path::new("README.md") && reason == "has local modifications"
+    ));
+    let removed = repo
+        .remove(&spec("README.md"), RemoveOptions::new().cached(true))
+        .unwrap();
+    assert_eq!(removed, [Path::new("README.md")]);
+    assert!(path.join("README.md").exists());
+    assert_eq!(tracked(&repo), ["src/lib.rs", "src/util/mod.rs"]);
+
+    // Staged changes that differ from the working tree are refused even
+    // with cached
+    fs::write(path.join("src/lib.rs"), "staged\n").unwrap();
+    repo.add("src/lib.rs").unwrap();
+    fs::write(path.join("src/lib.rs"), "unstaged\n").unwrap();
+    assert!(matches!(
+        repo.remove(&spec("src/lib.rs"), RemoveOptions::new().cached(true)),
+        Err(Error::UncommittedChanges { path, reason })
+            if path == Path::new("src/lib.rs")
+                && reason == "has staged content different from both the file and HEAD"
+    ));
+
+    // Recursive and forced removal deletes files and empty directories
+    let removed = repo
+        .remove(
+            &spec("src"),
+            RemoveOptions::new().recursive(true).force(true),
+        )
+        .unwrap();
+    assert_eq!(
+        removed,
+        [Path::new("src/lib.rs"), Path::new("src/util/mod.rs")]
+    );
+    assert!(!path.join("src").exists());
+    assert!(tracked(&repo).is_empty());
+}
+
+// W-008: rename() moves files and directories in the index and working tree
+#[test]
+fn test_w008_rename() {
+    let (temp, repo) = create_repo_with_tree();
+    let path = temp.path();
+
+    // File rename keeps the entry clean
+    let to = repo.rename("README.md", "docs/README.md").unwrap();
+    assert_eq!(to, Path::new("docs/README.md"));
+    assert!(!path.join("README.md").exists());
+    assert_eq!(
+        fs::read_to_string(path.join("docs/README.md")).unwrap(),
+        "readme\n"
+    );
+
+    // Moving into an existing directory keeps the source's name
+    let to = repo.rename("src/util", "docs").unwrap();
+    assert_eq!(to, Path::new("docs/util"));
+    assert!(!path.join("src/util").exists());
+    assert!(path.join("docs/util/mod.rs").exists());
+    assert_eq!(
+        tracked(&repo),
+        ["docs/README.md", "docs/util/mod.rs", "src/lib.rs"]
+    );
+
+    let status = repo
+        .status_with_options(StatusOptions::new().renames(true))
+        .unwrap();
+    assert_eq!(status.len(), 2);
+    assert!(status.iter().all(|e| e.status() == FileStatus::Renamed));
+
+    // Invalid renames are refused
+    assert!(matches!(
+        repo.rename("src/lib.rs", "docs/README.md"),
+        Err(Error::InvalidRename {
+            reason: "destination exists",
+            ..
+        })
+    ));
+    fs::write(path.join("untracked.txt"), "u").unwrap();
+    assert!(matches!(
+        repo.rename("untracked.txt", "other.txt"),
+        Err(Error::InvalidRename { .. })
+    ));
+    assert!(matches!(
+        repo.rename("docs", "docs/inner"),
+        Err(Error::InvalidRename { .. })
+    ));
+    for same in ["src/lib.rs", "docs"] {
+        assert!(matches!(
+            repo.rename(same, same),
+            Err(Error::InvalidRename {
+                reason: "source and destination are the same",
+                ..
+            })
+        ));
+    }
+    assert!(matches!(
+        repo.rename("missing.txt", "other.txt"),
+        Err(Error::PathNotFound(_))
+    ));
+}
+
+// W-009: The index is written in the version requested by index.version
+#[test]
+fn test_w009_index_version_config() {
+    let (temp, repo) = create_repo_with_tree();
+    let set_version = |version: u32| {
+        let mut config = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(temp.path().join(".git/config"))
+            .unwrap();
+        write!(config, "[index]\n\tversion = {}\n", version).unwrap();
+    };
+    let index_version = || {
+        let data = fs::read(repo.git_dir().join("index")).unwrap();
+        u32::from_be_bytes(data[4..8].try_into().unwrap())
+    };
+    assert_eq!(index_version(), 2);
+
+    set_version(4);
+    fs::write(temp.path().join("src/lib.rs"), "changed\n").unwrap();
+    repo.add("src/lib.rs").unwrap();
+    assert_eq!(index_version(), 4);
+    assert_eq!(
+        tracked(&repo),
+        ["README.md", "src/lib.rs", "src/util/mod.rs"]
+    );
+
+    let status = repo.status().unwrap();
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].status(), FileStatus::StagedModified);
+
+    // A v4 index is read back when committing and can be downgraded
+    repo.create_commit("v4", "Test User", "test@example.com")
+        .unwrap();
+    set_version(2);
+    repo.add("src/lib.rs").unwrap();
+    assert_eq!(index_version(), 2);
+    assert!(repo.status().unwrap().is_empty());
+
+    // Version 3 is only used when an entry needs extended flags
+    set_version(3);
+    fs::write(temp.path().join("README.md"), "changed\n").unwrap();
+    repo.add("README.md").unwrap();
+    assert_eq!(index_version(), 2);
+}
+
+// W-010: core.splitIndex keeps unchanged entries in a shared index
+#[test]
+fn test_w010_split_index() {
+    let (temp, repo) = create_repo_with_tree();
+    let git_dir = temp.path().join(".git");
+    let shared_files = || {
+        let mut names: Vec<String> = fs::read_dir(&git_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("sharedindex."))
+            .collect();
+        names.sort();
+        names
+    };
+
+    // Enabling split mode writes a shared index with every entry
+    fs::write(git_dir.join("config"), "[core]\n\tsplitIndex = true\n").unwrap();
+    fs::write(temp.path().join("src/lib.rs"), "changed\n").unwrap();
+    repo.add("src/lib.rs").unwrap();
+    let shared = shared_files();
+    assert_eq!(shared.len(), 1);
+    let index = repo.index().unwrap();
+    assert_eq!(
+        index
+            .shared_index()
+            .map(|oid| format!("sharedindex.{}", oid)),
+        Some(shared[0].clone())
+    );
+
+    // A small change only rewrites .git/index
+    fs::write(temp.path().join("README.md"), "changed\n").unwrap();
+    repo.add("README.md").unwrap();
+    assert_eq!(shared_files(), shared);
+    let split = zerogit::index::parse(&fs::read(git_dir.join("index")).unwrap()).unwrap();
+    assert_eq!(split.len(), 1);
+    assert_eq!(
+        tracked(&repo),
+        ["README.md", "src/lib.rs", "src/util/mod.rs"]
+    );
+    let status = repo.status().unwrap();
+    assert_eq!(status.len(), 2);
+    assert!(status
+        .iter()
+        .all(|entry| entry.status() == FileStatus::StagedModified));
+
+    // With maxPercentChange = 0 every write creates a new shared index
+    fs::write(
+        git_dir.join("config"),
+        "[core]\n\tsplitIndex = true\n[splitIndex]\n\tmaxPercentChange = 0\n",
+    )
+    .unwrap();
+    repo.add("README.md").unwrap();
+    assert_eq!(shared_files().len(), 2);
+
+    // With sharedIndexExpire = now the unreferenced shared indexes are deleted
+    fs::write(
+        git_dir.join("config"),
+        "[core]\n\tsplitIndex = true\n[splitIndex]\n\tmaxPercentChange = 0\n\tsharedIndexExpire = now\n",
+    )
+    .unwrap();
+    fs::write(temp.path().join("README.md"), "changed again\n").unwrap();
+    repo.add("README.md").unwrap();
+    let shared = shared_files();
+    assert_eq!(shared.len(), 1);
+    let index = repo.index().unwrap();
+    assert_eq!(
+        index
+            .shared_index()
+            .map(|oid| format!("sharedindex.{}", oid)),
+        Some(shared[0].clone())
+    );
+
+    // Disabling split mode writes a full index again
+    fs::write(git_dir.join("config"), "[core]\n\tsplitIndex = false\n").unwrap();
+    repo.add("README.md").unwrap();
+    let index = zerogit::index::parse(&fs::read(git_dir.join("index")).unwrap()).unwrap();
+    assert!(index.shared_index().is_none());
+    assert_eq!(index.len(), 3);
+}
+
+// W-011: sparse_checkout_set() skips paths outside the sparse set
+#[test]
+fn test_w011_sparse_checkout() {
+    let (temp, repo) = create_repo_with_tree();
+    let path = temp.path();
+    let git_dir = path.join(".git");
+    repo.create_branch("old", None).unwrap();
+    fs::create_dir_all(path.join("docs")).unwrap();
+    fs::write(path.join("docs/guide.md"), "guide\n").unwrap();
+    repo.add("docs/guide.md").unwrap();
+    repo.create_commit("Add docs", "Test User", "test@example.com")
+        .unwrap();
+    let skipped = |repo: &Repository| -> Vec<String> {
+        repo.index()
+            .unwrap()
+            .iter()
+            .filter(|e| e.is_skip_worktree())
+            .map(|e| e.path().to_string_lossy().into_owned())
+            .collect()
+    };
+
+    // Cone mode: only the top level and docs/ stay
+    repo.sparse_checkout_set(["docs"], true).unwrap();
+    assert_eq!(
+        fs::read_to_string(git_dir.join("info/sparse-checkout")).unwrap(),
+        "/*\n!/*/\n/docs/\n"
+    );
+    let config = repo.config().unwrap();
+    assert!(config.get_bool("core", "sparseCheckout").unwrap());
+    assert!(config.get_bool("core", "sparseCheckoutCone").unwrap());
+    assert!(!path.join("src").exists());
+    assert!(path.join("docs/guide.md").exists());
+    assert_eq!(skipped(&repo), ["src/lib.rs", "src/util/mod.rs"]);
+    assert!(repo.status().unwrap().is_empty());
+    assert!(repo.diff_index_to_workdir().unwrap().is_empty());
+
+    // Paths outside the sparse set are not staged
+    fs::create_dir_all(path.join("src")).unwrap();
+    fs::write(path.join("src/new.rs"), "new\n").unwrap();
+    assert!(matches!(
+        repo.add("src/new.rs"),
+        Err(Error::OutsideSparseCheckout(_))
+    ));
+    repo.add_all().unwrap();
+    assert_eq!(
+        tracked(&repo),
+        [
+            "README.md",
+            "docs/guide.md",
+            "src/lib.rs",
+            "src/util/mod.rs"
+        ]
+    );
+    fs::remove_dir_all(path.join("src")).unwrap();
+
+    // Checkout only writes files inside the sparse set
+    repo.checkout("old").unwrap();
+    assert!(!path.join("docs").exists());
+    assert!(!path.join("src").exists());
+    assert_eq!(skipped(&repo), ["src/lib.rs", "src/util/mod.rs"]);
+    repo.checkout("main").unwrap();
+    assert_eq!(
+        fs::read_to_string(path.join("docs/guide.md")).unwrap(),
+        "guide\n"
+    );
+    assert!(!path.join("src").exists());
+    assert!(repo.status().unwrap().is_empty());
+
+    // Non-cone patterns bring src/util back and drop docs/
+    repo.sparse_checkout_set(["/*", "!/*/", "/src/util/"], false)
+        .unwrap();
+    assert!(!repo
+        .config()
+        .unwrap()
+        .get_bool("core", "sparseCheckoutCone")
+        .unwrap());
+    assert_eq!(
+        fs::read_to_string(path.join("src/util/mod.rs")).unwrap(),
+        "util\n"
+    );
+    assert!(!path.join("docs").exists());
+    assert_eq!(skipped(&repo), ["docs/guide.md", "src/lib.rs"]);
+    assert!(repo.status().unwrap().is_empty());
+}
+
+// W-012: Large files are streamed into and out of the object store
+#[test]
+fn test_w012_stream_large_blobs() {
+    use std::io::Read;
+
+    let temp = create_test_repo();
+    let path = temp.path();
+    let git_dir = path.join(".git");
+    fs::write(git_dir.join("config"), "[core]\n\tbigFileThreshold = 1k\n").unwrap();
+    let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(path.join("asset.bin"), &content).unwrap();
+    fs::write(path.join("small.txt"), "small\n").unwrap();
+
+    let repo = Repository::open(path).unwrap();
+    repo.add("asset.bin").unwrap();
+    repo.add_all().unwrap();
+    assert!(repo
+        .status()
+        .unwrap()
+        .iter()
+        .all(|e| e.status() == FileStatus::Added));
+
+    let index = repo.index().unwrap();
+    let oid = index.get(Path::new("asset.bin")).unwrap().oid().to_hex();
+    let mut reader = repo.blob_reader(&oid).unwrap();
+    assert_eq!(reader.size(), content.len() as u64);
+    let mut read_back = Vec::new();
+    reader.read_to_end(&mut read_back).unwrap();
+    assert_eq!(read_back, content);
+    assert_eq!(repo.blob(&oid).unwrap().content(), content.as_slice());
+
+    let written = repo
+        .write_blob_from_reader(content.as_slice(), content.len() as u64)
+        .unwrap();
+    assert_eq!(written.to_hex(), oid);
+
+    let commit = repo
+        .create_commit("Add asset", "Test User", "test@example.com")
+        .unwrap();
+    assert!(matches!(
+        repo.blob_reader(&commit.to_hex()),
+        Err(Error::TypeMismatch { .. })
+    ));
+}
+
 // Additional: Multiple add operations work correctly
 #[test]
 fn test_add_multiple_times() {
@@ -489,3 +980,69 @@ fn create_index(entries: &[(&str, &str, usize)]) -> Vec<u8> {
 
     data
 }
+
+// W-013: remove() names the path and the changes it refuses to lose
+#[test]
+fn test_w013_remove_refusal() {
+    let (temp, repo) = create_repo_with_tree();
+    let path = temp.path();
+    let spec = |s: &str| Pathspec::new([s]).unwrap();
+
+    fs::write(path.join("src/util/mod.rs"), "staged\n").unwrap();
+    repo.add("src/util/mod.rs").unwrap();
+    let error = repo
+        .remove(&spec("src"), RemoveOptions::new().recursive(true))
+        .unwrap_err();
+    assert!(matches!(
+        &error,
+        Error::UncommittedChanges { path, reason }
+            if path == Path::new("src/util/mod.rs") && *reason == "has changes staged in the index"
+    ));
+    assert_eq!(
+        error.to_string(),
+        "cannot remove src/util/mod.rs: has changes staged in the index"
+    );
+
+    // Nothing is removed when any path is refused
+    assert!(path.join("src/lib.rs").exists());
+    assert_eq!(
+        tracked(&repo),
+        ["README.md", "src/lib.rs", "src/util/mod.rs"]
+    );
+
+    // A staged change that matches the working tree may be removed cached
+    repo.remove(&spec("src/util/mod.rs"), RemoveOptions::new().cached(true))
+        .unwrap();
+    assert!(path.join("src/util/mod.rs").exists());
+    assert_eq!(tracked(&repo), ["README.md", "src/lib.rs"]);
+}
+
+// W-014: checkout keeps a split index split
+#[test]
+fn test_w014_checkout_keeps_split_index() {
+    let (temp, repo) = create_repo_with_tree();
+    let git_dir = temp.path().join(".git");
+    repo.create_branch("old", None).unwrap();
+    fs::write(git_dir.join("config"), "[core]\n\tsplitIndex = true\n").unwrap();
+    fs::write(temp.path().join("README.md"), "changed\n").unwrap();
+    repo.add("README.md").unwrap();
+    repo.create_commit("Change README", "Test User", "test@example.com")
+        .unwrap();
+    let shared = repo.index().unwrap().shared_index().copied().unwrap();
+
+    // Without core.splitIndex, the index stays split as it is
+    fs::write(git_dir.join("config"), "[core]\n").unwrap();
+    repo.checkout("old").unwrap();
+    let split = zerogit::index::parse(&fs::read(git_dir.join("index")).unwrap()).unwrap();
+    assert_eq!(split.shared_index(), Some(&shared));
+    assert_eq!(split.len(), 1);
+    assert_eq!(
+        fs::read_to_string(temp.path().join("README.md")).unwrap(),
+        "readme\n"
+    );
+    assert_eq!(
+        tracked(&repo),
+        ["README.md", "src/lib.rs", "src/util/mod.rs"]
+    );
+    assert!(repo.status().unwrap().is_empty());
+}
@@ -1,12 +1,15 @@
 //! Integration tests for status functionality.
 //!
-//! Test cases: RP-020 to RP-024
+//! Test cases: RP-020 to RP-036
 
 use std::fs;
 use std::path::Path;
 use tempfile::TempDir;
 use zerogit::repository::Repository;
-use zerogit::status::FileStatus;
+use zerogit::status::{
+    EntryState, FileStatus, PorcelainOptions, PorcelainVersion, StatusOptions, UntrackedFiles,
+};
+use zerogit::Pathspec;
 
 /// Path to the simple test fixture
 const SIMPLE_FIXTURE: &str = "tests/fixtures/simple";
@@ -283,6 +286,15 @@ fn create_tree_content(entries: &[(&str, &str, &str)]) -> Vec<u8> {
 
 /// Helper to create a minimal index file.
 fn create_index(entries: &[(&str, &str)]) -> Vec<u8> {
+    let staged: Vec<(&str, &str, u16)> = entries
+        .iter()
+        .map(|(name, oid_hex)| (*name, *oid_hex, 0))
+        .collect();
+    create_index_with_stages(&staged)
+}
+
+/// Helper to create a minimal index file whose entries carry merge stages.
+fn create_index_with_stages(entries: &[(&str, &str, u16)]) -> Vec<u8> {
     let mut data = Vec::new();
 
     // Header: DIRC, version 2, entry count
@@ -290,7 +302,7 @@ fn create_index(entries: &[(&str, &str)]) -> Vec<u8> {
     data.extend_from_slice(&2u32.to_be_bytes()); // version
     data.extend_from_slice(&(entries.len() as u32).to_be_bytes());
 
-    for (name, oid_hex) in entries {
+    for (name, oid_hex, stage) in entries {
         let entry_start = data.len();
 
         // ctime_sec, ctime_nsec
@@ -318,9 +330,9 @@ fn create_index(entries: &[(&str, &str)]) -> Vec<u8> {
             data.push(byte);
         }
 
-        // flags (name length in lower 12 bits)
+        // flags (stage in bits 12-13, name length in lower 12 bits)
         let name_len = name.len().min(0xFFF) as u16;
-        data.extend_from_slice(&name_len.to_be_bytes());
+        data.extend_from_slice(&((stage << 12) | name_len).to_be_bytes());
 
         // name
         data.extend_from_slice(name.as_bytes());
@@ -338,6 +350,397 @@ fn create_index(entries: &[(&str, &str)]) -> Vec<u8> {
     data
 }
 
+/// Helper to create a repository with one committed file and a matching
+/// index whose stat data is all zero.
+fn create_committed_repo(content: &[u8]) -> TempDir {
+    let temp = create_test_repo();
+    let path = temp.path();
+    let git_dir = path.join(".git");
+
+    let blob_oid = create_object(&git_dir.join("objects"), content, "blob");
+    let tree_content = create_tree_content(&[("file.txt", "100644", &blob_oid)]);
+    let tree_oid = create_object(&git_dir.join("objects"), &tree_content, "tree");
+    let commit_content = format!(
+        "tree {}\nauthor Test <test@test.com> 1700000000 +0000\ncommitter Test <test@test.com> 1700000000 +0000\n\nInitial commit\n",
+        tree_oid
+    );
+    let commit_oid = create_object(
+        &git_dir.join("objects"),
+        commit_content.as_bytes(),
+        "commit",
+    );
+    fs::write(git_dir.join("refs/heads/main"), format!("{}\n", commit_oid)).unwrap();
+    fs::write(
+        git_dir.join("index"),
+        create_index(&[("file.txt", &blob_oid)]),
+    )
+    .unwrap();
+    fs::write(path.join("file.txt"), content).unwrap();
+
+    temp
+}
+
+// RP-025: Refresh writes stat data back to the index
+#[test]
+fn test_rp025_status_refresh_writes_index() {
+    let temp = create_committed_repo(b"Hello\n");
+    let path = temp.path();
+    let repo = Repository::open(path).unwrap();
+
+    let status = repo
+        .status_with_options(StatusOptions::new().refresh(true))
+        .unwrap();
+    assert!(
+        status.is_empty(),
+        "expected clean status, got: {:?}",
+        status
+    );
+
+    let index_data = fs::read(path.join(".git/index")).unwrap();
+    let index = zerogit::index::parse(&index_data).unwrap();
+    let entry = index.get(Path::new("file.txt")).unwrap();
+    let metadata = fs::metadata(path.join("file.txt")).unwrap();
+    assert!(entry.stat_matches(&metadata));
+    assert_eq!(entry.size(), 6);
+
+    // Status remains clean after the refresh
+    assert!(repo.status().unwrap().is_empty());
+}
+
+// RP-026: Same-size modification right after add is still detected
+#[test]
+fn test_rp026_same_size_change_after_add_detected() {
+    let temp = create_committed_repo(b"Hello\n");
+    let path = temp.path();
+    let repo = Repository::open(path).unwrap();
+
+    repo.add("file.txt").unwrap();
+    fs::write(path.join("file.txt"), b"Jello\n").unwrap();
+
+    let status = repo.status().unwrap();
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].status(), FileStatus::Modified);
+}
+
+// RP-027: Multi-threaded status matches single-threaded status
+#[test]
+fn test_rp027_parallel_status_matches_sequential() {
+    let temp = create_committed_repo(b"Hello\n");
+    let path = temp.path();
+
+    fs::write(path.join("file.txt"), b"Changed\n").unwrap();
+    for d in 0..4 {
+        fs::create_dir_all(path.join(format!("dir{}/sub", d))).unwrap();
+        for f in 0..8 {
+            fs::write(path.join(format!("dir{}/sub/f{}.txt", d, f)), b"new").unwrap();
+        }
+    }
+
+    let repo = Repository::open(path).unwrap();
+    let sequential = repo.status().unwrap();
+    let parallel = repo
+        .status_with_options(StatusOptions::new().threads(4))
+        .unwrap();
+
+    assert_eq!(sequential.len(), 33);
+    assert_eq!(parallel, sequential);
+    assert_eq!(
+        repo.status_with_options(StatusOptions::new().threads(0))
+            .unwrap(),
+        sequential
+    );
+}
+
+// RP-028: Staged and unstaged changes are reported as separate states
+#[test]
+fn test_rp028_index_and_worktree_states() {
+    let temp = create_committed_repo(b"Hello\n");
+    let path = temp.path();
+    let repo = Repository::open(path).unwrap();
+
+    fs::write(path.join("file.txt"), b"Staged\n").unwrap();
+    repo.add("file.txt").unwrap();
+    fs::write(path.join("file.txt"), b"Unstaged\n").unwrap();
+
+    let status = repo.status().unwrap();
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].index_state(), EntryState::Modified);
+    assert_eq!(status[0].worktree_state(), EntryState::Modified);
+    assert_eq!(status[0].status(), FileStatus::Modified);
+}
+
+// RP-029: Untracked files modes (no / normal / all)
+#[test]
+fn test_rp029_untracked_files_modes() {
+    let temp = create_committed_repo(b"Hello\n");
+    let path = temp.path();
+    fs::create_dir_all(path.join("new/deep")).unwrap();
+    fs::write(path.join("new/a.txt"), "a").unwrap();
+    fs::write(path.join("new/deep/b.txt"), "b").unwrap();
+
+    let repo = Repository::open(path).unwrap();
+    let paths = |mode| -> Vec<String> {
+        repo.status_with_options(StatusOptions::new().untracked_files(mode))
+            .unwrap()
+            .iter()
+            .map(|e| e.path().to_string_lossy().into_owned())
+            .collect()
+    };
+
+    assert_eq!(paths(UntrackedFiles::All), ["new/a.txt", "new/deep/b.txt"]);
+    assert_eq!(paths(UntrackedFiles::Normal), ["new/"]);
+    assert!(paths(UntrackedFiles::No).is_empty());
+}
+
+// RP-030: Ignored files are hidden by default and reported on request
+#[test]
+fn test_rp030_ignored_files() {
+    let temp = create_committed_repo(b"Hello\n");
+    let path = temp.path();
+    fs::write(path.join(".gitignore"), "*.log\ntarget/\n").unwrap();
+    fs::write(path.join("debug.log"), "log").unwrap();
+    fs::create_dir_all(path.join("target/debug")).unwrap();
+    fs::write(path.join("target/debug/app"), "bin").unwrap();
+
+    let repo = Repository::open(path).unwrap();
+    let status = repo.status().unwrap();
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].path(), Path::new(".gitignore"));
+
+    let status = repo
+        .status_with_options(
+            StatusOptions::new()
+                .ignored(true)
+                .untracked_files(UntrackedFiles::Normal),
+        )
+        .unwrap();
+    let ignored: Vec<_> = status
+        .iter()
+        .filter(|e| e.status() == FileStatus::Ignored)
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    assert_eq!(ignored, [Path::new("debug.log"), Path::new("target/")]);
+}
+
+// RP-031: Rename detection pairs a staged deletion with an identical addition
+#[test]
+fn test_rp031_rename_detection() {
+    let temp = create_committed_repo(b"Hello\n");
+    let path = temp.path();
+    let repo = Repository::open(path).unwrap();
+
+    fs::rename(path.join("file.txt"), path.join("moved.txt")).unwrap();
+    repo.add_all().unwrap();
+
+    let status = repo.status().unwrap();
+    assert_eq!(status.len(), 2);
+
+    let status = repo
+        .status_with_options(StatusOptions::new().renames(true))
+        .unwrap();
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].status(), FileStatus::Renamed);
+    assert_eq!(status[0].path(), Path::new("moved.txt"));
+    assert_eq!(status[0].orig_path(), Some(Path::new("file.txt")));
+}
+
+// RP-032: Pathspecs restrict the reported paths
+#[test]
+fn test_rp032_pathspec_filter() {
+    let temp = create_committed_repo(b"Hello\n");
+    let path = temp.path();
+    fs::write(path.join("file.txt"), b"Changed\n").unwrap();
+    fs::create_dir_all(path.join("src")).unwrap();
+    fs::write(path.join("src/lib.rs"), "").unwrap();
+    fs::write(path.join("notes.md"), "").unwrap();
+
+    let repo = Repository::open(path).unwrap();
+    let paths = |options: StatusOptions| -> Vec<String> {
+        repo.status_with_options(options)
+            .unwrap()
+            .iter()
+            .map(|e| e.path().to_string_lossy().into_owned())
+            .collect()
+    };
+
+    let spec = |specs: &[&str]| Pathspec::new(specs).unwrap();
+
+    assert_eq!(
+        paths(StatusOptions::new().pathspec(spec(&["src"]))),
+        ["src/lib.rs"]
+    );
+    assert_eq!(
+        paths(
+            StatusOptions::new()
+                .pathspec(spec(&["*.md"]))
+                .pathspec(spec(&["file.txt"]))
+        ),
+        ["file.txt", "notes.md"]
+    );
+    assert_eq!(
+        paths(StatusOptions::new().pathspec(spec(&[":(exclude)*.md"]))),
+        ["file.txt", "src/lib.rs"]
+    );
+}
+
+// RP-033: Unmerged index entries are reported as conflicts
+#[test]
+fn test_rp033_conflicted_entries() {
+    let temp = create_committed_repo(b"Hello\n");
+    let path = temp.path();
+    let git_dir = path.join(".git");
+    let base = create_object(&git_dir.join("objects"), b"Hello\n", "blob");
+    let ours = create_object(&git_dir.join("objects"), b"Ours\n", "blob");
+    let theirs = create_object(&git_dir.join("objects"), b"Theirs\n", "blob");
+    fs::write(
+        git_dir.join("index"),
+        create_index_with_stages(&[
+            ("added.txt", &ours, 2),
+            ("added.txt", &theirs, 3),
+            ("file.txt", &base, 1),
+            ("file.txt", &ours, 2),
+            ("file.txt", &theirs, 3),
+        ]),
+    )
+    .unwrap();
+    fs::write(path.join("added.txt"), "<<<<<<<\n").unwrap();
+
+    let repo = Repository::open(path).unwrap();
+    let status = repo.status().unwrap();
+    let codes: Vec<_> = status
+        .iter()
+        .map(|e| {
+            assert!(e.is_conflicted());
+            (
+                e.path().to_path_buf(),
+                e.index_state().as_char(),
+                e.worktree_state().as_char(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        codes,
+        [
+            (Path::new("added.txt").to_path_buf(), 'A', 'A'),
+            (Path::new("file.txt").to_path_buf(), 'U', 'U'),
+        ]
+    );
+}
+
+// RP-034: Porcelain v1 and v2 output
+#[test]
+fn test_rp034_status_porcelain() {
+    let temp = create_committed_repo(b"Hello\n");
+    let path = temp.path();
+    fs::write(path.join("file.txt"), b"Changed\n").unwrap();
+    fs::create_dir_all(path.join("new dir")).unwrap();
+    fs::write(path.join("new dir/a.txt"), "a").unwrap();
+
+    let repo = Repository::open(path).unwrap();
+    let status_options = || StatusOptions::new().untracked_files(UntrackedFiles::Normal);
+
+    let v1 = repo
+        .status_porcelain(status_options(), PorcelainOptions::new().branch(true))
+        .unwrap();
+    assert_eq!(v1, b"## main\n M file.txt\n?? \"new dir/\"\n");
+
+    let v2 = repo
+        .status_porcelain(
+            status_options(),
+            PorcelainOptions::new()
+                .version(PorcelainVersion::V2)
+                .branch(true)
+                .null_terminated(true),
+        )
+        .unwrap();
+    let head = repo.head().unwrap();
+    let index = zerogit::index::parse(&fs::read(path.join(".git/index")).unwrap()).unwrap();
+    let blob = index.get(Path::new("file.txt")).unwrap().oid().to_hex();
+    let expected = format!(
+        "# branch.oid {}\0# branch.head main\0\
+         1 .M N... 100644 100644 100644 {} {} file.txt\0? new dir/\0",
+        head.oid(),
+        blob,
+        blob
+    );
+    assert_eq!(String::from_utf8(v2).unwrap(), expected);
+}
+
+// RP-035: Branch info reports the upstream and ahead/behind counts
+#[test]
+fn test_rp035_branch_info_upstream() {
+    let temp = create_committed_repo(b"Hello\n");
+    let path = temp.path();
+    let git_dir = path.join(".git");
+    let repo = Repository::open(path).unwrap();
+
+    let info = repo.branch_info().unwrap();
+    assert_eq!(info.head(), Some("main"));
+    assert_eq!(info.upstream(), None);
+
+    // Upstream at the initial commit, one local commit on top
+    let base = fs::read_to_string(git_dir.join("refs/heads/main")).unwrap();
+    fs::create_dir_all(git_dir.join("refs/remotes/origin")).unwrap();
+    fs::write(git_dir.join("refs/remotes/origin/main"), &base).unwrap();
+    fs::write(
+        git_dir.join("config"),
+        "[remote \"origin\"]\n\tfetch = +refs/heads/*:refs/remotes/origin/*\n\
+         [branch \"main\"]\n\tremote = origin\n\tmerge = refs/heads/main\n",
+    )
+    .unwrap();
+    fs::write(path.join("file.txt"), b"Second\n").unwrap();
+    repo.add("file.txt").unwrap();
+    repo.create_commit("Second", "Test", "test@test.com")
+        .unwrap();
+
+    let info = repo.branch_info().unwrap();
+    assert_eq!(info.upstream(), Some("origin/main"));
+    assert_eq!(info.ahead_behind(), Some((1, 0)));
+
+    let header = repo
+        .status_porcelain(StatusOptions::new(), PorcelainOptions::new().branch(true))
+        .unwrap();
+    assert_eq!(header, b"## main...origin/main [ahead 1]\n");
+
+    // The upstream ref is gone
+    fs::remove_file(git_dir.join("refs/remotes/origin/main")).unwrap();
+    let info = repo.branch_info().unwrap();
+    assert_eq!(info.upstream(), Some("origin/main"));
+    assert_eq!(info.ahead_behind(), None);
+}
+
+// RP-036: Non-UTF-8 file names are tracked byte for byte
+#[cfg(unix)]
+#[test]
+fn test_rp036_non_utf8_file_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let temp = create_committed_repo(b"Hello\n");
+    let path = temp.path();
+    let name = Path::new(OsStr::from_bytes(b"caf\xe9.txt"));
+    fs::write(path.join(name), "latin-1").unwrap();
+
+    let repo = Repository::open(path).unwrap();
+    repo.add(name).unwrap();
+    let commit = repo.create_commit("Add", "Test", "test@test.com").unwrap();
+    assert!(repo.status().unwrap().is_empty());
+
+    let commit = repo.commit(&commit.to_hex()).unwrap();
+    let tree = repo.tree(&commit.tree().to_hex()).unwrap();
+    assert!(tree.get(b"caf\xe9.txt").is_some());
+
+    fs::write(path.join(name), "changed").unwrap();
+    let diff = repo.diff_index_to_workdir().unwrap();
+    assert_eq!(diff.len(), 1);
+    assert_eq!(diff.deltas()[0].path(), name);
+
+    let v1 = repo
+        .status_porcelain(StatusOptions::new(), PorcelainOptions::new())
+        .unwrap();
+    assert_eq!(v1, b" M \"caf\\351.txt\"\n");
+}
+
 // Additional: Test status with empty repository (no commits)
 #[test]
 fn test_status_empty_repository() {
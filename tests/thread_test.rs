@@ -0,0 +1,129 @@
+//! Integration tests for sharing a repository between threads.
+//!
+//! Test cases: TH-001 to TH-004
+
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+use tempfile::TempDir;
+use zerogit::log::{LogIterator, LogOptions};
+use zerogit::repository::Repository;
+use zerogit::Oid;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+fn assert_send<T: Send>() {}
+
+/// Helper to create a repository with five commits to `a.txt`.
+fn create_repo_with_history() -> (TempDir, Repository) {
+    let temp = TempDir::new().unwrap();
+    let repo = Repository::init(temp.path()).unwrap();
+    for i in 1..=5 {
+        fs::write(temp.path().join("a.txt"), format!("version {}\n", i)).unwrap();
+        repo.add("a.txt").unwrap();
+        repo.create_commit(&format!("Commit {}", i), "Test User", "test@example.com")
+            .unwrap();
+    }
+    (temp, repo)
+}
+
+/// Returns the OIDs of the commits that changed `a.txt`.
+fn log_a(repo: &Repository) -> Vec<Oid> {
+    repo.log_with_options(LogOptions::new().path("a.txt"))
+        .unwrap()
+        .map(|c| *c.unwrap().oid())
+        .collect()
+}
+
+// TH-001: Repositories can be shared and sent between threads
+#[test]
+fn test_th001_send_sync() {
+    assert_send_sync::<Repository>();
+    assert_send_sync::<zerogit::Error>();
+    assert_send::<LogIterator>();
+}
+
+// TH-002: Handles share the object cache
+#[test]
+fn test_th002_clone_handle_shares_cache() {
+    let (temp, repo) = create_repo_with_history();
+    let handle = repo.clone_handle();
+    assert_eq!(handle.path(), repo.path());
+    assert_eq!(handle.git_dir(), repo.git_dir());
+
+    repo.clear_cache();
+    let expected = log_a(&repo);
+    let misses = handle.cache_stats().object_misses();
+    assert!(misses > 0);
+    assert_eq!(log_a(&handle), expected);
+    assert_eq!(repo.cache_stats().object_misses(), misses);
+
+    // A repository opened separately has its own cache
+    let other = Repository::open(temp.path()).unwrap();
+    assert_eq!(other.cache_stats().object_hits(), 0);
+    assert_eq!(log_a(&other), expected);
+}
+
+// TH-003: Many threads read one repository concurrently
+#[test]
+fn test_th003_concurrent_reads() {
+    let (_temp, repo) = create_repo_with_history();
+    let expected = log_a(&repo);
+
+    let shared = Arc::new(repo.clone_handle());
+    thread::scope(|s| {
+        for i in 0..8 {
+            let repo = if i % 2 == 0 {
+                repo.clone_handle()
+            } else {
+                shared.clone_handle()
+            };
+            let shared = Arc::clone(&shared);
+            let expected = &expected;
+            s.spawn(move || {
+                for _ in 0..10 {
+                    assert_eq!(&log_a(&repo), expected);
+                    assert_eq!(&log_a(&shared), expected);
+                    assert!(repo.status().unwrap().is_empty());
+                }
+            });
+        }
+    });
+}
+
+// TH-004: Writes from many threads are serialized without losing updates
+#[test]
+fn test_th004_concurrent_writes() {
+    let (temp, repo) = create_repo_with_history();
+    for i in 0..8 {
+        fs::write(temp.path().join(format!("file{}.txt", i)), i.to_string()).unwrap();
+    }
+
+    thread::scope(|s| {
+        for i in 0..8 {
+            let repo = repo.clone_handle();
+            s.spawn(move || {
+                repo.add(format!("file{}.txt", i)).unwrap();
+                let head = *repo.head().unwrap().oid();
+                repo.create_branch(&format!("branch{}", i), Some(head))
+                    .unwrap();
+                // Readers run alongside the writers
+                repo.status().unwrap();
+            });
+        }
+    });
+
+    let index = repo.index().unwrap();
+    for i in 0..8 {
+        let path = format!("file{}.txt", i);
+        assert!(
+            index
+                .entries()
+                .iter()
+                .any(|e| e.path().to_str() == Some(&path)),
+            "{} missing from the index",
+            path
+        );
+    }
+    assert_eq!(repo.branches().unwrap().len(), 9);
+}